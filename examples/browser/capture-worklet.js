@@ -0,0 +1,26 @@
+// Posts the samples of the first input channel to the main thread in chunks of 4096 samples,
+// which are sent to speech2text as binary WebSocket messages of f32le samples.
+class CaptureProcessor extends AudioWorkletProcessor {
+  constructor() {
+    super();
+    this.chunk = new Float32Array(4096);
+    this.length = 0;
+  }
+
+  process(inputs) {
+    const channel = inputs[0][0];
+    if (channel === undefined) {
+      return true;
+    }
+    for (const sample of channel) {
+      this.chunk[this.length++] = sample;
+      if (this.length === this.chunk.length) {
+        this.port.postMessage(this.chunk.slice().buffer, []);
+        this.length = 0;
+      }
+    }
+    return true;
+  }
+}
+
+registerProcessor("capture", CaptureProcessor);
@@ -0,0 +1,82 @@
+//! Streams a WAV file to the speech2text gRPC service and prints the responses.
+//!
+//! ```sh
+//! cargo run --features grpc -- --model model.pbmm --grpc 127.0.0.1:50051
+//! cargo run --features grpc --example grpc_client -- http://127.0.0.1:50051 recording.wav
+//! ```
+
+use proto::transcriber_client::TranscriberClient;
+use proto::transcript_event::Kind;
+use proto::{AudioChunk, InfoRequest};
+use std::env;
+
+pub mod proto {
+    tonic::include_proto!("speech2text");
+}
+
+/// Number of samples sent per chunk.
+const CHUNK_SAMPLES: usize = 1600;
+
+#[tokio::main]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let addr = args.next().expect("Usage: grpc_client <address> <file>");
+    let path = args.next().expect("Usage: grpc_client <address> <file>");
+
+    let mut client = TranscriberClient::connect(addr)
+        .await
+        .expect("Failed to connect to server");
+    let info = client
+        .info(InfoRequest {})
+        .await
+        .expect("Failed to get server info")
+        .into_inner();
+
+    let mut reader = hound::WavReader::open(path).expect("Failed to open input file");
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 1, "Input file must be mono");
+    assert_eq!(
+        spec.sample_rate, info.sample_rate,
+        "Sample rate of input file must equal sample rate expected by the server"
+    );
+    let samples = reader
+        .samples::<i16>()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to read samples from input file");
+    let chunks = samples
+        .chunks(CHUNK_SAMPLES)
+        .map(|chunk| AudioChunk {
+            pcm: chunk
+                .iter()
+                .flat_map(|s| s.to_le_bytes().to_vec())
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut events = client
+        .transcribe(tokio_stream::iter(chunks))
+        .await
+        .expect("Failed to start transcription")
+        .into_inner();
+    while let Some(event) = events.message().await.expect("Failed to receive event") {
+        match Kind::from_i32(event.kind) {
+            Some(Kind::Ready) => println!("ready"),
+            Some(Kind::SpeechStarted) => println!("[{} ms] speech started", event.start_ms),
+            Some(Kind::SpeechStopped) => println!("[{} ms] speech stopped", event.end_ms),
+            Some(Kind::Partial) => println!(
+                "[{}-{} ms] partial: {}",
+                event.start_ms, event.end_ms, event.text
+            ),
+            Some(Kind::Mark) => println!("[{} ms] mark: {}", event.start_ms, event.text),
+            Some(Kind::StablePartial) => println!(
+                "[{}-{} ms] stable partial: {}",
+                event.start_ms, event.end_ms, event.text
+            ),
+            Some(Kind::Final) => println!(
+                "[{}-{} ms] final: {}",
+                event.start_ms, event.end_ms, event.text
+            ),
+            Some(Kind::Unspecified) | None => eprintln!("Unknown event kind {}", event.kind),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::vad::FvadMode;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Audio, over which the voiced ratio and the rejected utterances are evaluated, which is also
+/// the minimum time between changes of the mode.
+pub const INTERVAL: Duration = Duration::from_secs(3 * 60);
+
+/// Ratio of voiced frames, above which the audio is considered constant noise mistaken for
+/// speech.
+const MAX_VOICED_RATIO: f64 = 0.9;
+
+/// Ratio of utterances rejected for containing too little speech, above which onsets are
+/// considered clipped.
+const MAX_REJECTED_RATIO: f64 = 0.5;
+
+/// Number of utterances needed to evaluate the ratio of rejected ones.
+const MIN_UTTERANCES: usize = 5;
+
+/// Adjusts the Fvad mode to the audio: more aggressive if nearly everything is voiced, less
+/// aggressive if most utterances are too short to be decoded.
+pub struct Adaptive {
+    mode: FvadMode,
+    /// Frames per interval.
+    interval: usize,
+    /// Frames, voiced frames, utterances and rejected utterances since the last evaluation.
+    frames: usize,
+    voiced: usize,
+    utterances: usize,
+    rejected: usize,
+}
+
+impl Adaptive {
+    /// Starts adjusting `mode` of detection of frames of `frame` duration.
+    pub fn new(mode: FvadMode, frame: Duration) -> Self {
+        Self {
+            mode,
+            interval: (INTERVAL.as_micros() / frame.as_micros()) as usize,
+            frames: 0,
+            voiced: 0,
+            utterances: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Records the verdict of a frame.
+    pub fn frame(&mut self, voiced: bool) {
+        self.frames += 1;
+        self.voiced += voiced as usize;
+    }
+
+    /// Records a completed utterance, which was `rejected` for containing too little speech.
+    pub fn utterance(&mut self, rejected: bool) {
+        self.utterances += 1;
+        self.rejected += rejected as usize;
+    }
+
+    /// Returns the mode to switch to, once an interval passed and a change is warranted.
+    /// Must be called at utterance boundaries only, so that segments are detected in one mode.
+    pub fn adjust(&mut self) -> Option<FvadMode> {
+        if self.frames < self.interval {
+            return None;
+        }
+        let voiced = self.voiced as f64 / self.frames as f64;
+        let rejected = self.rejected as f64 / self.utterances.max(1) as f64;
+        let (step, reason) = if voiced > MAX_VOICED_RATIO {
+            (
+                1,
+                "nearly all audio is voiced, which is likely constant noise",
+            )
+        } else if self.utterances >= MIN_UTTERANCES && rejected > MAX_REJECTED_RATIO {
+            (
+                -1,
+                "most utterances are too short, which likely clipped their onsets",
+            )
+        } else {
+            (0, "")
+        };
+        debug!(
+            voiced,
+            rejected,
+            utterances = self.utterances,
+            "Evaluated Fvad mode"
+        );
+        self.frames = 0;
+        self.voiced = 0;
+        self.utterances = 0;
+        self.rejected = 0;
+        if step == 0 {
+            return None;
+        }
+        // The mode is already at the limit, if there is no mode in the direction of the step.
+        let mode = FvadMode::from_level(self.mode as i8 + step)?;
+        info!(
+            from = self.mode as u8,
+            to = mode as u8,
+            voiced,
+            rejected,
+            "Adjusting Fvad mode, since {}",
+            reason
+        );
+        self.mode = mode;
+        Some(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames of 1s, so that an interval is 180 frames.
+    const FRAME: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn noise() {
+        let mut adaptive = Adaptive::new(FvadMode::LowBitrate, FRAME);
+        for _ in 0..179 {
+            adaptive.frame(true);
+        }
+        assert!(adaptive.adjust().is_none());
+        adaptive.frame(true);
+        assert!(matches!(adaptive.adjust(), Some(FvadMode::Aggressive)));
+        for _ in 0..180 {
+            adaptive.frame(true);
+        }
+        assert!(matches!(adaptive.adjust(), Some(FvadMode::VeryAggressive)));
+        for _ in 0..180 {
+            adaptive.frame(true);
+        }
+        assert!(adaptive.adjust().is_none());
+    }
+
+    #[test]
+    fn clipped() {
+        let mut adaptive = Adaptive::new(FvadMode::Aggressive, FRAME);
+        for i in 0..180 {
+            adaptive.frame(i % 2 == 0);
+        }
+        for rejected in [true, true, false, true] {
+            adaptive.utterance(rejected);
+        }
+        // Too few utterances to tell.
+        assert!(adaptive.adjust().is_none());
+        for i in 0..180 {
+            adaptive.frame(i % 2 == 0);
+        }
+        for rejected in [true, true, false, true, false] {
+            adaptive.utterance(rejected);
+        }
+        assert!(matches!(adaptive.adjust(), Some(FvadMode::LowBitrate)));
+        for i in 0..180 {
+            adaptive.frame(i % 2 == 0);
+        }
+        for rejected in [true, false, false, true, false] {
+            adaptive.utterance(rejected);
+        }
+        assert!(adaptive.adjust().is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::control::{self, Control, Status};
+use serde_json::{json, Value};
+use std::{net::SocketAddr, sync::mpsc::Sender, thread};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::debug;
+
+/// Serves the admin API on `addr` on a separate thread: `GET /status` replies with the status
+/// reported by the control socket, while `POST /pause`, `/resume` and `/flush` send the control
+/// to `controls`.
+/// If `token` is set, requests must carry it as `Authorization: Bearer <token>`.
+pub fn serve(
+    addr: SocketAddr,
+    token: Option<String>,
+    controls: Sender<Control>,
+    status: Status,
+) -> Result<(), String> {
+    let server = Server::http(addr).map_err(|err| err.to_string())?;
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("Failed to construct Content-Type header");
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let (code, reply) = handle(&request, token.as_deref(), &controls, &status);
+            let response = Response::from_string(reply.to_string())
+                .with_status_code(code)
+                .with_header(content_type.clone());
+            if let Err(err) = request.respond(response) {
+                debug!(error = %err, "Failed to respond to admin request");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Returns the status code and the JSON body of the response to `request`.
+fn handle(
+    request: &Request,
+    token: Option<&str>,
+    controls: &Sender<Control>,
+    status: &Status,
+) -> (u16, Value) {
+    if let Some(token) = token {
+        let authorized = request.headers().iter().any(|header| {
+            header.field.equiv("Authorization")
+                && header
+                    .value
+                    .as_str()
+                    .strip_prefix("Bearer ")
+                    .is_some_and(|given| equal(given.trim().as_bytes(), token.as_bytes()))
+        });
+        if !authorized {
+            return (401, json!({ "error": "missing or invalid bearer token" }));
+        }
+    }
+    let command = match (request.method(), request.url()) {
+        (Method::Get, "/status") => "status",
+        (Method::Post, "/pause") => "pause",
+        (Method::Post, "/resume") => "resume",
+        (Method::Post, "/flush") => "flush",
+        (_, "/status") | (_, "/pause") | (_, "/resume") | (_, "/flush") => {
+            return (405, json!({ "error": "method not allowed" }))
+        }
+        _ => return (404, json!({ "error": "not found" })),
+    };
+    let reply = control::execute(command, controls, status);
+    // The controls only fail once the pipeline stopped.
+    let code = if reply.get("error").is_some() {
+        503
+    } else {
+        200
+    };
+    (code, reply)
+}
+
+/// Compares `a` and `b` in time independent of where they differ, so that the token cannot be
+/// guessed byte by byte.
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::{Ipv4Addr, TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    /// Sends a request with `method` for `path` to `addr`, authorized with `token`, if set,
+    /// returning the status line and the body of the response.
+    fn request(addr: SocketAddr, method: &str, path: &str, token: Option<&str>) -> (String, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nHost: localhost\r\n",
+            method, path
+        )
+        .unwrap();
+        if let Some(token) = token {
+            write!(stream, "Authorization: Bearer {}\r\n", token).unwrap();
+        }
+        write!(stream, "\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.lines().next().unwrap().to_string();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn api() {
+        let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        let (tx, rx) = mpsc::channel();
+        serve(addr, Some("secret".into()), tx, Status::new(None)).unwrap();
+
+        let (status, body) = request(addr, "POST", "/pause", Some("secret"));
+        assert!(status.contains(" 200 "), "{}", status);
+        assert_eq!(body, json!({ "ok": true }));
+        assert!(matches!(rx.recv().unwrap(), Control::Pause));
+
+        let (status, body) = request(addr, "GET", "/status", Some("secret"));
+        assert!(status.contains(" 200 "), "{}", status);
+        assert!(body["uptime_ms"].is_u64(), "{}", body);
+
+        let (status, _) = request(addr, "POST", "/pause", None);
+        assert!(status.contains(" 401 "), "{}", status);
+        let (status, _) = request(addr, "POST", "/pause", Some("secreT"));
+        assert!(status.contains(" 401 "), "{}", status);
+        let (status, _) = request(addr, "GET", "/pause", Some("secret"));
+        assert!(status.contains(" 405 "), "{}", status);
+        let (status, _) = request(addr, "GET", "/", Some("secret"));
+        assert!(status.contains(" 404 "), "{}", status);
+
+        // Controls fail once the pipeline stopped.
+        drop(rx);
+        let (status, body) = request(addr, "POST", "/flush", Some("secret"));
+        assert!(status.contains(" 503 "), "{}", status);
+        assert_eq!(body["error"], "pipeline stopped");
+    }
+
+    #[test]
+    fn equal_tokens() {
+        assert!(equal(b"secret", b"secret"));
+        assert!(!equal(b"secret", b"secreT"));
+        assert!(!equal(b"secret", b"secrets"));
+        assert!(equal(b"", b""));
+    }
+}
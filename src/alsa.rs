@@ -0,0 +1,93 @@
+use crate::capture;
+use crate::clock::Anchor;
+use crate::device;
+use crate::error::Error;
+use crate::metrics;
+use crate::priority;
+use crate::resample::{self, Resampler};
+use ::alsa::{
+    pcm::{Access, Format, HwParams, PCM},
+    Direction, ValueOr,
+};
+use std::{sync::atomic::Ordering, thread, time::Duration};
+use tracing::{error, info, warn};
+
+/// Duration of audio read from the device at once.
+const PERIOD_DURATION: Duration = Duration::from_millis(20);
+
+/// Returns a function, which wraps an ALSA error into a device error, prefixed with `context` and
+/// the PCM `name`.
+fn with<'a>(context: &'static str, name: &'a str) -> impl FnOnce(::alsa::Error) -> Error + 'a {
+    move |err| Error::Device(format!("{} `{}`: {}", context, name, err))
+}
+
+/// Opens the PCM named `name`, e.g. `plughw:1,0` or one defined in asound.conf, for capture,
+/// returning the captured samples downmixed to mono and resampled to `sample_rate` with
+/// `resampler`.
+/// The rate and channel count closest to the ones required are negotiated with the device, so
+/// that conversion is only done if the device does not support them.
+/// The capture thread is raised to realtime priority, if `realtime` is set.
+/// `anchor` is set to the capture time of the first sample.
+pub fn capture(
+    name: &str,
+    sample_rate: u32,
+    resampler: Resampler,
+    realtime: bool,
+    anchor: Anchor,
+) -> Result<Box<dyn Iterator<Item = i16>>, Error> {
+    let pcm =
+        PCM::new(name, Direction::Capture, false).map_err(with("Failed to open ALSA PCM", name))?;
+    let (rate, channels) = {
+        let params =
+            HwParams::any(&pcm).map_err(with("Failed to get ALSA PCM parameters", name))?;
+        params
+            .set_access(Access::RWInterleaved)
+            .map_err(with("Failed to set interleaved access on ALSA PCM", name))?;
+        params
+            .set_format(Format::s16())
+            .map_err(with("Failed to set i16 sample format on ALSA PCM", name))?;
+        let channels = params
+            .set_channels_near(1)
+            .map_err(with("Failed to set channels of ALSA PCM", name))?;
+        let rate = params
+            .set_rate_near(sample_rate, ValueOr::Nearest)
+            .map_err(with("Failed to set sample rate of ALSA PCM", name))?;
+        pcm.hw_params(&params)
+            .map_err(with("Failed to apply parameters to ALSA PCM", name))?;
+        (rate, channels as usize)
+    };
+    info!(pcm = name, rate, channels, "Opened ALSA PCM");
+
+    let (mut writer, reader) = capture::buffer(rate, anchor);
+    let period = (PERIOD_DURATION.as_millis() as u64 * rate as u64 / 1000) as usize;
+    let name = name.to_string();
+    thread::spawn(move || {
+        if realtime {
+            priority::realtime();
+        }
+        let io = match pcm.io_i16() {
+            Ok(io) => io,
+            Err(err) => return error!(pcm = %name, error = %err, "Failed to read from ALSA PCM"),
+        };
+        let mut frames = vec![0; period * channels];
+        let mut samples = Vec::with_capacity(period);
+        loop {
+            match io.readi(&mut frames) {
+                Ok(n) => {
+                    samples.clear();
+                    samples.extend(frames[..n * channels].chunks(channels).map(device::downmix));
+                    writer.write(&samples);
+                }
+                Err(err) => {
+                    // Overruns are reported as EPIPE and recovered from by restarting the PCM.
+                    if let Err(err) = pcm.try_recover(err, true) {
+                        return error!(pcm = %name, error = %err, "Failed to read from ALSA PCM");
+                    }
+                    metrics::XRUNS.fetch_add(1, Ordering::Relaxed);
+                    warn!(pcm = %name, "Recovered ALSA PCM from overrun");
+                }
+            }
+        }
+    });
+    Ok(resample::resample(reader, rate, sample_rate, resampler))
+}
@@ -0,0 +1,753 @@
+use crate::cache::Cache;
+use crate::durable::{self, Syncer};
+use crate::error::Error;
+use crate::models;
+use crate::output::{Event, Sink, Stdout, Utterance};
+use crate::transcript::{Cues, Details, Format};
+use glob::Pattern;
+use serde_json::{json, Map, Value};
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, info, warn};
+
+/// Returns whether the file name of `path` matches `pattern`.
+pub fn matches(pattern: &Pattern, path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| pattern.matches(name))
+}
+
+/// Returns the files within `dir` and its subdirectories, whose names match `pattern`, sorted by
+/// path.
+pub fn inputs(dir: &Path, pattern: &Pattern) -> Result<Vec<PathBuf>, String> {
+    let mut inputs = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|err| format!("Failed to read directory {}: {}", dir.display(), err))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|err| format!("Failed to read directory {}: {}", dir.display(), err))?
+                .path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if matches(pattern, &path) {
+                inputs.push(path);
+            }
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// What to do with an input, which was already transcribed.
+#[derive(Clone, Copy)]
+pub enum Existing {
+    /// Skip the input, unless it was modified since it was transcribed.
+    Skip,
+    /// Transcribe the input again.
+    Overwrite,
+    /// Abort the batch.
+    Error,
+}
+
+impl FromStr for Existing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "failed to parse `{}` into existing output policy of skip, overwrite or error",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the modification time of `path` in milliseconds since the Unix epoch.
+fn modified(path: &Path) -> io::Result<u64> {
+    let modified = fs::metadata(path)?.modified()?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64)
+}
+
+/// Prints the final transcripts of a recording, collecting them along the way.
+#[derive(Default)]
+pub struct Collector {
+    utterances: Vec<Utterance>,
+    stdout: Stdout,
+}
+
+impl Collector {
+    /// Returns the transcripts collected so far, clearing them.
+    pub fn take(&mut self) -> Vec<Utterance> {
+        self.stdout = Stdout::default();
+        std::mem::take(&mut self.utterances)
+    }
+}
+
+impl Sink for Collector {
+    fn send(&mut self, event: &Event) {
+        self.stdout.send(event);
+        if let Event::Final(utterance) = event {
+            self.utterances.push(utterance.clone())
+        }
+    }
+}
+
+/// Outcome of transcribing a single input.
+pub struct Transcription {
+    pub utterances: Vec<Utterance>,
+    /// Duration of the recording.
+    pub duration: Duration,
+}
+
+/// Transcript files written for every input.
+pub struct Outputs {
+    /// Directory, which inputs were found in.
+    pub root: PathBuf,
+    /// Directory to write transcripts to, preserving the paths of the inputs relative to `root`.
+    /// Transcripts are written next to their inputs, if unset.
+    pub dir: Option<PathBuf>,
+    pub format: Format,
+    /// Path of the model, which headers of transcripts name.
+    pub model: PathBuf,
+    /// Limits of the cues of subtitles.
+    pub cues: Cues,
+}
+
+impl Outputs {
+    /// Returns the path of the transcript of `input`.
+    pub fn path(&self, input: &Path) -> PathBuf {
+        let path = match &self.dir {
+            Some(dir) => dir.join(input.strip_prefix(&self.root).unwrap_or(input)),
+            None => input.to_path_buf(),
+        };
+        path.with_extension(self.format.extension())
+    }
+
+    /// Writes the transcript of `input`, returning its path.
+    fn write(&self, input: &Path, transcription: &Transcription) -> io::Result<PathBuf> {
+        let path = self.path(input);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let details = Details {
+            duration: transcription.duration,
+            // Transcripts are dated by the modification time of their inputs, which is when most
+            // recorders finish writing them.
+            date: fs::metadata(input)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now()),
+            model: Some(&self.model),
+            cues: self.cues,
+        };
+        fs::write(
+            &path,
+            self.format.render(&transcription.utterances, &details),
+        )?;
+        Ok(path)
+    }
+}
+
+/// JSON file mapping every input to its transcripts, rewritten after every input and synced
+/// to disk according to a policy.
+pub struct Manifest {
+    path: PathBuf,
+    files: Map<String, Value>,
+    syncer: Syncer,
+}
+
+impl Manifest {
+    /// Opens the manifest at `path`, reading the entries of an existing one.
+    pub fn open(path: PathBuf, sync: durable::Policy) -> io::Result<Self> {
+        let syncer = Syncer::new(sync);
+        if !path.exists() {
+            return Ok(Self {
+                path,
+                files: Map::new(),
+                syncer,
+            });
+        }
+        let manifest: Value = serde_json::from_slice(&fs::read(&path)?)?;
+        let files = match manifest.get("files") {
+            Some(Value::Object(files)) => files.clone(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "manifest lacks an object of files",
+                ))
+            }
+        };
+        Ok(Self {
+            path,
+            files,
+            syncer,
+        })
+    }
+
+    /// Returns the modification time of `input` in milliseconds since the Unix epoch recorded
+    /// along with its transcript, if it was transcribed successfully.
+    fn transcribed(&self, input: &Path) -> Option<u64> {
+        let entry = self.files.get(&input.display().to_string())?;
+        if !entry["error"].is_null() {
+            return None;
+        }
+        entry["input_modified_ms"].as_u64()
+    }
+
+    /// Records `entry` of `input` and rewrites the manifest.
+    pub fn insert(&mut self, input: &Path, entry: Value) -> io::Result<()> {
+        self.files.insert(input.display().to_string(), entry);
+        self.write()
+    }
+
+    fn write(&mut self) -> io::Result<()> {
+        write_synced(
+            &self.path,
+            &json!({ "files": self.files }),
+            &mut self.syncer,
+        )
+    }
+}
+
+/// Progress of a batch, rewritten after every transcribed input, so that an interrupted batch
+/// can be resumed.
+pub struct State {
+    path: PathBuf,
+    /// Model and options the inputs were transcribed with.
+    options: Value,
+    /// SHA256 digests of the transcribed inputs, keyed by path.
+    completed: Map<String, Value>,
+}
+
+impl State {
+    /// Name of the state file within the output directory.
+    pub const FILE: &'static str = ".speech2text-state.json";
+
+    /// Starts a batch transcribed with `options`, discarding the state of a previous one.
+    pub fn new(path: PathBuf, options: Value) -> Self {
+        Self {
+            path,
+            options,
+            completed: Map::new(),
+        }
+    }
+
+    /// Resumes the batch, whose state is at `path`. Fails if the batch was transcribed with
+    /// other `options`, unless `force` is set.
+    pub fn resume(path: PathBuf, options: Value, force: bool) -> Result<Self, Error> {
+        let state: Value = fs::read(&path)
+            .map_err(Error::with(Error::Usage, "Failed to read batch state"))
+            .and_then(|state| {
+                serde_json::from_slice(&state)
+                    .map_err(Error::with(Error::Usage, "Failed to parse batch state"))
+            })?;
+        if state["options"] != options {
+            if !force {
+                return Err(Error::Usage(format!(
+                    "Batch was started with other options {}, pass --force to resume anyway",
+                    state["options"]
+                )));
+            }
+            warn!(
+                previous = %state["options"],
+                "Resuming batch started with other options"
+            );
+        }
+        let completed = match &state["completed"] {
+            Value::Object(completed) => completed.clone(),
+            _ => Map::new(),
+        };
+        info!(completed = completed.len(), "Resuming batch");
+        Ok(Self {
+            path,
+            options,
+            completed,
+        })
+    }
+
+    fn is_completed(&self, input: &Path, sha256: &str) -> bool {
+        self.completed
+            .get(&input.display().to_string())
+            .is_some_and(|completed| completed == sha256)
+    }
+
+    /// Marks `input` with digest `sha256` as transcribed and rewrites the state.
+    fn complete(&mut self, input: &Path, sha256: String) -> io::Result<()> {
+        self.completed
+            .insert(input.display().to_string(), Value::String(sha256));
+        write(
+            &self.path,
+            &json!({ "options": self.options, "completed": self.completed }),
+        )
+    }
+}
+
+/// Writes `value` to a temporary file first, which is then renamed to `path`, so that `path` is
+/// never left partially written.
+pub fn write(path: &Path, value: &Value) -> io::Result<()> {
+    write_synced(path, value, &mut Syncer::new(durable::Policy::None))
+}
+
+/// Writes `value` to `path` like `write`, syncing it to disk according to `syncer`, along with
+/// the directory, so that the rename survives as well.
+fn write_synced(path: &Path, value: &Value, syncer: &mut Syncer) -> io::Result<()> {
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(format!("{:#}\n", value).as_bytes())?;
+    let synced = syncer.written(&file)?;
+    drop(file);
+    fs::rename(&tmp, path)?;
+    // Directories cannot be opened to be synced on other platforms.
+    if synced && cfg!(unix) {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Outcome of processing an input.
+pub enum Outcome {
+    Transcribed,
+    /// The input was already transcribed.
+    Skipped,
+    Failed,
+}
+
+/// Transcribes inputs, writing their transcripts to `outputs` and recording the outcomes in
+/// `manifest`, if set. Inputs with an existing transcript or manifest entry are handled according
+/// to `existing`. Inputs completed according to `state` are skipped, unless they changed since.
+/// Transcriptions are looked up in and stored to `cache`, if set.
+pub struct Batch {
+    pub manifest: Option<Manifest>,
+    pub outputs: Option<Outputs>,
+    pub existing: Existing,
+    pub state: State,
+    pub cache: Option<Cache>,
+}
+
+impl Batch {
+    /// Transcribes `input` with `transcribe`, unless it was transcribed already.
+    /// Failing to transcribe the input or to write its transcript is not an error, but an outcome.
+    pub fn process(
+        &mut self,
+        input: &Path,
+        transcribe: &mut impl FnMut(&Path) -> Result<Transcription, Error>,
+    ) -> Result<Outcome, Error> {
+        let sha256 = models::sha256(input);
+        if let Ok(sha256) = &sha256 {
+            if self.state.is_completed(input, sha256) {
+                debug!(input = %input.display(), "Skipping input completed by the resumed batch");
+                return Ok(Outcome::Skipped);
+            }
+        }
+
+        let input_modified = modified(input).ok();
+        let output = self.outputs.as_ref().map(|outputs| outputs.path(input));
+        let output_modified = output.as_deref().map(|path| modified(path).ok());
+        let transcribed = self
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.transcribed(input));
+        let exists =
+            matches!(output_modified, Some(Some(_))) || matches!(transcribed, Some(Some(_)));
+        if exists {
+            match self.existing {
+                Existing::Overwrite => {}
+                Existing::Error => {
+                    return Err(Error::Usage(format!(
+                        "{} was already transcribed, pass --existing skip or overwrite to continue",
+                        input.display()
+                    )))
+                }
+                Existing::Skip => {
+                    // Inputs without a modification time are considered unchanged.
+                    let fresh = |at: Option<u64>| match (at, input_modified) {
+                        (Some(at), Some(input_modified)) => at >= input_modified,
+                        (at, _) => at.is_some(),
+                    };
+                    if output_modified.is_none_or(fresh) && transcribed.is_none_or(fresh) {
+                        info!(input = %input.display(), "Skipping input, which was already transcribed");
+                        return Ok(Outcome::Skipped);
+                    }
+                    info!(input = %input.display(), "Transcribing input again, which was modified");
+                }
+            }
+        }
+
+        let start = Instant::now();
+        let outputs = self.outputs.as_ref();
+        let cached = match (&mut self.cache, &sha256) {
+            (Some(cache), Ok(sha256)) => cache.get(sha256),
+            _ => None,
+        };
+        let result = match cached {
+            Some(transcription) => {
+                debug!(input = %input.display(), "Using cached transcription");
+                // Cached transcripts are printed like decoded ones.
+                let mut stdout = Stdout::default();
+                for utterance in &transcription.utterances {
+                    stdout.send(&Event::Final(utterance.clone()));
+                }
+                Ok(transcription)
+            }
+            None => {
+                let result = transcribe(input);
+                if let (Some(cache), Ok(sha256), Ok(transcription)) =
+                    (&mut self.cache, &sha256, &result)
+                {
+                    if let Err(err) = cache.put(input, sha256, transcription) {
+                        warn!(
+                            input = %input.display(),
+                            error = %err,
+                            "Failed to cache transcription"
+                        );
+                    }
+                }
+                result
+            }
+        };
+        let result = result.and_then(|transcription| {
+            let output = outputs
+                .map(|outputs| outputs.write(input, &transcription))
+                .transpose()
+                .map_err(Error::with(Error::Other, "Failed to write transcript"))?;
+            Ok((transcription, output))
+        });
+        let processing = start.elapsed();
+        let (entry, outcome) = match result {
+            Ok((transcription, output)) => (
+                json!({
+                    "text": transcription
+                        .utterances
+                        .iter()
+                        .map(|utterance| utterance.text.as_str())
+                        .filter(|text| !text.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    "segments": transcription
+                        .utterances
+                        .iter()
+                        .map(Utterance::to_json)
+                        .collect::<Vec<_>>(),
+                    "duration_ms": transcription.duration.as_millis() as u64,
+                    "processing_ms": processing.as_millis() as u64,
+                    "output": output.map(|path| path.display().to_string()),
+                    "input_modified_ms": input_modified,
+                    "error": null,
+                }),
+                Outcome::Transcribed,
+            ),
+            Err(err) => {
+                warn!(input = %input.display(), error = %err, "Failed to transcribe");
+                (
+                    json!({
+                        "processing_ms": processing.as_millis() as u64,
+                        "error": err.to_string(),
+                    }),
+                    Outcome::Failed,
+                )
+            }
+        };
+        if let Some(manifest) = &mut self.manifest {
+            manifest
+                .insert(input, entry)
+                .map_err(Error::with(Error::Other, "Failed to write manifest"))?;
+        }
+        if let (Outcome::Transcribed, Ok(sha256)) = (&outcome, sha256) {
+            self.state
+                .complete(input, sha256)
+                .map_err(Error::with(Error::Other, "Failed to write batch state"))?;
+        }
+        Ok(outcome)
+    }
+}
+
+/// Processes every input of `inputs` as part of `batch`. Inputs, which fail to be transcribed or
+/// written, are skipped and fail the batch once all inputs were processed.
+pub fn run(
+    inputs: &[PathBuf],
+    batch: &mut Batch,
+    mut transcribe: impl FnMut(&Path) -> Result<Transcription, Error>,
+) -> Result<(), Error> {
+    let mut failed = 0;
+    let mut skipped = 0;
+    for (i, input) in inputs.iter().enumerate() {
+        info!(input = %input.display(), "Processing {} of {}", i + 1, inputs.len());
+        match batch.process(input, &mut transcribe)? {
+            Outcome::Transcribed => {}
+            Outcome::Skipped => skipped += 1,
+            Outcome::Failed => failed += 1,
+        }
+    }
+    eprintln!(
+        "Transcribed {} of {} inputs, skipped {}, failed {}",
+        inputs.len() - skipped - failed,
+        inputs.len(),
+        skipped,
+        failed
+    );
+    if let Some(cache) = &batch.cache {
+        eprintln!("Cache hits {}, misses {}", cache.hits, cache.misses);
+    }
+    if failed > 0 {
+        return Err(Error::Input(format!(
+            "Failed to transcribe {} of {} inputs",
+            failed,
+            inputs.len()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a new batch within `dir` overwriting existing outputs.
+    fn batch(dir: &Path) -> Batch {
+        Batch {
+            manifest: None,
+            outputs: None,
+            existing: Existing::Overwrite,
+            state: State::new(dir.join(State::FILE), json!({ "model": "model.pbmm" })),
+            cache: None,
+        }
+    }
+
+    #[test]
+    fn wav_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("b")).unwrap();
+        for name in ["c.wav", "a.wav", "notes.txt", "b/d.wav"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(
+            inputs(dir.path(), &Pattern::new("*.wav").unwrap()).unwrap(),
+            ["a.wav", "b/d.wav", "c.wav"].map(|name| dir.path().join(name))
+        );
+        assert_eq!(
+            inputs(dir.path(), &Pattern::new("[ab]*").unwrap()).unwrap(),
+            ["a.wav"].map(|name| dir.path().join(name))
+        );
+    }
+
+    #[test]
+    fn manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let inputs = [PathBuf::from("good.wav"), PathBuf::from("bad.wav")];
+        let mut batch = Batch {
+            manifest: Some(Manifest::open(path.clone(), durable::Policy::Line).unwrap()),
+            ..batch(dir.path())
+        };
+        let result = run(&inputs, &mut batch, |input| {
+            if input == Path::new("bad.wav") {
+                return Err(Error::Input("Failed to open input file".into()));
+            }
+            Ok(Transcription {
+                utterances: vec![
+                    Utterance::test("hello"),
+                    Utterance::test(""),
+                    Utterance::test("world"),
+                ],
+                duration: Duration::from_secs(3),
+            })
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to transcribe 1 of 2 inputs"
+        );
+
+        let manifest: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let good = &manifest["files"]["good.wav"];
+        assert_eq!(good["text"], "hello world");
+        assert_eq!(good["segments"].as_array().unwrap().len(), 3);
+        assert_eq!(good["duration_ms"], 3000);
+        assert!(good["error"].is_null());
+        let bad = &manifest["files"]["bad.wav"];
+        assert_eq!(bad["error"], "Failed to open input file");
+        assert!(bad.get("text").is_none());
+        assert!(!dir.path().join("manifest.json.tmp").exists());
+    }
+
+    #[test]
+    fn output_paths() {
+        let mut outputs = Outputs {
+            root: PathBuf::from("/in"),
+            dir: None,
+            format: Format::Srt,
+            model: PathBuf::from("model.pbmm"),
+            cues: Cues::default(),
+        };
+        assert_eq!(
+            outputs.path(Path::new("/in/a/b.wav")),
+            Path::new("/in/a/b.srt")
+        );
+        outputs.dir = Some(PathBuf::from("/out"));
+        assert_eq!(
+            outputs.path(Path::new("/in/a/b.wav")),
+            Path::new("/out/a/b.srt")
+        );
+    }
+
+    #[test]
+    fn transcripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut batch = Batch {
+            outputs: Some(Outputs {
+                root: dir.path().join("in"),
+                dir: Some(dir.path().join("out")),
+                format: Format::Txt,
+                model: PathBuf::from("model.pbmm"),
+                cues: Cues::default(),
+            }),
+            ..batch(dir.path())
+        };
+        let input = dir.path().join("in/a/b.wav");
+        run(&[input], &mut batch, |_| {
+            Ok(Transcription {
+                utterances: vec![Utterance::test("hello")],
+                duration: Duration::from_secs(1),
+            })
+        })
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("out/a/b.txt")).unwrap(),
+            Format::Txt.render(
+                &[Utterance::test("hello")],
+                &Details {
+                    duration: Duration::from_secs(1),
+                    date: UNIX_EPOCH,
+                    model: None,
+                    cues: Cues::default(),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, copy) = (dir.path().join("a.wav"), dir.path().join("b.wav"));
+        fs::write(&input, "audio").unwrap();
+        fs::write(&copy, "audio").unwrap();
+        let open = || Cache::open(dir.path().join("cache"), &json!({}), true).unwrap();
+        let mut batch = Batch {
+            cache: Some(open()),
+            ..batch(dir.path())
+        };
+        let mut transcribed = 0;
+        let mut transcribe = |_: &Path| {
+            transcribed += 1;
+            Ok(Transcription {
+                utterances: vec![Utterance::test("hello")],
+                duration: Duration::from_secs(1),
+            })
+        };
+        assert!(matches!(
+            batch.process(&input, &mut transcribe).unwrap(),
+            Outcome::Transcribed
+        ));
+        // Entries are keyed by the content of inputs, which copies share.
+        batch.cache = Some(open());
+        batch.process(&copy, &mut transcribe).unwrap();
+        assert_eq!(transcribed, 1);
+        let cache = batch.cache.unwrap();
+        assert_eq!((cache.hits, cache.misses), (1, 0));
+    }
+
+    #[test]
+    fn existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let input = dir.path().join("a.wav");
+        fs::write(&input, "").unwrap();
+        let mut transcribed = 0;
+        let mut transcribe = |existing| {
+            let mut batch = Batch {
+                manifest: Some(Manifest::open(path.clone(), durable::Policy::Line).unwrap()),
+                existing,
+                ..batch(dir.path())
+            };
+            run(std::slice::from_ref(&input), &mut batch, |_| {
+                transcribed += 1;
+                Ok(Transcription {
+                    utterances: vec![Utterance::test("hello")],
+                    duration: Duration::from_secs(1),
+                })
+            })
+        };
+        transcribe(Existing::Skip).unwrap();
+        transcribe(Existing::Skip).unwrap();
+        transcribe(Existing::Overwrite).unwrap();
+        assert!(matches!(transcribe(Existing::Error), Err(Error::Usage(_))));
+        assert_eq!(transcribed, 2);
+    }
+
+    #[test]
+    fn parse_existing() {
+        assert!(matches!("skip".parse(), Ok(Existing::Skip)));
+        assert!(matches!("error".parse(), Ok(Existing::Error)));
+        assert!("keep".parse::<Existing>().is_err());
+    }
+
+    #[test]
+    fn resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let inputs = [dir.path().join("a.wav"), dir.path().join("b.wav")];
+        for input in &inputs {
+            fs::write(input, input.display().to_string()).unwrap();
+        }
+        let path = dir.path().join(State::FILE);
+        let options = json!({ "model": "model.pbmm" });
+        let mut transcribed = Vec::new();
+        let mut transcribe = |state| {
+            let mut batch = Batch {
+                state,
+                ..batch(dir.path())
+            };
+            run(&inputs, &mut batch, |input| {
+                transcribed.push(input.to_path_buf());
+                if transcribed.len() == 2 {
+                    return Err(Error::Input("Failed to open input file".into()));
+                }
+                Ok(Transcription {
+                    utterances: vec![Utterance::test("hello")],
+                    duration: Duration::from_secs(1),
+                })
+            })
+        };
+        assert!(transcribe(State::new(path.clone(), options.clone())).is_err());
+        transcribe(State::resume(path.clone(), options.clone(), false).unwrap()).unwrap();
+        // Only the failed input is transcribed again.
+        assert_eq!(
+            transcribed,
+            [&inputs[0], &inputs[1], &inputs[1]].map(PathBuf::clone)
+        );
+
+        let other = json!({ "model": "other.pbmm" });
+        assert!(matches!(
+            State::resume(path.clone(), other.clone(), false),
+            Err(Error::Usage(_))
+        ));
+        assert!(State::resume(path, other, true).is_ok());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::error::Error;
+use crate::output::{Event, Sink};
+use crate::resample::{self, Resampler};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Sample, SampleFormat, Stream, StreamConfig, SupportedStreamConfig};
+use std::{
+    f32::consts::PI,
+    fs::File,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tracing::{error, warn};
+
+/// Frequency and duration of the tone confirming a transcript.
+const DONE_TONE: (f32, Duration) = (880.0, Duration::from_millis(80));
+/// Frequency and duration of the tone signaling an utterance without a transcript.
+const DROPPED_TONE: (f32, Duration) = (440.0, Duration::from_millis(150));
+/// Duration of the fade in and out of tones, which prevents clicks.
+const FADE: Duration = Duration::from_millis(5);
+/// Time allowed for the output stream to play the last samples of a sound before it is closed.
+const DRAIN: Duration = Duration::from_millis(50);
+
+enum Sound {
+    /// A transcript was output.
+    Done,
+    /// An utterance was empty or dropped.
+    Dropped,
+}
+
+/// Plays a sound on the default output device whenever an utterance is finalized.
+/// Sounds are played on a separate thread, so that they never hold up the pipeline, and are
+/// skipped if the device fails.
+pub struct Beeper {
+    sounds: Sender<Sound>,
+}
+
+impl Beeper {
+    /// Plays a high tone, or the WAV file at `file`, for transcripts and a lower tone for
+    /// utterances without one, scaled by `volume`.
+    pub fn spawn(file: Option<&Path>, volume: f32) -> Result<Self, Error> {
+        let file = file.map(read).transpose()?;
+        let volume = volume.clamp(0.0, 1.0);
+        let (sounds, rx) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(err) = play(rx, file, volume) {
+                error!(error = %err, "Failed to play beeps, disabling them");
+            }
+        });
+        Ok(Self { sounds })
+    }
+}
+
+impl Sink for Beeper {
+    fn send(&mut self, event: &Event) {
+        let sound = match event {
+            Event::Final(utterance) if !utterance.text.is_empty() => Sound::Done,
+            Event::Final(_) | Event::Dropped(_) => Sound::Dropped,
+            _ => return,
+        };
+        let _ = self.sounds.send(sound);
+    }
+}
+
+/// Reads the mono samples of the WAV file at `path` along with their sample rate.
+fn read(path: &Path) -> Result<(Vec<i16>, u32), Error> {
+    let file = File::open(path).map_err(Error::with(Error::Usage, "Failed to open beep file"))?;
+    let mut reader =
+        audrey::Reader::new(file).map_err(Error::with(Error::Usage, "Failed to read beep file"))?;
+    let desc = reader.description();
+    let channels = desc.channel_count() as usize;
+    let samples: Vec<i16> = reader
+        .samples()
+        .collect::<Result<_, _>>()
+        .map_err(Error::with(Error::Usage, "Failed to read beep file"))?;
+    // Keeps the first channel only, which is fine for a beep.
+    Ok((
+        samples.into_iter().step_by(channels.max(1)).collect(),
+        desc.sample_rate(),
+    ))
+}
+
+/// Returns a sine tone of `frequency` lasting `duration` at `sample_rate`, faded in and out.
+fn tone((frequency, duration): (f32, Duration), sample_rate: u32) -> Vec<f32> {
+    let len = (duration.as_millis() as u64 * sample_rate as u64 / 1000) as usize;
+    let fade = (FADE.as_millis() as u64 * sample_rate as u64 / 1000) as usize;
+    (0..len)
+        .map(|i| {
+            let envelope = (i.min(len - i) as f32 / fade as f32).min(1.0);
+            envelope * (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin()
+        })
+        .collect()
+}
+
+/// Returns the default output device and its default configuration.
+fn output() -> Result<(cpal::Device, SupportedStreamConfig), Error> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| Error::Device("Failed to find default output device".into()))?;
+    let config = device.default_output_config().map_err(Error::with(
+        Error::Device,
+        "Failed to get default device output configuration",
+    ))?;
+    Ok((device, config))
+}
+
+/// Plays `samples` at `sample_rate` on the default output device, returning once they were
+/// played.
+pub fn play_once(samples: Vec<i16>, sample_rate: u32) -> Result<(), Error> {
+    let (device, config) = output()?;
+    let samples = resample::resample(
+        samples.into_iter(),
+        sample_rate,
+        config.sample_rate().0,
+        Resampler::Linear,
+    )
+    .map(|sample| sample.to_f32())
+    .collect();
+    play_samples(&device, &config, Arc::new(samples))
+}
+
+/// Plays `samples` at the rate of `config` on `device`, returning once they were played.
+fn play_samples(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    samples: Arc<Vec<f32>>,
+) -> Result<(), Error> {
+    let sample_rate = config.sample_rate().0;
+    let duration = Duration::from_micros(samples.len() as u64 * 1_000_000 / sample_rate as u64);
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        SampleFormat::I16 => build::<i16>(device, &stream_config, samples),
+        SampleFormat::U16 => build::<u16>(device, &stream_config, samples),
+        SampleFormat::F32 => build::<f32>(device, &stream_config, samples),
+    }
+    .map_err(Error::with(Error::Device, "Failed to build output stream"))?;
+    stream
+        .play()
+        .map_err(Error::with(Error::Device, "Failed to play output stream"))?;
+    thread::sleep(duration + DRAIN);
+    Ok(())
+}
+
+/// Plays the sounds received on `sounds` until the sender is dropped.
+fn play(sounds: Receiver<Sound>, file: Option<(Vec<i16>, u32)>, volume: f32) -> Result<(), Error> {
+    let (device, config) = output()?;
+    let sample_rate = config.sample_rate().0;
+    let scale = |samples: Vec<f32>| -> Arc<Vec<f32>> {
+        Arc::new(samples.into_iter().map(|sample| sample * volume).collect())
+    };
+    let done = scale(match file {
+        Some((samples, rate)) => {
+            resample::resample(samples.into_iter(), rate, sample_rate, Resampler::Linear)
+                .map(|sample| sample.to_f32())
+                .collect()
+        }
+        None => tone(DONE_TONE, sample_rate),
+    });
+    let dropped = scale(tone(DROPPED_TONE, sample_rate));
+    for sound in sounds {
+        let samples = match sound {
+            Sound::Done => done.clone(),
+            Sound::Dropped => dropped.clone(),
+        };
+        if let Err(err) = play_samples(&device, &config, samples) {
+            warn!(error = %err, "Failed to play beep");
+        }
+    }
+    Ok(())
+}
+
+fn build<T: Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    samples: Arc<Vec<f32>>,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let mut position = 0;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(channels) {
+                let sample = T::from(samples.get(position).unwrap_or(&0.0));
+                position += 1;
+                for out in frame {
+                    *out = sample;
+                }
+            }
+        },
+        move |err| error!(error = %err, "Failed to play frame on output stream"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faded_tone() {
+        let samples = tone((1000.0, Duration::from_millis(20)), 8000);
+        assert_eq!(samples.len(), 160);
+        assert_eq!(samples[0], 0.0);
+        // The tone fades in over 5 ms, i.e. 40 samples.
+        let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!(peak(&samples[..10]) < 0.3);
+        assert!(peak(&samples[60..100]) > 0.99);
+        assert!(peak(&samples) <= 1.0);
+        assert!(peak(&samples[150..]) < 0.3);
+    }
+
+    #[test]
+    fn read_first_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("beep.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in &[1i16, -1, 2, -2, 3, -3] {
+            writer.write_sample(*sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert_eq!(read(&path).unwrap(), (vec![1, 2, 3], 22050));
+        assert!(matches!(
+            read(&dir.path().join("missing.wav")),
+            Err(Error::Usage(_))
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::segmenter::{Segment, Segmenter, Split, Step};
+use crate::vad::{self, FvadMode, FvadSampleLength};
+use serde_json::{json, Value};
+use std::{fs::File, path::Path, time::Duration};
+
+/// Fvad modes benchmarked.
+const MODES: [FvadMode; 4] = [
+    FvadMode::Quality,
+    FvadMode::LowBitrate,
+    FvadMode::Aggressive,
+    FvadMode::VeryAggressive,
+];
+
+/// Fvad sample lengths benchmarked.
+const LENGTHS: [FvadSampleLength; 3] = [
+    FvadSampleLength::Length10ms,
+    FvadSampleLength::Length20ms,
+    FvadSampleLength::Length30ms,
+];
+
+/// Silence paddings in milliseconds benchmarked.
+const PADDINGS: [u64; 5] = [100, 200, 300, 500, 800];
+
+/// Minimum ratio of the intersection to the union of a detected and a reference segment, for the
+/// detected segment to match the reference.
+const MIN_OVERLAP: f64 = 0.5;
+
+/// Segmentation parameters.
+#[derive(Clone, Copy)]
+pub struct Params {
+    pub mode: Option<FvadMode>,
+    pub length: FvadSampleLength,
+    pub padding: Duration,
+    pub min_speech: Duration,
+    pub max_utterance: Duration,
+    pub split_search: Duration,
+    pub split_overlap: Duration,
+    pub split_min_carry: Duration,
+}
+
+/// Boundaries of a segment in milliseconds from the start of the stream.
+#[derive(Clone, Copy)]
+pub struct Span {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Span {
+    fn overlap(&self, other: &Span) -> f64 {
+        let intersection = self
+            .end
+            .min(other.end)
+            .saturating_sub(self.start.max(other.start));
+        let union = self.end.max(other.end) - self.start.min(other.start);
+        if union == 0 {
+            return 0.0;
+        }
+        intersection as f64 / union as f64
+    }
+}
+
+/// Reads mono samples of the WAV file at `path` along with their sample rate.
+pub fn read(path: &Path) -> Result<(Vec<i16>, u32), String> {
+    let file = File::open(path).map_err(|err| format!("Failed to open input file: {}", err))?;
+    let mut reader =
+        audrey::Reader::new(file).map_err(|err| format!("Failed to read input file: {}", err))?;
+    let desc = reader.description();
+    if desc.channel_count() != 1 {
+        return Err(format!(
+            "Input file must have 1 channel, got {}",
+            desc.channel_count()
+        ));
+    }
+    if vad::supported_sample_rate(desc.sample_rate()) != Some(desc.sample_rate()) {
+        return Err(format!(
+            "Sample rate of input file must be one of 8000, 16000, 32000 or 48000, got {}",
+            desc.sample_rate()
+        ));
+    }
+    let samples = reader
+        .samples()
+        .collect::<Result<_, _>>()
+        .map_err(|err| format!("Failed to read sample from input file: {}", err))?;
+    Ok((samples, desc.sample_rate()))
+}
+
+/// Returns the segments detected in `samples` at `sample_rate`, including rejected ones.
+pub fn segment(samples: &[i16], sample_rate: u32, params: &Params) -> Vec<Span> {
+    let to_ms = |sample: u64| sample * 1000 / sample_rate as u64;
+    segments(samples, sample_rate, params, true)
+        .iter()
+        .map(|segment| Span {
+            start: to_ms(segment.start + segment.overlap as u64),
+            end: to_ms(segment.end()),
+        })
+        .collect()
+}
+
+/// Returns the segments detected in `samples` at `sample_rate`, including rejected ones if
+/// `rejected` is set.
+pub fn segments(
+    samples: &[i16],
+    sample_rate: u32,
+    params: &Params,
+    rejected: bool,
+) -> Vec<Segment> {
+    let frame_sample_count = (params.length as u32 * (sample_rate / 1000)) as usize;
+    let max_samples =
+        (params.max_utterance.as_millis() as u64 * sample_rate as u64 / 1000) as usize;
+    let frames = |duration: Duration| duration.as_millis() as usize / params.length as usize;
+    let mut segmenter = Segmenter::new(
+        vad::new(sample_rate, params.mode),
+        frame_sample_count,
+        frames(params.min_speech),
+        max_samples.max(frame_sample_count),
+        frames(params.padding),
+        Split {
+            search_frames: frames(params.split_search),
+            overlap_frames: frames(params.split_overlap),
+            min_carry_frames: frames(params.split_min_carry),
+        },
+    );
+    let mut segments = Vec::new();
+    for frame in samples.chunks_exact(frame_sample_count) {
+        match segmenter.push(frame) {
+            Some(Step::SpeechStopped(segment)) | Some(Step::Split(segment)) => {
+                segments.push(segment)
+            }
+            Some(Step::Rejected(segment)) if rejected => segments.push(segment),
+            Some(Step::Rejected(_)) | Some(Step::SpeechStarted(_)) | None => {}
+        }
+    }
+    segments.extend(segmenter.flush());
+    segments
+}
+
+/// Returns `spans` as JSON, the format of the reference segmentation.
+pub fn to_json(spans: &[Span]) -> Value {
+    Value::Array(
+        spans
+            .iter()
+            .map(|span| json!({ "start_ms": span.start, "end_ms": span.end }))
+            .collect(),
+    )
+}
+
+/// Reads the reference segmentation at `path`, a JSON array of objects with `start_ms` and
+/// `end_ms` fields as written by `to_json`.
+pub fn load(path: &Path) -> Result<Vec<Span>, String> {
+    let file = File::open(path).map_err(|err| format!("Failed to open reference: {}", err))?;
+    let value: Value = serde_json::from_reader(file)
+        .map_err(|err| format!("Failed to parse reference: {}", err))?;
+    let field = |segment: &Value, i: usize, name: &str| {
+        segment[name].as_u64().ok_or_else(|| {
+            format!(
+                "Segment {} of reference lacks `{}` in milliseconds",
+                i, name
+            )
+        })
+    };
+    value
+        .as_array()
+        .ok_or("Reference must be an array of segments")?
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            let span = Span {
+                start: field(segment, i, "start_ms")?,
+                end: field(segment, i, "end_ms")?,
+            };
+            if span.end < span.start {
+                return Err(format!("Segment {} of reference ends before it starts", i));
+            }
+            Ok(span)
+        })
+        .collect()
+}
+
+/// Accuracy of a segmentation compared to the reference.
+struct Score {
+    precision: f64,
+    recall: f64,
+    /// Mean distance in milliseconds of the boundaries of matched segments to the reference.
+    boundary_error: f64,
+}
+
+impl Score {
+    fn f1(&self) -> f64 {
+        if self.precision + self.recall == 0.0 {
+            return 0.0;
+        }
+        2.0 * self.precision * self.recall / (self.precision + self.recall)
+    }
+}
+
+/// Matches every detected segment to the unmatched reference segment it overlaps the most with.
+fn score(detected: &[Span], reference: &[Span]) -> Score {
+    let mut matched = vec![false; reference.len()];
+    let mut matches = 0;
+    let mut boundary_error = 0;
+    for span in detected {
+        let best = reference
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !matched[*i])
+            .map(|(i, reference)| (i, span.overlap(reference)))
+            .filter(|(_, overlap)| *overlap >= MIN_OVERLAP)
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).expect("Overlap is NaN"));
+        if let Some((i, _)) = best {
+            matched[i] = true;
+            matches += 1;
+            let reference = reference[i];
+            boundary_error += (span.start as i64 - reference.start as i64).abs()
+                + (span.end as i64 - reference.end as i64).abs();
+        }
+    }
+    let ratio = |total: usize| {
+        if total == 0 {
+            1.0
+        } else {
+            matches as f64 / total as f64
+        }
+    };
+    Score {
+        precision: ratio(detected.len()),
+        recall: ratio(reference.len()),
+        boundary_error: if matches == 0 {
+            0.0
+        } else {
+            boundary_error as f64 / (2 * matches) as f64
+        },
+    }
+}
+
+/// Segments `samples` with every combination of benchmarked parameters, printing the score of
+/// each against `reference` and recommending the best.
+pub fn run(samples: &[i16], sample_rate: u32, params: Params, reference: &[Span]) {
+    println!(
+        "{:>4} {:>6} {:>7} {:>8} {:>9} {:>6} {:>6} {:>14}",
+        "mode", "length", "padding", "segments", "precision", "recall", "f1", "boundary error"
+    );
+    let mut best: Option<(Params, Score)> = None;
+    for &mode in &MODES {
+        for &length in &LENGTHS {
+            for &padding in &PADDINGS {
+                let params = Params {
+                    mode: Some(mode),
+                    length,
+                    padding: Duration::from_millis(padding),
+                    ..params
+                };
+                let detected = segment(samples, sample_rate, &params);
+                let score = score(&detected, reference);
+                println!(
+                    "{:>4} {:>4}ms {:>5}ms {:>8} {:>9.3} {:>6.3} {:>6.3} {:>12.0}ms",
+                    mode as u8,
+                    length as u32,
+                    padding,
+                    detected.len(),
+                    score.precision,
+                    score.recall,
+                    score.f1(),
+                    score.boundary_error
+                );
+                let better = best.as_ref().is_none_or(|(_, best)| {
+                    score.f1() > best.f1()
+                        || score.f1() == best.f1() && score.boundary_error < best.boundary_error
+                });
+                if better {
+                    best = Some((params, score));
+                }
+            }
+        }
+    }
+    if let Some((params, score)) = best {
+        println!(
+            "\nBest: --fvad-mode {} --fvad-sample-length {}ms --silence-padding {} (f1 {:.3}, boundary error {:.0}ms)",
+            params.mode.map_or(0, |mode| mode as u8),
+            params.length as u32,
+            params.padding.as_millis(),
+            score.f1(),
+            score.boundary_error
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: u64, end: u64) -> Span {
+        Span { start, end }
+    }
+
+    #[test]
+    fn overlap() {
+        assert_eq!(span(0, 100).overlap(&span(0, 100)), 1.0);
+        assert_eq!(span(0, 100).overlap(&span(50, 150)), 50.0 / 150.0);
+        assert_eq!(span(0, 100).overlap(&span(200, 300)), 0.0);
+        assert_eq!(span(10, 10).overlap(&span(10, 10)), 0.0);
+    }
+
+    #[test]
+    fn scoring() {
+        let reference = [span(0, 1000), span(2000, 3000)];
+        let detected = [span(100, 900), span(2000, 3000), span(5000, 6000)];
+        let score = score(&detected, &reference);
+        assert_eq!(score.precision, 2.0 / 3.0);
+        assert_eq!(score.recall, 1.0);
+        assert_eq!(score.f1(), 0.8);
+        assert_eq!(score.boundary_error, 50.0);
+
+        let score = super::score(&[], &reference);
+        assert_eq!(score.f1(), 0.0);
+        assert_eq!(score.boundary_error, 0.0);
+    }
+
+    #[test]
+    fn reference() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reference.json");
+        let spans = [span(0, 1000), span(2000, 3000)];
+        std::fs::write(&path, to_json(&spans).to_string()).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!((loaded[1].start, loaded[1].end), (2000, 3000));
+
+        std::fs::write(&path, r#"[{"start_ms": 10, "end_ms": 5}]"#).unwrap();
+        assert_eq!(
+            load(&path).err().unwrap(),
+            "Segment 0 of reference ends before it starts"
+        );
+        std::fs::write(&path, r#"[{"start_ms": 10}]"#).unwrap();
+        assert_eq!(
+            load(&path).err().unwrap(),
+            "Segment 0 of reference lacks `end_ms` in milliseconds"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::batch::{self, Transcription};
+use crate::intents::Intent;
+use crate::output::{Offsets, Utterance};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// Transcriptions of batch inputs stored as a JSON file per input, named by the SHA256 digest of
+/// its audio content and of the options it was transcribed with, so that entries may be
+/// inspected or pruned by hand.
+pub struct Cache {
+    dir: PathBuf,
+    /// Digest of the options, which every key is derived from.
+    options: String,
+    /// Whether entries are looked up, or only stored.
+    lookup: bool,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl Cache {
+    /// Opens the cache in `dir` for transcriptions with `options`, creating `dir` if needed.
+    /// Entries are only stored, but never looked up, unless `lookup` is set.
+    pub fn open(dir: PathBuf, options: &Value, lookup: bool) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            options: format!("{:x}", Sha256::digest(options.to_string().as_bytes())),
+            lookup,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// Returns the path of the entry of the input with the SHA256 digest `sha256`.
+    fn path(&self, sha256: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.options.as_bytes());
+        hasher.update(sha256.as_bytes());
+        self.dir.join(format!("{:x}.json", hasher.finalize()))
+    }
+
+    /// Returns the cached transcription of the input with the SHA256 digest `sha256`, counting
+    /// the hit or miss. Unreadable entries are misses, as is every input, unless looking up.
+    pub fn get(&mut self, sha256: &str) -> Option<Transcription> {
+        if !self.lookup {
+            self.misses += 1;
+            return None;
+        }
+        let path = self.path(sha256);
+        let transcription = match fs::read(&path) {
+            Ok(entry) => match serde_json::from_slice(&entry) {
+                Ok(entry) => transcription(&entry),
+                Err(err) => {
+                    warn!(path = %path.display(), error = %err, "Failed to parse cache entry");
+                    None
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "Failed to read cache entry");
+                None
+            }
+        };
+        match transcription {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        transcription
+    }
+
+    /// Stores `transcription` of `input` with the SHA256 digest `sha256`.
+    pub fn put(
+        &mut self,
+        input: &Path,
+        sha256: &str,
+        transcription: &Transcription,
+    ) -> io::Result<()> {
+        let path = self.path(sha256);
+        debug!(input = %input.display(), path = %path.display(), "Caching transcription");
+        batch::write(
+            &path,
+            &json!({
+                "input": input.display().to_string(),
+                "sha256": sha256,
+                "duration_us": transcription.duration.as_micros() as u64,
+                "utterances": transcription.utterances.iter().map(entry).collect::<Vec<_>>(),
+            }),
+        )
+    }
+}
+
+/// Returns the fields of `utterance`, which transcripts are rendered from.
+fn entry(utterance: &Utterance) -> Value {
+    json!({
+        "seq": utterance.seq,
+        "text": utterance.text,
+        "speaker": utterance.speaker,
+        "confidence": utterance.confidence,
+        "start_us": utterance.start.as_micros() as u64,
+        "end_us": utterance.end.as_micros() as u64,
+        "start_sample": utterance.start_sample,
+        "end_sample": utterance.end_sample,
+        "offsets": utterance.offsets.map(Offsets::to_json),
+        "censored": utterance.censored,
+        "low_confidence": utterance.low_confidence,
+        "paragraph": utterance.paragraph,
+        "duplicate": utterance.duplicate,
+        "rescored": utterance.rescored,
+        "speech_us": utterance.speech.as_micros() as u64,
+        "intent": utterance.intent.as_ref().map(|intent| {
+            intent.as_ref().map(|intent| json!({ "name": intent.name, "slots": intent.slots }))
+        }),
+    })
+}
+
+/// Parses the intent of a cache entry, `None` if intents were not matched, `Some(None)` if
+/// none was.
+pub fn intent(intent: &Value) -> Option<Option<Intent>> {
+    match intent {
+        Value::Null => Some(None),
+        Value::Object(intent) => Some(Some(Intent {
+            name: intent.get("name")?.as_str()?.to_string(),
+            slots: intent
+                .get("slots")?
+                .as_object()?
+                .iter()
+                .map(|(slot, value)| (slot.clone(), value.clone()))
+                .collect(),
+        })),
+        _ => None,
+    }
+}
+
+/// Parses the offsets of an utterance of a cache entry, if stored.
+fn offsets(offsets: &Value) -> Option<Offsets> {
+    Some(Offsets {
+        buffer_start: offsets["buffer_start"].as_u64()?,
+        speech_start: offsets["speech_start"].as_u64()?,
+        speech_end: offsets["speech_end"].as_u64()?,
+        buffer_end: offsets["buffer_end"].as_u64()?,
+    })
+}
+
+/// Parses the transcription of a cache entry, returning `None` if it is malformed.
+fn transcription(entry: &Value) -> Option<Transcription> {
+    let utterances = entry["utterances"]
+        .as_array()?
+        .iter()
+        .map(|utterance| {
+            Some(Utterance {
+                seq: utterance["seq"].as_u64()?,
+                text: utterance["text"].as_str()?.to_string(),
+                speaker: utterance["speaker"].as_str().map(str::to_string),
+                confidence: utterance["confidence"].as_f64()?,
+                start: Duration::from_micros(utterance["start_us"].as_u64()?),
+                end: Duration::from_micros(utterance["end_us"].as_u64()?),
+                start_sample: utterance["start_sample"].as_u64()?,
+                end_sample: utterance["end_sample"].as_u64()?,
+                offsets: offsets(&utterance["offsets"]),
+                captured_at: None,
+                fingerprint: None,
+                loudness: None,
+                recording: None,
+                censored: utterance["censored"].as_bool()?,
+                low_confidence: utterance["low_confidence"].as_bool()?,
+                paragraph: utterance["paragraph"].as_u64(),
+                comparison: None,
+                intent: utterance.get("intent").and_then(intent),
+                duplicate: utterance["duplicate"].as_bool().unwrap_or(false),
+                rescored: utterance["rescored"].as_bool().unwrap_or(false),
+                speech: Duration::from_micros(utterance["speech_us"].as_u64().unwrap_or(0)),
+            })
+        })
+        .collect::<Option<_>>()?;
+    Some(Transcription {
+        utterances,
+        duration: Duration::from_micros(entry["duration_us"].as_u64()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcription() -> Transcription {
+        Transcription {
+            utterances: vec![
+                Utterance {
+                    seq: 1,
+                    speaker: Some("Alice".into()),
+                    confidence: -2.5,
+                    start: Duration::from_micros(1_500_250),
+                    end: Duration::from_secs(3),
+                    start_sample: 24004,
+                    end_sample: 48000,
+                    paragraph: Some(1),
+                    ..Utterance::test("hello")
+                },
+                Utterance {
+                    seq: 2,
+                    censored: true,
+                    low_confidence: true,
+                    ..Utterance::test("****")
+                },
+            ],
+            duration: Duration::from_millis(4500),
+        }
+    }
+
+    /// Returns the fields of the utterances of `transcription`, which are cached.
+    fn cached(transcription: &Transcription) -> Vec<Value> {
+        transcription.utterances.iter().map(entry).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = json!({ "model": "model.pbmm" });
+        let mut cache = Cache::open(dir.path().join("cache"), &options, true).unwrap();
+        assert!(cache.get("abc").is_none());
+        cache
+            .put(Path::new("in.wav"), "abc", &transcription())
+            .unwrap();
+        let got = cache.get("abc").unwrap();
+        assert_eq!(cached(&got), cached(&transcription()));
+        assert_eq!(got.duration, Duration::from_millis(4500));
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+
+        // Keys depend on the options, so that changing them transcribes inputs again.
+        let other = json!({ "model": "other.pbmm" });
+        let mut other = Cache::open(dir.path().join("cache"), &other, true).unwrap();
+        assert!(other.get("abc").is_none());
+        let mut reopened = Cache::open(dir.path().join("cache"), &options, true).unwrap();
+        assert!(reopened.get("abc").is_some());
+    }
+
+    #[test]
+    fn store_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = Cache::open(dir.path().to_path_buf(), &json!({}), false).unwrap();
+        cache
+            .put(Path::new("in.wav"), "abc", &transcription())
+            .unwrap();
+        assert!(cache.get("abc").is_none());
+        assert_eq!((cache.hits, cache.misses), (0, 1));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn malformed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = Cache::open(dir.path().to_path_buf(), &json!({}), true).unwrap();
+        fs::write(cache.path("abc"), "{").unwrap();
+        assert!(cache.get("abc").is_none());
+        fs::write(cache.path("abc"), r#"{"utterances":[{"text":"hi"}]}"#).unwrap();
+        assert!(cache.get("abc").is_none());
+        assert_eq!((cache.hits, cache.misses), (0, 2));
+    }
+}
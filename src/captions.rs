@@ -0,0 +1,174 @@
+use crate::output::{Event, Sink};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+};
+
+/// Clears the terminal and moves the cursor to the top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders the most recent utterances as captions filling the terminal, the most recent one at
+/// the bottom.
+pub struct Captions {
+    count: usize,
+    styled: bool,
+    utterances: VecDeque<String>,
+    partial: Option<String>,
+    /// Set when the terminal was resized and the captions need to be wrapped again.
+    resized: Arc<AtomicBool>,
+}
+
+impl Captions {
+    /// Creates captions showing the last `count` utterances, highlighting the most recent one if
+    /// `styled` is set.
+    pub fn new(count: usize, styled: bool) -> Self {
+        let resized = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        if let Err(err) =
+            signal_hook::flag::register(signal_hook::consts::SIGWINCH, resized.clone())
+        {
+            tracing::warn!(error = %err, "Failed to watch for terminal resizes");
+        }
+        let captions = Self {
+            count: count.max(1),
+            styled,
+            utterances: VecDeque::with_capacity(count),
+            partial: None,
+            resized,
+        };
+        captions.render();
+        captions
+    }
+
+    fn render(&self) {
+        let (width, height) = match terminal_size::terminal_size() {
+            Some((terminal_size::Width(width), terminal_size::Height(height))) => {
+                (width as usize, height as usize)
+            }
+            None => (80, 24),
+        };
+
+        let mut lines = Vec::new();
+        let last = self.utterances.len() + self.partial.iter().count();
+        for (i, text) in self
+            .utterances
+            .iter()
+            .chain(self.partial.iter())
+            .enumerate()
+        {
+            let style = match (self.styled, i + 1 == last) {
+                (false, _) => "",
+                (true, true) => BOLD,
+                (true, false) => DIM,
+            };
+            lines.extend(wrap(text, width).into_iter().map(|line| (style, line)));
+        }
+        // Leave the last row empty, so that the terminal does not scroll.
+        let rows = height.saturating_sub(1).max(1);
+        let skip = lines.len().saturating_sub(rows);
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let mut out = String::from(CLEAR_SCREEN);
+        for _ in lines.len()..rows {
+            out.push('\n');
+        }
+        for (style, line) in lines.into_iter().skip(skip) {
+            out.push_str(style);
+            out.push_str(&line);
+            if !style.is_empty() {
+                out.push_str(RESET);
+            }
+            out.push('\n');
+        }
+        // Failing to write to the terminal is not worth interrupting transcription for.
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking at whitespace where possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut len = 0;
+    for word in text.split_whitespace() {
+        let mut chars: Vec<char> = word.chars().collect();
+        if len > 0 && len + 1 + chars.len() > width {
+            lines.push(std::mem::take(&mut line));
+            len = 0;
+        }
+        if len > 0 {
+            line.push(' ');
+            len += 1;
+        }
+        while len + chars.len() > width {
+            let rest = chars.split_off(width - len);
+            line.extend(chars);
+            lines.push(std::mem::take(&mut line));
+            len = 0;
+            chars = rest;
+        }
+        len += chars.len();
+        line.extend(chars);
+    }
+    if len > 0 {
+        lines.push(line);
+    }
+    lines
+}
+
+impl Sink for Captions {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::Partial(utterance) | Event::StablePartial(utterance) => {
+                self.partial = Some(utterance.text.clone());
+            }
+            Event::Final(utterance) => {
+                self.partial = None;
+                if utterance.text.is_empty() {
+                    return;
+                }
+                if self.utterances.len() == self.count {
+                    self.utterances.pop_front();
+                }
+                self.utterances.push_back(utterance.text.clone());
+            }
+            _ => return,
+        }
+        self.render()
+    }
+
+    fn samples(&mut self, _: &[i16]) {
+        if self.resized.swap(false, Ordering::Relaxed) {
+            self.render()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_words() {
+        assert_eq!(wrap("the quick brown fox", 9), ["the quick", "brown fox"]);
+        assert_eq!(wrap("  the   quick  ", 20), ["the quick"]);
+        assert_eq!(wrap("", 20), Vec::<String>::new());
+    }
+
+    #[test]
+    fn wrap_long_words() {
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        // Long words start on a line of their own.
+        assert_eq!(wrap("a abcdefgh", 4), ["a", "abcd", "efgh"]);
+        assert_eq!(wrap("żółwik", 3), ["żół", "wik"]);
+        assert_eq!(wrap("ab", 0), ["a", "b"]);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::clock::Anchor;
+use crate::metrics;
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::warn;
+
+/// Duration of audio buffered between the audio callback and the pipeline.
+const BUFFER_DURATION: Duration = Duration::from_secs(5);
+
+/// Number of samples drained from the buffer at once.
+const CHUNK_SIZE: usize = 1024;
+
+/// Interval at which an empty buffer is polled for more samples.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Minimum interval between warnings about overflows.
+const WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Silence written in place of missing samples.
+const SILENCE: [i16; CHUNK_SIZE] = [0; CHUNK_SIZE];
+
+/// Creates a buffer holding `BUFFER_DURATION` of samples at `sample_rate`, split into its writing
+/// and reading halves.
+/// `anchor` is set to the capture time of the first sample once it is written.
+pub fn buffer(sample_rate: u32, anchor: Anchor) -> (Writer, Reader) {
+    let capacity = (BUFFER_DURATION.as_millis() as u64 * sample_rate as u64 / 1000) as usize;
+    let (producer, consumer) = RingBuffer::new(capacity).split();
+    let overflowed = Arc::new(AtomicU64::new(0));
+    let missing = Arc::new(AtomicU64::new(0));
+    (
+        Writer {
+            producer,
+            overflowed: overflowed.clone(),
+            missing: missing.clone(),
+            silence: 0,
+            anchor: Some(anchor),
+            sample_rate,
+        },
+        Reader {
+            consumer,
+            overflowed,
+            missing,
+            sample_rate,
+            reported: 0,
+            reported_missing: 0,
+            warned: None,
+            warned_missing: None,
+            chunk: vec![0; CHUNK_SIZE],
+            position: 0,
+            len: 0,
+        },
+    )
+}
+
+/// Writing half of the buffer, owned by the audio callback.
+pub struct Writer {
+    producer: Producer<i16>,
+    overflowed: Arc<AtomicU64>,
+    /// Number of samples missing from the captured stream.
+    missing: Arc<AtomicU64>,
+    /// Number of samples of silence, which are yet to be written in place of dropped or missing
+    /// samples.
+    silence: usize,
+    /// Anchor to set on the first write.
+    anchor: Option<Anchor>,
+    sample_rate: u32,
+}
+
+impl Writer {
+    /// Writes `samples` without blocking or allocating.
+    /// Samples not fitting in the buffer are dropped and counted as overflowed. They are replaced
+    /// with silence once the buffer has space, so that offsets of later samples stay aligned with
+    /// the time they were captured at.
+    pub fn write(&mut self, samples: &[i16]) {
+        if let Some(anchor) = self.anchor.take() {
+            // The samples were captured up to now.
+            let captured =
+                Duration::from_micros(samples.len() as u64 * 1_000_000 / self.sample_rate as u64);
+            let now = SystemTime::now();
+            anchor.set(now.checked_sub(captured).unwrap_or(now));
+        }
+        while self.silence > 0 {
+            let written = self
+                .producer
+                .push_slice(&SILENCE[..self.silence.min(CHUNK_SIZE)]);
+            if written == 0 {
+                break;
+            }
+            self.silence -= written;
+        }
+        let written = if self.silence == 0 {
+            self.producer.push_slice(samples)
+        } else {
+            0
+        };
+        if written < samples.len() {
+            let dropped = samples.len() - written;
+            self.silence += dropped;
+            self.overflowed.fetch_add(dropped as u64, Ordering::Relaxed);
+            metrics::DROPPED_SAMPLES.fetch_add(dropped as u64, Ordering::Relaxed);
+            self.count_dropped(dropped);
+        }
+    }
+
+    /// Records that `samples` are missing from the captured stream before the next samples
+    /// written, e.g. because the device skipped them, replacing them with silence.
+    pub fn gap(&mut self, samples: usize) {
+        self.silence += samples;
+        self.missing.fetch_add(samples as u64, Ordering::Relaxed);
+        metrics::GAPS.fetch_add(1, Ordering::Relaxed);
+        self.count_dropped(samples);
+    }
+
+    fn count_dropped(&self, samples: usize) {
+        metrics::add_duration(
+            &metrics::DROPPED_MICROS,
+            Duration::from_micros(samples as u64 * 1_000_000 / self.sample_rate as u64),
+        );
+    }
+}
+
+/// Reading half of the buffer, yielding samples as they are captured.
+pub struct Reader {
+    consumer: Consumer<i16>,
+    overflowed: Arc<AtomicU64>,
+    missing: Arc<AtomicU64>,
+    sample_rate: u32,
+    reported: u64,
+    reported_missing: u64,
+    warned: Option<Instant>,
+    warned_missing: Option<Instant>,
+    chunk: Vec<i16>,
+    position: usize,
+    len: usize,
+}
+
+impl Reader {
+    /// Drains the next chunk of samples, waiting for the audio callback if the buffer is empty.
+    fn fill(&mut self) {
+        loop {
+            let overflowed = self.overflowed.load(Ordering::Relaxed);
+            if overflowed > self.reported
+                && self.warned.is_none_or(|at| at.elapsed() >= WARN_INTERVAL)
+            {
+                warn!(
+                    dropped = overflowed - self.reported,
+                    total = overflowed,
+                    "Capture buffer overflowed, dropped samples"
+                );
+                self.reported = overflowed;
+                self.warned = Some(Instant::now());
+            }
+            let missing = self.missing.load(Ordering::Relaxed);
+            if missing > self.reported_missing
+                && self
+                    .warned_missing
+                    .is_none_or(|at| at.elapsed() >= WARN_INTERVAL)
+            {
+                warn!(
+                    dropped_ms = (missing - self.reported_missing) * 1000 / self.sample_rate as u64,
+                    total_ms = missing * 1000 / self.sample_rate as u64,
+                    "Detected gap in captured audio, inserted silence in its place"
+                );
+                self.reported_missing = missing;
+                self.warned_missing = Some(Instant::now());
+            }
+
+            self.len = self.consumer.pop_slice(&mut self.chunk);
+            self.position = 0;
+            if self.len > 0 {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl Iterator for Reader {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.position == self.len {
+            self.fill();
+        }
+        let sample = self.chunk[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        let overflowed = self.overflowed.load(Ordering::Relaxed);
+        if overflowed > 0 {
+            warn!(
+                samples = overflowed,
+                "Dropped samples, which could not be processed in time"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_written() {
+        let anchor = Anchor::default();
+        let (mut writer, mut reader) = buffer(CHUNK_SIZE as u32, anchor.clone());
+        assert!(anchor.at(Duration::ZERO).is_none());
+        let samples: Vec<i16> = (0..3 * CHUNK_SIZE as i16).collect();
+        writer.write(&samples[..CHUNK_SIZE + 1]);
+        writer.write(&samples[CHUNK_SIZE + 1..]);
+        // The anchor is set on the first write.
+        assert!(anchor.at(Duration::ZERO).is_some());
+        assert_eq!(
+            reader.by_ref().take(samples.len()).collect::<Vec<_>>(),
+            samples
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        // Buffers a sample per second.
+        let (mut writer, mut reader) = buffer(1, Anchor::default());
+        writer.write(&[1, 2, 3]);
+        writer.write(&[4, 5, 6, 7]);
+        assert_eq!(writer.overflowed.load(Ordering::Relaxed), 2);
+        assert_eq!(reader.by_ref().take(5).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        assert_eq!(reader.reported, 2);
+
+        // Reading waits for the audio callback.
+        let pusher = thread::spawn(move || {
+            thread::sleep(POLL_INTERVAL * 4);
+            writer.write(&[8]);
+        });
+        // The dropped samples are replaced with silence.
+        assert_eq!(reader.by_ref().take(3).collect::<Vec<_>>(), [0, 0, 8]);
+        pusher.join().unwrap();
+    }
+
+    #[test]
+    fn gap() {
+        let (mut writer, mut reader) = buffer(CHUNK_SIZE as u32, Anchor::default());
+        writer.write(&[1]);
+        writer.gap(2);
+        writer.write(&[2]);
+        assert_eq!(reader.by_ref().take(4).collect::<Vec<_>>(), [1, 0, 0, 2]);
+        assert_eq!(reader.reported_missing, 2);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Casing {
+    /// Leave the transcript as produced by the model.
+    Raw,
+    /// Capitalize the first letter of the transcript and the standalone word "i".
+    Sentence,
+    /// Capitalize the first letter of every word.
+    Title,
+    /// Convert the transcript to upper case.
+    Upper,
+}
+
+impl FromStr for Casing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "sentence" => Ok(Self::Sentence),
+            "title" => Ok(Self::Title),
+            "upper" => Ok(Self::Upper),
+            _ => Err(format!(
+                "failed to parse `{}` into casing of raw, sentence, title or upper",
+                s
+            )),
+        }
+    }
+}
+
+/// Capitalizes the first letter of `word`, leaving the rest as-is.
+/// Letters, which are upper case as multiple characters, e.g. "ß", are capitalized as title case.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+    let mut out = String::with_capacity(word.len());
+    for (i, c) in first.to_uppercase().enumerate() {
+        if i == 0 {
+            out.push(c);
+        } else {
+            out.extend(c.to_lowercase());
+        }
+    }
+    out.push_str(chars.as_str());
+    out
+}
+
+/// Capitalizes the first alphabetic character of `text`.
+fn capitalize_first(text: &str) -> String {
+    match text.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((i, _)) => format!("{}{}", &text[..i], capitalize(&text[i..])),
+        None => text.to_string(),
+    }
+}
+
+/// Returns whether `word` is the pronoun "i", possibly contracted, e.g. "i'm".
+fn is_pronoun(word: &str) -> bool {
+    word == "i" || word.starts_with("i'")
+}
+
+impl Pass for Casing {
+    fn apply(&self, utterance: &mut Utterance) {
+        let text = &utterance.text;
+        utterance.text = match self {
+            Self::Raw => return,
+            Self::Sentence => capitalize_first(
+                &text
+                    .split(' ')
+                    .map(|word| {
+                        if is_pronoun(word) {
+                            capitalize(word)
+                        } else {
+                            word.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Self::Title => text
+                .split(' ')
+                .map(capitalize)
+                .collect::<Vec<_>>()
+                .join(" "),
+            Self::Upper => text.to_uppercase(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(casing: Casing, text: &str) -> String {
+        let mut utterance = Utterance::test(text);
+        casing.apply(&mut utterance);
+        utterance.text
+    }
+
+    #[test]
+    fn cases() {
+        for (casing, text, expected) in [
+            (Casing::Raw, "i think so", "i think so"),
+            (Casing::Raw, "Whisper Cased", "Whisper Cased"),
+            (Casing::Sentence, "i think so", "I think so"),
+            (
+                Casing::Sentence,
+                "so i'm here and i am",
+                "So I'm here and I am",
+            ),
+            (Casing::Sentence, "it is in the bin", "It is in the bin"),
+            (Casing::Sentence, "42 is the answer", "42 Is the answer"),
+            (Casing::Sentence, "", ""),
+            (Casing::Title, "the quick fox", "The Quick Fox"),
+            (Casing::Title, "a  b", "A  B"),
+            (Casing::Upper, "the quick fox", "THE QUICK FOX"),
+        ] {
+            assert_eq!(apply(casing, text), expected, "{:?} {:?}", casing, text);
+        }
+    }
+
+    #[test]
+    fn unicode() {
+        for (casing, text, expected) in [
+            (Casing::Sentence, "été à paris", "Été à paris"),
+            (Casing::Sentence, "ßig", "Ssig"),
+            (Casing::Sentence, "çà et là", "Çà et là"),
+            (Casing::Title, "élan straße", "Élan Straße"),
+            (Casing::Title, "ßo", "Sso"),
+            (Casing::Upper, "straße café", "STRASSE CAFÉ"),
+        ] {
+            assert_eq!(apply(casing, text), expected, "{:?} {:?}", casing, text);
+        }
+    }
+}
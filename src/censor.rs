@@ -0,0 +1,172 @@
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+use std::{collections::HashSet, fs, path::Path, str::FromStr};
+
+/// Words censored by default.
+const WORDS: &[&str] = &[
+    "arse",
+    "arsehole",
+    "ass",
+    "asshole",
+    "bastard",
+    "bitch",
+    "bollocks",
+    "bullshit",
+    "cock",
+    "cunt",
+    "dick",
+    "fuck",
+    "fucked",
+    "fucker",
+    "fucking",
+    "motherfucker",
+    "piss",
+    "pussy",
+    "shit",
+    "shitty",
+    "slut",
+    "twat",
+    "wanker",
+    "whore",
+];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Mode {
+    /// Replace all but the first and last letter with asterisks.
+    Mask,
+    /// Remove the word.
+    Drop,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "mask" => Ok(Self::Mask),
+            "drop" => Ok(Self::Drop),
+            _ => Err(format!(
+                "failed to parse `{}` into censor mode of mask or drop",
+                s
+            )),
+        }
+    }
+}
+
+/// Censors words of a list, matching whole words case-insensitively.
+pub struct Censor {
+    mode: Mode,
+    words: HashSet<String>,
+}
+
+impl Censor {
+    /// Creates a censor of the built-in words, unless `builtin` is unset, and the words listed
+    /// one per line in `list`, if given.
+    pub fn new(mode: Mode, list: Option<&Path>, builtin: bool) -> Result<Self, String> {
+        let mut words = HashSet::new();
+        if builtin {
+            words.extend(WORDS.iter().map(|word| word.to_string()));
+        }
+        if let Some(path) = list {
+            let contents = fs::read_to_string(path)
+                .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+            words.extend(
+                contents
+                    .lines()
+                    .map(|line| line.trim().to_lowercase())
+                    .filter(|line| !line.is_empty() && !line.starts_with('#')),
+            );
+        }
+        Ok(Self { mode, words })
+    }
+}
+
+/// Replaces all but the first and last character of `word` with asterisks.
+fn mask(word: &str) -> String {
+    let len = word.chars().count();
+    word.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if i == 0 || (i == len - 1 && len > 2) {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+impl Pass for Censor {
+    fn apply(&self, utterance: &mut Utterance) {
+        let mut censored = false;
+        let words: Vec<String> = utterance
+            .text
+            .split_whitespace()
+            .filter_map(|word| {
+                if !self.words.contains(&word.to_lowercase()) {
+                    return Some(word.to_string());
+                }
+                censored = true;
+                match self.mode {
+                    Mode::Mask => Some(mask(word)),
+                    Mode::Drop => None,
+                }
+            })
+            .collect();
+        if censored {
+            utterance.text = words.join(" ");
+            utterance.censored = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn censor(censor: &Censor, text: &str) -> (String, bool) {
+        let mut utterance = Utterance::test(text);
+        censor.apply(&mut utterance);
+        (utterance.text, utterance.censored)
+    }
+
+    #[test]
+    fn mask_words() {
+        assert_eq!(mask("shit"), "s**t");
+        assert_eq!(mask("ass"), "a*s");
+        assert_eq!(mask("ab"), "a*");
+        assert_eq!(mask("żółw"), "ż**w");
+    }
+
+    #[test]
+    fn modes() {
+        let masking = Censor::new(Mode::Mask, None, true).unwrap();
+        assert_eq!(
+            censor(&masking, "well Shit happens"),
+            ("well S**t happens".to_string(), true)
+        );
+        assert_eq!(
+            censor(&masking, "nothing  to   see"),
+            ("nothing  to   see".to_string(), false)
+        );
+        let dropping = Censor::new(Mode::Drop, None, true).unwrap();
+        assert_eq!(
+            censor(&dropping, "well shit happens"),
+            ("well happens".to_string(), true)
+        );
+        assert!("mask".parse::<Mode>().is_ok());
+        assert!("bleep".parse::<Mode>().is_err());
+    }
+
+    #[test]
+    fn list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words");
+        fs::write(&path, "# Custom words\nDarn\n\n").unwrap();
+        let censor_list = Censor::new(Mode::Mask, Some(&path), false).unwrap();
+        assert_eq!(
+            censor(&censor_list, "darn shit"),
+            ("d**n shit".to_string(), true)
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::output::{Event, Sink};
+use std::{
+    env,
+    io::Write,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+use tracing::warn;
+
+#[derive(Clone, Copy)]
+pub enum Mode {
+    /// Each transcript replaces the clipboard contents.
+    Replace,
+    /// Transcripts accumulate in the clipboard, separated by newlines.
+    Append,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "replace" => Ok(Self::Replace),
+            "append" => Ok(Self::Append),
+            _ => Err(format!(
+                "failed to parse `{}` into clipboard mode of replace or append",
+                s
+            )),
+        }
+    }
+}
+
+/// Copies final transcripts to the clipboard using `wl-copy` on Wayland and `xclip` otherwise.
+/// The first failure disables the clipboard for the rest of the session.
+pub struct Clipboard {
+    mode: Mode,
+    contents: String,
+    disabled: bool,
+}
+
+impl Clipboard {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            contents: String::new(),
+            disabled: false,
+        }
+    }
+
+    /// Updates the contents to copy with `text` according to the mode.
+    fn push(&mut self, text: &str) {
+        match self.mode {
+            Mode::Replace => self.contents.clear(),
+            Mode::Append if !self.contents.is_empty() => self.contents.push('\n'),
+            Mode::Append => {}
+        }
+        self.contents.push_str(text);
+    }
+
+    fn copy(&self) -> Result<(), String> {
+        let mut command = if env::var_os("WAYLAND_DISPLAY").is_some() {
+            Command::new("wl-copy")
+        } else {
+            let mut command = Command::new("xclip");
+            command.args(["-selection", "clipboard"]);
+            command
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("failed to execute clipboard command: {}", err))?;
+        child
+            .stdin
+            .take()
+            .expect("Failed to get stdin of clipboard command")
+            .write_all(self.contents.as_bytes())
+            .map_err(|err| format!("failed to write to clipboard command: {}", err))?;
+        match child.wait() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(format!("clipboard command exited with {}", status)),
+            Err(err) => Err(format!("failed to wait for clipboard command: {}", err)),
+        }
+    }
+}
+
+impl Sink for Clipboard {
+    fn send(&mut self, event: &Event) {
+        let utterance = match event {
+            Event::Final(utterance) if !self.disabled => utterance,
+            _ => return,
+        };
+        self.push(&utterance.text);
+        if let Err(err) = self.copy() {
+            warn!(error = %err, "Failed to copy to clipboard, disabling it");
+            self.disabled = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(mode: &str, texts: &[&str]) -> String {
+        let mut clipboard = Clipboard::new(mode.parse().unwrap());
+        for text in texts {
+            clipboard.push(text);
+        }
+        clipboard.contents
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(contents("replace", &["hello", "world"]), "world");
+        assert_eq!(contents("append", &["hello", "world"]), "hello\nworld");
+        assert!("prepend".parse::<Mode>().is_err());
+    }
+}
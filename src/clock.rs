@@ -0,0 +1,264 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Wall-clock time, at which the first sample of a stream was captured.
+/// Capture times of later samples are derived from their offset, so that they do not depend on
+/// buffering or decoding delays. Samples dropped by the capture buffer shift later times.
+#[derive(Clone, Default)]
+pub struct Anchor(Arc<AtomicU64>);
+
+impl Anchor {
+    /// Returns an anchor set to `at`.
+    pub fn fixed(at: SystemTime) -> Self {
+        let anchor = Self::default();
+        anchor.set(at);
+        anchor
+    }
+
+    /// Sets the anchor to `at`, unless it is set already.
+    pub fn set(&self, at: SystemTime) {
+        // Zero marks an unset anchor, so that times at the epoch itself are shifted by 1µs.
+        let micros = at
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |since| (since.as_micros() as u64).max(1));
+        let _ = self
+            .0
+            .compare_exchange(0, micros, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// Returns the capture time of audio `offset` after the first sample, if the anchor is set.
+    pub fn at(&self, offset: Duration) -> Option<SystemTime> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(UNIX_EPOCH + Duration::from_micros(micros) + offset),
+        }
+    }
+}
+
+/// Formats `time` in RFC 3339 in UTC with millisecond precision, e.g. `2021-04-01T12:30:00.250Z`.
+pub fn format(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since.subsec_millis()
+    )
+}
+
+/// Parses an RFC 3339 timestamp, e.g. `2021-04-01T14:30:00+02:00`.
+pub fn parse(s: &str) -> Result<SystemTime, String> {
+    let err = || format!("failed to parse `{}` as RFC 3339 timestamp", s);
+    let number = |range: std::ops::Range<usize>| -> Result<u64, String> {
+        let digits = s.get(range).ok_or_else(err)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        digits.parse().map_err(|_| err())
+    };
+    let bytes = s.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return Err(err());
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return Err(err());
+    }
+    // Leap seconds are folded into the following second.
+    if second > 60 {
+        return Err(err());
+    }
+
+    let mut rest = &s[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return Err(err());
+        }
+        let digits = &fraction[..len.min(9)];
+        nanos = digits.parse::<u32>().map_err(|_| err())? * 10u32.pow(9 - digits.len() as u32);
+        rest = &fraction[len..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let hours = rest.get(1..3).ok_or_else(err)?;
+            let minutes = rest.get(4..6).ok_or_else(err)?;
+            let hours = hours.parse::<i64>().map_err(|_| err())?;
+            let minutes = minutes.parse::<i64>().map_err(|_| err())?;
+            let offset = hours * 3600 + minutes * 60;
+            match rest.as_bytes()[0] {
+                b'+' => offset,
+                b'-' => -offset,
+                _ => return Err(err()),
+            }
+        }
+        _ => return Err(err()),
+    };
+
+    let days = days_from_civil(year as i64, month as u32, day as u32);
+    let secs = days * 86400 + (hour * 3600 + minute * 60 + second) as i64 - offset;
+    if secs < 0 {
+        return Err(format!("timestamp `{}` is before the Unix epoch", s));
+    }
+    Ok(UNIX_EPOCH + Duration::new(secs as u64, nanos))
+}
+
+/// Calendar date and time of day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Day of the year starting at 1.
+    pub day_of_year: u32,
+    /// Day of the week starting at 0 for Monday.
+    pub weekday: u32,
+}
+
+/// Returns `time` in the local time zone.
+#[cfg(unix)]
+pub fn local(time: SystemTime) -> DateTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs()) as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return utc(time);
+    }
+    DateTime {
+        year: tm.tm_year as i64 + 1900,
+        month: tm.tm_mon as u32 + 1,
+        day: tm.tm_mday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+        day_of_year: tm.tm_yday as u32 + 1,
+        weekday: (tm.tm_wday as u32 + 6) % 7,
+    }
+}
+
+/// Returns `time` in UTC, since the local time zone is not known on this platform.
+#[cfg(not(unix))]
+pub fn local(time: SystemTime) -> DateTime {
+    utc(time)
+}
+
+/// Returns `time` in UTC.
+pub fn utc(time: SystemTime) -> DateTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = (secs / 86400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    DateTime {
+        year,
+        month,
+        day,
+        hour: (secs / 3600 % 24) as u32,
+        minute: (secs / 60 % 60) as u32,
+        second: (secs % 60) as u32,
+        day_of_year: (days - days_from_civil(year, 1, 1)) as u32 + 1,
+        // The Unix epoch was a Thursday.
+        weekday: (days + 3).rem_euclid(7) as u32,
+    }
+}
+
+/// Returns the number of days since the Unix epoch of a date in the proleptic Gregorian calendar.
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the date in the proleptic Gregorian calendar of a number of days since the Unix epoch.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc3339() {
+        let time = parse("2021-04-01T14:30:00.25+02:00").unwrap();
+        assert_eq!(format(time), "2021-04-01T12:30:00.250Z");
+        assert_eq!(
+            format(parse("2000-02-29t00:00:00z").unwrap()),
+            "2000-02-29T00:00:00.000Z"
+        );
+        assert_eq!(format(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        for invalid in [
+            "2021-04-01",
+            "2021-13-01T00:00:00Z",
+            "2021-04-01T00:00:00",
+            "2021-04-01T00:00:00.Z",
+            "2021-04-01T00:00:00+0200",
+        ] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            parse("1970-01-01T00:00:00+01:00").unwrap_err(),
+            "timestamp `1970-01-01T00:00:00+01:00` is before the Unix epoch"
+        );
+    }
+
+    #[test]
+    fn anchor() {
+        let at = parse("2021-04-01T12:30:00Z").unwrap();
+        let anchor = Anchor::fixed(at);
+        // The anchor is only set once.
+        anchor.set(UNIX_EPOCH);
+        assert_eq!(
+            anchor.at(Duration::from_millis(250)),
+            Some(at + Duration::from_millis(250))
+        );
+        assert_eq!(Anchor::default().at(Duration::ZERO), None);
+    }
+
+    #[test]
+    fn weekday() {
+        assert_eq!(utc(UNIX_EPOCH).weekday, 3);
+        assert_eq!(utc(parse("2021-04-05T00:00:00Z").unwrap()).weekday, 0);
+        assert_eq!(utc(parse("2021-04-04T23:59:59Z").unwrap()).weekday, 6);
+    }
+}
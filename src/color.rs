@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+const RESET: &str = "\x1b[0m";
+/// 256-color foregrounds of confident, uncertain and unlikely words.
+const GREEN: &str = "\x1b[38;5;34m";
+const YELLOW: &str = "\x1b[38;5;178m";
+const RED: &str = "\x1b[38;5;160m";
+
+/// Confidences of words, below which they are colored yellow and red.
+#[derive(Clone, Copy)]
+pub struct Thresholds {
+    pub yellow: f64,
+    pub red: f64,
+}
+
+impl FromStr for Thresholds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let err = || {
+            format!(
+                "failed to parse `{}` into thresholds of the form YELLOW,RED, e.g. -1,-3",
+                s
+            )
+        };
+        let mut thresholds = s.splitn(2, ',');
+        let (yellow, red) = match (thresholds.next(), thresholds.next()) {
+            (Some(yellow), Some(red)) => (yellow.trim(), red.trim()),
+            _ => return Err(err()),
+        };
+        let yellow: f64 = yellow.parse().map_err(|_| err())?;
+        let red: f64 = red.parse().map_err(|_| err())?;
+        if red > yellow {
+            return Err(format!(
+                "red threshold {} must not be above yellow threshold {}",
+                red, yellow
+            ));
+        }
+        Ok(Self { yellow, red })
+    }
+}
+
+impl Thresholds {
+    fn color(&self, confidence: f64) -> &'static str {
+        if confidence < self.red {
+            RED
+        } else if confidence < self.yellow {
+            YELLOW
+        } else {
+            GREEN
+        }
+    }
+
+    /// Returns `text` with every word colored by its confidence, leaving the whitespace between
+    /// words untouched, so that the text without escape sequences stays the same.
+    /// The model only reports the confidence of the whole transcript, which is the sum of the
+    /// log probabilities of its characters, so every word is assigned the average per word.
+    pub fn paint(&self, text: &str, confidence: f64) -> String {
+        let words = text.split_whitespace().count();
+        if words == 0 {
+            return text.to_string();
+        }
+        let color = self.color(confidence / words as f64);
+        let mut out = String::with_capacity(text.len() + words * (color.len() + RESET.len()));
+        let mut word = false;
+        for c in text.chars() {
+            if c.is_whitespace() == word {
+                out.push_str(if word { RESET } else { color });
+                word = !word;
+            }
+            out.push(c);
+        }
+        if word {
+            out.push_str(RESET);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let thresholds = Thresholds::from_str(" -1 , -3 ").unwrap();
+        assert_eq!((thresholds.yellow, thresholds.red), (-1.0, -3.0));
+        assert!(Thresholds::from_str("-1").is_err());
+        assert!(Thresholds::from_str("-1,low").is_err());
+        assert!(Thresholds::from_str("-3,-1")
+            .err()
+            .unwrap()
+            .contains("must not be above"));
+    }
+
+    #[test]
+    fn paint() {
+        let thresholds = Thresholds {
+            yellow: -1.0,
+            red: -3.0,
+        };
+        // The confidence is averaged over the words.
+        assert_eq!(
+            thresholds.paint(" hello  world", -4.0),
+            format!(" {}hello{}  {}world{}", YELLOW, RESET, YELLOW, RESET)
+        );
+        assert_eq!(thresholds.paint("hi", 0.0), format!("{}hi{}", GREEN, RESET));
+        assert_eq!(
+            thresholds.paint("hi ", -3.5),
+            format!("{}hi{} ", RED, RESET)
+        );
+        assert_eq!(thresholds.paint("  ", -10.0), "  ");
+    }
+}
@@ -0,0 +1,284 @@
+use crate::exec::Spawner;
+use crate::output::{Event, Sink};
+use crate::reload::Reloadable;
+use crate::segmenter::Segment;
+use regex::Regex;
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info};
+
+struct Rule {
+    /// Line of the rules file the rule is defined on.
+    line: usize,
+    pattern: Regex,
+    spawner: Spawner,
+    /// When the command of the rule was last executed.
+    executed: Option<Instant>,
+}
+
+/// Rules executing shell commands for transcripts matching their patterns.
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+    /// Reads rules from `path`, one `pattern => command` pair per line, whose commands are
+    /// executed like --exec, killed after `timeout`, with at most `limit` running at once.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn load(path: &Path, timeout: Duration, limit: usize) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (pattern, command) = match trimmed.find(" => ") {
+                Some(at) => (&trimmed[..at], trimmed[at + 4..].trim()),
+                None => {
+                    errors.push(format!(
+                        "{}:{}: expected `pattern => command`",
+                        path.display(),
+                        i + 1
+                    ));
+                    continue;
+                }
+            };
+            if command.is_empty() {
+                errors.push(format!("{}:{}: command is empty", path.display(), i + 1));
+                continue;
+            }
+            match Regex::new(pattern) {
+                Ok(pattern) => rules.push(Rule {
+                    line: i + 1,
+                    pattern,
+                    spawner: Spawner::new(command.to_string(), timeout, limit),
+                    executed: None,
+                }),
+                Err(err) => errors.push(format!("{}:{}: {}", path.display(), i + 1, err)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        Ok(Self(rules))
+    }
+}
+
+/// Executes the command of the first rule matching every final transcript, unless it was
+/// executed within the cooldown, before forwarding events to `sink`.
+/// Executed commands are output as marks. The transcript is passed on stdin and in
+/// `TRANSCRIPT`, the line of the rule in `COMMAND_RULE` and the capture groups in
+/// `COMMAND_GROUP_1` and so on, or `COMMAND_GROUP_<NAME>` for named groups.
+pub struct Commands<S> {
+    rules: Reloadable<Rules>,
+    cooldown: Duration,
+    /// Whether transcripts are only matched against the rules and not forwarded.
+    only: bool,
+    sink: S,
+}
+
+impl<S> Commands<S> {
+    pub fn new(rules: Reloadable<Rules>, cooldown: Duration, only: bool, sink: S) -> Self {
+        Self {
+            rules,
+            cooldown,
+            only,
+            sink,
+        }
+    }
+
+    /// Executes the command of the first rule matching `text`, returning it, if executed.
+    fn execute(&mut self, text: &str) -> Option<String> {
+        let cooldown = self.cooldown;
+        let mut rules = self.rules.lock();
+        let (rule, captures) = rules.0.iter_mut().find_map(|rule| {
+            let captures = rule.pattern.captures(text)?;
+            Some((rule, captures))
+        })?;
+        if let Some(executed) = rule.executed {
+            if executed.elapsed() < cooldown {
+                debug!(rule = rule.line, "Skipping command rule within cooldown");
+                return None;
+            }
+        }
+        let mut env = vec![
+            ("TRANSCRIPT".to_string(), text.to_string()),
+            ("COMMAND_RULE".to_string(), rule.line.to_string()),
+        ];
+        for (i, name) in rule.pattern.capture_names().enumerate().skip(1) {
+            let group = match captures.get(i) {
+                Some(group) => group.as_str().to_string(),
+                None => continue,
+            };
+            let key = match name {
+                Some(name) => format!("COMMAND_GROUP_{}", name.to_uppercase()),
+                None => format!("COMMAND_GROUP_{}", i),
+            };
+            env.push((key, group));
+        }
+        let env: Vec<(&str, String)> = env
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        info!(
+            rule = rule.line,
+            pattern = rule.pattern.as_str(),
+            transcript = text,
+            "Transcript matched command rule"
+        );
+        rule.spawner.spawn(text, &env);
+        rule.executed = Some(Instant::now());
+        Some(rule.spawner.command().to_string())
+    }
+}
+
+impl<S: Sink> Sink for Commands<S> {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::Final(utterance) => {
+                let command = self.execute(&utterance.text);
+                if !self.only {
+                    self.sink.send(event);
+                }
+                if let Some(command) = command {
+                    self.sink
+                        .send(&Event::Mark(utterance.end, format!("command: {}", command)));
+                }
+            }
+            Event::Partial(_) | Event::StablePartial(_) if self.only => {}
+            event => self.sink.send(event),
+        }
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        self.sink.samples(samples)
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        self.sink.segment(segment)
+    }
+
+    fn finish(&mut self) {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::thread;
+
+    /// Describes the events sent.
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn send(&mut self, event: &Event) {
+            self.0.push(match event {
+                Event::Final(utterance) => format!("final: {}", utterance.text),
+                Event::Mark(_, label) => format!("mark: {}", label),
+                _ => "other".to_string(),
+            })
+        }
+    }
+
+    /// Loads rules from `contents`.
+    fn load(contents: &str) -> Result<Rules, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules");
+        fs::write(&path, contents).unwrap();
+        Rules::load(&path, Duration::from_secs(5), 4)
+    }
+
+    /// Reads the file at `path` once it was written.
+    fn read(path: &Path) -> String {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Ok(contents) = fs::read_to_string(path) {
+                if contents.ends_with('\n') {
+                    return contents;
+                }
+            }
+            assert!(
+                Instant::now() < deadline,
+                "command did not write {}",
+                path.display()
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn load_errors() {
+        let rules = load("# comment\n\n^lights on$ => true\n").unwrap();
+        assert_eq!(rules.0.len(), 1);
+        assert_eq!(rules.0[0].line, 3);
+
+        // All errors are reported at once.
+        let err = load("lights on\n^on$ => true\n(unclosed => true\n")
+            .err()
+            .unwrap();
+        let (first, rest) = err.split_once('\n').unwrap();
+        assert!(
+            first.ends_with(":1: expected `pattern => command`"),
+            "{}",
+            err
+        );
+        assert!(rest.contains(":3: regex parse error"), "{}", err);
+    }
+
+    #[test]
+    fn env_and_marks() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let command = format!(
+            r#"echo "$COMMAND_RULE|$TRANSCRIPT|$COMMAND_GROUP_1|$COMMAND_GROUP_ROOM" > {}"#,
+            out.display()
+        );
+        let rules = load(&format!(
+            "^never$ => false\n^turn (on|off) the (?P<room>\\w+) lights$ => {}\n",
+            command
+        ))
+        .unwrap();
+        let mut commands = Commands::new(
+            Reloadable::new(rules),
+            Duration::from_secs(60),
+            false,
+            Events::default(),
+        );
+        commands.send(&Event::Final(Utterance::test("turn on the kitchen lights")));
+        assert_eq!(read(&out), "2|turn on the kitchen lights|on|kitchen\n");
+        // Rules within the cooldown are not executed again.
+        commands.send(&Event::Final(Utterance::test("turn off the hall lights")));
+        commands.send(&Event::Final(Utterance::test("hello")));
+        assert_eq!(
+            commands.sink.0,
+            [
+                "final: turn on the kitchen lights".to_string(),
+                format!("mark: command: {}", command),
+                "final: turn off the hall lights".to_string(),
+                "final: hello".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn only() {
+        let rules = load("^stop$ => true\n").unwrap();
+        let mut commands = Commands::new(
+            Reloadable::new(rules),
+            Duration::default(),
+            true,
+            Events::default(),
+        );
+        commands.send(&Event::Partial(Utterance::test("sto")));
+        commands.send(&Event::Final(Utterance::test("stop")));
+        commands.send(&Event::Final(Utterance::test("go")));
+        assert_eq!(commands.sink.0, ["mark: command: true"]);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::output::{Event, Sink};
+use std::time::Duration;
+
+/// Transcript of a segment decoded with the comparison configuration as well.
+#[derive(Clone)]
+pub struct Comparison {
+    pub text: String,
+    /// Confidence of the comparison model in the transcript.
+    pub confidence: f64,
+    /// Time taken to decode the segment with the comparison configuration.
+    pub elapsed: Duration,
+    /// Time taken to decode the segment with the primary configuration.
+    pub primary_elapsed: Duration,
+    /// Whether the transcripts differ, as decoded before post-processing.
+    pub differs: bool,
+}
+
+/// Counts how often the transcripts of compared utterances differ and prints a summary once the
+/// pipeline is done.
+#[derive(Default)]
+pub struct Summary {
+    compared: usize,
+    differed: usize,
+    /// Sum of the decoding times of the comparison configuration less the primary one.
+    delta_micros: i128,
+}
+
+impl Sink for Summary {
+    fn send(&mut self, event: &Event) {
+        let comparison = match event {
+            Event::Final(utterance) | Event::Dropped(utterance) => &utterance.comparison,
+            _ => return,
+        };
+        if let Some(comparison) = comparison {
+            self.compared += 1;
+            if comparison.differs {
+                self.differed += 1;
+            }
+            self.delta_micros += comparison.elapsed.as_micros() as i128
+                - comparison.primary_elapsed.as_micros() as i128;
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.compared == 0 {
+            return eprintln!("No utterances to compare");
+        }
+        eprintln!(
+            "Transcripts of {} of {} utterances differed ({:.1}%), the comparison decoded {:+.1}ms \
+             longer on average",
+            self.differed,
+            self.compared,
+            self.differed as f64 * 100.0 / self.compared as f64,
+            self.delta_micros as f64 / self.compared as f64 / 1000.0
+        );
+    }
+}
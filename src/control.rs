@@ -0,0 +1,167 @@
+use crate::clock;
+use crate::heartbeat::Heartbeat;
+use crate::metrics;
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead},
+    sync::atomic::Ordering,
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Command changing the behavior of a running pipeline.
+#[cfg_attr(not(feature = "dbus"), allow(dead_code))]
+#[derive(Clone)]
+pub enum Control {
+    /// Transcribe buffered speech and discard audio until resumed.
+    Pause,
+    /// Resume processing audio after a pause.
+    Resume,
+    /// Transcribe buffered speech immediately, without waiting for silence.
+    Flush,
+    /// Transcribe buffered speech and stop the pipeline.
+    Stop,
+    /// Output an annotation with the label at the current offset of the stream.
+    Mark(String),
+    /// Output the heartbeat as an event.
+    Heartbeat(Heartbeat),
+}
+
+/// State of the process reported by the status command of the control socket and the admin API.
+#[cfg(any(unix, feature = "metrics"))]
+#[derive(Clone)]
+pub struct Status {
+    started: Instant,
+    /// Name of the input device, if capturing from one.
+    device: Option<String>,
+}
+
+#[cfg(any(unix, feature = "metrics"))]
+impl Status {
+    pub fn new(device: Option<String>) -> Self {
+        Self {
+            started: Instant::now(),
+            device,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let last_transcript = match metrics::LAST_TRANSCRIPT_MILLIS.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(clock::format(UNIX_EPOCH + Duration::from_millis(millis))),
+        };
+        json!({
+            "uptime_ms": self.started.elapsed().as_millis() as u64,
+            "paused": metrics::PAUSED.load(Ordering::Relaxed),
+            "utterances": metrics::UTTERANCES.load(Ordering::Relaxed),
+            "last_transcript_at": last_transcript,
+            "queue_depth": metrics::DECODE_QUEUE_DEPTH.load(Ordering::Relaxed),
+            "device": self.device,
+            "dropped_samples": metrics::DROPPED_SAMPLES.load(Ordering::Relaxed),
+            "fvad_mode": metrics::fvad_mode(),
+            "pool_size": metrics::POOL_SIZE.load(Ordering::Relaxed),
+            "pool_in_use": metrics::POOL_IN_USE.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Executes a command of the control socket or the admin API, sending its control to
+/// `controls`, and returns the JSON reply: pause, resume, flush or status.
+#[cfg(any(unix, feature = "metrics"))]
+pub fn execute(command: &str, controls: &Sender<Control>, status: &Status) -> Value {
+    let control = match command {
+        "pause" => Control::Pause,
+        "resume" => Control::Resume,
+        "flush" => Control::Flush,
+        "status" => return status.to_json(),
+        "reload" => return json!({ "error": "reloading is not supported yet" }),
+        _ => {
+            return json!({
+                "error": format!(
+                    "unknown command `{}`, expected pause, resume, flush, status or reload",
+                    command
+                )
+            })
+        }
+    };
+    match controls.send(control) {
+        Ok(()) => json!({ "ok": true }),
+        Err(_) => json!({ "error": "pipeline stopped" }),
+    }
+}
+
+/// Reads a command per line from stdin on a separate thread, sending controls to `controls`:
+/// pause, resume, flush, quit or `mark <label>`.
+/// Unknown commands are reported on stderr and ignored. Stdin reaching its end only stops the
+/// reader.
+pub fn stdin(controls: Sender<Control>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return warn!(error = %err, "Failed to read control command from stdin"),
+            };
+            let control = match parse(line.trim()) {
+                Ok(Some(control)) => control,
+                Ok(None) => continue,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    continue;
+                }
+            };
+            if controls.send(control).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Parses a command read from stdin, returning None for empty lines.
+fn parse(command: &str) -> Result<Option<Control>, String> {
+    let (name, argument) = match command.find(char::is_whitespace) {
+        Some(i) => (&command[..i], command[i..].trim()),
+        None => (command, ""),
+    };
+    Ok(Some(match (name, argument) {
+        ("", _) => return Ok(None),
+        ("pause", "") => Control::Pause,
+        ("resume", "") => Control::Resume,
+        ("flush", "") => Control::Flush,
+        ("quit", "") => Control::Stop,
+        ("mark", "") => return Err("mark requires a label, e.g. `mark chapter 2`".into()),
+        ("mark", label) => Control::Mark(label.to_string()),
+        _ => {
+            return Err(format!(
+                "unknown command `{}`, expected pause, resume, flush, quit or mark <label>",
+                command
+            ))
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert!(matches!(parse("pause"), Ok(Some(Control::Pause))));
+        assert!(matches!(parse("resume"), Ok(Some(Control::Resume))));
+        assert!(matches!(parse("flush"), Ok(Some(Control::Flush))));
+        assert!(matches!(parse("quit"), Ok(Some(Control::Stop))));
+        assert!(matches!(parse(""), Ok(None)));
+        match parse("mark \t chapter 2") {
+            Ok(Some(Control::Mark(label))) => assert_eq!(label, "chapter 2"),
+            _ => panic!("mark was not parsed"),
+        }
+        assert!(parse("mark").err().unwrap().contains("requires a label"));
+        assert!(parse("pause now").is_err());
+        assert!(parse("stop")
+            .err()
+            .unwrap()
+            .contains("unknown command `stop`"));
+    }
+}
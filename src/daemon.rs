@@ -0,0 +1,125 @@
+use crate::control::{self, Control, Status};
+use serde_json::Value;
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::fs::FileTypeExt,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    thread,
+};
+use tracing::{debug, warn};
+
+/// Unix domain socket accepting a command per line, replying with a line of JSON to each.
+/// The socket file is removed when the daemon is dropped.
+pub struct Daemon {
+    path: PathBuf,
+}
+
+impl Daemon {
+    /// Listens on `path`, replacing a stale socket if present, sending controls to `controls`.
+    /// Every client is served on its own thread, so that clients never hold up the pipeline.
+    pub fn listen(path: PathBuf, controls: Sender<Control>, status: Status) -> io::Result<Self> {
+        match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => fs::remove_file(&path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let listener = UnixListener::bind(&path)?;
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let controls = controls.clone();
+                        let status = status.clone();
+                        thread::spawn(move || {
+                            if let Err(err) = serve(stream, &controls, &status) {
+                                debug!(error = %err, "Control client disconnected");
+                            }
+                        });
+                    }
+                    Err(err) => warn!(error = %err, "Failed to accept control client"),
+                }
+            }
+        });
+        Ok(Self { path })
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!(path = %self.path.display(), error = %err, "Failed to remove control socket");
+        }
+    }
+}
+
+/// Replies to every command sent by the client connected on `stream` until it disconnects.
+fn serve(stream: UnixStream, controls: &Sender<Control>, status: &Status) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let reply = control::execute(line?.trim(), controls, status);
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// Sends `command` to the daemon listening on `path`, returning its reply.
+pub fn send(path: &Path, command: &str) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{}", command)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::mpsc;
+
+    #[test]
+    fn commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let (tx, rx) = mpsc::channel();
+        let daemon = Daemon::listen(path.clone(), tx, Status::new(Some("mic".into()))).unwrap();
+
+        assert_eq!(send(&path, "pause").unwrap(), json!({ "ok": true }));
+        assert!(matches!(rx.recv().unwrap(), Control::Pause));
+        let status = send(&path, "status").unwrap();
+        assert!(status["uptime_ms"].is_u64(), "{}", status);
+        assert!(status["paused"].is_boolean(), "{}", status);
+        assert_eq!(status["device"], "mic");
+        assert!(send(&path, "bogus").unwrap()["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("unknown command `bogus`"));
+
+        drop(daemon);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn not_a_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        fs::write(&path, "").unwrap();
+        let (tx, _rx) = mpsc::channel();
+        assert_eq!(
+            Daemon::listen(path, tx, Status::new(None))
+                .err()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::AlreadyExists
+        );
+    }
+}
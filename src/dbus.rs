@@ -0,0 +1,107 @@
+use crate::control::Control;
+use crate::output::{Event, Sink};
+use std::{
+    convert::TryInto,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+use tracing::warn;
+use zbus::{dbus_interface, fdo, Connection, ObjectServer};
+
+const NAME: &str = "dev.rvolosatovs.speech2text";
+const PATH: &str = "/dev/rvolosatovs/speech2text";
+const INTERFACE: &str = "dev.rvolosatovs.speech2text";
+
+struct Controls(Sender<Control>);
+
+#[dbus_interface(name = "dev.rvolosatovs.speech2text")]
+impl Controls {
+    /// Transcribe buffered speech and stop listening until resumed.
+    fn pause(&self) {
+        let _ = self.0.send(Control::Pause);
+    }
+
+    /// Resume listening after a pause.
+    fn resume(&self) {
+        let _ = self.0.send(Control::Resume);
+    }
+
+    /// Transcribe buffered speech immediately.
+    fn flush(&self) {
+        let _ = self.0.send(Control::Flush);
+    }
+}
+
+/// Emits pipeline events as signals on the session bus and serves the control methods.
+pub struct Bus {
+    connection: Connection,
+}
+
+impl Bus {
+    pub fn connect(controls: Sender<Control>) -> zbus::Result<Self> {
+        let connection = Connection::new_session()?;
+        fdo::DBusProxy::new(&connection)?
+            .request_name(NAME, fdo::RequestNameFlags::ReplaceExisting.into())?;
+
+        // The object server is not `Send`, so it is set up on the thread serving it, which
+        // reports back whether the controls could be served.
+        let server_connection = connection.clone();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut server = ObjectServer::new(&server_connection);
+            let served = PATH
+                .try_into()
+                .map_err(zbus::Error::from)
+                .and_then(|path| server.at(&path, Controls(controls)));
+            if let Err(err) = served {
+                let _ = ready_tx.send(Err(err));
+                return;
+            }
+            let _ = ready_tx.send(Ok(()));
+            loop {
+                if let Err(err) = server.try_handle_next() {
+                    warn!(error = %err, "Failed to handle D-Bus message");
+                }
+            }
+        });
+        ready_rx
+            .recv()
+            .expect("D-Bus object server exited before serving the controls")?;
+        Ok(Self { connection })
+    }
+
+    fn emit<B>(&self, signal: &str, body: &B)
+    where
+        B: serde::Serialize + zvariant::Type,
+    {
+        if let Err(err) = self
+            .connection
+            .emit_signal(None, PATH, INTERFACE, signal, body)
+        {
+            warn!(error = %err, signal, "Failed to emit D-Bus signal");
+        }
+    }
+}
+
+impl Sink for Bus {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::Ready => self.emit("Ready", &()),
+            Event::SpeechStarted(at) => self.emit("SpeechStarted", &(at.as_millis() as u64)),
+            Event::SpeechStopped(at) => self.emit("SpeechStopped", &(at.as_millis() as u64)),
+            Event::Final(utterance) => self.emit(
+                "TranscriptReady",
+                &(
+                    utterance.text.as_str(),
+                    utterance.start.as_millis() as u64,
+                    utterance.end.as_millis() as u64,
+                ),
+            ),
+            Event::Partial(_)
+            | Event::StablePartial(_)
+            | Event::Dropped(_)
+            | Event::Mark(..)
+            | Event::Heartbeat(_) => {}
+        }
+    }
+}
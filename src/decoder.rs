@@ -0,0 +1,843 @@
+use crate::compare::Comparison;
+use crate::metrics;
+use crate::model::Model;
+use crate::normalize::Loudness;
+use crate::output::{Event, Fingerprint, Offsets, Utterance};
+use crate::pipeline::Config;
+use crate::priority;
+use crate::queue::Queue;
+use crate::segmenter::Segment;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::mpsc::{self, Receiver, Sender},
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, debug_span, error, warn, Span};
+
+/// Maximum number of words repeated at the start of a transcript continuing the previous one,
+/// which are removed.
+const MAX_OVERLAP_WORDS: usize = 10;
+
+/// What to do when a job is submitted while the decode queue is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    /// Wait for the worker to catch up.
+    Block,
+    /// Drop the oldest queued job.
+    DropOldest,
+    /// Drop the submitted job.
+    DropNewest,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "block" => Ok(Self::Block),
+            "drop-oldest" | "drop_oldest" => Ok(Self::DropOldest),
+            "drop-newest" | "drop_newest" => Ok(Self::DropNewest),
+            _ => Err(format!(
+                "failed to parse `{}` into overflow policy of block, drop-oldest or drop-newest",
+                s
+            )),
+        }
+    }
+}
+
+impl OverflowPolicy {
+    /// Appends `item` to `queue`, returning the item dropped if it is full.
+    fn push<T>(self, queue: &Queue<T>, item: T) -> Option<T> {
+        match self {
+            Self::Block => {
+                queue.push_wait(item);
+                None
+            }
+            Self::DropOldest => queue.push(item),
+            Self::DropNewest => queue.try_push(item).err(),
+        }
+    }
+}
+
+pub enum Job {
+    /// Decode speech still in progress.
+    Partial { start: u64, samples: Vec<i16> },
+    /// Decode a complete segment within the span of its utterance.
+    Final {
+        segment: Segment,
+        /// Path of the recording of the segment, if it was saved.
+        recording: Option<PathBuf>,
+        span: Span,
+    },
+}
+
+/// Configuration, which final utterances less confident than `below` are decoded again with,
+/// keeping the more confident transcript.
+#[derive(Clone)]
+pub struct Rescore {
+    pub below: f64,
+    /// Beam width of the second decode, if it differs.
+    pub beam_width: Option<u16>,
+    /// Scorer, which is disabled for the second decode and enabled again afterwards, if set.
+    /// DeepSpeech frees a disabled scorer, so that enabling it again loads it from this file,
+    /// which costs about as much as loading it at startup on every such decode.
+    pub without_scorer: Option<PathBuf>,
+}
+
+/// Remaining events of a decoder once it finished.
+pub struct Drained {
+    /// Events in submission order along with the span of their utterance.
+    pub events: Vec<(Event, Span)>,
+    /// Number of segments, which were still queued or being decoded once the timeout elapsed.
+    /// Partial transcripts still queued are not counted, while those being decoded are.
+    pub abandoned: usize,
+}
+
+/// Decodes jobs on worker threads, one per model, yielding the events in submission order.
+pub struct Decoder {
+    queue: Arc<Queue<(u64, Job)>>,
+    policy: OverflowPolicy,
+    /// Sequence number of the next submitted job.
+    submitted: Cell<u64>,
+    /// Number of segments dropped because the queue was full.
+    dropped: Cell<usize>,
+    results_tx: Sender<(u64, Option<(Event, Span)>)>,
+    results: Receiver<(u64, Option<(Event, Span)>)>,
+    /// Results received ahead of a job submitted earlier, keyed by sequence number.
+    pending: BTreeMap<u64, Option<(Event, Span)>>,
+    /// Sequence number of the next result to yield.
+    next: u64,
+    /// Sequence numbers of segments split from the previous segment, which they overlap.
+    continued: RefCell<BTreeSet<u64>>,
+    /// Last final transcript yielded.
+    previous: Option<String>,
+    /// Number of jobs in a row, which failed to decode.
+    failures: Arc<AtomicUsize>,
+    /// Number of failures in a row, after which the model is considered wedged, 0 for none.
+    max_failures: usize,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Decoder {
+    pub fn spawn(models: Vec<Arc<Mutex<Model>>>, config: Config) -> Self {
+        let decoders = models
+            .into_iter()
+            .map(|model| {
+                let config = config.clone();
+                move |job| decode_job(&model, &config, job)
+            })
+            .collect();
+        Self::with_decoders(
+            decoders,
+            config.decode_queue,
+            config.overflow_policy,
+            config.decode_nice,
+            config.max_decode_failures,
+        )
+    }
+
+    /// Spawns a worker thread per function of `decoders`, which decodes jobs with it, returning
+    /// `None` if decoding failed.
+    fn with_decoders<F>(
+        decoders: Vec<F>,
+        capacity: usize,
+        policy: OverflowPolicy,
+        nice: Option<i32>,
+        max_failures: usize,
+    ) -> Self
+    where
+        F: FnMut(Job) -> Option<(Event, Span)> + Send + 'static,
+    {
+        let queue = Arc::new(Queue::new(capacity));
+        let (results_tx, results) = mpsc::channel();
+        let failures = Arc::new(AtomicUsize::new(0));
+        let workers = decoders
+            .into_iter()
+            .map(|mut decode| {
+                let jobs = queue.clone();
+                let results_tx = results_tx.clone();
+                let failures = failures.clone();
+                thread::spawn(move || {
+                    if let Some(nice) = nice {
+                        priority::nice(nice);
+                    }
+                    while let Some((seq, job)) = jobs.pop() {
+                        metrics::DECODE_QUEUE_DEPTH.store(jobs.len() as u64, Ordering::Relaxed);
+                        let result = decode(job);
+                        match result {
+                            Some(_) => failures.store(0, Ordering::Relaxed),
+                            None => {
+                                failures.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        // Failed jobs are marked as done, so that later results are not held
+                        // back waiting for them.
+                        if results_tx.send((seq, result)).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self {
+            queue,
+            policy,
+            submitted: Cell::new(0),
+            dropped: Cell::new(0),
+            results_tx,
+            results,
+            pending: BTreeMap::new(),
+            next: 0,
+            continued: RefCell::new(BTreeSet::new()),
+            previous: None,
+            failures,
+            max_failures,
+            workers,
+        }
+    }
+
+    pub fn submit(&self, job: Job) {
+        let seq = self.submitted.get();
+        self.submitted.set(seq + 1);
+        if let Job::Final { segment, .. } = &job {
+            if segment.overlap > 0 {
+                self.continued.borrow_mut().insert(seq);
+            }
+        }
+        let dropped = self.policy.push(&self.queue, (seq, job));
+        metrics::DECODE_QUEUE_DEPTH.store(self.queue.len() as u64, Ordering::Relaxed);
+        let (seq, job) = match dropped {
+            Some(dropped) => dropped,
+            None => return,
+        };
+        self.continued.borrow_mut().remove(&seq);
+        match job {
+            Job::Final { segment, .. } => {
+                self.dropped.set(self.dropped.get() + 1);
+                metrics::DROPPED_SEGMENTS.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    samples = segment.samples.len(),
+                    total = self.dropped.get(),
+                    "Decode queue is full, dropping segment"
+                )
+            }
+            Job::Partial { .. } => debug!("Decode queue is full, dropping partial"),
+        }
+        // Mark the job as done, so that later results are not held back waiting for it.
+        let _ = self.results_tx.send((seq, None));
+    }
+
+    /// Returns whether decoding failed as many times in a row as allowed, in which case the model
+    /// is probably wedged.
+    pub fn wedged(&self) -> bool {
+        self.max_failures > 0 && self.failures.load(Ordering::Relaxed) >= self.max_failures
+    }
+
+    /// Returns events decoded so far without blocking, along with the span of their utterance.
+    pub fn try_iter(&mut self) -> impl Iterator<Item = (Event, Span)> + '_ {
+        while let Ok((seq, event)) = self.results.try_recv() {
+            self.pending.insert(seq, event);
+        }
+        self.ready()
+    }
+
+    /// Waits for all submitted jobs to be decoded, for at most `timeout` if set, returning the
+    /// remaining events. Jobs not decoded in time are abandoned, while the workers decoding them
+    /// are left to finish on their own.
+    pub fn finish(mut self, timeout: Option<Duration>) -> Drained {
+        self.queue.close();
+        let mut abandoned = 0;
+        match timeout {
+            None => {
+                for worker in self.workers.drain(..) {
+                    if worker.join().is_err() {
+                        warn!("Decode worker panicked");
+                    }
+                }
+            }
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                // Results of jobs lost to a panicked worker never arrive, so that those jobs
+                // are abandoned once the timeout elapses as well.
+                while self.outstanding() > 0 {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    match self.results.recv_timeout(left) {
+                        Ok((seq, event)) => {
+                            self.pending.insert(seq, event);
+                        }
+                        Err(_) => break,
+                    }
+                }
+                let outstanding = self.outstanding();
+                if outstanding > 0 {
+                    let queued = self.queue.clear();
+                    let decoding = outstanding - queued.len();
+                    let queued = queued
+                        .iter()
+                        .filter(|(_, job)| matches!(job, Job::Final { .. }))
+                        .count();
+                    abandoned = queued + decoding;
+                    warn!(
+                        queued,
+                        decoding,
+                        ?timeout,
+                        "Segments were not decoded within the drain timeout, abandoning them"
+                    );
+                }
+            }
+        }
+        if self.dropped.get() > 0 {
+            warn!(
+                segments = self.dropped.get(),
+                "Dropped segments, which could not be decoded in time"
+            );
+        }
+        while let Ok((seq, event)) = self.results.try_recv() {
+            self.pending.insert(seq, event);
+        }
+        // Results of abandoned jobs never arrive, yield everything left.
+        let mut events = Vec::with_capacity(self.pending.len());
+        for (seq, event) in std::mem::take(&mut self.pending) {
+            if let Some(event) = event {
+                events.push(self.join(seq, event));
+            }
+        }
+        Drained { events, abandoned }
+    }
+
+    /// Returns the number of submitted jobs, whose results were not received yet.
+    fn outstanding(&self) -> usize {
+        (self.submitted.get() - self.next) as usize - self.pending.len()
+    }
+
+    /// Removes the results, which are next in submission order, from `pending`.
+    fn ready(&mut self) -> impl Iterator<Item = (Event, Span)> + '_ {
+        std::iter::from_fn(move || loop {
+            let seq = self.next;
+            let event = self.pending.remove(&seq)?;
+            self.next += 1;
+            match event {
+                Some(event) => return Some(self.join(seq, event)),
+                None => {
+                    self.continued.borrow_mut().remove(&seq);
+                }
+            }
+        })
+    }
+
+    /// Removes the words from the transcript of segment `seq`, which it repeats from the previous
+    /// transcript because of the overlap of the segments.
+    fn join(&mut self, seq: u64, (mut event, span): (Event, Span)) -> (Event, Span) {
+        if let Event::Final(utterance) = &mut event {
+            if self.continued.borrow_mut().remove(&seq) {
+                if let Some(previous) = &self.previous {
+                    utterance.text = dedup(previous, &utterance.text);
+                }
+            }
+            self.previous = Some(utterance.text.clone());
+        }
+        (event, span)
+    }
+}
+
+/// Returns `text` without the longest run of leading words, which `previous` ends with.
+fn dedup(previous: &str, text: &str) -> String {
+    let previous: Vec<&str> = previous.split_whitespace().collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let max = MAX_OVERLAP_WORDS.min(previous.len()).min(words.len());
+    let repeated = (1..=max)
+        .rev()
+        .find(|&n| previous[previous.len() - n..] == words[..n])
+        .unwrap_or(0);
+    words[repeated..].join(" ")
+}
+
+/// Decodes `job`, returning its event along with the span of its utterance, or `None`, if
+/// decoding failed, in which case the utterance is skipped.
+fn decode_job(model: &Mutex<Model>, config: &Config, job: Job) -> Option<(Event, Span)> {
+    match job {
+        Job::Partial { start, samples } => {
+            let (text, confidence) = match decode(model, &samples) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    warn!(error = %err, samples = samples.len(), "Failed to decode partial");
+                    return None;
+                }
+            };
+            let event = Event::Partial(Utterance {
+                seq: 0,
+                text,
+                speaker: config.speaker.clone(),
+                confidence,
+                start: config.duration(start),
+                end: config.duration(start + samples.len() as u64),
+                start_sample: start,
+                end_sample: start + samples.len() as u64,
+                offsets: None,
+                captured_at: config
+                    .captured_at(start)
+                    .zip(config.captured_at(start + samples.len() as u64)),
+                fingerprint: None,
+                loudness: None,
+                recording: None,
+                censored: false,
+                low_confidence: false,
+                paragraph: None,
+                comparison: None,
+                intent: None,
+                duplicate: false,
+                rescored: false,
+                speech: Duration::default(),
+            });
+            Some((event, Span::none()))
+        }
+        Job::Final {
+            segment,
+            recording,
+            span,
+        } => {
+            // The overlap was transcribed as part of the previous segment already.
+            let start_sample = segment.start + segment.overlap as u64;
+            let speech = segment.speech();
+            let start = config.duration(speech.start);
+            let end = config.duration(speech.end);
+            let decode_span = debug_span!(parent: &span, "decode", samples = segment.samples.len());
+            let _entered = decode_span.enter();
+            let decode_start = Instant::now();
+            let started_at = SystemTime::now();
+            let (mut text, mut confidence) = match decode(model, &segment.samples) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    metrics::DECODE_FAILURES.fetch_add(1, Ordering::Relaxed);
+                    error!(
+                        error = %err,
+                        duration = ?(end - start),
+                        recording = ?recording.as_ref().map(|path| path.display()),
+                        "Failed to decode segment, skipping it"
+                    );
+                    return None;
+                }
+            };
+            let mut rescored = false;
+            if let Some(rescore) = config.rescore.as_ref().filter(|r| confidence < r.below) {
+                match redecode(model, rescore, &segment.samples) {
+                    Ok((again, again_confidence)) => {
+                        rescored = true;
+                        metrics::RESCORED.fetch_add(1, Ordering::Relaxed);
+                        debug!(
+                            confidence,
+                            rescored_confidence = again_confidence,
+                            text = %again,
+                            "Decoded segment again"
+                        );
+                        if again_confidence > confidence {
+                            if again != text {
+                                metrics::RESCORE_CHANGED.fetch_add(1, Ordering::Relaxed);
+                            }
+                            text = again;
+                            confidence = again_confidence;
+                        }
+                    }
+                    Err(err) => warn!(error = %err, "Failed to decode segment again"),
+                }
+            }
+            let elapsed = decode_start.elapsed();
+            if let Some(latency) = &config.latency {
+                latency.decoded(segment.end(), started_at, elapsed);
+            }
+            debug!(
+                samples = segment.samples.len(),
+                duration = ?(end - start),
+                elapsed = ?elapsed,
+                confidence,
+                "Decoded segment"
+            );
+            let comparison = config.comparison.as_ref().and_then(|model| {
+                let compare_start = Instant::now();
+                match transcribe(
+                    &mut model.lock().expect("Failed to lock model"),
+                    &segment.samples,
+                ) {
+                    Ok((comparison, confidence)) => Some(Comparison {
+                        differs: comparison != text,
+                        text: comparison,
+                        confidence,
+                        elapsed: compare_start.elapsed(),
+                        primary_elapsed: elapsed,
+                    }),
+                    Err(err) => {
+                        warn!(error = %err, "Failed to decode segment with the comparison model");
+                        None
+                    }
+                }
+            });
+            metrics::UTTERANCES.fetch_add(1, Ordering::Relaxed);
+            let audio = config.duration(segment.samples.len() as u64);
+            if audio > Duration::default() {
+                metrics::set_real_time_factor(elapsed.as_secs_f64() / audio.as_secs_f64());
+            }
+            let event = Event::Final(Utterance {
+                seq: 0,
+                text,
+                speaker: config.speaker.clone(),
+                confidence,
+                start,
+                end,
+                start_sample,
+                end_sample: segment.end(),
+                offsets: Some(Offsets {
+                    buffer_start: segment.start,
+                    speech_start: speech.start,
+                    speech_end: speech.end,
+                    buffer_end: segment.end(),
+                }),
+                captured_at: config
+                    .captured_at(speech.start)
+                    .zip(config.captured_at(speech.end)),
+                fingerprint: if config.fingerprint {
+                    Some(Fingerprint::of(&segment.samples))
+                } else {
+                    None
+                },
+                loudness: Some(Loudness::of(&segment.samples)),
+                recording,
+                censored: false,
+                low_confidence: false,
+                paragraph: None,
+                comparison,
+                intent: None,
+                duplicate: false,
+                rescored,
+                speech: config.duration(segment.speech_samples as u64),
+            });
+            Some((event, span))
+        }
+    }
+}
+
+/// Returns the most likely transcript of `samples` and its confidence.
+fn decode(model: &Mutex<Model>, samples: &[i16]) -> Result<(String, f64), String> {
+    let mut model = model.lock().expect("Failed to lock model");
+    let decode_start = Instant::now();
+    let decoded = transcribe(&mut model, samples)?;
+    metrics::add_duration(&metrics::DECODE_MICROS, decode_start.elapsed());
+    Ok(decoded)
+}
+
+/// Configuration of a locked model, which was changed for decoding again and is restored once
+/// dropped, so that the model is left as it was however decoding ends.
+struct Rescoring<'a> {
+    model: MutexGuard<'a, Model>,
+    /// Beam width to restore, if it was changed.
+    beam_width: Option<u16>,
+    /// Scorer to enable again, if it was disabled.
+    scorer: Option<&'a Path>,
+}
+
+impl Drop for Rescoring<'_> {
+    fn drop(&mut self) {
+        if let Some(width) = self.beam_width {
+            if let Err(err) = self.model.set_model_beam_width(width) {
+                error!(width, error = %err, "Failed to restore beam width");
+            }
+        }
+        if let Some(scorer) = self.scorer {
+            if let Err(err) = self.model.enable_external_scorer(scorer) {
+                error!(path = %scorer.display(), error = %err, "Failed to enable scorer again");
+            }
+        }
+    }
+}
+
+/// Decodes `samples` again with the configuration of `rescore`, restoring the one of `model`
+/// afterwards.
+fn redecode(
+    model: &Mutex<Model>,
+    rescore: &Rescore,
+    samples: &[i16],
+) -> Result<(String, f64), String> {
+    let mut rescoring = Rescoring {
+        model: model.lock().expect("Failed to lock model"),
+        beam_width: None,
+        scorer: None,
+    };
+    if let Some(width) = rescore.beam_width {
+        let previous = rescoring.model.get_model_beam_width();
+        rescoring
+            .model
+            .set_model_beam_width(width)
+            .map_err(|err| format!("failed to set beam width: {}", err))?;
+        rescoring.beam_width = Some(previous);
+    }
+    if let Some(scorer) = &rescore.without_scorer {
+        rescoring
+            .model
+            .disable_external_scorer()
+            .map_err(|err| format!("failed to disable scorer: {}", err))?;
+        rescoring.scorer = Some(scorer);
+    }
+    let decode_start = Instant::now();
+    let decoded = transcribe(&mut rescoring.model, samples);
+    metrics::add_duration(&metrics::DECODE_MICROS, decode_start.elapsed());
+    decoded
+}
+
+/// Returns the most likely transcript of `samples` decoded with `model` and its confidence.
+pub fn transcribe(model: &mut Model, samples: &[i16]) -> Result<(String, f64), String> {
+    let metadata = model
+        .speech_to_text_with_metadata(samples, 1)
+        .map_err(|err| err.to_string())?;
+    Ok(match metadata.transcripts().first() {
+        Some(transcript) => (
+            transcript
+                .tokens()
+                .iter()
+                .map(|token| token.text())
+                .collect::<Result<_, _>>()
+                .expect("Failed to decode transcript as UTF-8"),
+            transcript.confidence(),
+        ),
+        None => (String::new(), 0.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const CAPACITY: usize = 4;
+    const ITEMS: usize = 20;
+
+    /// Time the slow consumer takes per item.
+    const DECODE: Duration = Duration::from_millis(20);
+
+    /// Submits `ITEMS` items at once to a queue of `CAPACITY` according to `policy`, while a slow
+    /// consumer pops one per `DECODE`, returning the items consumed and dropped.
+    fn overflow(policy: OverflowPolicy) -> (Vec<usize>, Vec<usize>) {
+        let queue = Arc::new(Queue::new(CAPACITY));
+        let jobs = queue.clone();
+        let consumer = thread::spawn(move || {
+            let mut consumed = Vec::new();
+            while let Some(item) = jobs.pop() {
+                thread::sleep(DECODE);
+                consumed.push(item);
+            }
+            consumed
+        });
+        let dropped = (0..ITEMS)
+            .filter_map(|item| policy.push(&queue, item))
+            .collect();
+        queue.close();
+        (consumer.join().unwrap(), dropped)
+    }
+
+    #[test]
+    fn parse_overflow_policy() {
+        for (s, expected) in [
+            ("block", Some(OverflowPolicy::Block)),
+            ("drop-oldest", Some(OverflowPolicy::DropOldest)),
+            ("drop_oldest", Some(OverflowPolicy::DropOldest)),
+            ("drop-newest", Some(OverflowPolicy::DropNewest)),
+            ("drop_newest", Some(OverflowPolicy::DropNewest)),
+            ("drop", None),
+            ("", None),
+        ] {
+            assert_eq!(s.parse::<OverflowPolicy>().ok(), expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn block() {
+        let (consumed, dropped) = overflow(OverflowPolicy::Block);
+        assert_eq!(consumed, (0..ITEMS).collect::<Vec<_>>());
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let (consumed, dropped) = overflow(OverflowPolicy::DropOldest);
+        assert!(!dropped.is_empty());
+        assert_eq!(consumed.len() + dropped.len(), ITEMS);
+        assert!(consumed.windows(2).all(|pair| pair[0] < pair[1]));
+        // The latest items are kept.
+        assert!(consumed.ends_with(&(ITEMS - CAPACITY..ITEMS).collect::<Vec<_>>()));
+    }
+
+    #[test]
+    fn drop_newest() {
+        let (consumed, dropped) = overflow(OverflowPolicy::DropNewest);
+        assert_eq!(consumed.len() + dropped.len(), ITEMS);
+        assert!(consumed.len() <= CAPACITY + 1);
+        // The earliest items are kept.
+        assert_eq!(consumed, (0..consumed.len()).collect::<Vec<_>>());
+        assert_eq!(dropped.last(), Some(&(ITEMS - 1)));
+    }
+
+    #[test]
+    fn dedup_overlap() {
+        assert_eq!(dedup("turn on the", "on the lights"), "lights");
+        assert_eq!(dedup("turn on the", "the lights"), "lights");
+        assert_eq!(dedup("turn on", "lights on"), "lights on");
+        assert_eq!(dedup("", "lights"), "lights");
+        assert_eq!(dedup("on the", "on the"), "");
+    }
+
+    /// Returns a decoder with a single worker, which fails to decode jobs without samples and
+    /// transcribes the others as their start offset.
+    fn failing(max_failures: usize) -> Decoder {
+        let decode = |job| match job {
+            Job::Partial { samples, .. } if samples.is_empty() => None,
+            Job::Partial { start, .. } => Some((
+                Event::Partial(Utterance::test(&start.to_string())),
+                Span::none(),
+            )),
+            Job::Final { .. } => unreachable!(),
+        };
+        Decoder::with_decoders(
+            vec![decode],
+            CAPACITY,
+            OverflowPolicy::Block,
+            None,
+            max_failures,
+        )
+    }
+
+    /// Submits a job per element of `fails`, which fails to decode if set, and waits for them to
+    /// be decoded, returning the transcripts.
+    fn decode(decoder: &mut Decoder, fails: &[bool]) -> Vec<String> {
+        let start = decoder.submitted.get();
+        for (i, &fails) in fails.iter().enumerate() {
+            decoder.submit(Job::Partial {
+                start: start + i as u64,
+                samples: if fails { Vec::new() } else { vec![0] },
+            });
+        }
+        let mut transcripts = Vec::new();
+        while decoder.next < decoder.submitted.get() {
+            transcripts.extend(decoder.try_iter().map(|(event, _)| match event {
+                Event::Partial(utterance) => utterance.text,
+                _ => unreachable!(),
+            }));
+            thread::sleep(Duration::from_millis(1));
+        }
+        transcripts
+    }
+
+    #[test]
+    fn failures_skipped() {
+        let mut decoder = failing(3);
+        assert_eq!(
+            decode(&mut decoder, &[false, false, true, false, true, false]),
+            ["0", "1", "3", "5"]
+        );
+        assert!(!decoder.wedged());
+        assert_eq!(decoder.finish(None).events.len(), 0);
+    }
+
+    #[test]
+    fn failures_in_a_row_wedge() {
+        let mut decoder = failing(3);
+        assert_eq!(decode(&mut decoder, &[false, true, true]), ["0"]);
+        assert!(!decoder.wedged());
+        assert!(decode(&mut decoder, &[true]).is_empty());
+        assert!(decoder.wedged());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let mut decoder = failing(3);
+        assert_eq!(
+            decode(&mut decoder, &[true, true, false, true, true]),
+            ["2"]
+        );
+        assert!(!decoder.wedged());
+    }
+
+    #[test]
+    fn unlimited_failures() {
+        let mut decoder = failing(0);
+        assert!(decode(&mut decoder, &[true; 10]).is_empty());
+        assert!(!decoder.wedged());
+        assert_eq!(decode(&mut decoder, &[false]), ["10"]);
+    }
+
+    /// Returns a decoder with a single worker, which takes `delay` to decode each segment and
+    /// transcribes it as its start offset.
+    fn slow(delay: Duration) -> Decoder {
+        let decode = move |job| match job {
+            Job::Final { segment, .. } => {
+                thread::sleep(delay);
+                Some((
+                    Event::Final(Utterance::test(&segment.start.to_string())),
+                    Span::none(),
+                ))
+            }
+            Job::Partial { .. } => unreachable!(),
+        };
+        Decoder::with_decoders(vec![decode], CAPACITY, OverflowPolicy::Block, None, 0)
+    }
+
+    /// Submits `count` segments, finishes `decoder` within `timeout` and returns the transcripts
+    /// drained along with the number of segments abandoned.
+    fn drain(decoder: Decoder, count: u64, timeout: Option<Duration>) -> (Vec<String>, usize) {
+        for start in 0..count {
+            decoder.submit(Job::Final {
+                segment: Segment {
+                    start,
+                    samples: vec![0; 160],
+                    overlap: 0,
+                    voiced: 0..160,
+                    speech_samples: 160,
+                },
+                recording: None,
+                span: Span::none(),
+            });
+        }
+        let drained = decoder.finish(timeout);
+        let transcripts = drained
+            .events
+            .into_iter()
+            .map(|(event, _)| match event {
+                Event::Final(utterance) => utterance.text,
+                _ => unreachable!(),
+            })
+            .collect();
+        (transcripts, drained.abandoned)
+    }
+
+    #[test]
+    fn drain_within_timeout() {
+        let decoder = slow(Duration::from_millis(20));
+        assert_eq!(
+            drain(decoder, 3, Some(Duration::from_secs(5))),
+            (vec!["0".into(), "1".into(), "2".into()], 0)
+        );
+    }
+
+    #[test]
+    fn drain_without_timeout() {
+        let decoder = slow(Duration::from_millis(20));
+        let (transcripts, abandoned) = drain(decoder, 3, None);
+        assert_eq!(transcripts, ["0", "1", "2"]);
+        assert_eq!(abandoned, 0);
+    }
+
+    #[test]
+    fn drain_timeout_abandons() {
+        let decoder = slow(Duration::from_millis(300));
+        let started = Instant::now();
+        let (transcripts, abandoned) = drain(decoder, 3, Some(Duration::from_millis(450)));
+        assert!(started.elapsed() < Duration::from_millis(900));
+        // The first segment is decoded in time, the second one is being decoded once the
+        // timeout elapses, while the third one is still queued.
+        assert_eq!(transcripts, ["0"]);
+        assert_eq!(abandoned, 2);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::metrics;
+use crate::output::{Event, Sink};
+use crate::segmenter::Segment;
+use std::{str::FromStr, sync::atomic::Ordering, time::Duration};
+use tracing::debug;
+
+/// What to do with final transcripts repeating the previous one.
+#[derive(Clone, Copy, Debug)]
+pub enum Policy {
+    /// Do not output them.
+    Drop,
+    /// Output them marked as duplicates.
+    Mark,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "mark" => Ok(Self::Mark),
+            _ => Err(format!(
+                "failed to parse `{}` into duplicate policy of drop or mark",
+                s
+            )),
+        }
+    }
+}
+
+/// Suppresses final transcripts identical to the previous one, ignoring surrounding whitespace
+/// and case, which start within the window after it ended, e.g. because of echo, before
+/// forwarding events to `sink`.
+pub struct Dedup<S> {
+    window: Duration,
+    policy: Policy,
+    /// Normalized text and end of the previous final transcript.
+    previous: Option<(String, Duration)>,
+    sink: S,
+}
+
+impl<S> Dedup<S> {
+    pub fn new(window: Duration, policy: Policy, sink: S) -> Self {
+        Self {
+            window,
+            policy,
+            previous: None,
+            sink,
+        }
+    }
+}
+
+impl<S: Sink> Sink for Dedup<S> {
+    fn send(&mut self, event: &Event) {
+        let utterance = match event {
+            Event::Final(utterance) => utterance,
+            event => return self.sink.send(event),
+        };
+        let text = utterance.text.trim().to_lowercase();
+        let duplicate = match &self.previous {
+            Some((previous, end)) => {
+                *previous == text
+                    && utterance.start.checked_sub(*end).unwrap_or_default() <= self.window
+            }
+            None => false,
+        };
+        // Repetitions are matched against the previous transcript, so that a long run of them
+        // is suppressed as a whole.
+        self.previous = Some((text, utterance.end));
+        if !duplicate {
+            return self.sink.send(event);
+        }
+        metrics::DUPLICATES.fetch_add(1, Ordering::Relaxed);
+        debug!(
+            text = %utterance.text,
+            policy = ?self.policy,
+            "Suppressing duplicate transcript"
+        );
+        if let Policy::Mark = self.policy {
+            let mut utterance = utterance.clone();
+            utterance.duplicate = true;
+            self.sink.send(&Event::Final(utterance))
+        }
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        self.sink.samples(samples)
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        self.sink.segment(segment)
+    }
+
+    fn finish(&mut self) {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+
+    /// Describes the events sent.
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn send(&mut self, event: &Event) {
+            self.0.push(match event {
+                Event::Final(utterance) if utterance.duplicate => {
+                    format!("{} (duplicate)", utterance.text)
+                }
+                Event::Final(utterance) => utterance.text.clone(),
+                Event::Mark(_, label) => format!("# {}", label),
+                _ => "other".to_string(),
+            })
+        }
+    }
+
+    fn utterance(text: &str, start: u64, end: u64) -> Event {
+        Event::Final(Utterance {
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+            ..Utterance::test(text)
+        })
+    }
+
+    fn dedup(policy: Policy) -> Vec<String> {
+        let mut dedup = Dedup::new(Duration::from_secs(2), policy, Events::default());
+        dedup.send(&utterance("hello", 0, 1));
+        dedup.send(&utterance(" Hello ", 2, 3));
+        dedup.send(&Event::Mark(Duration::from_secs(3), "mark".into()));
+        dedup.send(&utterance("hello", 4, 5));
+        dedup.send(&utterance("hello", 8, 9));
+        dedup.send(&utterance("world", 9, 10));
+        dedup.send(&utterance("hello", 10, 11));
+        dedup.sink.0
+    }
+
+    #[test]
+    fn parse_policy() {
+        assert!(matches!("drop".parse(), Ok(Policy::Drop)));
+        assert!(matches!("mark".parse(), Ok(Policy::Mark)));
+        assert!("keep".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn drop() {
+        assert_eq!(
+            dedup(Policy::Drop),
+            ["hello", "# mark", "hello", "world", "hello"]
+        );
+    }
+
+    #[test]
+    fn mark() {
+        assert_eq!(
+            dedup(Policy::Mark),
+            [
+                "hello",
+                " Hello  (duplicate)",
+                "# mark",
+                "hello (duplicate)",
+                "hello",
+                "world",
+                "hello"
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::durable::{self, Syncer};
+use crate::metrics;
+use crate::output::{Event, Sink, TranscriptFile, Utterance};
+use crate::template::{Rolling, Template};
+use crate::transcript::{self, Cues, Details};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::Ordering,
+    time::{Duration, SystemTime},
+};
+use tracing::warn;
+
+/// Format final transcripts are written to a destination in.
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    /// Plain text, a line per transcript.
+    Txt,
+    /// JSON object per line of every transcript.
+    Jsonl,
+    /// Transcript file, which is written once the session ends, since it is numbered or
+    /// enclosed as a whole.
+    Document(transcript::Format),
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "txt" => Ok(Self::Txt),
+            "jsonl" => Ok(Self::Jsonl),
+            "srt" | "vtt" | "json" | "audacity" | "textgrid" | "markdown" | "ffmetadata" => {
+                s.parse().map(Self::Document)
+            }
+            _ => Err(format!(
+                "failed to parse `{}` into output format of txt, jsonl, srt, vtt, json, audacity, textgrid, markdown or ffmetadata",
+                s
+            )),
+        }
+    }
+}
+
+/// File final transcripts are written to, parsed from `<template>[:<format>]`, e.g.
+/// `captions.srt:srt`. The format defaults to txt, so that templates containing a colon, which
+/// does not precede a known format, are taken as they are.
+#[derive(Clone, Debug)]
+pub struct Destination {
+    template: Template,
+    format: Format,
+}
+
+impl FromStr for Destination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let (template, format) = match s.rfind(':') {
+            Some(i) => match s[i + 1..].parse() {
+                Ok(format) => (&s[..i], format),
+                Err(_) => (s, Format::Txt),
+            },
+            None => (s, Format::Txt),
+        };
+        Ok(Self {
+            template: template.parse()?,
+            format,
+        })
+    }
+}
+
+impl Destination {
+    /// Whether transcripts are written as plain text, which is prefixed with the speaker.
+    pub fn is_text(&self) -> bool {
+        matches!(self.format, Format::Txt)
+    }
+
+    /// Opens the destination, failing if the file cannot be created. Files are synced to disk
+    /// according to `sync`, documents name the model at `model` and subtitles are shaped into
+    /// `cues`.
+    pub fn open(
+        &self,
+        sync: durable::Policy,
+        model: Option<PathBuf>,
+        cues: Cues,
+    ) -> io::Result<Box<dyn Sink>> {
+        Ok(match self.format {
+            Format::Txt => Box::new(TranscriptFile::open(self.template.clone(), false, sync)?),
+            Format::Jsonl => Box::new(TranscriptFile::open(self.template.clone(), true, sync)?),
+            Format::Document(format) => Box::new(Document::create(
+                self.template.clone(),
+                format,
+                sync,
+                model,
+                cues,
+            )?),
+        })
+    }
+}
+
+/// Collects final transcripts and writes them as a transcript file once the session ends. The
+/// file is created, and a template expanded, at the start of the session.
+pub struct Document {
+    path: PathBuf,
+    file: Option<fs::File>,
+    format: transcript::Format,
+    utterances: Vec<Utterance>,
+    date: SystemTime,
+    model: Option<PathBuf>,
+    cues: Cues,
+    syncer: Syncer,
+}
+
+impl Document {
+    pub fn create(
+        template: Template,
+        format: transcript::Format,
+        sync: durable::Policy,
+        model: Option<PathBuf>,
+        cues: Cues,
+    ) -> io::Result<Self> {
+        let date = SystemTime::now();
+        let mut rolling = Rolling::new(template);
+        let path = rolling
+            .next(date)?
+            .expect("Failed to expand template")
+            .to_path_buf();
+        let file = fs::File::create(&path)?;
+        Ok(Self {
+            path,
+            file: Some(file),
+            format,
+            utterances: Vec::new(),
+            date,
+            model,
+            cues,
+            syncer: Syncer::new(sync),
+        })
+    }
+}
+
+impl Sink for Document {
+    fn send(&mut self, event: &Event) {
+        if let Event::Final(utterance) = event {
+            self.utterances.push(utterance.clone())
+        }
+    }
+
+    fn finish(&mut self) {
+        let mut file = match self.file.take() {
+            Some(file) => file,
+            None => return,
+        };
+        // Transcripts may end after the audio processed, if it started at an offset.
+        let end = self.utterances.last().map(|utterance| utterance.end);
+        let details = Details {
+            duration: Duration::from_micros(metrics::AUDIO_MICROS.load(Ordering::Relaxed))
+                .max(end.unwrap_or_default()),
+            date: self.date,
+            model: self.model.as_deref(),
+            cues: self.cues,
+        };
+        let rendered = self.format.render(&self.utterances, &details);
+        if let Err(err) = file
+            .write_all(rendered.as_bytes())
+            .and_then(|()| self.syncer.written(&file))
+            .and_then(|_| self.syncer.close(&file))
+        {
+            warn!(path = %self.path.display(), error = %err, "Failed to write transcript file");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> (String, String) {
+        let destination: Destination = s.parse().unwrap();
+        (
+            format!("{:?}", destination.template),
+            format!("{:?}", destination.format),
+        )
+    }
+
+    #[test]
+    fn parse_destination() {
+        let plain: Destination = "out.txt".parse().unwrap();
+        assert!(plain.is_text());
+        assert_eq!(parse("out.txt"), parse("out.txt:txt"));
+        assert!(parse("out.jsonl:jsonl").1.contains("Jsonl"));
+        assert!(parse("captions.srt:srt").1.contains("Document"));
+        // Colons not preceding a format are part of the template.
+        let (template, format) = parse("C:/transcripts/%H:%M.txt");
+        assert!(
+            template.contains("C:/transcripts/%H:%M.txt"),
+            "{}",
+            template
+        );
+        assert_eq!(format, "Txt");
+        assert!("out.txt:%q".parse::<Destination>().is_err());
+        assert!("wma".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn document() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("captions.srt");
+        let destination: Destination = format!("{}:srt", path.display()).parse().unwrap();
+        assert!(!destination.is_text());
+        let mut sink = destination
+            .open(durable::Policy::Line, None, Cues::default())
+            .unwrap();
+        // Created at the start of the session, but only written once it ends.
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        sink.send(&Event::Final(Utterance {
+            start: Duration::from_millis(1500),
+            end: Duration::from_millis(3250),
+            ..Utterance::test("hello world")
+        }));
+        sink.send(&Event::Partial(Utterance::test("ignored")));
+        sink.finish();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "1\n00:00:01,500 --> 00:00:03,250\nhello world\n\n"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::capture::{self, Writer};
+use crate::clock::Anchor;
+use crate::dither::Dither;
+use crate::error::Error;
+use crate::pipeline::CHANNELS;
+use crate::priority;
+use crate::resample::{self, Resampler};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    Device, Host, InputCallbackInfo, Sample, SampleFormat, Stream, StreamConfig, StreamInstant,
+    SupportedStreamConfig,
+};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Returns the device named `name` among `devices`, or `default`, if `name` is unset.
+pub fn find(
+    mut devices: impl Iterator<Item = Device>,
+    default: Option<Device>,
+    name: Option<&str>,
+) -> Result<Device, Error> {
+    let name = match name {
+        Some(name) => name,
+        None => {
+            return default.ok_or_else(|| Error::Device("Failed to find default device".into()))
+        }
+    };
+    devices
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| {
+            Error::Device(format!(
+                "Failed to find device `{}`, list devices with --list-devices",
+                name
+            ))
+        })
+}
+
+/// Prints the names of the input devices of `host` and, if `outputs` is set, of its output
+/// devices.
+pub fn list(host: &Host, outputs: bool) -> Result<(), Error> {
+    let devices = host
+        .input_devices()
+        .map_err(Error::with(Error::Device, "Failed to list input devices"))?;
+    for device in devices {
+        if let Ok(name) = device.name() {
+            println!("input: {}", name);
+        }
+    }
+    if outputs {
+        let devices = host
+            .output_devices()
+            .map_err(Error::with(Error::Device, "Failed to list output devices"))?;
+        for device in devices {
+            if let Ok(name) = device.name() {
+                println!("output: {}", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the configuration of `device` to capture audio with, which is mono i16 at
+/// `sample_rate` if supported, avoiding conversion, or else the default one.
+pub fn input_config(device: &Device, sample_rate: u32) -> Result<SupportedStreamConfig, Error> {
+    let rate = cpal::SampleRate(sample_rate);
+    let exact = device
+        .supported_input_configs()
+        .map_err(Error::with(
+            Error::Device,
+            "Failed to get supported device input configurations",
+        ))?
+        .find(|config| {
+            config.channels() == CHANNELS
+                && config.sample_format() == SampleFormat::I16
+                && config.min_sample_rate() <= rate
+                && rate <= config.max_sample_rate()
+        });
+    match exact {
+        Some(config) => Ok(config.with_sample_rate(rate)),
+        None => device.default_input_config().map_err(Error::with(
+            Error::Device,
+            "Failed to get default device input configuration",
+        )),
+    }
+}
+
+/// Starts capturing from `device` with `config`, returning the stream along with its samples
+/// downmixed to mono and resampled to `sample_rate` with `resampler`.
+/// Float samples are dithered when converted to i16, if `dither` is set.
+/// The callback thread is raised to realtime priority, if `realtime` is set.
+/// `anchor` is set to the capture time of the first sample.
+pub fn capture(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    sample_rate: u32,
+    resampler: Resampler,
+    dither: bool,
+    realtime: bool,
+    anchor: Anchor,
+) -> Result<(Stream, Box<dyn Iterator<Item = i16>>), Error> {
+    let device_rate = config.sample_rate().0;
+    let (writer, reader) = capture::buffer(device_rate, anchor);
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        SampleFormat::I16 => build::<i16>(device, &stream_config, writer, dither, realtime),
+        SampleFormat::U16 => build::<u16>(device, &stream_config, writer, dither, realtime),
+        SampleFormat::F32 => build::<f32>(device, &stream_config, writer, dither, realtime),
+    }
+    .map_err(Error::with(Error::Device, "Failed to build input stream"))?;
+    stream
+        .play()
+        .map_err(Error::with(Error::Device, "Failed to play input stream"))?;
+    if config.channels() != CHANNELS || config.sample_format() != SampleFormat::I16 {
+        info!(
+            channels = config.channels(),
+            format = ?config.sample_format(),
+            "Converting captured audio to mono i16"
+        );
+    }
+    Ok((
+        stream,
+        resample::resample(reader, device_rate, sample_rate, resampler),
+    ))
+}
+
+/// Maximum difference between the capture time reported for samples and the one expected from
+/// the preceding ones, which is not considered a gap.
+const GAP_TOLERANCE: Duration = Duration::from_millis(20);
+
+fn build<T: Sample>(
+    device: &Device,
+    config: &StreamConfig,
+    mut writer: Writer,
+    dither: bool,
+    realtime: bool,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let float = T::FORMAT == SampleFormat::F32;
+    let mut dither = Dither::new(dither);
+    let sample_rate = config.sample_rate.0 as u64;
+    // Reused across callbacks, so that the callback only allocates while the buffer grows.
+    let mut mono = Vec::new();
+    // Capture time of the sample following the ones of the last callback.
+    let mut expected: Option<StreamInstant> = None;
+    // The callback thread is only known once it is called.
+    let mut promote = realtime;
+    device.build_input_stream(
+        config,
+        move |data: &[T], info: &InputCallbackInfo| {
+            if promote {
+                promote = false;
+                priority::realtime();
+            }
+            let captured = info.timestamp().capture;
+            let frames = data.len() / channels;
+            if let Some(late) = expected.and_then(|expected| captured.duration_since(&expected)) {
+                if late > GAP_TOLERANCE {
+                    writer.gap((late.as_micros() as u64 * sample_rate / 1_000_000) as usize);
+                }
+            }
+            expected = captured.add(Duration::from_micros(
+                frames as u64 * 1_000_000 / sample_rate,
+            ));
+            mono.clear();
+            if float {
+                mono.extend(data.chunks(channels).map(|frame| {
+                    let sum: f32 = frame.iter().map(Sample::to_f32).sum();
+                    dither.quantize(sum / channels as f32)
+                }));
+            } else if channels == 1 {
+                mono.extend(data.iter().map(Sample::to_i16));
+            } else {
+                mono.extend(data.chunks(channels).map(downmix));
+            }
+            writer.write(&mono)
+        },
+        move |err| error!(error = %err, "Failed to capture frame on input stream"),
+    )
+}
+
+/// Returns the average of the samples of all channels of `frame`.
+pub fn downmix<T: Sample>(frame: &[T]) -> i16 {
+    let sum: f32 = frame.iter().map(Sample::to_f32).sum();
+    (sum / frame.len() as f32).to_i16()
+}
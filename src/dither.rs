@@ -0,0 +1,118 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Full scale of i16 samples.
+const FULL_SCALE: f32 = 32768.0;
+
+/// Converts float samples to i16, optionally adding triangular (TPDF) dither of ±1 LSB before
+/// rounding, so that the quantization error of quiet signals is noise uncorrelated with the
+/// signal, rather than distortion.
+pub struct Dither {
+    enabled: bool,
+    /// State of the xorshift generator of the dither noise.
+    state: u32,
+}
+
+impl Dither {
+    pub fn new(enabled: bool) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.subsec_nanos());
+        Self {
+            enabled,
+            // Xorshift must not be seeded with 0.
+            state: seed | 1,
+        }
+    }
+
+    /// Converts `sample` in `-1.0..=1.0` to i16.
+    pub fn quantize(&mut self, sample: f32) -> i16 {
+        let mut value = sample * FULL_SCALE;
+        if self.enabled {
+            value += self.uniform() - self.uniform();
+        }
+        value.round().max(i16::MIN as f32).min(i16::MAX as f32) as i16
+    }
+
+    /// Returns a pseudo-random number in `0.0..1.0`.
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of samples of every constant signal dithered.
+    const SAMPLES: usize = 100_000;
+
+    #[test]
+    fn constant_quiet_signals() {
+        // Constant signals within a few LSB of silence at fractions of an LSB, with the generator
+        // seeded differently for every one.
+        for step in -64..=64 {
+            let lsb = step as f32 / 16.0;
+            let mut dither = Dither {
+                enabled: true,
+                state: (step as u32).wrapping_mul(2_654_435_761) | 1,
+            };
+            let mut sum = 0.0;
+            for _ in 0..SAMPLES {
+                let sample = dither.quantize(lsb / FULL_SCALE) as f32;
+                // Dither of ±1 LSB is rounded to at most 1 further LSB.
+                assert!(
+                    (sample - lsb).abs() <= 1.5,
+                    "{} dithered to {}",
+                    lsb,
+                    sample
+                );
+                sum += sample as f64;
+            }
+            let mean = sum / SAMPLES as f64;
+            assert!(
+                (mean - lsb as f64).abs() < 0.02,
+                "{} dithered to a mean of {}",
+                lsb,
+                mean
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_range() {
+        let mut dither = Dither::new(true);
+        for _ in 0..SAMPLES {
+            assert!((0.0..1.0).contains(&dither.uniform()));
+        }
+    }
+
+    #[test]
+    fn disabled_rounds() {
+        let mut dither = Dither::new(false);
+        for (sample, expected) in [
+            (0.0, 0),
+            (0.4 / FULL_SCALE, 0),
+            (0.6 / FULL_SCALE, 1),
+            (-0.6 / FULL_SCALE, -1),
+            (0.5, 16384),
+            (1.0, i16::MAX),
+            (-1.0, i16::MIN),
+            (2.0, i16::MAX),
+            (-2.0, i16::MIN),
+        ] {
+            assert_eq!(dither.quantize(sample), expected, "{}", sample);
+        }
+    }
+
+    #[test]
+    fn full_scale_clipped() {
+        let mut dither = Dither::new(true);
+        for _ in 0..SAMPLES {
+            assert!(dither.quantize(1.0) >= i16::MAX - 1);
+            assert!(dither.quantize(-1.0) <= i16::MIN + 1);
+        }
+    }
+}
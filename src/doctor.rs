@@ -0,0 +1,236 @@
+#[cfg(feature = "capture")]
+use crate::device;
+use crate::error::Error;
+use crate::model::Model;
+use crate::models;
+use crate::recordings;
+use crate::vad::{self, FvadMode};
+#[cfg(feature = "capture")]
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Options of the setup checked, taken from the command line.
+pub struct Options {
+    pub model: Option<PathBuf>,
+    pub model_sha256: Option<String>,
+    pub device: Option<String>,
+    pub recordings_dir: Option<PathBuf>,
+    pub fvad_mode: Option<FvadMode>,
+    /// Sample rate assumed if the model cannot be loaded.
+    pub default_sample_rate: u32,
+}
+
+/// Outcome of a check.
+struct Check {
+    name: &'static str,
+    /// What was found, or why the check failed.
+    detail: String,
+    /// How to fix the failure, if the check failed.
+    hint: Option<&'static str>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Self {
+        Self {
+            name,
+            detail,
+            hint: None,
+        }
+    }
+
+    fn fail(name: &'static str, detail: String, hint: &'static str) -> Self {
+        Self {
+            name,
+            detail,
+            hint: Some(hint),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "ok": self.hint.is_none(),
+            "detail": self.detail,
+            "hint": self.hint,
+        })
+    }
+}
+
+/// Checks the setup the pipeline depends on in order, without capturing or writing anything, and
+/// prints the outcome of every check, as JSON if `json` is set.
+/// Fails if any check failed.
+pub fn run(options: Options, json: bool) -> Result<(), Error> {
+    let mut checks = Vec::new();
+
+    let sample_rate = match check_model(&options) {
+        Ok((path, sample_rate)) => {
+            checks.push(Check::pass(
+                "model",
+                format!("loaded {} at {} Hz", path.display(), sample_rate),
+            ));
+            sample_rate
+        }
+        Err((detail, hint)) => {
+            checks.push(Check::fail("model", detail, hint));
+            options.default_sample_rate
+        }
+    };
+
+    check_input(&options, sample_rate, &mut checks);
+
+    checks.push(match vad::supported_sample_rate(sample_rate) {
+        Some(vad_sample_rate) => {
+            let _ = vad::new(vad_sample_rate, options.fvad_mode);
+            Check::pass("fvad", format!("created at {} Hz", vad_sample_rate))
+        }
+        None => Check::fail(
+            "fvad",
+            format!(
+                "no sample rate supported by Fvad is close to {} Hz",
+                sample_rate
+            ),
+            "use a model with a sample rate of 8, 16, 32 or 48 kHz",
+        ),
+    });
+
+    let dir = options
+        .recordings_dir
+        .clone()
+        .unwrap_or_else(recordings::default_dir);
+    checks.push(match recordings::check_dir(&dir) {
+        Ok(()) => Check::pass("recordings directory", dir.display().to_string()),
+        Err(err) => Check::fail(
+            "recordings directory",
+            err,
+            "pass a writable directory with --recordings-dir",
+        ),
+    });
+
+    let failed = checks.iter().filter(|check| check.hint.is_some()).count();
+    if json {
+        println!(
+            "{:#}",
+            json!({
+                "ok": failed == 0,
+                "checks": checks.iter().map(Check::to_json).collect::<Vec<_>>(),
+            })
+        );
+    } else {
+        for check in &checks {
+            match check.hint {
+                None => println!("[ok]   {}: {}", check.name, check.detail),
+                Some(hint) => {
+                    println!("[fail] {}: {}", check.name, check.detail);
+                    println!("       hint: {}", hint);
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Error::Other(format!(
+            "{} of {} checks failed",
+            failed,
+            checks.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that the input device exists and has a usable configuration at `sample_rate`.
+#[cfg(feature = "capture")]
+fn check_input(options: &Options, sample_rate: u32, checks: &mut Vec<Check>) {
+    let host = cpal::default_host();
+    let device = host
+        .input_devices()
+        .map_err(Error::with(Error::Device, "Failed to list input devices"))
+        .and_then(|devices| {
+            device::find(
+                devices,
+                host.default_input_device(),
+                options.device.as_deref(),
+            )
+        });
+    match device {
+        Ok(device) => {
+            checks.push(Check::pass(
+                "input device",
+                device.name().unwrap_or_default(),
+            ));
+            checks.push(match device::input_config(&device, sample_rate) {
+                Ok(config) => Check::pass(
+                    "input configuration",
+                    format!(
+                        "{} channels of {:?} at {} Hz{}",
+                        config.channels(),
+                        config.sample_format(),
+                        config.sample_rate().0,
+                        if config.channels() == 1
+                            && config.sample_format() == cpal::SampleFormat::I16
+                            && config.sample_rate().0 == sample_rate
+                        {
+                            ""
+                        } else {
+                            ", converted to mono i16 at the rate of the model"
+                        }
+                    ),
+                ),
+                Err(err) => Check::fail(
+                    "input configuration",
+                    err.to_string(),
+                    "select another device with --device, see --list-devices",
+                ),
+            });
+        }
+        Err(err) => checks.push(Check::fail(
+            "input device",
+            err.to_string(),
+            "connect a microphone or select one with --device, see --list-devices",
+        )),
+    }
+}
+
+#[cfg(not(feature = "capture"))]
+fn check_input(options: &Options, _sample_rate: u32, checks: &mut Vec<Check>) {
+    checks.push(Check::pass(
+        "input device",
+        format!(
+            "skipped checking {}, built without capture support",
+            options.device.as_deref().unwrap_or("the default device")
+        ),
+    ));
+}
+
+/// Loads the model, returning its path and sample rate, or why it failed with a hint.
+fn check_model(options: &Options) -> Result<(PathBuf, u32), (String, &'static str)> {
+    let path = options
+        .model
+        .clone()
+        .or_else(models::default_model)
+        .ok_or((
+            "no model found".to_string(),
+            "pass --model or fetch one with the download-model command",
+        ))?;
+    let path = if path.is_dir() {
+        models::discover_dir(&path)
+            .map_err(|err| (err, "pass the path of the model file with --model"))?
+            .model
+    } else {
+        path
+    };
+    models::verify(&path, models::EXTENSIONS, options.model_sha256.as_deref()).map_err(|err| {
+        (
+            err,
+            "fetch the model again with the download-model command or pass another one with --model",
+        )
+    })?;
+    models::check_format(&path)
+        .map_err(|err| (err, "pass a model matching this build with --model"))?;
+    let model = Model::load_from_files(&path).map_err(|err| {
+        (
+            format!("failed to load {}: {}", path.display(), err),
+            "check that the model matches DeepSpeech 0.9 and libdeepspeech is installed",
+        )
+    })?;
+    Ok((path, model.get_sample_rate() as u32))
+}
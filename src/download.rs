@@ -0,0 +1,133 @@
+use crate::models::{MODEL_FILE, SCORER_FILE};
+use indicatif::{ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+const RELEASE_URL: &str = "https://github.com/mozilla/DeepSpeech/releases/download/v0.9.3";
+
+/// Writes to `inner`, hashing everything written.
+struct Hashing<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Downloads the model and scorer into `dir`, returning their paths.
+pub fn download(dir: &Path) -> Result<(PathBuf, PathBuf), String> {
+    fs::create_dir_all(dir)
+        .map_err(|err| format!("failed to create {}: {}", dir.display(), err))?;
+    let model = fetch(dir, MODEL_FILE)?;
+    let scorer = fetch(dir, SCORER_FILE)?;
+    Ok((model, scorer))
+}
+
+/// Downloads release artifact `name` into `dir`, unless already present.
+/// Interrupted downloads are resumed from the partially downloaded file.
+fn fetch(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = dir.join(name);
+    if path.is_file() {
+        info!(path = %path.display(), "Already downloaded");
+        return Ok(path);
+    }
+    let part = dir.join(format!("{}.part", name));
+    let url = format!("{}/{}", RELEASE_URL, name);
+
+    let offset = fs::metadata(&part).map(|meta| meta.len()).unwrap_or(0);
+    let mut request = ureq::get(&url);
+    if offset > 0 {
+        request = request.set("Range", &format!("bytes={}-", offset));
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(416, _)) => {
+            // The partial download is stale or already complete, start over.
+            fs::remove_file(&part)
+                .map_err(|err| format!("failed to remove {}: {}", part.display(), err))?;
+            return fetch(dir, name);
+        }
+        Err(err) => return Err(format!("failed to request {}: {}", url, err)),
+    };
+    let resumed = offset > 0 && response.status() == 206;
+    let len = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+
+    let mut hasher = Sha256::new();
+    let file = if resumed {
+        io::copy(
+            &mut File::open(&part)
+                .map_err(|err| format!("failed to open {}: {}", part.display(), err))?,
+            &mut hasher,
+        )
+        .map_err(|err| format!("failed to read {}: {}", part.display(), err))?;
+        OpenOptions::new().append(true).open(&part)
+    } else {
+        File::create(&part)
+    }
+    .map_err(|err| format!("failed to open {}: {}", part.display(), err))?;
+
+    let start = if resumed { offset } else { 0 };
+    let progress = match len {
+        Some(len) => ProgressBar::new(start + len).with_style(
+            ProgressStyle::default_bar()
+                .template("{msg} [{bar:40}] {bytes}/{total_bytes} {bytes_per_sec} {eta}")
+                .progress_chars("=> "),
+        ),
+        None => ProgressBar::new_spinner()
+            .with_style(ProgressStyle::default_spinner().template("{msg} {spinner} {bytes}")),
+    };
+    progress.set_message(name.to_string());
+    progress.set_position(start);
+
+    let mut writer = Hashing {
+        inner: io::BufWriter::new(file),
+        hasher,
+    };
+    io::copy(&mut progress.wrap_read(response.into_reader()), &mut writer)
+        .map_err(|err| format!("failed to download {}: {}", url, err))?;
+    writer
+        .flush()
+        .map_err(|err| format!("failed to write {}: {}", part.display(), err))?;
+    progress.finish();
+
+    fs::rename(&part, &path)
+        .map_err(|err| format!("failed to rename {}: {}", part.display(), err))?;
+    println!("{:x}  {}", writer.hasher.finalize(), name);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashing() {
+        let mut writer = Hashing {
+            inner: Vec::new(),
+            hasher: Sha256::new(),
+        };
+        writer.write_all(b"hello ").unwrap();
+        writer.write_all(b"world").unwrap();
+        assert_eq!(writer.inner, b"hello world");
+        assert_eq!(
+            format!("{:x}", writer.hasher.finalize()),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs::File,
+    io,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// When written files are synced to disk, so that they survive power cuts.
+#[derive(Clone, Copy, Debug)]
+pub enum Policy {
+    /// Leave it to the operating system.
+    None,
+    /// After every write.
+    Line,
+    /// With the first write once the interval passed since the previous sync, and when the file is
+    /// closed.
+    Interval(Duration),
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "line" => Ok(Self::Line),
+            _ => match s.strip_prefix("interval:") {
+                Some(interval) => crate::duration::positive_secs(interval).map(Self::Interval),
+                None => Err(format!(
+                    "failed to parse `{}` into output sync of none, line or interval:<duration>",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Syncs files written to according to a policy.
+pub struct Syncer {
+    policy: Policy,
+    /// Time of the previous sync.
+    synced: Instant,
+    /// Whether data was written since the previous sync.
+    dirty: bool,
+}
+
+impl Syncer {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            synced: Instant::now(),
+            dirty: false,
+        }
+    }
+
+    /// Syncs `file` after a write, if due, returning whether it did.
+    pub fn written(&mut self, file: &File) -> io::Result<bool> {
+        self.dirty = true;
+        match self.policy {
+            Policy::None => return Ok(false),
+            Policy::Line => self.sync(file)?,
+            Policy::Interval(interval) if self.synced.elapsed() >= interval => self.sync(file)?,
+            Policy::Interval(_) => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Syncs `file`, before it is closed, if anything written to it is not synced yet.
+    pub fn close(&mut self, file: &File) -> io::Result<()> {
+        match self.policy {
+            Policy::None => Ok(()),
+            _ if !self.dirty => Ok(()),
+            _ => self.sync(file),
+        }
+    }
+
+    fn sync(&mut self, file: &File) -> io::Result<()> {
+        file.sync_data()?;
+        self.synced = Instant::now();
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert!(matches!("none".parse(), Ok(Policy::None)));
+        assert!(matches!("line".parse(), Ok(Policy::Line)));
+        assert!(matches!(
+            "interval:5".parse(),
+            Ok(Policy::Interval(interval)) if interval == Duration::from_secs(5)
+        ));
+        assert!("interval:0".parse::<Policy>().is_err());
+        assert!("interval".parse::<Policy>().is_err());
+        assert!("always".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn sync() {
+        let file = tempfile::tempfile().unwrap();
+        let mut none = Syncer::new(Policy::None);
+        assert!(!none.written(&file).unwrap());
+        none.close(&file).unwrap();
+        assert!(none.dirty);
+
+        let mut line = Syncer::new(Policy::Line);
+        assert!(line.written(&file).unwrap());
+        assert!(line.written(&file).unwrap());
+        assert!(!line.dirty);
+
+        let mut interval = Syncer::new(Policy::Interval(Duration::from_millis(200)));
+        assert!(!interval.written(&file).unwrap());
+        assert!(interval.dirty);
+        interval.close(&file).unwrap();
+        assert!(!interval.dirty);
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(interval.written(&file).unwrap());
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+/// Units accepted after a number, along with their length in nanoseconds.
+const UNITS: [(&str, f64); 11] = [
+    ("ns", 1.0),
+    ("us", 1e3),
+    ("µs", 1e3),
+    ("ms", 1e6),
+    ("s", 1e9),
+    ("sec", 1e9),
+    ("m", 60e9),
+    ("min", 60e9),
+    ("h", 3600e9),
+    ("hr", 3600e9),
+    ("d", 86400e9),
+];
+
+/// Parses `s` as a sequence of numbers with units, e.g. `300ms`, `1.5s` or `1m30s`, or as a bare
+/// number of `default_unit`, which options taking a number of milliseconds or seconds used to
+/// accept.
+fn parse(s: &str, default_unit: &str) -> Result<Duration, String> {
+    let err = || {
+        format!(
+            "failed to parse `{}` into duration, expected e.g. 300ms, 1.5s or 2m, or a number of {}",
+            s,
+            unit_name(default_unit)
+        )
+    };
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(err());
+    }
+    let mut nanos = 0.0;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().map_err(|_| err())?;
+        rest = rest[number_len..].trim_start();
+        let unit_len = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "" if number_len == s.trim().len() => default_unit,
+            "" => return Err(err()),
+            unit => unit,
+        };
+        let (_, length) = UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or_else(err)?;
+        nanos += number * length;
+        rest = rest[unit_len..].trim_start();
+    }
+    if !nanos.is_finite() || nanos > u64::MAX as f64 {
+        return Err(err());
+    }
+    Ok(Duration::from_nanos(nanos.round() as u64))
+}
+
+fn unit_name(unit: &str) -> &'static str {
+    match unit {
+        "ms" => "milliseconds",
+        _ => "seconds",
+    }
+}
+
+/// Checks that `duration` parsed from `s` is at most `max`.
+fn at_most(s: &str, duration: Duration, max: Duration) -> Result<Duration, String> {
+    if duration > max {
+        return Err(format!(
+            "duration `{}` is out of range, expected at most {:?}",
+            s, max
+        ));
+    }
+    Ok(duration)
+}
+
+/// Parses a duration, in which bare numbers are in milliseconds.
+pub fn millis(s: &str) -> Result<Duration, String> {
+    parse(s, "ms")
+}
+
+/// Checks that `duration` parsed from `s` is not zero.
+fn positive(s: &str, duration: Duration) -> Result<Duration, String> {
+    if duration == Duration::from_secs(0) {
+        return Err(format!(
+            "duration `{}` is out of range, expected more than 0",
+            s
+        ));
+    }
+    Ok(duration)
+}
+
+/// Parses a non-zero duration, in which bare numbers are in milliseconds.
+pub fn positive_millis(s: &str) -> Result<Duration, String> {
+    positive(s, millis(s)?)
+}
+
+/// Parses a non-zero duration, in which bare numbers are in seconds.
+pub fn positive_secs(s: &str) -> Result<Duration, String> {
+    positive(s, parse(s, "s")?)
+}
+
+/// Parses a duration of at most 10 seconds, in which bare numbers are in milliseconds, for spans
+/// of audio buffered for every segment.
+pub fn padding(s: &str) -> Result<Duration, String> {
+    at_most(s, millis(s)?, Duration::from_secs(10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_millis() {
+        for (s, expected) in [
+            ("300ms", Duration::from_millis(300)),
+            ("300", Duration::from_millis(300)),
+            ("10 ms", Duration::from_millis(10)),
+            (" 10ms ", Duration::from_millis(10)),
+            ("1.5s", Duration::from_millis(1500)),
+            ("1.5 sec", Duration::from_millis(1500)),
+            ("2m", Duration::from_secs(120)),
+            ("2min", Duration::from_secs(120)),
+            ("1m30s", Duration::from_secs(90)),
+            ("1h 2m 3s", Duration::from_secs(3723)),
+            ("1hr", Duration::from_secs(3600)),
+            ("1d", Duration::from_secs(86400)),
+            ("250us", Duration::from_micros(250)),
+            ("250µs", Duration::from_micros(250)),
+            ("7ns", Duration::from_nanos(7)),
+            ("0", Duration::from_secs(0)),
+            ("0.5", Duration::from_micros(500)),
+        ] {
+            assert_eq!(millis(s), Ok(expected), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in [
+            "",
+            " ",
+            "ms",
+            "10 parsecs",
+            "1m30",
+            "-5ms",
+            "1..5s",
+            "1e3ms",
+            "ten",
+            "10ms!",
+        ] {
+            let err = millis(s).unwrap_err();
+            assert!(err.contains(&format!("`{}`", s)), "{}", err);
+            assert!(err.contains("number of milliseconds"), "{}", err);
+        }
+        assert!(positive_secs("soon")
+            .unwrap_err()
+            .contains("number of seconds"));
+    }
+
+    #[test]
+    fn parse_secs() {
+        assert_eq!(positive_secs("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(positive_secs("30ms"), Ok(Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            positive_millis("0s"),
+            Err("duration `0s` is out of range, expected more than 0".to_string())
+        );
+        assert!(positive_secs("0").is_err());
+        assert_eq!(padding("10s"), Ok(Duration::from_secs(10)));
+        assert_eq!(padding("0"), Ok(Duration::from_secs(0)));
+        assert_eq!(
+            padding("10001"),
+            Err("duration `10001` is out of range, expected at most 10s".to_string())
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// Failure to run, which determines the exit code of the process.
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid command line arguments.
+    Usage(String),
+    /// Failure to load the model.
+    Model(String),
+    /// Failure to open or configure the audio input device.
+    Device(String),
+    /// Failure to read the input file.
+    Input(String),
+    /// Repeated failure to decode speech, e.g. because the model is wedged.
+    Decode(String),
+    /// In-flight work did not complete within the grace period after termination was requested.
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    Drain(String),
+    /// No speech was detected for the idle timeout, e.g. because the microphone is muted.
+    Idle(String),
+    /// Any other failure, e.g. to set up an output.
+    Other(String),
+}
+
+impl Error {
+    /// Returns a function, which wraps an error into `kind`, prefixed with `context`.
+    pub fn with<E: fmt::Display>(
+        kind: fn(String) -> Self,
+        context: &'static str,
+    ) -> impl FnOnce(E) -> Self {
+        move |err| kind(format!("{}: {}", context, err))
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Usage(_) => 2,
+            Self::Model(_) => 3,
+            Self::Device(_) => 4,
+            Self::Input(_) => 5,
+            Self::Decode(_) => 6,
+            Self::Drain(_) => 7,
+            Self::Idle(_) => 8,
+        }
+    }
+
+    /// Prints the error to stderr and exits the process with its exit code.
+    pub fn exit(self) -> ! {
+        eprintln!("{}", self);
+        std::process::exit(self.exit_code())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message)
+            | Self::Model(message)
+            | Self::Device(message)
+            | Self::Input(message)
+            | Self::Decode(message)
+            | Self::Drain(message)
+            | Self::Idle(message)
+            | Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_context() {
+        let err = Error::with(Error::Input, "Failed to open input file")("no such file");
+        assert_eq!(err.exit_code(), 5);
+        assert_eq!(err.to_string(), "Failed to open input file: no such file");
+    }
+}
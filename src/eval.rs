@@ -0,0 +1,99 @@
+use crate::error::Error;
+use crate::output::{Event, Sink};
+use crate::wer::{self, Counts};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Collects the final transcripts of a recording.
+#[derive(Default)]
+pub struct Transcript {
+    texts: Vec<String>,
+}
+
+impl Transcript {
+    /// Returns the transcripts collected so far joined by spaces, clearing them.
+    pub fn take(&mut self) -> String {
+        let text = self.texts.join(" ");
+        self.texts.clear();
+        text
+    }
+}
+
+impl Sink for Transcript {
+    fn send(&mut self, event: &Event) {
+        if let Event::Final(utterance) = event {
+            if !utterance.text.is_empty() {
+                self.texts.push(utterance.text.clone())
+            }
+        }
+    }
+}
+
+/// Returns the recordings at `path` paired with their reference transcripts.
+/// If `path` is a directory, every WAV file in it is paired with the text file of the same name,
+/// otherwise `path` is paired with `reference`.
+pub fn pairs(path: &Path, reference: Option<&Path>) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    if !path.is_dir() {
+        let reference =
+            reference.ok_or("--reference is required, unless evaluating a directory")?;
+        return Ok(vec![(path.to_path_buf(), reference.to_path_buf())]);
+    }
+    let mut pairs = Vec::new();
+    for entry in fs::read_dir(path).map_err(|err| format!("Failed to read directory: {}", err))? {
+        let recording = entry
+            .map_err(|err| format!("Failed to read directory: {}", err))?
+            .path();
+        if recording.extension().is_none_or(|ext| ext != "wav") {
+            continue;
+        }
+        let reference = recording.with_extension("txt");
+        if !reference.is_file() {
+            return Err(format!(
+                "Reference transcript {} of {} not found",
+                reference.display(),
+                recording.display()
+            ));
+        }
+        pairs.push((recording, reference));
+    }
+    if pairs.is_empty() {
+        return Err(format!("No WAV files found in {}", path.display()));
+    }
+    pairs.sort();
+    Ok(pairs)
+}
+
+/// Transcribes every recording of `pairs` with `transcribe`, printing its word error rate
+/// compared to the reference and, if `alignment` is set, the alignment of the words.
+/// The total word error rate is printed once more than one recording was evaluated.
+pub fn run(
+    pairs: &[(PathBuf, PathBuf)],
+    alignment: bool,
+    mut transcribe: impl FnMut(&Path) -> Result<String, Error>,
+) -> Result<(), Error> {
+    let mut total = Counts::default();
+    for (recording, reference) in pairs {
+        let expected = fs::read_to_string(reference).map_err(Error::with(
+            Error::Input,
+            "Failed to read reference transcript",
+        ))?;
+        let actual = transcribe(recording)?;
+        let expected = wer::normalize(&expected);
+        let actual = wer::normalize(&actual);
+        let edits = wer::align(&expected, &actual);
+        let counts = Counts::of(&edits);
+        println!("{}: {}", recording.display(), counts);
+        if alignment {
+            for edit in &edits {
+                println!("{}", edit);
+            }
+        }
+        total.add(counts);
+    }
+    if pairs.len() > 1 {
+        println!("Total of {} recordings: {}", pairs.len(), total);
+    }
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use crate::clock;
+use crate::output::{Event, Sink};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{error, warn};
+
+/// Interval, at which running commands are polled for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Spawns commands without waiting for them to finish.
+/// Commands running longer than the timeout are killed, non-zero exit codes are logged.
+#[derive(Clone)]
+pub struct Spawner {
+    command: String,
+    timeout: Duration,
+    limit: usize,
+    running: Arc<AtomicUsize>,
+}
+
+impl Spawner {
+    /// If `command` contains a `{}` placeholder, it is split on whitespace and executed directly
+    /// with `{}` replaced by the input, otherwise it is executed via `sh -c`.
+    pub fn new(command: String, timeout: Duration, limit: usize) -> Self {
+        Self {
+            command,
+            timeout,
+            limit,
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Spawns the command with `env` set, writing `input` to its stdin.
+    pub fn spawn(&self, input: &str, env: &[(&str, String)]) {
+        if self.running.fetch_add(1, Ordering::SeqCst) >= self.limit {
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            warn!(
+                command = %self.command,
+                limit = self.limit,
+                "Too many instances of command are running, skipping"
+            );
+            return;
+        }
+
+        let mut command = if self.command.contains("{}") {
+            let mut args = self
+                .command
+                .split_whitespace()
+                .map(|arg| arg.replace("{}", input));
+            let mut command = Command::new(args.next().unwrap_or_default());
+            command.args(args);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.command);
+            command
+        };
+        let spawned = command
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) => {
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                error!(command = %self.command, error = %err, "Failed to execute command");
+                return;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command is free to ignore its stdin.
+            let _ = stdin.write_all(input.as_bytes());
+        }
+
+        let spawner = self.clone();
+        thread::spawn(move || {
+            let started = Instant::now();
+            loop {
+                match child.try_wait() {
+                    Ok(Some(status)) if !status.success() => {
+                        warn!(command = %spawner.command, %status, "Command failed");
+                        break;
+                    }
+                    Ok(Some(_)) => break,
+                    Ok(None) if started.elapsed() > spawner.timeout => {
+                        warn!(
+                            command = %spawner.command,
+                            timeout = ?spawner.timeout,
+                            "Command timed out, killing it"
+                        );
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        break;
+                    }
+                    Ok(None) => thread::sleep(POLL_INTERVAL),
+                    Err(err) => {
+                        error!(command = %spawner.command, error = %err, "Failed to wait for command");
+                        break;
+                    }
+                }
+            }
+            spawner.running.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+/// Executes a command for every final transcript.
+/// The transcript is passed on stdin and in `TRANSCRIPT`, along with `TRANSCRIPT_START_MS`,
+/// `TRANSCRIPT_END_MS` and `TRANSCRIPT_CONFIDENCE`.
+pub struct Exec(pub Spawner);
+
+impl Sink for Exec {
+    fn send(&mut self, event: &Event) {
+        if let Event::Final(utterance) = event {
+            self.0.spawn(
+                &utterance.text,
+                &[
+                    ("TRANSCRIPT", utterance.text.clone()),
+                    (
+                        "TRANSCRIPT_START_MS",
+                        utterance.start.as_millis().to_string(),
+                    ),
+                    ("TRANSCRIPT_END_MS", utterance.end.as_millis().to_string()),
+                    ("TRANSCRIPT_CONFIDENCE", utterance.confidence.to_string()),
+                ],
+            )
+        }
+    }
+}
+
+/// Executes commands when speech starts and ends, e.g. to pause music while dictating.
+/// The end of speech is only acted upon once no speech followed it for the debounce duration,
+/// so that brief pauses do not execute commands.
+/// The offsets of the start and end of speech in milliseconds are passed in `SPEECH_START_MS` and
+/// `SPEECH_END_MS`, along with `SPEECH_DURATION_MS` on end and the wall-clock time in
+/// `SPEECH_TIMESTAMP`.
+pub struct SpeechHooks {
+    pub start: Option<Spawner>,
+    pub end: Option<Spawner>,
+    pub debounce: Duration,
+    /// Offset of the start of speech in progress.
+    speaking: Option<Duration>,
+    /// When speech ended and the offset of its end, if it is yet to be acted upon.
+    ended: Option<(Instant, Duration)>,
+}
+
+impl SpeechHooks {
+    pub fn new(start: Option<Spawner>, end: Option<Spawner>, debounce: Duration) -> Self {
+        Self {
+            start,
+            end,
+            debounce,
+            speaking: None,
+            ended: None,
+        }
+    }
+
+    fn end(&mut self) {
+        let (start, (_, end)) = match (self.speaking.take(), self.ended.take()) {
+            (Some(start), Some(ended)) => (start, ended),
+            _ => return,
+        };
+        if let Some(spawner) = &self.end {
+            spawner.spawn(
+                "",
+                &[
+                    ("SPEECH_START_MS", start.as_millis().to_string()),
+                    ("SPEECH_END_MS", end.as_millis().to_string()),
+                    (
+                        "SPEECH_DURATION_MS",
+                        end.checked_sub(start)
+                            .unwrap_or_default()
+                            .as_millis()
+                            .to_string(),
+                    ),
+                    ("SPEECH_TIMESTAMP", clock::format(SystemTime::now())),
+                ],
+            )
+        }
+    }
+}
+
+impl Sink for SpeechHooks {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::SpeechStarted(offset) => {
+                if self.ended.take().is_some() {
+                    // Speech resumed within the debounce duration.
+                    return;
+                }
+                self.speaking = Some(*offset);
+                if let Some(spawner) = &self.start {
+                    spawner.spawn(
+                        "",
+                        &[
+                            ("SPEECH_START_MS", offset.as_millis().to_string()),
+                            ("SPEECH_TIMESTAMP", clock::format(SystemTime::now())),
+                        ],
+                    )
+                }
+            }
+            Event::SpeechStopped(offset) if self.speaking.is_some() => {
+                self.ended = Some((Instant::now(), *offset))
+            }
+            _ => {}
+        }
+    }
+
+    fn samples(&mut self, _samples: &[i16]) {
+        if let Some((at, _)) = self.ended {
+            if at.elapsed() >= self.debounce {
+                self.end()
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::fs;
+
+    /// Waits for all commands spawned by `spawner` to finish.
+    fn wait(spawner: &Spawner) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while spawner.running.load(Ordering::SeqCst) > 0 {
+            assert!(Instant::now() < deadline, "commands did not finish");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn env_and_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let spawner = Spawner::new(
+            format!(
+                r#"printf '%s|%s|%s|%s|' "$TRANSCRIPT" "$TRANSCRIPT_START_MS" "$TRANSCRIPT_END_MS" "$TRANSCRIPT_CONFIDENCE" > {0}; cat >> {0}"#,
+                out.display()
+            ),
+            Duration::from_secs(5),
+            1,
+        );
+        Exec(spawner.clone()).send(&Event::Final(Utterance {
+            confidence: -12.5,
+            start: Duration::from_millis(1200),
+            end: Duration::from_millis(3400),
+            ..Utterance::test("it's done")
+        }));
+        wait(&spawner);
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "it's done|1200|3400|-12.5|it's done"
+        );
+    }
+
+    #[test]
+    fn placeholder() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("touched");
+        let spawner = Spawner::new("touch {}".to_string(), Duration::from_secs(5), 1);
+        spawner.spawn(&out.to_string_lossy(), &[]);
+        wait(&spawner);
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn limit_and_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("touched");
+        let spawner = Spawner::new(
+            format!("sleep 10; touch {}", out.display()),
+            Duration::from_millis(100),
+            1,
+        );
+        spawner.spawn("", &[]);
+        spawner.spawn("", &[]);
+        assert_eq!(spawner.running.load(Ordering::SeqCst), 1);
+        let started = Instant::now();
+        wait(&spawner);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn speech_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let hook = |name: &str| {
+            Spawner::new(
+                format!(
+                    r#"echo "{} $SPEECH_START_MS $SPEECH_END_MS $SPEECH_DURATION_MS" >> {}"#,
+                    name,
+                    out.display()
+                ),
+                Duration::from_secs(5),
+                1,
+            )
+        };
+        let (start, end) = (hook("start"), hook("end"));
+        let mut hooks = SpeechHooks::new(
+            Some(start.clone()),
+            Some(end.clone()),
+            Duration::from_secs(60),
+        );
+        hooks.send(&Event::SpeechStarted(Duration::from_millis(1000)));
+        wait(&start);
+        hooks.send(&Event::SpeechStopped(Duration::from_millis(2000)));
+        // Speech resuming within the debounce duration continues the speech in progress.
+        hooks.send(&Event::SpeechStarted(Duration::from_millis(2500)));
+        hooks.send(&Event::SpeechStopped(Duration::from_millis(4000)));
+        hooks.samples(&[0]);
+        hooks.finish();
+        wait(&end);
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "start 1000  \nend 1000 4000 3000\n"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::cache;
+use crate::clock;
+use crate::output::{Event, Offsets, Paragraphs, Utterance};
+use crate::transcript::{Cues, Details, Format};
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// How stored utterances are rendered, each like the option of a live run of the same name.
+pub struct Options {
+    pub format: Format,
+    pub cues: Cues,
+    /// Pause, beyond which utterances are grouped into a new paragraph, if utterances are grouped
+    /// again rather than as they were stored.
+    pub paragraph_gap: Option<Duration>,
+}
+
+/// Parses the sample offsets of an utterance of a JSONL record, if they were output.
+fn offsets(record: &Value) -> Option<Offsets> {
+    Some(Offsets {
+        buffer_start: record["buffer_start"].as_u64()?,
+        speech_start: record["speech_start"].as_u64()?,
+        speech_end: record["speech_end"].as_u64()?,
+        buffer_end: record["buffer_end"].as_u64()?,
+    })
+}
+
+/// Parses the utterance of a JSONL record, returning `None` if it is malformed. Records of
+/// compared utterances yield their primary one.
+fn utterance(record: &Value) -> Option<Utterance> {
+    let record = record.get("primary").unwrap_or(record);
+    let captured_at = match &record["captured_at"] {
+        Value::Null => None,
+        captured_at => Some((
+            clock::parse(captured_at["start"].as_str()?).ok()?,
+            clock::parse(captured_at["end"].as_str()?).ok()?,
+        )),
+    };
+    Some(Utterance {
+        seq: record["seq"].as_u64()?,
+        text: record["text"].as_str()?.to_string(),
+        speaker: record["speaker"].as_str().map(str::to_string),
+        confidence: record["confidence"].as_f64()?,
+        start: Duration::from_millis(record["start_ms"].as_u64()?),
+        end: Duration::from_millis(record["end_ms"].as_u64()?),
+        start_sample: record["start_sample"].as_u64()?,
+        end_sample: record["end_sample"].as_u64()?,
+        offsets: offsets(record),
+        captured_at,
+        fingerprint: None,
+        loudness: None,
+        recording: record["recording"].as_str().map(Into::into),
+        censored: record["censored"].as_bool().unwrap_or(false),
+        low_confidence: record["low_confidence"].as_bool().unwrap_or(false),
+        paragraph: record["paragraph"].as_u64(),
+        comparison: None,
+        intent: record.get("intent").and_then(cache::intent),
+        duplicate: record["duplicate"].as_bool().unwrap_or(false),
+        rescored: record["rescored"].as_bool().unwrap_or(false),
+        speech: Duration::default(),
+    })
+}
+
+/// Reads the utterances of a JSONL transcript, e.g. written with `--output session.jsonl:jsonl`,
+/// in the order they were output. Blank lines are skipped.
+pub fn jsonl(path: &Path) -> io::Result<Vec<Utterance>> {
+    let invalid = |line: usize, err: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {}: {}", line + 1, err),
+        )
+    };
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let record: Value =
+                serde_json::from_str(line).map_err(|err| invalid(i, err.to_string()))?;
+            utterance(&record).ok_or_else(|| invalid(i, "malformed utterance".into()))
+        })
+        .collect()
+}
+
+/// Renders `utterances` of a session captured before with `options`, like the output of the live
+/// run would have been. The session is taken to have started at the time the first utterance was
+/// captured at, less its offset, and to have ended with the last utterance.
+pub fn render(mut utterances: Vec<Utterance>, options: &Options) -> String {
+    if let Some(gap) = options.paragraph_gap {
+        let mut paragraphs = Paragraphs::new(gap);
+        utterances = utterances
+            .into_iter()
+            .map(|utterance| {
+                let mut event = Event::Final(utterance);
+                paragraphs.assign(&mut event);
+                match event {
+                    Event::Final(utterance) => utterance,
+                    _ => unreachable!("paragraphs are assigned to final utterances only"),
+                }
+            })
+            .collect();
+    }
+    let date = utterances
+        .iter()
+        .find_map(|utterance| {
+            let (start, _) = utterance.captured_at?;
+            start.checked_sub(utterance.start)
+        })
+        .unwrap_or_else(SystemTime::now);
+    let details = Details {
+        duration: utterances
+            .iter()
+            .map(|utterance| utterance.end)
+            .max()
+            .unwrap_or_default(),
+        date,
+        model: None,
+        cues: options.cues,
+    };
+    options.format.render(&utterances, &details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [Format; 8] = [
+        Format::Txt,
+        Format::Srt,
+        Format::Vtt,
+        Format::Json,
+        Format::Audacity,
+        Format::Textgrid,
+        Format::Markdown,
+        Format::Ffmetadata,
+    ];
+
+    /// Returns utterances of a live session started at `date`, grouped into paragraphs by
+    /// pauses longer than a second, if `grouped`.
+    fn session(date: SystemTime, grouped: bool) -> Vec<Utterance> {
+        let mut paragraphs = Paragraphs::new(Duration::from_secs(1));
+        [
+            (500, 2000, "good morning everyone", Some("alice")),
+            (
+                2200,
+                9800,
+                "let us start with the numbers of the last quarter which were better than expected",
+                None,
+            ),
+            (12000, 13000, "any questions", Some("bob")),
+            (13500, 14000, "", None),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(start_ms, end_ms, text, speaker))| {
+            let mut utterance = Utterance::test(text);
+            utterance.seq = i as u64 + 1;
+            utterance.speaker = speaker.map(str::to_string);
+            utterance.confidence = -12.5;
+            utterance.start = Duration::from_millis(start_ms);
+            utterance.end = Duration::from_millis(end_ms);
+            utterance.start_sample = start_ms * 16;
+            utterance.end_sample = end_ms * 16;
+            utterance.offsets = Some(Offsets {
+                buffer_start: start_ms * 16 - 4800,
+                speech_start: start_ms * 16,
+                speech_end: end_ms * 16,
+                buffer_end: end_ms * 16 + 4800,
+            });
+            utterance.captured_at = Some((date + utterance.start, date + utterance.end));
+            let mut event = Event::Final(utterance);
+            if grouped {
+                paragraphs.assign(&mut event);
+            }
+            match event {
+                Event::Final(utterance) => utterance,
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+    }
+
+    /// Renders `utterances` in `format` like a live run started at `date` does.
+    fn live(utterances: &[Utterance], format: Format, date: SystemTime) -> String {
+        let details = Details {
+            duration: utterances.last().map(|utterance| utterance.end).unwrap(),
+            date,
+            model: None,
+            cues: Cues::default(),
+        };
+        format.render(utterances, &details)
+    }
+
+    /// Writes `utterances` as a JSONL transcript like `--output session.jsonl:jsonl` does and
+    /// reads them back.
+    fn stored(utterances: &[Utterance]) -> Vec<Utterance> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let lines: String = utterances
+            .iter()
+            .map(|utterance| format!("{}\n", utterance.to_json()))
+            .collect();
+        fs::write(&path, lines + "\n").unwrap();
+        jsonl(&path).unwrap()
+    }
+
+    #[test]
+    fn render_like_live() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        for grouped in [false, true] {
+            let utterances = session(date, grouped);
+            let stored = stored(&utterances);
+            for format in FORMATS {
+                let options = Options {
+                    format,
+                    cues: Cues::default(),
+                    paragraph_gap: None,
+                };
+                assert_eq!(
+                    render(stored.clone(), &options),
+                    live(&utterances, format, date),
+                    "{:?}",
+                    format
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn regroup_like_live() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let stored = stored(&session(date, false));
+        let grouped = session(date, true);
+        for format in FORMATS {
+            let options = Options {
+                format,
+                cues: Cues::default(),
+                paragraph_gap: Some(Duration::from_secs(1)),
+            };
+            assert_eq!(
+                render(stored.clone(), &options),
+                live(&grouped, format, date),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        fs::write(&path, "{\"text\": \"hello\"}\n").unwrap();
+        let err = jsonl(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "line 1: malformed utterance");
+        fs::write(&path, "\n\nnot json\n").unwrap();
+        assert!(jsonl(&path)
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("line 3: "));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::Error;
+use std::{
+    io::{self, BufReader, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+use tracing::debug;
+
+/// Why ffmpeg failed to decode a file, once it exited.
+#[derive(Clone, Default)]
+pub struct Status(Arc<Mutex<Option<String>>>);
+
+impl Status {
+    /// Returns the error ffmpeg failed with, if it did.
+    pub fn failure(&self) -> Option<String> {
+        self.0.lock().expect("Failed to lock ffmpeg status").clone()
+    }
+}
+
+/// Spawns ffmpeg decoding `path` into mono 16-bit samples at `sample_rate`, failing with a hint
+/// naming `required_by`, which requires ffmpeg, if it is not installed.
+pub fn spawn(path: &Path, sample_rate: u32, required_by: &str) -> Result<Decoder, Error> {
+    Decoder::spawn(path, sample_rate).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Error::Usage(format!(
+            "ffmpeg was not found, which {} requires, install it or add it to PATH",
+            required_by
+        )),
+        _ => Error::Input(format!("Failed to spawn ffmpeg: {}", err)),
+    })
+}
+
+/// Mono samples decoded by ffmpeg, which are read from its stdout as they are pulled.
+/// ffmpeg is killed once the decoder is dropped, e.g. when the pipeline stops early.
+pub struct Decoder {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    /// Collects the error output of ffmpeg, which is joined once stdout is exhausted.
+    stderr: Option<JoinHandle<String>>,
+    status: Status,
+}
+
+impl Decoder {
+    /// Spawns ffmpeg decoding `path` into mono 16-bit samples at `sample_rate`.
+    pub fn spawn(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-f", "s16le", "-ac", "1", "-ar"])
+            .arg(sample_rate.to_string())
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        debug!(path = %path.display(), "Spawned ffmpeg");
+        let stdout = child.stdout.take().expect("Failed to open ffmpeg stdout");
+        let mut stderr = child.stderr.take().expect("Failed to open ffmpeg stderr");
+        let stderr = thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        });
+        Ok(Self {
+            child,
+            stdout: BufReader::new(stdout),
+            stderr: Some(stderr),
+            status: Status::default(),
+        })
+    }
+
+    /// Returns the status, which records why ffmpeg failed, once it exited.
+    pub fn status(&self) -> Status {
+        self.status.clone()
+    }
+
+    /// Reads all samples, failing with the error output of ffmpeg if it failed.
+    pub fn read_all(mut self) -> Result<Vec<i16>, Error> {
+        let samples = self.by_ref().collect();
+        match self.status.failure() {
+            Some(err) => Err(Error::Input(err)),
+            None => Ok(samples),
+        }
+    }
+
+    /// Waits for ffmpeg to exit, recording why it failed, if it did.
+    fn finish(&mut self) {
+        let stderr = match self.stderr.take() {
+            Some(stderr) => stderr.join().unwrap_or_default(),
+            None => return,
+        };
+        let failure = match self.child.wait() {
+            Ok(status) if status.success() => return,
+            Ok(status) => format!("ffmpeg exited with {}: {}", status, stderr.trim()),
+            Err(err) => format!("Failed to wait for ffmpeg: {}", err),
+        };
+        *self.status.0.lock().expect("Failed to lock ffmpeg status") = Some(failure);
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let mut sample = [0; 2];
+        match self.stdout.read_exact(&mut sample) {
+            Ok(()) => Some(i16::from_le_bytes(sample)),
+            Err(_) => {
+                self.finish();
+                None
+            }
+        }
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        if self.stderr.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::output::{Event, Sink};
+use std::{
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::{ffi::OsStrExt, fs::FileTypeExt},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, SyncSender},
+    thread,
+};
+use tracing::{error, warn};
+
+/// Maximum number of transcripts buffered while no reader is attached.
+const QUEUE_CAPACITY: usize = 64;
+
+fn create(path: &Path) -> io::Result<()> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => return Ok(()),
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a FIFO", path.display()),
+            ))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o644) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Writes final transcripts to a named pipe, one per line.
+/// Opening the pipe blocks until a reader attaches, so it happens on a separate thread, which
+/// reopens the pipe whenever the reader goes away.
+pub struct Fifo {
+    lines: SyncSender<String>,
+}
+
+impl Fifo {
+    /// Creates the FIFO at `path` if missing.
+    pub fn create(path: PathBuf) -> io::Result<Self> {
+        create(&path)?;
+
+        let (lines, rx) = mpsc::sync_channel::<String>(QUEUE_CAPACITY);
+        thread::spawn(move || {
+            let mut pending = None;
+            loop {
+                let mut fifo = match OpenOptions::new().write(true).open(&path) {
+                    Ok(fifo) => fifo,
+                    Err(err) => {
+                        error!(path = %path.display(), error = %err, "Failed to open FIFO");
+                        return;
+                    }
+                };
+                loop {
+                    let line = match pending.take() {
+                        Some(line) => line,
+                        None => match rx.recv() {
+                            Ok(line) => line,
+                            Err(_) => return,
+                        },
+                    };
+                    if fifo.write_all(line.as_bytes()).is_err() {
+                        // The reader went away (EPIPE), retry the line once the next one attaches.
+                        pending = Some(line);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { lines })
+    }
+}
+
+impl Sink for Fifo {
+    fn send(&mut self, event: &Event) {
+        if let Event::Final(utterance) = event {
+            if self
+                .lines
+                .try_send(format!("{}\n", utterance.text))
+                .is_err()
+            {
+                warn!("No reader attached to FIFO, dropping transcript");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader},
+        time::Duration,
+    };
+
+    #[test]
+    fn create_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcripts");
+        create(&path).unwrap();
+        assert!(fs::metadata(&path).unwrap().file_type().is_fifo());
+        create(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        fs::write(&path, "").unwrap();
+        assert_eq!(
+            create(&path).err().unwrap().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+    }
+
+    #[test]
+    fn write_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcripts");
+        let mut fifo = Fifo::create(path.clone()).unwrap();
+        let mut reader = BufReader::new(File::open(&path).unwrap());
+        for text in ["hello", "world"] {
+            fifo.send(&Event::Final(Utterance {
+                confidence: 0.5,
+                start: Duration::default(),
+                end: Duration::default(),
+                ..Utterance::test(text)
+            }));
+        }
+        let mut lines = String::new();
+        reader.read_line(&mut lines).unwrap();
+        reader.read_line(&mut lines).unwrap();
+        assert_eq!(lines, "hello\nworld\n");
+    }
+}
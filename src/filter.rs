@@ -0,0 +1,372 @@
+use crate::output::{Event, Sink, Utterance};
+use crate::segmenter::Segment;
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    str::FromStr,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// Time waited before restarting a filter, which failed, doubling with every failure in a row.
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Format of the lines exchanged with a filter.
+#[derive(Clone, Copy)]
+pub enum Format {
+    /// The transcript, which the filter responds to with the filtered transcript.
+    Text,
+    /// The utterance as JSON, which the filter responds to with an object with the `text` of the
+    /// filtered transcript.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "failed to parse `{}` into filter format of text or json",
+                s
+            )),
+        }
+    }
+}
+
+/// External command, which final transcripts are filtered through.
+pub struct Options {
+    /// Command executed via `sh -c`.
+    pub command: String,
+    pub format: Format,
+    /// Time the filter has to respond to a transcript, after which it is output unfiltered.
+    pub timeout: Duration,
+}
+
+/// Replaces final transcripts with their filtered version before forwarding events to `sink`.
+/// Transcripts are filtered on a separate thread and the events following one being filtered are
+/// held back, so that a slow filter delays the output of transcripts in order, but never the
+/// pipeline reading audio.
+pub struct Filter<S> {
+    /// Sends transcripts to and receives filtered ones from the worker, if filtering.
+    worker: Option<(Sender<Utterance>, Receiver<String>)>,
+    /// Events held back in order, flagged if their transcript is being filtered.
+    held: VecDeque<(Event, bool)>,
+    pub sink: S,
+}
+
+impl<S> Filter<S> {
+    /// Spawns the filter of `options` along with the thread it is fed from, forwarding events to
+    /// `sink` unchanged if `options` is `None`.
+    pub fn spawn(options: Option<Options>, sink: S) -> Self {
+        let worker = options.map(|options| {
+            let (requests, requests_rx) = mpsc::channel();
+            let (results_tx, results) = mpsc::channel();
+            thread::spawn(move || work(options, requests_rx, results_tx));
+            (requests, results)
+        });
+        Self {
+            worker,
+            held: VecDeque::new(),
+            sink,
+        }
+    }
+}
+
+impl<S: Sink> Filter<S> {
+    /// Assigns the filtered transcripts received so far, waiting for all of them if `wait`, and
+    /// forwards the events no longer held back.
+    fn release(&mut self, wait: bool) {
+        if let Some((_, results)) = &self.worker {
+            while let Some((event, waiting)) = self.held.iter_mut().find(|(_, waiting)| *waiting) {
+                let text = if wait {
+                    results.recv().ok()
+                } else {
+                    match results.try_recv() {
+                        Ok(text) => Some(text),
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Err(mpsc::TryRecvError::Disconnected) => None,
+                    }
+                };
+                // Transcripts are output unfiltered if the worker is gone.
+                if let (Event::Final(utterance), Some(text)) = (event, text) {
+                    utterance.text = text;
+                }
+                *waiting = false;
+            }
+        }
+        while let Some((_, false)) = self.held.front() {
+            if let Some((event, _)) = self.held.pop_front() {
+                self.sink.send(&event);
+            }
+        }
+    }
+}
+
+impl<S: Sink> Sink for Filter<S> {
+    fn send(&mut self, event: &Event) {
+        let requests = match &self.worker {
+            Some((requests, _)) => requests,
+            None => return self.sink.send(event),
+        };
+        match event {
+            Event::Final(utterance) => {
+                let waiting = requests.send(utterance.clone()).is_ok();
+                self.held
+                    .push_back((Event::Final(utterance.clone()), waiting));
+            }
+            event if self.held.is_empty() => self.sink.send(event),
+            event => self.held.push_back((event.clone(), false)),
+        }
+        self.release(false)
+    }
+
+    /// Forwards `samples` and the transcripts filtered in the meantime.
+    fn samples(&mut self, samples: &[i16]) {
+        self.release(false);
+        self.sink.samples(samples)
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        self.sink.segment(segment)
+    }
+
+    /// Waits for the transcripts still being filtered, each for at most the timeout.
+    fn finish(&mut self) {
+        self.release(true);
+        self.sink.finish()
+    }
+}
+
+/// Running filter.
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines read from the stdout of the filter.
+    lines: Receiver<io::Result<String>>,
+}
+
+impl Process {
+    fn spawn(command: &str) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("Failed to open filter stdin");
+        let stdout = child.stdout.take().expect("Failed to open filter stdout");
+        let (lines_tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if lines_tx.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    /// Writes `line` to the filter, returning the line it responds with within `timeout`.
+    fn filter(&mut self, line: &str, timeout: Duration) -> Result<String, String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("failed to write to filter: {}", err))?;
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => line.map_err(|err| format!("failed to read from filter: {}", err)),
+            Err(RecvTimeoutError::Timeout) => {
+                Err(format!("filter did not respond within {:?}", timeout))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err("filter exited".into()),
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Filters the transcripts received on `requests`, sending the filtered transcripts to `results`
+/// in order until either is closed.
+/// The filter is spawned right away and restarted with backoff, once it failed, in the meantime
+/// transcripts are passed through unfiltered.
+fn work(options: Options, requests: Receiver<Utterance>, results: Sender<String>) {
+    let mut process = None;
+    let mut backoff = MIN_BACKOFF;
+    let mut retry_at = Instant::now();
+    let mut requests = requests.iter();
+    loop {
+        if process.is_none() && Instant::now() >= retry_at {
+            match Process::spawn(&options.command) {
+                Ok(spawned) => process = Some(spawned),
+                Err(err) => {
+                    warn!(
+                        command = %options.command,
+                        error = %err,
+                        retry_in = ?backoff,
+                        "Failed to spawn filter, passing transcripts through unfiltered"
+                    );
+                    retry_at = Instant::now() + backoff;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+        let utterance = match requests.next() {
+            Some(utterance) => utterance,
+            None => return,
+        };
+        let text = match &mut process {
+            Some(running) => match running
+                .filter(&request(&utterance, options.format), options.timeout)
+                .and_then(|line| response(&line, options.format))
+            {
+                Ok(text) => {
+                    backoff = MIN_BACKOFF;
+                    text
+                }
+                Err(err) => {
+                    warn!(
+                        command = %options.command,
+                        error = %err,
+                        retry_in = ?backoff,
+                        "Failed to filter transcript, passing transcripts through unfiltered"
+                    );
+                    // A filter, which failed to respond, may respond late and thereby to the
+                    // wrong transcript, so it is restarted.
+                    process = None;
+                    retry_at = Instant::now() + backoff;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    utterance.text
+                }
+            },
+            None => {
+                debug!("Filter is not running, passing transcript through unfiltered");
+                utterance.text
+            }
+        };
+        if results.send(text).is_err() {
+            return;
+        }
+    }
+}
+
+/// Returns the line written to the filter for `utterance`.
+fn request(utterance: &Utterance, format: Format) -> String {
+    match format {
+        Format::Text => utterance.text.replace(['\n', '\r'], " "),
+        Format::Json => utterance.to_json().to_string(),
+    }
+}
+
+/// Returns the filtered transcript of the line the filter responded with.
+fn response(line: &str, format: Format) -> Result<String, String> {
+    match format {
+        Format::Text => Ok(line.trim_end_matches('\r').to_string()),
+        Format::Json => {
+            let value: Value = serde_json::from_str(line)
+                .map_err(|err| format!("failed to parse filter output as JSON: {}", err))?;
+            value
+                .get("text")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| "filter output lacks a `text` string".into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Describes the events sent.
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn send(&mut self, event: &Event) {
+            self.0.push(match event {
+                Event::Final(utterance) => utterance.text.clone(),
+                Event::Mark(_, label) => format!("# {}", label),
+                _ => "other".to_string(),
+            })
+        }
+    }
+
+    fn filter(command: &str, format: Format) -> Filter<Events> {
+        Filter::spawn(
+            Some(Options {
+                command: command.into(),
+                format,
+                timeout: Duration::from_secs(5),
+            }),
+            Events::default(),
+        )
+    }
+
+    #[test]
+    fn lines() {
+        let utterance = Utterance::test("two\nlines");
+        assert_eq!(request(&utterance, Format::Text), "two lines");
+        let json: Value = serde_json::from_str(&request(&utterance, Format::Json)).unwrap();
+        assert_eq!(json["text"], "two\nlines");
+
+        assert_eq!(response("hi\r", Format::Text).unwrap(), "hi");
+        assert_eq!(
+            response(r#"{"text":"hi","extra":1}"#, Format::Json).unwrap(),
+            "hi"
+        );
+        assert!(response(r#"{"txt":"hi"}"#, Format::Json).is_err());
+        assert!(response("hi", Format::Json).is_err());
+    }
+
+    #[test]
+    fn in_order() {
+        let mut filter = filter(
+            r#"while read -r line; do echo "$line" | tr a-z A-Z; done"#,
+            Format::Text,
+        );
+        filter.send(&Event::Final(Utterance::test("hello")));
+        // Events following a transcript being filtered are held back.
+        filter.send(&Event::Mark(Duration::default(), "mark".into()));
+        filter.send(&Event::Final(Utterance::test("world")));
+        filter.finish();
+        assert_eq!(filter.sink.0, ["HELLO", "# mark", "WORLD"]);
+    }
+
+    #[test]
+    fn json() {
+        let mut filter = filter(
+            r#"while read -r line; do echo '{"text":"filtered"}'; done"#,
+            Format::Json,
+        );
+        filter.send(&Event::Final(Utterance::test("hello")));
+        filter.finish();
+        assert_eq!(filter.sink.0, ["filtered"]);
+    }
+
+    #[test]
+    fn unfiltered() {
+        let mut filter = filter("exit 1", Format::Text);
+        filter.send(&Event::Final(Utterance::test("hello")));
+        filter.finish();
+        assert_eq!(filter.sink.0, ["hello"]);
+
+        let mut passthrough = Filter::spawn(None, Events::default());
+        passthrough.send(&Event::Final(Utterance::test("hello")));
+        assert_eq!(passthrough.sink.0, ["hello"]);
+    }
+}
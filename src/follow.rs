@@ -0,0 +1,188 @@
+use crate::clock::Anchor;
+use crate::control::Control;
+use crate::device;
+use crate::error::Error;
+use crate::info;
+use crate::resample::Resampler;
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde_json::Value;
+use std::{
+    sync::mpsc::Sender,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Interval, at which the default input device is polled, since cpal does not notify about
+/// changes of it.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Samples yielded between checks whether the default input device changed.
+const CHECK_SAMPLES: usize = 1024;
+
+/// How audio is captured from the default input device.
+#[derive(Clone)]
+pub struct Options {
+    pub sample_rate: u32,
+    pub resampler: Resampler,
+    pub dither: bool,
+    pub realtime: bool,
+    pub anchor: Anchor,
+}
+
+/// Captures from the default input device of the default host, switching to the new default
+/// device whenever it changes, e.g. once a headset is plugged in or a laptop is docked.
+/// Buffered speech is transcribed before the switch. The time it takes is filled with silence,
+/// so that offsets and capture times of later samples stay aligned with the time they were
+/// captured at. Switches are output as marks via `controls`.
+pub struct Follower {
+    options: Options,
+    name: Option<String>,
+    /// Name of the new default device, once it changed, set by the polling thread.
+    changed: Arc<Mutex<Option<String>>>,
+    _stream: cpal::Stream,
+    samples: Box<dyn Iterator<Item = i16>>,
+    /// Samples to yield until it is checked again whether the default device changed.
+    countdown: usize,
+    /// Samples of silence to yield in place of the audio missed while switching.
+    silence: usize,
+    controls: Sender<Control>,
+}
+
+/// Stream capturing from the default input device.
+struct Capture {
+    name: Option<String>,
+    config: cpal::SupportedStreamConfig,
+    stream: cpal::Stream,
+    samples: Box<dyn Iterator<Item = i16>>,
+}
+
+/// Starts capturing from the default input device with `options`.
+fn open(options: &Options) -> Result<Capture, Error> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or_else(|| Error::Device("Failed to find default device".into()))?;
+    let config = device::input_config(&device, options.sample_rate)?;
+    let (stream, samples) = device::capture(
+        &device,
+        &config,
+        options.sample_rate,
+        options.resampler,
+        options.dither,
+        options.realtime,
+        options.anchor.clone(),
+    )?;
+    Ok(Capture {
+        name: device.name().ok(),
+        config,
+        stream,
+        samples,
+    })
+}
+
+impl Follower {
+    /// Starts capturing from the default input device, which is added to `report`, if set.
+    pub fn open(
+        options: Options,
+        controls: Sender<Control>,
+        report: &mut Option<Value>,
+    ) -> Result<Self, Error> {
+        let Capture {
+            name,
+            config,
+            stream,
+            samples,
+        } = open(&options)?;
+        if let Some(report) = report {
+            report["device"] = info::device(name.clone(), &config);
+        }
+        let changed = Arc::new(Mutex::new(None));
+        let polled = changed.clone();
+        let mut current = name.clone();
+        thread::spawn(move || {
+            // Hosts may not be sent to other threads, so the thread polls one of its own.
+            let host = cpal::default_host();
+            // The thread stops once the follower is dropped.
+            while Arc::strong_count(&polled) > 1 {
+                thread::sleep(POLL_INTERVAL);
+                let name = match host.default_input_device() {
+                    Some(device) => device.name().ok(),
+                    // Devices are switched once a new default one appears.
+                    None => continue,
+                };
+                if name.is_some() && name != current {
+                    current = name.clone();
+                    *polled.lock().expect("Failed to lock default device") = name;
+                }
+            }
+        });
+        Ok(Self {
+            options,
+            name,
+            changed,
+            _stream: stream,
+            samples,
+            countdown: CHECK_SAMPLES,
+            silence: 0,
+            controls,
+        })
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Switches to the default input device, keeping the current one if it fails to open.
+    fn switch(&mut self, to: String) {
+        info!(from = ?self.name, %to, "Default input device changed, switching to it");
+        // Speech in progress is transcribed from the previous device only.
+        let _ = self.controls.send(Control::Flush);
+        let started = Instant::now();
+        match open(&self.options) {
+            Ok(capture) => {
+                // The previous stream stops, once replaced.
+                self._stream = capture.stream;
+                self.samples = capture.samples;
+                self.silence = (started.elapsed().as_micros() as u64
+                    * self.options.sample_rate as u64
+                    / 1_000_000) as usize;
+                let label = format!(
+                    "input device changed to {}",
+                    capture.name.as_deref().unwrap_or("unknown device")
+                );
+                self.name = capture.name;
+                let _ = self.controls.send(Control::Mark(label));
+            }
+            Err(err) => warn!(
+                %to,
+                error = %err,
+                "Failed to switch input device, keeping the previous one"
+            ),
+        }
+    }
+}
+
+impl Iterator for Follower {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.silence > 0 {
+            self.silence -= 1;
+            return Some(0);
+        }
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.countdown = CHECK_SAMPLES;
+            let changed = self
+                .changed
+                .lock()
+                .expect("Failed to lock default device")
+                .take();
+            if let Some(name) = changed {
+                self.switch(name);
+            }
+        }
+        self.samples.next()
+    }
+}
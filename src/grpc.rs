@@ -0,0 +1,524 @@
+use crate::error::Error;
+use crate::output::{self, Event, Sink};
+use crate::pipeline::{self, Config};
+use crate::pool::Pool;
+use crate::stable::Stabilize;
+#[cfg(feature = "tls")]
+use crate::tls::Certificates;
+use proto::transcriber_server::{Transcriber, TranscriberServer};
+use proto::transcript_event::Kind;
+use proto::{AudioChunk, InfoRequest, InfoResponse, TranscriptEvent};
+#[cfg(feature = "tls")]
+use std::io;
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+#[cfg(feature = "tls")]
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(feature = "tls")]
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use tracing::{error, info, warn};
+
+type EventSender = tokio::sync::mpsc::Sender<Result<TranscriptEvent, Status>>;
+
+/// Application protocol negotiated with ALPN by connections over TLS, which gRPC requires.
+#[cfg(feature = "tls")]
+const ALPN: &[&[u8]] = &[b"h2"];
+
+/// Time a connection over TLS is given to complete its handshake.
+#[cfg(feature = "tls")]
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of connections, whose handshake completed, waiting to be served.
+#[cfg(feature = "tls")]
+const ACCEPT_BACKLOG: usize = 128;
+
+pub mod proto {
+    tonic::include_proto!("speech2text");
+}
+
+impl From<&Event> for TranscriptEvent {
+    fn from(event: &Event) -> Self {
+        let (kind, text, start, end, seq) = match event {
+            Event::Ready => (Kind::Ready, "", Duration::default(), Duration::default(), 0),
+            Event::SpeechStarted(at) => (Kind::SpeechStarted, "", *at, *at, 0),
+            Event::SpeechStopped(at) => (Kind::SpeechStopped, "", *at, *at, 0),
+            Event::Partial(u) => (Kind::Partial, u.text.as_str(), u.start, u.end, u.seq),
+            Event::StablePartial(u) => {
+                (Kind::StablePartial, u.text.as_str(), u.start, u.end, u.seq)
+            }
+            Event::Final(u) | Event::Dropped(u) => {
+                (Kind::Final, u.text.as_str(), u.start, u.end, u.seq)
+            }
+            Event::Mark(at, label) => (Kind::Mark, label.as_str(), *at, *at, 0),
+            Event::Heartbeat(_) => unreachable!("heartbeats are not streamed"),
+        };
+        let speaker = match event {
+            Event::Partial(u) | Event::StablePartial(u) | Event::Final(u) => {
+                u.speaker.clone().unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        Self {
+            kind: kind as i32,
+            text: text.to_string(),
+            start_ms: start.as_millis() as u64,
+            end_ms: end.as_millis() as u64,
+            seq,
+            session_id: output::session_id(),
+            speaker,
+        }
+    }
+}
+
+/// Forwards events to the response stream of an RPC.
+struct ResponseSink(EventSender);
+
+impl Sink for ResponseSink {
+    fn send(&mut self, event: &Event) {
+        if let Event::Dropped(_) | Event::Heartbeat(_) = event {
+            return;
+        }
+        // Sending only fails if the client went away, in which case the pipeline finishes as
+        // soon as the request stream is closed.
+        let _ = self.0.blocking_send(Ok(event.into()));
+    }
+}
+
+struct Service {
+    pool: Arc<Pool>,
+    config: Config,
+    /// Number of partial transcripts, across which words need to remain unchanged to be sent as
+    /// stable partials in place of partials, if set.
+    stable: Option<usize>,
+    streams: Streams,
+}
+
+/// Response streams of the transcriptions in flight, which are failed if they do not complete in
+/// time once the server drains.
+#[derive(Clone, Default)]
+struct Streams {
+    next: Arc<AtomicU64>,
+    senders: Arc<Mutex<HashMap<u64, EventSender>>>,
+}
+
+impl Streams {
+    fn insert(&self, sender: EventSender) -> u64 {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, sender);
+        id
+    }
+
+    fn remove(&self, id: u64) {
+        self.lock().remove(&id);
+    }
+
+    /// Ends every stream still in flight with `status`, returning their number.
+    fn abort(&self, status: Status) -> usize {
+        let senders = std::mem::take(&mut *self.lock());
+        for sender in senders.values() {
+            let _ = sender.try_send(Err(status.clone()));
+        }
+        senders.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, EventSender>> {
+        self.senders
+            .lock()
+            .expect("Failed to lock response streams")
+    }
+}
+
+#[tonic::async_trait]
+impl Transcriber for Service {
+    async fn info(&self, _: Request<InfoRequest>) -> Result<Response<InfoResponse>, Status> {
+        Ok(Response::new(InfoResponse {
+            sample_rate: self.config.sample_rate,
+        }))
+    }
+
+    type TranscribeStream = ReceiverStream<Result<TranscriptEvent, Status>>;
+
+    async fn transcribe(
+        &self,
+        request: Request<Streaming<AudioChunk>>,
+    ) -> Result<Response<Self::TranscribeStream>, Status> {
+        let pool = self.pool.clone();
+        let checkout = tokio::task::spawn_blocking(move || pool.checkout())
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+            .ok_or_else(|| {
+                warn!("No model became available in time, rejecting stream");
+                Status::resource_exhausted("all models are in use")
+            })?;
+        let mut chunks = request.into_inner();
+
+        let (sample_tx, sample_rx) = mpsc::channel::<Vec<i16>>();
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(64);
+
+        let config = self.config.clone();
+        let stable = self.stable;
+        let streams = self.streams.clone();
+        let id = streams.insert(event_tx.clone());
+        thread::spawn(move || {
+            let (_, controls) = mpsc::channel();
+            let mut sink: Box<dyn Sink> = match stable {
+                Some(count) => Box::new(Stabilize::new(count, ResponseSink(event_tx))),
+                None => Box::new(ResponseSink(event_tx)),
+            };
+            if let Err(err) = pipeline::run(
+                sample_rx.into_iter().flatten(),
+                vec![checkout.model()],
+                &config,
+                &controls,
+                sink.as_mut(),
+            ) {
+                error!(error = %err, "Failed to transcribe stream");
+            }
+            streams.remove(id);
+            // The model is returned to the pool only once the stream completed.
+            drop(checkout);
+        });
+        tokio::spawn(async move {
+            loop {
+                match chunks.message().await {
+                    Ok(Some(chunk)) => {
+                        let samples = chunk
+                            .pcm
+                            .chunks_exact(2)
+                            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                            .collect();
+                        if sample_tx.send(samples).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        warn!(error = %err, "Failed to receive audio chunk");
+                        break;
+                    }
+                }
+            }
+            // Dropping `sample_tx` flushes the pipeline, which closes the response stream once
+            // the buffered speech is transcribed.
+        });
+        Ok(Response::new(ReceiverStream::new(event_rx)))
+    }
+}
+
+/// Options of the gRPC server.
+pub struct Options {
+    pub config: Config,
+    /// Number of partial transcripts, across which words need to remain unchanged to be sent as
+    /// stable partials in place of partials, if set.
+    pub stable: Option<usize>,
+    /// Time the transcriptions in flight are given to complete, once the server drains.
+    pub grace: Duration,
+    /// Certificate the server is served over TLS with, if set.
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<Certificates>>,
+}
+
+/// Serves the gRPC transcription service on `addr` until SIGTERM or Ctrl-C is received.
+/// The server then drains: it stops accepting connections and waits up to the grace period for
+/// the transcriptions in flight to complete and deliver their responses, before closing the
+/// connections. Transcriptions still in flight after it are ended with `UNAVAILABLE` and
+/// `Error::Drain` is returned.
+/// Each stream decodes with a model checked out of `pool`, failing with `RESOURCE_EXHAUSTED`
+/// if none becomes available in time.
+pub fn serve(addr: SocketAddr, pool: Arc<Pool>, options: Options) -> Result<(), Error> {
+    let streams = Streams::default();
+    let service = TranscriberServer::new(Service {
+        pool,
+        config: options.config,
+        stable: options.stable,
+        streams: streams.clone(),
+    });
+    let grace = options.grace;
+    #[cfg(feature = "tls")]
+    let tls = options.tls;
+    tokio::runtime::Runtime::new()
+        .map_err(Error::with(Error::Other, "Failed to create Tokio runtime"))?
+        .block_on(async move {
+            let (draining_tx, draining) = tokio::sync::oneshot::channel();
+            let shutdown = async move {
+                terminated().await;
+                let _ = draining_tx.send(());
+            };
+            let router = Server::builder().add_service(service);
+            #[cfg(feature = "tls")]
+            if let Some(certificates) = tls {
+                let incoming = incoming(addr, certificates.server_config(ALPN)).await?;
+                info!(%addr, "Serving gRPC over TLS");
+                let server = router.serve_with_incoming_shutdown(incoming, shutdown);
+                return drain(server, draining, grace, streams).await;
+            }
+            drain(
+                router.serve_with_shutdown(addr, shutdown),
+                draining,
+                grace,
+                streams,
+            )
+            .await
+        })
+}
+
+/// Serves `server` until `draining` fires, after which the transcriptions in flight are given
+/// up to `grace` to complete, before they are aborted.
+async fn drain(
+    server: impl Future<Output = Result<(), tonic::transport::Error>>,
+    draining: tokio::sync::oneshot::Receiver<()>,
+    grace: Duration,
+    streams: Streams,
+) -> Result<(), Error> {
+    tokio::pin!(server);
+    tokio::select! {
+        result = &mut server => {
+            return result.map_err(Error::with(Error::Other, "Failed to serve gRPC"))
+        }
+        _ = draining => {}
+    }
+    info!(grace = ?grace, "Draining gRPC server");
+    match tokio::time::timeout(grace, server).await {
+        Ok(result) => {
+            info!("Drained gRPC server");
+            result.map_err(Error::with(Error::Other, "Failed to serve gRPC"))
+        }
+        Err(_) => {
+            let aborted = streams.abort(Status::unavailable("server is shutting down"));
+            // Gives the aborted streams a chance to deliver their status.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Err(Error::Drain(format!(
+                "{} transcriptions did not complete within the grace period of {:?}",
+                aborted, grace
+            )))
+        }
+    }
+}
+
+/// Accepts connections on `addr`, yielding them once their TLS handshake with `config`
+/// completed. Handshakes run concurrently and connections, whose handshake fails or does not
+/// complete within `HANDSHAKE_TIMEOUT`, are dropped, so that they hold back no other ones.
+#[cfg(feature = "tls")]
+async fn incoming(
+    addr: SocketAddr,
+    config: Arc<rustls::ServerConfig>,
+) -> Result<ReceiverStream<io::Result<TlsStream<TcpStream>>>, Error> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(Error::with(Error::Other, "Failed to bind gRPC server"))?;
+    let acceptor = TlsAcceptor::from(config);
+    let (tx, rx) = tokio::sync::mpsc::channel(ACCEPT_BACKLOG);
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!(error = %err, "Failed to accept gRPC connection");
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        // Sending only fails once the server stopped accepting connections.
+                        let _ = tx.send(Ok(stream)).await;
+                    }
+                    Ok(Err(err)) => warn!(%peer, error = %err, "Failed TLS handshake"),
+                    Err(_) => warn!(%peer, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    Ok(ReceiverStream::new(rx))
+}
+
+/// Waits for SIGTERM or Ctrl-C.
+async fn terminated() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = terminate.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            },
+            Err(err) => {
+                warn!(error = %err, "Failed to handle SIGTERM, draining on Ctrl-C only");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::time::Instant;
+
+    #[test]
+    fn events() {
+        let utterance = || Utterance {
+            confidence: 0.9,
+            start: Duration::from_millis(1500),
+            end: Duration::from_millis(2750),
+            ..Utterance::test("hello world")
+        };
+        for (event, kind, text, start_ms, end_ms) in [
+            (Event::Ready, Kind::Ready, "", 0, 0),
+            (
+                Event::SpeechStarted(Duration::from_millis(1500)),
+                Kind::SpeechStarted,
+                "",
+                1500,
+                1500,
+            ),
+            (
+                Event::SpeechStopped(Duration::from_millis(2750)),
+                Kind::SpeechStopped,
+                "",
+                2750,
+                2750,
+            ),
+            (
+                Event::Partial(utterance()),
+                Kind::Partial,
+                "hello world",
+                1500,
+                2750,
+            ),
+            (
+                Event::Final(utterance()),
+                Kind::Final,
+                "hello world",
+                1500,
+                2750,
+            ),
+        ] {
+            let event = TranscriptEvent::from(&event);
+            assert_eq!(event.kind, kind as i32);
+            assert_eq!(event.text, text);
+            assert_eq!((event.start_ms, event.end_ms), (start_ms, end_ms));
+        }
+    }
+
+    #[test]
+    fn unspecified_kind() {
+        assert_eq!(
+            Kind::from_i32(TranscriptEvent::default().kind),
+            Some(Kind::Unspecified)
+        );
+    }
+
+    /// Response of a transcription still in flight once the server drains.
+    fn response() -> TranscriptEvent {
+        (&Event::Final(Utterance::test("still delivered"))).into()
+    }
+
+    /// Drains a server, which is signaled to drain after 50 ms and takes `serving` to deliver
+    /// the response of a transcription in flight and stop, giving it `grace` to complete.
+    /// Returns the result of draining, the response received, if any, and the time it took.
+    fn drain_slow(
+        serving: Duration,
+        grace: Duration,
+    ) -> (
+        Result<(), Error>,
+        Option<Result<TranscriptEvent, Status>>,
+        Duration,
+    ) {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let streams = Streams::default();
+                let (event_tx, mut events) = tokio::sync::mpsc::channel(64);
+                let id = streams.insert(event_tx.clone());
+                let in_flight = streams.clone();
+                let server = async move {
+                    tokio::time::sleep(serving).await;
+                    let _ = event_tx.send(Ok(response())).await;
+                    in_flight.remove(id);
+                    Ok(())
+                };
+                let (draining_tx, draining) = tokio::sync::oneshot::channel();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    let _ = draining_tx.send(());
+                });
+                let started = Instant::now();
+                let result = drain(server, draining, grace, streams).await;
+                let elapsed = started.elapsed();
+                (result, events.recv().await, elapsed)
+            })
+    }
+
+    #[test]
+    fn drain_completes_in_flight() {
+        let (result, response, elapsed) =
+            drain_slow(Duration::from_millis(300), Duration::from_secs(5));
+        assert!(result.is_ok());
+        assert_eq!(response.unwrap().unwrap(), self::response());
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn drain_aborts_after_grace() {
+        let (result, response, elapsed) =
+            drain_slow(Duration::from_secs(60), Duration::from_millis(200));
+        match result {
+            Err(Error::Drain(message)) => assert!(message.starts_with("1 transcriptions")),
+            result => panic!("drained with {:?}", result),
+        }
+        assert_eq!(
+            response.unwrap().unwrap_err().code(),
+            tonic::Code::Unavailable
+        );
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn tls_handshakes() {
+        let (certificates, client) = crate::tls::test(ALPN);
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                use rustls::Session;
+                use tokio_stream::StreamExt;
+
+                let mut incoming = incoming(addr, certificates.server_config(ALPN))
+                    .await
+                    .unwrap();
+                // A client stalled in its handshake holds back no other one.
+                let _stalled = TcpStream::connect(addr).await.unwrap();
+                let localhost = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+                let stream = tokio_rustls::TlsConnector::from(client)
+                    .connect(localhost, TcpStream::connect(addr).await.unwrap())
+                    .await
+                    .unwrap();
+                assert_eq!(stream.get_ref().1.get_alpn_protocol(), Some(&b"h2"[..]));
+                let accepted = tokio::time::timeout(Duration::from_secs(5), incoming.next())
+                    .await
+                    .expect("handshake was held back")
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(accepted.get_ref().1.get_alpn_protocol(), Some(&b"h2"[..]));
+            });
+    }
+}
@@ -0,0 +1,118 @@
+use crate::control::Control;
+use crate::metrics;
+use serde_json::{json, Value};
+use std::{
+    sync::atomic::Ordering,
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Progress of the pipeline since the previous heartbeat.
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    /// Audio processed since the previous heartbeat, which is zero if the input stalled.
+    pub audio: Duration,
+    /// Audio processed since the start of the stream.
+    pub total_audio: Duration,
+    /// Whether voice activity was detected in the last frame processed.
+    pub speech: bool,
+    /// Number of jobs waiting to be decoded.
+    pub queue_depth: u64,
+    /// Number of captured samples dropped since the previous heartbeat.
+    pub dropped_samples: u64,
+    /// Fvad mode voice is detected in, if known, which changes with --vad-adaptive.
+    pub fvad_mode: Option<u8>,
+}
+
+impl Heartbeat {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "event": "heartbeat",
+            "audio_ms": self.audio.as_millis() as u64,
+            "total_audio_ms": self.total_audio.as_millis() as u64,
+            "speech": self.speech,
+            "queue_depth": self.queue_depth,
+            "dropped_samples": self.dropped_samples,
+            "fvad_mode": self.fvad_mode,
+        })
+    }
+}
+
+/// Emits a heartbeat every `interval` on a separate thread, printing it on stderr and sending it
+/// to `controls`, so that the pipeline outputs it as an event.
+/// Heartbeats are timed by the wall clock, but report the audio processed in between, so that a
+/// stalled input is reported as processing none, even though the pipeline waiting for it only
+/// outputs them once it resumes. The thread stops once the pipeline does.
+pub fn spawn(interval: Duration, controls: Sender<Control>) {
+    thread::spawn(move || {
+        let mut audio = metrics::AUDIO_MICROS.load(Ordering::Relaxed);
+        let mut dropped = metrics::DROPPED_SAMPLES.load(Ordering::Relaxed);
+        let mut next = Instant::now() + interval;
+        loop {
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+            next += interval;
+            let total_audio = metrics::AUDIO_MICROS.load(Ordering::Relaxed);
+            let total_dropped = metrics::DROPPED_SAMPLES.load(Ordering::Relaxed);
+            let heartbeat = Heartbeat {
+                audio: Duration::from_micros(total_audio - audio),
+                total_audio: Duration::from_micros(total_audio),
+                speech: metrics::SPEECH.load(Ordering::Relaxed),
+                queue_depth: metrics::DECODE_QUEUE_DEPTH.load(Ordering::Relaxed),
+                dropped_samples: total_dropped - dropped,
+                fvad_mode: metrics::fvad_mode(),
+            };
+            audio = total_audio;
+            dropped = total_dropped;
+            let mode = match heartbeat.fvad_mode {
+                Some(mode) => format!(", fvad mode {}", mode),
+                None => String::new(),
+            };
+            eprintln!(
+                "heartbeat: audio {:.1}s (total {:.1}s), {}, queue depth {}, dropped samples {}{}",
+                heartbeat.audio.as_secs_f64(),
+                heartbeat.total_audio.as_secs_f64(),
+                if heartbeat.speech {
+                    "speech"
+                } else {
+                    "silence"
+                },
+                heartbeat.queue_depth,
+                heartbeat.dropped_samples,
+                mode,
+            );
+            if controls.send(Control::Heartbeat(heartbeat)).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_json() {
+        let heartbeat = Heartbeat {
+            audio: Duration::from_millis(1500),
+            total_audio: Duration::from_secs(90),
+            speech: true,
+            queue_depth: 2,
+            dropped_samples: 0,
+            fvad_mode: Some(3),
+        };
+        assert_eq!(
+            heartbeat.to_json(),
+            json!({
+                "event": "heartbeat",
+                "audio_ms": 1500,
+                "total_audio_ms": 90000,
+                "speech": true,
+                "queue_depth": 2,
+                "dropped_samples": 0,
+                "fvad_mode": 3,
+            })
+        );
+    }
+}
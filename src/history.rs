@@ -0,0 +1,497 @@
+use crate::output::{self, Event, Sink, Utterance};
+use crate::queue::Queue;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+
+/// Statements migrating the schema from the version of their index to the following one, which
+/// the database records in its `user_version`. Released migrations must never change, later
+/// versions append new ones.
+const MIGRATIONS: &[&str] = &["CREATE TABLE utterances (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        -- Wall-clock time the speech started at in milliseconds since the Unix epoch.
+        captured_at INTEGER NOT NULL,
+        start_ms INTEGER NOT NULL,
+        end_ms INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        confidence REAL NOT NULL,
+        speaker TEXT,
+        source TEXT
+    );
+    CREATE INDEX utterances_captured_at ON utterances (captured_at);"];
+
+/// Maximum number of utterances waiting to be written, beyond which the oldest ones are dropped.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Time SQLite waits for a lock held by another connection, before failing with busy.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attempts to write an utterance, while the database stays locked, before it is given up on.
+const MAX_ATTEMPTS: u32 = 5;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Interval, at which utterances older than the retention are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time utterances still waiting are written for once the session ends.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Parses the number of days utterances are retained for, which needs to be positive.
+pub fn parse_days(s: &str) -> Result<Duration, String> {
+    match s.parse::<u64>() {
+        Ok(days) if days > 0 => Ok(Duration::from_secs(days * SECONDS_PER_DAY)),
+        _ => Err(format!(
+            "failed to parse `{}` into history retention of a positive number of days",
+            s
+        )),
+    }
+}
+
+pub struct Config {
+    pub path: PathBuf,
+    /// Age beyond which utterances are pruned, if set.
+    pub retain: Option<Duration>,
+    /// Input device or file transcribed, if known.
+    pub source: Option<String>,
+}
+
+/// Final utterance as it is written to the database.
+struct Row {
+    session_id: String,
+    seq: u64,
+    captured_at: SystemTime,
+    start: Duration,
+    end: Duration,
+    text: String,
+    confidence: f64,
+    speaker: Option<String>,
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+fn time(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+/// Migrates the schema of `connection` to the latest version.
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", &(version as i64 + 1))?;
+        transaction.commit()?;
+        debug!(version = version + 1, "Migrated history database");
+    }
+    Ok(())
+}
+
+/// Whether a failed statement may succeed if retried, which is the case while another
+/// connection holds a lock on the database.
+fn locked(err: &rusqlite::Error) -> bool {
+    match err {
+        rusqlite::Error::SqliteFailure(err, _) => matches!(
+            err.code,
+            ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked
+        ),
+        _ => false,
+    }
+}
+
+/// Retries `statement` with exponential backoff, while the database is locked.
+fn retry<T>(mut statement: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut backoff = MIN_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match statement() {
+            Err(err) if attempt < MAX_ATTEMPTS && locked(&err) => {
+                debug!(error = %err, ?backoff, "History database is locked, retrying");
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn insert(connection: &Connection, row: &Row, source: Option<&str>) -> rusqlite::Result<usize> {
+    connection.execute(
+        "INSERT INTO utterances
+            (session_id, seq, captured_at, start_ms, end_ms, text, confidence, speaker, source)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            row.session_id,
+            row.seq as i64,
+            millis(row.captured_at),
+            row.start.as_millis() as i64,
+            row.end.as_millis() as i64,
+            row.text,
+            row.confidence,
+            row.speaker,
+            source,
+        ],
+    )
+}
+
+/// Deletes the utterances captured longer than `retain` ago.
+fn prune(connection: &Connection, retain: Duration) -> rusqlite::Result<usize> {
+    let before = SystemTime::now().checked_sub(retain).unwrap_or(UNIX_EPOCH);
+    connection.execute(
+        "DELETE FROM utterances WHERE captured_at < ?",
+        params![millis(before)],
+    )
+}
+
+/// Writes final utterances to an SQLite database, which is created along with its schema on
+/// first use and migrated to the latest schema otherwise.
+/// Utterances are written on a separate thread, retrying while the database is locked, e.g. by a
+/// search of the history. They are queued in the meantime, dropping the oldest ones once the
+/// queue is full, so that a slow disk never holds back transcription.
+pub struct History {
+    queue: Arc<Queue<Row>>,
+    done: Option<mpsc::Receiver<()>>,
+}
+
+impl History {
+    pub fn open(config: Config) -> rusqlite::Result<Self> {
+        let mut connection = Connection::open(&config.path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        migrate(&mut connection)?;
+        let queue = Arc::new(Queue::new(QUEUE_CAPACITY));
+        let rows = queue.clone();
+        let (done_tx, done) = mpsc::channel();
+        let Config {
+            path,
+            retain,
+            source,
+        } = config;
+        thread::spawn(move || {
+            let mut pruned: Option<Instant> = None;
+            loop {
+                if let Some(retain) = retain {
+                    if pruned.is_none_or(|pruned| pruned.elapsed() >= PRUNE_INTERVAL) {
+                        pruned = Some(Instant::now());
+                        match retry(|| prune(&connection, retain)) {
+                            Ok(0) => {}
+                            Ok(deleted) => debug!(deleted, "Pruned history"),
+                            Err(err) => warn!(?path, error = %err, "Failed to prune history"),
+                        }
+                    }
+                }
+                // Waiting for the next utterance is interrupted once pruning is due again, so that
+                // the history is pruned even while nothing is transcribed.
+                let row = match pruned {
+                    Some(pruned) => {
+                        match rows.pop_timeout(PRUNE_INTERVAL.saturating_sub(pruned.elapsed())) {
+                            Ok(row) => row,
+                            Err(RecvTimeoutError::Timeout) => continue,
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        }
+                    }
+                    None => match rows.pop() {
+                        Some(row) => row,
+                        None => break,
+                    },
+                };
+                if let Err(err) = retry(|| insert(&connection, &row, source.as_deref())) {
+                    warn!(
+                        ?path,
+                        error = %err,
+                        seq = row.seq,
+                        "Failed to write utterance to history"
+                    );
+                }
+            }
+            let _ = done_tx.send(());
+        });
+        Ok(Self {
+            queue,
+            done: Some(done),
+        })
+    }
+}
+
+/// Session kept in the history.
+pub struct Summary {
+    pub session_id: String,
+    /// Wall-clock time the speech of the first utterance started at.
+    pub started: SystemTime,
+    pub utterances: u64,
+    /// Input device or file transcribed, if known.
+    pub source: Option<String>,
+}
+
+/// Opens the history at `path` for reading, without creating it.
+fn read(path: &Path) -> rusqlite::Result<Connection> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(connection)
+}
+
+/// Returns the sessions kept in the history at `path`, the latest last.
+pub fn sessions(path: &Path) -> rusqlite::Result<Vec<Summary>> {
+    let connection = read(path)?;
+    let mut statement = connection.prepare(
+        "SELECT session_id, MIN(captured_at), COUNT(*), MAX(source) FROM utterances
+            GROUP BY session_id ORDER BY MIN(captured_at)",
+    )?;
+    let sessions = statement.query_map(params![], |row| {
+        Ok(Summary {
+            session_id: row.get(0)?,
+            started: time(row.get(1)?),
+            utterances: row.get::<_, i64>(2)? as u64,
+            source: row.get(3)?,
+        })
+    })?;
+    sessions.collect()
+}
+
+/// Returns the final utterances of session `session_id` kept in the history at `path`, in the
+/// order they were output. Only what the history keeps of them is set.
+pub fn utterances(path: &Path, session_id: &str) -> rusqlite::Result<Vec<Utterance>> {
+    let connection = read(path)?;
+    let mut statement = connection.prepare(
+        "SELECT seq, captured_at, start_ms, end_ms, text, confidence, speaker FROM utterances
+            WHERE session_id = ? ORDER BY seq",
+    )?;
+    let utterances = statement.query_map(params![session_id], |row| {
+        let start = Duration::from_millis(row.get::<_, i64>(2)?.max(0) as u64);
+        let end = Duration::from_millis(row.get::<_, i64>(3)?.max(0) as u64);
+        let captured_at = time(row.get(1)?);
+        Ok(Utterance {
+            seq: row.get::<_, i64>(0)? as u64,
+            text: row.get(4)?,
+            speaker: row.get(6)?,
+            confidence: row.get(5)?,
+            start,
+            end,
+            start_sample: 0,
+            end_sample: 0,
+            offsets: None,
+            captured_at: Some((
+                captured_at,
+                captured_at + end.checked_sub(start).unwrap_or_default(),
+            )),
+            fingerprint: None,
+            loudness: None,
+            recording: None,
+            censored: false,
+            low_confidence: false,
+            paragraph: None,
+            comparison: None,
+            intent: None,
+            duplicate: false,
+            rescored: false,
+            speech: Duration::default(),
+        })
+    })?;
+    utterances.collect()
+}
+
+impl Sink for History {
+    fn send(&mut self, event: &Event) {
+        let utterance = match event {
+            Event::Final(utterance) => utterance,
+            _ => return,
+        };
+        let row = Row {
+            session_id: output::session_id(),
+            seq: utterance.seq,
+            captured_at: utterance
+                .captured_at
+                .map_or_else(SystemTime::now, |(start, _)| start),
+            start: utterance.start,
+            end: utterance.end,
+            text: utterance.text.clone(),
+            confidence: utterance.confidence,
+            speaker: utterance.speaker.clone(),
+        };
+        if self.queue.push(row).is_some() {
+            warn!("History queue is full, dropping oldest utterance");
+        }
+    }
+
+    fn finish(&mut self) {
+        self.queue.close();
+        let done = match self.done.take() {
+            Some(done) => done,
+            None => return,
+        };
+        if done.recv_timeout(FLUSH_TIMEOUT).is_err() {
+            warn!(
+                pending = self.queue.len(),
+                "Failed to write history before exiting, dropping it"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(seq: u64, captured_at: SystemTime) -> Row {
+        Row {
+            session_id: "session".into(),
+            seq,
+            captured_at,
+            start: Duration::from_millis(1000),
+            end: Duration::from_millis(2500),
+            text: format!("utterance {}", seq),
+            confidence: -3.5,
+            speaker: None,
+        }
+    }
+
+    fn version(connection: &Connection) -> i64 {
+        connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    fn count(connection: &Connection) -> i64 {
+        connection
+            .query_row("SELECT COUNT(*) FROM utterances", params![], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn migrate_fresh() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        assert_eq!(version(&connection), 0);
+        migrate(&mut connection).unwrap();
+        assert_eq!(version(&connection), MIGRATIONS.len() as i64);
+        assert_eq!(count(&connection), 0);
+    }
+
+    #[test]
+    fn migrate_reopened() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.sqlite");
+        {
+            let mut connection = Connection::open(&path).unwrap();
+            migrate(&mut connection).unwrap();
+            insert(&connection, &row(1, SystemTime::now()), Some("mic")).unwrap();
+        }
+        let mut connection = Connection::open(&path).unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(version(&connection), MIGRATIONS.len() as i64);
+        assert_eq!(count(&connection), 1);
+    }
+
+    #[test]
+    fn retry_while_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.sqlite");
+        let mut connection = Connection::open(&path).unwrap();
+        // Backing off is left to `retry` rather than SQLite.
+        connection.busy_timeout(Duration::default()).unwrap();
+        migrate(&mut connection).unwrap();
+
+        let lock = Connection::open(&path).unwrap();
+        lock.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let err = insert(&connection, &row(1, SystemTime::now()), None).unwrap_err();
+        assert!(locked(&err), "{}", err);
+
+        let unlocked = thread::spawn(move || {
+            thread::sleep(MIN_BACKOFF * 2);
+            lock.execute_batch("COMMIT").unwrap();
+        });
+        retry(|| insert(&connection, &row(1, SystemTime::now()), None)).unwrap();
+        unlocked.join().unwrap();
+        assert_eq!(count(&connection), 1);
+    }
+
+    #[test]
+    fn retry_gives_up() {
+        let mut attempts = 0;
+        let err = retry(|| -> rusqlite::Result<()> {
+            attempts += 1;
+            Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                None,
+            ))
+        })
+        .unwrap_err();
+        assert!(locked(&err));
+        assert_eq!(attempts, MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn retry_other_errors_once() {
+        let mut attempts = 0;
+        retry(|| -> rusqlite::Result<()> {
+            attempts += 1;
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        })
+        .unwrap_err();
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn prune_old() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(SECONDS_PER_DAY);
+        insert(&connection, &row(1, now - day * 3), None).unwrap();
+        insert(&connection, &row(2, now - day * 2), None).unwrap();
+        insert(&connection, &row(3, now), None).unwrap();
+
+        assert_eq!(prune(&connection, day * 7).unwrap(), 0);
+        assert_eq!(prune(&connection, day).unwrap(), 2);
+        assert_eq!(count(&connection), 1);
+        let seq: i64 = connection
+            .query_row("SELECT seq FROM utterances", params![], |row| row.get(0))
+            .unwrap();
+        assert_eq!(seq, 3);
+    }
+
+    #[test]
+    fn read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.sqlite");
+        let mut connection = Connection::open(&path).unwrap();
+        migrate(&mut connection).unwrap();
+        let captured_at = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        insert(&connection, &row(2, captured_at), Some("mic")).unwrap();
+        insert(&connection, &row(1, captured_at), Some("mic")).unwrap();
+
+        let sessions = sessions(&path).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "session");
+        assert_eq!(sessions[0].started, captured_at);
+        assert_eq!(sessions[0].utterances, 2);
+        assert_eq!(sessions[0].source.as_deref(), Some("mic"));
+
+        let utterances = utterances(&path, "session").unwrap();
+        let seqs: Vec<_> = utterances.iter().map(|utterance| utterance.seq).collect();
+        assert_eq!(seqs, [1, 2]);
+        assert_eq!(utterances[0].text, "utterance 1");
+        assert_eq!(utterances[0].start, Duration::from_millis(1000));
+        assert_eq!(
+            utterances[0].captured_at,
+            Some((captured_at, captured_at + Duration::from_millis(1500)))
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use crate::control::Control;
+use crate::error::Error;
+use crate::metrics;
+use evdev::{Device, InputEventKind};
+use std::{
+    path::Path, process::Command, str::FromStr, sync::atomic::Ordering, sync::mpsc::Sender, thread,
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// Highest key code looked up by name.
+const KEY_MAX: u16 = 0x2ff;
+
+/// Value of a key event on press.
+const PRESSED: i32 = 1;
+/// Value of a key event on release.
+const RELEASED: i32 = 0;
+
+/// Key given by its evdev name, e.g. `KEY_F13` or `f13`, or by its code.
+#[derive(Clone, Copy, Debug)]
+pub struct Key(evdev::Key);
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        if let Ok(code) = s.parse() {
+            return Ok(Self(evdev::Key::new(code)));
+        }
+        let name = s.to_uppercase();
+        let name = if name.starts_with("KEY_") || name.starts_with("BTN_") {
+            name
+        } else {
+            format!("KEY_{}", name)
+        };
+        (0..=KEY_MAX)
+            .map(evdev::Key::new)
+            .find(|key| format!("{:?}", key) == name)
+            .map(Self)
+            .ok_or_else(|| {
+                format!(
+                    "failed to parse `{}` into key, expected an evdev key name like KEY_F13 or a key code",
+                    s
+                )
+            })
+    }
+}
+
+/// Opens the evdev device at `path`, e.g. `/dev/input/by-id/...-event-kbd`, checking that it has
+/// `key`.
+/// The device is read without grabbing it, so that the key keeps working in other applications.
+/// Reading input devices requires read access to them, usually by membership of the `input`
+/// group.
+fn open(path: &Path, key: Key) -> Result<Device, Error> {
+    let device = Device::open(path).map_err(|err| {
+        Error::Device(format!(
+            "Failed to open input device {}, reading it requires membership of the input group: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    if !device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(key.0))
+    {
+        return Err(Error::Device(format!(
+            "Input device {} has no key {:?}",
+            path.display(),
+            key.0
+        )));
+    }
+    Ok(device)
+}
+
+/// Calls `f` with the value of every event of `key` read from `device` on a separate thread.
+fn listen(mut device: Device, key: Key, mut f: impl FnMut(i32) + Send + 'static) {
+    thread::spawn(move || loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(err) => return error!(error = %err, "Failed to read input device"),
+        };
+        for event in events {
+            if event.kind() == InputEventKind::Key(key.0) {
+                f(event.value())
+            }
+        }
+    });
+}
+
+/// Pauses the pipeline, except while `key` of the input device at `path` is held.
+/// Audio is transcribed for `tail` after the key is released, so that the last word is not cut.
+pub fn push_to_talk(
+    path: &Path,
+    key: Key,
+    tail: Duration,
+    controls: Sender<Control>,
+) -> Result<(), Error> {
+    let device = open(path, key)?;
+    let _ = controls.send(Control::Pause);
+    info!(device = %path.display(), key = ?key.0, "Hold key to talk");
+    listen(device, key, move |value| {
+        let control = match value {
+            PRESSED => Control::Resume,
+            RELEASED => {
+                thread::sleep(tail);
+                Control::Pause
+            }
+            // Repeated while held.
+            _ => return,
+        };
+        debug!(pressed = value == PRESSED, "Push-to-talk key changed");
+        let _ = controls.send(control);
+    });
+    Ok(())
+}
+
+/// Confirmation of pausing or resuming with the toggle key.
+pub struct Feedback {
+    /// Ring the terminal bell.
+    pub bell: bool,
+    /// Show a desktop notification.
+    pub notify: bool,
+}
+
+impl Feedback {
+    fn confirm(&self, paused: bool) {
+        if self.bell {
+            eprint!("\x07");
+        }
+        if self.notify {
+            let state = if paused { "Paused" } else { "Listening" };
+            match Command::new("notify-send")
+                .args(["--app-name=speech2text", "speech2text", state])
+                .status()
+            {
+                Ok(status) if !status.success() => warn!(%status, "Failed to show notification"),
+                Ok(_) => {}
+                Err(err) => warn!(error = %err, "Failed to show notification"),
+            }
+        }
+    }
+}
+
+/// Pauses the pipeline if it is listening and resumes it otherwise, whenever `key` of the input
+/// device at `path` is pressed, confirming the new state with `feedback`.
+/// The state is shared with the other controls, e.g. the control socket.
+pub fn toggle(
+    path: &Path,
+    key: Key,
+    feedback: Feedback,
+    controls: Sender<Control>,
+) -> Result<(), Error> {
+    let device = open(path, key)?;
+    info!(device = %path.display(), key = ?key.0, "Press key to pause or resume listening");
+    listen(device, key, move |value| {
+        if value != PRESSED {
+            return;
+        }
+        let pause = !metrics::PAUSED.load(Ordering::Relaxed);
+        debug!(pause, "Toggle key pressed");
+        let control = if pause {
+            Control::Pause
+        } else {
+            Control::Resume
+        };
+        if controls.send(control).is_ok() {
+            feedback.confirm(pause);
+        }
+    });
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key() {
+        for key in ["KEY_F13", "f13", "183"] {
+            assert_eq!(
+                key.parse::<Key>().unwrap().0,
+                evdev::Key::KEY_F13,
+                "{}",
+                key
+            );
+        }
+        assert_eq!("btn_left".parse::<Key>().unwrap().0, evdev::Key::BTN_LEFT);
+        assert!("KEY_BOGUS".parse::<Key>().is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::model::Model;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Returns the names of the optional features compiled in.
+fn features() -> Vec<&'static str> {
+    [
+        ("capture", cfg!(feature = "capture")),
+        ("dbus", cfg!(feature = "dbus")),
+        ("enigo", cfg!(feature = "enigo")),
+        ("grpc", cfg!(feature = "grpc")),
+        ("http", cfg!(feature = "http")),
+        ("journald", cfg!(feature = "journald")),
+        ("metrics", cfg!(feature = "metrics")),
+        ("mqtt", cfg!(feature = "mqtt")),
+        ("osc", cfg!(feature = "osc")),
+        ("tui", cfg!(feature = "tui")),
+        ("websocket", cfg!(feature = "websocket")),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect()
+}
+
+/// Describes the loaded model and the build.
+pub fn report(model: &Model, model_path: &Path, scorer_path: Option<&Path>) -> Value {
+    json!({
+        "model": model_path.display().to_string(),
+        "sample_rate": model.get_sample_rate(),
+        "scorer": scorer_path.map(|path| path.display().to_string()),
+        "deepspeech_version": deepspeech::deepspeech_version().ok(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "features": features(),
+        "audio_hosts": audio_hosts(),
+    })
+}
+
+/// Returns the names of the audio hosts available to capture from.
+#[cfg(feature = "capture")]
+fn audio_hosts() -> Vec<&'static str> {
+    cpal::available_hosts()
+        .iter()
+        .map(|host| host.name())
+        .collect()
+}
+
+#[cfg(not(feature = "capture"))]
+fn audio_hosts() -> Vec<&'static str> {
+    Vec::new()
+}
+
+/// Describes the configuration negotiated with the input device.
+#[cfg(feature = "capture")]
+pub fn device(name: Option<String>, config: &cpal::SupportedStreamConfig) -> Value {
+    json!({
+        "name": name,
+        "sample_rate": config.sample_rate().0,
+        "sample_format": format!("{:?}", config.sample_format()),
+        "channels": config.channels(),
+        "buffer_size": format!("{:?}", config.config().buffer_size),
+    })
+}
+
+/// Prints `report` as one `key: value` line per field.
+pub fn print(report: &Value) {
+    for line in lines(report) {
+        println!("{}", line);
+    }
+}
+
+fn lines(report: &Value) -> Vec<String> {
+    let fields = match report.as_object() {
+        Some(fields) => fields,
+        None => return vec![report.to_string()],
+    };
+    fields
+        .iter()
+        .map(|(key, value)| match value {
+            Value::Null => format!("{}: none", key),
+            Value::String(s) => format!("{}: {}", key, s),
+            Value::Array(values) => format!(
+                "{}: {}",
+                key,
+                values
+                    .iter()
+                    .map(|value| value
+                        .as_str()
+                        .map_or_else(|| value.to_string(), String::from))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            value => format!("{}: {}", key, value),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_lines() {
+        let report = json!({
+            "model": "model.pbmm",
+            "sample_rate": 16000,
+            "scorer": null,
+            "features": ["grpc", "mqtt"],
+            "device": {"channels": 1},
+        });
+        assert_eq!(
+            lines(&report),
+            [
+                "device: {\"channels\":1}",
+                "features: grpc, mqtt",
+                "model: model.pbmm",
+                "sample_rate: 16000",
+                "scorer: none",
+            ]
+        );
+        assert_eq!(lines(&json!(42)), ["42"]);
+    }
+}
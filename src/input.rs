@@ -0,0 +1,177 @@
+#[cfg(all(target_os = "linux", feature = "capture", feature = "alsa"))]
+use crate::alsa;
+use crate::clock::Anchor;
+#[cfg(feature = "capture")]
+use crate::device;
+use crate::error::Error;
+#[cfg(feature = "capture")]
+use crate::info;
+#[cfg(all(feature = "capture", feature = "jack"))]
+use crate::jack;
+#[cfg(all(feature = "capture", feature = "pulse"))]
+use crate::pulse;
+use crate::resample::Resampler;
+#[cfg(feature = "capture")]
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde_json::Value;
+
+/// Options selecting the live input, taken from the command line.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "capture"), allow(dead_code))]
+pub struct Options {
+    /// Name of the audio device to capture from.
+    pub device: Option<String>,
+    /// Whether the capture thread is raised to realtime priority.
+    pub realtime: bool,
+    /// Whether the audio played by the output device is captured instead.
+    #[cfg(windows)]
+    pub capture_output: bool,
+    /// Name of the JACK client to capture with, if capturing from JACK.
+    #[cfg(feature = "jack")]
+    pub jack: Option<String>,
+    /// Name of the PulseAudio source to capture from, if capturing from PulseAudio.
+    #[cfg(feature = "pulse")]
+    pub pulse_source: Option<String>,
+    /// Name of the ALSA device to capture from directly, if capturing from ALSA.
+    #[cfg(all(target_os = "linux", feature = "alsa"))]
+    pub alsa_device: Option<String>,
+}
+
+impl Options {
+    /// Whether the input is captured from a device of the default host rather than a sound
+    /// server or ALSA directly.
+    pub fn default_host(&self) -> bool {
+        #[cfg(feature = "jack")]
+        if self.jack.is_some() {
+            return false;
+        }
+        #[cfg(feature = "pulse")]
+        if self.pulse_source.is_some() {
+            return false;
+        }
+        #[cfg(all(target_os = "linux", feature = "alsa"))]
+        if self.alsa_device.is_some() {
+            return false;
+        }
+        true
+    }
+}
+
+/// Keeps capturing from the live input until dropped.
+#[cfg_attr(not(feature = "capture"), allow(dead_code))]
+pub struct Input {
+    /// Name of the device or source captured from, if known.
+    name: Option<String>,
+    #[cfg(feature = "capture")]
+    _stream: Option<cpal::Stream>,
+    #[cfg(all(feature = "capture", feature = "jack"))]
+    _jack: Option<jack::Capture>,
+}
+
+impl Input {
+    /// Returns the name of the device or source captured from, if known.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// Starts capturing from the live input selected by `options`, returning the samples captured
+/// at `sample_rate`. The input device is added to `report`, if set.
+#[cfg(feature = "capture")]
+pub fn open(
+    options: &Options,
+    sample_rate: u32,
+    resampler: Resampler,
+    dither: bool,
+    anchor: Anchor,
+    report: &mut Option<Value>,
+) -> Result<(Box<dyn Iterator<Item = i16>>, Input), Error> {
+    let mut input = Input {
+        name: None,
+        _stream: None,
+        #[cfg(feature = "jack")]
+        _jack: None,
+    };
+    #[cfg(feature = "jack")]
+    if let Some(name) = &options.jack {
+        let (client, samples) = jack::capture(name, sample_rate, resampler, dither, anchor)?;
+        input._jack = Some(client);
+        input.name = Some(name.clone());
+        return Ok((samples, input));
+    }
+    #[cfg(feature = "pulse")]
+    if let Some(name) = &options.pulse_source {
+        input.name = Some(name.clone());
+        return Ok((pulse::capture(name, sample_rate, anchor)?, input));
+    }
+    #[cfg(all(target_os = "linux", feature = "alsa"))]
+    if let Some(name) = &options.alsa_device {
+        let samples = alsa::capture(name, sample_rate, resampler, options.realtime, anchor)?;
+        input.name = Some(name.clone());
+        return Ok((samples, input));
+    }
+
+    let host = cpal::default_host();
+    #[cfg(windows)]
+    let capture_output = options.capture_output;
+    #[cfg(not(windows))]
+    let capture_output = false;
+    let (input_device, input_stream_conf) = if capture_output {
+        let devices = host
+            .output_devices()
+            .map_err(Error::with(Error::Device, "Failed to list output devices"))?;
+        let device = device::find(
+            devices,
+            host.default_output_device(),
+            options.device.as_deref(),
+        )?;
+        // Input streams built on output devices capture in loopback mode on WASAPI.
+        let conf = device.default_output_config().map_err(Error::with(
+            Error::Device,
+            "Failed to get default device output configuration",
+        ))?;
+        (device, conf)
+    } else {
+        let devices = host
+            .input_devices()
+            .map_err(Error::with(Error::Device, "Failed to list input devices"))?;
+        let device = device::find(
+            devices,
+            host.default_input_device(),
+            options.device.as_deref(),
+        )?;
+        let conf = device::input_config(&device, sample_rate)?;
+        (device, conf)
+    };
+    if let Some(report) = report {
+        report["device"] = info::device(input_device.name().ok(), &input_stream_conf);
+    }
+
+    let (stream, samples) = device::capture(
+        &input_device,
+        &input_stream_conf,
+        sample_rate,
+        resampler,
+        dither,
+        options.realtime,
+        anchor,
+    )?;
+    input._stream = Some(stream);
+    input.name = input_device.name().ok();
+    Ok((samples, input))
+}
+
+/// Fails, since live inputs cannot be captured from without the `capture` feature.
+#[cfg(not(feature = "capture"))]
+pub fn open(
+    _options: &Options,
+    _sample_rate: u32,
+    _resampler: Resampler,
+    _dither: bool,
+    _anchor: Anchor,
+    _report: &mut Option<Value>,
+) -> Result<(Box<dyn Iterator<Item = i16>>, Input), Error> {
+    Err(Error::Usage(
+        "speech2text was built without capture support, pass --file to transcribe a file".into(),
+    ))
+}
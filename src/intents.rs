@@ -0,0 +1,409 @@
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+use regex::Regex;
+use serde_json::Value;
+use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
+use tracing::debug;
+
+/// How the intent of a transcript matching the patterns of several intents is chosen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// The intent defined first in the file.
+    First,
+    /// The intent whose pattern is the most specific, i.e. has the most literal characters,
+    /// which the one defined first wins ties of.
+    Best,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "first" => Ok(Self::First),
+            "best" => Ok(Self::Best),
+            _ => Err(format!(
+                "failed to parse `{}` into intent policy of first or best",
+                s
+            )),
+        }
+    }
+}
+
+/// Type of the value a slot captures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// Integer or decimal number in digits, as output by number normalization.
+    Number,
+    /// Single word.
+    Word,
+    /// One or more words.
+    Text,
+}
+
+impl Kind {
+    fn regex(self) -> &'static str {
+        match self {
+            Self::Number => r"-?\d+(?:[.,]\d+)?",
+            Self::Word => r"\S+",
+            Self::Text => r".+?",
+        }
+    }
+
+    /// Returns the value of `capture`, numbers as JSON numbers.
+    fn value(self, capture: &str) -> Value {
+        match self {
+            Self::Number => match capture.parse::<i64>() {
+                Ok(number) => number.into(),
+                Err(_) => capture
+                    .replace(',', ".")
+                    .parse::<f64>()
+                    .map_or_else(|_| capture.into(), Value::from),
+            },
+            Self::Word | Self::Text => capture.into(),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "number" => Ok(Self::Number),
+            "word" => Ok(Self::Word),
+            "text" => Ok(Self::Text),
+            _ => Err(format!(
+                "failed to parse `{}` into slot type of number, word or text",
+                s
+            )),
+        }
+    }
+}
+
+/// Intent a transcript matched along with the values of its slots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Intent {
+    pub name: String,
+    pub slots: BTreeMap<String, Value>,
+}
+
+/// Pattern of an intent compiled into a regular expression with a capture group per slot.
+struct Rule {
+    name: String,
+    regex: Regex,
+    slots: Vec<(String, Kind)>,
+    /// Number of literal characters of the pattern, which ranks the specificity of the rule.
+    literals: usize,
+}
+
+impl Rule {
+    /// Compiles `pattern` of the intent `name`, in which `{slot:type}` captures a value of
+    /// `type`, which is a number, a word or text, and defaults to word. Whitespace matches any
+    /// amount of whitespace and literal words are matched case-insensitively.
+    fn new(name: &str, pattern: &str) -> Result<Self, String> {
+        let mut regex = String::from(r"(?i)^\s*");
+        let mut slots = Vec::new();
+        let mut literals = 0;
+        for (i, chunk) in pattern.split_whitespace().enumerate() {
+            if i > 0 {
+                regex.push_str(r"\s+");
+            }
+            let mut rest = chunk;
+            while !rest.is_empty() {
+                let start = match rest.find('{') {
+                    Some(start) => start,
+                    None => {
+                        literals += rest.chars().count();
+                        regex.push_str(&regex::escape(rest));
+                        break;
+                    }
+                };
+                literals += rest[..start].chars().count();
+                regex.push_str(&regex::escape(&rest[..start]));
+                let end = rest[start..]
+                    .find('}')
+                    .map(|end| start + end)
+                    .ok_or_else(|| format!("slot `{}` is not closed", &rest[start..]))?;
+                let slot = &rest[start + 1..end];
+                let (slot, kind) = match slot.find(':') {
+                    Some(at) => (&slot[..at], slot[at + 1..].parse()?),
+                    None => (slot, Kind::Word),
+                };
+                let valid = slot.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                    && slot.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(format!(
+                        "slot name `{}` must start with a letter and contain only letters, \
+                         digits and underscores",
+                        slot
+                    ));
+                }
+                if slots.iter().any(|(name, _)| name == slot) {
+                    return Err(format!("slot `{}` is defined more than once", slot));
+                }
+                regex.push_str(&format!("(?P<{}>{})", slot, kind.regex()));
+                slots.push((slot.to_string(), kind));
+                rest = &rest[end + 1..];
+            }
+        }
+        regex.push_str(r"\s*$");
+        Ok(Self {
+            name: name.to_string(),
+            regex: Regex::new(&regex).map_err(|err| err.to_string())?,
+            slots,
+            literals,
+        })
+    }
+
+    /// Returns the intent of `text`, if it matches the rule.
+    fn matches(&self, text: &str) -> Option<Intent> {
+        let captures = self.regex.captures(text)?;
+        Some(Intent {
+            name: self.name.clone(),
+            slots: self
+                .slots
+                .iter()
+                .filter_map(|(slot, kind)| {
+                    let capture = captures.name(slot)?;
+                    Some((slot.clone(), kind.value(capture.as_str())))
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Intents defined by patterns with typed slots, which transcripts are matched against.
+pub struct Intents {
+    rules: Vec<Rule>,
+    policy: Policy,
+}
+
+impl Intents {
+    /// Reads intents from `path`, one `name = "pattern"` pair per line, e.g.
+    /// `set_timer = "set a timer for {minutes:number} minutes"`. An intent may be defined by
+    /// several patterns on lines of their own. Blank lines and lines starting with `#` are
+    /// ignored.
+    pub fn load(path: &Path, policy: Policy) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        Self::parse(&contents, policy).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|(line, err)| format!("{}:{}: {}", path.display(), line, err))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+
+    /// Parses intents from `contents`, failing with the errors of each line they occurred on.
+    fn parse(contents: &str, policy: Policy) -> Result<Self, Vec<(usize, String)>> {
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (name, pattern) = match trimmed.find('=') {
+                Some(at) => (trimmed[..at].trim(), trimmed[at + 1..].trim()),
+                None => {
+                    errors.push((i + 1, "expected `name = \"pattern\"`".into()));
+                    continue;
+                }
+            };
+            let pattern = match pattern.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                Some(pattern) if !pattern.trim().is_empty() => pattern,
+                _ => {
+                    errors.push((i + 1, "pattern must be a non-empty quoted string".into()));
+                    continue;
+                }
+            };
+            if name.is_empty() {
+                errors.push((i + 1, "intent name is empty".into()));
+                continue;
+            }
+            match Rule::new(name, pattern) {
+                Ok(rule) => rules.push(rule),
+                Err(err) => errors.push((i + 1, err)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self { rules, policy })
+    }
+
+    /// Returns the intent `text` matches according to the policy, if any.
+    pub fn of(&self, text: &str) -> Option<Intent> {
+        match self.policy {
+            Policy::First => self.rules.iter().find_map(|rule| rule.matches(text)),
+            Policy::Best => {
+                let mut best: Option<(usize, Intent)> = None;
+                for rule in &self.rules {
+                    if best
+                        .as_ref()
+                        .is_some_and(|(literals, _)| *literals >= rule.literals)
+                    {
+                        continue;
+                    }
+                    if let Some(intent) = rule.matches(text) {
+                        best = Some((rule.literals, intent));
+                    }
+                }
+                best.map(|(_, intent)| intent)
+            }
+        }
+    }
+}
+
+/// Assigns the intent transcripts match, or none, to them.
+impl Pass for Intents {
+    fn apply(&self, utterance: &mut Utterance) {
+        let intent = self.of(&utterance.text);
+        if let Some(intent) = &intent {
+            debug!(
+                intent = %intent.name,
+                transcript = %utterance.text,
+                "Transcript matched intent"
+            );
+        }
+        utterance.intent = Some(intent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const INTENTS: &str = r#"
+# Timers
+set_timer = "set a timer for {minutes:number} minutes"
+set_timer = "set a {minutes:number} minute timer"
+weather = "what is the weather in {city:text}"
+play = "play {song:text}"
+play_by = "play {song:text} by {artist:text}"
+lights = "turn {state} the lights"
+"#;
+
+    fn intents(policy: Policy) -> Intents {
+        Intents::parse(INTENTS, policy).unwrap()
+    }
+
+    fn intent(name: &str, slots: Value) -> Option<Intent> {
+        Some(Intent {
+            name: name.into(),
+            slots: serde_json::from_value(slots).unwrap(),
+        })
+    }
+
+    #[test]
+    fn matches() {
+        let intents = intents(Policy::First);
+        for (text, expected) in [
+            (
+                "set a timer for 5 minutes",
+                intent("set_timer", json!({"minutes": 5})),
+            ),
+            (
+                "  Set A  timer for 2.5 MINUTES ",
+                intent("set_timer", json!({"minutes": 2.5})),
+            ),
+            (
+                "set a timer for 2,5 minutes",
+                intent("set_timer", json!({"minutes": 2.5})),
+            ),
+            (
+                "set a -1 minute timer",
+                intent("set_timer", json!({"minutes": -1})),
+            ),
+            (
+                "what is the weather in new york",
+                intent("weather", json!({"city": "new york"})),
+            ),
+            (
+                "turn off the lights",
+                intent("lights", json!({"state": "off"})),
+            ),
+            ("set a timer for five minutes", None),
+            ("turn the lights off", None),
+            ("please set a timer for 5 minutes", None),
+            ("set a timer for 5 minutes please", None),
+            ("", None),
+        ] {
+            assert_eq!(intents.of(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn ambiguity() {
+        let text = "play yesterday by the beatles";
+        assert_eq!(
+            intents(Policy::First).of(text),
+            intent("play", json!({"song": "yesterday by the beatles"}))
+        );
+        assert_eq!(
+            intents(Policy::Best).of(text),
+            intent(
+                "play_by",
+                json!({"song": "yesterday", "artist": "the beatles"})
+            )
+        );
+        // Ties go to the intent defined first.
+        let tied = Intents::parse("a = \"say {x}\"\nb = \"say {y}\"", Policy::Best).unwrap();
+        assert_eq!(tied.of("say hi"), intent("a", json!({"x": "hi"})));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let errors = Intents::parse(
+            "\
+ok = \"fine\"
+missing equals
+empty = \"\"
+unquoted = hello
+= \"no name\"
+open = \"{slot\"
+kind = \"{slot:date}\"
+name = \"{1slot}\"
+twice = \"{a} {a}\"",
+            Policy::First,
+        )
+        .err()
+        .unwrap();
+        let lines: Vec<usize> = errors.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(errors[4].1.contains("is not closed"), "{:?}", errors[4]);
+        assert!(errors[5].1.contains("slot type"), "{:?}", errors[5]);
+        assert!(
+            errors[6].1.contains("must start with a letter"),
+            "{:?}",
+            errors[6]
+        );
+        assert!(errors[7].1.contains("more than once"), "{:?}", errors[7]);
+    }
+
+    #[test]
+    fn pass() {
+        let intents = intents(Policy::First);
+        let mut utterance = Utterance::test("turn on the lights");
+        intents.apply(&mut utterance);
+        assert_eq!(
+            utterance.intent,
+            Some(intent("lights", json!({"state": "on"})))
+        );
+        let mut utterance = Utterance::test("hello");
+        intents.apply(&mut utterance);
+        assert_eq!(utterance.intent, Some(None));
+
+        // Intents match after number normalization.
+        let mut utterance = Utterance::test("set a timer for twenty five minutes");
+        crate::numbers::Numbers.apply(&mut utterance);
+        intents.apply(&mut utterance);
+        assert_eq!(
+            utterance.intent,
+            Some(intent("set_timer", json!({"minutes": 25})))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::capture::{self, Writer};
+use crate::clock::Anchor;
+use crate::dither::Dither;
+use crate::error::Error;
+use crate::metrics;
+use crate::resample::{self, Resampler};
+use ::jack::{
+    AsyncClient, AudioIn, Client, ClientOptions, Control, NotificationHandler, Port,
+    ProcessHandler, ProcessScope,
+};
+use std::sync::atomic::Ordering;
+use tracing::{info, warn};
+
+/// Name of the input port of the client.
+const PORT_NAME: &str = "input";
+
+/// Counts the XRUNs reported by the server.
+struct Notifications;
+
+impl NotificationHandler for Notifications {
+    fn xrun(&mut self, _: &Client) -> Control {
+        metrics::XRUNS.fetch_add(1, Ordering::Relaxed);
+        Control::Continue
+    }
+}
+
+/// Writes the samples of the input port to the capture buffer.
+struct Process {
+    port: Port<AudioIn>,
+    writer: Writer,
+    /// Reused across cycles, so that the process callback does not allocate.
+    samples: Vec<i16>,
+    dither: Dither,
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, scope: &ProcessScope) -> Control {
+        self.samples.clear();
+        let dither = &mut self.dither;
+        self.samples.extend(
+            self.port
+                .as_slice(scope)
+                .iter()
+                .map(|&sample| dither.quantize(sample)),
+        );
+        self.writer.write(&self.samples);
+        Control::Continue
+    }
+}
+
+/// Active JACK client, which captures until dropped.
+pub struct Capture(#[allow(dead_code)] AsyncClient<Notifications, Process>);
+
+/// Registers a JACK client named `name` with a single mono input port, which is left for the user
+/// to connect, returning the client along with the captured samples resampled to `sample_rate`
+/// with `resampler`.
+/// Samples are dithered when converted to i16, if `dither` is set.
+/// `anchor` is set to the capture time of the first sample.
+pub fn capture(
+    name: &str,
+    sample_rate: u32,
+    resampler: Resampler,
+    dither: bool,
+    anchor: Anchor,
+) -> Result<(Capture, Box<dyn Iterator<Item = i16>>), Error> {
+    let (client, status) = Client::new(name, ClientOptions::NO_START_SERVER).map_err(
+        Error::with(Error::Device, "Failed to connect to JACK server"),
+    )?;
+    let port = client
+        .register_port(PORT_NAME, AudioIn)
+        .map_err(Error::with(
+            Error::Device,
+            "Failed to register JACK input port",
+        ))?;
+    let port_name = port
+        .name()
+        .unwrap_or_else(|_| format!("{}:{}", client.name(), PORT_NAME));
+    let jack_rate = client.sample_rate() as u32;
+    let (writer, reader) = capture::buffer(jack_rate, anchor);
+    let process = Process {
+        port,
+        writer,
+        samples: Vec::with_capacity(client.buffer_size() as usize),
+        dither: Dither::new(dither),
+    };
+    let client = client
+        .activate_async(Notifications, process)
+        .map_err(Error::with(Error::Device, "Failed to activate JACK client"))?;
+    if status.contains(::jack::ClientStatus::NAME_NOT_UNIQUE) {
+        warn!(name, port = %port_name, "JACK client name is taken, registered under another one");
+    }
+    info!(port = %port_name, sample_rate = jack_rate, "Registered JACK input port");
+    Ok((
+        Capture(client),
+        resample::resample(reader, jack_rate, sample_rate, resampler),
+    ))
+}
@@ -0,0 +1,215 @@
+use crate::models;
+use crate::paths;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Contents of the config file, of which only the `[models]` table is read.
+#[derive(Deserialize)]
+struct File {
+    #[serde(default)]
+    models: BTreeMap<String, Language>,
+}
+
+/// Model and scorer of a language, e.g. configured as
+/// `[models.de]` with `model = "german.pbmm"` and `scorer = "german.scorer"`.
+#[derive(Clone, Deserialize)]
+pub struct Language {
+    /// Path of the model, or of a directory to discover the model and scorer in.
+    pub model: PathBuf,
+    pub scorer: Option<PathBuf>,
+}
+
+impl Language {
+    /// Returns the paths of the model and of the scorer, if any, discovering them first if the
+    /// model is a directory.
+    pub fn resolve(&self) -> Result<(PathBuf, Option<PathBuf>), String> {
+        if !self.model.is_dir() {
+            return Ok((self.model.clone(), self.scorer.clone()));
+        }
+        let discovered = models::discover_dir(&self.model)?;
+        Ok((discovered.model, self.scorer.clone().or(discovered.scorer)))
+    }
+}
+
+/// Languages configured in the `[models]` table of the config file, keyed by code.
+#[derive(Default)]
+pub struct Languages(BTreeMap<String, Language>);
+
+impl Languages {
+    /// Returns the path of the default config file, `$XDG_CONFIG_HOME/speech2text/config.toml`.
+    pub fn default_path() -> PathBuf {
+        paths::config_file("config.toml")
+    }
+
+    /// Reads the languages configured in the config file at `path`. Relative paths are relative
+    /// to the directory of the file. A missing file configures no languages, unless `required`.
+    pub fn load(path: &Path, required: bool) -> Result<Self, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default())
+            }
+            Err(err) => return Err(format!("failed to read {}: {}", path.display(), err)),
+        };
+        let file: File = toml::from_str(&contents)
+            .map_err(|err| format!("failed to parse {}: {}", path.display(), err))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(Self(
+            file.models
+                .into_iter()
+                .map(|(code, language)| {
+                    let language = Language {
+                        model: dir.join(language.model),
+                        scorer: language.scorer.map(|scorer| dir.join(scorer)),
+                    };
+                    (code, language)
+                })
+                .collect(),
+        ))
+    }
+
+    /// Returns the language of `code`, failing with the configured codes if there is none.
+    pub fn get(&self, code: &str) -> Result<&Language, String> {
+        self.0.get(code).ok_or_else(|| {
+            if self.0.is_empty() {
+                format!("language `{}` is not configured, no languages are", code)
+            } else {
+                format!(
+                    "language `{}` is not configured, pass one of {}",
+                    code,
+                    self.0.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            }
+        })
+    }
+
+    /// Returns the code of the configured language, which `path` is named after, either as the
+    /// extension before its actual one, e.g. `foo.de.wav`, or as one of its directories below
+    /// `root`, e.g. `de/foo.wav`. The name of the file takes precedence.
+    pub fn of(&self, path: &Path, root: &Path) -> Option<&str> {
+        let configured = |name: Option<&str>| {
+            name.and_then(|name| self.0.get_key_value(name))
+                .map(|(code, _)| code.as_str())
+        };
+        let stem = path.file_stem().map(Path::new);
+        configured(stem.and_then(Path::extension).and_then(|ext| ext.to_str())).or_else(|| {
+            path.strip_prefix(root)
+                .ok()
+                .and_then(Path::parent)
+                .and_then(|dirs| dirs.iter().rev().find_map(|dir| configured(dir.to_str())))
+        })
+    }
+
+    /// Prints every configured language along with its files and whether they exist.
+    pub fn print(&self) {
+        if self.0.is_empty() {
+            return println!("No languages configured");
+        }
+        let state = |path: &Path| if path.exists() { "ok" } else { "missing" };
+        for (code, language) in &self.0 {
+            print!(
+                "{}: model {} ({})",
+                code,
+                language.model.display(),
+                state(&language.model)
+            );
+            match &language.scorer {
+                Some(scorer) => println!(", scorer {} ({})", scorer.display(), state(scorer)),
+                None => println!(", no scorer"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> (tempfile::TempDir, Languages) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, contents).unwrap();
+        let languages = Languages::load(&path, true).unwrap();
+        (dir, languages)
+    }
+
+    #[test]
+    fn load_relative() {
+        let (dir, languages) = load(
+            "[models.de]\nmodel = \"german.pbmm\"\nscorer = \"german.scorer\"\n\n\
+             [models.en]\nmodel = \"/models/english.pbmm\"\n",
+        );
+        let de = languages.get("de").unwrap();
+        assert_eq!(de.model, dir.path().join("german.pbmm"));
+        assert_eq!(de.scorer, Some(dir.path().join("german.scorer")));
+        let en = languages.get("en").unwrap();
+        assert_eq!(en.model, Path::new("/models/english.pbmm"));
+        assert_eq!(en.scorer, None);
+        assert_eq!(
+            languages.get("fr").err().unwrap(),
+            "language `fr` is not configured, pass one of de, en"
+        );
+    }
+
+    #[test]
+    fn load_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let languages = Languages::load(&path, false).unwrap();
+        assert_eq!(
+            languages.get("de").err().unwrap(),
+            "language `de` is not configured, no languages are"
+        );
+        assert!(Languages::load(&path, true)
+            .err()
+            .unwrap()
+            .starts_with("failed to read"));
+        fs::write(&path, "[models.de]\nscorer = \"german.scorer\"\n").unwrap();
+        assert!(Languages::load(&path, false)
+            .err()
+            .unwrap()
+            .starts_with("failed to parse"));
+    }
+
+    #[test]
+    fn of() {
+        let (_dir, languages) =
+            load("[models.de]\nmodel = \"de.pbmm\"\n\n[models.en]\nmodel = \"en.pbmm\"\n");
+        let root = Path::new("/audio");
+        let of = |path: &str| languages.of(Path::new(path), root);
+        assert_eq!(of("/audio/foo.de.wav"), Some("de"));
+        assert_eq!(of("/audio/de/foo.wav"), Some("de"));
+        assert_eq!(of("/audio/de/talks/foo.wav"), Some("de"));
+        assert_eq!(of("/audio/de/foo.en.wav"), Some("en"));
+        assert_eq!(of("/audio/fr/foo.wav"), None);
+        assert_eq!(of("/audio/foo.wav"), None);
+        assert_eq!(of("/de/foo.wav"), None);
+    }
+
+    #[test]
+    fn resolve_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("german.pbmm"), "").unwrap();
+        fs::write(dir.path().join("german.scorer"), "").unwrap();
+        let language = Language {
+            model: dir.path().to_path_buf(),
+            scorer: None,
+        };
+        assert_eq!(
+            language.resolve().unwrap(),
+            (
+                dir.path().join("german.pbmm"),
+                Some(dir.path().join("german.scorer"))
+            )
+        );
+        let language = Language {
+            scorer: Some("other.scorer".into()),
+            ..language
+        };
+        assert_eq!(language.resolve().unwrap().1, Some("other.scorer".into()));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tracing::info;
+
+/// Percentiles reported in the summary.
+const PERCENTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// Stages of an utterance, whose latency is measured so far.
+struct Pending {
+    /// When the last voiced frame was captured.
+    voiced: SystemTime,
+    /// When the silence padding completing the segment was captured.
+    padded: SystemTime,
+    /// When the pipeline completed the segment.
+    completed: SystemTime,
+    /// When decoding started and how long it took.
+    decoded: Option<(SystemTime, Duration)>,
+}
+
+/// Latency of an utterance, from the capture of its last voiced frame to its transcript being
+/// sent to the outputs, broken down by stage.
+#[derive(Clone, Copy)]
+pub struct Breakdown {
+    /// Silence padding, which completes the segment.
+    pub padding: Duration,
+    /// Buffering of captured audio, until the pipeline completed the segment.
+    pub buffering: Duration,
+    /// Waiting for a decoder worker.
+    pub queue: Duration,
+    pub decode: Duration,
+    /// Waiting for transcripts of preceding segments and sending to the outputs.
+    pub output: Duration,
+    pub total: Duration,
+}
+
+impl Breakdown {
+    const STAGES: [&'static str; 6] =
+        ["padding", "buffering", "queue", "decode", "output", "total"];
+
+    fn stages(&self) -> [Duration; 6] {
+        [
+            self.padding,
+            self.buffering,
+            self.queue,
+            self.decode,
+            self.output,
+            self.total,
+        ]
+    }
+}
+
+/// Measures the latency of every utterance, shared by the pipeline and the decoder workers.
+/// Utterances are identified by the sample offset of their end.
+#[derive(Clone, Default)]
+pub struct Latency(Arc<Mutex<State>>);
+
+#[derive(Default)]
+struct State {
+    pending: HashMap<u64, Pending>,
+    measured: Vec<Breakdown>,
+}
+
+impl Latency {
+    /// Records that the segment ending at `end` was completed, with its last voiced frame and the
+    /// following silence padding captured at `voiced` and `padded`, if known.
+    /// If unknown, the audio is assumed to have been captured in real time, as the padding
+    /// completing the segment was processed.
+    pub fn completed(
+        &self,
+        end: u64,
+        padding: Duration,
+        voiced: Option<SystemTime>,
+        padded: Option<SystemTime>,
+    ) {
+        let completed = SystemTime::now();
+        let padded = padded.unwrap_or(completed);
+        let voiced = voiced.unwrap_or_else(|| padded.checked_sub(padding).unwrap_or(padded));
+        self.lock().pending.insert(
+            end,
+            Pending {
+                voiced,
+                padded,
+                completed,
+                decoded: None,
+            },
+        );
+    }
+
+    /// Records that the segment ending at `end` was trimmed to end at `trimmed`, which its
+    /// transcript is sent with.
+    pub fn trimmed(&self, end: u64, trimmed: u64) {
+        let mut state = self.lock();
+        if let Some(pending) = state.pending.remove(&end) {
+            state.pending.insert(trimmed, pending);
+        }
+    }
+
+    /// Forgets the segment ending at `end`, the transcript of which is not sent.
+    pub fn discard(&self, end: u64) {
+        self.lock().pending.remove(&end);
+    }
+
+    /// Records that decoding the segment ending at `end` started at `started` and took `elapsed`.
+    pub fn decoded(&self, end: u64, started: SystemTime, elapsed: Duration) {
+        if let Some(pending) = self.lock().pending.get_mut(&end) {
+            pending.decoded = Some((started, elapsed));
+        }
+    }
+
+    /// Records that the transcript of the segment ending at `end` was sent to the outputs,
+    /// logging its latency.
+    pub fn sent(&self, end: u64) {
+        let sent = SystemTime::now();
+        let mut state = self.lock();
+        let pending = match state.pending.remove(&end) {
+            Some(pending) => pending,
+            None => return,
+        };
+        let (started, decode) = pending
+            .decoded
+            .unwrap_or((pending.completed, Duration::default()));
+        let since = |later: SystemTime, earlier: SystemTime| {
+            later.duration_since(earlier).unwrap_or_default()
+        };
+        let breakdown = Breakdown {
+            padding: since(pending.padded, pending.voiced),
+            buffering: since(pending.completed, pending.padded),
+            queue: since(started, pending.completed),
+            decode,
+            output: since(sent, started + decode),
+            total: since(sent, pending.voiced),
+        };
+        info!(
+            end,
+            padding = ?breakdown.padding,
+            buffering = ?breakdown.buffering,
+            queue = ?breakdown.queue,
+            decode = ?breakdown.decode,
+            output = ?breakdown.output,
+            total = ?breakdown.total,
+            "Measured latency of utterance"
+        );
+        state.measured.push(breakdown);
+    }
+
+    /// Prints the percentiles of the latency of every stage over all measured utterances.
+    pub fn print_summary(&self) {
+        let state = self.lock();
+        if state.measured.is_empty() {
+            return eprintln!("No utterances to measure latency of");
+        }
+        eprintln!(
+            "Latency of {} utterances in milliseconds:",
+            state.measured.len()
+        );
+        eprint!("{:>10}", "");
+        for percentile in PERCENTILES.iter() {
+            eprint!("{:>8}", format!("p{}", percentile * 100.0));
+        }
+        eprintln!("{:>8}", "max");
+        for (i, stage) in Breakdown::STAGES.iter().enumerate() {
+            let mut values: Vec<_> = state
+                .measured
+                .iter()
+                .map(|breakdown| breakdown.stages()[i])
+                .collect();
+            values.sort();
+            eprint!("{:>10}", stage);
+            for &p in PERCENTILES.iter() {
+                eprint!("{:>8}", percentile(&values, p).as_millis());
+            }
+            eprintln!("{:>8}", values[values.len() - 1].as_millis());
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0.lock().expect("Failed to lock latency")
+    }
+}
+
+/// Returns the nearest-rank `percentile` of the non-empty `sorted` values.
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    let index = ((sorted.len() as f64 * percentile).ceil() as usize).max(1) - 1;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let values: Vec<_> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&values, 0.5), Duration::from_millis(5));
+        assert_eq!(percentile(&values, 0.9), Duration::from_millis(9));
+        assert_eq!(percentile(&values, 0.99), Duration::from_millis(10));
+        assert_eq!(percentile(&values[..1], 0.5), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn breakdown() {
+        let latency = Latency::default();
+        let padded = SystemTime::now() - Duration::from_millis(300);
+        latency.completed(
+            100,
+            Duration::from_millis(200),
+            Some(padded - Duration::from_millis(200)),
+            Some(padded),
+        );
+        latency.decoded(100, SystemTime::now(), Duration::from_millis(50));
+        latency.sent(100);
+        // Only pending utterances are measured.
+        latency.sent(200);
+
+        let state = latency.lock();
+        assert_eq!(state.measured.len(), 1);
+        assert!(state.pending.is_empty());
+        let breakdown = state.measured[0];
+        assert_eq!(breakdown.padding, Duration::from_millis(200));
+        assert!(breakdown.buffering >= Duration::from_millis(300));
+        assert_eq!(breakdown.decode, Duration::from_millis(50));
+        assert!(breakdown.total >= Duration::from_millis(500));
+    }
+}
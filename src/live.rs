@@ -0,0 +1,81 @@
+use crate::output::{Event, Sink};
+use std::io::{self, Write};
+
+/// Erases the current line of the terminal.
+const CLEAR_LINE: &str = "\r\x1b[K";
+
+/// Renders partial transcripts on the current line of the terminal, overwriting them in place,
+/// until the final transcript is printed.
+#[derive(Default)]
+pub struct Live {
+    /// Whether a partial transcript is currently displayed.
+    partial: bool,
+}
+
+/// Returns whether stdout is a terminal.
+pub fn is_tty() -> bool {
+    atty::is(atty::Stream::Stdout)
+}
+
+/// Returns the end of `text` fitting into a line of the terminal, prefixed with an ellipsis if
+/// truncated.
+fn fit(text: &str) -> String {
+    match terminal_size::terminal_size() {
+        Some((terminal_size::Width(width), _)) => fit_width(text, width as usize),
+        None => text.to_string(),
+    }
+}
+
+/// Returns the end of `text` fitting into a line of `width` columns.
+fn fit_width(text: &str, width: usize) -> String {
+    // Leave the last column empty, so that the cursor does not wrap onto the next line.
+    let width = width.saturating_sub(1).max(1);
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_string();
+    }
+    let mut fitted = String::from("…");
+    fitted.extend(text.chars().skip(len - width + 1));
+    fitted
+}
+
+impl Sink for Live {
+    fn send(&mut self, event: &Event) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        // Failing to write to the terminal is not worth interrupting transcription for.
+        let _ = match event {
+            Event::Partial(utterance) | Event::StablePartial(utterance) => {
+                self.partial = true;
+                write!(stdout, "{}{}", CLEAR_LINE, fit(&utterance.text))
+            }
+            Event::Final(utterance) => {
+                let clear = if self.partial { CLEAR_LINE } else { "" };
+                self.partial = false;
+                writeln!(stdout, "{}{}", clear, utterance.text)
+            }
+            _ => return,
+        };
+        let _ = stdout.flush();
+    }
+
+    fn finish(&mut self) {
+        if self.partial {
+            print!("{}", CLEAR_LINE);
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit() {
+        assert_eq!(fit_width("hello world", 12), "hello world");
+        assert_eq!(fit_width("hello world", 8), "… world");
+        assert_eq!(fit_width("żółw żółw", 6), "…żółw");
+        assert_eq!(fit_width("hello", 0), "…");
+    }
+}
@@ -0,0 +1,98 @@
+use std::{io, str::FromStr};
+use tracing::Level;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Pretty,
+    Json,
+    #[cfg(feature = "journald")]
+    Journald,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            #[cfg(feature = "journald")]
+            "journald" => Ok(Self::Journald),
+            _ => Err(format!(
+                "failed to parse `{}` into log format of pretty or json{}",
+                s,
+                if cfg!(feature = "journald") {
+                    " or journald"
+                } else {
+                    ""
+                }
+            )),
+        }
+    }
+}
+
+/// Maps verbosity to the maximum level logged, only warnings and errors are logged by default.
+pub fn level(verbosity: u8) -> Level {
+    match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG`, if set, takes precedence over `level`.
+/// Closed spans are logged along with their duration.
+pub fn init(format: Format, level: Level) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(level.to_string().to_lowercase()));
+    match format {
+        Format::Pretty => tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(io::stderr)
+            .init(),
+        Format::Json => tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(filter)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(io::stderr)
+            .init(),
+        #[cfg(feature = "journald")]
+        Format::Journald => {
+            use tracing_subscriber::layer::SubscriberExt;
+
+            let journald = tracing_journald::layer().expect("Failed to connect to journald");
+            tracing::subscriber::set_global_default(
+                tracing_subscriber::registry().with(filter).with(journald),
+            )
+            .expect("Failed to set global tracing subscriber")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert_eq!(level(0), Level::WARN);
+        assert_eq!(level(1), Level::INFO);
+        assert_eq!(level(2), Level::DEBUG);
+        assert_eq!(level(3), Level::TRACE);
+        assert_eq!(level(u8::MAX), Level::TRACE);
+    }
+
+    #[test]
+    fn formats() {
+        assert!(matches!("pretty".parse(), Ok(Format::Pretty)));
+        assert!(matches!("json".parse(), Ok(Format::Json)));
+        assert_eq!(
+            "journald".parse::<Format>().is_ok(),
+            cfg!(feature = "journald")
+        );
+        assert!("syslog".parse::<Format>().is_err());
+    }
+}
@@ -1,261 +1,3434 @@
+use crate::model::Model;
 use audrey::Reader;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use dasp::sample::Sample;
-use dasp::Signal;
-use deepspeech::Model;
-use fvad::Fvad;
-use std::env;
-use std::{convert::TryInto, fs::File, path::PathBuf, str::FromStr, sync::mpsc, time::SystemTime};
-use structopt::StructOpt;
-
-enum FvadSampleLength {
-    Length10ms = 10,
-    Length20ms = 20,
-    Length30ms = 30,
-}
-
-impl FromStr for FvadSampleLength {
-    type Err = String;
-
-    fn from_str(s: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
-        match s {
-            "10" => Ok(Self::Length10ms),
-            "10ms" => Ok(Self::Length10ms),
-            "10 ms" => Ok(Self::Length10ms),
-
-            "20" => Ok(Self::Length20ms),
-            "20ms" => Ok(Self::Length20ms),
-            "20 ms" => Ok(Self::Length20ms),
-
-            "30" => Ok(Self::Length30ms),
-            "30ms" => Ok(Self::Length30ms),
-            "30 ms" => Ok(Self::Length30ms),
-
-            _ => Err(format!(
-                "failed to parse `{}` into Fvad sample rate of 10, 20 or 30 ms",
-                s
-            )),
+use clap::{ArgAction, ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::mpsc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::info;
+
+mod adaptive;
+#[cfg(feature = "metrics")]
+mod admin;
+#[cfg(all(target_os = "linux", feature = "capture", feature = "alsa"))]
+mod alsa;
+mod batch;
+#[cfg(feature = "capture")]
+mod beep;
+mod bench;
+mod cache;
+mod captions;
+#[cfg(feature = "capture")]
+mod capture;
+mod casing;
+mod censor;
+mod clipboard;
+mod clock;
+mod color;
+mod commands;
+mod compare;
+mod control;
+#[cfg(unix)]
+mod daemon;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod decoder;
+mod dedup;
+mod destination;
+#[cfg(feature = "capture")]
+mod device;
+mod dither;
+mod doctor;
+#[cfg(feature = "http")]
+mod download;
+mod durable;
+mod duration;
+mod error;
+mod eval;
+mod exec;
+mod export;
+mod ffmpeg;
+#[cfg(unix)]
+mod fifo;
+mod filter;
+#[cfg(feature = "capture")]
+mod follow;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod heartbeat;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(all(target_os = "linux", feature = "hotkey"))]
+mod hotkey;
+mod info;
+mod input;
+mod intents;
+#[cfg(all(feature = "capture", feature = "jack"))]
+mod jack;
+mod languages;
+mod latency;
+mod live;
+mod logging;
+mod metrics;
+#[cfg(feature = "capture")]
+mod mictest;
+mod model;
+mod models;
+#[cfg(feature = "capture")]
+mod monitor;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod multi;
+mod normalize;
+#[cfg(feature = "notifications")]
+mod notification;
+mod numbers;
+#[cfg(feature = "osc")]
+mod osc;
+mod output;
+mod paths;
+mod pipeline;
+#[cfg(any(feature = "grpc", feature = "websocket"))]
+mod pool;
+mod postprocess;
+mod priority;
+mod profile;
+#[cfg(all(feature = "capture", feature = "pulse"))]
+mod pulse;
+mod punctuate;
+#[cfg(feature = "websocket")]
+mod push;
+mod queue;
+mod raw;
+mod recordings;
+mod reload;
+mod replace;
+mod resample;
+mod retranscribe;
+mod schedule;
+mod schema;
+mod scorer;
+mod segmenter;
+mod segments;
+#[cfg(unix)]
+mod socket;
+mod stable;
+mod stats;
+mod statusbar;
+mod stuck;
+#[cfg(feature = "systemd")]
+mod systemd;
+mod template;
+mod timestamp;
+#[cfg(feature = "tls")]
+mod tls;
+mod transcript;
+#[cfg(feature = "tui")]
+mod tui;
+mod typing;
+mod vad;
+mod watch;
+mod wav;
+#[cfg(feature = "webhook")]
+mod webhook;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod wer;
+
+use error::Error;
+use pipeline::CHANNELS;
+use resample::Resampler;
+use vad::{FvadMode, FvadSampleLength};
+
+/// Sample rate of the released DeepSpeech models, which is used where no model is loaded: files
+/// are segmented at it with --vad-only, so that the segments match the ones transcribed, and the
+/// input is captured at it by mic-test.
+const MODEL_SAMPLE_RATE: u32 = 16000;
+
+#[derive(Parser)]
+#[command(
+    name = "speech2text",
+    version,
+    about = "Record voice and print text to stdout.",
+    // Audio is transcribed from a single kind of input.
+    group(ArgGroup::new("input").args(["file", "watch", "device"])),
+    after_help = "EXIT CODES:
+    0    Input exhausted or stopped by SIGTERM or Ctrl-C
+    1    Failed to set up an output or other failure
+    2    Invalid command line arguments
+    3    Failed to load the model
+    4    Failed to open the audio input device
+    5    Failed to read the input file
+    6    Failed to decode --max-decode-failures segments in a row
+    7    Failed to decode the speech in flight within --drain-timeout or --drain-grace
+    8    No speech was detected within --idle-timeout
+    130  Interrupted by a second SIGTERM or Ctrl-C"
+)]
+struct Opt {
+    /// Enable debugging, which writes recordings of segments and logs debug messages
+    #[arg(short, long)]
+    debug: bool,
+
+    /// Directory to write recordings of segments to when debugging, created if missing.
+    /// Defaults to $XDG_DATA_HOME/speech2text/recordings
+    #[arg(long)]
+    recordings_dir: Option<PathBuf>,
+
+    /// Maximum number of recordings kept in the recordings directory, the oldest are deleted first
+    #[arg(long)]
+    max_recordings: Option<usize>,
+
+    /// Maximum total size in bytes of recordings kept in the recordings directory, the oldest are
+    /// deleted first
+    #[arg(long)]
+    max_recordings_size: Option<u64>,
+
+    /// Format of recordings of segments, --record-session and --record-speech: wav or flac, if
+    /// built with the `flac` feature. FLAC takes about half the space of WAV for speech and is
+    /// read by --file just as well
+    #[arg(long, default_value = "wav")]
+    recording_format: recordings::Format,
+
+    /// Naming of recordings of segments: sequential, which names them <session start>_<seq>
+    /// after the sequence number of their utterance in structured output, e.g.
+    /// 20210501-093000_000042.wav, or timestamp, which names them after the nanoseconds since the
+    /// Unix epoch they were saved at
+    #[arg(long, default_value = "sequential")]
+    recording_names: recordings::Naming,
+
+    /// Bring every segment to --normalize-target with a constant gain before decoding, which
+    /// improves transcripts of quiet speakers. Segments are only amplified, never attenuated
+    #[arg(long)]
+    normalize_segments: bool,
+
+    /// RMS level in dBFS to bring segments to with --normalize-segments
+    #[arg(long, default_value = "-20", allow_hyphen_values = true)]
+    normalize_target: normalize::Level,
+
+    /// Peak level in dBFS, beyond which --normalize-segments never amplifies samples, so that they
+    /// do not clip
+    #[arg(long, default_value = "-1", allow_hyphen_values = true)]
+    normalize_ceiling: normalize::Level,
+
+    /// Record segments as normalized by --normalize-segments instead of as captured
+    #[arg(long, requires = "normalize_segments")]
+    record_normalized: bool,
+
+    /// Path to file to record the whole session to, including silence, in --recording-format.
+    /// Like --output, it may contain placeholders, so that recordings rotate, e.g. daily
+    #[arg(long)]
+    record_session: Option<template::Template>,
+
+    /// Path to file to record all speech segments to, without the silence between them, in
+    /// --recording-format
+    #[arg(long)]
+    record_speech: Option<PathBuf>,
+
+    /// Duration of silence inserted between segments recorded to --record-speech, e.g. 500ms.
+    /// Durations of this and other options are given with a unit, e.g. 300ms, 1.5s or 2m,
+    /// or as a bare number in the unit stated by the option
+    #[arg(long, default_value = "0", value_parser = duration::millis)]
+    record_speech_gap: Duration,
+
+    /// Minimum duration of speech in a segment, segments with less are not decoded. A bare number
+    /// is in milliseconds
+    #[arg(long, default_value = "0", value_parser = duration::millis)]
+    min_speech: Duration,
+
+    /// Duration of silence, which completes a segment once it follows speech, of at most 10s.
+    /// Segments are padded with up to as much silence on either side. A bare number is in
+    /// milliseconds
+    #[arg(long, default_value = "200ms", value_parser = duration::padding)]
+    silence_padding: Duration,
+
+    /// Maximum duration of an utterance, longer speech is split, which bounds the memory used for
+    /// buffering. A bare number is in seconds
+    #[arg(long, default_value = "60s", value_parser = duration::positive_secs)]
+    max_utterance_duration: Duration,
+
+    /// Duration at the end of an utterance reaching --max-utterance-duration, within which it is
+    /// split at the quietest frame, so that words are not cut in half, of at most 10s.
+    /// 0 splits right at the maximum duration. A bare number is in milliseconds
+    #[arg(long, default_value = "3s", value_parser = duration::padding)]
+    split_search: Duration,
+
+    /// Duration of audio before the split of an utterance, which is decoded again as part of the
+    /// following one, so that words cut by the split are not lost, of at most 10s. Words repeated
+    /// in both transcripts are removed from the following one. A bare number is in milliseconds
+    #[arg(long, default_value = "500ms", value_parser = duration::padding)]
+    split_overlap: Duration,
+
+    /// Minimum duration at the end of an utterance reaching --max-utterance-duration, which is
+    /// carried over into the following one instead of being searched for the quietest frame, so
+    /// that a split leaves at least this much, of less than --split-search. A bare number is in
+    /// milliseconds
+    #[arg(long, default_value = "0", value_parser = duration::padding)]
+    split_min_carry: Duration,
+
+    /// Trim the silence padding of segments before decoding them to --trim-margin around the
+    /// voiced frames, which makes decoding faster. Recordings are not trimmed
+    #[arg(long)]
+    trim_decode: bool,
+
+    /// Audio kept before the first voiced frame and after the last one of segments with
+    /// --trim-decode, of at most 10s. A bare number is in milliseconds
+    #[arg(long, default_value = "100ms", value_parser = duration::padding)]
+    trim_margin: Duration,
+
+    /// Audio discarded at the start of live capture before voice activity detection, since
+    /// microphones often click when powered on, of at most 10s. Files are never skipped. A bare
+    /// number is in milliseconds
+    #[arg(long, default_value = "100ms", value_parser = duration::padding)]
+    startup_skip: Duration,
+
+    /// Maximum number of segments waiting to be decoded
+    #[arg(long, default_value = "8")]
+    decode_queue: usize,
+
+    /// Niceness of the decode workers, e.g. 10, so that decoding yields CPU to audio capture and
+    /// other processes. Negative values require CAP_SYS_NICE or a nice limit allowing them
+    #[arg(long, allow_hyphen_values = true)]
+    decode_nice: Option<i32>,
+
+    /// Raise the audio capture thread to realtime priority, so that it is not starved by
+    /// decoding, warning if not permitted. On Linux, this requires CAP_SYS_NICE or an rtprio limit
+    /// of at least 10, e.g. `@audio - rtprio 95` in /etc/security/limits.conf for members of the
+    /// audio group. JACK clients run at realtime priority configured by the JACK server already
+    #[arg(long)]
+    realtime_audio: bool,
+
+    /// Number of segments failing to decode in a row, after which the process exits with code 6,
+    /// since the model is probably wedged. Segments failing to decode are skipped. 0 never exits
+    #[arg(long, default_value = "5")]
+    max_decode_failures: usize,
+
+    /// Path to write statistics of voice activity detection to as JSON once the session ends,
+    /// including on Ctrl-C: the number of frames, voiced frames and segments, histograms of the
+    /// durations of segments and of the silence between them with fixed buckets, so that runs are
+    /// comparable, and the settings of the detector. With several --device, those of the others
+    /// are written next to it with the position of the device appended, e.g. vad-2.json
+    #[arg(long)]
+    vad_stats: Option<PathBuf>,
+
+    /// Confidence of the model, below which final utterances are handled according to
+    /// --low-confidence. Recordings of them are saved regardless, so that the threshold can be
+    /// tuned with their confidence saved alongside
+    #[arg(long, allow_hyphen_values = true)]
+    min_confidence: Option<f64>,
+
+    /// What to do with utterances less confident than --min-confidence: drop them or mark them
+    /// with `low_confidence` in JSON
+    #[arg(long, default_value = "drop")]
+    low_confidence: pipeline::LowConfidence,
+
+    /// What to do when speech arrives faster than it can be decoded: block, drop-oldest or
+    /// drop-newest. Blocking stalls segmentation, until the capture buffer overflows.
+    /// Always block when transcribing a file
+    #[arg(long, default_value = "block")]
+    overflow_policy: decoder::OverflowPolicy,
+
+    /// Directory to save segments to, which contained speech, but were not decoded
+    #[arg(long)]
+    save_rejected: Option<PathBuf>,
+
+    /// Interval, at which a sample of continuous silence is saved to --save-rejected, e.g. 5m.
+    /// A bare number is in seconds
+    #[arg(long, value_parser = duration::positive_secs)]
+    save_silence_interval: Option<Duration>,
+
+    /// Increase verbosity of logs, may be repeated
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Format of logs written to stderr: pretty or json, or journald if built with the `journald`
+    /// feature
+    #[arg(long, default_value = "pretty")]
+    log_format: logging::Format,
+
+    /// Path to model, defaults to the model fetched by the download-model command. Given a
+    /// directory, the only model in it is used along with the only scorer in it, if any and
+    /// unless --scorer is set, preferring pbmm over tflite and pb models
+    #[arg(short, long, global = true)]
+    model: Option<PathBuf>,
+
+    /// Expected SHA256 digest of the model in hex, checked before loading it
+    #[arg(long)]
+    model_sha256: Option<String>,
+
+    /// Code of the language to transcribe, which selects the model and scorer configured for it
+    /// in the `[models]` table of the config file, e.g. `[models.de]` with `model = "de.pbmm"`
+    /// and `scorer = "de.scorer"`. When transcribing a directory, inputs named like `foo.de.wav`
+    /// or within a directory named `de` are transcribed with the model of `de` instead
+    #[arg(long, conflicts_with = "model")]
+    lang: Option<String>,
+
+    /// Print the languages configured in the config file, along with whether their files exist
+    #[arg(long)]
+    list_langs: bool,
+
+    /// Path to the config file, defaults to $XDG_CONFIG_HOME/speech2text/config.toml
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Path to external scorer, defaults to the scorer fetched by the download-model command
+    #[arg(long, global = true)]
+    scorer: Option<PathBuf>,
+
+    /// Decode without a scorer, even if one was downloaded, to compare against the acoustic model
+    /// alone
+    #[arg(long, conflicts_with = "scorer")]
+    no_scorer: bool,
+
+    /// Expected SHA256 digest of the scorer in hex, checked before loading it
+    #[arg(long)]
+    scorer_sha256: Option<String>,
+
+    /// Beam width of the model, a narrower beam decodes faster but less accurately. Defaults to
+    /// the beam width of the model
+    #[arg(long)]
+    beam_width: Option<u16>,
+
+    /// Bundle of defaults trading latency for accuracy: fast (beam width 100, very aggressive
+    /// Fvad, 100ms --silence-padding, 250ms --partial-interval and --trim-decode), balanced (beam
+    /// width 250, aggressive Fvad, 200ms padding, 500ms partials and --trim-decode) or accurate
+    /// (the beam width of the model, quality Fvad, 400ms padding and 1s partials). Options passed
+    /// explicitly override the values of the profile, which --print-config shows the sources of
+    #[arg(long)]
+    profile: Option<profile::Profile>,
+
+    /// Decode every segment with this model as well, e.g. to decide whether a new model is worth
+    /// switching to. Both transcripts are output, in text as a line prefixed with `primary:` and
+    /// one prefixed with `comparison:` and in JSON as objects `primary` and `comparison`, and a
+    /// summary of how often they differed and of the difference of decoding times is printed
+    /// once done. Segmentation runs once, so that both decode the same audio. Defaults to
+    /// --model, if --compare-scorer or --compare-beam-width is set. Both models are kept in
+    /// memory, which the user needs to have enough of
+    #[arg(long)]
+    compare_model: Option<PathBuf>,
+
+    /// Scorer of the comparison model, defaults to --scorer
+    #[arg(long)]
+    compare_scorer: Option<PathBuf>,
+
+    /// Beam width of the comparison model, defaults to the beam width of the model
+    #[arg(long)]
+    compare_beam_width: Option<u16>,
+
+    /// Decode final utterances less confident than this again with --rescore-beam-width and/or
+    /// without the scorer with --rescore-without-scorer, keeping the more confident transcript,
+    /// so that extra time is only spent on hard utterances. Rescored utterances are marked in
+    /// JSON and counted in the summary once done
+    #[arg(long)]
+    rescore_below: Option<f64>,
+
+    /// Beam width to decode utterances less confident than --rescore-below again with, e.g.
+    /// wider than --beam-width
+    #[arg(long, requires = "rescore_below")]
+    rescore_beam_width: Option<u16>,
+
+    /// Decode utterances less confident than --rescore-below again without the scorer.
+    /// DeepSpeech cannot switch a scorer off and on, so that the scorer is loaded from its file
+    /// again after every such decode, which takes about as long as loading it at startup
+    #[arg(long, requires = "rescore_below")]
+    rescore_without_scorer: bool,
+
+    /// Skip decoding silence after loading the model, which makes startup faster, but the first
+    /// utterance slower to transcribe
+    #[arg(long)]
+    no_warmup: bool,
+
+    /// Path to recording file, or to a directory to transcribe all WAV files within
+    #[arg(short, long)]
+    file: Option<PathBuf>,
+
+    /// Treat --file as headerless samples described like rate=16000,channels=1,format=s16le,
+    /// where format is one of s16le, s16be, f32le or u8. Channels default to 1 and format to s16le
+    #[arg(long)]
+    raw_format: Option<raw::Format>,
+
+    /// Decode --file and the files of transcribed directories with ffmpeg, which needs to be
+    /// installed, e.g. to transcribe m4a, webm or mkv files. Files the built-in decoders reject
+    /// are decoded with ffmpeg anyway, if it is installed
+    #[arg(long, conflicts_with = "raw_format")]
+    via_ffmpeg: bool,
+
+    /// Wall-clock time in RFC 3339, at which the recording passed to --file started, e.g.
+    /// 2021-04-01T14:30:00+02:00, so that utterances are reported with the times they were
+    /// captured at
+    #[arg(long, requires = "file", value_parser = clock::parse)]
+    file_start_time: Option<SystemTime>,
+
+    /// Start transcribing --file this far into it, e.g. 10s. A bare number is in seconds.
+    /// Timestamps are reported relative to the start of the file nonetheless, in its original
+    /// timebase regardless of its sample rate, unless --relative-timestamps is set
+    #[arg(long, requires = "file", value_parser = duration::positive_secs)]
+    start: Option<Duration>,
+
+    /// Report timestamps relative to --start rather than to the start of the file
+    #[arg(long, requires = "start")]
+    relative_timestamps: bool,
+
+    /// Path to write a JSON manifest to when transcribing or watching a directory, mapping every
+    /// input to its transcript, segments, duration, processing time and error, if any. It is
+    /// rewritten after every input
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+
+    /// Format of the transcript written for every input when transcribing or watching a
+    /// directory: txt, srt, vtt, json, audacity, a label track with the extension .labels.txt,
+    /// which Audacity imports, textgrid, a Praat TextGrid with an interval tier of utterances
+    /// spanning the whole input, markdown, meeting notes with a paragraph per pause longer than
+    /// --paragraph-gap, or 2s if unset, or ffmetadata, FFmpeg metadata with a chapter per
+    /// utterance, or per paragraph with --paragraph-gap, to mux with `ffmpeg -i audio -i
+    /// metadata -map_metadata 1`. Transcripts are written next to their inputs, unless
+    /// --output-dir is set. Defaults to txt if --output-dir or --watch is set
+    #[arg(long)]
+    output_format: Option<transcript::Format>,
+
+    /// Directory to write the transcript of every input to when transcribing or watching a
+    /// directory, preserving the paths of the inputs relative to that directory
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Path to file to append final transcripts to, one per line, in addition to stdout.
+    /// It may contain strftime-style placeholders expanded in local time: %Y, %m, %d, %H, %M, %S
+    /// and %j, e.g. transcripts/%Y-%m-%d.txt, in which case a new file is opened whenever the
+    /// expansion changes. Missing directories are created. It may be repeated and suffixed with
+    /// the format of the file: txt, the default, jsonl, a JSON object per line, or a format of
+    /// --output-format, e.g. captions.srt:srt, which is written once the session ends. Outputs,
+    /// which fail to be written, are disabled with a warning
+    #[arg(long, num_args = 1)]
+    output: Vec<destination::Destination>,
+
+    /// Maximum number of characters of a line of SRT and VTT subtitles, which cues are wrapped to
+    /// at word boundaries. Cues have at most two lines, utterances not fitting into them are
+    /// split into several cues. 0 disables wrapping
+    #[arg(long, default_value = "42")]
+    max_cue_chars: usize,
+
+    /// Maximum duration of a cue of SRT and VTT subtitles, beyond which utterances are split into
+    /// several consecutive cues, which share the words and the duration of the utterance evenly.
+    /// 0 disables splitting. A bare number is in milliseconds
+    #[arg(long, default_value = "6s", value_parser = duration::millis)]
+    max_cue_duration: Duration,
+
+    /// When --output and the --manifest of a batch are synced to disk, so that they survive power
+    /// cuts: none, which leaves it to the operating system, line, after every transcript, or
+    /// interval:<duration>, e.g. interval:10s, with the first transcript once the interval passed
+    /// since the previous sync and once the session ends
+    #[arg(long, default_value = "none")]
+    output_sync: durable::Policy,
+
+    /// What to do with an input of a directory, which already has a transcript written or a
+    /// manifest entry: skip it unless it was modified since, overwrite it or abort
+    #[arg(long, default_value = "overwrite")]
+    existing: batch::Existing,
+
+    /// Resume transcribing a directory, skipping inputs completed by the previous run, as
+    /// recorded in .speech2text-state.json within --output-dir or the transcribed directory.
+    /// Fails if the previous run used another model or other options, unless --force is set
+    #[arg(long)]
+    resume: bool,
+
+    /// Resume even if the previous run used another model or other options
+    #[arg(long, requires = "resume")]
+    force: bool,
+
+    /// Directory to cache the transcripts of inputs in when transcribing or watching a directory,
+    /// so that inputs, whose audio and options did not change, are not decoded again. Entries are
+    /// JSON files named by the SHA256 digest of the audio, the model path and modification time,
+    /// the scorer, the beam width and the other options, which may be deleted to prune the cache
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Decode every input without looking it up in --cache-dir, while still storing the new
+    /// transcripts in it
+    #[arg(long, requires = "cache_dir")]
+    no_cache: bool,
+
+    /// Directory to watch for new files to transcribe, writing a transcript of every file.
+    /// Files present when watching starts are transcribed first, unless --watch-new-only is set.
+    /// SIGTERM stops watching once the file in progress is transcribed
+    #[arg(long)]
+    watch: Option<PathBuf>,
+
+    /// Only transcribe files created after watching started
+    #[arg(long, requires = "watch")]
+    watch_new_only: bool,
+
+    /// Duration, for which the size of a new file must not change before it is transcribed, so
+    /// that files still being written are not transcribed early. A bare number is in milliseconds
+    #[arg(long, default_value = "2s", value_parser = duration::positive_millis)]
+    watch_debounce: Duration,
+
+    /// Glob matching the names of files to transcribe when transcribing or watching a directory
+    #[arg(long, default_value = "*.wav")]
+    pattern: glob::Pattern,
+
+    /// Segment --file without loading a model and print the boundaries of the segments, which
+    /// would be transcribed, in --output-format, text by default, e.g. to tune Fvad options.
+    /// Text has a line per segment of its start, end and duration in seconds
+    #[arg(long, requires = "file")]
+    vad_only: bool,
+
+    /// Directory to write every segment to as a numbered WAV file with --vad-only
+    #[arg(long, requires = "vad_only")]
+    save_segments: Option<PathBuf>,
+
+    /// Show a desktop notification of every transcript. Transcripts following each other quickly
+    /// are shown in one notification
+    #[cfg(feature = "notifications")]
+    #[arg(long)]
+    notify: bool,
+
+    /// Confidence, below which notifications of --notify are shown with low urgency
+    #[cfg(feature = "notifications")]
+    #[arg(long, allow_hyphen_values = true, requires = "notify")]
+    notify_low_urgency_below: Option<f64>,
+
+    /// Play a short tone on the default output device whenever a transcript is output, and a
+    /// lower one when an utterance is empty or dropped for --min-confidence
+    #[cfg(feature = "capture")]
+    #[arg(long)]
+    beep: bool,
+
+    /// WAV file to play instead of the tone for transcripts with --beep
+    #[cfg(feature = "capture")]
+    #[arg(long, requires = "beep")]
+    beep_file: Option<PathBuf>,
+
+    /// Volume of --beep from 0 to 1
+    #[cfg(feature = "capture")]
+    #[arg(long, default_value = "0.5", requires = "beep")]
+    beep_volume: f32,
+
+    /// Play back the audio the pipeline hears on the named output device, or the default one if no
+    /// name is given, e.g. to tune the gain and position of the microphone. Monitoring on speakers
+    /// picked up by the microphone causes feedback, so use headphones
+    #[cfg(feature = "capture")]
+    #[arg(long, conflicts_with = "file")]
+    monitor: Option<Option<String>>,
+
+    /// Name of the audio device to capture from, the default one is used if unset. Given several
+    /// times, the devices are captured from at once and each is segmented on its own, while
+    /// utterances are attributed to the device or the --speaker-label of the same position
+    #[arg(long = "device", id = "device", num_args = 1)]
+    devices: Vec<String>,
+
+    /// Follow changes of the default input device, e.g. once a headset is plugged in, switching
+    /// to the new one. Buffered speech is transcribed before the switch, which is output as a
+    /// mark. The default device is polled every 2s
+    #[cfg(feature = "capture")]
+    #[arg(long, conflicts_with_all = ["device", "file"])]
+    follow_default: bool,
+
+    /// Converter resampling files and captured audio to the sample rate of the model: linear or
+    /// sinc, if built with the `resample-hq` feature. Sinc is band-limited, so that consonants are
+    /// not smeared when downsampling, but costs more CPU and adds about 10ms of latency
+    #[arg(long, default_value = "linear")]
+    resampler: Resampler,
+
+    /// Do not dither float samples of files and audio devices when converting them to 16 bits,
+    /// which decorrelates the quantization error from quiet speech
+    #[arg(long)]
+    no_dither: bool,
+
+    /// List the audio devices, which can be passed to --device, and exit
+    #[cfg(feature = "capture")]
+    #[arg(long)]
+    list_devices: bool,
+
+    /// Capture the audio played by an output device, e.g. a call, instead of an input device,
+    /// using WASAPI loopback. Only the whole output of the device can be captured, not that of a
+    /// single application
+    #[cfg(windows)]
+    #[arg(long, conflicts_with_all = ["file", "watch"])]
+    capture_output: bool,
+
+    /// Capture from a JACK input port, which is left to be connected, e.g. with a patchbay.
+    /// Audio is resampled from the JACK sample rate to the one of the model
+    #[cfg(feature = "jack")]
+    #[arg(long, conflicts_with_all = ["file", "watch", "device"])]
+    jack: bool,
+
+    /// Name of the JACK client registered with --jack
+    #[cfg(feature = "jack")]
+    #[arg(long, default_value = "speech2text")]
+    jack_client_name: String,
+
+    /// Name of the PulseAudio or PipeWire source to record from, e.g. a monitor of a sink, as
+    /// listed by --list-devices. The server resamples the audio to the rate of the model
+    #[cfg(feature = "pulse")]
+    #[arg(long, conflicts_with_all = ["file", "watch", "device"])]
+    pulse_source: Option<String>,
+
+    /// ALSA PCM to capture from, e.g. plughw:1,0 or a PCM defined in asound.conf, bypassing the
+    /// device enumeration of --device
+    #[cfg(all(target_os = "linux", feature = "alsa"))]
+    #[arg(long, conflicts_with_all = ["file", "watch", "device"])]
+    alsa_device: Option<String>,
+
+    /// Number of segments of the recording file to decode in parallel, each by a separate copy of
+    /// the model. Every copy takes up as much memory as the first one does. Defaults to 1
+    #[arg(long, requires = "file")]
+    segment_jobs: Option<usize>,
+
+    /// Fvad sample length, e.g. 20ms: only values of 10, 20 or 30 ms are supported. A bare number
+    /// is in milliseconds
+    #[arg(long, default_value = "10ms", value_parser = vad::SampleLengthParser)]
+    fvad_sample_length: FvadSampleLength,
+
+    /// Voice activity detector delimiting segments: fvad, energy, which classifies frames louder
+    /// than an adaptive estimate of the noise floor by --energy-margin as voiced and needs no C
+    /// library, e.g. for clean studio recordings, or none, which classifies every frame as voiced,
+    /// so that audio is only split at --max-utterance-duration. Defaults to fvad, unless built
+    /// without the `fvad` feature
+    #[arg(long, default_value = vad::DEFAULT_DETECTOR)]
+    vad: vad::Detector,
+
+    /// Margin in dB above the noise floor, which frames need to exceed to be voiced with --vad
+    /// energy
+    #[arg(long, default_value = vad::DEFAULT_ENERGY_MARGIN)]
+    energy_margin: f64,
+
+    /// Fvad mode
+    #[arg(long)]
+    fvad_mode: Option<FvadMode>,
+
+    /// Adjust the Fvad mode to the audio, starting at --fvad-mode: it is made more aggressive if
+    /// nearly all audio is voiced, which is likely constant noise, and less aggressive if most
+    /// utterances are shorter than --min-speech, which likely clipped their onsets. The mode
+    /// changes between utterances at most once every 3 minutes of audio and each change is logged
+    #[arg(long, conflicts_with = "vad_ensemble")]
+    vad_adaptive: bool,
+
+    /// Detect voice with 2 or 3 Fvad instances in the given comma-separated modes instead of
+    /// --fvad-mode, e.g. quality,very-aggressive, combining their votes per --vad-policy. Votes
+    /// are traced per frame with RUST_LOG=speech2text::vad=trace
+    #[arg(long, conflicts_with = "fvad_mode")]
+    vad_ensemble: Option<vad::Ensemble>,
+
+    /// Policy combining the votes of --vad-ensemble: majority, or any, which starts speech once
+    /// any instance detects voice and ends it once all of them detect silence
+    #[arg(long, default_value = "majority", requires = "vad_ensemble")]
+    vad_policy: vad::Policy,
+
+    /// Address to serve Prometheus metrics on at /metrics, e.g. 127.0.0.1:9090
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Address to serve the admin API on, e.g. 127.0.0.1:9091: `GET /status` replies with the
+    /// JSON status of the control socket, including the input device and the time of the last
+    /// transcript, `POST /pause`, `/resume` and `/flush` control the pipeline like it
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    admin_addr: Option<std::net::SocketAddr>,
+
+    /// Bearer token, which requests to --admin-addr must carry in their Authorization header
+    #[cfg(feature = "metrics")]
+    #[arg(long, requires = "admin_addr")]
+    admin_token: Option<String>,
+
+    /// Serve the gRPC transcription service on the given address instead of recording
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc: Option<std::net::SocketAddr>,
+
+    /// Serve transcription sessions over WebSocket on the given address instead of recording, e.g.
+    /// for browsers. A session is configured with a first text message like
+    /// `{"sample_rate": 48000, "channels": 1, "format": "f32le"}`, followed by binary messages of
+    /// interleaved samples and `{"end": true}` once the audio ends. Transcripts and speech events
+    /// are sent back as JSON text messages
+    #[cfg(feature = "websocket")]
+    #[arg(long)]
+    ws_listen: Option<std::net::SocketAddr>,
+
+    /// Number of copies of the model loaded in server modes, so that as many gRPC streams and
+    /// WebSocket sessions are transcribed concurrently, sharing them across both servers. Every
+    /// copy takes up as much memory as the first one does
+    #[cfg(any(feature = "grpc", feature = "websocket"))]
+    #[arg(long, default_value = "1", value_parser = pool::parse_size)]
+    pool_size: usize,
+
+    /// Time a gRPC stream or WebSocket session waits for a copy of the model to become available
+    /// in server modes, once all are in use, before it is rejected with RESOURCE_EXHAUSTED or 503.
+    /// A bare number is in seconds
+    #[cfg(any(feature = "grpc", feature = "websocket"))]
+    #[arg(long, default_value = "30s", value_parser = duration::positive_secs)]
+    queue_timeout: Duration,
+
+    /// Time transcriptions in flight are given to complete once SIGTERM or Ctrl-C is received in
+    /// server modes, during which no new requests are accepted. The process exits with code 7,
+    /// if they did not complete in time, or 0 otherwise. A bare number is in seconds
+    #[cfg(feature = "grpc")]
+    #[arg(long, default_value = "30s", value_parser = duration::positive_secs)]
+    drain_grace: Duration,
+
+    /// PEM-encoded certificate chain, leaf first, which --grpc and --ws-listen are served over TLS
+    /// with, e.g. the fullchain.pem of Let's Encrypt. It is reloaded along with --tls-key on
+    /// SIGHUP, so that renewed certificates are served to new connections without a restart.
+    /// Servers are served without TLS, unless it is set
+    #[cfg(feature = "tls")]
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM-encoded PKCS#8 or RSA private key of --tls-cert, e.g. the privkey.pem of Let's Encrypt
+    #[cfg(feature = "tls")]
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Interval between partial transcripts of speech in progress, which are displayed with --live
+    /// and sent to gRPC and WebSocket clients, 0 disables them. A bare number is in milliseconds
+    #[arg(long, default_value = "1s", value_parser = duration::millis)]
+    partial_interval: Duration,
+
+    /// Output only the prefix of partial transcripts, which remained unchanged across this many
+    /// partial transcripts in a row, as stable partials in place of them. The prefix is only ever
+    /// extended until the final transcript replaces it, so that it may be rendered append-only
+    #[arg(long)]
+    stable_partials: Option<usize>,
+
+    /// Display partial transcripts as you speak, overwriting them in place once the final
+    /// transcript is ready. Only final transcripts are printed if stdout is not a terminal
+    #[arg(long)]
+    live: bool,
+
+    /// Clear the terminal and display the most recent transcripts as captions, e.g. on a second
+    /// monitor. Transcripts are printed as usual if stdout is not a terminal
+    #[arg(long, conflicts_with = "live")]
+    captions: bool,
+
+    /// Pause between utterances, after which a new paragraph starts. Paragraphs are separated by
+    /// blank lines in text, numbered in `paragraph` of JSON utterances and group utterances in
+    /// JSON transcripts. A bare number is in milliseconds
+    #[arg(long, value_parser = duration::positive_millis)]
+    paragraph_gap: Option<Duration>,
+
+    /// Prefix transcripts printed to stdout with the timestamps of their start and end in
+    /// --timestamp-format, seconds by default
+    #[arg(long)]
+    timestamps: bool,
+
+    /// Color every word of transcripts printed to stdout green, yellow or red by the confidence
+    /// of the model in it, see --color-thresholds. Disabled unless stdout is a terminal and
+    /// NO_COLOR is unset
+    #[arg(long)]
+    color_confidence: bool,
+
+    /// Confidences of words, below which --color-confidence colors them yellow and red, as
+    /// YELLOW,RED. The model only reports the confidence of whole transcripts, so every word is
+    /// assigned the confidence of its transcript divided by the number of its words
+    #[arg(long, default_value = "-1,-3", allow_hyphen_values = true)]
+    color_thresholds: color::Thresholds,
+
+    /// Format of timestamps printed with --timestamps and added to JSON as `start` and `end`:
+    /// seconds, e.g. 83.250, hms, e.g. 00:01:23.250, srt, e.g. 00:01:23,250, or iso8601, which is
+    /// the wall-clock time of capture, or a duration like PT83.250S if it is unknown. SRT and VTT
+    /// transcripts keep their own format
+    #[arg(long)]
+    timestamp_format: Option<timestamp::Format>,
+
+    /// Signed offset added to the timestamps of every output, e.g. +3.2s or -500ms, to sync
+    /// subtitles with a recording started before or after the transcription. Timestamps shifted
+    /// before the start are clamped to 0 with a warning. A bare number is in milliseconds. Capture
+    /// times are not shifted, neither are the transcripts of directories and --watch
+    #[arg(long, allow_hyphen_values = true)]
+    offset: Option<timestamp::Offset>,
+
+    /// Print a line of JSON in the format of waybar custom modules instead of transcripts, whenever
+    /// listening is paused or resumed, speech is detected or a transcript is output. The line
+    /// holds the last words heard in `text`, the most recent transcripts in `tooltip` and the
+    /// state in `class`: listening, paused or speech
+    #[arg(long, conflicts_with_all = ["live", "captions"])]
+    statusbar: bool,
+
+    /// Show an interactive terminal interface of the transcript, the input level, voice activity
+    /// and decoding statistics instead of printing transcripts, with keys to pause and resume,
+    /// flush, toggle recordings of --debug and quit. Logs are drawn over the interface, so
+    /// redirect stderr, e.g. 2>speech2text.log. Requires building with the `tui` feature
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with_all = ["statusbar", "live", "captions"])]
+    tui: bool,
+
+    /// Number of most recent transcripts displayed with --captions
+    #[arg(long, default_value = "3")]
+    caption_count: usize,
+
+    /// Display captions without highlighting the most recent transcript
+    #[arg(long)]
+    plain_captions: bool,
+
+    /// URL of the MQTT broker to publish transcripts to, e.g. mqtt://localhost:1883
+    #[cfg(feature = "mqtt")]
+    #[arg(long)]
+    mqtt_url: Option<String>,
+
+    /// MQTT topic to publish transcripts to, speech events are published to its `speech` sub-topic
+    /// and the online status to its `status` sub-topic
+    #[cfg(feature = "mqtt")]
+    #[arg(long, default_value = "speech2text")]
+    mqtt_topic: String,
+
+    /// MQTT username
+    #[cfg(feature = "mqtt")]
+    #[arg(long)]
+    mqtt_username: Option<String>,
+
+    /// MQTT password
+    #[cfg(feature = "mqtt")]
+    #[arg(long)]
+    mqtt_password: Option<String>,
+
+    /// MQTT QoS level of published messages: 0, 1 or 2
+    #[cfg(feature = "mqtt")]
+    #[arg(long, default_value = "0", value_parser = mqtt::parse_qos)]
+    mqtt_qos: rumqttc::QoS,
+
+    /// Emit transcripts as signals and accept control methods on the D-Bus session bus
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    dbus: bool,
+
+    /// Path to Unix domain socket to broadcast transcripts on as newline-delimited JSON
+    #[cfg(unix)]
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Octal permissions of the socket, e.g. 0660
+    #[cfg(unix)]
+    #[arg(long, value_parser = socket::parse_mode)]
+    socket_mode: Option<u32>,
+
+    /// Path to Unix domain socket to accept control commands on, a command per line: pause,
+    /// resume, flush or status. Every command is replied to with a line of JSON. The ctl command
+    /// sends commands to it
+    #[cfg(unix)]
+    #[arg(long, global = true)]
+    daemon_control: Option<PathBuf>,
+
+    /// Read control commands from stdin, a command per line: pause, resume, flush, quit or
+    /// `mark <label>`, which outputs the label as an annotation at the current offset, prefixed
+    /// with `#` on stdout. Unknown commands are reported on stderr and ignored
+    #[arg(long)]
+    control_stdin: bool,
+
+    /// Time the speech buffered and queued for decoding is given to be transcribed once SIGTERM,
+    /// Ctrl-C or the quit command is received. The transcripts decoded in time are output and
+    /// the process exits with code 7, if the others were abandoned. A bare number is in seconds
+    #[arg(long, default_value = "30s", value_parser = duration::positive_secs)]
+    drain_timeout: Duration,
+
+    /// Exit with code 8 once no speech was detected for this long, e.g. because the microphone is
+    /// muted or unplugged. Audio discarded while paused, outside of --schedule or by
+    /// --startup-skip does not count, nor do sessions of the gRPC and WebSocket servers. A bare
+    /// number is in seconds
+    #[arg(long, value_parser = duration::positive_secs)]
+    idle_timeout: Option<Duration>,
+
+    /// Windows of local time to listen during, outside of which audio is discarded without being
+    /// decoded, as if paused, e.g. 08:00-22:00 or mon-fri=07:00-23:00,sat+sun=09:00-24:00.
+    /// Speech in progress is transcribed once a window ends, marks are output once one starts or
+    /// ends. A pause applies within windows, while resuming has no effect outside of them
+    #[arg(long)]
+    schedule: Option<schedule::Schedule>,
+
+    /// Interval, at which a heartbeat is printed on stderr and sent to the socket, MQTT and
+    /// WebSocket outputs, reporting the audio processed since the previous one, which is none if
+    /// the input stalled, whether speech is in progress, the decode queue depth and the samples
+    /// dropped since the previous one. A bare number is in milliseconds
+    #[arg(long, value_parser = duration::positive_millis)]
+    heartbeat: Option<Duration>,
+
+    /// Path to named pipe to write transcripts to, one per line, created if missing
+    #[cfg(unix)]
+    #[arg(long)]
+    fifo: Option<PathBuf>,
+
+    /// Command to execute for every transcript, via `sh -c` unless it contains a `{}` placeholder
+    /// for the transcript. The transcript is also passed on stdin and in `TRANSCRIPT`, timestamps
+    /// in `TRANSCRIPT_START_MS` and `TRANSCRIPT_END_MS` and confidence in `TRANSCRIPT_CONFIDENCE`.
+    #[arg(long)]
+    exec: Option<String>,
+
+    /// Time after which commands executed for transcripts are killed. A bare number is in
+    /// milliseconds
+    #[arg(long, default_value = "10s", value_parser = duration::positive_millis)]
+    exec_timeout: Duration,
+
+    /// Maximum number of commands executed for transcripts running at once, transcripts arriving
+    /// while the limit is reached are skipped
+    #[arg(long, default_value = "4")]
+    exec_parallel_limit: usize,
+
+    /// File of rules executing commands for transcripts, one `pattern => command` pair per line,
+    /// e.g. `^(please )?lock the screen$ => loginctl lock-session`. The command of the first
+    /// rule matching a transcript after post-processing is executed like --exec, with capture
+    /// groups in `COMMAND_GROUP_1` and so on, or `COMMAND_GROUP_<NAME>` for named groups, and
+    /// output as a mark. Blank lines and lines starting with `#` are ignored
+    #[arg(long)]
+    command_rules: Option<PathBuf>,
+
+    /// Time after a rule of --command-rules executed its command, during which it does not
+    /// execute it again. A bare number is in milliseconds
+    #[arg(long, default_value = "2s", value_parser = duration::millis)]
+    command_cooldown: Duration,
+
+    /// Only match transcripts against --command-rules instead of outputting them, e.g. for kiosks
+    #[arg(long, requires = "command_rules")]
+    commands_only: bool,
+
+    /// Command to pipe final transcripts through after post-processing, e.g. a punctuation
+    /// model. It is executed once via `sh -c` and is written a line per transcript, to which it
+    /// responds with a line replacing the transcript. Transcripts are output unfiltered with a
+    /// warning, if it fails, exits or does not respond within --filter-timeout, in which case it
+    /// is restarted with backoff. A slow filter holds back the output, but not audio capture
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// Format of the lines exchanged with --filter: text, the transcript, or json, the utterance
+    /// as output by sockets, to which the filter responds with an object with the `text` of the
+    /// filtered transcript
+    #[arg(long, default_value = "text")]
+    filter_format: filter::Format,
+
+    /// Time --filter has to respond to a transcript. A bare number is in milliseconds
+    #[arg(long, default_value = "2s", value_parser = duration::positive_millis)]
+    filter_timeout: Duration,
+
+    /// Only transcribe while this key of --hotkey-device is held, e.g. KEY_F13 or a key code.
+    /// Audio is transcribed for --push-to-talk-tail after the key is released.
+    /// Hotkeys require Linux and the hotkey feature
+    #[arg(long)]
+    push_to_talk: Option<String>,
+
+    /// Duration of audio transcribed after the --push-to-talk key is released. A bare number is in
+    /// milliseconds
+    #[cfg_attr(not(all(target_os = "linux", feature = "hotkey")), allow(dead_code))]
+    #[arg(long, default_value = "300ms", value_parser = duration::millis)]
+    push_to_talk_tail: Duration,
+
+    /// Key of --hotkey-device, which pauses and resumes listening when pressed, e.g. KEY_PAUSE
+    #[arg(long)]
+    toggle_key: Option<String>,
+
+    /// Ring the terminal bell when --toggle-key pauses or resumes listening
+    #[cfg_attr(not(all(target_os = "linux", feature = "hotkey")), allow(dead_code))]
+    #[arg(long, requires = "toggle_key")]
+    toggle_bell: bool,
+
+    /// Show a desktop notification via notify-send when --toggle-key pauses or resumes listening
+    #[cfg_attr(not(all(target_os = "linux", feature = "hotkey")), allow(dead_code))]
+    #[arg(long, requires = "toggle_key")]
+    toggle_notify: bool,
+
+    /// Path to the evdev device to read hotkeys from, e.g. /dev/input/by-id/...-event-kbd.
+    /// Reading it requires membership of the input group or a udev rule granting access.
+    /// The device is not grabbed, so keys keep working in other applications
+    #[cfg_attr(not(all(target_os = "linux", feature = "hotkey")), allow(dead_code))]
+    #[arg(long)]
+    hotkey_device: Option<PathBuf>,
+
+    /// Command to execute via `sh -c` when speech starts, e.g. to pause music while dictating
+    #[arg(long)]
+    exec_on_speech_start: Option<String>,
+
+    /// Command to execute via `sh -c` when speech ends and does not resume within
+    /// --exec-on-speech-debounce
+    #[arg(long)]
+    exec_on_speech_end: Option<String>,
+
+    /// Duration, for which speech must not resume after it ended, before --exec-on-speech-end is
+    /// executed, so that brief pauses do not execute commands. A bare number is in milliseconds
+    #[arg(long, default_value = "500ms", value_parser = duration::millis)]
+    exec_on_speech_debounce: Duration,
+
+    /// Type transcripts into the focused window
+    #[arg(long = "type")]
+    type_text: bool,
+
+    /// Typing backend: wtype, xdotool or enigo, if built with the `enigo` feature.
+    /// Defaults to wtype on Wayland and xdotool on X11.
+    #[arg(long)]
+    type_backend: Option<typing::Backend>,
+
+    /// Key typed after each transcript: space, enter or none
+    #[arg(long, default_value = "space")]
+    type_separator: typing::Separator,
+
+    /// Delay between typed characters. A bare number is in milliseconds
+    #[arg(long, default_value = "12ms", value_parser = duration::millis)]
+    type_delay: Duration,
+
+    /// Only type transcripts starting with this wake word, which is itself not typed
+    #[arg(long)]
+    type_confirm: Option<String>,
+
+    /// Copy transcripts to the clipboard: replace overwrites it with each transcript and append
+    /// accumulates transcripts separated by newlines
+    #[arg(long)]
+    clipboard: Option<clipboard::Mode>,
+
+    /// Host and port to send transcripts to as OSC messages
+    #[cfg(feature = "osc")]
+    #[arg(long)]
+    osc: Option<String>,
+
+    /// OSC address of transcript messages
+    #[cfg(feature = "osc")]
+    #[arg(long, default_value = "/speech2text/transcript")]
+    osc_address: String,
+
+    /// WebSocket URL to push transcripts to as JSON text frames, e.g. wss://example.com/captions
+    #[cfg(feature = "websocket")]
+    #[arg(long)]
+    push_ws: Option<String>,
+
+    /// Bearer token to authenticate to the WebSocket with
+    #[cfg(feature = "websocket")]
+    #[arg(long)]
+    push_ws_token: Option<String>,
+
+    /// URL to POST events to as JSON, e.g. https://example.com/webhook/speech2text. Deliveries
+    /// failing with a server or connection error are retried with backoff, while at most 256
+    /// events wait to be delivered, beyond which the oldest are dropped
+    #[cfg(feature = "webhook")]
+    #[arg(long)]
+    webhook: Option<String>,
+
+    /// Comma-separated events POSTed to --webhook: transcript, with the utterance, speech_start
+    /// and speech_end, with the offset_ms of the speech
+    #[cfg(feature = "webhook")]
+    #[arg(long, default_value = "transcript", requires = "webhook")]
+    webhook_events: webhook::Events,
+
+    /// Secret shared with --webhook, with which bodies are signed in the X-Speech2text-Signature
+    /// header as `sha256=` followed by the hex-encoded HMAC-SHA256 of the body
+    #[cfg(feature = "webhook")]
+    #[arg(long, requires = "webhook")]
+    webhook_secret: Option<String>,
+
+    /// Path of an SQLite database to keep the history of final transcripts in, along with their
+    /// session, time, offsets, confidence, speaker and the input device or file. The database and
+    /// its schema are created on first use
+    #[cfg(feature = "history")]
+    #[arg(long)]
+    history_db: Option<PathBuf>,
+
+    /// Keep no history, even if --history-db is set, e.g. while dictating something sensitive
+    #[cfg(feature = "history")]
+    #[arg(long)]
+    history_off: bool,
+
+    /// Number of days transcripts are kept in --history-db for, after which they are pruned.
+    /// Defaults to keeping them forever
+    #[cfg(feature = "history")]
+    #[arg(long, requires = "history_db", value_parser = history::parse_days)]
+    history_retain: Option<Duration>,
+
+    /// Convert spoken English numbers into digits, e.g. "one hundred and five" into "105",
+    /// before replacement rules are applied
+    #[arg(long)]
+    normalize_numbers: bool,
+
+    /// Restore punctuation of transcripts after numbers are converted and before replacement
+    /// rules are applied: heuristic, which ends transcripts with a period, or a question mark if
+    /// they start with a question word, and a comma if they were split at
+    /// --max-utterance-duration while speech continued. Defaults to heuristic if no backend is
+    /// given
+    #[arg(long)]
+    punctuate: Option<Option<punctuate::Backend>>,
+
+    /// File of regular expression replacement rules applied to transcripts, one
+    /// `pattern => replacement` pair per line. Patterns starting with `(?i)` are case-insensitive
+    #[arg(long)]
+    replace_rules: Option<PathBuf>,
+
+    /// Log which replacement rules were applied to each transcript, at info level
+    #[arg(long, requires = "replace_rules")]
+    explain_replacements: bool,
+
+    /// Reload --replace-rules and --command-rules whenever their files change, keeping the
+    /// previous rules if the new ones fail to load. Reloads are output as marks
+    #[arg(long)]
+    reload_rules: bool,
+
+    /// File of intents matched against transcripts after replacement rules, one
+    /// `name = "pattern"` pair per line, e.g. `set_timer = "set a timer for {minutes:number}
+    /// minutes"`. Slots are of type number, word or text, defaulting to word. JSON records, e.g.
+    /// of jsonl --output, MQTT or the webhook, carry the intent and its slots as `intent`, or a
+    /// null one if none matches, while plain text output is left as it is
+    #[arg(long)]
+    intents: Option<PathBuf>,
+
+    /// Suppress final transcripts identical to the previous one, ignoring case and surrounding
+    /// whitespace, which start within this duration after it ended, e.g. because of echo. A bare
+    /// number is in milliseconds
+    #[arg(long, value_parser = duration::positive_millis)]
+    dedup_window: Option<Duration>,
+
+    /// What to do with transcripts suppressed by --dedup-window: drop, which does not output
+    /// them, or mark, which outputs them with `duplicate` set in JSON
+    #[arg(long, default_value = "drop", requires = "dedup_window")]
+    dedup_policy: dedup::Policy,
+
+    /// Intent chosen if a transcript matches several: first, which is the one defined first, or
+    /// best, which is the one with the most specific pattern
+    #[arg(long, default_value = "first", requires = "intents")]
+    intent_policy: intents::Policy,
+
+    /// Censor profanity in transcripts: mask, which replaces inner letters with asterisks, or
+    /// drop, which removes the words. Defaults to mask if no mode is given
+    #[arg(long)]
+    censor: Option<Option<censor::Mode>>,
+
+    /// File of additional words to censor, one per line
+    #[arg(long, requires = "censor")]
+    censor_list: Option<PathBuf>,
+
+    /// Only censor the words of --censor-list instead of extending the built-in list
+    #[arg(long, requires = "censor_list")]
+    censor_list_only: bool,
+
+    /// Casing of transcripts: raw, as produced by the model, sentence, which capitalizes the first
+    /// letter and "i", title or upper
+    #[arg(long, default_value = "raw")]
+    casing: casing::Casing,
+
+    /// Add the SHA256 digest and the number of the samples decoded to every utterance output as
+    /// JSON as audio_sha256 and audio_samples, which identify the audio across recordings and runs
+    #[arg(long)]
+    fingerprint: bool,
+
+    /// Label of the speaker attached to every utterance, e.g. when running an instance per
+    /// microphone. It is added to JSON and gRPC outputs and to SRT and WebVTT cues. Given once per
+    /// --device, it labels the utterances of the device of the same position
+    #[arg(long = "speaker-label", id = "speaker_label", num_args = 1)]
+    speaker_labels: Vec<String>,
+
+    /// Prefix plain text transcripts with --speaker-label, e.g. `Alice: hello`
+    #[arg(long, requires = "speaker_label")]
+    speaker_prefix: bool,
+
+    /// Log the latency of every utterance from the capture of its last voiced frame to its
+    /// transcript being output, broken down by stage, and print percentiles once finished
+    #[arg(long)]
+    measure_latency: bool,
+
+    /// Print the model, build and input device configuration to stderr as JSON once listening,
+    /// along with the effective value of every option set and its source: the command line,
+    /// --profile, the config file, the environment or the default. Secrets, like --mqtt-password,
+    /// are redacted. The options are logged with -v as well
+    #[arg(long)]
+    print_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download the DeepSpeech 0.9.3 English model and scorer, which are used by default.
+    /// Requires building with the `http` feature
+    DownloadModel {
+        /// Directory to download to, defaults to $XDG_DATA_HOME/speech2text/models
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Build a scorer from a plain text corpus, which can be passed to --scorer.
+    ///
+    /// Requires `lmplz`, `filter` and `build_binary` of KenLM (https://github.com/kpu/kenlm) and
+    /// `generate_scorer_package` of the DeepSpeech native client release in PATH.
+    BuildScorer {
+        /// Plain text corpus, one sentence per line
+        #[arg(long)]
+        corpus: PathBuf,
+
+        /// Alphabet the model was trained with, e.g. data/alphabet.txt of the DeepSpeech
+        /// repository for the English model
+        #[arg(long)]
+        alphabet: PathBuf,
+
+        /// Path to write the scorer to
+        #[arg(long)]
+        output: PathBuf,
+
+        /// Number of most frequent words of the corpus to keep in the vocabulary
+        #[arg(long, default_value = "500000")]
+        top_k: usize,
+
+        /// Order of the n-gram language model
+        #[arg(long, default_value = "5")]
+        order: u8,
+
+        /// Default language model weight of the scorer
+        #[arg(long, default_value = "0.93")]
+        default_alpha: f32,
+
+        /// Default word insertion weight of the scorer
+        #[arg(long, default_value = "1.18")]
+        default_beta: f32,
+    },
+    /// Segment a file with every combination of Fvad modes, sample lengths and silence paddings,
+    /// comparing the segments against a reference segmentation, and recommend the best one.
+    ///
+    /// Without --reference, the file is segmented once with the given Fvad and segmentation
+    /// options and the segments are printed as JSON, which can be corrected by hand to serve as
+    /// the reference.
+    BenchVad {
+        /// WAV file to segment
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Reference segmentation, a JSON array of objects with `start_ms` and `end_ms` fields
+        #[arg(long)]
+        reference: Option<PathBuf>,
+    },
+    /// Transcribe a recording and compute the word error rate of the transcript compared to a
+    /// reference transcript, after lowercasing both and stripping punctuation.
+    ///
+    /// If --file is a directory, every WAV file in it is evaluated against the text file of the
+    /// same name and the total word error rate is printed as well.
+    Eval {
+        /// WAV file or directory of WAV files to transcribe
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Reference transcript of --file, required unless --file is a directory
+        #[arg(long)]
+        reference: Option<PathBuf>,
+
+        /// Print the alignment of the transcript to the reference, word by word
+        #[arg(long)]
+        alignment: bool,
+    },
+    /// Transcribe the recordings in a directory, e.g. saved by --debug, again with the current
+    /// model and options and compare the transcripts to the ones in their JSON metadata.
+    ///
+    /// Prints the old and the new transcript of every recording whose transcript changed and a
+    /// summary of how many did.
+    Retranscribe {
+        /// Directory of recordings
+        dir: PathBuf,
+
+        /// Replace the text, confidence and model in the metadata of recordings with the new
+        /// results. Recordings without metadata are left as they are
+        #[arg(long)]
+        update: bool,
+    },
+    /// Send a command to the process listening on --daemon-control and print its reply: pause,
+    /// resume, flush or status
+    #[cfg(unix)]
+    Ctl { command: String },
+    /// Print the JSON Schema of utterances output as JSON, e.g. on --socket or in transcripts,
+    /// whose `schema_version` is bumped whenever their shape changes incompatibly
+    Schema,
+    /// Record a few seconds from the input selected by --device or the other input options,
+    /// print their peak and RMS levels and the fraction of frames Fvad classified as voice. Exits
+    /// with code 4 if the input was silent or clipping. No model is needed
+    #[cfg(feature = "capture")]
+    MicTest {
+        /// Duration to record, a bare number is in seconds
+        #[arg(long, default_value = "5s", value_parser = duration::positive_secs)]
+        seconds: Duration,
+
+        /// Play the recording back on the default output device
+        #[arg(long)]
+        playback: bool,
+    },
+    /// Check that the model loads, that an input device with a usable configuration exists,
+    /// that Fvad supports the rate of the model and that the recordings directory is writable,
+    /// printing remediation hints for failed checks. Exits with code 1 if any check failed
+    Doctor {
+        /// Print as JSON, e.g. to attach to bug reports
+        #[arg(long)]
+        json: bool,
+    },
+    /// Render the utterances of a session captured before in another format without decoding its
+    /// audio again, like its live output would have been rendered with --max-cue-chars,
+    /// --max-cue-duration, --paragraph-gap and --timestamp-format, which precede the command.
+    ///
+    /// The session is either a JSONL transcript, e.g. written with --output session.jsonl:jsonl,
+    /// or the ID of a session kept in --history-db. The output is printed to stdout.
+    Export {
+        /// JSONL transcript or ID of a session kept in --history-db, as listed by --list
+        #[arg(long, required_unless_present = "list")]
+        session: Option<String>,
+
+        /// Print the ID, start time, number of utterances and input of every session kept in
+        /// --history-db, separated by tabs, instead of exporting one
+        #[arg(long, conflicts_with = "session")]
+        list: bool,
+
+        /// Format to render the session in: txt, srt, vtt, json, audacity, textgrid, markdown or
+        /// ffmetadata, as of --output-format
+        #[arg(long, default_value = "txt")]
+        output_format: transcript::Format,
+    },
+    /// Print details about the model and the build
+    Info {
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+fn main() {
+    let matches = Opt::command()
+        .try_get_matches_from(std::env::args_os())
+        .unwrap_or_else(|err| {
+            if !err.use_stderr() {
+                // Help or version was requested.
+                err.exit()
+            }
+            Error::Usage(err.to_string()).exit()
+        });
+    let mut opt =
+        Opt::from_arg_matches(&matches).unwrap_or_else(|err| Error::Usage(err.to_string()).exit());
+    if let Err(err) = check_vad(&opt, &matches) {
+        err.exit()
+    }
+    let effective = apply_profile(&mut opt, &matches);
+    if let Err(err) = run(opt, effective) {
+        err.exit()
+    }
+}
+
+/// Fails with `Error::Usage`, if options of Fvad are passed along with another --vad, which clap
+/// cannot declare conflicts with, since the detector is a value of --vad. Values the profile gives
+/// them are ignored by other detectors instead, as are they by the default detector of builds
+/// without the `fvad` feature.
+fn check_vad(opt: &Opt, matches: &clap::ArgMatches) -> Result<(), Error> {
+    let explicit =
+        |id: &str| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+    if matches!(opt.vad, vad::Detector::Fvad) || !explicit("vad") {
+        return Ok(());
+    }
+    for (id, flag, what) in [
+        ("vad_adaptive", "--vad-adaptive", "adjusts the Fvad mode"),
+        ("fvad_mode", "--fvad-mode", "configures Fvad"),
+        ("vad_ensemble", "--vad-ensemble", "detects voice with Fvad"),
+    ] {
+        if explicit(id) {
+            return Err(Error::Usage(format!(
+                "{} {}, which requires --vad fvad",
+                flag, what
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the values of the options covered by --profile, which were not passed explicitly,
+/// with the ones of the profile, returning the effective values of all options set along with
+/// their sources.
+fn apply_profile(opt: &mut Opt, matches: &clap::ArgMatches) -> profile::Effective {
+    let explicit =
+        |id: &str| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+    let millis = |duration: &Duration| serde_json::json!(duration.as_millis() as u64);
+    let mut effective = profile::Effective::new(opt.profile);
+    effective.resolve(
+        "beam-width",
+        explicit("beam_width"),
+        &mut opt.beam_width,
+        |settings| settings.beam_width,
+        |width| serde_json::json!(width),
+    );
+    effective.resolve(
+        "fvad-mode",
+        explicit("fvad_mode") || explicit("vad_ensemble"),
+        &mut opt.fvad_mode,
+        |settings| settings.fvad_mode,
+        |mode| serde_json::json!(mode.map(|mode| mode as u8)),
+    );
+    effective.resolve(
+        "silence-padding",
+        explicit("silence_padding"),
+        &mut opt.silence_padding,
+        |settings| settings.silence_padding,
+        millis,
+    );
+    effective.resolve(
+        "partial-interval",
+        explicit("partial_interval"),
+        &mut opt.partial_interval,
+        |settings| settings.partial_interval,
+        millis,
+    );
+    effective.resolve(
+        "trim-decode",
+        explicit("trim_decode"),
+        &mut opt.trim_decode,
+        |settings| settings.trim_decode,
+        |trim| serde_json::json!(trim),
+    );
+    effective.record(&Opt::command(), matches);
+    if let Ok(filter) = std::env::var("RUST_LOG") {
+        effective.set("RUST_LOG", serde_json::json!(filter), "environment");
+    }
+    effective
+}
+
+fn run(mut opt: Opt, mut effective: profile::Effective) -> Result<(), Error> {
+    logging::init(
+        opt.log_format,
+        logging::level(opt.verbose + if opt.debug { 2 } else { 0 }),
+    );
+    if let Some(format) = opt.timestamp_format {
+        timestamp::configure(format);
+    }
+    let input_options = input_options(&opt);
+
+    let mut eval = None;
+    let mut retranscribe = None;
+    #[cfg(feature = "capture")]
+    let mut mic_test = None;
+    let info_json = match opt.command.take() {
+        Some(Command::DownloadModel { dir }) => return download_model(dir),
+        Some(Command::BuildScorer {
+            corpus,
+            alphabet,
+            output,
+            top_k,
+            order,
+            default_alpha,
+            default_beta,
+        }) => {
+            return scorer::build(&scorer::Options {
+                corpus,
+                alphabet,
+                output,
+                top_k,
+                order,
+                default_alpha,
+                default_beta,
+            })
+            .map_err(Error::with(Error::Other, "Failed to build scorer"))
+        }
+        Some(Command::BenchVad { file, reference }) => {
+            let (samples, sample_rate) = bench::read(&file).map_err(Error::Input)?;
+            let params = bench_params(&opt);
+            match reference {
+                Some(reference) => {
+                    let reference = bench::load(&reference).map_err(Error::Input)?;
+                    bench::run(&samples, sample_rate, params, &reference)
+                }
+                None => println!(
+                    "{}",
+                    bench::to_json(&bench::segment(&samples, sample_rate, &params))
+                ),
+            }
+            return Ok(());
+        }
+        Some(Command::Eval {
+            file,
+            reference,
+            alignment,
+        }) => {
+            let pairs = eval::pairs(&file, reference.as_deref()).map_err(Error::Usage)?;
+            eval = Some((pairs, alignment));
+            None
+        }
+        Some(Command::Retranscribe { dir, update }) => {
+            let recordings = retranscribe::recordings(&dir).map_err(Error::Usage)?;
+            retranscribe = Some((recordings, update));
+            None
+        }
+        #[cfg(unix)]
+        Some(Command::Ctl { command }) => return ctl(opt.daemon_control, &command),
+        Some(Command::Schema) => {
+            println!(
+                "{:#}",
+                serde_json::to_value(schema::utterance()).expect("Failed to serialize schema")
+            );
+            return Ok(());
+        }
+        Some(Command::Doctor { json }) => {
+            return doctor::run(
+                doctor::Options {
+                    model: opt.model,
+                    model_sha256: opt.model_sha256,
+                    device: opt.devices.into_iter().next(),
+                    recordings_dir: opt.recordings_dir,
+                    fvad_mode: opt.fvad_mode,
+                    default_sample_rate: MODEL_SAMPLE_RATE,
+                },
+                json,
+            )
+        }
+        #[cfg(feature = "capture")]
+        Some(Command::MicTest { seconds, playback }) => {
+            mic_test = Some((seconds, playback));
+            None
+        }
+        Some(Command::Export {
+            session,
+            list,
+            output_format,
+        }) => {
+            #[cfg(feature = "history")]
+            let history_db = opt.history_db.as_deref();
+            #[cfg(not(feature = "history"))]
+            let history_db = None;
+            let options = export::Options {
+                format: output_format,
+                cues: transcript::Cues {
+                    max_chars: opt.max_cue_chars,
+                    max_duration: opt.max_cue_duration,
+                },
+                paragraph_gap: opt.paragraph_gap,
+            };
+            return export_session(session, list, history_db, &options);
+        }
+        Some(Command::Info { json }) => Some(json),
+        None => None,
+    };
+    if cfg!(not(all(target_os = "linux", feature = "hotkey")))
+        && (opt.push_to_talk.is_some() || opt.toggle_key.is_some())
+    {
+        return Err(Error::Usage(
+            "Hotkeys require Linux and speech2text built with the hotkey feature".into(),
+        ));
+    }
+    #[cfg(feature = "capture")]
+    if opt.list_devices {
+        device::list(&cpal::default_host(), cfg!(windows))?;
+        #[cfg(feature = "pulse")]
+        pulse::list()?;
+        return Ok(());
+    }
+    #[cfg(feature = "capture")]
+    if let Some((duration, playback)) = mic_test {
+        let (samples, _input) = input::open(
+            &input_options,
+            MODEL_SAMPLE_RATE,
+            opt.resampler,
+            !opt.no_dither,
+            clock::Anchor::default(),
+            &mut None,
+        )?;
+        return mictest::run(
+            samples,
+            MODEL_SAMPLE_RATE,
+            duration,
+            opt.fvad_mode,
+            opt.fvad_sample_length,
+            playback,
+        );
+    }
+    if opt.vad_only {
+        return vad_only(opt);
+    }
+
+    let languages = match &opt.config {
+        Some(path) => languages::Languages::load(path, true),
+        None => languages::Languages::load(&languages::Languages::default_path(), false),
+    }
+    .map_err(Error::with(Error::Usage, "Failed to read config file"))?;
+    if opt.list_langs {
+        languages.print();
+        return Ok(());
+    }
+    let files = select_model(&opt, &languages, &mut effective)?;
+    let (passes, replace_rules) = passes(&opt)?;
+    let (exec_timeout, exec_parallel_limit) = (opt.exec_timeout, opt.exec_parallel_limit);
+    let command_rules = opt
+        .command_rules
+        .as_ref()
+        .map(|path| commands::Rules::load(path, exec_timeout, exec_parallel_limit))
+        .transpose()
+        .map_err(Error::with(Error::Usage, "Failed to load command rules"))?
+        .map(reload::Reloadable::new);
+
+    let mut model = files.load()?;
+    let sample_rate = model.get_sample_rate() as u32;
+    let (resampler, dither) = (opt.resampler, !opt.no_dither);
+    let (raw_format, via_ffmpeg) = (opt.raw_format, opt.via_ffmpeg);
+    info!(path = %files.model.display(), sample_rate, "Loaded model");
+    match &files.scorer {
+        Some(path) => info!(path = %path.display(), "Enabled scorer"),
+        None => info!("Decoding without a scorer"),
+    }
+    if let Some(json) = info_json {
+        let report = info::report(&model, &files.model, files.scorer.as_deref());
+        if json {
+            println!("{}", report);
+        } else {
+            info::print(&report);
+        }
+        return Ok(());
+    }
+    let mut report = if opt.print_config {
+        let mut report = info::report(&model, &files.model, files.scorer.as_deref());
+        report["config"] = effective.to_json();
+        Some(report)
+    } else {
+        None
+    };
+    if !opt.no_warmup {
+        warm_up(&mut model, sample_rate)?;
+    }
+    let comparison = comparison(&opt, &files, sample_rate)?;
+    let rescore = rescore(&opt, files.scorer.as_ref())?;
+    check_options(&opt)?;
+    let mut config = pipeline_config(&opt, sample_rate, &files.model, comparison, rescore)?;
+
+    if let Some((recordings, update)) = retranscribe {
+        return retranscribe::run(&recordings, update, &files.model, |path| {
+            let samples = read_file(path, None, false, sample_rate, resampler, dither)?;
+            decoder::transcribe(&mut model, &samples)
+                .map_err(Error::with(Error::Decode, "Failed to decode recording"))
+        });
+    }
+
+    if let Some((pairs, alignment)) = eval {
+        config.overflow_policy = decoder::OverflowPolicy::Block;
+        let models = vec![Arc::new(Mutex::new(model))];
+        let (_control_tx, control_rx) = mpsc::channel();
+        let mut sink = postprocess::PostProcess {
+            passes,
+            sink: eval::Transcript::default(),
+        };
+        return eval::run(&pairs, alignment, |path| {
+            let samples = read_file(path, raw_format, via_ffmpeg, sample_rate, resampler, dither)?;
+            pipeline::run(
+                samples.into_iter(),
+                models.clone(),
+                &config,
+                &control_rx,
+                &mut sink,
+            )?;
+            Ok(sink.sink.take())
+        });
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = opt.metrics_addr {
+        metrics::serve(addr).map_err(Error::with(Error::Other, "Failed to serve metrics"))?;
+        info!(%addr, "Serving metrics");
+    }
+
+    #[cfg(any(feature = "grpc", feature = "websocket"))]
+    if serves(&opt) {
+        return serve(&opt, model, &files, config);
+    }
+
+    let models: Vec<_> = copies(&opt, model, &files, opt.segment_jobs.unwrap_or(1))?
+        .into_iter()
+        .map(|model| Arc::new(Mutex::new(model)))
+        .collect();
+    if models.len() > 1 {
+        info!(
+            copies = models.len(),
+            "Loaded model copies for parallel decoding"
+        );
+    }
+
+    let batch_dir = match (&opt.file, &opt.watch) {
+        (Some(path), _) if path.is_dir() => Some(path.clone()),
+        (_, Some(dir)) => Some(dir.clone()),
+        _ => None,
+    };
+    if let Some(dir) = batch_dir {
+        return transcribe_dir(opt, dir, &files, &languages, models, &config, passes);
+    }
+
+    let (control_tx, control_rx) = mpsc::channel();
+    let live = opt.file.is_none();
+    if live {
+        config.startup_skip = opt.startup_skip;
+    }
+    let source = open_source(
+        &opt,
+        &input_options,
+        &models,
+        &mut config,
+        &control_tx,
+        &mut report,
+    )?;
+
+    stop_on_interrupt(control_tx.clone())?;
+    #[cfg(any(unix, feature = "metrics"))]
+    let status = control::Status::new(source.device_name.clone());
+    #[cfg(unix)]
+    let _daemon = opt
+        .daemon_control
+        .clone()
+        .map(|path| daemon::Daemon::listen(path, control_tx.clone(), status.clone()))
+        .transpose()
+        .map_err(Error::with(Error::Other, "Failed to bind control socket"))?;
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = opt.admin_addr {
+        admin::serve(addr, opt.admin_token.clone(), control_tx.clone(), status)
+            .map_err(Error::with(Error::Other, "Failed to serve admin API"))?;
+        info!(%addr, "Serving admin API");
+    }
+    if opt.control_stdin {
+        control::stdin(control_tx.clone());
+    }
+    if let Some(interval) = opt.heartbeat {
+        heartbeat::spawn(interval, control_tx.clone());
+    }
+    if opt.reload_rules {
+        watch_rules(
+            &opt,
+            replace_rules.as_ref(),
+            command_rules.as_ref(),
+            &control_tx,
+        )?;
+    }
+    #[cfg(all(target_os = "linux", feature = "hotkey"))]
+    hotkeys(&opt, &control_tx)?;
+
+    let tty = live::is_tty();
+    if (opt.live || opt.captions) && tty && opt.partial_interval > Duration::from_millis(0) {
+        config.partial_interval = Some(opt.partial_interval);
+    }
+    let mut sinks = vec![prefixed(&opt, text_sink(&opt, tty, &control_tx)?)];
+    let loudness = normalize::Summary::default();
+    sinks.push(Box::new(loudness.clone()));
+    let speech = stats::Speech::default();
+    sinks.push(Box::new(speech.clone()));
+    sinks.extend(outputs(
+        &opt,
+        &config,
+        &files.model,
+        source.device_name.as_deref(),
+        &control_tx,
+    )?);
+
+    if let Some(report) = report {
+        eprintln!("{}", report);
+    }
+    eprintln!("ready");
+    let mut sink = postprocess::PostProcess {
+        passes,
+        sink: filter::Filter::spawn(filter_options(&opt), layer(&opt, command_rules, sinks)),
+    };
+    let result = match source.inputs {
+        Some(inputs) => inputs.run(&config, &opt.speaker_labels, control_rx, &mut sink),
+        None => pipeline::run(source.samples, models, &config, &control_rx, &mut sink),
+    };
+    let ffmpeg_status = source.ffmpeg_status;
+    let result =
+        result.and_then(
+            |()| match ffmpeg_status.as_ref().and_then(ffmpeg::Status::failure) {
+                Some(err) => Err(Error::Input(err)),
+                None => Ok(()),
+            },
+        );
+    if live {
+        log_session(&loudness, &speech);
+    }
+    if let Some(latency) = &config.latency {
+        latency.print_summary();
+    }
+    result
+}
+
+/// Stops transcribing on SIGTERM or Ctrl-C, exiting at once on a second one.
+fn stop_on_interrupt(control_tx: mpsc::Sender<control::Control>) -> Result<(), Error> {
+    let interrupted = AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        if interrupted.swap(true, Ordering::SeqCst) {
+            // Interrupted while already stopping, give up on finishing gracefully.
+            process::exit(130);
         }
+        #[cfg(feature = "systemd")]
+        systemd::notify(sd_notify::NotifyState::Stopping);
+        let _ = control_tx.send(control::Control::Stop);
+    })
+    .map_err(Error::with(Error::Other, "Failed to set Ctrl-C handler"))
+}
+
+/// Logs the counters of the session, along with the loudness and speech of its utterances.
+fn log_session(loudness: &normalize::Summary, speech: &stats::Speech) {
+    info!(
+        utterances = metrics::UTTERANCES.load(Ordering::Relaxed),
+        dropped_samples = metrics::DROPPED_SAMPLES.load(Ordering::Relaxed),
+        gaps = metrics::GAPS.load(Ordering::Relaxed),
+        dropped_ms = metrics::DROPPED_MICROS.load(Ordering::Relaxed) / 1000,
+        dropped_segments = metrics::DROPPED_SEGMENTS.load(Ordering::Relaxed),
+        low_confidence = metrics::LOW_CONFIDENCE_UTTERANCES.load(Ordering::Relaxed),
+        duplicates = metrics::DUPLICATES.load(Ordering::Relaxed),
+        rescored = metrics::RESCORED.load(Ordering::Relaxed),
+        rescore_changed = metrics::RESCORE_CHANGED.load(Ordering::Relaxed),
+        xruns = metrics::XRUNS.load(Ordering::Relaxed),
+        webhook_sent = metrics::WEBHOOK_SENT.load(Ordering::Relaxed),
+        webhook_failed = metrics::WEBHOOK_FAILED.load(Ordering::Relaxed),
+        webhook_dropped = metrics::WEBHOOK_DROPPED.load(Ordering::Relaxed),
+        "Session finished"
+    );
+    if let Some((min, median)) = loudness.rms() {
+        info!(
+            min_rms_db = min,
+            median_rms_db = median,
+            "Loudness of utterances"
+        );
     }
+    speech.log_summary();
 }
 
-enum FvadMode {
-    Quality = 0,
-    LowBitrate = 1,
-    Aggressive = 2,
-    VeryAggressive = 3,
+/// Returns the options of the audio input captured from.
+fn input_options(opt: &Opt) -> input::Options {
+    input::Options {
+        device: opt.devices.first().cloned(),
+        realtime: opt.realtime_audio,
+        #[cfg(windows)]
+        capture_output: opt.capture_output,
+        #[cfg(feature = "jack")]
+        jack: if opt.jack {
+            Some(opt.jack_client_name.clone())
+        } else {
+            None
+        },
+        #[cfg(feature = "pulse")]
+        pulse_source: opt.pulse_source.clone(),
+        #[cfg(all(target_os = "linux", feature = "alsa"))]
+        alsa_device: opt.alsa_device.clone(),
+    }
 }
 
-impl Into<fvad::Mode> for FvadMode {
-    fn into(self) -> fvad::Mode {
-        match self {
-            Self::Quality => fvad::Mode::Quality,
-            Self::LowBitrate => fvad::Mode::LowBitrate,
-            Self::Aggressive => fvad::Mode::Aggressive,
-            Self::VeryAggressive => fvad::Mode::VeryAggressive,
-        }
+/// Returns the parameters audio is segmented with by bench-vad and --vad-only.
+fn bench_params(opt: &Opt) -> bench::Params {
+    bench::Params {
+        mode: opt.fvad_mode,
+        length: opt.fvad_sample_length,
+        padding: opt.silence_padding,
+        min_speech: opt.min_speech,
+        max_utterance: opt.max_utterance_duration,
+        split_search: opt.split_search,
+        split_overlap: opt.split_overlap,
+        split_min_carry: opt.split_min_carry,
+    }
+}
+
+/// Prints the segments of speech of --file without loading a model, saving them to
+/// --save-segments, if passed.
+fn vad_only(opt: Opt) -> Result<(), Error> {
+    let path = opt
+        .file
+        .as_ref()
+        .expect("Failed to require --file for --vad-only");
+    let samples = read_file(
+        path,
+        opt.raw_format,
+        opt.via_ffmpeg,
+        MODEL_SAMPLE_RATE,
+        opt.resampler,
+        !opt.no_dither,
+    )?;
+    let segments = bench::segments(&samples, MODEL_SAMPLE_RATE, &bench_params(&opt), false);
+    print!(
+        "{}",
+        segments::render(
+            &segments,
+            MODEL_SAMPLE_RATE,
+            samples.len(),
+            opt.output_format.unwrap_or(transcript::Format::Txt)
+        )
+    );
+    if let Some(dir) = &opt.save_segments {
+        segments::save(dir, &segments, MODEL_SAMPLE_RATE)
+            .map_err(Error::with(Error::Other, "Failed to save segments"))?;
     }
+    Ok(())
 }
 
-impl FromStr for FvadMode {
-    type Err = String;
+/// Files of the model decoded with, along with the beam width it is decoded at.
+struct ModelFiles {
+    model: PathBuf,
+    scorer: Option<PathBuf>,
+    beam_width: Option<u16>,
+}
 
-    fn from_str(s: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
-        match s {
-            "0" => Ok(Self::Quality),
-            "quality" => Ok(Self::Quality),
+impl ModelFiles {
+    fn load(&self) -> Result<Model, Error> {
+        load_model(&self.model, self.scorer.as_deref(), self.beam_width)
+    }
+}
 
-            "1" => Ok(Self::LowBitrate),
-            "low bitrate" => Ok(Self::LowBitrate),
-            "low-bitrate" => Ok(Self::LowBitrate),
-            "low_bitrate" => Ok(Self::LowBitrate),
-            "lowbitrate" => Ok(Self::LowBitrate),
+/// Loads the model at `model_path`, enabling the scorer at `scorer_path` and setting
+/// `beam_width`, if any.
+fn load_model(
+    model_path: &Path,
+    scorer_path: Option<&Path>,
+    beam_width: Option<u16>,
+) -> Result<Model, Error> {
+    let mut model = Model::load_from_files(model_path)
+        .map_err(Error::with(Error::Model, "Failed to load Deepspeech model"))?;
+    if let Some(path) = scorer_path {
+        model
+            .enable_external_scorer(path)
+            .map_err(Error::with(Error::Model, "Failed to load scorer"))?;
+    }
+    if let Some(beam_width) = beam_width {
+        model
+            .set_model_beam_width(beam_width)
+            .map_err(Error::with(Error::Usage, "Failed to set beam width"))?;
+    }
+    Ok(model)
+}
 
-            "2" => Ok(Self::Aggressive),
-            "aggressive"  => Ok(Self::Aggressive),
+/// Selects the model and scorer of --lang, --model and --scorer, recording them in `effective`
+/// along with their sources, and verifies them.
+fn select_model(
+    opt: &Opt,
+    languages: &languages::Languages,
+    effective: &mut profile::Effective,
+) -> Result<ModelFiles, Error> {
+    let language = opt
+        .lang
+        .as_deref()
+        .map(|code| languages.get(code).and_then(languages::Language::resolve))
+        .transpose()
+        .map_err(Error::with(Error::Usage, "Failed to select language"))?;
+    let model_source = if language.is_some() {
+        "config file"
+    } else if opt.model.is_some() {
+        "--model"
+    } else {
+        "default"
+    };
+    let scorer_source = if opt.no_scorer {
+        "--no-scorer"
+    } else if opt.scorer.is_some() {
+        "--scorer"
+    } else {
+        model_source
+    };
+    let (model_path, default_scorer) = match (language, opt.model.clone()) {
+        (Some(language), _) => language,
+        (None, Some(dir)) if dir.is_dir() => {
+            let discovered = models::discover_dir(&dir)
+                .map_err(Error::with(Error::Usage, "Failed to find model"))?;
+            info!(
+                model = %discovered.model.display(),
+                scorer = ?discovered.scorer.as_ref().map(|path| path.display().to_string()),
+                "Found model in directory"
+            );
+            (discovered.model, discovered.scorer)
+        }
+        (None, path) => (
+            path.or_else(models::default_model).ok_or_else(|| {
+                Error::Usage(
+                    "No model found, pass --model or fetch one with the download-model command"
+                        .into(),
+                )
+            })?,
+            models::default_scorer(),
+        ),
+    };
+    let scorer_path = if opt.no_scorer {
+        None
+    } else {
+        opt.scorer.clone().or(default_scorer)
+    };
+    effective.set(
+        "model",
+        serde_json::json!(model_path.display().to_string()),
+        model_source,
+    );
+    effective.set(
+        "scorer",
+        serde_json::json!(scorer_path.as_ref().map(|path| path.display().to_string())),
+        scorer_source,
+    );
+    info!(config = %effective.to_json(), "Effective configuration");
+    models::verify(&model_path, models::EXTENSIONS, opt.model_sha256.as_deref())
+        .map_err(Error::with(Error::Model, "Failed to verify model"))?;
+    models::check_format(&model_path).map_err(Error::with(Error::Model, "Unsupported model"))?;
+    if let Some(path) = &scorer_path {
+        models::verify(path, &["scorer"], opt.scorer_sha256.as_deref())
+            .map_err(Error::with(Error::Model, "Failed to verify scorer"))?;
+    }
+    Ok(ModelFiles {
+        model: model_path,
+        scorer: scorer_path,
+        beam_width: opt.beam_width,
+    })
+}
 
-            "3" => Ok(Self::VeryAggressive),
-            "very aggressive" => Ok(Self::VeryAggressive),
-            "very-aggressive" => Ok(Self::VeryAggressive),
-            "very_aggressive" => Ok(Self::VeryAggressive),
-            "veryaggressive" => Ok(Self::VeryAggressive),
+/// Passes transcripts are post-processed with, in order.
+type Passes = Vec<Box<dyn postprocess::Pass>>;
 
-            _ => Err(format!(
-                "failed to parse `{}` into Fvad mode of 0 (quality), 1 (low bitrate), 2 (aggresive) or 3 (very aggresive)",
-                s
+/// Returns the passes transcripts are post-processed with, in order, along with the replacement
+/// rules among them, which --reload-rules reloads.
+fn passes(opt: &Opt) -> Result<(Passes, Option<reload::Reloadable<replace::Rules>>), Error> {
+    let mut passes: Passes = Vec::new();
+    if opt.normalize_numbers {
+        passes.push(Box::new(numbers::Numbers));
+    }
+    if let Some(backend) = opt.punctuate {
+        // Speech of segments split is at least as long as the segment up to the split window,
+        // less the silence padding preceding it.
+        let split_length = opt
+            .max_utterance_duration
+            .checked_sub(opt.split_search + opt.silence_padding)
+            .unwrap_or_default();
+        passes.push(match backend.unwrap_or(punctuate::Backend::Heuristic) {
+            punctuate::Backend::Heuristic => Box::new(punctuate::Punctuate::new(
+                punctuate::Heuristic,
+                split_length,
             )),
+        });
+    }
+    let explain_replacements = opt.explain_replacements;
+    let replace_rules = opt
+        .replace_rules
+        .as_ref()
+        .map(|path| replace::Rules::load(path, explain_replacements))
+        .transpose()
+        .map_err(Error::with(
+            Error::Usage,
+            "Failed to load replacement rules",
+        ))?
+        .map(reload::Reloadable::new);
+    if let Some(rules) = &replace_rules {
+        passes.push(Box::new(rules.clone()));
+    }
+    if let Some(path) = &opt.intents {
+        passes.push(Box::new(
+            intents::Intents::load(path, opt.intent_policy)
+                .map_err(Error::with(Error::Usage, "Failed to load intents"))?,
+        ));
+    }
+    if let Some(mode) = opt.censor {
+        passes.push(Box::new(
+            censor::Censor::new(
+                mode.unwrap_or(censor::Mode::Mask),
+                opt.censor_list.as_deref(),
+                !opt.censor_list_only,
+            )
+            .map_err(Error::with(Error::Usage, "Failed to load censored words"))?,
+        ));
+    }
+    if !matches!(opt.casing, casing::Casing::Raw) {
+        passes.push(Box::new(opt.casing));
+    }
+    Ok((passes, replace_rules))
+}
+
+/// Loads the model of --compare-model, --compare-scorer and --compare-beam-width, if any of them
+/// is passed, which transcripts are compared with.
+fn comparison(
+    opt: &Opt,
+    files: &ModelFiles,
+    sample_rate: u32,
+) -> Result<Option<Arc<Mutex<Model>>>, Error> {
+    if opt.compare_model.is_none()
+        && opt.compare_scorer.is_none()
+        && opt.compare_beam_width.is_none()
+    {
+        return Ok(None);
+    }
+    let path = opt.compare_model.as_ref().unwrap_or(&files.model);
+    let scorer = opt.compare_scorer.as_ref().or(files.scorer.as_ref());
+    models::verify(path, models::EXTENSIONS, None).map_err(Error::with(
+        Error::Model,
+        "Failed to verify comparison model",
+    ))?;
+    models::check_format(path)
+        .map_err(Error::with(Error::Model, "Unsupported comparison model"))?;
+    let mut comparison = Model::load_from_files(path).map_err(Error::with(
+        Error::Model,
+        "Failed to load comparison Deepspeech model",
+    ))?;
+    if let Some(scorer) = scorer {
+        comparison
+            .enable_external_scorer(scorer)
+            .map_err(Error::with(
+                Error::Model,
+                "Failed to load comparison scorer",
+            ))?;
+    }
+    if let Some(beam_width) = opt.compare_beam_width {
+        comparison
+            .set_model_beam_width(beam_width)
+            .map_err(Error::with(
+                Error::Usage,
+                "Failed to set comparison beam width",
+            ))?;
+    }
+    if comparison.get_sample_rate() as u32 != sample_rate {
+        return Err(Error::Usage(format!(
+            "Comparison model expects a sample rate of {}Hz, but the model expects {}Hz",
+            comparison.get_sample_rate(),
+            sample_rate
+        )));
+    }
+    if !opt.no_warmup {
+        warm_up(&mut comparison, sample_rate)?;
+    }
+    info!(
+        path = %path.display(),
+        scorer = ?scorer.map(|path| path.display()),
+        beam_width = comparison.get_model_beam_width(),
+        "Loaded comparison model"
+    );
+    Ok(Some(Arc::new(Mutex::new(comparison))))
+}
+
+/// Returns how utterances decoded with a confidence below --rescore-below are decoded again, if
+/// it is passed, given the scorer decoded with.
+fn rescore(opt: &Opt, scorer_path: Option<&PathBuf>) -> Result<Option<decoder::Rescore>, Error> {
+    let below = match opt.rescore_below {
+        Some(below) => below,
+        None => return Ok(None),
+    };
+    if opt.rescore_beam_width.is_none() && !opt.rescore_without_scorer {
+        return Err(Error::Usage(
+            "--rescore-below requires --rescore-beam-width or --rescore-without-scorer".into(),
+        ));
+    }
+    let without_scorer = match (scorer_path, opt.rescore_without_scorer) {
+        (Some(path), true) => Some(path.clone()),
+        (None, true) => {
+            return Err(Error::Usage(
+                "--rescore-without-scorer requires decoding with a scorer".into(),
+            ))
         }
+        (_, false) => None,
+    };
+    Ok(Some(decoder::Rescore {
+        below,
+        beam_width: opt.rescore_beam_width,
+        without_scorer,
+    }))
+}
+
+/// Fails with `Error::Usage`, if segmenting options are passed in combinations, which clap cannot
+/// declare conflicts of, since they depend on their values.
+fn check_options(opt: &Opt) -> Result<(), Error> {
+    if opt.split_min_carry > Duration::from_millis(0) && opt.split_min_carry >= opt.split_search {
+        return Err(Error::Usage(
+            "--split-min-carry must be less than --split-search, within which utterances are split"
+                .into(),
+        ));
     }
+    if opt.stable_partials == Some(0) {
+        return Err(Error::Usage(
+            "--stable-partials requires at least 1 partial transcript".into(),
+        ));
+    }
+    if opt.stable_partials.is_some() && opt.partial_interval == Duration::from_millis(0) {
+        return Err(Error::Usage(
+            "--stable-partials stabilizes partial transcripts, which --partial-interval 0 disables"
+                .into(),
+        ));
+    }
+    Ok(())
 }
 
-#[derive(StructOpt)]
-#[structopt(name = "speech2text", about = "Record voice and print text to stdout.")]
-struct Opt {
-    /// Enable debugging
-    #[structopt(short, long)]
-    debug: bool,
+/// Returns the configuration of the pipeline audio at `sample_rate` is transcribed with by the
+/// model at `model_path`.
+fn pipeline_config(
+    opt: &Opt,
+    sample_rate: u32,
+    model_path: &Path,
+    comparison: Option<Arc<Mutex<Model>>>,
+    rescore: Option<decoder::Rescore>,
+) -> Result<pipeline::Config, Error> {
+    let recording_format = opt.recording_format;
+    let recording_names = opt.recording_names;
+    let quota = recordings::Quota {
+        max_files: opt.max_recordings,
+        max_bytes: opt.max_recordings_size,
+    };
+    Ok(pipeline::Config {
+        sample_rate,
+        bits_per_sample: 16,
+        fvad_sample_length: opt.fvad_sample_length,
+        fvad_mode: opt.fvad_mode,
+        vad_ensemble: opt.vad_ensemble.clone(),
+        vad_policy: opt.vad_policy,
+        vad: opt.vad,
+        energy_margin: opt.energy_margin,
+        vad_adaptive: opt.vad_adaptive,
+        recorder: if opt.debug {
+            let dir = recordings::prepare_dir(opt.recordings_dir.clone()).map_err(Error::with(
+                Error::Other,
+                "Failed to prepare recordings directory",
+            ))?;
+            Some(recordings::Recorder::new(
+                dir,
+                quota.clone(),
+                recording_format,
+                recording_names,
+            ))
+        } else {
+            None
+        },
+        min_speech: opt.min_speech,
+        silence_padding: opt.silence_padding,
+        max_utterance: opt.max_utterance_duration,
+        split_search: opt.split_search,
+        split_overlap: opt.split_overlap,
+        split_min_carry: opt.split_min_carry,
+        rejected_recorder: opt
+            .save_rejected
+            .clone()
+            .map(|dir| {
+                recordings::prepare_dir(Some(dir))
+                    .map(|dir| {
+                        recordings::Recorder::new(dir, quota, recording_format, recording_names)
+                    })
+                    .map_err(Error::with(
+                        Error::Other,
+                        "Failed to prepare rejected recordings directory",
+                    ))
+            })
+            .transpose()?,
+        silence_sample_interval: opt.save_silence_interval,
+        partial_interval: None,
+        startup_skip: Duration::default(),
+        origin: 0,
+        trim_margin: if opt.trim_decode {
+            Some(opt.trim_margin)
+        } else {
+            None
+        },
+        decode_queue: opt.decode_queue,
+        decode_nice: opt.decode_nice,
+        overflow_policy: if opt.file.is_some() {
+            decoder::OverflowPolicy::Block
+        } else {
+            opt.overflow_policy
+        },
+        anchor: opt
+            .file_start_time
+            .map(clock::Anchor::fixed)
+            .unwrap_or_default(),
+        latency: if opt.measure_latency {
+            Some(latency::Latency::default())
+        } else {
+            None
+        },
+        normalize: if opt.normalize_segments {
+            Some(normalize::Normalize {
+                target: opt.normalize_target,
+                ceiling: opt.normalize_ceiling,
+            })
+        } else {
+            None
+        },
+        record_normalized: opt.record_normalized,
+        fingerprint: opt.fingerprint,
+        speaker: opt.speaker_labels.first().cloned(),
+        model: model_path.to_path_buf(),
+        comparison,
+        rescore,
+        paragraph_gap: opt.paragraph_gap,
+        min_confidence: opt.min_confidence,
+        low_confidence: opt.low_confidence,
+        max_decode_failures: opt.max_decode_failures,
+        vad_stats: opt.vad_stats.clone(),
+        schedule: opt.schedule.clone(),
+        drain_timeout: Some(opt.drain_timeout),
+        idle_timeout: opt.idle_timeout,
+    })
+}
 
-    /// Path to model
-    #[structopt(short, long, parse(from_os_str))]
-    model: PathBuf,
+/// Returns `model` along with `count - 1` copies of it, which are loaded from `files` and
+/// warmed up, unless --no-warmup.
+fn copies(opt: &Opt, model: Model, files: &ModelFiles, count: usize) -> Result<Vec<Model>, Error> {
+    let sample_rate = model.get_sample_rate() as u32;
+    let mut models = vec![model];
+    for _ in 1..count {
+        let mut model = files.load()?;
+        if !opt.no_warmup {
+            warm_up(&mut model, sample_rate)?;
+        }
+        models.push(model);
+    }
+    Ok(models)
+}
 
-    /// Path to recording file
-    #[structopt(short, long, parse(from_os_str))]
-    file: Option<PathBuf>,
+/// Returns whether sessions are served over gRPC or WebSocket instead of transcribing an input.
+#[cfg(any(feature = "grpc", feature = "websocket"))]
+fn serves(opt: &Opt) -> bool {
+    #[cfg(feature = "grpc")]
+    if opt.grpc.is_some() {
+        return true;
+    }
+    #[cfg(feature = "websocket")]
+    if opt.ws_listen.is_some() {
+        return true;
+    }
+    false
+}
 
-    /// Fvad sample length in milliseconds: only values of 10, 20 or 30 ms are supported.
-    #[structopt(long, default_value = "10ms")]
-    fvad_sample_length: FvadSampleLength,
+/// Serves sessions over --grpc and --ws-listen, which share a pool of --pool-size copies of
+/// `model`, until the server fails or is stopped.
+#[cfg(any(feature = "grpc", feature = "websocket"))]
+fn serve(
+    opt: &Opt,
+    model: Model,
+    files: &ModelFiles,
+    mut config: pipeline::Config,
+) -> Result<(), Error> {
+    #[cfg(feature = "grpc")]
+    let grpc_addr = opt.grpc;
+    #[cfg(not(feature = "grpc"))]
+    let grpc_addr = None::<std::net::SocketAddr>;
+    #[cfg(feature = "websocket")]
+    let ws_addr = opt.ws_listen;
+    #[cfg(not(feature = "websocket"))]
+    let ws_addr = None::<std::net::SocketAddr>;
+    if opt.partial_interval > Duration::from_millis(0) {
+        config.partial_interval = Some(opt.partial_interval);
+    }
+    // Sessions end once their client closes the stream rather than after being idle.
+    config.idle_timeout = None;
+    // The certificate is loaded before the pool, so that a bad one fails fast.
+    #[cfg(feature = "tls")]
+    let tls = match (opt.tls_cert.clone(), opt.tls_key.clone()) {
+        (Some(cert), Some(key)) => Some(tls::Certificates::load(cert, key)?),
+        _ => None,
+    };
+    let models = copies(opt, model, files, opt.pool_size)?;
+    info!(size = models.len(), "Loaded model pool");
+    let pool = pool::Pool::new(models, opt.queue_timeout);
+    eprintln!("ready");
 
-    /// Fvad mode
-    #[structopt(long)]
-    fvad_mode: Option<FvadMode>,
+    #[cfg(feature = "websocket")]
+    if let Some(addr) = ws_addr {
+        let options = websocket::Options {
+            config: config.clone(),
+            resampler: opt.resampler,
+            dither: !opt.no_dither,
+            stable: opt.stable_partials,
+            #[cfg(feature = "tls")]
+            tls: tls.clone(),
+        };
+        if grpc_addr.is_none() {
+            return websocket::serve(addr, pool, options);
+        }
+        // Both servers share the pool, the WebSocket one on a separate thread.
+        let pool = pool.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = websocket::serve(addr, pool, options) {
+                tracing::error!(error = %err, "Failed to serve WebSocket sessions");
+            }
+        });
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = grpc_addr {
+        let options = grpc::Options {
+            config,
+            stable: opt.stable_partials,
+            grace: opt.drain_grace,
+            #[cfg(feature = "tls")]
+            tls,
+        };
+        return grpc::serve(addr, pool, options);
+    }
+    unreachable!("either server was served");
 }
 
-fn main() {
-    let opt = Opt::from_args();
+/// Returns the options of the --filter transcripts are piped through, if any.
+fn filter_options(opt: &Opt) -> Option<filter::Options> {
+    opt.filter.clone().map(|command| filter::Options {
+        command,
+        format: opt.filter_format,
+        timeout: opt.filter_timeout,
+    })
+}
 
-    let mut model = Model::load_from_files(&opt.model).expect("Failed to load Deepspeech model");
+/// Transcribes the files in `dir`, once or as they appear in it with --watch, with `models`,
+/// or with the ones of the languages files are named after.
+fn transcribe_dir(
+    opt: Opt,
+    dir: PathBuf,
+    files: &ModelFiles,
+    languages: &languages::Languages,
+    models: Vec<Arc<Mutex<Model>>>,
+    config: &pipeline::Config,
+    passes: Passes,
+) -> Result<(), Error> {
+    let sample_rate = config.sample_rate;
+    let (resampler, dither) = (opt.resampler, !opt.no_dither);
+    let (raw_format, via_ffmpeg) = (opt.raw_format, opt.via_ffmpeg);
+    let state_path = opt
+        .output_dir
+        .as_ref()
+        .unwrap_or(&dir)
+        .join(batch::State::FILE);
+    let (_control_tx, control_rx) = mpsc::channel();
+    let mut sink = postprocess::PostProcess {
+        passes,
+        sink: filter::Filter::spawn(filter_options(&opt), batch::Collector::default()),
+    };
+    let outputs = if opt.watch.is_some() || opt.output_format.is_some() || opt.output_dir.is_some()
+    {
+        Some(batch::Outputs {
+            root: dir.clone(),
+            dir: opt.output_dir.clone(),
+            format: opt.output_format.unwrap_or(transcript::Format::Txt),
+            model: files.model.clone(),
+            cues: transcript::Cues {
+                max_chars: opt.max_cue_chars,
+                max_duration: opt.max_cue_duration,
+            },
+        })
+    } else {
+        None
+    };
+    let options = serde_json::json!({
+        "model": files.model.display().to_string(),
+        "scorer": files.scorer.as_ref().map(|path| path.display().to_string()),
+        "fvad_mode": opt.fvad_mode.map(|mode| mode as u8),
+        "fvad_sample_length": opt.fvad_sample_length as u32,
+        "silence_padding": opt.silence_padding.as_millis() as u64,
+        "min_speech": opt.min_speech.as_millis() as u64,
+        "max_utterance_duration": opt.max_utterance_duration.as_secs(),
+        "split_search": opt.split_search.as_millis() as u64,
+        "split_overlap": opt.split_overlap.as_millis() as u64,
+        "split_min_carry": opt.split_min_carry.as_millis() as u64,
+        "normalize_numbers": opt.normalize_numbers,
+        "punctuate": opt.punctuate.map(|backend| {
+            format!("{:?}", backend.unwrap_or(punctuate::Backend::Heuristic))
+        }),
+        "replace_rules": opt.replace_rules.as_ref().map(|path| path.display().to_string()),
+        "intents": opt.intents.as_ref().map(|path| path.display().to_string()),
+        "intent_policy": format!("{:?}", opt.intent_policy),
+        "censor": opt.censor.map(|mode| format!("{:?}", mode.unwrap_or(censor::Mode::Mask))),
+        "casing": format!("{:?}", opt.casing),
+    });
+    let cache = match opt.cache_dir {
+        Some(dir) => {
+            let mut options = options.clone();
+            options["engine"] = "deepspeech".into();
+            options["beam_width"] = serde_json::json!(opt.beam_width);
+            options["model_modified"] = std::fs::metadata(&files.model)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|modified| modified.as_millis() as u64)
+                .map_or(serde_json::Value::Null, Into::into);
+            Some(
+                cache::Cache::open(dir, &options, !opt.no_cache)
+                    .map_err(Error::with(Error::Usage, "Failed to open cache"))?,
+            )
+        }
+        None => None,
+    };
+    let output_sync = opt.output_sync;
+    let mut batch = batch::Batch {
+        manifest: opt
+            .manifest
+            .map(|path| batch::Manifest::open(path, output_sync))
+            .transpose()
+            .map_err(Error::with(Error::Usage, "Failed to read manifest"))?,
+        outputs,
+        existing: opt.existing,
+        state: if opt.resume {
+            batch::State::resume(state_path, options, opt.force)?
+        } else {
+            batch::State::new(state_path, options)
+        },
+        cache,
+    };
+    // Models of the languages inputs are named after, loaded once the first of them is.
+    let mut language_models = HashMap::new();
+    let lang = opt.lang;
+    let transcribe = |path: &Path| -> Result<batch::Transcription, Error> {
+        let models = match languages
+            .of(path, &dir)
+            .filter(|&code| Some(code) != lang.as_deref())
+        {
+            Some(code) => {
+                if !language_models.contains_key(code) {
+                    let (model_path, scorer_path) = languages
+                        .get(code)
+                        .and_then(languages::Language::resolve)
+                        .map_err(Error::with(Error::Usage, "Failed to select language"))?;
+                    let model = load_model(&model_path, scorer_path.as_deref(), files.beam_width)?;
+                    if model.get_sample_rate() as u32 != sample_rate {
+                        return Err(Error::Usage(format!(
+                            "Model of language `{}` expects audio at {}Hz instead of {}Hz",
+                            code,
+                            model.get_sample_rate(),
+                            sample_rate
+                        )));
+                    }
+                    info!(lang = code, path = %model_path.display(), "Loaded model");
+                    language_models.insert(code.to_string(), vec![Arc::new(Mutex::new(model))]);
+                }
+                language_models[code].clone()
+            }
+            None => models.clone(),
+        };
+        let samples = read_file(path, raw_format, via_ffmpeg, sample_rate, resampler, dither)?;
+        let duration = config.duration(samples.len() as u64);
+        pipeline::run(samples.into_iter(), models, config, &control_rx, &mut sink)?;
+        Ok(batch::Transcription {
+            utterances: sink.sink.sink.take(),
+            duration,
+        })
+    };
+    if opt.watch.is_some() {
+        let options = watch::Options {
+            dir: &dir,
+            pattern: opt.pattern,
+            new_only: opt.watch_new_only,
+            debounce: opt.watch_debounce,
+        };
+        return watch::run(options, &mut batch, transcribe);
+    }
+    let inputs = batch::inputs(&dir, &opt.pattern).map_err(Error::Input)?;
+    batch::run(&inputs, &mut batch, transcribe)
+}
 
-    let sample_rate = model.get_sample_rate() as u32;
-    let channels: u16 = 1;
-    let bits_per_sample: u16;
-
-    // input_stream is necessary to prevent the value from being dropped at the end of conditional
-    // scope.
-    #[allow(unused_variables)]
-    let input_stream: _;
-    let (tx, rx) = mpsc::channel();
-    if let Some(path) = opt.file {
-        let mut reader = Reader::new(File::open(path).expect("Failed to open input file"))
-            .expect("Failed to read input file");
-
-        let desc = reader.description();
-        assert_eq!(desc.channel_count(), channels as u32);
-        assert_eq!(
-            desc.sample_rate(),
-            sample_rate,
-            "Sample rate of input file must equal sample rate expected by the model"
-        );
-        bits_per_sample = 16;
+/// Audio transcribed from --file or captured from the input devices.
+struct Source {
+    samples: Box<dyn Iterator<Item = i16>>,
+    /// Inputs of multiple --device, each of which is read by a pipeline of its own.
+    inputs: Option<multi::Inputs>,
+    /// Input, which is necessary to keep capturing until the end of the session.
+    _input: Option<input::Input>,
+    /// Records why ffmpeg failed, which decodes --file as it is transcribed with --via-ffmpeg.
+    ffmpeg_status: Option<ffmpeg::Status>,
+    device_name: Option<String>,
+}
 
-        for s in reader.samples() {
-            tx.send(s.expect("Failed to read sample from input file"))
-                .expect("Failed to send sample from input stream");
+/// Opens --file, --device or the default input device, setting the origin of timestamps of
+/// `config` to --start into the file.
+fn open_source(
+    opt: &Opt,
+    input_options: &input::Options,
+    models: &[Arc<Mutex<Model>>],
+    config: &mut pipeline::Config,
+    control_tx: &mpsc::Sender<control::Control>,
+    report: &mut Option<serde_json::Value>,
+) -> Result<Source, Error> {
+    let sample_rate = config.sample_rate;
+    let (resampler, dither) = (opt.resampler, !opt.no_dither);
+    let mut source = Source {
+        samples: Box::new(std::iter::empty()),
+        inputs: None,
+        _input: None,
+        ffmpeg_status: None,
+        device_name: None,
+    };
+    if let Some(path) = &opt.file {
+        let samples: Box<dyn Iterator<Item = i16>> = if opt.via_ffmpeg {
+            let decoder = ffmpeg::spawn(path, sample_rate, "--via-ffmpeg")?;
+            source.ffmpeg_status = Some(decoder.status());
+            Box::new(decoder)
+        } else {
+            Box::new(
+                read_file(path, opt.raw_format, false, sample_rate, resampler, dither)?.into_iter(),
+            )
+        };
+        source.samples = match opt.start {
+            Some(start) => {
+                let skipped = start_offset(start, sample_rate);
+                if !opt.relative_timestamps {
+                    config.origin = skipped;
+                }
+                Box::new(samples.skip(skipped as usize))
+            }
+            None => samples,
+        };
+        return Ok(source);
+    }
+    if opt.devices.len() > 1 {
+        let inputs = multi::Inputs::open(
+            input_options,
+            &opt.devices,
+            sample_rate,
+            resampler,
+            dither,
+            models.to_vec(),
+        )?;
+        source.device_name = Some(inputs.names().join(", "));
+        // Each input is read by a pipeline of its own.
+        source.inputs = Some(inputs);
+        return Ok(source);
+    }
+    #[cfg(feature = "capture")]
+    if opt.follow_default {
+        if !input_options.default_host() {
+            return Err(Error::Usage(
+                "--follow-default follows the default device of the default host, not of JACK, \
+                 PulseAudio or ALSA"
+                    .into(),
+            ));
         }
-        drop(tx)
+        let follower = follow::Follower::open(
+            follow::Options {
+                sample_rate,
+                resampler,
+                dither,
+                realtime: input_options.realtime,
+                anchor: config.anchor.clone(),
+            },
+            control_tx.clone(),
+            report,
+        )?;
+        source.device_name = follower.name().map(str::to_string);
+        source.samples = Box::new(follower);
+        return Ok(source);
+    }
+    #[cfg(not(feature = "capture"))]
+    let _ = control_tx;
+    let (samples, input) = input::open(
+        input_options,
+        sample_rate,
+        resampler,
+        dither,
+        config.anchor.clone(),
+        report,
+    )?;
+    source.samples = samples;
+    source.device_name = input.name().map(str::to_string);
+    source._input = Some(input);
+    Ok(source)
+}
+
+/// Reloads the --replace-rules and --command-rules, once their files change.
+fn watch_rules(
+    opt: &Opt,
+    replace_rules: Option<&reload::Reloadable<replace::Rules>>,
+    command_rules: Option<&reload::Reloadable<commands::Rules>>,
+    control_tx: &mpsc::Sender<control::Control>,
+) -> Result<(), Error> {
+    if let (Some(rules), Some(path)) = (replace_rules, &opt.replace_rules) {
+        let explain = opt.explain_replacements;
+        rules
+            .watch(
+                path,
+                "replacement rules",
+                move |path| replace::Rules::load(path, explain),
+                control_tx.clone(),
+            )
+            .map_err(Error::with(
+                Error::Other,
+                "Failed to watch replacement rules",
+            ))?;
+    }
+    if let (Some(rules), Some(path)) = (command_rules, &opt.command_rules) {
+        let (timeout, limit) = (opt.exec_timeout, opt.exec_parallel_limit);
+        rules
+            .watch(
+                path,
+                "command rules",
+                move |path| commands::Rules::load(path, timeout, limit),
+                control_tx.clone(),
+            )
+            .map_err(Error::with(Error::Other, "Failed to watch command rules"))?;
+    }
+    Ok(())
+}
+
+/// Reads --push-to-talk and --toggle-key from --hotkey-device, if either is passed.
+#[cfg(all(target_os = "linux", feature = "hotkey"))]
+fn hotkeys(opt: &Opt, control_tx: &mpsc::Sender<control::Control>) -> Result<(), Error> {
+    if opt.push_to_talk.is_none() && opt.toggle_key.is_none() {
+        return Ok(());
+    }
+    let device = opt
+        .hotkey_device
+        .as_deref()
+        .ok_or_else(|| Error::Usage("--hotkey-device is required to read hotkeys from".into()))?;
+    let key = |key: &str| key.parse::<hotkey::Key>().map_err(Error::Usage);
+    if let Some(push_to_talk) = &opt.push_to_talk {
+        hotkey::push_to_talk(
+            device,
+            key(push_to_talk)?,
+            opt.push_to_talk_tail,
+            control_tx.clone(),
+        )?;
+    }
+    if let Some(toggle_key) = &opt.toggle_key {
+        let feedback = hotkey::Feedback {
+            bell: opt.toggle_bell,
+            notify: opt.toggle_notify,
+        };
+        hotkey::toggle(device, key(toggle_key)?, feedback, control_tx.clone())?;
+    }
+    Ok(())
+}
+
+/// Returns the sink transcripts are shown with on the terminal, or printed to stdout with.
+fn text_sink(
+    opt: &Opt,
+    tty: bool,
+    control_tx: &mpsc::Sender<control::Control>,
+) -> Result<Box<dyn output::Sink>, Error> {
+    #[cfg(feature = "tui")]
+    if opt.tui {
+        return Ok(Box::new(
+            tui::Tui::new(control_tx.clone())
+                .map_err(Error::with(Error::Other, "Failed to start TUI"))?,
+        ));
+    }
+    #[cfg(not(feature = "tui"))]
+    let _ = control_tx;
+    Ok(if opt.statusbar {
+        Box::new(statusbar::Statusbar::default())
+    } else if opt.live && tty {
+        Box::new(live::Live::default())
+    } else if opt.captions && tty {
+        Box::new(captions::Captions::new(
+            opt.caption_count,
+            !opt.plain_captions,
+        ))
+    } else {
+        let colors = if opt.color_confidence && tty && std::env::var_os("NO_COLOR").is_none() {
+            Some(opt.color_thresholds)
+        } else {
+            None
+        };
+        Box::new(output::Stdout::new(opt.timestamps, colors))
+    })
+}
+
+/// Prefixes the transcripts `sink` receives with their speaker, if --speaker-prefix.
+fn prefixed(opt: &Opt, sink: Box<dyn output::Sink>) -> Box<dyn output::Sink> {
+    if opt.speaker_prefix {
+        Box::new(postprocess::PostProcess {
+            passes: vec![Box::new(postprocess::SpeakerPrefix)],
+            sink: vec![sink],
+        })
     } else {
-        let host = cpal::default_host();
-        let input_device = host
-            .default_input_device()
-            .expect("Failed to find default input device");
-
-        let input_stream_conf = input_device
-            .supported_input_configs()
-            .expect("Failed to get supported device input configurations")
-            .find(|x| x.channels() == channels && x.sample_format() == cpal::SampleFormat::I16)
-            .expect(
-                "Failed to find a single-channel input stream configuration with i16 sample format",
+        sink
+    }
+}
+
+/// Opens the outputs transcripts are sent to besides the terminal, `source` being the device or
+/// file they are transcribed from.
+fn outputs(
+    opt: &Opt,
+    config: &pipeline::Config,
+    model_path: &Path,
+    source: Option<&str>,
+    control_tx: &mpsc::Sender<control::Control>,
+) -> Result<Vec<Box<dyn output::Sink>>, Error> {
+    let mut sinks: Vec<Box<dyn output::Sink>> = Vec::new();
+    if config.comparison.is_some() {
+        sinks.push(Box::new(compare::Summary::default()));
+    }
+    #[cfg(feature = "notifications")]
+    if opt.notify {
+        sinks.push(Box::new(notification::Notifier::spawn(
+            opt.notify_low_urgency_below,
+        )));
+    }
+    #[cfg(feature = "capture")]
+    if opt.beep {
+        sinks.push(Box::new(beep::Beeper::spawn(
+            opt.beep_file.as_deref(),
+            opt.beep_volume,
+        )?));
+    }
+    #[cfg(feature = "capture")]
+    if let Some(name) = &opt.monitor {
+        sinks.push(Box::new(monitor::Monitor::open(
+            name.as_deref(),
+            config.sample_rate,
+        )?));
+    }
+    let cues = transcript::Cues {
+        max_chars: opt.max_cue_chars,
+        max_duration: opt.max_cue_duration,
+    };
+    for destination in &opt.output {
+        let sink = destination
+            .open(opt.output_sync, Some(model_path.to_path_buf()), cues)
+            .map_err(Error::with(Error::Other, "Failed to open output"))?;
+        sinks.push(if destination.is_text() {
+            prefixed(opt, sink)
+        } else {
+            sink
+        });
+    }
+    if let Some(template) = &opt.record_session {
+        sinks.push(Box::new(
+            recordings::SessionRecorder::create(
+                template.clone(),
+                config.wav_spec(),
+                opt.recording_format,
             )
-            .with_sample_rate(cpal::SampleRate(sample_rate));
-        bits_per_sample = (input_stream_conf.sample_format().sample_size() * 8) as _;
-
-        input_stream = input_device
-            .build_input_stream(
-                &input_stream_conf.config(),
-                move |data: &[i16], _| {
-                    for sample in data {
-                        tx.send(sample.to_sample::<i16>())
-                            .expect("Failed to send sample from input stream")
-                    }
-                },
-                move |err| eprintln!("Failed to capture frame on input stream: {}", err),
+            .map_err(Error::with(
+                Error::Other,
+                "Failed to create session recording",
+            ))?,
+        ));
+    }
+    if let Some(path) = &opt.record_speech {
+        let gap =
+            (opt.record_speech_gap.as_millis() as u64 * config.sample_rate as u64 / 1000) as usize;
+        sinks.push(Box::new(
+            recordings::SpeechRecorder::create(
+                path.clone(),
+                config.wav_spec(),
+                opt.recording_format,
+                gap,
             )
-            .expect("Failed to build input stream");
-        input_stream.play().expect("Failed to play input stream");
+            .map_err(Error::with(
+                Error::Other,
+                "Failed to create speech recording",
+            ))?,
+        ));
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(url) = &opt.mqtt_url {
+        let publisher = mqtt::Publisher::connect(mqtt::Config {
+            url: url.clone(),
+            topic: opt.mqtt_topic.clone(),
+            username: opt.mqtt_username.clone(),
+            password: opt.mqtt_password.clone(),
+            qos: opt.mqtt_qos,
+        })
+        .map_err(Error::with(Error::Other, "Failed to configure MQTT"))?;
+        sinks.push(Box::new(publisher));
+    }
+    #[cfg(feature = "webhook")]
+    if let Some(url) = &opt.webhook {
+        let webhook = webhook::Webhook::spawn(webhook::Config {
+            url: url.clone(),
+            events: opt.webhook_events,
+            secret: opt.webhook_secret.clone(),
+        })
+        .map_err(Error::with(Error::Usage, "Failed to configure webhook"))?;
+        sinks.push(Box::new(webhook));
+    }
+    #[cfg(feature = "history")]
+    match &opt.history_db {
+        Some(path) if !opt.history_off => {
+            let history = history::History::open(history::Config {
+                path: path.clone(),
+                retain: opt.history_retain,
+                source: source
+                    .map(str::to_string)
+                    .or_else(|| opt.file.as_ref().map(|path| path.display().to_string())),
+            })
+            .map_err(Error::with(Error::Other, "Failed to open history database"))?;
+            sinks.push(Box::new(history));
+        }
+        _ => {}
+    }
+    #[cfg(not(feature = "history"))]
+    let _ = source;
+    #[cfg(unix)]
+    if let Some(path) = &opt.socket {
+        let broadcaster = socket::Broadcaster::bind(path.clone(), opt.socket_mode)
+            .map_err(Error::with(Error::Other, "Failed to bind socket"))?;
+        sinks.push(Box::new(broadcaster));
+    }
+    #[cfg(unix)]
+    if let Some(path) = &opt.fifo {
+        sinks
+            .push(Box::new(fifo::Fifo::create(path.clone()).map_err(
+                Error::with(Error::Other, "Failed to create FIFO"),
+            )?));
+    }
+    let spawner = |command: &String| {
+        exec::Spawner::new(command.clone(), opt.exec_timeout, opt.exec_parallel_limit)
+    };
+    if let Some(command) = &opt.exec {
+        sinks.push(Box::new(exec::Exec(spawner(command))));
+    }
+    if opt.exec_on_speech_start.is_some() || opt.exec_on_speech_end.is_some() {
+        sinks.push(Box::new(exec::SpeechHooks::new(
+            opt.exec_on_speech_start.as_ref().map(spawner),
+            opt.exec_on_speech_end.as_ref().map(spawner),
+            opt.exec_on_speech_debounce,
+        )));
+    }
+    if opt.type_text {
+        let typist = typing::Typist::new(typing::Config {
+            backend: opt.type_backend,
+            separator: opt.type_separator,
+            delay: opt.type_delay,
+            wake_word: opt.type_confirm.clone(),
+        })
+        .map_err(Error::with(Error::Other, "Failed to set up typing"))?;
+        sinks.push(Box::new(typist));
+    }
+    if let Some(mode) = opt.clipboard {
+        sinks.push(Box::new(clipboard::Clipboard::new(mode)));
+    }
+    #[cfg(feature = "osc")]
+    if let Some(target) = &opt.osc {
+        sinks.push(Box::new(
+            osc::Osc::connect(target, opt.osc_address.clone())
+                .map_err(Error::with(Error::Other, "Failed to set up OSC"))?,
+        ));
+    }
+    #[cfg(feature = "websocket")]
+    if let Some(url) = &opt.push_ws {
+        sinks.push(Box::new(
+            push::Pusher::connect(url.clone(), opt.push_ws_token.clone())
+                .map_err(Error::with(Error::Other, "Failed to set up WebSocket"))?,
+        ));
+    }
+    #[cfg(feature = "systemd")]
+    if let Some(notifier) = systemd::Notifier::from_env() {
+        sinks.push(Box::new(notifier));
+    }
+    #[cfg(feature = "dbus")]
+    if opt.dbus {
+        match dbus::Bus::connect(control_tx.clone()) {
+            Ok(bus) => sinks.push(Box::new(bus)),
+            Err(err) => tracing::warn!(
+                error = %err,
+                "Failed to register on D-Bus, continuing without it"
+            ),
+        }
+    }
+    #[cfg(not(feature = "dbus"))]
+    let _ = control_tx;
+    Ok(sinks)
+}
+
+/// Wraps `sinks` in the stages transcripts pass through before reaching them: --command-rules,
+/// --stable-partials, --dedup-window and --offset, in order.
+fn layer(
+    opt: &Opt,
+    command_rules: Option<reload::Reloadable<commands::Rules>>,
+    sinks: Vec<Box<dyn output::Sink>>,
+) -> Vec<Box<dyn output::Sink>> {
+    let sinks: Vec<Box<dyn output::Sink>> = match command_rules {
+        Some(rules) => vec![Box::new(commands::Commands::new(
+            rules,
+            opt.command_cooldown,
+            opt.commands_only,
+            sinks,
+        ))],
+        None => sinks,
+    };
+    let sinks: Vec<Box<dyn output::Sink>> = match opt.stable_partials {
+        Some(count) => vec![Box::new(stable::Stabilize::new(count, sinks))],
+        None => sinks,
+    };
+    let sinks: Vec<Box<dyn output::Sink>> = match opt.dedup_window {
+        Some(window) => vec![Box::new(dedup::Dedup::new(window, opt.dedup_policy, sinks))],
+        None => sinks,
+    };
+    match opt.offset {
+        Some(offset) => vec![Box::new(timestamp::Shift::new(offset, sinks))],
+        None => sinks,
+    }
+}
+
+/// Sends `command` to the control socket at `path`, printing the reply.
+#[cfg(unix)]
+fn ctl(path: Option<PathBuf>, command: &str) -> Result<(), Error> {
+    let path =
+        path.ok_or_else(|| Error::Usage("--daemon-control is required to send commands".into()))?;
+    let reply = daemon::send(&path, command)
+        .map_err(Error::with(Error::Other, "Failed to send command"))?;
+    println!("{}", reply);
+    match reply.get("error").and_then(|err| err.as_str()) {
+        Some(err) => Err(Error::Other(err.into())),
+        None => Ok(()),
+    }
+}
+
+/// Lists the sessions kept in the history at `history_db`, or prints the utterances of `session`,
+/// a JSONL transcript or the ID of a session kept in it, rendered with `options`.
+fn export_session(
+    session: Option<String>,
+    list: bool,
+    history_db: Option<&Path>,
+    options: &export::Options,
+) -> Result<(), Error> {
+    if list {
+        return list_sessions(history_db);
+    }
+    let session = session.expect("Failed to get --session, which is required without --list");
+    let path = Path::new(&session);
+    let utterances = if path.is_file() {
+        export::jsonl(path).map_err(Error::with(Error::Input, "Failed to read session"))?
+    } else {
+        session_utterances(history_db, &session)?
+    };
+    if utterances.is_empty() {
+        return Err(Error::Usage(format!(
+            "session `{}` has no utterances",
+            session
+        )));
+    }
+    print!("{}", export::render(utterances, options));
+    Ok(())
+}
+
+#[cfg(feature = "history")]
+fn list_sessions(history_db: Option<&Path>) -> Result<(), Error> {
+    let history_db = history_db
+        .ok_or_else(|| Error::Usage("--history-db is required to list sessions".into()))?;
+    let sessions = history::sessions(history_db)
+        .map_err(Error::with(Error::Other, "Failed to read history database"))?;
+    for session in sessions {
+        println!(
+            "{}\t{}\t{}\t{}",
+            session.session_id,
+            clock::format(session.started),
+            session.utterances,
+            session.source.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "history"))]
+fn list_sessions(_: Option<&Path>) -> Result<(), Error> {
+    Err(Error::Usage(
+        "Listing sessions requires building with the `history` feature".into(),
+    ))
+}
+
+#[cfg(feature = "history")]
+fn session_utterances(
+    history_db: Option<&Path>,
+    session: &str,
+) -> Result<Vec<output::Utterance>, Error> {
+    let history_db = history_db.ok_or_else(|| {
+        Error::Usage(format!(
+            "session `{}` is no file, --history-db is required to export it by its ID",
+            session
+        ))
+    })?;
+    history::utterances(history_db, session)
+        .map_err(Error::with(Error::Other, "Failed to read history database"))
+}
+
+#[cfg(not(feature = "history"))]
+fn session_utterances(_: Option<&Path>, session: &str) -> Result<Vec<output::Utterance>, Error> {
+    Err(Error::Usage(format!(
+        "session `{}` is no file, exporting sessions by their ID requires building with the \
+         `history` feature",
+        session
+    )))
+}
+
+/// Reads the samples of the WAV file at `path`, which must be mono, or of the headerless file
+/// described by `raw_format`, resampling them to `sample_rate` with `resampler`.
+/// Files are decoded with ffmpeg if `via_ffmpeg` is set, or if the built-in decoders reject them
+/// and ffmpeg is installed.
+/// Float samples are dithered when converted to i16, if `dither` is set.
+fn read_file(
+    path: &Path,
+    raw_format: Option<raw::Format>,
+    via_ffmpeg: bool,
+    sample_rate: u32,
+    resampler: Resampler,
+    dither: bool,
+) -> Result<Vec<i16>, Error> {
+    if let Some(format) = raw_format {
+        let samples = raw::read(path, format, dither)?;
+        return Ok(
+            resample::resample(samples.into_iter(), format.rate, sample_rate, resampler).collect(),
+        );
+    }
+    if via_ffmpeg {
+        return ffmpeg::spawn(path, sample_rate, "--via-ffmpeg")?.read_all();
+    }
+    match decode_file(path, sample_rate, resampler, dither) {
+        Err(Error::Input(err)) => match ffmpeg::Decoder::spawn(path, sample_rate) {
+            Ok(decoder) => {
+                info!(
+                    path = %path.display(),
+                    error = %err,
+                    "Decoding with ffmpeg, since the built-in decoders failed to"
+                );
+                decoder.read_all()
+            }
+            Err(_) => Err(Error::Input(format!(
+                "{}, install ffmpeg to decode the file with it",
+                err
+            ))),
+        },
+        result => result,
+    }
+}
+
+/// Returns the sample offset of `start` into a file at `sample_rate`, the rate of the model.
+/// Samples are counted at the rate of the model, which they were resampled to, so that offsets
+/// map to time in the file regardless of its sample rate.
+fn start_offset(start: Duration, sample_rate: u32) -> u64 {
+    (start.as_micros() * sample_rate as u128 / 1_000_000) as u64
+}
+
+/// Reads the samples of the mono WAV, including WAVE_FORMAT_EXTENSIBLE ones, FLAC, Ogg Vorbis or
+/// ALAC file at `path` with the built-in decoders, resampling them to `sample_rate` with
+/// `resampler`.
+fn decode_file(
+    path: &Path,
+    sample_rate: u32,
+    resampler: Resampler,
+    dither: bool,
+) -> Result<Vec<i16>, Error> {
+    let bytes = fs::read(path).map_err(Error::with(Error::Input, "Failed to open input file"))?;
+    if let Some(wav) = wav::read_extensible(&bytes, path, dither)? {
+        if wav.channels != CHANNELS {
+            return Err(Error::Input(format!(
+                "Input file must have {} channel, got {}",
+                CHANNELS, wav.channels
+            )));
+        }
+        return Ok(resample::resample(
+            wav.samples.into_iter(),
+            wav.sample_rate,
+            sample_rate,
+            resampler,
+        )
+        .collect());
+    }
+    let mut reader = Reader::new(io::Cursor::new(bytes)).map_err(|err| {
+        Error::Input(format!(
+            "Failed to read input file, which must be a mono WAV, FLAC, Ogg Vorbis or ALAC file: \
+             {}",
+            err
+        ))
+    })?;
+
+    let desc = reader.description();
+    if desc.channel_count() != CHANNELS as u32 {
+        return Err(Error::Input(format!(
+            "Input file must have {} channel, got {}",
+            CHANNELS,
+            desc.channel_count()
+        )));
     }
 
-    let vad_sample_rate = match sample_rate / 8000 {
-        1 => 8000,
-        2 | 3 => 16000,
-        4 | 5 => 32000,
-        6 => 48000,
-        _ => todo!("handling of sample rate {}", sample_rate),
+    let float = match &reader {
+        Reader::Wav(wav) => wav.spec().sample_format == hound::SampleFormat::Float,
+        _ => false,
     };
-    let mut vad = Fvad::new().expect("Failed to create Fvad").set_sample_rate(
-        vad_sample_rate
-            .try_into()
-            .expect("Failed to set Fvad sample rate"),
+    let samples: Vec<i16> = if float {
+        let mut dither = dither::Dither::new(dither);
+        reader
+            .samples::<f32>()
+            .map(|sample| sample.map(|sample| dither.quantize(sample)))
+            .collect::<Result<_, _>>()
+    } else {
+        reader.samples().collect::<Result<_, _>>()
+    }
+    .map_err(Error::with(
+        Error::Input,
+        "Failed to read sample from input file",
+    ))?;
+    Ok(resample::resample(
+        samples.into_iter(),
+        desc.sample_rate(),
+        sample_rate,
+        resampler,
+    )
+    .collect())
+}
+
+#[cfg(feature = "http")]
+fn download_model(dir: Option<PathBuf>) -> Result<(), Error> {
+    let dir = dir.unwrap_or_else(models::default_dir);
+    let (model, scorer) =
+        download::download(&dir).map_err(Error::with(Error::Other, "Failed to download model"))?;
+    eprintln!(
+        "Downloaded model, pass --model {} --scorer {}",
+        model.display(),
+        scorer.display()
     );
-    if let Some(mode) = opt.fvad_mode {
-        vad = vad.set_mode(mode.into());
-    }
-
-    let frame_sample_count = (opt.fvad_sample_length as u32 * (vad_sample_rate / 1000)) as usize;
-    let mut signal = dasp::signal::from_iter(rx.iter()).buffered(dasp::ring_buffer::Bounded::from(
-        vec![0; frame_sample_count],
-    ));
-
-    let mut buffer = Vec::new();
-    let mut silence_frames = 0;
-    let mut speech_frames = 0;
-    while !signal.is_exhausted() {
-        let mut frame = signal.next_frames().collect::<Vec<i16>>();
-
-        let is_voice = vad
-            .is_voice_frame(&frame)
-            .expect("Invalid frame received from input stream");
-        buffer.append(&mut frame);
-
-        if is_voice {
-            speech_frames += 1;
-            silence_frames = 0;
-            continue;
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn download_model(_: Option<PathBuf>) -> Result<(), Error> {
+    Err(Error::Usage(
+        "Downloading models requires building with the `http` feature".into(),
+    ))
+}
+
+/// Decodes half a second of silence, so that the model initializes before the first utterance.
+fn warm_up(model: &mut Model, sample_rate: u32) -> Result<(), Error> {
+    let start = Instant::now();
+    model
+        .speech_to_text(&vec![0; sample_rate as usize / 2])
+        .map_err(Error::with(Error::Model, "Failed to warm up model"))?;
+    info!(elapsed = ?start.elapsed(), "Warmed up model");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segmenter::{Segmenter, Split, Step};
+
+    /// Samples of a frame of 30 ms at 16 kHz.
+    const FRAME: usize = 480;
+
+    /// Returns the time of the first voiced frame in ms of the 48 kHz `path` transcribed from
+    /// `start` into it, relative to the start of the file, unless `relative`.
+    fn speech_start(path: &Path, start: Duration, relative: bool) -> u64 {
+        let samples = decode_file(path, 16000, Resampler::Linear, false).unwrap();
+        let skipped = start_offset(start, 16000);
+        let split = Split {
+            search_frames: 20,
+            overlap_frames: 3,
+            min_carry_frames: 2,
+        };
+        let mut segmenter = Segmenter::new(vad::energy(10.0), FRAME, 1, 16000 * 10, 2, split);
+        if !relative {
+            segmenter.skip(skipped as usize);
         }
-        silence_frames += 1;
-        // TODO: Make amount of silence "padding" configurable.
-        // Let the user specify duration and compute frame count.
-        const SILENCE_PADDING: usize = 20;
-        if speech_frames == 0 {
-            if silence_frames > SILENCE_PADDING {
-                buffer = buffer[buffer.len() - frame_sample_count * SILENCE_PADDING..].to_vec();
-                silence_frames = SILENCE_PADDING;
+        for frame in samples[skipped as usize..].chunks_exact(FRAME) {
+            if let Some(Step::SpeechStarted(offset)) = segmenter.push(frame) {
+                return offset * 1000 / 16000;
             }
-            continue;
         }
-        if silence_frames < SILENCE_PADDING {
-            continue;
+        panic!("no speech detected");
+    }
+
+    #[test]
+    fn start_timebase() {
+        // 13 s at 48 kHz of noise with a burst of speech at 12.01 s, which is frame-aligned after
+        // skipping 10 s.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("48k.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let burst = 48000 * 12010 / 1000..48000 * 12600 / 1000;
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..48000 * 13 {
+            let amplitude = if burst.contains(&i) { 8000 } else { 30 };
+            let sign = if i / 60 % 2 == 0 { 1 } else { -1 };
+            writer.write_sample(sign * amplitude as i16).unwrap();
         }
+        writer.finalize().unwrap();
 
-        if opt.debug {
-            let mut writer = hound::WavWriter::create(
-                PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join(format!(
-                    "recordings/recording{}.wav",
-                    SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .expect("SystemTime before UNIX EPOCH!")
-                        .as_nanos()
-                )),
-                hound::WavSpec {
-                    channels,
-                    sample_rate,
-                    bits_per_sample,
-                    sample_format: hound::SampleFormat::Int,
-                },
-            )
-            .expect("Failed to create WAV writer");
-            for &sample in &buffer {
-                writer.write_sample(sample).expect("Failed to write to WAV");
-            }
+        assert_eq!(start_offset(Duration::from_secs(10), 16000), 160000);
+        assert_eq!(start_offset(Duration::from_millis(10500), 48000), 504000);
+
+        let start = speech_start(&path, Duration::from_secs(10), false);
+        assert!((12010..12010 + 3).contains(&start), "{}", start);
+        let start = speech_start(&path, Duration::from_secs(10), true);
+        assert!((2010..2010 + 3).contains(&start), "{}", start);
+        let start = speech_start(&path, Duration::default(), false);
+        assert!((12000..12010 + 3).contains(&start), "{}", start);
+    }
+
+    fn parse(args: &[&str]) -> Result<Opt, clap::Error> {
+        Opt::try_parse_from(std::iter::once("speech2text").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn parse_fvad_sample_length() {
+        for (s, expected) in [
+            ("10", Some(10)),
+            ("10ms", Some(10)),
+            ("10 ms", Some(10)),
+            ("20", Some(20)),
+            ("20 ms", Some(20)),
+            ("0.02s", Some(20)),
+            ("30ms", Some(30)),
+            ("30 ms", Some(30)),
+            ("15ms", None),
+            ("40", None),
+            ("", None),
+        ] {
+            let length = parse(&["--fvad-sample-length", s])
+                .ok()
+                .map(|opt| opt.fvad_sample_length as u8);
+            assert_eq!(length, expected, "{}", s);
+        }
+        assert_eq!(parse(&[]).unwrap().fvad_sample_length as u8, 10);
+    }
+
+    #[test]
+    fn parse_fvad_mode() {
+        for (s, expected) in [
+            ("0", Some(0)),
+            ("quality", Some(0)),
+            ("1", Some(1)),
+            ("low bitrate", Some(1)),
+            ("low-bitrate", Some(1)),
+            ("low_bitrate", Some(1)),
+            ("lowbitrate", Some(1)),
+            ("2", Some(2)),
+            ("aggressive", Some(2)),
+            ("3", Some(3)),
+            ("very aggressive", Some(3)),
+            ("very-aggressive", Some(3)),
+            ("very_aggressive", Some(3)),
+            ("veryaggressive", Some(3)),
+            ("4", None),
+            ("very", None),
+        ] {
+            let mode = parse(&["--fvad-mode", s])
+                .ok()
+                .map(|opt| opt.fvad_mode.map(|mode| mode as u8));
+            assert_eq!(mode, expected.map(Some), "{}", s);
+        }
+        assert!(parse(&[]).unwrap().fvad_mode.is_none());
+    }
+
+    #[test]
+    fn parse_verbosity() {
+        assert_eq!(parse(&[]).unwrap().verbose, 0);
+        assert_eq!(parse(&["-v"]).unwrap().verbose, 1);
+        assert_eq!(parse(&["-vv", "--verbose"]).unwrap().verbose, 3);
+    }
+
+    #[test]
+    fn parse_conflicts() {
+        for args in [
+            &["--file", "a.wav", "--device", "mic"][..],
+            &["--file", "a.wav", "--watch", "dir"],
+            &["--watch", "dir", "--device", "mic"],
+            &["--fvad-mode", "1", "--vad-ensemble", "1,2"],
+            &["--vad-adaptive", "--vad-ensemble", "1,2"],
+            &[
+                "--file",
+                "a.wav",
+                "--raw-format",
+                "rate=8000",
+                "--via-ffmpeg",
+            ],
+            &["--vad-only"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+        for args in [
+            &["--file", "a.wav"][..],
+            &["--watch", "dir"],
+            &["--device", "mic", "--device", "headset"],
+            &["--file", "a.wav", "--vad-only"],
+            &["--vad", "energy", "--fvad-sample-length", "20ms"],
+        ] {
+            assert!(parse(args).is_ok(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn fvad_options_need_fvad() {
+        let check = |args: &[&str]| {
+            let args = std::iter::once("speech2text").chain(args.iter().copied());
+            let matches = Opt::command().try_get_matches_from(args).unwrap();
+            check_vad(&Opt::from_arg_matches(&matches).unwrap(), &matches)
+        };
+        assert!(check(&["--vad", "fvad", "--fvad-mode", "3"]).is_ok());
+        assert!(check(&["--vad", "energy"]).is_ok());
+        assert!(check(&["--fvad-mode", "3"]).is_ok());
+        // Profiles give the Fvad mode a value, which other detectors ignore.
+        assert!(check(&["--vad", "energy", "--profile", "accurate"]).is_ok());
+        for args in [
+            &["--vad", "energy", "--fvad-mode", "3"][..],
+            &["--vad", "none", "--vad-ensemble", "1,2"],
+            &["--vad", "energy", "--vad-adaptive"],
+        ] {
+            assert!(matches!(check(args), Err(Error::Usage(_))), "{:?}", args);
         }
-        println!(
-            "{}",
-            model
-                .speech_to_text(&buffer)
-                .expect("Failed to process frame"),
-        );
-        buffer.clear();
-        silence_frames = 0;
-        speech_frames = 0;
     }
 }
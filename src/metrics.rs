@@ -0,0 +1,337 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Number of utterances decoded.
+pub static UTTERANCES: AtomicU64 = AtomicU64::new(0);
+/// Time spent decoding in microseconds.
+pub static DECODE_MICROS: AtomicU64 = AtomicU64::new(0);
+/// Duration of audio processed in microseconds.
+pub static AUDIO_MICROS: AtomicU64 = AtomicU64::new(0);
+/// Number of captured samples dropped because the pipeline fell behind.
+pub static DROPPED_SAMPLES: AtomicU64 = AtomicU64::new(0);
+/// Number of gaps detected in the captured audio.
+pub static GAPS: AtomicU64 = AtomicU64::new(0);
+/// Duration of captured audio dropped or missing in microseconds, which was replaced with silence.
+pub static DROPPED_MICROS: AtomicU64 = AtomicU64::new(0);
+/// Number of segments, which failed to decode.
+pub static DECODE_FAILURES: AtomicU64 = AtomicU64::new(0);
+/// Number of final utterances less confident than the minimum confidence.
+pub static LOW_CONFIDENCE_UTTERANCES: AtomicU64 = AtomicU64::new(0);
+/// Number of final transcripts suppressed for repeating the previous one.
+pub static DUPLICATES: AtomicU64 = AtomicU64::new(0);
+/// Number of final utterances decoded again for their low confidence.
+pub static RESCORED: AtomicU64 = AtomicU64::new(0);
+/// Number of final utterances, whose transcript changed by decoding them again.
+pub static RESCORE_CHANGED: AtomicU64 = AtomicU64::new(0);
+/// Number of events delivered to the webhook.
+pub static WEBHOOK_SENT: AtomicU64 = AtomicU64::new(0);
+/// Number of events, which failed to be delivered to the webhook after retrying.
+pub static WEBHOOK_FAILED: AtomicU64 = AtomicU64::new(0);
+/// Number of events dropped, because the webhook queue was full or the session ended.
+pub static WEBHOOK_DROPPED: AtomicU64 = AtomicU64::new(0);
+/// Number of segments dropped because decoding fell behind.
+pub static DROPPED_SEGMENTS: AtomicU64 = AtomicU64::new(0);
+/// Number of jobs waiting to be decoded.
+pub static DECODE_QUEUE_DEPTH: AtomicU64 = AtomicU64::new(0);
+/// Number of copies of the model in the pool of the servers.
+pub static POOL_SIZE: AtomicU64 = AtomicU64::new(0);
+/// Number of copies of the model in the pool of the servers, which sessions decode with.
+pub static POOL_IN_USE: AtomicU64 = AtomicU64::new(0);
+/// Number of XRUNs reported by the JACK server or ALSA device.
+pub static XRUNS: AtomicU64 = AtomicU64::new(0);
+/// Time the last final transcript was output at in milliseconds since the Unix epoch, 0 if none
+/// was.
+pub static LAST_TRANSCRIPT_MILLIS: AtomicU64 = AtomicU64::new(0);
+/// Whether processing of audio is paused.
+pub static PAUSED: AtomicBool = AtomicBool::new(false);
+/// Whether voice activity was detected in the last frame processed.
+pub static SPEECH: AtomicBool = AtomicBool::new(false);
+/// Whether voice activity detection seems stuck open, classifying everything as voiced.
+pub static VAD_STUCK: AtomicBool = AtomicBool::new(false);
+/// Whether saving recordings of segments is suspended, e.g. from the TUI.
+pub static RECORDING_SUSPENDED: AtomicBool = AtomicBool::new(false);
+/// Fvad mode voice is detected in plus 1, 0 if unknown, e.g. because Fvad runs in its default
+/// mode or is not used.
+static FVAD_MODE: AtomicU64 = AtomicU64::new(0);
+/// Bits of the ratio of decoding time to duration of the last utterance.
+static REAL_TIME_FACTOR: AtomicU64 = AtomicU64::new(0);
+
+pub fn add_duration(counter: &AtomicU64, duration: Duration) {
+    counter.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+}
+
+pub fn set_fvad_mode(mode: Option<u8>) {
+    FVAD_MODE.store(mode.map_or(0, |mode| mode as u64 + 1), Ordering::Relaxed);
+}
+
+/// Returns the Fvad mode voice is currently detected in, if known.
+pub fn fvad_mode() -> Option<u8> {
+    match FVAD_MODE.load(Ordering::Relaxed) {
+        0 => None,
+        mode => Some(mode as u8 - 1),
+    }
+}
+
+pub fn set_real_time_factor(factor: f64) {
+    REAL_TIME_FACTOR.store(factor.to_bits(), Ordering::Relaxed);
+}
+
+/// Returns the ratio of decoding time to duration of the last utterance.
+#[cfg(any(feature = "metrics", feature = "tui"))]
+pub fn real_time_factor() -> f64 {
+    f64::from_bits(REAL_TIME_FACTOR.load(Ordering::Relaxed))
+}
+
+/// Renders the metrics in the Prometheus text exposition format.
+#[cfg(feature = "metrics")]
+fn render() -> String {
+    let seconds = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64 / 1e6;
+    let count = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+    let metrics = [
+        (
+            "speech2text_utterances_total",
+            "counter",
+            "Number of utterances decoded.",
+            count(&UTTERANCES),
+        ),
+        (
+            "speech2text_decode_seconds_total",
+            "counter",
+            "Time spent decoding.",
+            seconds(&DECODE_MICROS),
+        ),
+        (
+            "speech2text_audio_seconds_total",
+            "counter",
+            "Duration of audio processed.",
+            seconds(&AUDIO_MICROS),
+        ),
+        (
+            "speech2text_dropped_samples_total",
+            "counter",
+            "Number of captured samples dropped, because processing fell behind.",
+            count(&DROPPED_SAMPLES),
+        ),
+        (
+            "speech2text_gaps_total",
+            "counter",
+            "Number of gaps detected in the captured audio.",
+            count(&GAPS),
+        ),
+        (
+            "speech2text_dropped_milliseconds",
+            "gauge",
+            "Duration of captured audio dropped or missing, which was replaced with silence.",
+            count(&DROPPED_MICROS) / 1000.0,
+        ),
+        (
+            "speech2text_dropped_segments_total",
+            "counter",
+            "Number of speech segments dropped, because decoding fell behind.",
+            count(&DROPPED_SEGMENTS),
+        ),
+        (
+            "speech2text_decode_failures_total",
+            "counter",
+            "Number of speech segments, which failed to decode.",
+            count(&DECODE_FAILURES),
+        ),
+        (
+            "speech2text_low_confidence_utterances_total",
+            "counter",
+            "Number of utterances less confident than --min-confidence.",
+            count(&LOW_CONFIDENCE_UTTERANCES),
+        ),
+        (
+            "speech2text_duplicates_total",
+            "counter",
+            "Number of transcripts suppressed for repeating the previous one.",
+            count(&DUPLICATES),
+        ),
+        (
+            "speech2text_rescored_total",
+            "counter",
+            "Number of utterances decoded again for their low confidence.",
+            count(&RESCORED),
+        ),
+        (
+            "speech2text_rescore_changed_total",
+            "counter",
+            "Number of utterances, whose transcript changed by decoding them again.",
+            count(&RESCORE_CHANGED),
+        ),
+        (
+            "speech2text_webhook_sent_total",
+            "counter",
+            "Number of events delivered to the webhook.",
+            count(&WEBHOOK_SENT),
+        ),
+        (
+            "speech2text_webhook_failed_total",
+            "counter",
+            "Number of events, which failed to be delivered to the webhook.",
+            count(&WEBHOOK_FAILED),
+        ),
+        (
+            "speech2text_webhook_dropped_total",
+            "counter",
+            "Number of events dropped before they were delivered to the webhook.",
+            count(&WEBHOOK_DROPPED),
+        ),
+        (
+            "speech2text_xruns_total",
+            "counter",
+            "Number of XRUNs reported by the JACK server or ALSA device.",
+            count(&XRUNS),
+        ),
+        (
+            "speech2text_decode_queue_depth",
+            "gauge",
+            "Number of jobs waiting to be decoded.",
+            count(&DECODE_QUEUE_DEPTH),
+        ),
+        (
+            "speech2text_pool_size",
+            "gauge",
+            "Number of copies of the model in the pool of the servers.",
+            count(&POOL_SIZE),
+        ),
+        (
+            "speech2text_pool_in_use",
+            "gauge",
+            "Number of copies of the model in the pool of the servers, which are in use.",
+            count(&POOL_IN_USE),
+        ),
+        (
+            "speech2text_paused",
+            "gauge",
+            "Whether processing of audio is paused.",
+            PAUSED.load(Ordering::Relaxed) as u8 as f64,
+        ),
+        (
+            "speech2text_vad_stuck",
+            "gauge",
+            "Whether voice activity detection seems stuck classifying everything as voice.",
+            VAD_STUCK.load(Ordering::Relaxed) as u8 as f64,
+        ),
+        (
+            "speech2text_real_time_factor",
+            "gauge",
+            "Ratio of decoding time to duration of the last utterance.",
+            real_time_factor(),
+        ),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics.iter() {
+        out.push_str(&format!(
+            "# HELP {name} {}\n# TYPE {name} {}\n{name} {}\n",
+            help,
+            kind,
+            value,
+            name = name
+        ));
+    }
+    out
+}
+
+/// Serves the metrics at `/metrics` on `addr` on a separate thread.
+#[cfg(feature = "metrics")]
+pub fn serve(addr: std::net::SocketAddr) -> Result<(), String> {
+    let server = tiny_http::Server::http(addr).map_err(|err| err.to_string())?;
+    let content_type =
+        tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..])
+            .expect("Failed to construct Content-Type header");
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                tiny_http::Response::from_string(render()).with_header(content_type.clone())
+            } else {
+                tiny_http::Response::from_string("Not Found").with_status_code(404)
+            };
+            if let Err(err) = request.respond(response) {
+                tracing::debug!(error = %err, "Failed to respond to metrics request");
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    };
+
+    /// Names of the metrics, which dashboards and alerts depend on and hence must stay stable.
+    const NAMES: &[(&str, &str)] = &[
+        ("speech2text_utterances_total", "counter"),
+        ("speech2text_decode_seconds_total", "counter"),
+        ("speech2text_audio_seconds_total", "counter"),
+        ("speech2text_dropped_samples_total", "counter"),
+        ("speech2text_gaps_total", "counter"),
+        ("speech2text_dropped_milliseconds", "gauge"),
+        ("speech2text_dropped_segments_total", "counter"),
+        ("speech2text_decode_failures_total", "counter"),
+        ("speech2text_low_confidence_utterances_total", "counter"),
+        ("speech2text_duplicates_total", "counter"),
+        ("speech2text_rescored_total", "counter"),
+        ("speech2text_rescore_changed_total", "counter"),
+        ("speech2text_webhook_sent_total", "counter"),
+        ("speech2text_webhook_failed_total", "counter"),
+        ("speech2text_webhook_dropped_total", "counter"),
+        ("speech2text_xruns_total", "counter"),
+        ("speech2text_decode_queue_depth", "gauge"),
+        ("speech2text_pool_size", "gauge"),
+        ("speech2text_pool_in_use", "gauge"),
+        ("speech2text_paused", "gauge"),
+        ("speech2text_vad_stuck", "gauge"),
+        ("speech2text_real_time_factor", "gauge"),
+    ];
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn scrape() {
+        let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        serve(addr).unwrap();
+
+        let response = get(addr, "/metrics");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.0 200"), "{}", head);
+        assert!(head.contains("text/plain; version=0.0.4"), "{}", head);
+        for (name, kind) in NAMES {
+            assert!(
+                body.contains(&format!("# TYPE {} {}\n", name, kind)),
+                "{} missing in:\n{}",
+                name,
+                body
+            );
+            let value = body
+                .lines()
+                .find_map(|line| line.strip_prefix(&format!("{} ", name)))
+                .unwrap_or_else(|| panic!("{} has no sample in:\n{}", name, body));
+            value.parse::<f64>().unwrap();
+        }
+        assert_eq!(body.lines().count(), NAMES.len() * 3);
+
+        assert!(get(addr, "/").starts_with("HTTP/1.0 404"));
+    }
+
+    #[test]
+    fn render_values() {
+        set_real_time_factor(0.25);
+        assert!(render().contains("\nspeech2text_real_time_factor 0.25\n"));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::beep;
+use crate::error::Error;
+use crate::normalize::Loudness;
+use crate::vad::{self, FvadMode, FvadSampleLength};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Peak level in dBFS, below which the input is considered silent, e.g. because the microphone
+/// is muted or the wrong device was selected.
+const SILENT_PEAK: f64 = -60.0;
+/// Peak level in dBFS, at or above which the input is considered clipping.
+const CLIPPING_PEAK: f64 = -0.5;
+/// RMS level in dBFS, below which the input is considered too quiet to transcribe well.
+const QUIET_RMS: f64 = -50.0;
+
+/// Records `duration` of `samples` at `sample_rate`, prints their levels and the fraction of
+/// frames Fvad classified as voice and plays the recording back, if `playback` is set.
+/// Fails if the input was silent or clipping, so that scripts can tell whether the microphone
+/// works.
+pub fn run(
+    samples: impl Iterator<Item = i16>,
+    sample_rate: u32,
+    duration: Duration,
+    mode: Option<FvadMode>,
+    length: FvadSampleLength,
+    playback: bool,
+) -> Result<(), Error> {
+    let count = (duration.as_millis() as u64 * sample_rate as u64 / 1000) as usize;
+    info!(?duration, "Recording, speak into the microphone");
+    let recording: Vec<i16> = samples.take(count).collect();
+    if recording.len() < count {
+        return Err(Error::Device(format!(
+            "Input ended after {} of {} samples",
+            recording.len(),
+            count
+        )));
+    }
+
+    let loudness = Loudness::of(&recording);
+    let mut vad = vad::new(sample_rate, mode);
+    let frame_sample_count = (length as u32 * (sample_rate / 1000)) as usize;
+    let frames = recording.len() / frame_sample_count;
+    let voiced = recording
+        .chunks_exact(frame_sample_count)
+        .filter(|frame| {
+            vad.is_voice_frame(frame)
+                .expect("Invalid frame received from input stream")
+        })
+        .count();
+    println!("peak: {:.1} dBFS", loudness.peak.0);
+    println!("rms: {:.1} dBFS", loudness.rms.0);
+    println!(
+        "voice: {:.0}% of {} frames",
+        100.0 * voiced as f64 / frames.max(1) as f64,
+        frames
+    );
+
+    if playback {
+        info!("Playing the recording back");
+        if let Err(err) = beep::play_once(recording, sample_rate) {
+            warn!(error = %err, "Failed to play the recording back");
+        }
+    }
+
+    if loudness.peak.0 < SILENT_PEAK {
+        return Err(Error::Device(format!(
+            "Input is silent with a peak of {:.1} dBFS, check that the microphone is unmuted and selected",
+            loudness.peak.0
+        )));
+    }
+    if loudness.peak.0 >= CLIPPING_PEAK {
+        return Err(Error::Device(
+            "Input is clipping, lower the gain of the microphone".into(),
+        ));
+    }
+    if loudness.rms.0 < QUIET_RMS {
+        warn!(
+            rms = loudness.rms.0,
+            "Input is quiet, raise the gain of the microphone or move closer to it"
+        );
+    }
+    if voiced == 0 {
+        warn!("No voice was detected in the recording");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the test on `samples` recorded for a second at 16 kHz without playback.
+    fn test(samples: Vec<i16>) -> Result<(), Error> {
+        run(
+            samples.into_iter(),
+            16000,
+            Duration::from_secs(1),
+            None,
+            FvadSampleLength::Length30ms,
+            false,
+        )
+    }
+
+    /// Returns a second of a 440 Hz tone of `amplitude` at 16 kHz.
+    fn tone(amplitude: f32) -> Vec<i16> {
+        (0..16000)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16000.0;
+                (amplitude * phase.sin()) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn levels() {
+        assert!(test(tone(8000.0)).is_ok());
+        assert!(matches!(test(vec![0; 16000]), Err(Error::Device(_))));
+        assert!(matches!(test(tone(32767.0)), Err(Error::Device(_))));
+    }
+
+    #[test]
+    fn input_ended() {
+        assert!(matches!(
+            test(tone(8000.0)[..8000].to_vec()),
+            Err(Error::Device(_))
+        ));
+    }
+}
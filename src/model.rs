@@ -0,0 +1,35 @@
+use deepspeech::errors::DeepspeechError;
+use std::{
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+/// DeepSpeech model, which may be moved to the thread decoding with it, e.g. that of a server
+/// session. Sharing it between threads still requires a lock, such as the `Mutex` it is kept in.
+pub struct Model(deepspeech::Model);
+
+// SAFETY: The model state of DeepSpeech is a heap allocation, which is not tied to the thread
+// that created it, so it may be used and freed by whichever thread owns the model. This is not
+// `Sync`, since DeepSpeech does not support concurrent use of a model state.
+unsafe impl Send for Model {}
+
+impl Model {
+    /// Loads the DeepSpeech model at `path`.
+    pub fn load_from_files(path: &Path) -> Result<Self, DeepspeechError> {
+        deepspeech::Model::load_from_files(path).map(Self)
+    }
+}
+
+impl Deref for Model {
+    type Target = deepspeech::Model;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Model {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
@@ -0,0 +1,377 @@
+use crate::paths;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+/// Name of the model file of the supported DeepSpeech release.
+pub const MODEL_FILE: &str = "deepspeech-0.9.3-models.pbmm";
+
+/// Name of the scorer file of the supported DeepSpeech release.
+pub const SCORER_FILE: &str = "deepspeech-0.9.3-models.scorer";
+
+/// Extensions of model files supported by DeepSpeech, preferring the format `SUPPORTED`.
+#[cfg(not(feature = "tflite"))]
+pub const EXTENSIONS: &[&str] = &["pbmm", "pb", "tflite"];
+#[cfg(feature = "tflite")]
+pub const EXTENSIONS: &[&str] = &["tflite", "pbmm", "pb"];
+
+/// Format of a model file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// TensorFlow graph, either as a protobuf or memory-mapped, with a `pb` or `pbmm` extension.
+    TensorFlow,
+    /// TFLite flatbuffer with a `tflite` extension, which is the only format Coqui STT exports.
+    TfLite,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Self::TensorFlow => "TensorFlow (.pbmm, .pb)",
+            Self::TfLite => "TFLite (.tflite)",
+        }
+    }
+}
+
+/// Format of the models the linked libdeepspeech loads: the TensorFlow one of the desktop
+/// releases, unless built with the `tflite` feature for the TFLite build of libdeepspeech.
+/// The library aborts the process on a model of the other format, rather than failing.
+pub const SUPPORTED: Format = if cfg!(feature = "tflite") {
+    Format::TfLite
+} else {
+    Format::TensorFlow
+};
+
+/// Identifier of TFLite flatbuffers, which follows the offset of the root table.
+const TFLITE_IDENTIFIER: &[u8] = b"TFL3";
+
+/// Tells the format of the model at `path` by its contents.
+pub fn format(path: &Path) -> Result<Format, String> {
+    let mut header = [0; 8];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    if &header[4..] == TFLITE_IDENTIFIER {
+        Ok(Format::TfLite)
+    } else {
+        Ok(Format::TensorFlow)
+    }
+}
+
+/// Checks that the model at `path` has the format its extension claims and the one the linked
+/// libdeepspeech loads, explaining what is needed otherwise.
+pub fn check_format(path: &Path) -> Result<(), String> {
+    let format = format(path)?;
+    let claimed = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tflite") => Format::TfLite,
+        _ => Format::TensorFlow,
+    };
+    if format != claimed {
+        return Err(format!(
+            "{} is a {} model despite its extension, rename it or fetch the model again",
+            path.display(),
+            format.name()
+        ));
+    }
+    if format == SUPPORTED {
+        return Ok(());
+    }
+    Err(match format {
+        Format::TfLite => format!(
+            "{} is a {} model, e.g. one exported by Coqui STT, but this build loads {} models of \
+             DeepSpeech 0.9, use {} or rebuild with the `tflite` feature against the TFLite build \
+             of libdeepspeech 0.9",
+            path.display(),
+            format.name(),
+            SUPPORTED.name(),
+            MODEL_FILE
+        ),
+        Format::TensorFlow => format!(
+            "{} is a {} model, but this build with the `tflite` feature loads {} models, use the \
+             .tflite model of DeepSpeech 0.9 or rebuild without the `tflite` feature",
+            path.display(),
+            format.name(),
+            SUPPORTED.name()
+        ),
+    })
+}
+
+/// Returns the directory models are downloaded to, `$XDG_DATA_HOME/speech2text/models`.
+pub fn default_dir() -> PathBuf {
+    paths::data_dir("models")
+}
+
+/// Returns the path of the downloaded model, if present.
+pub fn default_model() -> Option<PathBuf> {
+    Some(default_dir().join(MODEL_FILE)).filter(|path| path.is_file())
+}
+
+/// Returns the path of the downloaded scorer, if present.
+pub fn default_scorer() -> Option<PathBuf> {
+    Some(default_dir().join(SCORER_FILE)).filter(|path| path.is_file())
+}
+
+/// Model and scorer found in a directory.
+#[derive(Debug)]
+pub struct Discovered {
+    pub model: PathBuf,
+    pub scorer: Option<PathBuf>,
+}
+
+/// Picks the model and the scorer, if any, among the `files` of a directory: the only file with
+/// the first of `EXTENSIONS` any file has, so that a model of the `SUPPORTED` format is
+/// preferred, and the only file with the `scorer` extension.
+/// Fails with the candidates, if either choice is ambiguous, or if there is no model.
+pub fn discover(files: &[PathBuf]) -> Result<Discovered, String> {
+    let with_extension = |extension: &str| -> Vec<&PathBuf> {
+        let mut found: Vec<_> = files
+            .iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+            .collect();
+        found.sort();
+        found
+    };
+    let only = |found: Vec<&PathBuf>, kind: &str| -> Result<Option<PathBuf>, String> {
+        match found.as_slice() {
+            [] => Ok(None),
+            [path] => Ok(Some(path.to_path_buf())),
+            _ => Err(format!(
+                "found {} {} files, pass the one to use: {}",
+                found.len(),
+                kind,
+                found
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    };
+    let models = EXTENSIONS
+        .iter()
+        .map(|&extension| with_extension(extension))
+        .find(|found| !found.is_empty())
+        .unwrap_or_default();
+    let model = only(models, "model")?.ok_or_else(|| {
+        format!(
+            "found no model file with an extension of {}",
+            EXTENSIONS.join(", ")
+        )
+    })?;
+    let scorer = only(with_extension("scorer"), "scorer")?;
+    Ok(Discovered { model, scorer })
+}
+
+/// Discovers the model and the scorer among the files in `dir`.
+pub fn discover_dir(dir: &Path) -> Result<Discovered, String> {
+    let mut files = Vec::new();
+    for entry in
+        fs::read_dir(dir).map_err(|err| format!("failed to read {}: {}", dir.display(), err))?
+    {
+        let path = entry
+            .map_err(|err| format!("failed to read {}: {}", dir.display(), err))?
+            .path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    discover(&files).map_err(|err| format!("{} in {}", err, dir.display()))
+}
+
+/// Returns the SHA256 digest of the file at `path` in hex.
+pub fn sha256(path: &Path) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    io::copy(
+        &mut File::open(path)
+            .map_err(|err| format!("failed to open {}: {}", path.display(), err))?,
+        &mut hasher,
+    )
+    .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks that `path` is a plausible model file with one of `extensions` and, if given, that its
+/// SHA256 digest equals `expected`, so that a corrupted file is not passed to DeepSpeech.
+pub fn verify(path: &Path, extensions: &[&str], expected: Option<&str>) -> Result<(), String> {
+    let meta =
+        fs::metadata(path).map_err(|err| format!("failed to stat {}: {}", path.display(), err))?;
+    if !meta.is_file() || meta.len() == 0 {
+        return Err(format!("{} is not a non-empty file", path.display()));
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if extensions.contains(&ext) => {}
+        _ => {
+            return Err(format!(
+                "{} does not have an extension of {}",
+                path.display(),
+                extensions.join(" or ")
+            ))
+        }
+    }
+
+    let expected = match expected {
+        Some(expected) => expected.trim().to_lowercase(),
+        None => return Ok(()),
+    };
+    let actual = sha256(path)?;
+    if actual != expected {
+        return Err(format!(
+            "SHA256 of {} is {}, expected {}, the file may be truncated or corrupted",
+            path.display(),
+            actual,
+            expected
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty");
+        fs::write(&path, b"").unwrap();
+        assert_eq!(
+            sha256(&path).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(sha256(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn verify_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.pbmm");
+        fs::write(&path, b"hello world").unwrap();
+        let digest = "B94D27B9934D3E08A52E52D7DA7DABFAC484EFE37A5380EE9088F7ACE2EFCDE9 ";
+        assert_eq!(verify(&path, &["pbmm"], None), Ok(()));
+        assert_eq!(verify(&path, &["pbmm"], Some(digest)), Ok(()));
+        let err = verify(&path, &["pbmm"], Some(&"0".repeat(64))).unwrap_err();
+        assert!(err.contains("may be truncated or corrupted"), "{}", err);
+        assert!(verify(&path, &["scorer"], None).is_err());
+    }
+
+    fn files(names: &[&str]) -> Vec<PathBuf> {
+        names
+            .iter()
+            .map(|name| Path::new("models").join(name))
+            .collect()
+    }
+
+    fn discovered(names: &[&str]) -> Result<(String, Option<String>), String> {
+        discover(&files(names)).map(|discovered| {
+            (
+                discovered.model.display().to_string(),
+                discovered.scorer.map(|scorer| scorer.display().to_string()),
+            )
+        })
+    }
+
+    #[test]
+    fn discover_model_and_scorer() {
+        assert_eq!(
+            discovered(&["a.pbmm", "a.scorer", "README.md"]),
+            Ok(("models/a.pbmm".into(), Some("models/a.scorer".into())))
+        );
+        assert_eq!(discovered(&["a.pbmm"]), Ok(("models/a.pbmm".into(), None)));
+        assert_eq!(discovered(&["a.pb"]), Ok(("models/a.pb".into(), None)));
+    }
+
+    #[cfg(not(feature = "tflite"))]
+    #[test]
+    fn discover_prefers_supported() {
+        assert_eq!(
+            discovered(&["a.tflite", "a.pbmm", "b.pb"]),
+            Ok(("models/a.pbmm".into(), None))
+        );
+        assert_eq!(
+            discovered(&["a.tflite"]),
+            Ok(("models/a.tflite".into(), None))
+        );
+    }
+
+    #[test]
+    fn discover_ambiguous() {
+        assert_eq!(
+            discovered(&["b.pbmm", "a.pbmm", "a.scorer"]),
+            Err("found 2 model files, pass the one to use: models/a.pbmm, models/b.pbmm".into())
+        );
+        assert_eq!(
+            discovered(&["a.pbmm", "b.scorer", "a.scorer"]),
+            Err(
+                "found 2 scorer files, pass the one to use: models/a.scorer, models/b.scorer"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn discover_missing() {
+        let expected = Err(format!(
+            "found no model file with an extension of {}",
+            EXTENSIONS.join(", ")
+        ));
+        assert_eq!(discovered(&[]), expected);
+        assert_eq!(discovered(&["a.scorer", "pbmm", "a.pbmm.txt"]), expected);
+    }
+
+    #[test]
+    fn discover_in_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("model.pbmm"), b"model").unwrap();
+        fs::create_dir(dir.path().join("other.scorer")).unwrap();
+        let discovered = discover_dir(dir.path()).unwrap();
+        assert_eq!(discovered.model, dir.path().join("model.pbmm"));
+        // Directories are not files to discover.
+        assert_eq!(discovered.scorer, None);
+
+        let err = discover_dir(&dir.path().join("missing")).unwrap_err();
+        assert!(err.starts_with("failed to read"), "{}", err);
+    }
+
+    #[test]
+    fn detect_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let tflite = dir.path().join("model.tflite");
+        fs::write(&tflite, b"\x1c\0\0\0TFL3\0\0").unwrap();
+        assert_eq!(format(&tflite), Ok(Format::TfLite));
+        let pbmm = dir.path().join("model.pbmm");
+        fs::write(&pbmm, b"\x0a\x0cgraph_def\0").unwrap();
+        assert_eq!(format(&pbmm), Ok(Format::TensorFlow));
+        let short = dir.path().join("short.pbmm");
+        fs::write(&short, b"TFL3").unwrap();
+        assert!(format(&short).unwrap_err().starts_with("failed to read"));
+    }
+
+    #[test]
+    fn check_model_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        };
+        let tflite = write("model.tflite", b"\x1c\0\0\0TFL3\0\0");
+        let pbmm = write("model.pbmm", b"\x0a\x0cgraph_def\0");
+        let renamed = write("renamed.pbmm", b"\x1c\0\0\0TFL3\0\0");
+        let err = check_format(&renamed).unwrap_err();
+        assert!(
+            err.contains("is a TFLite (.tflite) model despite its extension"),
+            "{}",
+            err
+        );
+        let (supported, other) = match SUPPORTED {
+            Format::TensorFlow => (pbmm, tflite),
+            Format::TfLite => (tflite, pbmm),
+        };
+        assert_eq!(check_format(&supported), Ok(()));
+        let err = check_format(&other).unwrap_err();
+        assert!(err.contains("rebuild"), "{}", err);
+    }
+}
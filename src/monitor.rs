@@ -0,0 +1,120 @@
+use crate::device;
+use crate::error::Error;
+use crate::output::{Event, Sink};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Sample, SampleFormat, Stream, StreamConfig};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+/// Maximum duration of audio buffered for playback, beyond which the oldest samples are dropped,
+/// so that monitoring lags behind the input by at most as much.
+const MAX_LATENCY: Duration = Duration::from_millis(100);
+
+/// Plays back the samples pulled from the input on an output device, so that one can hear what
+/// the pipeline hears.
+pub struct Monitor {
+    /// Kept, so that playback continues until the monitor is dropped.
+    _stream: Stream,
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    /// Maximum number of samples buffered.
+    capacity: usize,
+    /// Number of input samples per output sample.
+    step: f64,
+    /// Position of the next output sample after the last input sample.
+    phase: f64,
+    last: f32,
+}
+
+impl Monitor {
+    /// Opens the output device named `name`, or the default one, for playback of samples at
+    /// `sample_rate`.
+    pub fn open(name: Option<&str>, sample_rate: u32) -> Result<Self, Error> {
+        let host = cpal::default_host();
+        let devices = host
+            .output_devices()
+            .map_err(Error::with(Error::Device, "Failed to list output devices"))?;
+        let device = device::find(devices, host.default_output_device(), name)?;
+        let config = device.default_output_config().map_err(Error::with(
+            Error::Device,
+            "Failed to get default device output configuration",
+        ))?;
+        let output_rate = config.sample_rate().0;
+        let capacity = (MAX_LATENCY.as_millis() as u64 * output_rate as u64 / 1000) as usize;
+        let buffer = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+        let stream_config = config.config();
+        let stream = match config.sample_format() {
+            SampleFormat::I16 => build::<i16>(&device, &stream_config, buffer.clone()),
+            SampleFormat::U16 => build::<u16>(&device, &stream_config, buffer.clone()),
+            SampleFormat::F32 => build::<f32>(&device, &stream_config, buffer.clone()),
+        }
+        .map_err(Error::with(Error::Device, "Failed to build output stream"))?;
+        stream
+            .play()
+            .map_err(Error::with(Error::Device, "Failed to play output stream"))?;
+        let device_name = device.name().unwrap_or_default();
+        info!(device = %device_name, rate = output_rate, "Monitoring input");
+        if name.is_none() {
+            warn!(
+                device = %device_name,
+                "MONITORING ON THE DEFAULT OUTPUT DEVICE, USE HEADPHONES: SPEAKERS PICKED UP BY THE MICROPHONE CAUSE FEEDBACK"
+            );
+        }
+        Ok(Self {
+            _stream: stream,
+            buffer,
+            capacity,
+            step: sample_rate as f64 / output_rate as f64,
+            phase: 0.0,
+            last: 0.0,
+        })
+    }
+}
+
+fn build<T: Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut buffer = buffer.lock().expect("Failed to lock monitor buffer");
+            for frame in data.chunks_mut(channels) {
+                // Plays silence while the input lags behind.
+                let sample = T::from(&buffer.pop_front().unwrap_or(0.0));
+                for out in frame {
+                    *out = sample;
+                }
+            }
+        },
+        move |err| error!(error = %err, "Failed to play frame on output stream"),
+    )
+}
+
+impl Sink for Monitor {
+    fn send(&mut self, _event: &Event) {}
+
+    /// Resamples `samples` to the rate of the output device by linear interpolation, which is
+    /// good enough to listen to.
+    fn samples(&mut self, samples: &[i16]) {
+        let mut buffer = self.buffer.lock().expect("Failed to lock monitor buffer");
+        for &sample in samples {
+            let sample = sample.to_f32();
+            while self.phase < 1.0 {
+                buffer.push_back(self.last + (sample - self.last) * self.phase as f32);
+                self.phase += self.step;
+            }
+            self.phase -= 1.0;
+            self.last = sample;
+        }
+        if buffer.len() > self.capacity {
+            let excess = buffer.len() - self.capacity;
+            buffer.drain(..excess);
+        }
+    }
+}
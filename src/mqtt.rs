@@ -0,0 +1,191 @@
+use crate::output::{Event, Sink};
+use crate::queue::Queue;
+use rumqttc::{Client, LastWill, MqttOptions, QoS};
+use serde_json::json;
+use std::{process, sync::Arc, thread, time::Duration};
+use tracing::warn;
+
+/// Maximum number of messages queued while the broker is unreachable.
+const QUEUE_CAPACITY: usize = 256;
+
+pub struct Config {
+    /// Broker URL of the form `mqtt://host[:port]`.
+    pub url: String,
+    pub topic: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub qos: QoS,
+}
+
+/// Parses QoS level 0, 1 or 2.
+pub fn parse_qos(s: &str) -> Result<QoS, String> {
+    match s {
+        "0" => Ok(QoS::AtMostOnce),
+        "1" => Ok(QoS::AtLeastOnce),
+        "2" => Ok(QoS::ExactlyOnce),
+        _ => Err(format!(
+            "failed to parse `{}` into MQTT QoS of 0, 1 or 2",
+            s
+        )),
+    }
+}
+
+fn parse_url(url: &str) -> Result<(String, u16), String> {
+    let addr = url
+        .strip_prefix("mqtt://")
+        .or_else(|| url.strip_prefix("tcp://"))
+        .unwrap_or(url)
+        .trim_end_matches('/');
+    match addr.rsplit_once(':') {
+        Some((host, port)) => port
+            .parse()
+            .map(|port| (host.to_string(), port))
+            .map_err(|err| format!("invalid port in MQTT URL `{}`: {}", url, err)),
+        None => Ok((addr.to_string(), 1883)),
+    }
+}
+
+struct Message {
+    topic: String,
+    payload: Vec<u8>,
+    qos: QoS,
+    retain: bool,
+}
+
+/// Publishes final transcripts to the configured topic and speech events to its `speech`
+/// sub-topic. Publishing happens on background threads, so sending never blocks the pipeline.
+pub struct Publisher {
+    topic: String,
+    qos: QoS,
+    queue: Arc<Queue<Message>>,
+}
+
+impl Publisher {
+    pub fn connect(config: Config) -> Result<Self, String> {
+        let (host, port) = parse_url(&config.url)?;
+        let status_topic = format!("{}/status", config.topic);
+
+        let mut options = MqttOptions::new(format!("speech2text-{}", process::id()), host, port);
+        options.set_keep_alive(30);
+        options.set_last_will(LastWill::new(
+            &status_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = config.username {
+            options.set_credentials(username, config.password.unwrap_or_default());
+        }
+        let (client, mut connection) = Client::new(options, 16);
+
+        // Publishing blocks while the request channel is full, which the event loop drains, so the
+        // status is published by the publishing thread rather than the event loop itself.
+        let queue = Arc::new(Queue::new(QUEUE_CAPACITY));
+        let status_queue = queue.clone();
+        thread::spawn(move || {
+            // Iterating the connection drives the event loop, which reconnects on the next
+            // iteration after an error.
+            for notification in connection.iter() {
+                match notification {
+                    Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                        let status = Message {
+                            topic: status_topic.clone(),
+                            payload: b"online".to_vec(),
+                            qos: QoS::AtLeastOnce,
+                            retain: true,
+                        };
+                        if status_queue.push(status).is_some() {
+                            warn!("MQTT queue is full, dropping oldest message");
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        warn!(error = %err, "MQTT connection error, reconnecting");
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+        });
+
+        let publish_queue = queue.clone();
+        let mut publish_client = client;
+        thread::spawn(move || {
+            while let Some(message) = publish_queue.pop() {
+                if let Err(err) = publish_client.publish(
+                    message.topic,
+                    message.qos,
+                    message.retain,
+                    message.payload,
+                ) {
+                    warn!(error = %err, "Failed to publish MQTT message");
+                }
+            }
+        });
+
+        Ok(Self {
+            topic: config.topic,
+            qos: config.qos,
+            queue,
+        })
+    }
+}
+
+impl Sink for Publisher {
+    fn send(&mut self, event: &Event) {
+        let (topic, payload) = match event {
+            Event::Ready => (format!("{}/speech", self.topic), json!({"event": "ready"})),
+            Event::SpeechStarted(at) => (
+                format!("{}/speech", self.topic),
+                json!({"event": "started", "offset_ms": at.as_millis() as u64}),
+            ),
+            Event::SpeechStopped(at) => (
+                format!("{}/speech", self.topic),
+                json!({"event": "stopped", "offset_ms": at.as_millis() as u64}),
+            ),
+            Event::Mark(at, label) => (
+                format!("{}/speech", self.topic),
+                json!({"event": "mark", "label": label, "offset_ms": at.as_millis() as u64}),
+            ),
+            Event::Final(utterance) => (self.topic.clone(), utterance.to_json()),
+            Event::Heartbeat(heartbeat) => {
+                (format!("{}/heartbeat", self.topic), heartbeat.to_json())
+            }
+            Event::Partial(_) | Event::StablePartial(_) | Event::Dropped(_) => return,
+        };
+        let message = Message {
+            topic,
+            payload: payload.to_string().into_bytes(),
+            qos: self.qos,
+            retain: false,
+        };
+        if self.queue.push(message).is_some() {
+            warn!("MQTT queue is full, dropping oldest message");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url() {
+        for (url, host, port) in [
+            ("mqtt://broker.local", "broker.local", 1883),
+            ("mqtt://broker.local:8883/", "broker.local", 8883),
+            ("tcp://10.0.0.1:1884", "10.0.0.1", 1884),
+            ("localhost", "localhost", 1883),
+        ] {
+            assert_eq!(parse_url(url), Ok((host.to_string(), port)), "{}", url);
+        }
+        assert!(parse_url("mqtt://broker.local:mqtt").is_err());
+    }
+
+    #[test]
+    fn qos() {
+        assert_eq!(parse_qos("0"), Ok(QoS::AtMostOnce));
+        assert_eq!(parse_qos("1"), Ok(QoS::AtLeastOnce));
+        assert_eq!(parse_qos("2"), Ok(QoS::ExactlyOnce));
+        assert!(parse_qos("3").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::clock::Anchor;
+use crate::control::Control;
+use crate::error::Error;
+use crate::input::{self, Options};
+use crate::model::Model;
+use crate::output::{Event, Sink};
+use crate::pipeline::{self, Config};
+use crate::resample::Resampler;
+use crate::segmenter::Segment;
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+use tracing::debug;
+
+/// Output of the pipeline of one of the inputs.
+enum Message {
+    Event(Box<Event>),
+    Samples(Vec<i16>),
+    Segment(Segment),
+}
+
+/// What an opened input needs to start its pipeline.
+struct Start {
+    config: Config,
+    controls: Receiver<Control>,
+    messages: Sender<Message>,
+}
+
+/// Input capturing from one of several devices at once.
+struct Device {
+    name: String,
+    start: Sender<Start>,
+    thread: JoinHandle<Result<(), Error>>,
+}
+
+/// Forwards the output of a pipeline to the thread dispatching it to the shared sink.
+struct Forward {
+    messages: Sender<Message>,
+    /// Whether samples are forwarded, which is the case for the first input only.
+    samples: bool,
+}
+
+impl Sink for Forward {
+    fn send(&mut self, event: &Event) {
+        let _ = self.messages.send(Message::Event(Box::new(event.clone())));
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        if self.samples {
+            let _ = self.messages.send(Message::Samples(samples.to_vec()));
+        }
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        let _ = self.messages.send(Message::Segment(segment.clone()));
+    }
+
+    /// The shared sink is finished once all pipelines are done instead.
+    fn finish(&mut self) {}
+}
+
+/// Devices captured from at once, each segmented by a pipeline of its own, while sharing the
+/// models segments are decoded with.
+pub struct Inputs {
+    devices: Vec<Device>,
+}
+
+impl Inputs {
+    /// Starts capturing from each of `names` with `options` at `sample_rate`, failing if any of
+    /// them fails to open.
+    /// Inputs are opened on the thread their pipeline runs on, since streams cannot be moved
+    /// between threads.
+    pub fn open(
+        options: &Options,
+        names: &[String],
+        sample_rate: u32,
+        resampler: Resampler,
+        dither: bool,
+        models: Vec<Arc<Mutex<Model>>>,
+    ) -> Result<Self, Error> {
+        if !options.default_host() {
+            return Err(Error::Usage(
+                "Capturing from several devices at once is only supported on the default host"
+                    .into(),
+            ));
+        }
+        let mut devices = Vec::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            let options = Options {
+                device: Some(name.clone()),
+                ..options.clone()
+            };
+            let models = models.clone();
+            let (opened_tx, opened) = mpsc::channel();
+            let (start, start_rx) = mpsc::channel::<Start>();
+            let thread = thread::spawn(move || {
+                let anchor = Anchor::default();
+                let (samples, input) = match input::open(
+                    &options,
+                    sample_rate,
+                    resampler,
+                    dither,
+                    anchor.clone(),
+                    &mut None,
+                ) {
+                    Ok(opened) => opened,
+                    Err(err) => {
+                        let _ = opened_tx.send(Err(err));
+                        return Ok(());
+                    }
+                };
+                let _ = opened_tx.send(Ok(input.name().map(str::to_string)));
+                let Start {
+                    mut config,
+                    controls,
+                    messages,
+                } = match start_rx.recv() {
+                    Ok(start) => start,
+                    // The other inputs failed to open.
+                    Err(_) => return Ok(()),
+                };
+                config.anchor = anchor;
+                let mut sink = Forward {
+                    messages,
+                    samples: i == 0,
+                };
+                let result = pipeline::run(samples, models, &config, &controls, &mut sink);
+                drop(input);
+                result
+            });
+            let name = match opened.recv() {
+                Ok(Ok(opened)) => opened.unwrap_or_else(|| name.clone()),
+                Ok(Err(err)) => return Err(err),
+                Err(_) => return Err(Error::Device(format!("Failed to open device {}", name))),
+            };
+            debug!(device = %name, "Opened input");
+            devices.push(Device {
+                name,
+                start,
+                thread,
+            });
+        }
+        Ok(Self { devices })
+    }
+
+    /// Names of the devices captured from, in the order given.
+    pub fn names(&self) -> Vec<&str> {
+        self.devices
+            .iter()
+            .map(|device| device.name.as_str())
+            .collect()
+    }
+
+    /// Transcribes the inputs with `config` until each is exhausted or stopped, sending the events
+    /// of all of them to `sink` as they arrive.
+    /// Utterances are attributed to the speaker of the same position in `labels`, or else the
+    /// name of the device.
+    /// Commands received on `controls` are applied to every input, except for marks and
+    /// heartbeats, which are applied to the first one only, so that they are output once.
+    /// The first error of any input is returned after all of them are done and `sink` is
+    /// finished.
+    pub fn run(
+        self,
+        config: &Config,
+        labels: &[String],
+        controls: Receiver<Control>,
+        sink: &mut dyn Sink,
+    ) -> Result<(), Error> {
+        let (messages, received) = mpsc::channel();
+        let mut fanned = Vec::with_capacity(self.devices.len());
+        let mut threads = Vec::with_capacity(self.devices.len());
+        for (i, device) in self.devices.into_iter().enumerate() {
+            let (controls_tx, controls_rx) = mpsc::channel();
+            let mut config = config.clone();
+            config.speaker = Some(labels.get(i).cloned().unwrap_or(device.name));
+            // Statistics of the other inputs are written next to those of the first one.
+            if let (Some(path), true) = (&config.vad_stats, i > 0) {
+                let mut name = path.file_stem().unwrap_or_default().to_os_string();
+                name.push(format!("-{}", i + 1));
+                if let Some(ext) = path.extension() {
+                    name.push(".");
+                    name.push(ext);
+                }
+                config.vad_stats = Some(path.with_file_name(name));
+            }
+            let _ = device.start.send(Start {
+                config,
+                controls: controls_rx,
+                messages: messages.clone(),
+            });
+            fanned.push(controls_tx);
+            threads.push(device.thread);
+        }
+        drop(messages);
+        thread::spawn(move || {
+            for control in controls {
+                match control {
+                    Control::Mark(_) | Control::Heartbeat(_) => {
+                        let _ = fanned[0].send(control);
+                    }
+                    control => {
+                        for controls in &fanned {
+                            let _ = controls.send(control.clone());
+                        }
+                    }
+                }
+            }
+        });
+        let mut ready = false;
+        for message in received {
+            match message {
+                // Every pipeline signals that it is ready, but the inputs are ready as one.
+                Message::Event(event) if ready && matches!(*event, Event::Ready) => {}
+                Message::Event(event) => {
+                    ready |= matches!(*event, Event::Ready);
+                    sink.send(&event)
+                }
+                Message::Samples(samples) => sink.samples(&samples),
+                Message::Segment(segment) => sink.segment(&segment),
+            }
+        }
+        sink.finish();
+        // Every thread is joined, even once one of them failed.
+        let mut result = Ok(());
+        for thread in threads {
+            result = result.and(thread.join().expect("Failed to join input thread"));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn forward() {
+        let (messages, received) = mpsc::channel();
+        let mut first = Forward {
+            messages: messages.clone(),
+            samples: true,
+        };
+        let mut second = Forward {
+            messages,
+            samples: false,
+        };
+        first.samples(&[1, 2]);
+        second.samples(&[3, 4]);
+        second.send(&Event::SpeechStarted(Duration::from_millis(10)));
+        second.finish();
+        drop((first, second));
+        let received: Vec<_> = received.iter().collect();
+        assert!(matches!(&received[..], [
+            Message::Samples(samples),
+            Message::Event(event),
+        ] if samples == &[1, 2] && matches!(**event, Event::SpeechStarted(_))));
+    }
+}
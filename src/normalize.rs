@@ -0,0 +1,223 @@
+use crate::output::{Event, Sink};
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// Lowest level reported, about the one of a single LSB of 16-bit audio, so that silence has a
+/// finite level.
+const MIN_LEVEL: f64 = -96.0;
+
+/// Level in dBFS relative to a full-scale sine wave.
+#[derive(Clone, Copy, Debug)]
+pub struct Level(pub f64);
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s.trim_end_matches("dBFS").trim().parse::<f64>() {
+            Ok(level) if level <= 0.0 => Ok(Self(level)),
+            _ => Err(format!(
+                "failed to parse `{}` into level in dBFS of at most 0, e.g. -20",
+                s
+            )),
+        }
+    }
+}
+
+impl Level {
+    /// Returns the RMS of a sine wave at the level, as a fraction of full scale.
+    fn rms(self) -> f64 {
+        10f64.powf(self.0 / 20.0) / std::f64::consts::SQRT_2
+    }
+
+    /// Returns the peak amplitude of a sine wave at the level, as a fraction of full scale.
+    fn peak(self) -> f64 {
+        10f64.powf(self.0 / 20.0)
+    }
+
+    /// Returns the level of a sine wave with `rms` as a fraction of full scale.
+    fn of_rms(rms: f64) -> Self {
+        Self::of_peak(rms * std::f64::consts::SQRT_2)
+    }
+
+    /// Returns the level of a sine wave with `peak` amplitude as a fraction of full scale.
+    fn of_peak(peak: f64) -> Self {
+        Self((20.0 * peak.log10()).clamp(MIN_LEVEL, 0.0))
+    }
+}
+
+/// Returns the RMS and the peak amplitude of `samples` as fractions of full scale.
+fn measure(samples: &[i16]) -> (f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let full_scale = -(i16::MIN as f64);
+    let mut sum = 0.0;
+    let mut peak = 0.0f64;
+    for &sample in samples {
+        let sample = sample as f64 / full_scale;
+        sum += sample * sample;
+        peak = peak.max(sample.abs());
+    }
+    ((sum / samples.len() as f64).sqrt(), peak)
+}
+
+/// Loudness of audio, e.g. to tell whether a bad transcript was caused by too quiet or clipping
+/// audio.
+#[derive(Clone, Copy, Debug)]
+pub struct Loudness {
+    pub rms: Level,
+    pub peak: Level,
+}
+
+impl Loudness {
+    pub fn of(samples: &[i16]) -> Self {
+        let (rms, peak) = measure(samples);
+        Self {
+            rms: Level::of_rms(rms),
+            peak: Level::of_peak(peak),
+        }
+    }
+}
+
+/// Collects the RMS level of every final utterance, which is summarized once the pipeline is
+/// done.
+#[derive(Clone, Default)]
+pub struct Summary(Arc<Mutex<Vec<f64>>>);
+
+impl Summary {
+    /// Returns the minimum and the median RMS level of the utterances, if there were any.
+    pub fn rms(&self) -> Option<(f64, f64)> {
+        let mut levels = self.0.lock().expect("Failed to lock levels").clone();
+        if levels.is_empty() {
+            return None;
+        }
+        levels.sort_by(|a, b| a.partial_cmp(b).expect("Failed to compare levels"));
+        Some((levels[0], levels[levels.len() / 2]))
+    }
+}
+
+impl Sink for Summary {
+    fn send(&mut self, event: &Event) {
+        if let Event::Final(utterance) = event {
+            if let Some(loudness) = utterance.loudness {
+                self.0
+                    .lock()
+                    .expect("Failed to lock levels")
+                    .push(loudness.rms.0);
+            }
+        }
+    }
+}
+
+/// Brings segments to a target loudness with a constant gain, computed over the whole segment,
+/// so that quiet speech is decoded better without the pumping of an automatic gain control.
+/// Segments are only ever amplified, so that loud segments are left untouched.
+#[derive(Clone, Copy, Debug)]
+pub struct Normalize {
+    /// RMS level to bring segments to.
+    pub target: Level,
+    /// Peak level, which the gain must not push samples beyond, so that they do not clip.
+    pub ceiling: Level,
+}
+
+impl Normalize {
+    /// Returns the gain bringing `samples` to the target level, which is at least 1.
+    pub fn gain(&self, samples: &[i16]) -> f64 {
+        let (rms, peak) = measure(samples);
+        if rms == 0.0 {
+            return 1.0;
+        }
+        (self.target.rms() / rms)
+            .min(self.ceiling.peak() / peak)
+            .max(1.0)
+    }
+
+    /// Applies the gain bringing `samples` to the target level, returning it.
+    pub fn apply(&self, samples: &mut [i16]) -> f64 {
+        let gain = self.gain(samples);
+        if gain > 1.0 {
+            for sample in samples.iter_mut() {
+                *sample = (*sample as f64 * gain)
+                    .round()
+                    .max(i16::MIN as f64)
+                    .min(i16::MAX as f64) as i16;
+            }
+        }
+        gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    /// Returns a second of a 440 Hz sine wave at `level`, sampled at 16 kHz.
+    fn sine(level: Level) -> Vec<i16> {
+        let amplitude = level.peak() * i16::MAX as f64;
+        (0..16000)
+            .map(|i| (amplitude * (TAU * 440.0 * i as f64 / 16000.0).sin()).round() as i16)
+            .collect()
+    }
+
+    const NORMALIZE: Normalize = Normalize {
+        target: Level(-20.0),
+        ceiling: Level(-1.0),
+    };
+
+    #[test]
+    fn parse() {
+        assert_eq!("-20".parse::<Level>().unwrap().0, -20.0);
+        assert_eq!("-3.5dBFS".parse::<Level>().unwrap().0, -3.5);
+        assert_eq!("0 dBFS".parse::<Level>().unwrap().0, 0.0);
+        assert!("3".parse::<Level>().is_err());
+        assert!("loud".parse::<Level>().is_err());
+    }
+
+    #[test]
+    fn loudness() {
+        let loudness = Loudness::of(&sine(Level(-40.0)));
+        assert!((loudness.rms.0 + 40.0).abs() < 0.1, "{:?}", loudness);
+        assert!((loudness.peak.0 + 40.0).abs() < 0.1, "{:?}", loudness);
+        assert_eq!(Loudness::of(&[0; 160]).rms.0, MIN_LEVEL);
+        assert_eq!(Loudness::of(&[]).peak.0, MIN_LEVEL);
+    }
+
+    #[test]
+    fn quiet_brought_to_target() {
+        let mut samples = sine(Level(-40.0));
+        let gain = NORMALIZE.apply(&mut samples);
+        assert!((gain - 10.0).abs() < 0.1, "{}", gain);
+        let loudness = Loudness::of(&samples);
+        assert!((loudness.rms.0 + 20.0).abs() < 0.1, "{:?}", loudness);
+    }
+
+    #[test]
+    fn loud_untouched() {
+        let samples = sine(Level(-6.0));
+        let mut normalized = samples.clone();
+        assert_eq!(NORMALIZE.apply(&mut normalized), 1.0);
+        assert_eq!(normalized, samples);
+    }
+
+    #[test]
+    fn peak_ceiling() {
+        // A click among quiet samples limits the gain, so that it does not clip.
+        let mut samples = sine(Level(-40.0));
+        samples[100] = i16::MAX / 4;
+        NORMALIZE.apply(&mut samples);
+        let loudness = Loudness::of(&samples);
+        assert!((loudness.peak.0 + 1.0).abs() < 0.1, "{:?}", loudness);
+        assert!(loudness.rms.0 < -20.0, "{:?}", loudness);
+    }
+
+    #[test]
+    fn silence_untouched() {
+        let mut samples = vec![0; 160];
+        assert_eq!(NORMALIZE.apply(&mut samples), 1.0);
+        assert_eq!(samples, [0; 160]);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::output::{Event, Sink};
+use notify_rust::Notification;
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Maximum number of characters of a notification, beyond which the text is truncated.
+const MAX_CHARS: usize = 200;
+
+/// Duration, for which transcripts following each other are collected into one notification, so
+/// that fast speech does not flood the notification daemon.
+const COALESCE: Duration = Duration::from_millis(750);
+
+struct Transcript {
+    text: String,
+    confidence: f64,
+}
+
+/// Shows a desktop notification of every transcript on a separate thread, so that the pipeline is
+/// not held up by the notification daemon.
+/// Transcripts output within `COALESCE` of each other are shown in one notification.
+/// Notifications are disabled with a warning once one fails, e.g. because no notification daemon
+/// is running.
+pub struct Notifier {
+    transcripts: Sender<Transcript>,
+}
+
+impl Notifier {
+    /// Notifications of transcripts less confident than `low_urgency_below` are shown with low
+    /// urgency, where supported.
+    pub fn spawn(low_urgency_below: Option<f64>) -> Self {
+        let (transcripts, rx) = mpsc::channel();
+        thread::spawn(move || show(rx, low_urgency_below));
+        Self { transcripts }
+    }
+}
+
+impl Sink for Notifier {
+    fn send(&mut self, event: &Event) {
+        if let Event::Final(utterance) = event {
+            if utterance.text.is_empty() {
+                return;
+            }
+            let _ = self.transcripts.send(Transcript {
+                text: utterance.text.clone(),
+                confidence: utterance.confidence,
+            });
+        }
+    }
+}
+
+/// Shows the transcripts received on `transcripts` until the sender is dropped or showing one
+/// fails.
+fn show(transcripts: Receiver<Transcript>, low_urgency_below: Option<f64>) {
+    while let Ok(first) = transcripts.recv() {
+        let mut text = first.text;
+        let mut confidence = first.confidence;
+        let deadline = Instant::now() + COALESCE;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match transcripts.recv_timeout(timeout) {
+                Ok(next) => {
+                    text.push(' ');
+                    text.push_str(&next.text);
+                    // Coalesced transcripts are low urgency only if all of them are.
+                    confidence = confidence.max(next.confidence);
+                }
+                Err(_) => break,
+            }
+        }
+        let mut notification = Notification::new();
+        notification
+            .appname("speech2text")
+            .summary("speech2text")
+            .body(&truncate(&text));
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if low_urgency_below.is_some_and(|min| confidence < min) {
+            notification.urgency(notify_rust::Urgency::Low);
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let _ = (confidence, low_urgency_below);
+        if let Err(err) = notification.show() {
+            return warn!(error = %err, "Failed to show notification, disabling notifications");
+        }
+    }
+}
+
+/// Returns `text` truncated to `MAX_CHARS` characters, ending with an ellipsis if it was.
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_CHARS) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated() {
+        assert_eq!(truncate("hello"), "hello");
+        let exact = "é".repeat(MAX_CHARS);
+        assert_eq!(truncate(&exact), exact);
+        let long = "é".repeat(MAX_CHARS + 1);
+        assert_eq!(truncate(&long), format!("{}…", exact));
+    }
+}
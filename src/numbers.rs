@@ -0,0 +1,279 @@
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+
+/// Converts spoken English numbers into digits, e.g. "one hundred and five" into "105", "third"
+/// into "3rd" and "two point five" into "2.5".
+pub struct Numbers;
+
+impl Pass for Numbers {
+    fn apply(&self, utterance: &mut Utterance) {
+        utterance.text = normalize(&utterance.text)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Unit,
+    Teen,
+    Tens,
+    Hundred,
+    Scale,
+    And,
+}
+
+fn cardinal(word: &str) -> Option<(Kind, u64)> {
+    let (kind, value) = match word {
+        "zero" => (Kind::Unit, 0),
+        "one" => (Kind::Unit, 1),
+        "two" => (Kind::Unit, 2),
+        "three" => (Kind::Unit, 3),
+        "four" => (Kind::Unit, 4),
+        "five" => (Kind::Unit, 5),
+        "six" => (Kind::Unit, 6),
+        "seven" => (Kind::Unit, 7),
+        "eight" => (Kind::Unit, 8),
+        "nine" => (Kind::Unit, 9),
+        "ten" => (Kind::Teen, 10),
+        "eleven" => (Kind::Teen, 11),
+        "twelve" => (Kind::Teen, 12),
+        "thirteen" => (Kind::Teen, 13),
+        "fourteen" => (Kind::Teen, 14),
+        "fifteen" => (Kind::Teen, 15),
+        "sixteen" => (Kind::Teen, 16),
+        "seventeen" => (Kind::Teen, 17),
+        "eighteen" => (Kind::Teen, 18),
+        "nineteen" => (Kind::Teen, 19),
+        "twenty" => (Kind::Tens, 20),
+        "thirty" => (Kind::Tens, 30),
+        "forty" => (Kind::Tens, 40),
+        "fifty" => (Kind::Tens, 50),
+        "sixty" => (Kind::Tens, 60),
+        "seventy" => (Kind::Tens, 70),
+        "eighty" => (Kind::Tens, 80),
+        "ninety" => (Kind::Tens, 90),
+        "hundred" => (Kind::Hundred, 100),
+        "thousand" => (Kind::Scale, 1_000),
+        "million" => (Kind::Scale, 1_000_000),
+        "billion" => (Kind::Scale, 1_000_000_000),
+        _ => return None,
+    };
+    Some((kind, value))
+}
+
+fn ordinal(word: &str) -> Option<(Kind, u64)> {
+    let base = match word {
+        "first" => "one",
+        "second" => "two",
+        "third" => "three",
+        "fifth" => "five",
+        "eighth" => "eight",
+        "ninth" => "nine",
+        "twelfth" => "twelve",
+        _ if word.ends_with("ieth") => return cardinal(&format!("{}y", &word[..word.len() - 4])),
+        _ if word.ends_with("th") => &word[..word.len() - 2],
+        _ => return None,
+    };
+    cardinal(base)
+}
+
+fn digit(word: &str) -> Option<char> {
+    match cardinal(word) {
+        Some((Kind::Unit, value)) => std::char::from_digit(value as u32, 10),
+        _ if word == "oh" => Some('0'),
+        _ => None,
+    }
+}
+
+fn suffix(value: u64) -> &'static str {
+    match (value % 10, value % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// Number spelled out by the `len` words it was parsed from.
+struct Number {
+    value: u64,
+    len: usize,
+    ordinal: bool,
+    decimals: String,
+}
+
+impl Number {
+    fn render(&self) -> String {
+        if self.ordinal {
+            format!("{}{}", self.value, suffix(self.value))
+        } else if self.decimals.is_empty() {
+            self.value.to_string()
+        } else {
+            format!("{}.{}", self.value, self.decimals)
+        }
+    }
+}
+
+/// Parses the number spelled out by the longest prefix of `words`, if any.
+fn parse(words: &[&str]) -> Option<Number> {
+    let mut total = 0;
+    let mut current = 0;
+    let mut last: Option<Kind> = None;
+    let mut last_scale = u64::MAX;
+    let mut len = 0;
+    while let Some(word) = words.get(len) {
+        if *word == "and" {
+            let followed = words
+                .get(len + 1)
+                .is_some_and(|next| cardinal(next).or_else(|| ordinal(next)).is_some());
+            if followed && matches!(last, Some(Kind::Hundred) | Some(Kind::Scale)) {
+                last = Some(Kind::And);
+                len += 1;
+                continue;
+            }
+            break;
+        }
+
+        let (kind, value, is_ordinal) = match (cardinal(word), ordinal(word)) {
+            (Some((kind, value)), _) => (kind, value, false),
+            (None, Some((kind, value))) => (kind, value, true),
+            (None, None) => break,
+        };
+        let fits = match kind {
+            Kind::Unit if value == 0 => last.is_none(),
+            Kind::Unit => matches!(
+                last,
+                None | Some(Kind::Tens) | Some(Kind::Hundred) | Some(Kind::Scale) | Some(Kind::And)
+            ),
+            Kind::Teen | Kind::Tens => matches!(
+                last,
+                None | Some(Kind::Hundred) | Some(Kind::Scale) | Some(Kind::And)
+            ),
+            Kind::Hundred => {
+                matches!(last, Some(Kind::Unit) | Some(Kind::Teen) | Some(Kind::Tens))
+                    && current > 0
+                    && current < 100
+            }
+            Kind::Scale => {
+                matches!(
+                    last,
+                    Some(Kind::Unit) | Some(Kind::Teen) | Some(Kind::Tens) | Some(Kind::Hundred)
+                ) && current > 0
+                    && value < last_scale
+            }
+            Kind::And => false,
+        };
+        if !fits {
+            break;
+        }
+        match kind {
+            Kind::Hundred => current *= 100,
+            Kind::Scale => {
+                total += current * value;
+                current = 0;
+                last_scale = value;
+            }
+            _ => current += value,
+        }
+        last = Some(kind);
+        len += 1;
+        if is_ordinal {
+            return Some(Number {
+                value: total + current,
+                len,
+                ordinal: true,
+                decimals: String::new(),
+            });
+        }
+    }
+    if len == 0 {
+        return None;
+    }
+
+    let mut decimals = String::new();
+    if words.get(len) == Some(&"point") {
+        for word in &words[len + 1..] {
+            match digit(word) {
+                Some(digit) => decimals.push(digit),
+                None => break,
+            }
+        }
+        if !decimals.is_empty() {
+            len += 1 + decimals.len();
+        }
+    }
+    Some(Number {
+        value: total + current,
+        len,
+        ordinal: false,
+        decimals,
+    })
+}
+
+/// Returns whether `number` parsed from `words` is more likely meant as a word, e.g. the "one" in
+/// "one of the" or the "second" in "wait a second".
+fn is_ambiguous(number: &Number, words: &[&str]) -> bool {
+    number.len == 1 && number.decimals.is_empty() && matches!(words[0], "one" | "first" | "second")
+}
+
+/// Replaces spoken numbers in `text` by digits.
+pub fn normalize(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut out = Vec::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        match parse(&words[i..]) {
+            Some(number) if !is_ambiguous(&number, &words[i..]) => {
+                out.push(number.render());
+                i += number.len;
+            }
+            _ => {
+                out.push(words[i].to_string());
+                i += 1;
+            }
+        }
+    }
+    out.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_spoken() {
+        for (spoken, expected) in [
+            ("one hundred and five", "105"),
+            ("twenty three point five", "23.5"),
+            ("two point five", "2.5"),
+            ("zero point oh seven", "0.07"),
+            ("third", "3rd"),
+            ("twenty first", "21st"),
+            ("eleventh", "11th"),
+            ("twelfth", "12th"),
+            ("twentieth", "20th"),
+            ("one hundredth", "100th"),
+            ("nineteen ninety nine", "19 99"),
+            ("three million two hundred thousand and one", "3200001"),
+            ("two thousand and twenty", "2020"),
+            ("take one of the two", "take one of the 2"),
+            ("one", "one"),
+            ("wait a second", "wait a second"),
+            ("the first one", "the first one"),
+            ("one two", "one 2"),
+            ("zero one", "0 one"),
+            ("zero zero", "0 0"),
+            ("five point", "5 point"),
+            ("point five", "point 5"),
+            ("hundred", "hundred"),
+            ("thousand million", "thousand million"),
+            ("one thousand thousand", "1000 thousand"),
+            ("rock and roll", "rock and roll"),
+            ("one hundred and", "100 and"),
+            ("no numbers here", "no numbers here"),
+            ("", ""),
+        ] {
+            assert_eq!(normalize(spoken), expected, "{:?}", spoken);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::output::{Event, Sink};
+use rosc::{encoder, OscMessage, OscPacket, OscType};
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+/// Maximum length of a transcript in bytes, which keeps packets below typical MTU.
+const MAX_TEXT_LEN: usize = 1024;
+
+const ELLIPSIS: &str = "…";
+
+const VAD_ADDRESS: &str = "/speech2text/vad";
+
+/// Truncates `text` to at most `MAX_TEXT_LEN` bytes on a character boundary, marking the
+/// truncation with an ellipsis.
+fn truncate(text: &str) -> String {
+    if text.len() <= MAX_TEXT_LEN {
+        return text.to_string();
+    }
+    let mut end = MAX_TEXT_LEN - ELLIPSIS.len();
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &text[..end], ELLIPSIS)
+}
+
+/// Sends final transcripts as OSC messages with the text, start and end in seconds as arguments,
+/// and speech start/stop as `/speech2text/vad` messages with 1 or 0 and the offset in seconds.
+pub struct Osc {
+    socket: UdpSocket,
+    target: SocketAddr,
+    address: String,
+}
+
+impl Osc {
+    pub fn connect(target: &str, address: String) -> io::Result<Self> {
+        let target = target.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` did not resolve to an address", target),
+            )
+        })?;
+        let socket = UdpSocket::bind(if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        Ok(Self {
+            socket,
+            target,
+            address,
+        })
+    }
+
+    fn send_message(&self, addr: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        });
+        // Delivery is best-effort, so failures are silently ignored.
+        if let Ok(buf) = encoder::encode(&packet) {
+            let _ = self.socket.send_to(&buf, self.target);
+        }
+    }
+}
+
+impl Sink for Osc {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::SpeechStarted(at) => self.send_message(
+                VAD_ADDRESS,
+                vec![OscType::Int(1), OscType::Float(at.as_secs_f32())],
+            ),
+            Event::SpeechStopped(at) => self.send_message(
+                VAD_ADDRESS,
+                vec![OscType::Int(0), OscType::Float(at.as_secs_f32())],
+            ),
+            Event::Final(utterance) => self.send_message(
+                &self.address,
+                vec![
+                    OscType::String(truncate(&utterance.text)),
+                    OscType::Float(utterance.start.as_secs_f32()),
+                    OscType::Float(utterance.end.as_secs_f32()),
+                ],
+            ),
+            Event::Ready
+            | Event::Partial(_)
+            | Event::StablePartial(_)
+            | Event::Dropped(_)
+            | Event::Mark(..)
+            | Event::Heartbeat(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use rosc::decoder;
+    use std::time::Duration;
+
+    #[test]
+    fn truncate_on_char_boundary() {
+        assert_eq!(truncate("hello"), "hello");
+        let text = "ä".repeat(MAX_TEXT_LEN);
+        let truncated = truncate(&text);
+        assert!(truncated.len() <= MAX_TEXT_LEN);
+        assert!(truncated.ends_with(ELLIPSIS));
+        assert!(truncated
+            .trim_end_matches(ELLIPSIS)
+            .chars()
+            .all(|c| c == 'ä'));
+    }
+
+    #[test]
+    fn encode() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut osc = Osc::connect(
+            &receiver.local_addr().unwrap().to_string(),
+            "/transcript".to_string(),
+        )
+        .unwrap();
+        osc.send(&Event::SpeechStarted(Duration::from_millis(500)));
+        osc.send(&Event::Partial(Utterance {
+            confidence: 0.0,
+            start: Duration::from_millis(500),
+            end: Duration::from_millis(750),
+            ..Utterance::test("hel")
+        }));
+        osc.send(&Event::Final(Utterance {
+            confidence: 0.0,
+            start: Duration::from_millis(500),
+            end: Duration::from_millis(1250),
+            ..Utterance::test("hello")
+        }));
+        osc.send(&Event::SpeechStopped(Duration::from_millis(1250)));
+
+        let mut messages = Vec::new();
+        let mut buf = [0; 2048];
+        for _ in 0..3 {
+            let n = receiver.recv(&mut buf).unwrap();
+            match decoder::decode(&buf[..n]).unwrap() {
+                OscPacket::Message(message) => messages.push((message.addr, message.args)),
+                OscPacket::Bundle(_) => panic!("unexpected bundle"),
+            }
+        }
+        assert_eq!(
+            messages,
+            [
+                (
+                    VAD_ADDRESS.to_string(),
+                    vec![OscType::Int(1), OscType::Float(0.5)]
+                ),
+                (
+                    "/transcript".to_string(),
+                    vec![
+                        OscType::String("hello".to_string()),
+                        OscType::Float(0.5),
+                        OscType::Float(1.25)
+                    ]
+                ),
+                (
+                    VAD_ADDRESS.to_string(),
+                    vec![OscType::Int(0), OscType::Float(1.25)]
+                ),
+            ]
+        );
+    }
+}
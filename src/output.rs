@@ -0,0 +1,732 @@
+use crate::clock;
+use crate::color::Thresholds;
+use crate::compare::Comparison;
+use crate::durable::{self, Syncer};
+use crate::heartbeat::Heartbeat;
+use crate::intents::Intent;
+use crate::normalize::Loudness;
+use crate::schema;
+use crate::segmenter::Segment;
+use crate::template::{Rolling, Template};
+use crate::timestamp::{self, Format};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+use tracing::warn;
+
+/// Sequence number of the last final utterance output by the process.
+static SEQ: AtomicU64 = AtomicU64::new(0);
+/// Random identifier of the process run, 0 until generated.
+static SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Assigns the sequence number to `event`, which starts at 1 for the first final utterance output
+/// by the process and increases by one for every following one.
+pub fn assign_seq(event: &mut Event) {
+    match event {
+        Event::Final(utterance) => utterance.seq = SEQ.fetch_add(1, Ordering::Relaxed) + 1,
+        Event::Partial(utterance) | Event::StablePartial(utterance) => {
+            utterance.seq = SEQ.load(Ordering::Relaxed) + 1
+        }
+        _ => {}
+    }
+}
+
+/// Groups final utterances into paragraphs, starting a new one whenever the pause since the end
+/// of the previous utterance exceeds the gap.
+pub struct Paragraphs {
+    gap: Duration,
+    /// End of the previous final utterance.
+    previous: Option<Duration>,
+    current: u64,
+}
+
+impl Paragraphs {
+    pub fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            previous: None,
+            current: 1,
+        }
+    }
+
+    /// Assigns the number of its paragraph to `event`, if it is a final utterance.
+    pub fn assign(&mut self, event: &mut Event) {
+        if let Event::Final(utterance) = event {
+            if let Some(previous) = self.previous {
+                if utterance.start.checked_sub(previous).unwrap_or_default() > self.gap {
+                    self.current += 1;
+                }
+            }
+            self.previous = Some(utterance.end);
+            utterance.paragraph = Some(self.current);
+        }
+    }
+}
+
+/// Returns the random identifier of the process run, which distinguishes sequence numbers of
+/// different runs.
+pub fn session_id() -> String {
+    let mut id = SESSION_ID.load(Ordering::Relaxed);
+    if id == 0 {
+        // Hashers of the standard library are seeded randomly per process.
+        let random = RandomState::new().build_hasher().finish().max(1);
+        id = match SESSION_ID.compare_exchange(0, random, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => random,
+            Err(id) => id,
+        };
+    }
+    format!("{:016x}", id)
+}
+
+/// Recognized speech.
+#[derive(Clone)]
+pub struct Utterance {
+    /// Sequence number of the utterance, which partial transcripts share with the final one of
+    /// the utterance they belong to. Assigned as utterances are output.
+    pub seq: u64,
+    pub text: String,
+    /// Label of the speaker, if set.
+    pub speaker: Option<String>,
+    /// Confidence of the model in the transcript, higher is more confident.
+    pub confidence: f64,
+    /// Offset of the start of the speech of the utterance from the start of the stream.
+    pub start: Duration,
+    /// Offset of the end of the speech of the utterance from the start of the stream.
+    pub end: Duration,
+    /// Sample offset of the start of the audio decoded, excluding the overlap with the previous
+    /// utterance, from the start of the stream.
+    pub start_sample: u64,
+    /// Sample offset of the end of the audio decoded from the start of the stream.
+    pub end_sample: u64,
+    /// Sample offsets of the audio decoded and of the speech within it, if known.
+    pub offsets: Option<Offsets>,
+    /// Wall-clock times, at which the start and the end of the speech of the utterance were
+    /// captured, if known.
+    pub captured_at: Option<(SystemTime, SystemTime)>,
+    /// Fingerprint of the audio decoded, if enabled.
+    pub fingerprint: Option<Fingerprint>,
+    /// Loudness of the audio decoded, if known.
+    pub loudness: Option<Loudness>,
+    /// Path of the recording of the utterance, if it was saved.
+    pub recording: Option<PathBuf>,
+    /// Whether words were censored from the transcript.
+    pub censored: bool,
+    /// Whether the confidence is below the minimum.
+    pub low_confidence: bool,
+    /// Number of the paragraph of the utterance starting at 1, if utterances are grouped into
+    /// paragraphs.
+    pub paragraph: Option<u64>,
+    /// Transcript of the audio decoded with the comparison configuration, if comparing.
+    pub comparison: Option<Comparison>,
+    /// Intent the transcript matched, if any, once matched against intents.
+    pub intent: Option<Option<Intent>>,
+    /// Whether the transcript repeats the previous one.
+    pub duplicate: bool,
+    /// Whether the utterance was decoded again for its low confidence.
+    pub rescored: bool,
+    /// Duration of voice detected in the utterance, excluding pauses within it.
+    pub speech: Duration,
+}
+
+/// Sample offsets from the start of the stream, which delimit the audio decoded and the speech
+/// detected within it, making the silence padding the speech explicit.
+#[derive(Clone, Copy, Debug)]
+pub struct Offsets {
+    /// First sample decoded, including any overlap with the previous utterance.
+    pub buffer_start: u64,
+    /// First voiced sample, after the overlap.
+    pub speech_start: u64,
+    /// Sample following the last voiced one.
+    pub speech_end: u64,
+    /// Sample following the last one decoded.
+    pub buffer_end: u64,
+}
+
+impl Offsets {
+    pub fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "buffer_start": self.buffer_start,
+            "speech_start": self.speech_start,
+            "speech_end": self.speech_end,
+            "buffer_end": self.buffer_end,
+        })
+    }
+}
+
+/// Identifies the exact audio an utterance was decoded from, so that transcripts can be matched
+/// with recordings or with transcripts of other runs.
+#[derive(Clone)]
+pub struct Fingerprint {
+    /// SHA256 digest of the samples as little-endian i16, as stored in WAV files.
+    pub sha256: String,
+    pub samples: usize,
+}
+
+impl Fingerprint {
+    pub fn of(samples: &[i16]) -> Self {
+        let mut hasher = Sha256::new();
+        for sample in samples {
+            hasher.update(sample.to_le_bytes());
+        }
+        Self {
+            sha256: format!("{:x}", hasher.finalize()),
+            samples: samples.len(),
+        }
+    }
+}
+
+impl Utterance {
+    /// Renders the timestamps of the start and the end of the utterance in `format`.
+    pub fn timestamps(&self, format: Format) -> (String, String) {
+        (
+            format.render(self.start, self.captured_at.map(|(start, _)| start)),
+            format.render(self.end, self.captured_at.map(|(_, end)| end)),
+        )
+    }
+
+    /// Returns the record of the utterance in the versioned schema.
+    pub fn to_record(&self) -> schema::Utterance {
+        let (start, end) = match timestamp::configured() {
+            Some(format) => {
+                let (start, end) = self.timestamps(format);
+                (Some(start), Some(end))
+            }
+            None => (None, None),
+        };
+        schema::Utterance {
+            schema_version: schema::VERSION,
+            seq: self.seq,
+            session_id: session_id(),
+            text: self.text.clone(),
+            start_ms: self.start.as_millis() as u64,
+            end_ms: self.end.as_millis() as u64,
+            start_sample: self.start_sample,
+            end_sample: self.end_sample,
+            buffer_start: self.offsets.map(|offsets| offsets.buffer_start),
+            speech_start: self.offsets.map(|offsets| offsets.speech_start),
+            speech_end: self.offsets.map(|offsets| offsets.speech_end),
+            buffer_end: self.offsets.map(|offsets| offsets.buffer_end),
+            confidence: self.confidence,
+            start,
+            end,
+            speaker: self.speaker.clone(),
+            paragraph: self.paragraph,
+            captured_at: self.captured_at.map(|(start, end)| schema::CapturedAt {
+                start: clock::format(start),
+                end: clock::format(end),
+            }),
+            audio_sha256: self
+                .fingerprint
+                .as_ref()
+                .map(|fingerprint| fingerprint.sha256.clone()),
+            audio_samples: self
+                .fingerprint
+                .as_ref()
+                .map(|fingerprint| fingerprint.samples),
+            rms_db: self.loudness.map(|loudness| loudness.rms.0),
+            peak_db: self.loudness.map(|loudness| loudness.peak.0),
+            recording: self
+                .recording
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            censored: self.censored,
+            low_confidence: self.low_confidence,
+            duplicate: self.duplicate,
+            rescored: self.rescored,
+            intent: self.intent.as_ref().map(|intent| {
+                intent.as_ref().map(|intent| schema::Intent {
+                    name: intent.name.clone(),
+                    slots: intent.slots.clone(),
+                })
+            }),
+        }
+    }
+
+    /// Returns the record of the utterance as JSON, paired with the comparison transcript, if
+    /// compared.
+    pub fn to_json(&self) -> serde_json::Value {
+        match &self.comparison {
+            Some(comparison) => serde_json::to_value(schema::Pair {
+                primary: self.to_record(),
+                comparison: schema::Comparison {
+                    text: comparison.text.clone(),
+                    confidence: comparison.confidence,
+                    decode_ms: comparison.elapsed.as_millis() as u64,
+                    primary_decode_ms: comparison.primary_elapsed.as_millis() as u64,
+                },
+            }),
+            None => serde_json::to_value(self.to_record()),
+        }
+        .expect("Failed to serialize utterance")
+    }
+
+    /// Returns a final utterance transcribed as `text`, which is otherwise empty.
+    #[cfg(test)]
+    pub fn test(text: &str) -> Self {
+        Self {
+            seq: 0,
+            text: text.to_string(),
+            speaker: None,
+            confidence: 0.0,
+            start: Duration::default(),
+            end: Duration::default(),
+            start_sample: 0,
+            end_sample: 0,
+            offsets: None,
+            captured_at: None,
+            fingerprint: None,
+            loudness: None,
+            recording: None,
+            censored: false,
+            low_confidence: false,
+            paragraph: None,
+            comparison: None,
+            intent: None,
+            duplicate: false,
+            rescored: false,
+            speech: Duration::default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(
+    not(any(feature = "dbus", feature = "grpc", feature = "mqtt")),
+    allow(dead_code)
+)]
+pub enum Event {
+    /// The pipeline started and is ready to transcribe speech.
+    Ready,
+    /// Speech was detected at the given offset.
+    SpeechStarted(Duration),
+    /// Speech ended at the given offset, the transcript will follow.
+    SpeechStopped(Duration),
+    /// Intermediate transcript of speech still in progress.
+    Partial(Utterance),
+    /// Prefix of the transcript of speech still in progress, which remained unchanged across
+    /// recent partial transcripts and is only extended until the final transcript follows.
+    StablePartial(Utterance),
+    /// Transcript of a complete utterance.
+    Final(Utterance),
+    /// Transcript of a complete utterance, which is not output, because it was less confident
+    /// than the minimum confidence.
+    Dropped(Utterance),
+    /// Annotation with a label, e.g. read from stdin, at the given offset.
+    Mark(Duration, String),
+    /// Progress of the pipeline, emitted periodically if enabled.
+    Heartbeat(Heartbeat),
+}
+
+/// Receives events emitted by the pipeline.
+pub trait Sink {
+    fn send(&mut self, event: &Event);
+
+    /// Receives every sample pulled from the input, in order.
+    fn samples(&mut self, _samples: &[i16]) {}
+
+    /// Receives every speech segment before it is decoded.
+    fn segment(&mut self, _segment: &Segment) {}
+
+    /// Called once the pipeline is done, after all events were sent.
+    fn finish(&mut self) {}
+}
+
+impl Sink for Vec<Box<dyn Sink>> {
+    fn send(&mut self, event: &Event) {
+        for sink in self {
+            sink.send(event)
+        }
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        for sink in self {
+            sink.samples(samples)
+        }
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        for sink in self {
+            sink.segment(segment)
+        }
+    }
+
+    fn finish(&mut self) {
+        for sink in self {
+            sink.finish()
+        }
+    }
+}
+
+/// Prints final transcripts to stdout.
+#[derive(Default)]
+pub struct Stdout {
+    /// Whether transcripts are prefixed with their timestamps.
+    timestamps: bool,
+    /// Paragraph of the last transcript printed, which is followed by a blank line once another
+    /// one starts.
+    paragraph: Option<u64>,
+    /// Thresholds words are colored by, if colored.
+    colors: Option<Thresholds>,
+}
+
+impl Stdout {
+    pub fn new(timestamps: bool, colors: Option<Thresholds>) -> Self {
+        Self {
+            timestamps,
+            paragraph: None,
+            colors,
+        }
+    }
+}
+
+impl Sink for Stdout {
+    fn send(&mut self, event: &Event) {
+        if let Event::Mark(at, label) = event {
+            return if self.timestamps {
+                let format = timestamp::configured().unwrap_or(Format::Seconds);
+                println!("[{}] # {}", format.render(*at, None), label)
+            } else {
+                println!("# {}", label)
+            };
+        }
+        if let Event::Final(utterance) = event {
+            if self.paragraph.is_some() && utterance.paragraph != self.paragraph {
+                println!();
+            }
+            self.paragraph = utterance.paragraph;
+            let mut lines = vec![("", &utterance.text, utterance.confidence)];
+            // Compared transcripts are printed in pairs of lines prefixed with their configuration.
+            if let Some(comparison) = &utterance.comparison {
+                lines = vec![
+                    ("primary: ", &utterance.text, utterance.confidence),
+                    ("comparison: ", &comparison.text, comparison.confidence),
+                ];
+            }
+            for (prefix, text, confidence) in lines {
+                let text = match &self.colors {
+                    Some(colors) => colors.paint(text, confidence),
+                    None => text.clone(),
+                };
+                if self.timestamps {
+                    let (start, end) =
+                        utterance.timestamps(timestamp::configured().unwrap_or(Format::Seconds));
+                    println!("[{} --> {}] {}{}", start, end, prefix, text)
+                } else {
+                    println!("{}{}", prefix, text)
+                }
+            }
+        }
+    }
+}
+
+/// Appends final transcripts to a file at the expansion of a template, one per line, switching to
+/// a new one whenever the expansion changes, e.g. daily.
+/// Lines are written with a single write to a file opened for appending, so that lines of
+/// several processes appending to the same file do not interleave. The file is synced to disk
+/// according to `sync`. Once a write fails, nothing more is written, so that the other outputs
+/// continue.
+pub struct TranscriptFile {
+    rolling: Rolling,
+    /// Whether transcripts are written as JSON objects instead of text.
+    json: bool,
+    file: Option<fs::File>,
+    syncer: Syncer,
+    disabled: bool,
+}
+
+impl TranscriptFile {
+    /// Opens the file at the current expansion of `template`, failing if it cannot be opened.
+    pub fn open(template: Template, json: bool, sync: durable::Policy) -> io::Result<Self> {
+        let mut file = Self {
+            rolling: Rolling::new(template),
+            json,
+            file: None,
+            syncer: Syncer::new(sync),
+            disabled: false,
+        };
+        file.rotate()?;
+        Ok(file)
+    }
+
+    /// Switches to the file at the expansion of the template, if it changed.
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(path) = self.rolling.next(SystemTime::now())? {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            self.close();
+            self.file = Some(file);
+        }
+        Ok(())
+    }
+
+    /// Syncs the current file, if any, before it is closed.
+    fn close(&mut self) {
+        if let Some(file) = self.file.take() {
+            if let Err(err) = self.syncer.close(&file) {
+                warn!(error = %err, "Failed to sync transcript file");
+            }
+        }
+    }
+}
+
+impl Sink for TranscriptFile {
+    fn send(&mut self, event: &Event) {
+        let utterance = match event {
+            Event::Final(utterance) if !self.disabled => utterance,
+            _ => return,
+        };
+        if let Err(err) = self.rotate() {
+            warn!(error = %err, "Failed to open transcript file");
+        }
+        let line = if self.json {
+            format!("{}\n", utterance.to_json())
+        } else {
+            format!("{}\n", utterance.text)
+        };
+        if let Some(file) = &mut self.file {
+            if let Err(err) = file.write_all(line.as_bytes()) {
+                warn!(error = %err, "Failed to write transcript file, disabling it");
+                self.disabled = true;
+            } else if let Err(err) = self.syncer.written(file) {
+                warn!(error = %err, "Failed to sync transcript file");
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn utterance_json() {
+        let mut utterance = Utterance {
+            seq: 7,
+            confidence: -3.5,
+            start: Duration::from_millis(1500),
+            end: Duration::from_millis(2750),
+            start_sample: 24000,
+            end_sample: 44000,
+            ..Utterance::test("hello")
+        };
+        assert_eq!(
+            utterance.to_json(),
+            json!({
+                "schema_version": 1,
+                "seq": 7,
+                "session_id": session_id(),
+                "text": "hello",
+                "start_ms": 1500,
+                "end_ms": 2750,
+                "start_sample": 24000,
+                "end_sample": 44000,
+                "confidence": -3.5,
+            })
+        );
+        utterance.recording = Some(PathBuf::from("/recordings/1.wav"));
+        assert_eq!(utterance.to_json()["recording"], "/recordings/1.wav");
+        utterance.low_confidence = true;
+        assert_eq!(utterance.to_json()["low_confidence"], true);
+    }
+
+    #[test]
+    fn comparison_json() {
+        let utterance = Utterance {
+            comparison: Some(Comparison {
+                text: "hullo".into(),
+                confidence: -4.5,
+                elapsed: Duration::from_millis(30),
+                primary_elapsed: Duration::from_millis(20),
+                differs: true,
+            }),
+            ..Utterance::test("hello")
+        };
+        let json = utterance.to_json();
+        assert_eq!(json["primary"]["text"], "hello");
+        assert_eq!(
+            json["comparison"],
+            json!({
+                "text": "hullo",
+                "confidence": -4.5,
+                "decode_ms": 30,
+                "primary_decode_ms": 20,
+            })
+        );
+    }
+
+    #[test]
+    fn offsets_json() {
+        let json = Utterance::test("hello").to_json();
+        assert!(json.get("buffer_start").is_none());
+        assert!(json.get("speech_end").is_none());
+        let utterance = Utterance {
+            offsets: Some(Offsets {
+                buffer_start: 1600,
+                speech_start: 4800,
+                speech_end: 20800,
+                buffer_end: 24000,
+            }),
+            ..Utterance::test("hello")
+        };
+        let json = utterance.to_json();
+        assert_eq!(
+            [
+                &json["buffer_start"],
+                &json["speech_start"],
+                &json["speech_end"],
+                &json["buffer_end"]
+            ],
+            [1600, 4800, 20800, 24000]
+        );
+    }
+
+    #[test]
+    fn intent_json() {
+        assert!(Utterance::test("hello").to_json().get("intent").is_none());
+        let unmatched = Utterance {
+            intent: Some(None),
+            ..Utterance::test("hello")
+        };
+        assert_eq!(unmatched.to_json()["intent"], serde_json::Value::Null);
+        let matched = Utterance {
+            intent: Some(Some(Intent {
+                name: "set_timer".into(),
+                slots: [("minutes".to_string(), serde_json::json!(5))].into(),
+            })),
+            ..Utterance::test("set a timer for five minutes")
+        };
+        assert_eq!(
+            matched.to_json()["intent"],
+            serde_json::json!({ "name": "set_timer", "slots": { "minutes": 5 } })
+        );
+    }
+
+    #[test]
+    fn seq() {
+        let mut events = [
+            Event::Partial(Utterance::test("hel")),
+            Event::Final(Utterance::test("hello")),
+            Event::Partial(Utterance::test("wor")),
+            Event::Final(Utterance::test("world")),
+        ];
+        events.iter_mut().for_each(assign_seq);
+        let seqs: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                Event::Partial(utterance) | Event::Final(utterance) => utterance.seq,
+                _ => unreachable!(),
+            })
+            .collect();
+        // Partial utterances carry the number of the final one they precede.
+        assert_eq!(seqs, [1, 1, 2, 2]);
+
+        let id = session_id();
+        assert_eq!(id.len(), 16);
+        assert_eq!(session_id(), id);
+    }
+
+    #[test]
+    fn paragraphs() {
+        let mut paragraphs = Paragraphs::new(Duration::from_secs(2));
+        let numbers: Vec<_> = [(0, 1), (2, 4), (6, 7), (10, 11), (11, 12)]
+            .iter()
+            .map(|&(start, end)| {
+                let mut event = Event::Final(Utterance {
+                    start: Duration::from_secs(start),
+                    end: Duration::from_secs(end),
+                    ..Utterance::test("hello")
+                });
+                paragraphs.assign(&mut event);
+                match event {
+                    Event::Final(utterance) => utterance.paragraph,
+                    _ => unreachable!(),
+                }
+            })
+            .collect();
+        // A pause of exactly the gap continues the paragraph.
+        assert_eq!(numbers, [Some(1), Some(1), Some(1), Some(2), Some(2)]);
+
+        let mut event = Event::Partial(Utterance::test("hel"));
+        paragraphs.assign(&mut event);
+        assert!(matches!(
+            event,
+            Event::Partial(Utterance {
+                paragraph: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let utterance = Utterance {
+            fingerprint: Some(Fingerprint::of(&[1, -1])),
+            ..Utterance::test("hello")
+        };
+        let value = utterance.to_json();
+        // Digest of the bytes 01 00 ff ff.
+        assert_eq!(
+            value["audio_sha256"],
+            format!("{:x}", Sha256::digest(&[0x01, 0x00, 0xff, 0xff]))
+        );
+        assert_eq!(value["audio_samples"], 2);
+        assert_ne!(
+            Fingerprint::of(&[1]).sha256,
+            Fingerprint::of(&[1, 0]).sha256
+        );
+    }
+
+    #[test]
+    fn transcript_file_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcripts.txt");
+        let template: Template = path.display().to_string().parse().unwrap();
+        let mut first =
+            TranscriptFile::open(template.clone(), false, durable::Policy::Line).unwrap();
+        let mut second = TranscriptFile::open(template, false, durable::Policy::None).unwrap();
+        first.send(&Event::Final(Utterance::test("one")));
+        second.send(&Event::Final(Utterance::test("two")));
+        first.send(&Event::Partial(Utterance::test("partial")));
+        first.send(&Event::Final(Utterance::test("three")));
+        first.finish();
+        second.finish();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn transcript_file_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcripts.jsonl");
+        let template: Template = path.display().to_string().parse().unwrap();
+        let mut file = TranscriptFile::open(template, true, durable::Policy::None).unwrap();
+        file.send(&Event::Final(Utterance::test("hello")));
+        file.finish();
+        let contents = fs::read_to_string(&path).unwrap();
+        let record: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+        assert_eq!(record["text"], "hello");
+
+        let missing: Template = dir
+            .path()
+            .join("file/in/file")
+            .display()
+            .to_string()
+            .parse()
+            .unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        assert!(TranscriptFile::open(missing, false, durable::Policy::None).is_err());
+    }
+}
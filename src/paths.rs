@@ -0,0 +1,50 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Returns the path of the configuration file `name`, `$XDG_CONFIG_HOME/speech2text/<name>`,
+/// falling back to `~/.config/speech2text/<name>` and `./<name>`.
+pub fn config_file(name: &str) -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|config| config.join("speech2text").join(name))
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Returns the data directory `name`, `$XDG_DATA_HOME/speech2text/<name>`, falling back to
+/// `~/.local/share/speech2text/<name>` and `./<name>`.
+pub fn data_dir(name: &str) -> PathBuf {
+    data_dir_in(env::var_os("XDG_DATA_HOME"), env::var_os("HOME"), name)
+}
+
+fn data_dir_in(xdg_data_home: Option<OsString>, home: Option<OsString>, name: &str) -> PathBuf {
+    xdg_data_home
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| Path::new(&home).join(".local/share")))
+        .map(|data| data.join("speech2text").join(name))
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_dir_fallbacks() {
+        let home = || Some(OsString::from("/home/user"));
+        assert_eq!(
+            data_dir_in(Some("/data".into()), home(), "models"),
+            Path::new("/data/speech2text/models")
+        );
+        assert_eq!(
+            data_dir_in(Some("".into()), home(), "models"),
+            Path::new("/home/user/.local/share/speech2text/models")
+        );
+        assert_eq!(data_dir_in(None, None, "models"), Path::new("models"));
+    }
+}
@@ -0,0 +1,851 @@
+use crate::adaptive::Adaptive;
+use crate::clock::{self, Anchor};
+use crate::control::Control;
+use crate::decoder::{Decoder, Job, OverflowPolicy, Rescore};
+use crate::error::Error;
+use crate::latency::Latency;
+use crate::metrics;
+use crate::model::Model;
+use crate::normalize::Normalize;
+use crate::output::{assign_seq, Event, Offsets, Paragraphs, Sink};
+use crate::recordings::{self, Recorder};
+use crate::schedule::Schedule;
+use crate::segmenter::{Segment, Segmenter, Split, Step};
+use crate::stats;
+use crate::stuck::{self, Watchdog};
+use crate::vad::{self, Detector, Ensemble, FvadMode, FvadSampleLength, Policy};
+use dasp::Signal;
+use serde_json::json;
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::Ordering,
+    sync::mpsc::Receiver,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, debug_span, field, info, info_span, warn, Span};
+
+pub const CHANNELS: u16 = 1;
+
+/// What to do with final utterances less confident than the minimum confidence.
+#[derive(Clone, Copy)]
+pub enum LowConfidence {
+    /// Do not output them.
+    Drop,
+    /// Output them marked as low confidence.
+    Mark,
+}
+
+impl FromStr for LowConfidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "mark" => Ok(Self::Mark),
+            _ => Err(format!(
+                "failed to parse `{}` into low confidence policy of drop or mark",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub fvad_sample_length: FvadSampleLength,
+    /// Detector segments are delimited by voice activity with.
+    pub vad: Detector,
+    pub fvad_mode: Option<FvadMode>,
+    /// Whether the Fvad mode is adjusted to the audio, starting at `fvad_mode`.
+    pub vad_adaptive: bool,
+    /// Fvad modes, whose votes are combined according to `vad_policy` instead of `fvad_mode`.
+    pub vad_ensemble: Option<Ensemble>,
+    pub vad_policy: Policy,
+    /// Margin in dB above the noise floor, which frames need to exceed to be voiced by the energy
+    /// detector.
+    pub energy_margin: f64,
+    /// Recorder to save each segment with.
+    pub recorder: Option<Recorder>,
+    /// Segments with less speech are not decoded.
+    pub min_speech: Duration,
+    /// Silence padding segments, which completes them once it follows speech.
+    pub silence_padding: Duration,
+    /// Segments are split once they reach this duration, even if speech continues.
+    pub max_utterance: Duration,
+    /// Duration at the end of a segment reaching `max_utterance`, within which it is split at the
+    /// quietest frame.
+    pub split_search: Duration,
+    /// Duration of audio before a split, which is repeated at the start of the following segment,
+    /// so that words cut by the split are not lost.
+    pub split_overlap: Duration,
+    /// Duration at the end of a segment reaching `max_utterance`, which is carried over into the
+    /// following segment at least.
+    pub split_min_carry: Duration,
+    /// Recorder to save segments rejected for containing too little speech with.
+    pub rejected_recorder: Option<Recorder>,
+    /// Interval, at which a sample of continuous silence is saved with `rejected_recorder`.
+    pub silence_sample_interval: Option<Duration>,
+    /// Interval between partial transcripts of speech in progress, disabled if `None`.
+    pub partial_interval: Option<Duration>,
+    /// Duration at the start of the stream, which is discarded before voice activity detection,
+    /// e.g. because devices click when they start capturing.
+    pub startup_skip: Duration,
+    /// Sample offset of the first sample from the start of the timebase offsets are reported in,
+    /// e.g. the start of a file, which was started transcribing later into.
+    pub origin: u64,
+    /// Margin of audio kept before the first voiced frame and after the last one of segments,
+    /// which are trimmed to it before decoding, but not before recording, if set.
+    pub trim_margin: Option<Duration>,
+    /// Maximum number of segments waiting to be decoded.
+    pub decode_queue: usize,
+    /// Niceness of the decode workers, if set.
+    pub decode_nice: Option<i32>,
+    /// What to do when segments are completed faster than they are decoded.
+    pub overflow_policy: OverflowPolicy,
+    /// Capture time of the first sample, which the capture times of utterances are derived from.
+    pub anchor: Anchor,
+    /// Measures the latency of utterances, if set.
+    pub latency: Option<Latency>,
+    /// Normalizes the loudness of segments before decoding, if set.
+    pub normalize: Option<Normalize>,
+    /// Whether segments are recorded after normalization rather than as captured.
+    pub record_normalized: bool,
+    /// Whether final utterances carry a fingerprint of the audio decoded.
+    pub fingerprint: bool,
+    /// Label of the speaker attached to every utterance, if set.
+    pub speaker: Option<String>,
+    /// Path of the model utterances are decoded with.
+    pub model: PathBuf,
+    /// Model, which segments are decoded with as well to compare transcripts, if set.
+    /// It is shared by all decode workers.
+    pub comparison: Option<Arc<Mutex<Model>>>,
+    /// Configuration, which final utterances of low confidence are decoded again with, if set.
+    pub rescore: Option<Rescore>,
+    /// Confidence, below which final utterances are handled according to `low_confidence`, if
+    /// set.
+    pub min_confidence: Option<f64>,
+    pub low_confidence: LowConfidence,
+    /// Pause between utterances, after which a new paragraph starts, if utterances are grouped
+    /// into paragraphs.
+    pub paragraph_gap: Option<Duration>,
+    /// Number of segments failing to decode in a row, after which the pipeline stops, 0 for none.
+    pub max_decode_failures: usize,
+    /// Path to write statistics of voice activity detection to once the pipeline stops, if set.
+    pub vad_stats: Option<PathBuf>,
+    /// Windows of local time, outside of which the pipeline behaves as paused, if set. They are
+    /// evaluated at the capture time of the audio, falling back to the current time if unknown.
+    pub schedule: Option<Schedule>,
+    /// Time the segments submitted are given to be decoded, once the pipeline is stopped, after
+    /// which they are abandoned, if set.
+    pub drain_timeout: Option<Duration>,
+    /// Time of audio listened to without speech, after which the pipeline stops with an error,
+    /// if set.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Config {
+    pub fn wav_spec(&self) -> hound::WavSpec {
+        hound::WavSpec {
+            channels: CHANNELS,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.bits_per_sample,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    /// Converts a sample offset into a duration.
+    pub fn duration(&self, offset: u64) -> Duration {
+        Duration::from_millis(offset * 1000 / self.sample_rate as u64)
+    }
+
+    /// Returns the capture time of the sample at `offset`, if the capture time of the first one is
+    /// known.
+    pub fn captured_at(&self, offset: u64) -> Option<SystemTime> {
+        self.anchor.at(Duration::from_micros(
+            offset * 1_000_000 / self.sample_rate as u64,
+        ))
+    }
+}
+
+/// Transcribes `samples`, sending the events to `sink` until `samples` is exhausted, starting
+/// with `Event::Ready`.
+/// Speech still buffered once `samples` is exhausted is transcribed as well.
+/// Segments are decoded on a worker thread per model in `models`, so that decoding does not hold
+/// up segmentation, and the transcripts are sent to `sink` in order between frames.
+/// Commands received on `controls` are applied between frames.
+/// Segments, which fail to decode, are skipped, unless `max_decode_failures` fail in a row, in
+/// which case the pipeline stops with an error.
+/// Once stopped, the speech buffered is decoded as well, unless it takes longer than
+/// `drain_timeout`, in which case the pipeline stops with an error after outputting the
+/// transcripts decoded in time.
+/// If no speech is detected for `idle_timeout`, the pipeline stops with an error as well.
+/// `sink` is finished before returning.
+pub fn run(
+    samples: impl Iterator<Item = i16>,
+    models: Vec<Arc<Mutex<Model>>>,
+    config: &Config,
+    controls: &Receiver<Control>,
+    sink: &mut dyn Sink,
+) -> Result<(), Error> {
+    let vad_sample_rate = vad::sample_rate(config.sample_rate).map_err(Error::Usage)?;
+    let vad = vad::detector(
+        config.vad,
+        vad_sample_rate,
+        config.fvad_mode,
+        config.vad_ensemble.as_ref(),
+        config.vad_policy,
+        config.energy_margin,
+    )
+    .map_err(Error::Usage)?;
+
+    let frame_sample_count = (config.fvad_sample_length as u32 * (vad_sample_rate / 1000)) as usize;
+    let partial_frames = config.partial_interval.map(|interval| {
+        (interval.as_millis() as usize / config.fvad_sample_length as usize).max(1)
+    });
+    let mut signal = dasp::signal::from_iter(samples).buffered(dasp::ring_buffer::Bounded::from(
+        vec![0; frame_sample_count],
+    ));
+
+    let min_speech_frames =
+        config.min_speech.as_millis() as usize / config.fvad_sample_length as usize;
+    let silence_sample_frames = config.silence_sample_interval.map(|interval| {
+        (interval.as_millis() as usize / config.fvad_sample_length as usize).max(1)
+    });
+    let max_samples =
+        (config.max_utterance.as_millis() as u64 * config.sample_rate as u64 / 1000) as usize;
+    let mut decoder = Decoder::spawn(models, config.clone());
+    let frames =
+        |duration: Duration| duration.as_millis() as usize / config.fvad_sample_length as usize;
+    let mut segmenter = Segmenter::new(
+        vad,
+        frame_sample_count,
+        min_speech_frames,
+        max_samples.max(frame_sample_count),
+        frames(config.silence_padding),
+        Split {
+            search_frames: frames(config.split_search),
+            overlap_frames: frames(config.split_overlap),
+            min_carry_frames: frames(config.split_min_carry),
+        },
+    );
+    let mut adaptive = if config.vad_adaptive {
+        Some(Adaptive::new(
+            config.fvad_mode.unwrap_or(FvadMode::Quality),
+            Duration::from_millis(config.fvad_sample_length as u64),
+        ))
+    } else {
+        None
+    };
+    metrics::set_fvad_mode(match config.vad {
+        Detector::Fvad if config.vad_ensemble.is_none() => config.fvad_mode.map(|mode| mode as u8),
+        _ => None,
+    });
+    // Every frame is voiced without a detector, as intended.
+    let mut watchdog = match config.vad {
+        Detector::None => None,
+        _ => Some(Watchdog::new(
+            Duration::from_millis(config.fvad_sample_length as u64),
+            config.max_utterance * stuck::RUN_UTTERANCES,
+            stuck::WINDOW,
+        )),
+    };
+    let mut vad_stats = stats::Vad::default();
+    let mut frames_since_partial = 0;
+    let mut frames_since_silence_sample = 0;
+    let mut frame = Vec::with_capacity(frame_sample_count);
+    // Spans of the utterance in progress and of voice activity detection within it.
+    let mut utterance: Option<(Span, Span)> = None;
+    let mut paused = false;
+    // Whether the audio is within the windows of the schedule, if any. A pause applies within them
+    // as well, while resuming has no effect outside of them.
+    let mut scheduled = true;
+    // Evaluated every second of audio, immediately at the start of the stream.
+    let schedule_frames = (1000 / config.fvad_sample_length as usize).max(1);
+    let mut frames_since_schedule = schedule_frames;
+    let mut stopped = false;
+    let mut wedged = false;
+    let mut paragraphs = config.paragraph_gap.map(Paragraphs::new);
+    if config.startup_skip > Duration::default() {
+        debug!(skip = ?config.startup_skip, "Skipping start of the stream");
+    }
+    // Offsets of the stream start at the origin, so that everything derived from them is within
+    // its timebase.
+    segmenter.skip(config.origin as usize);
+    let startup_skip = StartupSkip::new(config.startup_skip, config.sample_rate, config.origin);
+    let mut idle = config
+        .idle_timeout
+        .map(|timeout| Idle::new(timeout, config.sample_rate, config.origin));
+    let mut timed_out = false;
+    sink.send(&Event::Ready);
+    while !stopped && !signal.is_exhausted() {
+        for control in controls.try_iter() {
+            match control {
+                Control::Pause => paused = true,
+                Control::Resume => paused = false,
+                Control::Flush => {}
+                Control::Stop => stopped = true,
+                Control::Mark(label) => {
+                    // Marks annotate the stream without interrupting speech in progress.
+                    sink.send(&Event::Mark(config.duration(segmenter.offset()), label));
+                    continue;
+                }
+                Control::Heartbeat(heartbeat) => {
+                    sink.send(&Event::Heartbeat(heartbeat));
+                    continue;
+                }
+            }
+            metrics::PAUSED.store(paused || !scheduled, Ordering::Relaxed);
+            if let Some(segment) = segmenter.flush() {
+                vad_stats.segment(
+                    config.duration(segment.start),
+                    config.duration(segment.end()),
+                    false,
+                );
+                submit(segment, utterance.take(), &decoder, config, sink)
+            }
+        }
+        if stopped {
+            break;
+        }
+        if let Some(schedule) = &config.schedule {
+            frames_since_schedule += 1;
+            if frames_since_schedule >= schedule_frames {
+                frames_since_schedule = 0;
+                let at = config
+                    .captured_at(segmenter.offset())
+                    .unwrap_or_else(SystemTime::now);
+                let active = schedule.active(&clock::local(at));
+                if active != scheduled {
+                    scheduled = active;
+                    info!(active, "Listening window of the schedule changed");
+                    metrics::PAUSED.store(paused || !scheduled, Ordering::Relaxed);
+                    let label = if active {
+                        "listening window started"
+                    } else {
+                        "listening window ended"
+                    };
+                    sink.send(&Event::Mark(
+                        config.duration(segmenter.offset()),
+                        label.into(),
+                    ));
+                    // Speech in progress is transcribed up to the end of the window, while none
+                    // is in progress at its start.
+                    if let Some(segment) = segmenter.flush() {
+                        vad_stats.segment(
+                            config.duration(segment.start),
+                            config.duration(segment.end()),
+                            false,
+                        );
+                        submit(segment, utterance.take(), &decoder, config, sink)
+                    }
+                }
+            }
+        }
+        if decoder.wedged() {
+            wedged = true;
+            break;
+        }
+
+        for (event, span) in decoder.try_iter() {
+            output(event, &span, config, &mut paragraphs, sink)
+        }
+
+        frame.clear();
+        frame.extend(signal.next_frames());
+        metrics::add_duration(&metrics::AUDIO_MICROS, config.duration(frame.len() as u64));
+        sink.samples(&frame);
+        if paused || !scheduled || startup_skip.discards(segmenter.offset()) {
+            segmenter.skip(frame.len());
+            if let Some(idle) = &mut idle {
+                idle.reset(segmenter.offset());
+            }
+            continue;
+        }
+        let step = {
+            let _entered = utterance.as_ref().map(|(_, vad)| vad.enter());
+            segmenter.push(&frame)
+        };
+        metrics::SPEECH.store(segmenter.pending().is_some(), Ordering::Relaxed);
+        if let Some(idle) = &mut idle {
+            if segmenter.pending().is_some() {
+                idle.reset(segmenter.offset());
+            } else if idle.elapsed(segmenter.offset()) {
+                timed_out = true;
+                break;
+            }
+        }
+        vad_stats.frame(segmenter.voiced());
+        match &step {
+            Some(Step::SpeechStopped(segment)) | Some(Step::Split(segment)) => vad_stats.segment(
+                config.duration(segment.start),
+                config.duration(segment.end()),
+                false,
+            ),
+            Some(Step::Rejected(segment)) => vad_stats.segment(
+                config.duration(segment.start),
+                config.duration(segment.end()),
+                true,
+            ),
+            _ => {}
+        }
+        if let Some(adaptive) = &mut adaptive {
+            adaptive.frame(segmenter.voiced());
+            match &step {
+                Some(Step::SpeechStopped(_)) => adaptive.utterance(false),
+                Some(Step::Rejected(_)) => adaptive.utterance(true),
+                _ => {}
+            }
+            // Modes only change between utterances, so that each is detected in a single mode.
+            if segmenter.pending().is_none() {
+                if let Some(mode) = adaptive.adjust() {
+                    segmenter.vad().set_mode(mode);
+                    metrics::set_fvad_mode(Some(mode as u8));
+                }
+            }
+        }
+        if let Some(watchdog) = &mut watchdog {
+            if let Some(reason) = watchdog.frame(segmenter.voiced()) {
+                warn!(
+                    %reason,
+                    "Voice activity detection seems stuck, mistaking noise for speech, so that \
+                     nothing is transcribed. Try a more aggressive --fvad-mode, --vad energy with \
+                     a higher --energy-margin or reducing the background noise"
+                );
+                sink.send(&Event::Mark(
+                    config.duration(segmenter.offset()),
+                    format!("warning: voice activity detection seems stuck, {}", reason),
+                ));
+            }
+            metrics::VAD_STUCK.store(watchdog.stuck(), Ordering::Relaxed);
+        }
+        match step {
+            Some(Step::SpeechStarted(offset)) => {
+                frames_since_partial = 0;
+                let span = utterance_span(config.duration(offset));
+                let vad = debug_span!(parent: &span, "vad");
+                utterance = Some((span, vad));
+                sink.send(&Event::SpeechStarted(config.duration(offset)));
+            }
+            Some(Step::SpeechStopped(segment)) => {
+                if let Some(latency) = &config.latency {
+                    let padding = (config.silence_padding.as_millis() as u64
+                        * config.sample_rate as u64
+                        / 1000)
+                        .min(segment.end());
+                    latency.completed(
+                        segment.end(),
+                        config.silence_padding,
+                        config.captured_at(segment.end() - padding),
+                        config.captured_at(segment.end()),
+                    );
+                }
+                submit(segment, utterance.take(), &decoder, config, sink)
+            }
+            Some(Step::Split(segment)) => {
+                // Speech continues right where the overlap of the next segment ends.
+                let offset = segment.end();
+                if let Some(latency) = &config.latency {
+                    let captured = config.captured_at(offset);
+                    latency.completed(offset, Duration::default(), captured, captured);
+                }
+                submit(segment, utterance.take(), &decoder, config, sink);
+                frames_since_partial = 0;
+                let span = utterance_span(config.duration(offset));
+                let vad = debug_span!(parent: &span, "vad");
+                utterance = Some((span, vad));
+                sink.send(&Event::SpeechStarted(config.duration(offset)));
+            }
+            Some(Step::Rejected(segment)) => {
+                if let Some((span, _)) = utterance.take() {
+                    span.record("rejected", true);
+                }
+                sink.send(&Event::SpeechStopped(config.duration(segment.end())));
+                debug!(
+                    samples = segment.samples.len(),
+                    "Rejected segment with too little speech"
+                );
+                if let Some(recorder) = &config.rejected_recorder {
+                    recorder.save(
+                        &recordings::timestamped_name("rejected-too-short-"),
+                        config.wav_spec(),
+                        &segment.samples,
+                    );
+                }
+            }
+            None => {}
+        }
+
+        if let (Some(recorder), Some(interval)) = (&config.rejected_recorder, silence_sample_frames)
+        {
+            if segmenter.pending().is_some() {
+                frames_since_silence_sample = 0;
+            } else {
+                frames_since_silence_sample += 1;
+                if frames_since_silence_sample >= interval {
+                    frames_since_silence_sample = 0;
+                    recorder.save(
+                        &recordings::timestamped_name("rejected-silence-"),
+                        config.wav_spec(),
+                        segmenter.buffered().1,
+                    );
+                }
+            }
+        }
+
+        if let (Some(partial_frames), Some((start, buffer))) = (partial_frames, segmenter.pending())
+        {
+            frames_since_partial += 1;
+            if frames_since_partial < partial_frames {
+                continue;
+            }
+            frames_since_partial = 0;
+            decoder.submit(Job::Partial {
+                start,
+                samples: buffer.to_vec(),
+            });
+        }
+    }
+
+    // The audio is not read any further, which stops capturing it.
+    drop(signal);
+    let timeout = if stopped { config.drain_timeout } else { None };
+    // Speech still buffered is submitted, before waiting for the decoder to decode it.
+    if let Some(segment) = segmenter.flush() {
+        vad_stats.segment(
+            config.duration(segment.start),
+            config.duration(segment.end()),
+            false,
+        );
+        submit(segment, utterance.take(), &decoder, config, sink)
+    }
+    debug!(?timeout, "Waiting for the decode queue to drain");
+    let drained = decoder.finish(timeout);
+    for (event, span) in drained.events {
+        output(event, &span, config, &mut paragraphs, sink)
+    }
+    // Finishing the sink finalizes recordings and transcript files.
+    sink.finish();
+    if let Some(path) = &config.vad_stats {
+        let detector = match config.vad {
+            Detector::Fvad => "fvad",
+            Detector::Energy => "energy",
+            Detector::None => "none",
+        };
+        vad_stats.write(
+            path,
+            json!({
+                "vad": detector,
+                "fvad_mode": config.fvad_mode.map(|mode| mode as u8),
+                "final_fvad_mode": metrics::fvad_mode(),
+                "vad_adaptive": config.vad_adaptive,
+                "vad_ensemble": config.vad_ensemble.as_ref().map(|ensemble| {
+                    ensemble.0.iter().map(|&mode| mode as u8).collect::<Vec<_>>()
+                }),
+                "vad_policy": match config.vad_policy {
+                    Policy::Majority => "majority",
+                    Policy::Any => "any",
+                },
+                "fvad_sample_length_ms": config.fvad_sample_length as u8,
+                "energy_margin_db": config.energy_margin,
+                "min_speech_ms": config.min_speech.as_millis() as u64,
+                "silence_padding_ms": config.silence_padding.as_millis() as u64,
+                "max_utterance_ms": config.max_utterance.as_millis() as u64,
+            }),
+        );
+    }
+    if wedged {
+        return Err(Error::Decode(format!(
+            "Failed to decode {} segments in a row, the model is probably wedged",
+            config.max_decode_failures
+        )));
+    }
+    if drained.abandoned > 0 {
+        return Err(Error::Drain(format!(
+            "Failed to decode {} segments within the drain timeout of {:?}",
+            drained.abandoned,
+            timeout.unwrap_or_default()
+        )));
+    }
+    if timed_out {
+        return Err(Error::Idle(format!(
+            "No speech was detected within the idle timeout of {:?}",
+            config.idle_timeout.unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// Start of the stream, which is discarded before voice activity detection, e.g. because devices
+/// click when they start capturing.
+#[derive(Clone, Copy)]
+struct StartupSkip {
+    /// Sample offset of the first sample, which is not discarded.
+    end: u64,
+}
+
+impl StartupSkip {
+    /// Discards `skip` of audio at `sample_rate` following the sample offset `origin`.
+    fn new(skip: Duration, sample_rate: u32, origin: u64) -> Self {
+        Self {
+            end: origin + skip.as_millis() as u64 * sample_rate as u64 / 1000,
+        }
+    }
+
+    /// Returns whether the frame starting at the sample offset `offset` is discarded.
+    fn discards(self, offset: u64) -> bool {
+        offset < self.end
+    }
+}
+
+/// Time of audio listened to without speech, which is not counted while paused or discarded.
+struct Idle {
+    /// Number of samples without speech, after which the pipeline is idle.
+    timeout: u64,
+    /// Sample offset, since which no speech was detected.
+    since: u64,
+}
+
+impl Idle {
+    /// Times out after `timeout` of audio at `sample_rate` from the sample offset `origin`.
+    fn new(timeout: Duration, sample_rate: u32, origin: u64) -> Self {
+        Self {
+            timeout: timeout.as_millis() as u64 * sample_rate as u64 / 1000,
+            since: origin,
+        }
+    }
+
+    /// Restarts counting at the sample offset `offset`, e.g. because speech is in progress.
+    fn reset(&mut self, offset: u64) {
+        self.since = offset;
+    }
+
+    /// Returns whether no speech was detected for the timeout up to the sample offset `offset`.
+    fn elapsed(&self, offset: u64) -> bool {
+        offset - self.since >= self.timeout
+    }
+}
+
+/// Returns a span covering an utterance starting at `start` from detection to output.
+fn utterance_span(start: Duration) -> Span {
+    info_span!(
+        "utterance",
+        start_ms = start.as_millis() as u64,
+        end_ms = field::Empty,
+        samples = field::Empty,
+        rejected = field::Empty,
+    )
+}
+
+/// Sends `event` to `sink` within `span`, assigning its sequence number.
+/// Final utterances less confident than `min_confidence` are sent as `Event::Dropped` or marked,
+/// but annotated in their recording either way.
+fn output(
+    mut event: Event,
+    span: &Span,
+    config: &Config,
+    paragraphs: &mut Option<Paragraphs>,
+    sink: &mut dyn Sink,
+) {
+    let _entered = debug_span!(parent: span, "output").entered();
+    let mut dropped = false;
+    if let (Some(min), Event::Final(utterance)) = (config.min_confidence, &mut event) {
+        if utterance.confidence < min {
+            metrics::LOW_CONFIDENCE_UTTERANCES.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                confidence = utterance.confidence,
+                text = %utterance.text,
+                "Utterance is less confident than the minimum"
+            );
+            utterance.low_confidence = true;
+            dropped = matches!(config.low_confidence, LowConfidence::Drop);
+        }
+    }
+    if dropped {
+        if let Event::Final(utterance) = event {
+            event = Event::Dropped(utterance);
+        }
+    } else {
+        assign_seq(&mut event);
+        if let (Some(recorder), Event::Final(utterance)) = (&config.recorder, &mut event) {
+            if let Some(path) = &utterance.recording {
+                utterance.recording = Some(recorder.number(path, utterance.seq));
+            }
+        }
+        if let Some(paragraphs) = paragraphs {
+            paragraphs.assign(&mut event);
+        }
+    }
+    let finished = match &event {
+        Event::Final(utterance) | Event::Dropped(utterance) => Some(utterance),
+        _ => None,
+    };
+    if let (Some(recorder), Some(utterance)) = (&config.recorder, finished) {
+        if let Some(path) = &utterance.recording {
+            recorder.annotate(
+                path,
+                &json!({
+                    "text": utterance.text,
+                    "confidence": utterance.confidence,
+                    "low_confidence": utterance.low_confidence,
+                    "start_ms": utterance.start.as_millis() as u64,
+                    "end_ms": utterance.end.as_millis() as u64,
+                    "start_sample": utterance.start_sample,
+                    "end_sample": utterance.end_sample,
+                    "offsets": utterance.offsets.map(Offsets::to_json),
+                    "fvad_mode": config.fvad_mode.map(|mode| mode as u8),
+                    "fvad_sample_length_ms": config.fvad_sample_length as u8,
+                    "model": config.model.display().to_string(),
+                }),
+            );
+        }
+    }
+    if let Event::Final(_) = event {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        metrics::LAST_TRANSCRIPT_MILLIS.store(now.as_millis() as u64, Ordering::Relaxed);
+    }
+    sink.send(&event);
+    match (&config.latency, &event) {
+        (Some(latency), Event::Final(utterance)) => latency.sent(utterance.end_sample),
+        (Some(latency), Event::Dropped(utterance)) => latency.discard(utterance.end_sample),
+        _ => {}
+    }
+}
+
+/// Completes `segment` within the span of its utterance, if any, submitting it for decoding.
+fn submit(
+    mut segment: Segment,
+    utterance: Option<(Span, Span)>,
+    decoder: &Decoder,
+    config: &Config,
+    sink: &mut dyn Sink,
+) {
+    let span = match utterance {
+        Some((span, _)) => span,
+        None => utterance_span(config.duration(segment.start)),
+    };
+    span.record("end_ms", config.duration(segment.end()).as_millis() as u64);
+    span.record("samples", segment.samples.len());
+    let _entered = span.enter();
+
+    sink.send(&Event::SpeechStopped(config.duration(segment.end())));
+    let normalize = |segment: &mut Segment| {
+        if let Some(normalize) = &config.normalize {
+            let gain = normalize.apply(&mut segment.samples);
+            debug!(gain, "Normalized segment");
+        }
+    };
+    if config.record_normalized {
+        normalize(&mut segment);
+    }
+    let recording = config
+        .recorder
+        .as_ref()
+        .and_then(|recorder| recorder.save_segment(config.wav_spec(), &segment.samples));
+    sink.segment(&segment);
+    if let Some(margin) = config.trim_margin {
+        let end = segment.end();
+        segment.trim((margin.as_millis() as u64 * config.sample_rate as u64 / 1000) as usize);
+        debug!(samples = segment.samples.len(), "Trimmed segment");
+        span.record("end_ms", config.duration(segment.end()).as_millis() as u64);
+        span.record("samples", segment.samples.len());
+        if let Some(latency) = &config.latency {
+            latency.trimmed(end, segment.end());
+        }
+    }
+    if !config.record_normalized {
+        normalize(&mut segment);
+    }
+    decoder.submit(Job::Final {
+        segment,
+        recording,
+        span: span.clone(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples of a frame of 30 ms at 16 kHz.
+    const FRAME: usize = 480;
+
+    /// Returns a frame of a square wave of `amplitude`.
+    fn frame(amplitude: i16) -> Vec<i16> {
+        (0..FRAME)
+            .map(|i| {
+                if i / 20 % 2 == 0 {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the sample offsets, at which speech is detected in `frames`, if the start of the
+    /// stream is discarded according to `skip`.
+    fn speech_started(frames: &[Vec<i16>], skip: StartupSkip) -> Vec<u64> {
+        let split = Split {
+            search_frames: 20,
+            overlap_frames: 3,
+            min_carry_frames: 2,
+        };
+        let mut segmenter = Segmenter::new(vad::new(16000, None), FRAME, 1, 16000 * 10, 2, split);
+        let mut started = Vec::new();
+        for frame in frames {
+            if skip.discards(segmenter.offset()) {
+                segmenter.skip(frame.len());
+                continue;
+            }
+            if let Some(Step::SpeechStarted(offset)) = segmenter.push(frame) {
+                started.push(offset);
+            }
+        }
+        started
+    }
+
+    #[test]
+    fn startup_skip() {
+        // Audio of a device, which clicks once it starts capturing and then captures silence,
+        // followed by speech.
+        let mut frames = vec![frame(0), frame(20000), frame(20000)];
+        frames.extend(std::iter::repeat_n(frame(0), 20));
+        frames.extend(std::iter::repeat_n(frame(8000), 10));
+        frames.extend(std::iter::repeat_n(frame(0), 10));
+        let speech = 23 * FRAME as u64;
+
+        let none = StartupSkip::new(Duration::default(), 16000, 0);
+        assert_eq!(speech_started(&frames, none), [FRAME as u64, speech]);
+
+        let skip = StartupSkip::new(Duration::from_millis(100), 16000, 0);
+        assert_eq!(skip.end, 1600);
+        assert!(skip.discards(3 * FRAME as u64));
+        assert!(!skip.discards(4 * FRAME as u64));
+        assert_eq!(speech_started(&frames, skip), [speech]);
+    }
+
+    #[test]
+    fn startup_skip_after_origin() {
+        let skip = StartupSkip::new(Duration::from_millis(100), 16000, 16000);
+        assert!(skip.discards(16000));
+        assert!(skip.discards(17599));
+        assert!(!skip.discards(17600));
+    }
+
+    #[test]
+    fn idle() {
+        let mut idle = Idle::new(Duration::from_secs(2), 16000, 8000);
+        assert!(!idle.elapsed(8000 + 31999));
+        assert!(idle.elapsed(8000 + 32000));
+        // Speech restarts counting.
+        idle.reset(48000);
+        assert!(!idle.elapsed(79999));
+        assert!(idle.elapsed(80000));
+    }
+}
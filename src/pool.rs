@@ -0,0 +1,179 @@
+use crate::metrics;
+use crate::model::Model;
+use std::{
+    sync::atomic::Ordering,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Parses the number of copies of the model in a pool, which needs to be positive.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!(
+            "failed to parse `{}` into pool size of a positive number",
+            s
+        )),
+    }
+}
+
+/// Copies of the model shared by the sessions of the servers, each decoding with a copy of its
+/// own, so that as many sessions are decoded concurrently as there are copies. Sessions beyond
+/// that wait for a copy to be returned for up to `timeout`.
+pub struct Pool<M = Model> {
+    idle: Mutex<Vec<Arc<Mutex<M>>>>,
+    returned: Condvar,
+    timeout: Duration,
+}
+
+/// Copy of the model checked out of a pool, which is returned to it once dropped.
+pub struct Checkout<M = Model> {
+    pool: Arc<Pool<M>>,
+    model: Arc<Mutex<M>>,
+}
+
+impl<M> Pool<M> {
+    pub fn new(models: Vec<M>, timeout: Duration) -> Arc<Self> {
+        metrics::POOL_SIZE.store(models.len() as u64, Ordering::Relaxed);
+        metrics::POOL_IN_USE.store(0, Ordering::Relaxed);
+        Arc::new(Self {
+            idle: Mutex::new(
+                models
+                    .into_iter()
+                    .map(|model| Arc::new(Mutex::new(model)))
+                    .collect(),
+            ),
+            returned: Condvar::new(),
+            timeout,
+        })
+    }
+
+    /// Checks out a copy of the model, waiting for one to be returned if all are in use.
+    /// Returns `None`, if none was returned within the timeout of the pool.
+    pub fn checkout(self: &Arc<Self>) -> Option<Checkout<M>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut idle = self.idle.lock().expect("Failed to lock model pool");
+        loop {
+            if let Some(model) = idle.pop() {
+                metrics::POOL_IN_USE.fetch_add(1, Ordering::Relaxed);
+                return Some(Checkout {
+                    pool: self.clone(),
+                    model,
+                });
+            }
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| *remaining > Duration::default())?;
+            idle = self
+                .returned
+                .wait_timeout(idle, remaining)
+                .expect("Failed to lock model pool")
+                .0;
+        }
+    }
+}
+
+impl<M> Checkout<M> {
+    pub fn model(&self) -> Arc<Mutex<M>> {
+        self.model.clone()
+    }
+}
+
+impl<M> Drop for Checkout<M> {
+    fn drop(&mut self) {
+        self.pool
+            .idle
+            .lock()
+            .expect("Failed to lock model pool")
+            .push(self.model.clone());
+        metrics::POOL_IN_USE.fetch_sub(1, Ordering::Relaxed);
+        self.pool.returned.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::AtomicUsize, thread};
+
+    /// Time a mock model takes to decode a session.
+    const DECODE: Duration = Duration::from_millis(200);
+
+    /// Model decoding for `DECODE`, which records how many sessions decode at once.
+    struct Mock {
+        decoding: Arc<AtomicUsize>,
+        max: Arc<AtomicUsize>,
+    }
+
+    impl Mock {
+        fn decode(&mut self) {
+            let decoding = self.decoding.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max.fetch_max(decoding, Ordering::SeqCst);
+            thread::sleep(DECODE);
+            self.decoding.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_size("4"), Ok(4));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("many").is_err());
+    }
+
+    #[test]
+    fn concurrent_sessions() {
+        const SIZE: usize = 4;
+        const SESSIONS: usize = 3 * SIZE;
+        let decoding = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let models = (0..SIZE)
+            .map(|_| Mock {
+                decoding: decoding.clone(),
+                max: max.clone(),
+            })
+            .collect();
+        let pool = Pool::new(models, DECODE * SESSIONS as u32);
+
+        let started = Instant::now();
+        let sessions: Vec<_> = (0..SESSIONS)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    let checkout = pool.checkout().expect("No model became available");
+                    checkout.model().lock().unwrap().decode();
+                })
+            })
+            .collect();
+        for session in sessions {
+            session.join().unwrap();
+        }
+        let elapsed = started.elapsed();
+
+        assert_eq!(max.load(Ordering::SeqCst), SIZE);
+        // Sessions decode in batches of the pool size, rather than one after another.
+        let batches = (SESSIONS / SIZE) as u32;
+        assert!(elapsed >= DECODE * batches, "{:?}", elapsed);
+        assert!(elapsed < DECODE * (batches + 2), "{:?}", elapsed);
+        assert_eq!(pool.idle.lock().unwrap().len(), SIZE);
+    }
+
+    #[test]
+    fn checkout_times_out() {
+        let timeout = Duration::from_millis(50);
+        let pool = Pool::new(vec![()], timeout);
+        let checkout = pool.checkout().unwrap();
+
+        let started = Instant::now();
+        assert!(pool.checkout().is_none());
+        assert!(started.elapsed() >= timeout);
+
+        let returned = thread::spawn(move || {
+            thread::sleep(timeout / 2);
+            drop(checkout);
+        });
+        assert!(pool.checkout().is_some());
+        returned.join().unwrap();
+    }
+}
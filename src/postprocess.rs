@@ -0,0 +1,124 @@
+use crate::output::{Event, Sink, Utterance};
+use crate::segmenter::Segment;
+
+/// Transformation of transcripts applied before they are output.
+pub trait Pass {
+    fn apply(&self, utterance: &mut Utterance);
+}
+
+/// Applies `passes` in order to transcripts before forwarding them to `sink`.
+pub struct PostProcess<S> {
+    pub passes: Vec<Box<dyn Pass>>,
+    pub sink: S,
+}
+
+impl<S: Sink> Sink for PostProcess<S> {
+    fn send(&mut self, event: &Event) {
+        if self.passes.is_empty() {
+            return self.sink.send(event);
+        }
+        let event = match event {
+            Event::Partial(utterance) => Event::Partial(self.process(utterance)),
+            Event::StablePartial(utterance) => Event::StablePartial(self.process(utterance)),
+            Event::Final(utterance) => Event::Final(self.process(utterance)),
+            event => return self.sink.send(event),
+        };
+        self.sink.send(&event)
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        self.sink.samples(samples)
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        self.sink.segment(segment)
+    }
+
+    fn finish(&mut self) {
+        self.sink.finish()
+    }
+}
+
+/// Prefixes transcripts with the label of their speaker, e.g. `Alice: hello`.
+pub struct SpeakerPrefix;
+
+impl Pass for SpeakerPrefix {
+    fn apply(&self, utterance: &mut Utterance) {
+        if let Some(speaker) = &utterance.speaker {
+            utterance.text = format!("{}: {}", speaker, utterance.text);
+        }
+    }
+}
+
+impl<S> PostProcess<S> {
+    fn process(&self, utterance: &Utterance) -> Utterance {
+        let mut utterance = utterance.clone();
+        for pass in &self.passes {
+            pass.apply(&mut utterance)
+        }
+        utterance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    struct Upper;
+
+    impl Pass for Upper {
+        fn apply(&self, utterance: &mut Utterance) {
+            utterance.text = utterance.text.to_uppercase();
+        }
+    }
+
+    struct Exclaim;
+
+    impl Pass for Exclaim {
+        fn apply(&self, utterance: &mut Utterance) {
+            utterance.text.push('!');
+        }
+    }
+
+    /// Collects the texts of the utterances sent.
+    #[derive(Default)]
+    struct Texts(Vec<String>);
+
+    impl Sink for Texts {
+        fn send(&mut self, event: &Event) {
+            match event {
+                Event::Partial(utterance) | Event::Final(utterance) => {
+                    self.0.push(utterance.text.clone())
+                }
+                _ => self.0.push(String::new()),
+            }
+        }
+    }
+
+    #[test]
+    fn passes_in_order() {
+        let mut postprocess = PostProcess {
+            passes: vec![Box::new(Upper), Box::new(Exclaim)],
+            sink: Texts::default(),
+        };
+        postprocess.send(&Event::Partial(Utterance::test("hel")));
+        postprocess.send(&Event::SpeechStopped(Duration::default()));
+        postprocess.send(&Event::Final(Utterance::test("hello")));
+        assert_eq!(postprocess.sink.0, ["HEL!", "", "HELLO!"]);
+    }
+
+    #[test]
+    fn speaker_prefix() {
+        let mut postprocess = PostProcess {
+            passes: vec![Box::new(SpeakerPrefix)],
+            sink: Texts::default(),
+        };
+        postprocess.send(&Event::Final(Utterance {
+            speaker: Some("Alice".into()),
+            ..Utterance::test("hello")
+        }));
+        postprocess.send(&Event::Final(Utterance::test("world")));
+        assert_eq!(postprocess.sink.0, ["Alice: hello", "world"]);
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(all(unix, feature = "capture"))]
+use tracing::debug;
+use tracing::warn;
+
+/// SCHED_FIFO priority of audio threads, which is above the default of 0 of ordinary threads and
+/// within the usual rtprio limit granted to the audio group.
+#[cfg(all(unix, feature = "capture"))]
+const REALTIME_PRIORITY: libc::c_int = 10;
+
+/// Raises the calling thread to realtime SCHED_FIFO scheduling, so that it is not starved of CPU
+/// by the decoder, warning if the process is not permitted to.
+/// On Linux, this requires CAP_SYS_NICE or an rtprio limit of at least 10, e.g.
+/// `@audio - rtprio 95` in /etc/security/limits.conf along with membership of the audio group.
+#[cfg(all(unix, feature = "capture"))]
+pub fn realtime() {
+    let priority = unsafe {
+        REALTIME_PRIORITY
+            .max(libc::sched_get_priority_min(libc::SCHED_FIFO))
+            .min(libc::sched_get_priority_max(libc::SCHED_FIFO))
+    };
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    match unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) } {
+        0 => debug!(priority, "Raised audio thread to realtime priority"),
+        code => warn!(
+            error = %std::io::Error::from_raw_os_error(code),
+            "Failed to raise audio thread to realtime priority, raise the rtprio limit of the user"
+        ),
+    }
+}
+
+#[cfg(all(not(unix), feature = "capture"))]
+pub fn realtime() {
+    warn!("Realtime priority of the audio thread is only supported on Unix");
+}
+
+/// Sets the niceness of the calling thread to `nice`, so that it yields CPU to other threads.
+/// Lowering the niceness below 0 requires CAP_SYS_NICE or a nice limit allowing it.
+#[cfg(target_os = "linux")]
+pub fn nice(nice: i32) {
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } != 0 {
+        warn!(
+            nice,
+            error = %std::io::Error::last_os_error(),
+            "Failed to set niceness of decode worker"
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn nice(nice: i32) {
+    warn!(
+        nice,
+        "Niceness of decode workers is only supported on Linux"
+    );
+}
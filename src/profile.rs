@@ -0,0 +1,313 @@
+use crate::vad::FvadMode;
+use clap::{parser::ValueSource, ArgAction, ArgMatches, Command};
+use serde_json::{json, Map, Value};
+use std::{str::FromStr, time::Duration};
+
+/// Options, whose values are secrets, which are redacted from the effective configuration.
+const SECRETS: &[&str] = &[
+    "admin-token",
+    "mqtt-password",
+    "push-ws-token",
+    "webhook-secret",
+];
+
+/// Bundle of defaults trading latency for accuracy.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Profile {
+    Fast,
+    Balanced,
+    Accurate,
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "fast" => Ok(Self::Fast),
+            "balanced" => Ok(Self::Balanced),
+            "accurate" => Ok(Self::Accurate),
+            _ => Err(format!(
+                "failed to parse `{}` into profile of fast, balanced or accurate",
+                s
+            )),
+        }
+    }
+}
+
+impl Profile {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fast => "fast",
+            Self::Balanced => "balanced",
+            Self::Accurate => "accurate",
+        }
+    }
+
+    pub fn settings(self) -> &'static Settings {
+        &PROFILES
+            .iter()
+            .find(|(profile, _)| *profile == self)
+            .expect("Failed to find profile settings")
+            .1
+    }
+}
+
+/// Values a profile gives the options it covers, unless they are passed explicitly.
+pub struct Settings {
+    /// Beam width of the model, the one of the model itself if `None`.
+    pub beam_width: Option<u16>,
+    pub fvad_mode: Option<FvadMode>,
+    pub silence_padding: Duration,
+    pub partial_interval: Duration,
+    pub trim_decode: bool,
+}
+
+/// Settings of every profile, from the lowest latency to the highest accuracy: the beam widens,
+/// voice activity detection gets less aggressive, paddings get longer and partial transcripts
+/// less frequent.
+pub static PROFILES: [(Profile, Settings); 3] = [
+    (
+        Profile::Fast,
+        Settings {
+            beam_width: Some(100),
+            fvad_mode: Some(FvadMode::VeryAggressive),
+            silence_padding: Duration::from_millis(100),
+            partial_interval: Duration::from_millis(250),
+            trim_decode: true,
+        },
+    ),
+    (
+        Profile::Balanced,
+        Settings {
+            beam_width: Some(250),
+            fvad_mode: Some(FvadMode::Aggressive),
+            silence_padding: Duration::from_millis(200),
+            partial_interval: Duration::from_millis(500),
+            trim_decode: true,
+        },
+    ),
+    (
+        Profile::Accurate,
+        Settings {
+            beam_width: None,
+            fvad_mode: Some(FvadMode::Quality),
+            silence_padding: Duration::from_millis(400),
+            partial_interval: Duration::from_secs(1),
+            trim_decode: false,
+        },
+    ),
+];
+
+/// Effective values of the options along with where they came from: the command line, a profile,
+/// the config file, the environment or their default.
+pub struct Effective {
+    profile: Option<Profile>,
+    values: Map<String, Value>,
+}
+
+impl Effective {
+    pub fn new(profile: Option<Profile>) -> Self {
+        Self {
+            profile,
+            values: Map::new(),
+        }
+    }
+
+    /// Sets `value` of the option `name` to the one `profiled` returns for the profile, unless
+    /// the option was passed `explicit`ly, and records the effective value as shown by `show`.
+    pub fn resolve<T>(
+        &mut self,
+        name: &str,
+        explicit: bool,
+        value: &mut T,
+        profiled: impl FnOnce(&Settings) -> T,
+        show: impl FnOnce(&T) -> Value,
+    ) {
+        let source = match self.profile {
+            _ if explicit => format!("--{}", name),
+            Some(profile) => {
+                *value = profiled(profile.settings());
+                format!("--profile {}", profile.name())
+            }
+            None => "default".into(),
+        };
+        self.values.insert(
+            name.into(),
+            json!({ "value": show(value), "source": source }),
+        );
+    }
+
+    /// Records the values of the options of `command` in `matches`, which were not resolved
+    /// already: the ones passed on the command line and the ones with a default value.
+    pub fn record(&mut self, command: &Command, matches: &ArgMatches) {
+        for arg in command.get_arguments() {
+            let id = arg.get_id().as_str();
+            // Options are shown by their flag, e.g. `type` rather than `type_text`.
+            let name = arg.get_long().unwrap_or(id);
+            if self.values.contains_key(name) {
+                continue;
+            }
+            let source = match matches.value_source(id) {
+                Some(ValueSource::CommandLine) => format!("--{}", name),
+                Some(_) => "default".into(),
+                None => continue,
+            };
+            let value = match arg.get_action() {
+                // Flags, which are set only if passed.
+                ArgAction::SetTrue if source == "default" => continue,
+                ArgAction::SetTrue => json!(true),
+                // Flags, which are counted if repeated, e.g. -vv.
+                ArgAction::Count => match matches.get_count(id) {
+                    0 => continue,
+                    1 => json!(true),
+                    count => json!(count),
+                },
+                _ => {
+                    let values = match matches.get_raw(id) {
+                        Some(values) => values,
+                        None => continue,
+                    };
+                    match values.collect::<Vec<_>>().as_slice() {
+                        [value] => json!(value.to_string_lossy()),
+                        values => values
+                            .iter()
+                            .map(|value| json!(value.to_string_lossy()))
+                            .collect(),
+                    }
+                }
+            };
+            self.set(name, value, source);
+        }
+    }
+
+    /// Records `value` of the option `name`, which came from `source`, redacting secrets.
+    pub fn set(&mut self, name: &str, value: Value, source: impl Into<String>) {
+        let value = if SECRETS.contains(&name) {
+            json!("<redacted>")
+        } else {
+            value
+        };
+        self.values.insert(
+            name.into(),
+            json!({ "value": value, "source": source.into() }),
+        );
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "profile": self.profile.map(Profile::name),
+            "options": self.values,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for (profile, _) in &PROFILES {
+            assert!(profile.name().parse::<Profile>() == Ok(*profile));
+        }
+        assert!("slow".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let mut effective = Effective::new(Some(Profile::Fast));
+        let mut beam_width = Some(500);
+        effective.resolve(
+            "beam-width",
+            true,
+            &mut beam_width,
+            |settings| settings.beam_width,
+            |value| json!(value),
+        );
+        let mut padding = Duration::from_millis(300);
+        effective.resolve(
+            "silence-padding",
+            false,
+            &mut padding,
+            |settings| settings.silence_padding,
+            |value| json!(value.as_millis() as u64),
+        );
+        assert_eq!(beam_width, Some(500));
+        assert_eq!(padding, Duration::from_millis(100));
+        assert_eq!(
+            effective.to_json(),
+            json!({
+                "profile": "fast",
+                "options": {
+                    "beam-width": { "value": 500, "source": "--beam-width" },
+                    "silence-padding": { "value": 100, "source": "--profile fast" },
+                },
+            })
+        );
+
+        let mut defaults = Effective::new(None);
+        let mut padding = Duration::from_millis(300);
+        defaults.resolve(
+            "silence-padding",
+            false,
+            &mut padding,
+            |settings| settings.silence_padding,
+            |value| json!(value.as_millis() as u64),
+        );
+        assert_eq!(padding, Duration::from_millis(300));
+        assert_eq!(
+            defaults.to_json()["options"]["silence-padding"]["source"],
+            "default"
+        );
+    }
+
+    #[test]
+    fn record() {
+        use clap::Arg;
+
+        let command = Command::new("speech2text")
+            .arg(Arg::new("beam_width").long("beam-width"))
+            .arg(
+                Arg::new("caption_count")
+                    .long("caption-count")
+                    .default_value("2"),
+            )
+            .arg(Arg::new("mqtt_password").long("mqtt-password"))
+            .arg(Arg::new("devices").long("device").action(ArgAction::Append))
+            .arg(
+                Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .action(ArgAction::Count),
+            )
+            .arg(Arg::new("debug").long("debug").action(ArgAction::SetTrue));
+        let matches = command.clone().get_matches_from(vec![
+            "speech2text",
+            "--beam-width",
+            "500",
+            "--mqtt-password",
+            "hunter2",
+            "--device",
+            "a",
+            "--device",
+            "b",
+            "-vv",
+            "--debug",
+        ]);
+        let mut effective = Effective::new(None);
+        effective.set("beam-width", json!(1024), "--profile accurate");
+        effective.record(&command, &matches);
+        assert_eq!(
+            effective.to_json()["options"],
+            json!({
+                "beam-width": { "value": 1024, "source": "--profile accurate" },
+                "caption-count": { "value": "2", "source": "default" },
+                "mqtt-password": { "value": "<redacted>", "source": "--mqtt-password" },
+                "device": { "value": ["a", "b"], "source": "--device" },
+                "verbose": { "value": 2, "source": "--verbose" },
+                "debug": { "value": true, "source": "--debug" },
+            })
+        );
+    }
+}
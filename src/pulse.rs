@@ -0,0 +1,141 @@
+use crate::capture;
+use crate::clock::Anchor;
+use crate::error::Error;
+use libpulse_binding::{
+    callbacks::ListResult,
+    context::{self, Context},
+    def::BufferAttr,
+    mainloop::standard::{IterateResult, Mainloop},
+    operation,
+    sample::{Format, Spec},
+    stream::Direction,
+};
+use libpulse_simple_binding::Simple;
+use std::{cell::RefCell, rc::Rc, thread, time::Duration};
+use tracing::{error, info};
+
+/// Name of the application reported to the server.
+const APPLICATION_NAME: &str = "speech2text";
+
+/// Duration of audio read from the server at once, which bounds the latency added by the server.
+const FRAGMENT_DURATION: Duration = Duration::from_millis(20);
+
+/// Records from the source named `name`, which may be a monitor of a sink, returning the
+/// recorded samples.
+/// The server converts the audio to mono i16 at `sample_rate`, so that no conversion is needed.
+/// `anchor` is set to the capture time of the first sample.
+pub fn capture(
+    name: &str,
+    sample_rate: u32,
+    anchor: Anchor,
+) -> Result<Box<dyn Iterator<Item = i16>>, Error> {
+    let spec = Spec {
+        format: Format::S16NE,
+        channels: 1,
+        rate: sample_rate,
+    };
+    let fragment = (FRAGMENT_DURATION.as_millis() as u64 * sample_rate as u64 / 1000) as usize;
+    // Only the fragment size applies to recording streams, the server defaults the others.
+    let attr = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: u32::MAX,
+        prebuf: u32::MAX,
+        minreq: u32::MAX,
+        fragsize: (fragment * 2) as u32,
+    };
+    let simple = Simple::new(
+        None,
+        APPLICATION_NAME,
+        Direction::Record,
+        Some(name),
+        "speech recognition",
+        &spec,
+        None,
+        Some(&attr),
+    )
+    .map_err(|err| {
+        Error::Device(format!(
+            "Failed to record from PulseAudio source `{}`: {}",
+            name, err
+        ))
+    })?;
+    info!(
+        source = name,
+        sample_rate, "Recording from PulseAudio source"
+    );
+
+    let (mut writer, reader) = capture::buffer(sample_rate, anchor);
+    let name = name.to_string();
+    thread::spawn(move || {
+        let mut bytes = vec![0; fragment * 2];
+        let mut samples = vec![0; fragment];
+        loop {
+            if let Err(err) = simple.read(&mut bytes) {
+                error!(source = %name, error = %err, "Failed to read from PulseAudio source");
+                return;
+            }
+            for (sample, bytes) in samples.iter_mut().zip(bytes.chunks_exact(2)) {
+                *sample = i16::from_ne_bytes([bytes[0], bytes[1]]);
+            }
+            writer.write(&samples);
+        }
+    });
+    Ok(Box::new(reader))
+}
+
+/// Prints the names of the sources of the server, including monitors of sinks, along with their
+/// descriptions.
+pub fn list() -> Result<(), Error> {
+    let mut mainloop = Mainloop::new()
+        .ok_or_else(|| Error::Device("Failed to create PulseAudio main loop".into()))?;
+    let mut context = Context::new(&mainloop, APPLICATION_NAME)
+        .ok_or_else(|| Error::Device("Failed to create PulseAudio context".into()))?;
+    context
+        .connect(None, context::FlagSet::NOFLAGS, None)
+        .map_err(Error::with(
+            Error::Device,
+            "Failed to connect to PulseAudio",
+        ))?;
+    loop {
+        iterate(&mut mainloop)?;
+        match context.get_state() {
+            context::State::Ready => break,
+            context::State::Failed | context::State::Terminated => {
+                return Err(Error::Device("Failed to connect to PulseAudio".into()))
+            }
+            _ => {}
+        }
+    }
+
+    let sources = Rc::new(RefCell::new(Vec::new()));
+    let listing = context.introspect().get_source_info_list({
+        let sources = sources.clone();
+        move |result| {
+            if let ListResult::Item(info) = result {
+                sources.borrow_mut().push((
+                    info.name.as_deref().unwrap_or_default().to_string(),
+                    info.description.as_deref().unwrap_or_default().to_string(),
+                ));
+            }
+        }
+    });
+    while listing.get_state() == operation::State::Running {
+        iterate(&mut mainloop)?;
+    }
+    context.disconnect();
+    for (name, description) in sources.borrow().iter() {
+        println!("pulse: {} ({})", name, description);
+    }
+    Ok(())
+}
+
+fn iterate(mainloop: &mut Mainloop) -> Result<(), Error> {
+    match mainloop.iterate(true) {
+        IterateResult::Success(_) => Ok(()),
+        IterateResult::Quit(_) => Err(Error::Device("PulseAudio main loop quit".into())),
+        IterateResult::Err(err) => Err(Error::Device(format!(
+            "Failed to iterate PulseAudio main loop: {}",
+            err
+        ))),
+    }
+}
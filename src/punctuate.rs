@@ -0,0 +1,269 @@
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+use std::{cell::RefCell, str::FromStr, time::Duration};
+
+/// Words, which questions start with, if the transcript starts with them.
+const QUESTION_WORDS: &[&str] = &[
+    "am",
+    "are",
+    "aren't",
+    "can",
+    "can't",
+    "could",
+    "couldn't",
+    "did",
+    "didn't",
+    "do",
+    "does",
+    "doesn't",
+    "don't",
+    "had",
+    "has",
+    "have",
+    "how",
+    "is",
+    "isn't",
+    "may",
+    "might",
+    "shall",
+    "should",
+    "shouldn't",
+    "was",
+    "wasn't",
+    "were",
+    "weren't",
+    "what",
+    "when",
+    "where",
+    "which",
+    "who",
+    "whom",
+    "whose",
+    "why",
+    "will",
+    "won't",
+    "would",
+    "wouldn't",
+];
+
+/// Pause between transcripts, below which a sentence cut off continues in the next one.
+const SENTENCE_GAP: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Backend {
+    /// Punctuation guessed from the pauses between utterances and their first words.
+    Heuristic,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "heuristic" => Ok(Self::Heuristic),
+            _ => Err(format!(
+                "failed to parse `{}` into punctuation backend of heuristic",
+                s
+            )),
+        }
+    }
+}
+
+/// Where a transcript is within the sentence it belongs to.
+pub struct Context<'a> {
+    /// Words of the sentence transcribed before, if the transcript continues it.
+    pub sentence: Option<&'a str>,
+    /// Whether the speech of the transcript is complete, rather than in progress.
+    pub complete: bool,
+    /// Whether the transcript was cut off while speech continued, so that the sentence continues
+    /// in the next one.
+    pub cut: bool,
+}
+
+/// Restores the punctuation of transcripts, which models like DeepSpeech do not produce.
+pub trait Punctuator {
+    fn punctuate(&self, text: &str, context: &Context<'_>) -> String;
+}
+
+/// Punctuates transcripts as sentences, which end with a question mark if they start with a
+/// question word or a period otherwise, unless they continue in the next transcript, in which
+/// case they end with a comma. Sentences start with a capital letter.
+pub struct Heuristic;
+
+impl Punctuator for Heuristic {
+    fn punctuate(&self, text: &str, context: &Context<'_>) -> String {
+        let text = text.trim();
+        if text.is_empty() {
+            return String::new();
+        }
+        let first = context
+            .sentence
+            .unwrap_or(text)
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+        let mut punctuated = match context.sentence {
+            Some(_) => text.to_string(),
+            None => {
+                let mut chars = text.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+        };
+        if !context.complete {
+            return punctuated;
+        }
+        punctuated.push(if context.cut {
+            ','
+        } else if QUESTION_WORDS.contains(&first.to_lowercase().as_str()) {
+            '?'
+        } else {
+            '.'
+        });
+        punctuated
+    }
+}
+
+/// Final transcript output before, which later ones may continue the sentence of.
+struct Previous {
+    end: Duration,
+    /// Words of the sentence in progress, if the transcript was cut off before it ended.
+    sentence: Option<String>,
+}
+
+/// Restores the punctuation of transcripts with `punctuator`, telling it where they are within
+/// their sentence. Final transcripts of speech lasting `split_length` or longer are taken to be
+/// cut off while speech continued, in which case the sentence continues in the next transcript,
+/// if it starts less than `SENTENCE_GAP` after it.
+pub struct Punctuate<P> {
+    punctuator: P,
+    split_length: Duration,
+    previous: RefCell<Option<Previous>>,
+}
+
+impl<P> Punctuate<P> {
+    pub fn new(punctuator: P, split_length: Duration) -> Self {
+        Self {
+            punctuator,
+            split_length,
+            previous: RefCell::new(None),
+        }
+    }
+}
+
+impl<P: Punctuator> Pass for Punctuate<P> {
+    fn apply(&self, utterance: &mut Utterance) {
+        let mut previous = self.previous.borrow_mut();
+        let sentence = previous
+            .as_ref()
+            .filter(|previous| {
+                utterance
+                    .start
+                    .checked_sub(previous.end)
+                    .is_some_and(|gap| gap < SENTENCE_GAP)
+            })
+            .and_then(|previous| previous.sentence.clone());
+        // Only final transcripts carry the offsets of the audio decoded.
+        let complete = utterance.offsets.is_some();
+        let cut = complete
+            && utterance
+                .end
+                .checked_sub(utterance.start)
+                .is_some_and(|speech| speech >= self.split_length);
+        let context = Context {
+            sentence: sentence.as_deref(),
+            complete,
+            cut,
+        };
+        let text = self.punctuator.punctuate(&utterance.text, &context);
+        if complete {
+            *previous = Some(Previous {
+                end: utterance.end,
+                sentence: if cut {
+                    Some(match sentence {
+                        Some(sentence) => format!("{} {}", sentence, utterance.text),
+                        None => utterance.text.clone(),
+                    })
+                } else {
+                    None
+                },
+            });
+        }
+        utterance.text = text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Offsets;
+
+    /// Returns a final utterance transcribed as `text` from `start_ms` to `end_ms`.
+    fn utterance(start_ms: u64, end_ms: u64, text: &str) -> Utterance {
+        let mut utterance = Utterance::test(text);
+        utterance.start = Duration::from_millis(start_ms);
+        utterance.end = Duration::from_millis(end_ms);
+        utterance.offsets = Some(Offsets {
+            buffer_start: 0,
+            speech_start: 0,
+            speech_end: 0,
+            buffer_end: 0,
+        });
+        utterance
+    }
+
+    #[test]
+    fn transcripts() {
+        let pass = Punctuate::new(Heuristic, Duration::from_secs(10));
+        for (start, end, text, expected) in [
+            (0, 2000, "what time is it", "What time is it?"),
+            (3000, 5000, "it is noon", "It is noon."),
+            (
+                5000,
+                15000,
+                "so i was thinking that we",
+                "So i was thinking that we,",
+            ),
+            (15200, 17000, "could go out", "could go out."),
+            (20000, 30000, "why did", "Why did,"),
+            (30100, 31000, "you go", "you go?"),
+            // The pause is too long for the sentence to continue.
+            (40000, 50000, "and then", "And then,"),
+            (51000, 52000, "nothing", "Nothing."),
+            (53000, 54000, "  ", ""),
+            (55000, 56000, "éclair", "Éclair."),
+        ] {
+            let mut utterance = utterance(start, end, text);
+            pass.apply(&mut utterance);
+            assert_eq!(utterance.text, expected);
+        }
+    }
+
+    #[test]
+    fn partial() {
+        let pass = Punctuate::new(Heuristic, Duration::from_secs(10));
+        let mut partial = Utterance::test("is it noon");
+        pass.apply(&mut partial);
+        assert_eq!(partial.text, "Is it noon");
+
+        // Partial transcripts continue sentences, but are not continued by final ones.
+        let mut cut = utterance(0, 10000, "so we");
+        pass.apply(&mut cut);
+        assert_eq!(cut.text, "So we,");
+        let mut partial = Utterance::test("could");
+        partial.start = Duration::from_millis(10100);
+        pass.apply(&mut partial);
+        assert_eq!(partial.text, "could");
+        let mut continued = utterance(10100, 11000, "could go");
+        pass.apply(&mut continued);
+        assert_eq!(continued.text, "could go.");
+    }
+
+    #[test]
+    fn parse_backend() {
+        assert!(matches!("heuristic".parse(), Ok(Backend::Heuristic)));
+        assert!("onnx".parse::<Backend>().is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::output::{Event, Sink};
+use crate::queue::Queue;
+use std::{sync::Arc, thread, time::Duration};
+use tracing::{info, warn};
+use tungstenite::{client::IntoClientRequest, handshake::client::Request, http::HeaderValue};
+
+/// Maximum number of transcripts buffered while disconnected.
+const QUEUE_CAPACITY: usize = 128;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+fn request(url: &str, token: Option<&str>) -> Result<Request, String> {
+    let mut request = url
+        .into_client_request()
+        .map_err(|err| format!("invalid WebSocket URL `{}`: {}", url, err))?;
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|err| format!("invalid bearer token: {}", err))?;
+        request.headers_mut().insert("Authorization", value);
+    }
+    Ok(request)
+}
+
+/// Pushes final transcripts and heartbeats as JSON text frames to a WebSocket endpoint.
+/// The connection is maintained on a separate thread, which reconnects with exponential backoff and
+/// buffers a bounded number of transcripts while disconnected.
+pub struct Pusher {
+    queue: Arc<Queue<String>>,
+}
+
+impl Pusher {
+    pub fn connect(url: String, token: Option<String>) -> Result<Self, String> {
+        // Validate the request once upfront, so that misconfiguration is reported at startup.
+        request(&url, token.as_deref())?;
+
+        let queue = Arc::new(Queue::<String>::new(QUEUE_CAPACITY));
+        let messages = queue.clone();
+        thread::spawn(move || {
+            let mut backoff = MIN_BACKOFF;
+            loop {
+                let req = request(&url, token.as_deref()).expect("Failed to build request");
+                let mut socket = match tungstenite::connect(req) {
+                    Ok((socket, _)) => socket,
+                    Err(err) => {
+                        warn!(%url, error = %err, ?backoff, "Failed to connect to WebSocket, retrying");
+                        thread::sleep(backoff);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                        continue;
+                    }
+                };
+                info!(%url, "Connected to WebSocket");
+                backoff = MIN_BACKOFF;
+                while let Some(message) = messages.pop() {
+                    if let Err(err) =
+                        socket.write_message(tungstenite::Message::Text(message.clone()))
+                    {
+                        warn!(%url, error = %err, "WebSocket connection lost, reconnecting");
+                        messages.unpop(message);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for Pusher {
+    fn send(&mut self, event: &Event) {
+        let message = match event {
+            Event::Final(utterance) => utterance.to_json(),
+            Event::Heartbeat(heartbeat) => heartbeat.to_json(),
+            _ => return,
+        };
+        if self.queue.push(message.to_string()).is_some() {
+            warn!("WebSocket queue is full, dropping oldest message");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::net::TcpListener;
+
+    #[test]
+    fn bearer_token() {
+        let authorized = request("ws://localhost:9000/transcripts", Some("secret")).unwrap();
+        assert_eq!(authorized.uri().path(), "/transcripts");
+        assert_eq!(authorized.headers()["Authorization"], "Bearer secret");
+        let anonymous = request("ws://localhost:9000/", None).unwrap();
+        assert!(!anonymous.headers().contains_key("Authorization"));
+        assert!(request("ws://[::1/", None).is_err());
+        assert!(request("ws://localhost:9000/", Some("new\nline")).is_err());
+    }
+
+    #[test]
+    fn push() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/", listener.local_addr().unwrap());
+        let mut pusher = Pusher::connect(url, None).unwrap();
+        pusher.send(&Event::Final(Utterance {
+            confidence: 0.5,
+            start: Duration::from_secs(1),
+            end: Duration::from_secs(2),
+            ..Utterance::test("hello")
+        }));
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        let message = socket.read_message().unwrap();
+        let json: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(json["text"], "hello");
+        assert_eq!(json["end_ms"], 2000);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+};
+#[cfg(feature = "history")]
+use std::{
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+struct State<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+/// Bounded blocking queue.
+pub struct Queue<T> {
+    capacity: usize,
+    state: Mutex<State<T>>,
+    ready: Condvar,
+    space: Condvar,
+}
+
+impl<T> Queue<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(State {
+                items: VecDeque::with_capacity(capacity),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+            space: Condvar::new(),
+        }
+    }
+
+    /// Returns the number of queued items.
+    pub fn len(&self) -> usize {
+        self.state.lock().expect("Failed to lock queue").items.len()
+    }
+
+    /// Appends `item` without blocking, returning the oldest item if it had to be dropped to make
+    /// room.
+    pub fn push(&self, item: T) -> Option<T> {
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        let dropped = if state.items.len() >= self.capacity {
+            state.items.pop_front()
+        } else {
+            None
+        };
+        state.items.push_back(item);
+        self.ready.notify_one();
+        dropped
+    }
+
+    /// Appends `item` without blocking, returning it if the queue is full.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        if state.items.len() >= self.capacity {
+            return Err(item);
+        }
+        state.items.push_back(item);
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    /// Appends `item`, blocking while the queue is full.
+    pub fn push_wait(&self, item: T) {
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        while state.items.len() >= self.capacity {
+            state = self.space.wait(state).expect("Failed to wait for queue");
+        }
+        state.items.push_back(item);
+        self.ready.notify_one();
+    }
+
+    /// Returns `item` to the front of the queue, unless the queue filled up in the meantime.
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub fn unpop(&self, item: T) {
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        if state.items.len() < self.capacity {
+            state.items.push_front(item);
+            self.ready.notify_one();
+        }
+    }
+
+    /// Removes the oldest item, blocking until one is available.
+    /// Returns `None` once the queue is closed and empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                self.space.notify_one();
+                return Some(item);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.ready.wait(state).expect("Failed to wait for queue");
+        }
+    }
+
+    /// Removes the oldest item, blocking until one is available or `timeout` elapsed.
+    /// Fails with `Disconnected` once the queue is closed and empty.
+    #[cfg(feature = "history")]
+    pub fn pop_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                self.space.notify_one();
+                return Ok(item);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| *remaining > Duration::default())
+                .ok_or(RecvTimeoutError::Timeout)?;
+            state = self
+                .ready
+                .wait_timeout(state, remaining)
+                .expect("Failed to wait for queue")
+                .0;
+        }
+    }
+
+    /// Removes all queued items, returning them oldest first.
+    pub fn clear(&self) -> Vec<T> {
+        let mut state = self.state.lock().expect("Failed to lock queue");
+        let items = state.items.drain(..).collect();
+        self.space.notify_all();
+        items
+    }
+
+    /// Wakes up consumers, which receive the remaining items followed by `None`.
+    pub fn close(&self) {
+        self.state.lock().expect("Failed to lock queue").closed = true;
+        self.ready.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread, time::Duration};
+
+    #[test]
+    fn drop_oldest() {
+        let queue = Queue::new(2);
+        assert_eq!(queue.push(1), None);
+        assert_eq!(queue.push(2), None);
+        assert_eq!(queue.push(3), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+        queue.unpop(2);
+        assert_eq!(queue.push(4), Some(2));
+        // Unpopping into a full queue keeps the newer items.
+        queue.unpop(2);
+        assert_eq!((queue.pop(), queue.pop()), (Some(3), Some(4)));
+    }
+
+    #[test]
+    fn pop_blocks() {
+        let queue = Arc::new(Queue::new(1));
+        let pushing = queue.clone();
+        let pusher = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            pushing.push("ready")
+        });
+        assert_eq!(queue.pop(), Some("ready"));
+        assert_eq!(pusher.join().unwrap(), None);
+    }
+
+    #[test]
+    fn push_wait_blocks() {
+        let queue = Arc::new(Queue::new(1));
+        queue.push_wait(1);
+        let pushing = queue.clone();
+        let pusher = thread::spawn(move || pushing.push_wait(2));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!pusher.is_finished());
+        assert_eq!(queue.pop(), Some(1));
+        pusher.join().unwrap();
+        assert_eq!(queue.pop(), Some(2));
+    }
+
+    #[test]
+    fn close() {
+        let queue = Arc::new(Queue::new(2));
+        queue.push(1);
+        let popping = queue.clone();
+        let popper = thread::spawn(move || (popping.pop(), popping.pop()));
+        thread::sleep(Duration::from_millis(50));
+        queue.close();
+        assert_eq!(popper.join().unwrap(), (Some(1), None));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn pop_timeout() {
+        let queue = Queue::new(2);
+        let timeout = Duration::from_millis(50);
+        let started = Instant::now();
+        assert_eq!(queue.pop_timeout(timeout), Err(RecvTimeoutError::Timeout));
+        assert!(started.elapsed() >= timeout);
+
+        queue.push(1);
+        assert_eq!(queue.pop_timeout(timeout), Ok(1));
+        queue.push(2);
+        queue.close();
+        assert_eq!(queue.pop_timeout(timeout), Ok(2));
+        assert_eq!(
+            queue.pop_timeout(timeout),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::dither::Dither;
+use crate::error::Error;
+use std::{convert::TryInto, fs, path::Path, str::FromStr};
+use tracing::warn;
+
+/// Encoding of the samples of a headerless file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    S16le,
+    S16be,
+    F32le,
+    U8,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "s16le" => Ok(Self::S16le),
+            "s16be" => Ok(Self::S16be),
+            "f32le" => Ok(Self::F32le),
+            "u8" => Ok(Self::U8),
+            _ => Err(format!(
+                "failed to parse `{}` into sample format of s16le, s16be, f32le or u8",
+                s
+            )),
+        }
+    }
+}
+
+impl Encoding {
+    /// Returns the number of bytes of a sample.
+    fn width(self) -> usize {
+        match self {
+            Self::S16le | Self::S16be => 2,
+            Self::F32le => 4,
+            Self::U8 => 1,
+        }
+    }
+
+    /// Returns the sample encoded in `bytes`, which are `width` long, in `-1.0..=1.0`.
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::S16le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            Self::S16be => i16::from_be_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            Self::F32le => f32::from_le_bytes(bytes.try_into().expect("Failed to decode sample")),
+            Self::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+        }
+    }
+}
+
+/// Description of a headerless file of interleaved samples, e.g.
+/// `rate=16000,channels=1,format=s16le`. Channels default to 1 and the format to s16le.
+#[derive(Clone, Copy, Debug)]
+pub struct Format {
+    pub rate: u32,
+    pub channels: usize,
+    pub encoding: Encoding,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let mut rate = None;
+        let mut channels = 1;
+        let mut encoding = Encoding::S16le;
+        for field in s
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+        {
+            let (key, value) = match field.find('=') {
+                Some(i) => (&field[..i], &field[i + 1..]),
+                None => {
+                    return Err(format!(
+                        "failed to parse `{}` into raw format, expected key=value pairs like rate=16000,channels=1,format=s16le",
+                        s
+                    ))
+                }
+            };
+            match key {
+                "rate" => match value.parse() {
+                    Ok(value) if value > 0 => rate = Some(value),
+                    _ => {
+                        return Err(format!(
+                            "failed to parse `{}` into sample rate of raw format",
+                            value
+                        ))
+                    }
+                },
+                "channels" => match value.parse() {
+                    Ok(value) if value > 0 => channels = value,
+                    _ => {
+                        return Err(format!(
+                            "failed to parse `{}` into channel count of raw format",
+                            value
+                        ))
+                    }
+                },
+                "format" => encoding = value.parse()?,
+                _ => {
+                    return Err(format!(
+                        "unknown key `{}` of raw format, expected rate, channels or format",
+                        key
+                    ))
+                }
+            }
+        }
+        let rate = rate.ok_or_else(|| {
+            format!(
+                "failed to parse `{}` into raw format, rate is required, e.g. rate=16000",
+                s
+            )
+        })?;
+        Ok(Self {
+            rate,
+            channels,
+            encoding,
+        })
+    }
+}
+
+impl Format {
+    /// Returns the number of bytes of a frame of the samples of all channels.
+    pub fn frame_width(&self) -> usize {
+        self.encoding.width() * self.channels
+    }
+
+    /// Returns the samples of the whole frames of `bytes` downmixed to mono, quantizing them
+    /// with `dither`.
+    pub fn decode(&self, bytes: &[u8], dither: &mut Dither) -> Vec<i16> {
+        let width = self.encoding.width();
+        bytes
+            .chunks_exact(self.frame_width())
+            .map(|frame| {
+                let sum: f32 = frame
+                    .chunks_exact(width)
+                    .map(|sample| self.encoding.decode(sample))
+                    .sum();
+                dither.quantize(sum / self.channels as f32)
+            })
+            .collect()
+    }
+}
+
+/// Reads the headerless file at `path` described by `format`, returning its samples downmixed to
+/// mono at `format.rate`. Float samples are dithered if `dither` is set.
+/// Bytes of a trailing partial frame are dropped with a warning, since they usually indicate that
+/// the format does not match the file.
+pub fn read(path: &Path, format: Format, dither: bool) -> Result<Vec<i16>, Error> {
+    let bytes = fs::read(path).map_err(Error::with(Error::Input, "Failed to read input file"))?;
+    let frame = format.frame_width();
+    let trailing = bytes.len() % frame;
+    if trailing > 0 {
+        warn!(
+            path = %path.display(),
+            bytes = trailing,
+            frame,
+            "Dropping trailing partial sample of raw input file, check that --raw-format matches it"
+        );
+    }
+    let mut dither = Dither::new(dither && format.encoding == Encoding::F32le);
+    Ok(format.decode(&bytes, &mut dither))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let format = Format::from_str("rate=8000").unwrap();
+        assert_eq!(
+            (format.rate, format.channels, format.encoding),
+            (8000, 1, Encoding::S16le)
+        );
+        let format = Format::from_str(" format=f32le, channels=2 ,rate=48000,").unwrap();
+        assert_eq!(
+            (format.rate, format.channels, format.encoding),
+            (48000, 2, Encoding::F32le)
+        );
+        for s in &[
+            "",
+            "channels=2",
+            "rate=0",
+            "rate=fast",
+            "rate=16000,channels=0",
+            "rate=16000,format=s24le",
+            "rate=16000,bits=16",
+            "rate",
+        ] {
+            assert!(Format::from_str(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn read_downmixed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.raw");
+        let format = Format {
+            rate: 16000,
+            channels: 2,
+            encoding: Encoding::S16le,
+        };
+        let frames: &[[i16; 2]] = &[[100, 300], [-2, -4], [i16::MIN, i16::MIN]];
+        let mut bytes: Vec<u8> = frames
+            .iter()
+            .flatten()
+            .flat_map(|sample| sample.to_le_bytes().to_vec())
+            .collect();
+        // Trailing partial frame.
+        bytes.extend(&[1, 2, 3]);
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(read(&path, format, false).unwrap(), [200, -3, i16::MIN]);
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(Encoding::S16le.decode(&[0x00, 0x40]), 0.5);
+        assert_eq!(Encoding::S16be.decode(&[0x40, 0x00]), 0.5);
+        assert_eq!(Encoding::F32le.decode(&(-0.25f32).to_le_bytes()), -0.25);
+        assert_eq!(Encoding::U8.decode(&[128]), 0.0);
+        assert_eq!(Encoding::U8.decode(&[0]), -1.0);
+    }
+}
@@ -0,0 +1,873 @@
+use crate::clock;
+use crate::metrics;
+use crate::output::{Event, Sink};
+use crate::paths;
+use crate::segmenter::Segment;
+use crate::template::{Rolling, Template};
+use std::{
+    fs, io, iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::Ordering,
+    time::SystemTime,
+};
+use tracing::{error, warn};
+
+/// File format of recordings.
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Wav,
+    /// Lossless compression, which takes about half the space of WAV for speech.
+    #[cfg(feature = "flac")]
+    Flac,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "wav" => Ok(Self::Wav),
+            #[cfg(feature = "flac")]
+            "flac" => Ok(Self::Flac),
+            _ => Err(format!(
+                "failed to parse `{}` into recording format of wav{}",
+                s,
+                if cfg!(feature = "flac") {
+                    " or flac"
+                } else {
+                    ", flac requires building with the `flac` feature"
+                }
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Extensions of recordings in any format, which are subject to the quota.
+    pub const EXTENSIONS: [&'static str; 2] = ["wav", "flac"];
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            #[cfg(feature = "flac")]
+            Self::Flac => "flac",
+        }
+    }
+}
+
+/// How recordings of segments are named.
+#[derive(Clone, Copy, Debug)]
+pub enum Naming {
+    /// `<session start>_<seq>`, where the zero-padded sequence number is the one of the
+    /// utterance in structured output, so that recordings sort in order and pair with transcripts.
+    Sequential,
+    /// `recording<nanoseconds since the Unix epoch>`.
+    Timestamp,
+}
+
+impl FromStr for Naming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "sequential" => Ok(Self::Sequential),
+            "timestamp" => Ok(Self::Timestamp),
+            _ => Err(format!(
+                "failed to parse `{}` into recording naming of sequential or timestamp",
+                s
+            )),
+        }
+    }
+}
+
+/// Infix of recordings of segments, which are yet to be numbered by their utterance.
+const UNNUMBERED: &str = "_unnumbered-";
+
+/// Returns a unique recording file name starting with `prefix`, without an extension.
+pub fn timestamped_name(prefix: &str) -> String {
+    format!(
+        "{}{}",
+        prefix,
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("SystemTime before UNIX EPOCH!")
+            .as_nanos()
+    )
+}
+
+/// Limits on the recordings kept in a directory, shared by all recording sinks.
+#[derive(Clone, Default)]
+pub struct Quota {
+    pub max_files: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+/// Saves recordings to a directory, deleting the oldest ones to stay within the quota.
+#[derive(Clone)]
+pub struct Recorder {
+    dir: PathBuf,
+    quota: Quota,
+    format: Format,
+    naming: Naming,
+    /// Local start time of the session, which prefixes sequentially named recordings.
+    session: String,
+    /// Highest sequence number of recordings of the session already in the directory, which
+    /// sequence numbers of utterances are offset by, so that none are overwritten.
+    offset: u64,
+}
+
+impl Recorder {
+    pub fn new(dir: PathBuf, quota: Quota, format: Format, naming: Naming) -> Self {
+        let start = clock::local(SystemTime::now());
+        let session = format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            start.year, start.month, start.day, start.hour, start.minute, start.second
+        );
+        let offset = match naming {
+            Naming::Sequential => last_seq(&dir, &session),
+            Naming::Timestamp => 0,
+        };
+        Self {
+            dir,
+            quota,
+            format,
+            naming,
+            session,
+            offset,
+        }
+    }
+
+    /// Saves the recording of a segment. If named sequentially, it is named after its utterance
+    /// with `number` once the utterance is output.
+    pub fn save_segment(&self, spec: hound::WavSpec, samples: &[i16]) -> Option<PathBuf> {
+        let name = match self.naming {
+            Naming::Sequential => timestamped_name(&format!("{}{}", self.session, UNNUMBERED)),
+            Naming::Timestamp => timestamped_name("recording"),
+        };
+        self.save(&name, spec, samples)
+    }
+
+    /// Renames the recording of a segment at `path` after the sequence number of its utterance,
+    /// if named sequentially, returning its new path.
+    /// Failures are logged, keeping the recording at `path`.
+    pub fn number(&self, path: &Path, seq: u64) -> PathBuf {
+        if let Naming::Timestamp = self.naming {
+            return path.into();
+        }
+        let numbered = path.with_file_name(format!(
+            "{}_{:06}.{}",
+            self.session,
+            self.offset + seq,
+            self.format.extension()
+        ));
+        match fs::rename(path, &numbered) {
+            Ok(()) => numbered,
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "Failed to number recording");
+                path.into()
+            }
+        }
+    }
+
+    /// Writes `samples` to `name` with the extension of the format in the recordings directory,
+    /// returning the path on success.
+    /// Failures are logged.
+    pub fn save(&self, name: &str, spec: hound::WavSpec, samples: &[i16]) -> Option<PathBuf> {
+        if metrics::RECORDING_SUSPENDED.load(Ordering::Relaxed) {
+            return None;
+        }
+        // Compressed recordings are smaller, so that the size of a WAV file is an upper bound.
+        let size = 44 + samples.len() as u64 * (spec.bits_per_sample as u64 / 8);
+        if let Err(err) = self.enforce_quota(size) {
+            warn!(dir = %self.dir.display(), error = %err, "Failed to enforce recordings quota");
+        }
+        let path = self
+            .dir
+            .join(format!("{}.{}", name, self.format.extension()));
+        match write(&path, spec, self.format, samples) {
+            Ok(()) => Some(path),
+            Err(err) => {
+                error!(path = %path.display(), error = %err, "Failed to write recording");
+                None
+            }
+        }
+    }
+
+    /// Writes `metadata` next to the recording at `path` with the extension replaced by `json`, so
+    /// that the recordings directory describes itself.
+    /// Failures are logged.
+    pub fn annotate(&self, path: &Path, metadata: &serde_json::Value) {
+        let path = path.with_extension("json");
+        if let Err(err) = fs::write(&path, format!("{:#}\n", metadata)) {
+            error!(path = %path.display(), error = %err, "Failed to write recording metadata");
+        }
+    }
+
+    /// Deletes the oldest recordings until a new one of `size` bytes fits within the quota.
+    fn enforce_quota(&self, size: u64) -> io::Result<()> {
+        if self.quota.max_files.is_none() && self.quota.max_bytes.is_none() {
+            return Ok(());
+        }
+        let mut recordings = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path
+                .extension()
+                .is_none_or(|ext| !Format::EXTENSIONS.iter().any(|extension| ext == *extension))
+            {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            recordings.push((modified, path, meta.len()));
+        }
+        recordings.sort();
+
+        let mut count = recordings.len();
+        let mut total: u64 = recordings.iter().map(|(_, _, len)| len).sum();
+        for (_, path, len) in recordings {
+            let files_ok = self.quota.max_files.is_none_or(|max| count < max);
+            let bytes_ok = self.quota.max_bytes.is_none_or(|max| total + size <= max);
+            if files_ok && bytes_ok {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    count -= 1;
+                    total -= len;
+                    // The sidecar is missing for recordings, which were not transcribed.
+                    let _ = fs::remove_file(path.with_extension("json"));
+                }
+                Err(err) => {
+                    warn!(path = %path.display(), error = %err, "Failed to delete old recording")
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the highest sequence number of recordings of `session` in `dir`, or 0 if there are
+/// none, e.g. because the directory is resumed into within the same second.
+fn last_seq(dir: &Path, session: &str) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!(dir = %dir.display(), error = %err, "Failed to list recordings");
+            return 0;
+        }
+    };
+    let prefix = format!("{}_", session);
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let ext = path.extension()?;
+            if !Format::EXTENSIONS.iter().any(|extension| ext == *extension) {
+                return None;
+            }
+            path.file_stem()?
+                .to_str()?
+                .strip_prefix(&prefix)?
+                .parse::<u64>()
+                .ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the default recordings directory, `$XDG_DATA_HOME/speech2text/recordings`, falling
+/// back to `~/.local/share/speech2text/recordings` and `./recordings`.
+pub fn default_dir() -> PathBuf {
+    paths::data_dir("recordings")
+}
+
+/// Creates the recordings directory if missing and checks that it is writable.
+pub fn prepare_dir(dir: Option<PathBuf>) -> Result<PathBuf, String> {
+    let dir = dir.unwrap_or_else(default_dir);
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to create {}: {}", dir.display(), err))?;
+    probe(&dir)?;
+    Ok(dir)
+}
+
+/// Checks that `dir` is writable, or that it could be created if missing, without creating it.
+pub fn check_dir(dir: &Path) -> Result<(), String> {
+    match dir.ancestors().find(|dir| dir.exists()) {
+        Some(existing) if existing.is_dir() => probe(existing),
+        Some(existing) => Err(format!("{} is not a directory", existing.display())),
+        None => Err(format!("no ancestor of {} exists", dir.display())),
+    }
+}
+
+/// Checks that a file can be written to `dir`, removing it again.
+fn probe(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(".speech2text-write-test");
+    fs::write(&probe, b"")
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|err: io::Error| format!("{} is not writable: {}", dir.display(), err))
+}
+
+/// Writer of a recording in any format.
+enum Encoder {
+    Wav(hound::WavWriter<io::BufWriter<fs::File>>),
+    #[cfg(feature = "flac")]
+    Flac(flac::Writer),
+}
+
+impl Encoder {
+    /// Opens the recording at `path` to append to it, creating it if missing.
+    /// FLAC files cannot be appended to, so that a new file is created next to an existing one.
+    fn append(
+        path: &Path,
+        spec: hound::WavSpec,
+        format: Format,
+    ) -> Result<(Self, PathBuf), String> {
+        if !path.exists() {
+            return Self::create(path, spec, format).map(|encoder| (encoder, path.into()));
+        }
+        match format {
+            Format::Wav => {
+                let writer = hound::WavWriter::append(path).map_err(|err| err.to_string())?;
+                if writer.spec() != spec {
+                    return Err(format!(
+                        "{} has another sample rate or format than the one recorded",
+                        path.display()
+                    ));
+                }
+                Ok((Self::Wav(writer), path.into()))
+            }
+            #[cfg(feature = "flac")]
+            Format::Flac => {
+                let stem = path.with_extension("");
+                let path = (1..)
+                    .map(|n| PathBuf::from(format!("{}-{}.flac", stem.display(), n)))
+                    .find(|path| !path.exists())
+                    .expect("Failed to find unused recording path");
+                Self::create(&path, spec, format).map(|encoder| (encoder, path))
+            }
+        }
+    }
+
+    fn create(path: &Path, spec: hound::WavSpec, format: Format) -> Result<Self, String> {
+        match format {
+            Format::Wav => hound::WavWriter::create(path, spec)
+                .map(Self::Wav)
+                .map_err(|err| err.to_string()),
+            #[cfg(feature = "flac")]
+            Format::Flac => flac::Writer::create(path, spec).map(Self::Flac),
+        }
+    }
+
+    fn write(&mut self, samples: impl IntoIterator<Item = i16>) -> Result<(), String> {
+        match self {
+            Self::Wav(writer) => samples
+                .into_iter()
+                .try_for_each(|sample| writer.write_sample(sample))
+                .map_err(|err| err.to_string()),
+            #[cfg(feature = "flac")]
+            Self::Flac(writer) => writer.write(samples),
+        }
+    }
+
+    fn finalize(self) -> Result<(), String> {
+        match self {
+            Self::Wav(writer) => writer.finalize().map_err(|err| err.to_string()),
+            #[cfg(feature = "flac")]
+            Self::Flac(writer) => writer.finalize(),
+        }
+    }
+}
+
+/// Recording, which is written to incrementally.
+/// Writing stops with a warning on the first error, e.g. when the disk is full.
+struct Continuous {
+    path: PathBuf,
+    encoder: Option<Encoder>,
+}
+
+impl Continuous {
+    fn create(path: PathBuf, spec: hound::WavSpec, format: Format) -> Result<Self, String> {
+        let encoder = Encoder::create(&path, spec, format)?;
+        Ok(Self {
+            path,
+            encoder: Some(encoder),
+        })
+    }
+
+    fn append(path: &Path, spec: hound::WavSpec, format: Format) -> Result<Self, String> {
+        let (encoder, path) = Encoder::append(path, spec, format)?;
+        Ok(Self {
+            path,
+            encoder: Some(encoder),
+        })
+    }
+
+    fn write(&mut self, samples: impl IntoIterator<Item = i16>) {
+        if let Some(encoder) = &mut self.encoder {
+            if let Err(err) = encoder.write(samples) {
+                warn!(path = %self.path.display(), error = %err, "Failed to write recording, stopping it");
+                self.finalize();
+            }
+        }
+    }
+
+    fn finalize(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            if let Err(err) = encoder.finalize() {
+                error!(path = %self.path.display(), error = %err, "Failed to finalize recording");
+            }
+        }
+    }
+}
+
+/// Records every sample of the session into a file at the expansion of a template, switching to
+/// a new one whenever the expansion changes. Existing files are appended to.
+pub struct SessionRecorder {
+    rolling: Rolling,
+    spec: hound::WavSpec,
+    format: Format,
+    recording: Continuous,
+}
+
+impl SessionRecorder {
+    pub fn create(
+        template: Template,
+        spec: hound::WavSpec,
+        format: Format,
+    ) -> Result<Self, String> {
+        let mut rolling = Rolling::new(template);
+        let path = rolling
+            .next(SystemTime::now())
+            .map_err(|err| format!("failed to create directory: {}", err))?
+            .expect("Failed to expand session recording path");
+        let recording = Continuous::append(path, spec, format)?;
+        Ok(Self {
+            rolling,
+            spec,
+            format,
+            recording,
+        })
+    }
+}
+
+impl Sink for SessionRecorder {
+    fn send(&mut self, _: &Event) {}
+
+    fn samples(&mut self, samples: &[i16]) {
+        match self.rolling.next(SystemTime::now()) {
+            Ok(Some(path)) => match Continuous::append(path, self.spec, self.format) {
+                Ok(recording) => {
+                    self.recording.finalize();
+                    self.recording = recording;
+                }
+                Err(err) => {
+                    error!(path = %path.display(), error = %err, "Failed to create session recording")
+                }
+            },
+            Ok(None) => {}
+            Err(err) => warn!(error = %err, "Failed to create session recording directory"),
+        }
+        self.recording.write(samples.iter().copied())
+    }
+
+    fn finish(&mut self) {
+        self.recording.finalize()
+    }
+}
+
+/// Records all speech segments into a single file, separated by `gap` samples of silence.
+pub struct SpeechRecorder {
+    recording: Continuous,
+    gap: usize,
+    empty: bool,
+}
+
+impl SpeechRecorder {
+    pub fn create(
+        path: PathBuf,
+        spec: hound::WavSpec,
+        format: Format,
+        gap: usize,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            recording: Continuous::create(path, spec, format)?,
+            gap,
+            empty: true,
+        })
+    }
+}
+
+impl Sink for SpeechRecorder {
+    fn send(&mut self, _: &Event) {}
+
+    fn segment(&mut self, segment: &Segment) {
+        if !self.empty {
+            self.recording.write(iter::repeat_n(0, self.gap));
+        }
+        self.recording.write(segment.samples.iter().copied());
+        self.empty = false;
+    }
+
+    fn finish(&mut self) {
+        self.recording.finalize()
+    }
+}
+
+/// Writes `samples` to a file at `path` in `format`, finalizing it so that the header is valid.
+pub fn write(
+    path: &Path,
+    spec: hound::WavSpec,
+    format: Format,
+    samples: &[i16],
+) -> Result<(), String> {
+    let mut encoder = Encoder::create(path, spec, format)?;
+    encoder.write(samples.iter().copied())?;
+    encoder.finalize()
+}
+
+#[cfg(feature = "flac")]
+mod flac {
+    use flac_bound::FlacEncoder;
+    use std::path::Path;
+
+    /// FLAC file, which is encoded by libFLAC as samples are written.
+    pub struct Writer {
+        encoder: FlacEncoder<'static>,
+        channels: u32,
+        /// Reused across writes, since libFLAC takes samples as i32.
+        buffer: Vec<i32>,
+    }
+
+    impl Writer {
+        pub fn create(path: &Path, spec: hound::WavSpec) -> Result<Self, String> {
+            let encoder = FlacEncoder::new()
+                .ok_or_else(|| "failed to allocate FLAC encoder".to_string())?
+                .channels(spec.channels as u32)
+                .bits_per_sample(spec.bits_per_sample as u32)
+                .sample_rate(spec.sample_rate)
+                .compression_level(5)
+                .init_file(&path)
+                .map_err(|err| format!("failed to initialize FLAC encoder: {:?}", err))?;
+            Ok(Self {
+                encoder,
+                channels: spec.channels as u32,
+                buffer: Vec::new(),
+            })
+        }
+
+        pub fn write(&mut self, samples: impl IntoIterator<Item = i16>) -> Result<(), String> {
+            self.buffer.clear();
+            self.buffer
+                .extend(samples.into_iter().map(|sample| sample as i32));
+            if self.buffer.is_empty() {
+                return Ok(());
+            }
+            let frames = self.buffer.len() as u32 / self.channels;
+            self.encoder
+                .process_interleaved(&self.buffer, frames)
+                .map_err(|_| format!("failed to encode FLAC: {:?}", self.encoder.state()))
+        }
+
+        pub fn finalize(self) -> Result<(), String> {
+            self.encoder
+                .finish()
+                .map(|_| ())
+                .map_err(|encoder| format!("failed to finish FLAC: {:?}", encoder.state()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    const SPEC: hound::WavSpec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    fn samples(len: usize) -> Vec<i16> {
+        (0..len).map(|i| (i % 512) as i16 - 256).collect()
+    }
+
+    fn read(path: &Path) -> (hound::WavSpec, Vec<i16>) {
+        let mut reader = hound::WavReader::open(path).unwrap();
+        let samples = reader.samples::<i16>().map(Result::unwrap).collect();
+        (reader.spec(), samples)
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(Format::from_str("wav").map(Format::extension), Ok("wav"));
+        #[cfg(feature = "flac")]
+        assert_eq!(Format::from_str("flac").map(Format::extension), Ok("flac"));
+        #[cfg(not(feature = "flac"))]
+        assert!(Format::from_str("flac")
+            .unwrap_err()
+            .contains("`flac` feature"));
+        assert!(Format::from_str("mp3").is_err());
+    }
+
+    #[test]
+    fn prepare() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("nested/recordings");
+        assert_eq!(prepare_dir(Some(dir.clone())), Ok(dir.clone()));
+        assert!(dir.is_dir());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let file = root.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(prepare_dir(Some(file)).is_err());
+    }
+
+    #[test]
+    fn write_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.wav");
+        let written = [0, 1, -1, i16::MAX, i16::MIN];
+        write(&path, SPEC, Format::Wav, &written).unwrap();
+        assert_eq!(read(&path), (SPEC, written.to_vec()));
+    }
+
+    #[test]
+    fn quota() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(
+            dir.path().to_path_buf(),
+            Quota {
+                max_files: Some(2),
+                max_bytes: None,
+            },
+            Format::Wav,
+            Naming::Timestamp,
+        );
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::write(dir.path().join("0.flac"), "").unwrap();
+        for name in &["1", "2", "3"] {
+            assert!(recorder.save(name, SPEC, &[0; 10]).is_some());
+            // Modification times must differ for the oldest recording to be deleted first.
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["2.wav", "3.wav", "notes.txt"]);
+
+        let recorder = Recorder::new(
+            dir.path().to_path_buf(),
+            Quota {
+                max_files: None,
+                max_bytes: Some(2 * (44 + 20)),
+            },
+            Format::Wav,
+            Naming::Timestamp,
+        );
+        assert!(recorder.save("4", SPEC, &[0; 10]).is_some());
+        assert!(!dir.path().join("2.wav").exists());
+        assert!(dir.path().join("3.wav").exists());
+    }
+
+    #[test]
+    fn sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(
+            dir.path().to_path_buf(),
+            Quota {
+                max_files: Some(1),
+                max_bytes: None,
+            },
+            Format::Wav,
+            Naming::Timestamp,
+        );
+        let path = recorder.save("1", SPEC, &[0; 10]).unwrap();
+        recorder.annotate(&path, &serde_json::json!({ "text": "hello" }));
+        let sidecar = dir.path().join("1.json");
+        let metadata: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&sidecar).unwrap()).unwrap();
+        assert_eq!(metadata["text"], "hello");
+
+        thread::sleep(Duration::from_millis(10));
+        recorder.save("2", SPEC, &[0; 10]).unwrap();
+        assert!(!path.exists());
+        assert!(!sidecar.exists());
+    }
+
+    #[test]
+    fn session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.wav");
+        let template = Template::from_str(path.to_str().unwrap()).unwrap();
+        let mut recorder = SessionRecorder::create(template.clone(), SPEC, Format::Wav).unwrap();
+        recorder.samples(&[1, 2, 3]);
+        recorder.samples(&[4, 5]);
+        recorder.finish();
+        recorder.samples(&[6]);
+        recorder.finish();
+        assert_eq!(read(&path), (SPEC, vec![1, 2, 3, 4, 5]));
+
+        // Restarting appends to the recording of the same period.
+        let mut recorder = SessionRecorder::create(template, SPEC, Format::Wav).unwrap();
+        recorder.samples(&[6]);
+        recorder.finish();
+        assert_eq!(read(&path), (SPEC, vec![1, 2, 3, 4, 5, 6]));
+    }
+
+    #[test]
+    fn append_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.wav");
+        let first = [1, 2, 3];
+        let second = [-1, -2];
+
+        let mut recording = Continuous::append(&path, SPEC, Format::Wav).unwrap();
+        recording.write(first.iter().copied());
+        recording.finalize();
+        let mut recording = Continuous::append(&path, SPEC, Format::Wav).unwrap();
+        recording.write(second.iter().copied());
+        recording.finalize();
+        assert_eq!(read(&path), (SPEC, [&first[..], &second[..]].concat()));
+    }
+
+    #[test]
+    fn append_other_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.wav");
+        write(&path, SPEC, Format::Wav, &[0; 10]).unwrap();
+        let spec = hound::WavSpec {
+            sample_rate: 48000,
+            ..SPEC
+        };
+        assert!(Continuous::append(&path, spec, Format::Wav).is_err());
+    }
+
+    #[test]
+    fn speech() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        let mut recorder = SpeechRecorder::create(path.clone(), SPEC, Format::Wav, 2).unwrap();
+        recorder.segment(&Segment {
+            start: 0,
+            samples: vec![1, 2],
+            overlap: 0,
+            voiced: 0..0,
+            speech_samples: 0,
+        });
+        recorder.segment(&Segment {
+            start: 100,
+            samples: vec![3],
+            overlap: 0,
+            voiced: 0..0,
+            speech_samples: 0,
+        });
+        recorder.finish();
+        assert_eq!(read(&path), (SPEC, vec![1, 2, 0, 0, 3]));
+    }
+
+    #[test]
+    fn check_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        super::check_dir(dir.path()).unwrap();
+        let missing = dir.path().join("a").join("b");
+        super::check_dir(&missing).unwrap();
+        assert!(!dir.path().join("a").exists());
+        assert!(!dir.path().join(".speech2text-write-test").exists());
+
+        let file = dir.path().join("file");
+        fs::write(&file, b"").unwrap();
+        let err = super::check_dir(&file.join("recordings")).unwrap_err();
+        assert!(err.contains("is not a directory"), "{}", err);
+    }
+
+    #[test]
+    fn sequential_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(
+            dir.path().into(),
+            Quota::default(),
+            Format::Wav,
+            Naming::Sequential,
+        );
+        assert_eq!(recorder.offset, 0);
+
+        let unnumbered = recorder.save_segment(SPEC, &samples(10)).unwrap();
+        let name = unnumbered.file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.starts_with(&format!("{}{}", recorder.session, UNNUMBERED)),
+            "{}",
+            name
+        );
+
+        let numbered = recorder.number(&unnumbered, 42);
+        assert_eq!(
+            numbered,
+            dir.path().join(format!("{}_000042.wav", recorder.session))
+        );
+        assert!(!unnumbered.exists());
+        assert_eq!(read(&numbered), (SPEC, samples(10)));
+    }
+
+    #[test]
+    fn timestamp_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(
+            dir.path().into(),
+            Quota::default(),
+            Format::Wav,
+            Naming::Timestamp,
+        );
+        let path = recorder.save_segment(SPEC, &samples(10)).unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("recording"), "{}", name);
+        assert_eq!(recorder.number(&path, 1), path);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn resume_after_last_seq() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(
+            dir.path().into(),
+            Quota::default(),
+            Format::Wav,
+            Naming::Sequential,
+        );
+        let session = recorder.session.clone();
+        for name in [
+            format!("{}_000003.wav", session),
+            format!("{}_000007.flac", session),
+            // Neither recordings nor of the session.
+            format!("{}_000099.json", session),
+            format!("{}_unnumbered-123.wav", session),
+            "20000101-000000_000050.wav".into(),
+        ] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        assert_eq!(last_seq(dir.path(), &session), 7);
+        assert_eq!(last_seq(&dir.path().join("missing"), &session), 0);
+
+        let resumed = Recorder {
+            offset: last_seq(dir.path(), &session),
+            ..recorder
+        };
+        let unnumbered = resumed.save_segment(SPEC, &samples(10)).unwrap();
+        assert_eq!(
+            resumed.number(&unnumbered, 1),
+            dir.path().join(format!("{}_000008.wav", session))
+        );
+        // Existing recordings are not overwritten.
+        assert_eq!(
+            fs::metadata(dir.path().join(format!("{}_000007.flac", session)))
+                .unwrap()
+                .len(),
+            0
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use crate::control::Control;
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Delay, for which filesystem events are coalesced by the watcher.
+const EVENT_DELAY: Duration = Duration::from_millis(500);
+
+/// Rules, which may be swapped for the ones reloaded from their file once it changes.
+/// They are locked while applied, so that each transcript is processed by either the previous
+/// or the reloaded rules as a whole.
+pub struct Reloadable<T>(Arc<Mutex<T>>);
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Reloadable<T> {
+    pub fn new(rules: T) -> Self {
+        Self(Arc::new(Mutex::new(rules)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().expect("Failed to lock rules")
+    }
+}
+
+impl<T: Send + 'static> Reloadable<T> {
+    /// Reloads the rules with `load` on a separate thread whenever the file at `path` changes,
+    /// including when it is replaced, as editors do. The previous rules are kept if the new ones
+    /// fail to load, logging why. Successful reloads are output as marks via `controls`, naming
+    /// the rules `name`.
+    pub fn watch(
+        &self,
+        path: &Path,
+        name: &'static str,
+        load: impl Fn(&Path) -> Result<T, String> + Send + 'static,
+        controls: Sender<Control>,
+    ) -> Result<(), String> {
+        // The directory of the file is watched, since replacing the file would end watching it.
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = dir
+            .canonicalize()
+            .map_err(|err| format!("failed to resolve {}: {}", dir.display(), err))?;
+        let path: PathBuf = match path.file_name() {
+            Some(name) => dir.join(name),
+            None => return Err(format!("{} is not a file", path.display())),
+        };
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::watcher(tx, EVENT_DELAY)
+            .map_err(|err| format!("failed to create watcher: {}", err))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|err| format!("failed to watch {}: {}", dir.display(), err))?;
+        let rules = self.0.clone();
+        thread::spawn(move || {
+            // The watcher stops once dropped.
+            let _watcher = watcher;
+            for event in rx {
+                match event {
+                    DebouncedEvent::Create(changed)
+                    | DebouncedEvent::Write(changed)
+                    | DebouncedEvent::Rename(_, changed)
+                        if changed == path => {}
+                    DebouncedEvent::Error(err, _) => {
+                        warn!(path = %path.display(), error = %err, "Failed to watch {}", name);
+                        continue;
+                    }
+                    _ => continue,
+                }
+                match load(&path) {
+                    Ok(reloaded) => {
+                        *rules.lock().expect("Failed to lock rules") = reloaded;
+                        info!(path = %path.display(), "Reloaded {}", name);
+                        if controls
+                            .send(Control::Mark(format!("reloaded {}", name)))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(err) => warn!(
+                        path = %path.display(),
+                        "Failed to reload {}, keeping the previous ones:\n{}",
+                        name,
+                        err
+                    ),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl<T: Pass> Pass for Reloadable<T> {
+    fn apply(&self, utterance: &mut Utterance) {
+        self.lock().apply(utterance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules");
+        fs::write(&path, "one").unwrap();
+        let rules = Reloadable::new("one".to_string());
+        let (tx, rx) = mpsc::channel();
+        rules
+            .watch(
+                &path,
+                "rules",
+                |path| match fs::read_to_string(path).map_err(|err| err.to_string())? {
+                    contents if contents.is_empty() => Err("empty".into()),
+                    contents => Ok(contents),
+                },
+                tx,
+            )
+            .unwrap();
+        let reloaded = || match rx.recv_timeout(Duration::from_secs(10)) {
+            Ok(Control::Mark(label)) => label,
+            _ => panic!("rules were not reloaded"),
+        };
+
+        fs::write(dir.path().join("other"), "other").unwrap();
+        fs::write(&path, "two").unwrap();
+        assert_eq!(reloaded(), "reloaded rules");
+        assert_eq!(*rules.lock(), "two");
+
+        // Rules failing to load are not swapped in.
+        fs::write(&path, "").unwrap();
+        assert!(rx.recv_timeout(EVENT_DELAY * 4).is_err());
+        assert_eq!(*rules.lock(), "two");
+
+        // Replacing the file, as editors do, reloads it, too.
+        let replacement = dir.path().join("rules.tmp");
+        fs::write(&replacement, "three").unwrap();
+        fs::rename(&replacement, &path).unwrap();
+        assert_eq!(reloaded(), "reloaded rules");
+        assert_eq!(*rules.lock(), "three");
+    }
+}
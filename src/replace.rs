@@ -0,0 +1,134 @@
+use crate::output::Utterance;
+use crate::postprocess::Pass;
+use regex::Regex;
+use std::{fs, path::Path};
+use tracing::info;
+
+struct Rule {
+    /// Line of the rules file the rule is defined on.
+    line: usize,
+    pattern: Regex,
+    replacement: String,
+}
+
+/// Regular expression replacement rules.
+pub struct Rules {
+    rules: Vec<Rule>,
+    /// Whether to log the rules applied to each transcript.
+    explain: bool,
+}
+
+impl Rules {
+    /// Reads rules from `path`, one `pattern => replacement` pair per line.
+    /// Replacements may refer to capture groups as `$1` or `${name}`, patterns starting with
+    /// `(?i)` match case-insensitively. Blank lines and lines starting with `#` are ignored.
+    pub fn load(path: &Path, explain: bool) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (pattern, replacement) = match trimmed.find(" => ") {
+                Some(at) => (&trimmed[..at], &trimmed[at + 4..]),
+                None => {
+                    errors.push(format!(
+                        "{}:{}: expected `pattern => replacement`",
+                        path.display(),
+                        i + 1
+                    ));
+                    continue;
+                }
+            };
+            match Regex::new(pattern) {
+                Ok(pattern) => rules.push(Rule {
+                    line: i + 1,
+                    pattern,
+                    replacement: replacement.to_string(),
+                }),
+                Err(err) => errors.push(format!("{}:{}: {}", path.display(), i + 1, err)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        Ok(Self { rules, explain })
+    }
+}
+
+impl Pass for Rules {
+    fn apply(&self, utterance: &mut Utterance) {
+        for rule in &self.rules {
+            if !rule.pattern.is_match(&utterance.text) {
+                continue;
+            }
+            let replaced = rule
+                .pattern
+                .replace_all(&utterance.text, rule.replacement.as_str())
+                .into_owned();
+            if self.explain {
+                info!(
+                    rule = rule.line,
+                    pattern = rule.pattern.as_str(),
+                    before = utterance.text.as_str(),
+                    after = replaced.as_str(),
+                    "Applied replacement rule"
+                );
+            }
+            utterance.text = replaced;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(contents: &str) -> Result<Rules, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules");
+        fs::write(&path, contents).unwrap();
+        Rules::load(&path, false)
+    }
+
+    fn apply(rules: &Rules, text: &str) -> String {
+        let mut utterance = Utterance::test(text);
+        rules.apply(&mut utterance);
+        utterance.text
+    }
+
+    #[test]
+    fn replace() {
+        let rules = rules(concat!(
+            "# Units\n",
+            "\n",
+            r"(\d+) percent => ${1}%",
+            "\n",
+            r"(?i)\bspeech two text\b => speech2text",
+            "\n",
+            r"\bi\b => I",
+            "\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            apply(&rules, "i use Speech Two Text 99 percent of the time"),
+            "I use speech2text 99% of the time"
+        );
+        assert_eq!(apply(&rules, "nothing to do"), "nothing to do");
+    }
+
+    #[test]
+    fn invalid() {
+        let err = rules("a => b\nno arrow\n(unclosed => x\n").err().unwrap();
+        assert!(
+            err.contains(":2: expected `pattern => replacement`"),
+            "{}",
+            err
+        );
+        assert!(err.contains(":3: regex parse error"), "{}", err);
+        assert!(!err.contains(":1:"), "{}", err);
+    }
+}
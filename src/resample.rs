@@ -0,0 +1,270 @@
+use dasp::{interpolate::linear::Linear, Signal};
+use std::str::FromStr;
+use tracing::info;
+
+/// Converter used to resample audio to the sample rate of the model.
+#[derive(Clone, Copy, Debug)]
+pub enum Resampler {
+    /// Linear interpolation, which is cheap, but smears high frequencies when downsampling.
+    Linear,
+    /// Windowed sinc interpolation, which is band-limited, but costs more CPU and adds latency.
+    #[cfg(feature = "resample-hq")]
+    Sinc,
+}
+
+impl FromStr for Resampler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            #[cfg(feature = "resample-hq")]
+            "sinc" => Ok(Self::Sinc),
+            _ => Err(format!(
+                "failed to parse `{}` into resampler of linear{}",
+                s,
+                if cfg!(feature = "resample-hq") {
+                    " or sinc"
+                } else {
+                    ", sinc requires building with the `resample-hq` feature"
+                }
+            )),
+        }
+    }
+}
+
+/// Resamples `samples` from `from` Hz to `to` Hz with `resampler`.
+pub fn resample(
+    samples: impl Iterator<Item = i16> + 'static,
+    from: u32,
+    to: u32,
+    resampler: Resampler,
+) -> Box<dyn Iterator<Item = i16>> {
+    if from == to {
+        return Box::new(samples);
+    }
+    info!(from, to, ?resampler, "Resampling audio");
+    match resampler {
+        Resampler::Linear => {
+            let mut signal = dasp::signal::from_iter(samples);
+            let left = signal.next();
+            let right = signal.next();
+            Box::new(
+                signal
+                    .from_hz_to_hz(Linear::new(left, right), from as f64, to as f64)
+                    .until_exhausted(),
+            )
+        }
+        #[cfg(feature = "resample-hq")]
+        Resampler::Sinc => Box::new(sinc::Sinc::new(samples, from, to)),
+    }
+}
+
+#[cfg(feature = "resample-hq")]
+mod sinc {
+    use rubato::{
+        InterpolationParameters, InterpolationType, Resampler, SincFixedIn, WindowFunction,
+    };
+    use std::vec;
+    use tracing::error;
+
+    /// Length of the sinc filter in input samples.
+    const SINC_LEN: usize = 256;
+
+    /// Number of chunks resampled per second, which bounds the latency added to live capture.
+    const CHUNKS_PER_SECOND: u32 = 100;
+
+    /// Iterator resampling the samples of another one by windowed sinc interpolation.
+    /// Samples are resampled in chunks and the input is flushed with silence at its end. The
+    /// resampler compensates the delay of its filter, starting it half its length before the first
+    /// sample, so that output sample `n` corresponds to input sample `n * from / to` and segment
+    /// timestamps are not shifted.
+    pub struct Sinc<I> {
+        samples: I,
+        resampler: SincFixedIn<f32>,
+        ratio: f64,
+        /// Reused chunk of input samples.
+        input: Vec<Vec<f32>>,
+        output: vec::IntoIter<f32>,
+        consumed: u64,
+        emitted: u64,
+        exhausted: bool,
+    }
+
+    impl<I: Iterator<Item = i16>> Sinc<I> {
+        pub fn new(samples: I, from: u32, to: u32) -> Self {
+            let ratio = to as f64 / from as f64;
+            let parameters = InterpolationParameters {
+                sinc_len: SINC_LEN,
+                f_cutoff: 0.95,
+                interpolation: InterpolationType::Linear,
+                oversampling_factor: 256,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let chunk_size = (from / CHUNKS_PER_SECOND).max(1) as usize;
+            Self {
+                samples,
+                resampler: SincFixedIn::new(ratio, parameters, chunk_size, 1),
+                ratio,
+                input: vec![Vec::with_capacity(chunk_size)],
+                output: Vec::new().into_iter(),
+                consumed: 0,
+                emitted: 0,
+                exhausted: false,
+            }
+        }
+
+        /// Returns the number of output samples corresponding to the input consumed so far.
+        fn expected(&self) -> u64 {
+            (self.consumed as f64 * self.ratio).round() as u64
+        }
+
+        /// Resamples the next chunk of input, returning whether it succeeded.
+        fn process(&mut self) -> bool {
+            let needed = self.resampler.nbr_frames_needed();
+            let input = &mut self.input[0];
+            input.clear();
+            while !self.exhausted && input.len() < needed {
+                match self.samples.next() {
+                    Some(sample) => {
+                        input.push(sample as f32 / -(i16::MIN as f32));
+                        self.consumed += 1;
+                    }
+                    None => self.exhausted = true,
+                }
+            }
+            // Flushes the filter with silence once the input is exhausted.
+            input.resize(needed, 0.0);
+            match self.resampler.process(&self.input) {
+                Ok(mut output) => {
+                    self.output = output.swap_remove(0).into_iter();
+                    true
+                }
+                Err(err) => {
+                    error!(error = %err, "Failed to resample audio");
+                    false
+                }
+            }
+        }
+    }
+
+    impl<I: Iterator<Item = i16>> Iterator for Sinc<I> {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            loop {
+                if self.exhausted && self.emitted >= self.expected() {
+                    return None;
+                }
+                match self.output.next() {
+                    Some(sample) => {
+                        self.emitted += 1;
+                        return Some(
+                            (sample * -(i16::MIN as f32))
+                                .round()
+                                .max(i16::MIN as f32)
+                                .min(i16::MAX as f32) as i16,
+                        );
+                    }
+                    None => {
+                        if !self.process() {
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    /// Returns a second of a 440 Hz sine wave at half of full scale, sampled at `rate` Hz.
+    fn sine(rate: u32) -> Vec<i16> {
+        (0..rate)
+            .map(|i| (16384.0 * (TAU * 440.0 * i as f64 / rate as f64).sin()).round() as i16)
+            .collect()
+    }
+
+    fn rms(samples: &[i16]) -> f64 {
+        (samples.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    /// Checks that resampling the sine wave from 48 kHz to 16 kHz with `resampler` preserves its
+    /// length and energy and does not shift it, within `tolerance` of full scale per sample, and
+    /// that an impulse stays in place.
+    fn check(resampler: Resampler, tolerance: f64) {
+        let output: Vec<i16> = resample(sine(48000).into_iter(), 48000, 16000, resampler).collect();
+        let expected = sine(16000);
+        assert!(
+            (output.len() as i64 - expected.len() as i64).abs() <= 2,
+            "{:?} resampled to {} samples",
+            resampler,
+            output.len()
+        );
+        let (rms, expected_rms) = (rms(&output), rms(&expected));
+        assert!(
+            (rms / expected_rms - 1.0).abs() < 0.02,
+            "{:?} resampled to RMS {} instead of {}",
+            resampler,
+            rms,
+            expected_rms
+        );
+        // The edges are skipped, where the filter of the sinc resampler sees silence.
+        let error = output[500..15500]
+            .iter()
+            .zip(&expected[500..15500])
+            .map(|(&a, &b)| (a as f64 - b as f64).abs())
+            .fold(0.0, f64::max);
+        assert!(
+            error / 32768.0 < tolerance,
+            "{:?} deviates by {}",
+            resampler,
+            error
+        );
+
+        let mut impulse = vec![0; 48000];
+        impulse[24000] = i16::MAX;
+        let output: Vec<i16> = resample(impulse.into_iter(), 48000, 16000, resampler).collect();
+        let peak = (0..output.len())
+            .max_by_key(|&i| output[i].unsigned_abs())
+            .unwrap();
+        assert!(
+            (7999..=8000).contains(&peak),
+            "{:?} moved the impulse to {}",
+            resampler,
+            peak
+        );
+    }
+
+    #[test]
+    fn linear() {
+        check(Resampler::Linear, 0.01);
+    }
+
+    #[cfg(feature = "resample-hq")]
+    #[test]
+    fn sinc() {
+        // The filter shifts by a fraction of a sample, which is negligible for timestamps.
+        check(Resampler::Sinc, 0.1);
+    }
+
+    #[test]
+    fn same_rate() {
+        let samples = sine(16000);
+        let output: Vec<i16> =
+            resample(samples.clone().into_iter(), 16000, 16000, Resampler::Linear).collect();
+        assert_eq!(output, samples);
+    }
+
+    #[test]
+    fn parse() {
+        assert!(matches!("linear".parse(), Ok(Resampler::Linear)));
+        #[cfg(feature = "resample-hq")]
+        assert!(matches!("sinc".parse(), Ok(Resampler::Sinc)));
+        assert!("cubic".parse::<Resampler>().is_err());
+    }
+}
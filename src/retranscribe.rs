@@ -0,0 +1,214 @@
+use crate::error::Error;
+use crate::recordings;
+use serde_json::{json, Map, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// Saved recording along with its sidecar metadata, if any.
+pub struct Recording {
+    pub audio: PathBuf,
+    /// Path of the sidecar with the extension of the recording replaced by `json`.
+    pub sidecar: Option<PathBuf>,
+}
+
+/// Returns the recordings in `dir` ordered by path, each paired with the JSON sidecar of the same
+/// name, if it exists.
+pub fn recordings(dir: &Path) -> Result<Vec<Recording>, String> {
+    let mut recordings = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|err| format!("Failed to read directory: {}", err))? {
+        let audio = entry
+            .map_err(|err| format!("Failed to read directory: {}", err))?
+            .path();
+        let is_recording = audio.extension().is_some_and(|ext| {
+            recordings::Format::EXTENSIONS
+                .iter()
+                .any(|extension| ext == *extension)
+        });
+        if !is_recording || !audio.is_file() {
+            continue;
+        }
+        let sidecar = audio.with_extension("json");
+        recordings.push(Recording {
+            sidecar: if sidecar.is_file() {
+                Some(sidecar)
+            } else {
+                None
+            },
+            audio,
+        });
+    }
+    if recordings.is_empty() {
+        return Err(format!("No recordings found in {}", dir.display()));
+    }
+    recordings.sort_by(|a, b| a.audio.cmp(&b.audio));
+    Ok(recordings)
+}
+
+/// Reads the sidecar at `path`, returning `None` with a warning if it is not a JSON object.
+fn read_sidecar(path: &Path) -> Option<Map<String, Value>> {
+    let parsed = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()));
+    match parsed {
+        Ok(Value::Object(sidecar)) => Some(sidecar),
+        Ok(_) => {
+            warn!(path = %path.display(), "Recording metadata is not an object, ignoring it");
+            None
+        }
+        Err(err) => {
+            warn!(
+                path = %path.display(),
+                error = %err,
+                "Failed to read recording metadata, ignoring it"
+            );
+            None
+        }
+    }
+}
+
+/// Transcribes every recording of `recordings` with `transcribe`, which returns the transcript
+/// and its confidence, printing the old transcript of its sidecar along with the new one, if
+/// they differ, and a summary once done.
+/// If `update` is set, the text, confidence and model of sidecars are replaced with the new
+/// results decoded by `model`, keeping the other fields, while recordings without a sidecar are
+/// left as they are.
+/// Recordings, which fail to transcribe, are reported and skipped.
+pub fn run(
+    recordings: &[Recording],
+    update: bool,
+    model: &Path,
+    mut transcribe: impl FnMut(&Path) -> Result<(String, f64), Error>,
+) -> Result<(), Error> {
+    let (mut changed, mut unchanged, mut missing, mut failed) = (0, 0, 0, 0);
+    for recording in recordings {
+        let name = recording.audio.display();
+        let (text, confidence) = match transcribe(&recording.audio) {
+            Ok(transcribed) => transcribed,
+            Err(err) => {
+                failed += 1;
+                println!("{}: failed: {}", name, err);
+                continue;
+            }
+        };
+        let sidecar = recording
+            .sidecar
+            .as_ref()
+            .and_then(|path| read_sidecar(path).map(|sidecar| (path, sidecar)));
+        let (path, mut sidecar) = match sidecar {
+            Some(sidecar) => sidecar,
+            None => {
+                missing += 1;
+                println!("{}: no metadata\n  new: {}", name, text);
+                continue;
+            }
+        };
+        let old = sidecar
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if old == text {
+            unchanged += 1;
+            println!("{}: unchanged", name);
+        } else {
+            changed += 1;
+            println!("{}: changed\n  old: {}\n  new: {}", name, old, text);
+        }
+        if update {
+            sidecar.insert("text".into(), json!(text));
+            sidecar.insert("confidence".into(), json!(confidence));
+            sidecar.insert("model".into(), json!(model.display().to_string()));
+            fs::write(path, format!("{:#}\n", Value::Object(sidecar))).map_err(Error::with(
+                Error::Other,
+                "Failed to write recording metadata",
+            ))?;
+        }
+    }
+    println!(
+        "Transcribed {} recordings: {} changed, {} unchanged, {} without metadata, {} failed",
+        recordings.len(),
+        changed,
+        unchanged,
+        missing,
+        failed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(recordings(dir.path()).is_err());
+        for name in ["b.wav", "a.flac", "a.json", "notes.txt", "c.json"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        fs::create_dir(dir.path().join("d.wav")).unwrap();
+        let found = recordings(dir.path()).unwrap();
+        let found: Vec<_> = found
+            .iter()
+            .map(|recording| (recording.audio.clone(), recording.sidecar.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (dir.path().join("a.flac"), Some(dir.path().join("a.json"))),
+                (dir.path().join("b.wav"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn update() {
+        let dir = tempfile::tempdir().unwrap();
+        let sidecar = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            Some(path)
+        };
+        let recordings = [
+            Recording {
+                audio: dir.path().join("1.wav"),
+                sidecar: sidecar("1.json", r#"{"text":"hello","start_ms":100}"#),
+            },
+            Recording {
+                audio: dir.path().join("2.wav"),
+                sidecar: sidecar("2.json", "[]"),
+            },
+            Recording {
+                audio: dir.path().join("3.wav"),
+                sidecar: sidecar("3.json", r#"{"text":"old"}"#),
+            },
+        ];
+        run(
+            &recordings,
+            true,
+            Path::new("model.pbmm"),
+            |path| match path.file_name().unwrap().to_str().unwrap() {
+                "3.wav" => Err(Error::Decode("failed".into())),
+                _ => Ok(("hullo".into(), -2.5)),
+            },
+        )
+        .unwrap();
+        let read = |name| -> Value {
+            serde_json::from_str(&fs::read_to_string(dir.path().join(name)).unwrap()).unwrap()
+        };
+        assert_eq!(
+            read("1.json"),
+            json!({
+                "text": "hullo",
+                "confidence": -2.5,
+                "model": "model.pbmm",
+                "start_ms": 100,
+            })
+        );
+        // Sidecars, which are not objects or whose recordings fail to transcribe, are kept.
+        assert_eq!(read("2.json"), json!([]));
+        assert_eq!(read("3.json"), json!({"text": "old"}));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::clock::DateTime;
+use std::str::FromStr;
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Window of the day, mid-night crossing ones included, active on some days of the week.
+#[derive(Clone, Debug)]
+struct Window {
+    /// Whether the window starts on a day, indexed by the day of the week starting at Monday.
+    days: [bool; 7],
+    /// Minute of the day the window starts at.
+    start: u32,
+    /// Minute of the day the window ends at, before `start` if it ends on the following day.
+    end: u32,
+}
+
+impl Window {
+    fn active(&self, weekday: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            self.days[weekday] && (self.start..self.end).contains(&minute)
+        } else {
+            // The end of a window crossing mid-night belongs to the day it started on.
+            (self.days[weekday] && minute >= self.start)
+                || (self.days[(weekday + 6) % 7] && minute < self.end)
+        }
+    }
+}
+
+/// Parses a time of day, e.g. `08:00`, into the minute of the day. `24:00` ends the day.
+fn minute(s: &str) -> Option<u32> {
+    let i = s.find(':')?;
+    let (hour, minute) = (&s[..i], &s[i + 1..]);
+    if hour.is_empty() || hour.len() > 2 || minute.len() != 2 {
+        return None;
+    }
+    let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+    match (hour, minute) {
+        (24, 0) => Some(MINUTES_PER_DAY),
+        (0..=23, 0..=59) => Some(hour * 60 + minute),
+        _ => None,
+    }
+}
+
+fn day(s: &str) -> Option<usize> {
+    DAYS.iter().position(|day| *day == s)
+}
+
+/// Parses days of the week, e.g. `mon-fri` or `sat+sun`. Ranges may wrap around, e.g. `fri-mon`.
+fn days(s: &str) -> Option<[bool; 7]> {
+    let mut days = [false; 7];
+    for part in s.split('+') {
+        match part.find('-') {
+            Some(i) => {
+                let (first, last) = (day(&part[..i])?, day(&part[i + 1..])?);
+                let mut day = first;
+                loop {
+                    days[day] = true;
+                    if day == last {
+                        break;
+                    }
+                    day = (day + 1) % 7;
+                }
+            }
+            None => days[day(part)?] = true,
+        }
+    }
+    Some(days)
+}
+
+/// Windows of local time, during which listening is active, parsed from a comma-separated list of
+/// `[<days>=]<start>-<end>`, e.g. `08:00-22:00` or `mon-fri=07:00-23:00,sat+sun=09:00-24:00`.
+/// Windows without days are active every day, those ending before they start end on the following
+/// day, e.g. `22:00-06:00`.
+#[derive(Clone, Debug)]
+pub struct Schedule(Vec<Window>);
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        s.split(',')
+            .map(|window| {
+                let window = window.trim();
+                let err = || {
+                    format!(
+                        "failed to parse `{}` into schedule window like 08:00-22:00 or mon-fri=07:00-23:00",
+                        window
+                    )
+                };
+                let (days, times) = match window.find('=') {
+                    Some(i) => (days(&window[..i]).ok_or_else(err)?, &window[i + 1..]),
+                    None => ([true; 7], window),
+                };
+                let i = times.find('-').ok_or_else(err)?;
+                let start = minute(&times[..i]).ok_or_else(err)?;
+                let end = minute(&times[i + 1..]).ok_or_else(err)?;
+                if start == end || start == MINUTES_PER_DAY {
+                    return Err(format!("schedule window `{}` is empty", window));
+                }
+                Ok(Window {
+                    days,
+                    start,
+                    end: end % MINUTES_PER_DAY,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Schedule {
+    /// Whether listening is active at the local time `at`. It takes the time rather than reading
+    /// the clock, so that callers decide which clock the schedule follows.
+    pub fn active(&self, at: &DateTime) -> bool {
+        let minute = at.hour * 60 + at.minute;
+        self.0
+            .iter()
+            .any(|window| window.active(at.weekday as usize, minute))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+
+    /// Returns whether `schedule` is active at the UTC `time` of the week of Monday 2021-04-05.
+    fn active(schedule: &str, time: &str) -> bool {
+        let (day, time) = time.split_once(' ').unwrap();
+        let at = clock::parse(&format!(
+            "2021-04-{:02}T{}:00Z",
+            5 + day.parse::<u32>().unwrap(),
+            time
+        ))
+        .unwrap();
+        schedule
+            .parse::<Schedule>()
+            .unwrap()
+            .active(&clock::utc(at))
+    }
+
+    #[test]
+    fn parse() {
+        for invalid in [
+            "08:00",
+            "8-22",
+            "08:00-08:00",
+            "24:00-08:00",
+            "08:00-24:30",
+            "08:0-22:00",
+            "mon-fry=08:00-22:00",
+            "08:00-22:00,",
+        ] {
+            assert!(invalid.parse::<Schedule>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn daily() {
+        assert!(!active("08:00-22:00", "0 07:59"));
+        assert!(active("08:00-22:00", "0 08:00"));
+        assert!(active("08:00-22:00", "6 21:59"));
+        assert!(!active("08:00-22:00", "6 22:00"));
+        assert!(active("09:00-24:00", "2 23:59"));
+    }
+
+    #[test]
+    fn days() {
+        let schedule = "mon-fri=07:00-23:00,sat+sun=09:00-12:00";
+        assert!(active(schedule, "4 07:30"));
+        assert!(!active(schedule, "5 07:30"));
+        assert!(active(schedule, "6 11:00"));
+        // Ranges wrap around the end of the week.
+        assert!(active("sat-mon=10:00-11:00", "0 10:30"));
+        assert!(!active("sat-mon=10:00-11:00", "1 10:30"));
+    }
+
+    #[test]
+    fn across_midnight() {
+        // The end of the window belongs to the day it started on.
+        assert!(active("fri=22:00-06:00", "4 23:00"));
+        assert!(active("fri=22:00-06:00", "5 05:59"));
+        assert!(!active("fri=22:00-06:00", "5 06:00"));
+        assert!(!active("fri=22:00-06:00", "4 05:00"));
+        assert!(active("sun=22:00-06:00", "0 01:00"));
+    }
+}
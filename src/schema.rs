@@ -0,0 +1,165 @@
+//! Records output as JSON, which downstream tooling parses.
+//!
+//! Changing the shape of any of them, beyond adding optional fields, requires bumping `VERSION`.
+
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Version of the shape of the records, output as `schema_version` of every utterance.
+pub const VERSION: u32 = 1;
+
+/// Transcript of an utterance, output by sockets, MQTT, push, transcripts and manifests.
+#[derive(Serialize, JsonSchema)]
+pub struct Utterance {
+    /// Always `VERSION`.
+    pub schema_version: u32,
+    /// Sequence number of the utterance, which partial transcripts share with the final one.
+    pub seq: u64,
+    /// Random identifier of the process run, which distinguishes sequence numbers of runs.
+    pub session_id: String,
+    pub text: String,
+    /// Offset of the start of the speech of the utterance from the start of the stream in
+    /// milliseconds.
+    pub start_ms: u64,
+    /// Offset of the end of the speech of the utterance from the start of the stream in
+    /// milliseconds.
+    pub end_ms: u64,
+    pub start_sample: u64,
+    pub end_sample: u64,
+    /// Sample offset of the first sample decoded, including silence before the speech and the
+    /// overlap with the previous utterance, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_start: Option<u64>,
+    /// Sample offset of the first voiced sample, which `start_ms` corresponds to, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speech_start: Option<u64>,
+    /// Sample offset following the last voiced sample, which `end_ms` corresponds to, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speech_end: Option<u64>,
+    /// Sample offset following the last sample decoded, including silence after the speech, if
+    /// known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_end: Option<u64>,
+    /// Confidence of the model in the transcript, higher is more confident.
+    pub confidence: f64,
+    /// Start of the utterance in the configured timestamp format, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// End of the utterance in the configured timestamp format, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Number of the paragraph of the utterance starting at 1, if grouped into paragraphs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paragraph: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<CapturedAt>,
+    /// SHA256 digest of the samples decoded as little-endian i16, if fingerprinted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_samples: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rms_db: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_db: Option<f64>,
+    /// Path of the recording of the utterance, if saved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording: Option<String>,
+    /// Whether words were censored from the transcript, omitted unless they were.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub censored: bool,
+    /// Whether the confidence is below the minimum, omitted unless it is.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub low_confidence: bool,
+    /// Whether the transcript repeats the previous one, omitted unless it does.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub duplicate: bool,
+    /// Whether the utterance was decoded again with --rescore-beam-width or without the scorer
+    /// for its low confidence, omitted unless it was.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub rescored: bool,
+    /// Intent the transcript matched, `null` if none, omitted unless matching intents.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intent: Option<Option<Intent>>,
+}
+
+/// Wall-clock times, at which the start and the end of an utterance were captured, in RFC 3339.
+#[derive(Serialize, JsonSchema)]
+pub struct CapturedAt {
+    pub start: String,
+    pub end: String,
+}
+
+/// Intent of a transcript along with the values of its slots, numbers as JSON numbers.
+#[derive(Serialize, JsonSchema)]
+pub struct Intent {
+    pub name: String,
+    pub slots: BTreeMap<String, Value>,
+}
+
+/// Transcripts of an utterance decoded with two configurations, output in place of the utterance
+/// when comparing them.
+#[derive(Serialize, JsonSchema)]
+pub struct Pair {
+    pub primary: Utterance,
+    pub comparison: Comparison,
+}
+
+/// Transcript of an utterance decoded with the comparison configuration.
+#[derive(Serialize, JsonSchema)]
+pub struct Comparison {
+    pub text: String,
+    pub confidence: f64,
+    /// Time taken to decode the utterance with the comparison configuration in milliseconds.
+    pub decode_ms: u64,
+    /// Time taken to decode the utterance with the primary configuration in milliseconds.
+    pub primary_decode_ms: u64,
+}
+
+/// Paragraph of a JSON transcript of utterances grouped into paragraphs.
+#[derive(Serialize, JsonSchema)]
+pub struct Paragraph {
+    pub paragraph: u64,
+    pub utterances: Vec<Utterance>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Returns the JSON Schema of utterances.
+pub fn utterance() -> RootSchema {
+    schema_for!(Utterance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::{fs, path::PathBuf};
+
+    /// Compares the schema to the snapshot of its version, so that changing its shape fails until
+    /// the snapshot is updated, e.g. with `speech2text schema > tests/fixtures/schema-v1.json`,
+    /// or `VERSION` is bumped and the snapshot of the new version is added, unless only optional
+    /// fields were added.
+    #[test]
+    fn snapshot() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(format!("schema-v{}.json", VERSION));
+        let snapshot = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("failed to read {}: {}", path.display(), err));
+        let snapshot: Value = serde_json::from_str(&snapshot).unwrap();
+        let schema = serde_json::to_value(utterance()).unwrap();
+        assert!(
+            schema == snapshot,
+            "schema differs from {}, update it if only optional fields were added or bump VERSION:\n{:#}",
+            path.display(),
+            schema
+        );
+    }
+}
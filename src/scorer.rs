@@ -0,0 +1,203 @@
+use crate::models;
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+use tracing::info;
+
+pub struct Options {
+    /// Plain text corpus, one sentence per line.
+    pub corpus: PathBuf,
+    /// Alphabet of the model, as used for training it.
+    pub alphabet: PathBuf,
+    /// Path to write the scorer to.
+    pub output: PathBuf,
+    /// Number of most frequent words of the corpus kept in the vocabulary.
+    pub top_k: usize,
+    /// Order of the n-gram language model.
+    pub order: u8,
+    pub default_alpha: f32,
+    pub default_beta: f32,
+}
+
+/// Returns the path of executable `name` found in `PATH`.
+fn find(name: &str, origin: &str) -> Result<PathBuf, String> {
+    env::var_os("PATH")
+        .and_then(|paths| {
+            env::split_paths(&paths)
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+        })
+        .ok_or_else(|| format!("`{}` not found in PATH, it is part of {}", name, origin))
+}
+
+/// Runs `command`, failing unless it exits successfully.
+fn run(name: &str, command: &mut Command) -> Result<(), String> {
+    info!(?command, "Running {}", name);
+    let status = command
+        .status()
+        .map_err(|err| format!("failed to run `{}`: {}", name, err))?;
+    if !status.success() {
+        return Err(format!("`{}` failed with {}", name, status));
+    }
+    Ok(())
+}
+
+/// Writes the `top_k` most frequent words of `corpus` to `path`, one per line.
+fn write_vocabulary(corpus: &str, top_k: usize, path: &Path) -> Result<(), String> {
+    let mut counts = HashMap::new();
+    for word in corpus.split_whitespace() {
+        *counts.entry(word).or_insert(0usize) += 1;
+    }
+    let mut words: Vec<_> = counts.into_iter().collect();
+    words.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    let vocabulary: Vec<_> = words
+        .into_iter()
+        .take(top_k)
+        .map(|(word, _)| word)
+        .collect();
+    fs::write(path, vocabulary.join("\n"))
+        .map_err(|err| format!("failed to write {}: {}", path.display(), err))
+}
+
+/// Builds a scorer from a text corpus the same way DeepSpeech's `generate_lm.py` does, using the
+/// KenLM `lmplz`, `filter` and `build_binary` tools and DeepSpeech's `generate_scorer_package`.
+pub fn build(options: &Options) -> Result<(), String> {
+    const KENLM: &str = "KenLM, see https://github.com/kpu/kenlm";
+    const NATIVE_CLIENT: &str = "the DeepSpeech native client release";
+    let lmplz = find("lmplz", KENLM)?;
+    let filter = find("filter", KENLM)?;
+    let build_binary = find("build_binary", KENLM)?;
+    let generate_scorer_package = find("generate_scorer_package", NATIVE_CLIENT)?;
+
+    let corpus = fs::read_to_string(&options.corpus)
+        .map_err(|err| format!("failed to read {}: {}", options.corpus.display(), err))?;
+    if corpus.trim().is_empty() {
+        return Err(format!("{} is empty", options.corpus.display()));
+    }
+    if !options.alphabet.is_file() {
+        return Err(format!("{} is not a file", options.alphabet.display()));
+    }
+    if options.order < 2 {
+        return Err("order of the language model must be at least 2".into());
+    }
+
+    let dir = env::temp_dir().join(format!("speech2text-scorer-{}", process::id()));
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to create {}: {}", dir.display(), err))?;
+    let result = build_in(
+        options,
+        &corpus,
+        &dir,
+        [
+            lmplz.as_path(),
+            filter.as_path(),
+            build_binary.as_path(),
+            generate_scorer_package.as_path(),
+        ],
+    );
+    let _ = fs::remove_dir_all(&dir);
+    result?;
+
+    println!(
+        "{}  {}",
+        models::sha256(&options.output)?,
+        options.output.display()
+    );
+    Ok(())
+}
+
+fn build_in(
+    options: &Options,
+    corpus: &str,
+    dir: &Path,
+    [lmplz, filter, build_binary, generate_scorer_package]: [&Path; 4],
+) -> Result<(), String> {
+    let vocabulary = dir.join("vocabulary.txt");
+    write_vocabulary(corpus, options.top_k, &vocabulary)?;
+
+    let arpa = dir.join("lm.arpa");
+    run(
+        "lmplz",
+        Command::new(lmplz)
+            .arg("--order")
+            .arg(options.order.to_string())
+            .arg("--temp_prefix")
+            .arg(dir)
+            .arg("--memory")
+            .arg("50%")
+            .arg("--text")
+            .arg(&options.corpus)
+            .arg("--arpa")
+            .arg(&arpa)
+            .arg("--prune")
+            .args(["0", "0", "1"]),
+    )?;
+
+    let filtered = dir.join("lm_filtered.arpa");
+    let mut model = std::ffi::OsString::from("model:");
+    model.push(&arpa);
+    run(
+        "filter",
+        Command::new(filter)
+            .arg("single")
+            .arg(model)
+            .arg(&filtered)
+            .stdin(Stdio::from(File::open(&vocabulary).map_err(|err| {
+                format!("failed to open {}: {}", vocabulary.display(), err)
+            })?)),
+    )?;
+
+    let binary = dir.join("lm.binary");
+    run(
+        "build_binary",
+        Command::new(build_binary)
+            .args(["-a", "255", "-q", "8", "-v", "trie"])
+            .arg(&filtered)
+            .arg(&binary),
+    )?;
+
+    run(
+        "generate_scorer_package",
+        Command::new(generate_scorer_package)
+            .arg("--alphabet")
+            .arg(&options.alphabet)
+            .arg("--lm")
+            .arg(&binary)
+            .arg("--vocab")
+            .arg(&vocabulary)
+            .arg("--package")
+            .arg(&options.output)
+            .arg("--default_alpha")
+            .arg(options.default_alpha.to_string())
+            .arg("--default_beta")
+            .arg(options.default_beta.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vocabulary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vocabulary.txt");
+        write_vocabulary("b a c\nc b c\nd", 3, &path).unwrap();
+        // Ties are broken alphabetically.
+        assert_eq!(fs::read_to_string(&path).unwrap(), "c\nb\na");
+    }
+
+    #[test]
+    fn missing_tool() {
+        let err = find("speech2text-missing-tool", "nothing").unwrap_err();
+        assert_eq!(
+            err,
+            "`speech2text-missing-tool` not found in PATH, it is part of nothing"
+        );
+        assert!(find("sh", "a shell").is_ok());
+    }
+}
@@ -0,0 +1,609 @@
+use crate::vad::Vad;
+use std::ops::Range;
+use tracing::warn;
+
+/// Number of voiced frames in a typical utterance, which the buffer is sized for.
+const TYPICAL_SPEECH_FRAMES: usize = 500;
+
+/// Buffered speech, padded with silence.
+#[derive(Clone)]
+pub struct Segment {
+    /// Offset of the first sample of the segment from the start of the stream.
+    pub start: u64,
+    pub samples: Vec<i16>,
+    /// Number of leading samples, which the segment shares with the previous one it was split
+    /// from.
+    pub overlap: usize,
+    /// Samples from the start of the first voiced frame to the end of the last one.
+    pub voiced: Range<usize>,
+    /// Number of samples of voiced frames, i.e. excluding pauses within the segment.
+    pub speech_samples: usize,
+}
+
+impl Segment {
+    /// Offset of the sample following the last sample of the segment from the start of the stream.
+    pub fn end(&self) -> u64 {
+        self.start + self.samples.len() as u64
+    }
+
+    /// Offsets of the first voiced sample and of the one following the last voiced sample from
+    /// the start of the stream, excluding the overlap with the previous segment, which speech of
+    /// the previous segment ended in already.
+    pub fn speech(&self) -> Range<u64> {
+        let start = self.voiced.start.max(self.overlap).min(self.voiced.end);
+        self.start + start as u64..self.start + self.voiced.end as u64
+    }
+
+    /// Drops the samples more than `margin` samples before the first voiced frame or after the
+    /// last one, except for the overlap with the previous segment.
+    pub fn trim(&mut self, margin: usize) {
+        // Segments split from the previous one are voiced from their start, so that the overlap
+        // is kept.
+        let start = self.voiced.start.saturating_sub(margin);
+        let end = (self.voiced.end + margin).min(self.samples.len());
+        if start >= end {
+            return;
+        }
+        self.samples.truncate(end);
+        self.samples.drain(..start);
+        self.start += start as u64;
+        self.overlap = self.overlap.saturating_sub(start);
+        self.voiced = self.voiced.start - start..self.voiced.end - start;
+    }
+}
+
+pub enum Step {
+    /// Voice was detected after silence at the given sample offset.
+    SpeechStarted(u64),
+    /// Enough silence followed speech to consider the segment complete.
+    SpeechStopped(Segment),
+    /// Enough silence followed speech, but the segment contained too little speech to decode.
+    Rejected(Segment),
+    /// The segment reached the maximum duration and was split, speech continues in the next one.
+    Split(Segment),
+}
+
+/// How segments reaching the maximum duration are split, in frames.
+#[derive(Clone, Copy)]
+pub struct Split {
+    /// Frames at the end of the segment, before the quietest of which it is split.
+    pub search_frames: usize,
+    /// Frames before the split, which the following segment repeats.
+    pub overlap_frames: usize,
+    /// Frames at the end of the segment, which are carried over into the following one at least.
+    pub min_carry_frames: usize,
+}
+
+/// Splits a stream of audio frames into speech segments using a voice activity detector.
+pub struct Segmenter {
+    vad: Vad,
+    frame_sample_count: usize,
+    min_speech_frames: usize,
+    max_samples: usize,
+    padding_frames: usize,
+    split: Split,
+    buffer: Vec<i16>,
+    /// Number of leading samples of the buffer shared with the previous segment.
+    overlap: usize,
+    /// Samples of the buffer from the start of the first voiced frame to the end of the last one.
+    voiced: Range<usize>,
+    offset: u64,
+    silence_frames: usize,
+    speech_frames: usize,
+    /// Number of voiced frames of the buffered segment.
+    voiced_frames: usize,
+    /// Whether the last frame pushed was voiced.
+    last_voiced: bool,
+}
+
+impl Segmenter {
+    /// Segments with less than `min_speech_frames` voiced frames are rejected.
+    /// Segments are completed as soon as they reach `max_samples`, even if speech continues.
+    /// Segments are padded with up to `padding_frames` silent frames on either side and are
+    /// complete once followed by as many silent frames.
+    /// Segments reaching `max_samples` are split according to `split`.
+    pub fn new(
+        vad: Vad,
+        frame_sample_count: usize,
+        min_speech_frames: usize,
+        max_samples: usize,
+        padding_frames: usize,
+        split: Split,
+    ) -> Self {
+        let capacity = (frame_sample_count * (2 * padding_frames + TYPICAL_SPEECH_FRAMES))
+            .min(max_samples + frame_sample_count * padding_frames);
+        Self {
+            vad,
+            frame_sample_count,
+            min_speech_frames,
+            max_samples,
+            padding_frames,
+            split,
+            buffer: Vec::with_capacity(capacity),
+            overlap: 0,
+            voiced: 0..0,
+            offset: 0,
+            silence_frames: 0,
+            speech_frames: 0,
+            voiced_frames: 0,
+            last_voiced: false,
+        }
+    }
+
+    /// Appends `frame` to the buffer.
+    pub fn push(&mut self, frame: &[i16]) -> Option<Step> {
+        let is_voice = self
+            .vad
+            .is_voice_frame(frame)
+            .expect("Invalid frame received from input stream");
+        self.last_voiced = is_voice;
+        let frame_start = self.offset;
+        self.offset += frame.len() as u64;
+        self.buffer.extend_from_slice(frame);
+
+        if is_voice {
+            self.speech_frames += 1;
+            self.voiced_frames += 1;
+            self.silence_frames = 0;
+            if self.speech_frames == 1 {
+                self.voiced.start = self.buffer.len() - frame.len();
+            }
+            self.voiced.end = self.buffer.len();
+            if self.speech_frames == 1 {
+                return Some(Step::SpeechStarted(frame_start));
+            }
+            if self.buffer.len() >= self.max_samples {
+                warn!(
+                    samples = self.buffer.len(),
+                    "Utterance reached maximum duration, splitting it"
+                );
+                return Some(self.split());
+            }
+            return None;
+        }
+        self.silence_frames += 1;
+        if self.speech_frames == 0 {
+            if self.silence_frames > self.padding_frames {
+                let excess = self.buffer.len() - self.frame_sample_count * self.padding_frames;
+                self.buffer.drain(..excess);
+                self.silence_frames = self.padding_frames;
+            }
+            return None;
+        }
+        if self.silence_frames < self.padding_frames {
+            return None;
+        }
+        if self.speech_frames < self.min_speech_frames {
+            return Some(Step::Rejected(self.take()));
+        }
+        Some(Step::SpeechStopped(self.take()))
+    }
+
+    /// Returns the detector frames are classified with.
+    pub fn vad(&mut self) -> &mut Vad {
+        &mut self.vad
+    }
+
+    /// Returns whether the last frame pushed was voiced.
+    pub fn voiced(&self) -> bool {
+        self.last_voiced
+    }
+
+    /// Returns the offset of the next sample of the stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the offset of the buffer and the buffer itself.
+    pub fn buffered(&self) -> (u64, &[i16]) {
+        (self.offset - self.buffer.len() as u64, &self.buffer)
+    }
+
+    /// Discards `sample_count` samples of the stream along with buffered silence.
+    /// Buffered speech must be flushed before skipping.
+    pub fn skip(&mut self, sample_count: usize) {
+        self.offset += sample_count as u64;
+        self.buffer.clear();
+        self.overlap = 0;
+        self.silence_frames = 0;
+        self.speech_frames = 0;
+        self.voiced_frames = 0;
+    }
+
+    /// Returns the offset of the buffer and the buffer itself, if it contains speech.
+    pub fn pending(&self) -> Option<(u64, &[i16])> {
+        if self.speech_frames == 0 {
+            return None;
+        }
+        Some((self.offset - self.buffer.len() as u64, &self.buffer))
+    }
+
+    /// Returns the buffered segment regardless of trailing silence, if it contains speech.
+    pub fn flush(&mut self) -> Option<Segment> {
+        if self.speech_frames == 0 {
+            return None;
+        }
+        Some(self.take())
+    }
+
+    /// Splits the buffered segment before its quietest frame among the last `search_frames`
+    /// ones, except for the last `min_carry_frames`, keeping the rest along with the last
+    /// `overlap_frames` frames before the split buffered.
+    fn split(&mut self) -> Step {
+        let frames = self.buffer.len() / self.frame_sample_count;
+        let search = self.split.search_frames.min(frames);
+        let last = frames
+            .saturating_sub(self.split.min_carry_frames)
+            .max(frames - search);
+        let split = (frames - search..last)
+            .min_by_key(|i| {
+                self.buffer[i * self.frame_sample_count..(i + 1) * self.frame_sample_count]
+                    .iter()
+                    .map(|&sample| sample as i64 * sample as i64)
+                    .sum::<i64>()
+            })
+            .map_or(frames, |i| i.max(1));
+        if split == frames {
+            return Step::SpeechStopped(self.take());
+        }
+
+        let start = self.offset - self.buffer.len() as u64;
+        let end = split * self.frame_sample_count;
+        let overlap = (self.split.overlap_frames * self.frame_sample_count).min(end);
+        let samples = self.buffer[..end].to_vec();
+        self.buffer.drain(..end - overlap);
+        self.speech_frames = frames - split;
+        // Voiced frames after the split are attributed to the split segment as well.
+        let segment = Segment {
+            start,
+            samples,
+            overlap: self.overlap,
+            voiced: self.voiced.start.min(end)..end,
+            speech_samples: std::mem::take(&mut self.voiced_frames) * self.frame_sample_count,
+        };
+        self.overlap = overlap;
+        // The next segment is voiced from its start, since speech continues through the split.
+        self.voiced = 0..self.voiced.end - (end - overlap);
+        Step::Split(segment)
+    }
+
+    fn take(&mut self) -> Segment {
+        let start = self.offset - self.buffer.len() as u64;
+        let samples = self.buffer.clone();
+        self.buffer.clear();
+        let capacity = self.frame_sample_count * (2 * self.padding_frames + TYPICAL_SPEECH_FRAMES);
+        if self.buffer.capacity() > 2 * capacity {
+            // Release memory retained after an unusually long utterance.
+            self.buffer.shrink_to(capacity);
+        }
+        self.silence_frames = 0;
+        self.speech_frames = 0;
+        let overlap = self.overlap;
+        self.overlap = 0;
+        let voiced = std::mem::replace(&mut self.voiced, 0..0);
+        Segment {
+            start,
+            samples,
+            overlap,
+            voiced,
+            speech_samples: std::mem::take(&mut self.voiced_frames) * self.frame_sample_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Samples of a frame of 30 ms at 16 kHz.
+    const FRAME: usize = 480;
+
+    /// Returns a frame of a 400 Hz square wave of `amplitude`.
+    fn frame(amplitude: i16) -> Vec<i16> {
+        (0..FRAME)
+            .map(|i| {
+                if i / 20 % 2 == 0 {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            })
+            .collect()
+    }
+
+    /// Frames of silence padding segments.
+    const PADDING: usize = 20;
+
+    fn segmenter(min_speech_frames: usize, max_samples: usize) -> Segmenter {
+        splitting_segmenter(min_speech_frames, max_samples, 0, 0)
+    }
+
+    fn splitting_segmenter(
+        min_speech_frames: usize,
+        max_samples: usize,
+        split_search_frames: usize,
+        split_overlap_frames: usize,
+    ) -> Segmenter {
+        Segmenter::new(
+            crate::vad::new(16000, None),
+            FRAME,
+            min_speech_frames,
+            max_samples,
+            PADDING,
+            Split {
+                search_frames: split_search_frames,
+                overlap_frames: split_overlap_frames,
+                min_carry_frames: 0,
+            },
+        )
+    }
+
+    fn split_config() -> Split {
+        Split {
+            search_frames: 20,
+            overlap_frames: 3,
+            min_carry_frames: 2,
+        }
+    }
+
+    /// Pushes `silence` frames of silence, `speech` frames of a burst and trailing silence,
+    /// returning the step that ended the burst.
+    fn burst(segmenter: &mut Segmenter, silence: usize, speech: usize) -> Step {
+        let frames = std::iter::repeat_n(0, silence)
+            .chain(std::iter::repeat_n(8000, speech))
+            .chain(std::iter::repeat_n(0, 100));
+        for amplitude in frames {
+            match segmenter.push(&frame(amplitude)) {
+                Some(Step::SpeechStarted(_)) | None => {}
+                Some(step) => return step,
+            }
+        }
+        panic!("burst was not segmented");
+    }
+
+    #[test]
+    fn speech_samples() {
+        let mut segmenter = segmenter(5, 16000 * 10);
+        let frames = std::iter::repeat_n(0, 30)
+            .chain(std::iter::repeat_n(8000, 30))
+            .chain(std::iter::repeat_n(0, 18))
+            .chain(std::iter::repeat_n(8000, 30))
+            .chain(std::iter::repeat_n(0, 100));
+        let segment = frames
+            .filter_map(|amplitude| match segmenter.push(&frame(amplitude)) {
+                Some(Step::SpeechStopped(segment)) => Some(segment),
+                _ => None,
+            })
+            .next()
+            .expect("burst was not segmented");
+        // The pause within the segment is not counted as speech, only Fvad's hangover is.
+        let frames = segment.speech_samples / FRAME;
+        assert_eq!(segment.speech_samples % FRAME, 0);
+        assert!((60..75).contains(&frames), "{} voiced frames", frames);
+        assert!(segment.speech_samples < segment.voiced.len());
+    }
+
+    #[test]
+    fn speech_stopped() {
+        let mut segmenter = segmenter(5, 16000 * 10);
+        match burst(&mut segmenter, 30, 30) {
+            Step::SpeechStopped(segment) => {
+                // The segment keeps `PADDING` frames of silence around the speech.
+                assert_eq!(segment.start, (30 - PADDING) as u64 * FRAME as u64);
+                assert!(segment.samples.len() >= (30 + 2 * PADDING) * FRAME);
+            }
+            _ => panic!("burst was not segmented as speech"),
+        }
+    }
+
+    #[test]
+    fn trim() {
+        let mut segmenter = segmenter(5, 16000 * 10);
+        let mut segment = match burst(&mut segmenter, 30, 30) {
+            Step::SpeechStopped(segment) => segment,
+            _ => panic!("burst was not segmented as speech"),
+        };
+        // The voiced range covers the burst, but not the silence padding it.
+        assert!(segment.voiced.start <= PADDING * FRAME);
+        assert!(segment.voiced.end >= (PADDING + 30) * FRAME);
+        assert!(segment.voiced.end < segment.samples.len());
+
+        let (start, voiced) = (segment.start, segment.voiced.clone());
+        segment.trim(2 * FRAME);
+        assert_eq!(segment.start, start + (voiced.start - 2 * FRAME) as u64);
+        assert_eq!(segment.voiced, 2 * FRAME..2 * FRAME + voiced.len());
+        assert_eq!(segment.samples.len(), voiced.len() + 4 * FRAME);
+
+        let mut segment = Segment {
+            start: 100,
+            samples: (0..10).collect(),
+            overlap: 3,
+            voiced: 0..6,
+            speech_samples: 6,
+        };
+        // Split segments keep their overlap, which is voiced.
+        segment.trim(1);
+        assert_eq!(segment.start, 100);
+        assert_eq!(segment.samples, (0..7).collect::<Vec<_>>());
+        assert_eq!((segment.overlap, segment.voiced), (3, 0..6));
+    }
+
+    #[test]
+    fn rejected() {
+        let mut segmenter = segmenter(50, 16000 * 10);
+        assert!(matches!(burst(&mut segmenter, 30, 10), Step::Rejected(_)));
+        assert!(segmenter.pending().is_none());
+    }
+
+    #[test]
+    fn max_duration() {
+        let mut segmenter = segmenter(5, 50 * FRAME);
+        match burst(&mut segmenter, 30, 100) {
+            Step::SpeechStopped(segment) => assert_eq!(segment.samples.len(), 50 * FRAME),
+            _ => panic!("burst was not segmented as speech"),
+        }
+        // Speech continues in the next segment.
+        assert!(matches!(
+            segmenter.push(&frame(8000)),
+            Some(Step::SpeechStarted(_))
+        ));
+    }
+
+    #[test]
+    fn split() {
+        let mut segmenter = splitting_segmenter(5, 50 * FRAME, 10, 4);
+        // The last 10 frames are equally loud, so the segment is split before the first of them.
+        let first = match burst(&mut segmenter, 30, 100) {
+            Step::Split(segment) => segment,
+            _ => panic!("burst was not split"),
+        };
+        assert_eq!(first.samples.len(), 40 * FRAME);
+        assert_eq!(first.overlap, 0);
+
+        let second = loop {
+            if let Some(step) = segmenter.push(&frame(8000)) {
+                break step;
+            }
+        };
+        match second {
+            Step::Split(segment) => {
+                // The segment repeats the last 4 frames of the previous one.
+                assert_eq!(segment.overlap, 4 * FRAME);
+                assert_eq!(segment.start, first.start + 36 * FRAME as u64);
+                assert_eq!(segment.samples.len(), 40 * FRAME);
+            }
+            _ => panic!("speech was not split"),
+        }
+    }
+
+    /// Pushes `silence` frames of quiet noise followed by `speech` frames of a loud burst and
+    /// trailing noise, until the burst is segmented.
+    fn noisy_burst(segmenter: &mut Segmenter, silence: usize, speech: usize) -> Segment {
+        for frame in std::iter::repeat_n(frame(30), silence)
+            .chain(std::iter::repeat_n(frame(8000), speech))
+            .chain(std::iter::repeat_n(frame(30), 100))
+        {
+            if let Some(Step::SpeechStopped(segment)) = segmenter.push(&frame) {
+                return segment;
+            }
+        }
+        panic!("burst was not segmented");
+    }
+
+    #[test]
+    fn burst_offsets() {
+        let f = FRAME as u64;
+        let mut segmenter = Segmenter::new(
+            crate::vad::energy(10.0),
+            FRAME,
+            5,
+            16000 * 10,
+            10,
+            split_config(),
+        );
+
+        let mut segment = noisy_burst(&mut segmenter, 25, 30);
+        assert_eq!(segment.start, 15 * f);
+        assert_eq!(segment.speech(), 25 * f..55 * f);
+        assert_eq!(segment.end(), 65 * f);
+        assert_eq!(segment.speech_samples, 30 * FRAME);
+
+        segment.trim(3 * FRAME);
+        assert_eq!(segment.start, 22 * f);
+        assert_eq!(segment.speech(), 25 * f..55 * f);
+        assert_eq!(segment.end(), 58 * f);
+
+        // Offsets of later bursts are relative to the start of the stream.
+        let segment = noisy_burst(&mut segmenter, 40, 20);
+        assert_eq!(segment.start, 95 * f);
+        assert_eq!(segment.speech(), 105 * f..125 * f);
+        assert_eq!(segment.end(), 135 * f);
+    }
+
+    /// Returns the segment split from 10 frames of silence followed by speech, whose frames
+    /// at the indices of the buffer in `dips` are of the given amplitude, once it reaches 100
+    /// frames.
+    fn split_at_dips(dips: &[(usize, i16)]) -> Segment {
+        let mut segmenter = Segmenter::new(
+            crate::vad::energy(10.0),
+            FRAME,
+            5,
+            100 * FRAME,
+            10,
+            split_config(),
+        );
+        for i in 0..200 {
+            let amplitude = match dips.iter().find(|(dip, _)| *dip == i) {
+                Some((_, amplitude)) => *amplitude,
+                None if i < 10 => 30,
+                None => 8000,
+            };
+            match segmenter.push(&frame(amplitude)) {
+                Some(Step::Split(segment)) => {
+                    assert_eq!(
+                        segmenter.buffered().1.len(),
+                        (100 - segment.samples.len() / FRAME + 3) * FRAME
+                    );
+                    return segment;
+                }
+                Some(Step::SpeechStopped(_)) => panic!("segment was not split"),
+                _ => {}
+            }
+        }
+        panic!("segment was not split");
+    }
+
+    #[test]
+    fn split_in_dip() {
+        let segment = split_at_dips(&[(90, 500)]);
+        assert_eq!(segment.start, 0);
+        assert_eq!(segment.samples.len(), 90 * FRAME);
+        assert_eq!(segment.voiced, 10 * FRAME..90 * FRAME);
+
+        // The quietest frame among the last `min_carry_frames` ones is not split at.
+        let segment = split_at_dips(&[(85, 2000), (98, 500)]);
+        assert_eq!(segment.samples.len(), 85 * FRAME);
+
+        // Without a dip, the segment is split at the first quietest frame of the search window.
+        let segment = split_at_dips(&[]);
+        assert_eq!(segment.samples.len(), 80 * FRAME);
+    }
+
+    #[test]
+    fn hour_of_audio() {
+        const SPEECH_FRAMES: usize = 100;
+        const SILENCE_FRAMES: usize = 67;
+        const UTTERANCES: usize = 720;
+        let mut segmenter = segmenter(5, 16000 * 10);
+        let capacity = segmenter.buffer.capacity();
+        let (speech, silence) = (frame(8000), frame(0));
+
+        let started = Instant::now();
+        let mut stopped = 0;
+        for _ in 0..UTTERANCES {
+            for frame in std::iter::repeat_n(&silence, SILENCE_FRAMES)
+                .chain(std::iter::repeat_n(&speech, SPEECH_FRAMES))
+            {
+                if let Some(Step::SpeechStopped(segment)) = segmenter.push(frame) {
+                    assert!(segment.samples.len() >= SPEECH_FRAMES * FRAME);
+                    stopped += 1;
+                }
+                // Buffering never reallocates.
+                assert_eq!(segmenter.buffer.capacity(), capacity);
+            }
+        }
+        for frame in std::iter::repeat_n(&silence, SILENCE_FRAMES) {
+            if let Some(Step::SpeechStopped(_)) = segmenter.push(frame) {
+                stopped += 1;
+            }
+        }
+        println!("Segmented an hour of audio in {:?}", started.elapsed());
+        assert_eq!(stopped, UTTERANCES);
+        let (offset, buffer) = segmenter.buffered();
+        assert_eq!(
+            offset + buffer.len() as u64,
+            (UTTERANCES * (SPEECH_FRAMES + SILENCE_FRAMES) + SILENCE_FRAMES) as u64 * FRAME as u64
+        );
+    }
+}
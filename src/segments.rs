@@ -0,0 +1,225 @@
+use crate::pipeline::CHANNELS;
+use crate::recordings;
+use crate::segmenter::Segment;
+use crate::timestamp::hms;
+use crate::transcript::{self, Format};
+use serde_json::{json, Value};
+use std::{
+    fmt::Write,
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// Text of the cues of subtitles of segments.
+const CUE: &str = "[speech]";
+
+/// Renders the boundaries of `segments` of audio at `sample_rate` in `format`, excluding the
+/// overlap with the previous segment, as transcripts do.
+/// Text has a line per segment of its start, end and duration in seconds separated by tabs.
+/// `len` is the number of samples of the audio, which TextGrids span.
+pub fn render(segments: &[Segment], sample_rate: u32, len: usize, format: Format) -> String {
+    let duration = |offset: u64| Duration::from_millis(offset * 1000 / sample_rate as u64);
+    let bounds = segments.iter().map(|segment| {
+        let start = segment.start + segment.overlap as u64;
+        (
+            start,
+            segment.end(),
+            duration(start),
+            duration(segment.end()),
+        )
+    });
+    let mut out = String::new();
+    match format {
+        Format::Txt => {
+            for (_, _, start, end) in bounds {
+                let _ = writeln!(
+                    out,
+                    "{:.3}\t{:.3}\t{:.3}",
+                    start.as_secs_f64(),
+                    end.as_secs_f64(),
+                    (end - start).as_secs_f64()
+                );
+            }
+        }
+        Format::Srt => {
+            for (i, (_, _, start, end)) in bounds.enumerate() {
+                let _ = write!(
+                    out,
+                    "{}\n{} --> {}\n{}\n\n",
+                    i + 1,
+                    hms(start, ','),
+                    hms(end, ','),
+                    CUE
+                );
+            }
+        }
+        Format::Vtt => {
+            out.push_str("WEBVTT\n\n");
+            for (_, _, start, end) in bounds {
+                let _ = write!(
+                    out,
+                    "{} --> {}\n{}\n\n",
+                    hms(start, '.'),
+                    hms(end, '.'),
+                    CUE
+                );
+            }
+        }
+        Format::Audacity => {
+            for (_, _, start, end) in bounds {
+                out.push_str(&transcript::label(start, end, "speech"));
+            }
+        }
+        Format::Textgrid => {
+            out = transcript::textgrid(
+                "segments",
+                bounds.map(|(_, _, start, end)| (start, end, "speech")),
+                duration(len as u64),
+            )
+        }
+        Format::Markdown => {
+            out = transcript::notes_header(
+                "Speech segments",
+                &transcript::Details {
+                    duration: duration(len as u64),
+                    date: SystemTime::now(),
+                    model: None,
+                    cues: transcript::Cues::default(),
+                },
+            );
+            for (_, _, start, end) in bounds {
+                let _ = writeln!(
+                    out,
+                    "- **[{}]** {:.3}s",
+                    transcript::clock_time(start),
+                    (end - start).as_secs_f64()
+                );
+            }
+        }
+        Format::Ffmetadata => {
+            out = transcript::ffmetadata(
+                bounds
+                    .enumerate()
+                    .map(|(i, (_, _, start, end))| (start, end, format!("Speech {}", i + 1))),
+            )
+        }
+        Format::Json => {
+            let segments: Vec<Value> = bounds
+                .map(|(start_sample, end_sample, start, end)| {
+                    json!({
+                        "start_ms": start.as_millis() as u64,
+                        "end_ms": end.as_millis() as u64,
+                        "duration_ms": (end - start).as_millis() as u64,
+                        "start_sample": start_sample,
+                        "end_sample": end_sample,
+                    })
+                })
+                .collect();
+            out = Value::Array(segments).to_string();
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Writes every segment of `segments` at `sample_rate` to a numbered WAV file in `dir`, starting
+/// with `segment-0001.wav`, including its padding and overlap.
+pub fn save(dir: &Path, segments: &[Segment], sample_rate: u32) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|err| format!("failed to create {}: {}", dir.display(), err))?;
+    let spec = hound::WavSpec {
+        channels: CHANNELS,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    for (i, segment) in segments.iter().enumerate() {
+        recordings::write(
+            &dir.join(format!("segment-{:04}.wav", i + 1)),
+            spec,
+            recordings::Format::Wav,
+            &segment.samples,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Segments at 1 kHz, the second of which overlaps the first by 500 samples.
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment {
+                start: 500,
+                samples: vec![1; 1000],
+                overlap: 0,
+                voiced: 0..1000,
+                speech_samples: 1000,
+            },
+            Segment {
+                start: 1000,
+                samples: vec![2; 2250],
+                overlap: 500,
+                voiced: 0..2250,
+                speech_samples: 2250,
+            },
+        ]
+    }
+
+    #[test]
+    fn render_formats() {
+        let segments = segments();
+        assert_eq!(
+            render(&segments, 1000, 4000, Format::Txt),
+            "0.500\t1.500\t1.000\n1.500\t3.250\t1.750\n"
+        );
+        assert_eq!(
+            render(&segments, 1000, 4000, Format::Srt),
+            "1\n00:00:00,500 --> 00:00:01,500\n[speech]\n\n\
+             2\n00:00:01,500 --> 00:00:03,250\n[speech]\n\n"
+        );
+        assert_eq!(
+            render(&segments, 1000, 4000, Format::Vtt),
+            "WEBVTT\n\n\
+             00:00:00.500 --> 00:00:01.500\n[speech]\n\n\
+             00:00:01.500 --> 00:00:03.250\n[speech]\n\n"
+        );
+        let json: Value =
+            serde_json::from_str(&render(&segments, 1000, 4000, Format::Json)).unwrap();
+        assert_eq!(
+            json[1],
+            json!({
+                "start_ms": 1500,
+                "end_ms": 3250,
+                "duration_ms": 1750,
+                "start_sample": 1500,
+                "end_sample": 3250,
+            })
+        );
+        assert_eq!(
+            render(&segments, 1000, 4000, Format::Audacity),
+            "0.500000\t1.500000\tspeech\n1.500000\t3.250000\tspeech\n"
+        );
+        let textgrid = render(&segments, 1000, 4000, Format::Textgrid);
+        assert!(textgrid.contains("xmax = 4\n"), "{}", textgrid);
+        assert!(textgrid.contains("intervals: size = 4\n"), "{}", textgrid);
+        assert_eq!(render(&[], 1000, 0, Format::Txt), "");
+    }
+
+    #[test]
+    fn save_numbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("segments");
+        save(&out, &segments(), 1000).unwrap();
+        let mut reader = hound::WavReader::open(out.join("segment-0002.wav")).unwrap();
+        assert_eq!(reader.spec().sample_rate, 1000);
+        // The overlap is saved along with the rest of the segment.
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, vec![2; 2250]);
+        assert!(out.join("segment-0001.wav").exists());
+        assert!(!out.join("segment-0003.wav").exists());
+    }
+}
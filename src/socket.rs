@@ -0,0 +1,153 @@
+use crate::output::{Event, Sink};
+use serde_json::json;
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing::warn;
+
+/// Maximum time a write to a single client may take before the client is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Parses octal file mode, e.g. `0660`.
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s.trim_start_matches("0o"), 8)
+        .map_err(|err| format!("failed to parse `{}` into octal file mode: {}", s, err))
+}
+
+/// Broadcasts final transcripts as newline-delimited JSON to all clients connected to a Unix
+/// domain socket. The socket file is removed when the broadcaster is dropped.
+pub struct Broadcaster {
+    path: PathBuf,
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+}
+
+impl Broadcaster {
+    /// Listens on `path`, replacing a stale socket if present.
+    pub fn bind(path: PathBuf, mode: Option<u32>) -> io::Result<Self> {
+        match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => fs::remove_file(&path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let listener = UnixListener::bind(&path)?;
+        if let Some(mode) = mode {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+        }
+
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = clients.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream.and_then(|stream| {
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    Ok(stream)
+                }) {
+                    Ok(stream) => accepted
+                        .lock()
+                        .expect("Failed to lock socket clients")
+                        .push(stream),
+                    Err(err) => warn!(error = %err, "Failed to accept socket client"),
+                }
+            }
+        });
+        Ok(Self { path, clients })
+    }
+}
+
+impl Sink for Broadcaster {
+    fn send(&mut self, event: &Event) {
+        let line = match event {
+            Event::Final(utterance) => format!("{}\n", utterance.to_json()),
+            Event::Mark(at, label) => format!(
+                "{}\n",
+                json!({ "event": "mark", "label": label, "offset_ms": at.as_millis() as u64 })
+            ),
+            Event::Heartbeat(heartbeat) => format!("{}\n", heartbeat.to_json()),
+            _ => return,
+        };
+        // Clients which went away (EPIPE) or are too slow to read are disconnected.
+        self.clients
+            .lock()
+            .expect("Failed to lock socket clients")
+            .retain(|mut client| client.write_all(line.as_bytes()).is_ok());
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!(path = %self.path.display(), error = %err, "Failed to remove socket");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn mode() {
+        assert_eq!(parse_mode("0660"), Ok(0o660));
+        assert_eq!(parse_mode("0o600"), Ok(0o600));
+        assert!(parse_mode("0980").is_err());
+    }
+
+    #[test]
+    fn broadcast() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech2text.sock");
+        let mut broadcaster = Broadcaster::bind(path.clone(), Some(0o600)).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let client = UnixStream::connect(&path).unwrap();
+        while broadcaster.clients.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        broadcaster.send(&Event::SpeechStarted(Duration::from_secs(1)));
+        broadcaster.send(&Event::Final(Utterance {
+            confidence: 0.5,
+            start: Duration::from_secs(1),
+            end: Duration::from_secs(2),
+            ..Utterance::test("hello world")
+        }));
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["text"], "hello world");
+        assert_eq!(json["start_ms"], 1000);
+
+        drop(broadcaster);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn bind_replaces_only_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech2text.sock");
+        drop(UnixListener::bind(&path).unwrap());
+        let broadcaster = Broadcaster::bind(path.clone(), None).unwrap();
+        drop(broadcaster);
+
+        fs::write(&path, "").unwrap();
+        let err = Broadcaster::bind(path.clone(), None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::output::{Event, Sink};
+use crate::segmenter::Segment;
+use std::collections::VecDeque;
+
+/// Replaces partial transcripts with stable partials, which hold the prefix of words unchanged
+/// across the last partial transcripts, before forwarding events to `sink`. The prefix is only
+/// ever extended until the final transcript replaces it, so that it may be rendered append-only,
+/// while the tail of the transcript, which changes with every partial transcript, is held back.
+pub struct Stabilize<S> {
+    count: usize,
+    /// Words of the last `count` partial transcripts of the utterance in progress.
+    recent: VecDeque<Vec<String>>,
+    /// Words of the last stable partial sent.
+    stable: Vec<String>,
+    sink: S,
+}
+
+impl<S> Stabilize<S> {
+    /// Considers words stable once they remained unchanged across `count` partial transcripts in
+    /// a row.
+    pub fn new(count: usize, sink: S) -> Self {
+        Self {
+            count,
+            recent: VecDeque::with_capacity(count),
+            stable: Vec::new(),
+            sink,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.recent.clear();
+        self.stable.clear();
+    }
+
+    /// Returns the words common to the start of all recent partial transcripts, if they extend
+    /// the stable prefix sent.
+    fn extended(&self) -> Option<&[String]> {
+        if self.recent.len() < self.count {
+            return None;
+        }
+        let mut recent = self.recent.iter();
+        let first = recent.next()?;
+        let common = recent.fold(first.len(), |common, words| {
+            first
+                .iter()
+                .zip(words.iter())
+                .take(common)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        let words = &first[..common];
+        // A revision of words already sent is not followed, since they cannot be taken back.
+        if common > self.stable.len() && words.starts_with(&self.stable) {
+            Some(words)
+        } else {
+            None
+        }
+    }
+}
+
+impl<S: Sink> Sink for Stabilize<S> {
+    fn send(&mut self, event: &Event) {
+        let utterance = match event {
+            Event::Partial(utterance) => utterance,
+            Event::SpeechStarted(_) | Event::Final(_) | Event::Dropped(_) => {
+                self.reset();
+                return self.sink.send(event);
+            }
+            event => return self.sink.send(event),
+        };
+        if self.recent.len() == self.count {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(
+            utterance
+                .text
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        );
+        let words = match self.extended() {
+            Some(words) => words.to_vec(),
+            None => return,
+        };
+        let mut utterance = utterance.clone();
+        utterance.text = words.join(" ");
+        self.stable = words;
+        self.sink.send(&Event::StablePartial(utterance))
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        self.sink.samples(samples)
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        self.sink.segment(segment)
+    }
+
+    fn finish(&mut self) {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::time::Duration;
+
+    /// Describes the events sent.
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn send(&mut self, event: &Event) {
+            self.0.push(match event {
+                Event::Partial(utterance) => format!("partial: {}", utterance.text),
+                Event::StablePartial(utterance) => format!("stable: {}", utterance.text),
+                Event::Final(utterance) => format!("final: {}", utterance.text),
+                Event::SpeechStarted(_) => "started".into(),
+                _ => "other".into(),
+            })
+        }
+    }
+
+    fn partial(text: &str) -> Event {
+        Event::Partial(Utterance::test(text))
+    }
+
+    #[test]
+    fn extends_prefix() {
+        let mut stabilize = Stabilize::new(2, Events::default());
+        stabilize.send(&Event::SpeechStarted(Duration::default()));
+        stabilize.send(&partial("the"));
+        stabilize.send(&partial("the cat"));
+        stabilize.send(&partial("the cat sad"));
+        stabilize.send(&partial("the cat sat on"));
+        // Revisions of words after the stable prefix are held back.
+        stabilize.send(&partial("the cat sat in"));
+        stabilize.send(&partial("the cat sat in the"));
+        stabilize.send(&Event::Final(Utterance::test("the cat sat in the hat")));
+        assert_eq!(
+            stabilize.sink.0,
+            [
+                "started",
+                "stable: the",
+                "stable: the cat",
+                "stable: the cat sat",
+                "stable: the cat sat in",
+                "final: the cat sat in the hat",
+            ]
+        );
+    }
+
+    #[test]
+    fn revised_prefix() {
+        let mut stabilize = Stabilize::new(2, Events::default());
+        stabilize.send(&partial("a cat"));
+        stabilize.send(&partial("a cat"));
+        // Words sent already cannot be taken back, so that nothing follows a revision of them.
+        stabilize.send(&partial("the cat sat"));
+        stabilize.send(&partial("the cat sat"));
+        stabilize.send(&Event::Final(Utterance::test("the cat sat")));
+        // The next utterance starts over.
+        stabilize.send(&partial("hello"));
+        stabilize.send(&partial("hello world"));
+        assert_eq!(
+            stabilize.sink.0,
+            ["stable: a cat", "final: the cat sat", "stable: hello"]
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::output::{Event, Sink};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{error, info};
+
+/// Returns the speaking rate of `words` within `speech` in words per minute.
+fn words_per_minute(words: usize, speech: Duration) -> f64 {
+    if speech == Duration::default() {
+        return 0.0;
+    }
+    words as f64 * 60.0 / speech.as_secs_f64()
+}
+
+#[derive(Default)]
+struct Totals {
+    words: usize,
+    speech: Duration,
+    /// End of the previous final utterance.
+    previous: Duration,
+    longest_silence: Duration,
+}
+
+/// Tracks the speaking rate of final utterances by the duration of voice detected in them,
+/// rather than their duration, logging it per utterance. The totals are summarized once the
+/// pipeline is done.
+#[derive(Clone, Default)]
+pub struct Speech(Arc<Mutex<Totals>>);
+
+impl Speech {
+    /// Logs the total words and speech, the average speaking rate and the longest silence
+    /// between utterances, if there were any.
+    pub fn log_summary(&self) {
+        let totals = self.0.lock().expect("Failed to lock speech statistics");
+        if totals.words == 0 {
+            return;
+        }
+        info!(
+            words = totals.words,
+            speech_secs = totals.speech.as_secs_f64(),
+            words_per_minute = words_per_minute(totals.words, totals.speech),
+            longest_silence_secs = totals.longest_silence.as_secs_f64(),
+            "Speech statistics"
+        );
+    }
+}
+
+impl Sink for Speech {
+    fn send(&mut self, event: &Event) {
+        let utterance = match event {
+            Event::Final(utterance) => utterance,
+            _ => return,
+        };
+        let words = utterance.text.split_whitespace().count();
+        let mut totals = self.0.lock().expect("Failed to lock speech statistics");
+        totals.words += words;
+        totals.speech += utterance.speech;
+        let silence = utterance
+            .start
+            .checked_sub(totals.previous)
+            .unwrap_or_default();
+        totals.longest_silence = totals.longest_silence.max(silence);
+        totals.previous = totals.previous.max(utterance.end);
+        info!(
+            seq = utterance.seq,
+            words,
+            speech_ms = utterance.speech.as_millis() as u64,
+            words_per_minute = words_per_minute(words, utterance.speech),
+            "Speaking rate"
+        );
+    }
+}
+
+/// Upper bounds of the buckets of the histogram of segment durations, in milliseconds, followed
+/// by a bucket of longer segments. They are fixed, so that runs are comparable.
+const SEGMENT_BUCKETS_MS: &[u64] = &[250, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 30_000];
+
+/// Upper bounds of the buckets of the histogram of silence between segments, in milliseconds,
+/// followed by a bucket of longer silence.
+const SILENCE_BUCKETS_MS: &[u64] = &[100, 250, 500, 1_000, 2_000, 5_000, 10_000, 30_000, 60_000];
+
+/// Counts of durations within fixed buckets.
+struct Histogram {
+    bounds: &'static [u64],
+    /// Count per bucket, the last one counting durations above all bounds.
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// Returns the buckets with their upper bound as `le_ms`, which is `null` for the last one.
+    fn to_json(&self) -> Value {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| json!({ "le_ms": self.bounds.get(i), "count": count }))
+            .collect()
+    }
+}
+
+/// Statistics of voice activity detection over a session, which are written as JSON once it
+/// ends, e.g. to tune the detector on live audio without a reference.
+pub struct Vad {
+    frames: u64,
+    voiced: u64,
+    segments: u64,
+    rejected: u64,
+    durations: Histogram,
+    silences: Histogram,
+    /// End of the previous segment.
+    previous: Option<Duration>,
+}
+
+impl Default for Vad {
+    fn default() -> Self {
+        Self {
+            frames: 0,
+            voiced: 0,
+            segments: 0,
+            rejected: 0,
+            durations: Histogram::new(SEGMENT_BUCKETS_MS),
+            silences: Histogram::new(SILENCE_BUCKETS_MS),
+            previous: None,
+        }
+    }
+}
+
+impl Vad {
+    /// Records the verdict of a frame.
+    pub fn frame(&mut self, voiced: bool) {
+        self.frames += 1;
+        self.voiced += voiced as u64;
+    }
+
+    /// Records a segment from `start` to `end`, which was `rejected` for containing too little
+    /// speech, and the silence since the previous one. Segments continuing a split one follow no
+    /// silence.
+    pub fn segment(&mut self, start: Duration, end: Duration, rejected: bool) {
+        self.segments += 1;
+        self.rejected += rejected as u64;
+        self.durations
+            .add(end.checked_sub(start).unwrap_or_default());
+        match self.previous {
+            Some(previous) if start > previous => self.silences.add(start - previous),
+            _ => {}
+        }
+        self.previous = Some(end);
+    }
+
+    /// Writes the statistics along with the `settings` of the detector to `path` as JSON.
+    /// Failures are logged.
+    pub fn write(&self, path: &Path, settings: Value) {
+        let stats = json!({
+            "frames": self.frames,
+            "voiced_frames": self.voiced,
+            "segments": self.segments,
+            "rejected_segments": self.rejected,
+            "segment_durations": self.durations.to_json(),
+            "silence_durations": self.silences.to_json(),
+            "settings": settings,
+        });
+        match fs::write(path, format!("{:#}\n", stats)) {
+            Ok(()) => info!(path = %path.display(), "Wrote VAD statistics"),
+            Err(err) => {
+                error!(path = %path.display(), error = %err, "Failed to write VAD statistics")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+
+    fn utterance(text: &str, start: u64, end: u64, speech: u64) -> Event {
+        Event::Final(Utterance {
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+            speech: Duration::from_secs(speech),
+            ..Utterance::test(text)
+        })
+    }
+
+    #[test]
+    fn rate() {
+        assert_eq!(words_per_minute(10, Duration::from_secs(4)), 150.0);
+        assert_eq!(words_per_minute(10, Duration::default()), 0.0);
+    }
+
+    #[test]
+    fn totals() {
+        let mut speech = Speech::default();
+        speech.send(&utterance("one two three", 2, 5, 2));
+        speech.send(&Event::Partial(Utterance::test("ignored words")));
+        speech.send(&utterance("four", 12, 14, 1));
+        speech.send(&utterance("five six", 16, 18, 1));
+        let totals = speech.0.lock().unwrap();
+        assert_eq!(totals.words, 6);
+        assert_eq!(totals.speech, Duration::from_secs(4));
+        assert_eq!(totals.longest_silence, Duration::from_secs(7));
+        assert_eq!(totals.previous, Duration::from_secs(18));
+    }
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::new(&[100, 250]);
+        for ms in [0, 100, 101, 250, 251, 10_000] {
+            histogram.add(Duration::from_millis(ms));
+        }
+        assert_eq!(
+            histogram.to_json(),
+            json!([
+                { "le_ms": 100, "count": 2 },
+                { "le_ms": 250, "count": 2 },
+                { "le_ms": null, "count": 2 },
+            ])
+        );
+    }
+
+    #[test]
+    fn vad() {
+        let mut vad = Vad::default();
+        for voiced in [false, true, true, false] {
+            vad.frame(voiced);
+        }
+        let secs = Duration::from_secs;
+        vad.segment(secs(1), secs(3), false);
+        // Continues the previous segment, which was split.
+        vad.segment(secs(3), secs(4), false);
+        vad.segment(Duration::from_millis(4_300), secs(40), true);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vad.json");
+        vad.write(&path, json!({ "vad": "fvad" }));
+        let stats: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(stats["frames"], 4);
+        assert_eq!(stats["voiced_frames"], 2);
+        assert_eq!(stats["segments"], 3);
+        assert_eq!(stats["rejected_segments"], 1);
+        assert_eq!(stats["settings"], json!({ "vad": "fvad" }));
+        let counts = |name: &str| -> Vec<u64> {
+            stats[name]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|bucket| bucket["count"].as_u64().unwrap())
+                .collect()
+        };
+        assert_eq!(counts("segment_durations"), [0, 0, 1, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(counts("silence_durations"), [0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::metrics;
+use crate::output::{Event, Sink};
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::atomic::Ordering,
+};
+
+/// Number of most recent words displayed in the status bar.
+const WORDS: usize = 6;
+
+/// Number of most recent transcripts displayed in the tooltip.
+const TOOLTIP_TRANSCRIPTS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Listening,
+    Paused,
+    Speech,
+}
+
+impl State {
+    fn class(self) -> &'static str {
+        match self {
+            Self::Listening => "listening",
+            Self::Paused => "paused",
+            Self::Speech => "speech",
+        }
+    }
+}
+
+/// Prints a line of JSON in the format of waybar custom modules, which i3blocks can read as well,
+/// whenever the state changes or a transcript is output.
+/// `text` holds the last words heard, `tooltip` the most recent transcripts and `class` one of
+/// `listening`, `paused` or `speech`, while speech is detected.
+pub struct Statusbar {
+    state: State,
+    transcripts: VecDeque<String>,
+}
+
+impl Default for Statusbar {
+    fn default() -> Self {
+        Self {
+            state: State::Listening,
+            transcripts: VecDeque::with_capacity(TOOLTIP_TRANSCRIPTS),
+        }
+    }
+}
+
+impl Statusbar {
+    /// Returns the JSON line describing the current state.
+    fn line(&self) -> Value {
+        let words: Vec<&str> = self
+            .transcripts
+            .iter()
+            .rev()
+            .flat_map(|text| text.split_whitespace().rev())
+            .take(WORDS)
+            .collect();
+        let mut text: Vec<&str> = words.into_iter().rev().collect();
+        if self.state == State::Paused {
+            text.insert(0, "⏸");
+        }
+        json!({
+            "text": text.join(" "),
+            "tooltip": self.transcripts.iter().cloned().collect::<Vec<_>>().join("\n"),
+            "class": self.state.class(),
+            "alt": self.state.class(),
+        })
+    }
+
+    fn print(&self) {
+        let line = self.line();
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        // Failing to write to the bar is not worth interrupting transcription for.
+        let _ = writeln!(stdout, "{}", line);
+        let _ = stdout.flush();
+    }
+
+    fn set(&mut self, state: State) {
+        if self.state != state {
+            self.state = state;
+            self.print()
+        }
+    }
+}
+
+impl Sink for Statusbar {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::Ready => self.print(),
+            Event::SpeechStarted(_) => self.set(State::Speech),
+            Event::SpeechStopped(_) => self.set(State::Listening),
+            Event::Final(utterance) if !utterance.text.is_empty() => {
+                if self.transcripts.len() == TOOLTIP_TRANSCRIPTS {
+                    self.transcripts.pop_front();
+                }
+                self.transcripts.push_back(utterance.text.clone());
+                self.print()
+            }
+            _ => {}
+        }
+    }
+
+    /// Polls the pause state, which is shared with the other controls.
+    fn samples(&mut self, _samples: &[i16]) {
+        let paused = metrics::PAUSED.load(Ordering::Relaxed);
+        if paused {
+            self.set(State::Paused)
+        } else if self.state == State::Paused {
+            self.set(State::Listening)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::time::Duration;
+
+    #[test]
+    fn line() {
+        let mut statusbar = Statusbar::default();
+        assert_eq!(
+            statusbar.line(),
+            json!({"text": "", "tooltip": "", "class": "listening", "alt": "listening"})
+        );
+
+        statusbar.send(&Event::SpeechStarted(Duration::default()));
+        assert_eq!(statusbar.line()["class"], "speech");
+        for text in &["one two three", "", "four five", "six seven eight"] {
+            statusbar.send(&Event::Final(Utterance::test(text)));
+        }
+        statusbar.send(&Event::SpeechStopped(Duration::default()));
+        let line = statusbar.line();
+        // The last words span transcripts, empty ones are skipped.
+        assert_eq!(line["text"], "three four five six seven eight");
+        assert_eq!(line["tooltip"], "one two three\nfour five\nsix seven eight");
+        assert_eq!(line["class"], "listening");
+
+        for i in 0..TOOLTIP_TRANSCRIPTS {
+            statusbar.send(&Event::Final(Utterance::test(&i.to_string())));
+        }
+        assert_eq!(statusbar.line()["tooltip"], "0\n1\n2\n3\n4");
+
+        statusbar.state = State::Paused;
+        assert_eq!(statusbar.line()["text"], "⏸ 0 1 2 3 4");
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+/// Ratio of voiced frames within a window, above which voice activity detection is considered
+/// stuck open.
+const MAX_VOICED_RATIO: f64 = 0.98;
+
+/// Duration of the windows the ratio of voiced frames is computed over.
+pub const WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Multiple of the maximum utterance duration, which a run of voiced frames needs to exceed for
+/// voice activity detection to be considered stuck open.
+pub const RUN_UTTERANCES: u32 = 4;
+
+/// Detects voice activity detection classifying every frame as voiced, e.g. because background
+/// noise of a changed room is mistaken for speech, in which case nothing is transcribed, since
+/// speech never stops, while the pipeline appears healthy.
+pub struct Watchdog {
+    frame: Duration,
+    /// Voiced frames in a row, beyond which detection is stuck.
+    max_run: usize,
+    /// Frames per window.
+    window: usize,
+    run: usize,
+    /// Voiced frames and all frames of the current window.
+    voiced: usize,
+    frames: usize,
+    /// Whether detection was reported stuck, which is reported once until a window passes with
+    /// enough silence.
+    stuck: bool,
+}
+
+impl Watchdog {
+    /// Considers detection of frames of `frame` duration stuck once more than `max_run` of them
+    /// in a row or more than 98% of those within `window` are voiced.
+    pub fn new(frame: Duration, max_run: Duration, window: Duration) -> Self {
+        let frames = |duration: Duration| (duration.as_micros() / frame.as_micros()) as usize;
+        Self {
+            frame,
+            max_run: frames(max_run).max(1),
+            window: frames(window).max(1),
+            run: 0,
+            voiced: 0,
+            frames: 0,
+            stuck: false,
+        }
+    }
+
+    /// Records the verdict of a frame, returning why detection is stuck, once it got stuck.
+    pub fn frame(&mut self, voiced: bool) -> Option<String> {
+        self.frames += 1;
+        if voiced {
+            self.run += 1;
+            self.voiced += 1;
+        } else {
+            self.run = 0;
+        }
+        let mut reason = None;
+        if self.run > self.max_run {
+            reason = Some(format!(
+                "voice was detected for {}s without a pause",
+                (self.frame * self.run as u32).as_secs()
+            ));
+        }
+        if self.frames == self.window {
+            let ratio = self.voiced as f64 / self.frames as f64;
+            if ratio > MAX_VOICED_RATIO {
+                reason = Some(format!(
+                    "voice was detected in {:.1}% of the last {}s",
+                    ratio * 100.0,
+                    (self.frame * self.window as u32).as_secs()
+                ));
+            } else if reason.is_none() {
+                // Recovered, so that getting stuck again is reported again.
+                self.stuck = false;
+            }
+            self.voiced = 0;
+            self.frames = 0;
+        }
+        match reason {
+            Some(reason) if !self.stuck => {
+                self.stuck = true;
+                Some(reason)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether detection is stuck open.
+    pub fn stuck(&self) -> bool {
+        self.stuck
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn run() {
+        let mut watchdog = Watchdog::new(FRAME, Duration::from_secs(1), Duration::from_secs(3600));
+        for _ in 0..100 {
+            assert_eq!(watchdog.frame(true), None);
+        }
+        assert_eq!(
+            watchdog.frame(true).as_deref(),
+            Some("voice was detected for 1s without a pause")
+        );
+        assert!(watchdog.stuck());
+        // Reported once only, even after a pause, until a window passes with enough silence.
+        assert_eq!(watchdog.frame(false), None);
+        for _ in 0..200 {
+            assert_eq!(watchdog.frame(true), None);
+        }
+        assert!(watchdog.stuck());
+    }
+
+    #[test]
+    fn window() {
+        let mut watchdog = Watchdog::new(FRAME, Duration::from_secs(60), Duration::from_secs(1));
+        let mut reasons = Vec::new();
+        for i in 0..100 {
+            reasons.extend(watchdog.frame(i != 50));
+        }
+        assert_eq!(reasons, ["voice was detected in 99.0% of the last 1s"]);
+        assert!(watchdog.stuck());
+        for i in 0..100 {
+            assert_eq!(watchdog.frame(i % 2 == 0), None);
+        }
+        assert!(!watchdog.stuck());
+        let reasons: Vec<_> = (0..100).filter_map(|_| watchdog.frame(true)).collect();
+        assert_eq!(reasons, ["voice was detected in 100.0% of the last 1s"]);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::metrics;
+use crate::output::{Event, Sink};
+use sd_notify::NotifyState;
+use std::{
+    env,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Maximum interval at which the status is updated.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends `state` to the service manager.
+pub fn notify(state: NotifyState) {
+    if let Err(err) = sd_notify::notify(false, &[state]) {
+        warn!(error = %err, "Failed to notify systemd");
+    }
+}
+
+/// Reports readiness and status of the pipeline to systemd and pings its watchdog, if enabled,
+/// while frames are processed, so that a stalled pipeline is restarted.
+pub struct Notifier {
+    /// Interval at which the watchdog is pinged and the status is updated.
+    interval: Duration,
+    watchdog: bool,
+    checked: Instant,
+    utterances: u64,
+    status: String,
+}
+
+impl Notifier {
+    /// Returns a notifier, if the process was started by systemd with notification enabled.
+    pub fn from_env() -> Option<Self> {
+        env::var_os("NOTIFY_SOCKET")?;
+        let mut usec = 0;
+        let watchdog = sd_notify::watchdog_enabled(false, &mut usec);
+        let interval = if watchdog {
+            // Ping twice per timeout, as recommended by sd_watchdog_enabled(3).
+            (Duration::from_micros(usec) / 2).min(STATUS_INTERVAL)
+        } else {
+            STATUS_INTERVAL
+        };
+        Some(Self {
+            interval,
+            watchdog,
+            checked: Instant::now(),
+            utterances: 0,
+            status: String::new(),
+        })
+    }
+
+    fn update_status(&mut self) {
+        let state = if metrics::PAUSED.load(Ordering::Relaxed) {
+            "Paused"
+        } else {
+            "Listening"
+        };
+        let status = format!("{}, {} utterances transcribed", state, self.utterances);
+        if status != self.status {
+            notify(NotifyState::Status(&status));
+            self.status = status;
+        }
+    }
+}
+
+impl Sink for Notifier {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::Ready => notify(NotifyState::Ready),
+            Event::Final(_) => self.utterances += 1,
+            _ => return,
+        }
+        self.update_status()
+    }
+
+    fn samples(&mut self, _samples: &[i16]) {
+        if self.checked.elapsed() < self.interval {
+            return;
+        }
+        self.checked = Instant::now();
+        if self.watchdog {
+            notify(NotifyState::Watchdog);
+        }
+        self.update_status();
+    }
+
+    fn finish(&mut self) {
+        notify(NotifyState::Stopping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn notifications() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        env::set_var("NOTIFY_SOCKET", &path);
+        let mut notifier = Notifier::from_env().unwrap();
+        let receive = || {
+            let mut buf = [0; 256];
+            let n = socket.recv(&mut buf).unwrap();
+            String::from_utf8_lossy(&buf[..n]).trim_end().to_string()
+        };
+
+        notifier.send(&Event::Ready);
+        assert_eq!(receive(), "READY=1");
+        assert_eq!(receive(), "STATUS=Listening, 0 utterances transcribed");
+        notifier.send(&Event::Final(Utterance::test("hello")));
+        assert_eq!(receive(), "STATUS=Listening, 1 utterances transcribed");
+        notifier.finish();
+        assert_eq!(receive(), "STOPPING=1");
+        env::remove_var("NOTIFY_SOCKET");
+    }
+}
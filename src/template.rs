@@ -0,0 +1,181 @@
+use crate::clock::{self, DateTime};
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+/// Conversions supported in templates.
+const CONVERSIONS: &str = "YmdHMSj%";
+
+/// Path containing strftime-style placeholders, e.g. `transcripts/%Y-%m-%d.txt`, which are
+/// expanded in the local time zone: %Y, %m, %d, %H, %M, %S, %j and %%.
+#[derive(Clone, Debug)]
+pub struct Template(String);
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some(conversion) if CONVERSIONS.contains(conversion) => {}
+                Some(conversion) => {
+                    return Err(format!(
+                        "failed to parse `{}` into path template, unsupported placeholder %{}, expected one of %Y, %m, %d, %H, %M, %S, %j or %%",
+                        s, conversion
+                    ))
+                }
+                None => {
+                    return Err(format!(
+                        "failed to parse `{}` into path template, trailing %, write %% for a literal one",
+                        s
+                    ))
+                }
+            }
+        }
+        Ok(Self(s.into()))
+    }
+}
+
+impl Template {
+    /// Returns the path with the placeholders replaced by the components of `time`.
+    pub fn expand(&self, time: &DateTime) -> PathBuf {
+        let mut path = String::with_capacity(self.0.len());
+        let mut chars = self.0.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                path.push(c);
+                continue;
+            }
+            let _ = match chars.next() {
+                Some('Y') => write!(path, "{:04}", time.year),
+                Some('m') => write!(path, "{:02}", time.month),
+                Some('d') => write!(path, "{:02}", time.day),
+                Some('H') => write!(path, "{:02}", time.hour),
+                Some('M') => write!(path, "{:02}", time.minute),
+                Some('S') => write!(path, "{:02}", time.second),
+                Some('j') => write!(path, "{:03}", time.day_of_year),
+                _ => write!(path, "%"),
+            };
+        }
+        PathBuf::from(path)
+    }
+}
+
+/// Tracks the expansion of a template over time, so that a new file is opened whenever it
+/// changes, e.g. at midnight for a template containing the date.
+pub struct Rolling {
+    template: Template,
+    /// Converts times to the calendar the template is expanded in.
+    calendar: fn(SystemTime) -> DateTime,
+    current: Option<PathBuf>,
+}
+
+impl Rolling {
+    /// Expands `template` in the local time zone.
+    pub fn new(template: Template) -> Self {
+        Self::with_calendar(template, clock::local)
+    }
+
+    /// Expands `template` in the calendar `calendar` converts times to, e.g. `clock::utc`.
+    pub fn with_calendar(template: Template, calendar: fn(SystemTime) -> DateTime) -> Self {
+        Self {
+            template,
+            calendar,
+            current: None,
+        }
+    }
+
+    /// Returns the path to switch to at `now`, if it differs from the current one, creating its
+    /// parent directories.
+    pub fn next(&mut self, now: SystemTime) -> io::Result<Option<&Path>> {
+        let path = self.template.expand(&(self.calendar)(now));
+        if self.current.as_ref() == Some(&path) {
+            return Ok(None);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.current = Some(path);
+        Ok(self.current.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// 2021-03-04 23:59:58 UTC, a Thursday and the 63rd day of the year.
+    const NOW: Duration = Duration::from_secs(1_614_902_398);
+
+    fn at(offset: Duration) -> SystemTime {
+        UNIX_EPOCH + NOW + offset
+    }
+
+    #[test]
+    fn parse() {
+        assert!("transcripts/%Y-%m-%d.txt".parse::<Template>().is_ok());
+        assert!("100%%.txt".parse::<Template>().is_ok());
+        assert!("plain.txt".parse::<Template>().is_ok());
+        let err = "%Y-%b.txt".parse::<Template>().unwrap_err();
+        assert!(err.contains("unsupported placeholder %b"), "{}", err);
+        let err = "trailing%".parse::<Template>().unwrap_err();
+        assert!(err.contains("trailing %"), "{}", err);
+    }
+
+    #[test]
+    fn expand() {
+        for (template, expected) in [
+            ("%Y-%m-%d.txt", "2021-03-04.txt"),
+            ("%H:%M:%S", "23:59:58"),
+            ("day %j", "day 063"),
+            ("100%%/%Y", "100%/2021"),
+            ("plain.txt", "plain.txt"),
+        ] {
+            let template: Template = template.parse().unwrap();
+            assert_eq!(
+                template.expand(&clock::utc(at(Duration::default()))),
+                PathBuf::from(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn rollover() {
+        let dir = tempfile::tempdir().unwrap();
+        let template: Template = format!("{}/%Y/%m-%d.txt", dir.path().display())
+            .parse()
+            .unwrap();
+        let mut rolling = Rolling::with_calendar(template, clock::utc);
+
+        let first = dir.path().join("2021/03-04.txt");
+        assert_eq!(
+            rolling.next(at(Duration::default())).unwrap(),
+            Some(&*first)
+        );
+        assert!(dir.path().join("2021").is_dir());
+        assert_eq!(rolling.next(at(Duration::from_secs(1))).unwrap(), None);
+
+        // Rolls over at midnight.
+        let second = dir.path().join("2021/03-05.txt");
+        assert_eq!(
+            rolling.next(at(Duration::from_secs(2))).unwrap(),
+            Some(&*second)
+        );
+        assert_eq!(rolling.next(at(Duration::from_secs(3600))).unwrap(), None);
+
+        // Rolls over into the directory of the next year.
+        let third = dir.path().join("2022/01-01.txt");
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_640_995_200);
+        assert_eq!(rolling.next(new_year).unwrap(), Some(&*third));
+        assert!(dir.path().join("2022").is_dir());
+    }
+}
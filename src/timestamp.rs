@@ -0,0 +1,372 @@
+use crate::clock;
+use crate::duration;
+use crate::output::{Event, Sink, Utterance};
+use crate::segmenter::Segment;
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+    time::{Duration, SystemTime},
+};
+use tracing::warn;
+
+/// Format of timestamps in transcripts, 0 until configured.
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+
+/// Rendering of the timestamps of utterances.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Seconds with milliseconds, e.g. `83.250`.
+    Seconds = 1,
+    /// Hours, minutes and seconds with milliseconds, e.g. `00:01:23.250`.
+    Hms,
+    /// Like `Hms`, but with a comma before the milliseconds as in SubRip, e.g. `00:01:23,250`.
+    Srt,
+    /// Wall-clock time of capture in ISO 8601, e.g. `2021-04-01T12:30:00.000Z`, or an ISO 8601
+    /// duration from the start of the stream, e.g. `PT83.250S`, if the capture time is unknown.
+    Iso8601,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "seconds" => Ok(Self::Seconds),
+            "hms" => Ok(Self::Hms),
+            "srt" => Ok(Self::Srt),
+            "iso8601" => Ok(Self::Iso8601),
+            _ => Err(format!(
+                "failed to parse `{}` into timestamp format of seconds, hms, srt or iso8601",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Renders the timestamp `offset` from the start of the stream, which was captured at
+    /// `captured`, if known.
+    pub fn render(self, offset: Duration, captured: Option<SystemTime>) -> String {
+        let millis = offset.as_millis();
+        match self {
+            Self::Seconds => format!("{}.{:03}", millis / 1000, millis % 1000),
+            Self::Hms => hms(offset, '.'),
+            Self::Srt => hms(offset, ','),
+            Self::Iso8601 => match captured {
+                Some(captured) => clock::format(captured),
+                None => format!("PT{}.{:03}S", millis / 1000, millis % 1000),
+            },
+        }
+    }
+}
+
+/// Sets the format of timestamps in transcripts for the rest of the process.
+pub fn configure(format: Format) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// Returns the format of timestamps in transcripts, if configured.
+pub fn configured() -> Option<Format> {
+    match FORMAT.load(Ordering::Relaxed) {
+        1 => Some(Format::Seconds),
+        2 => Some(Format::Hms),
+        3 => Some(Format::Srt),
+        4 => Some(Format::Iso8601),
+        _ => None,
+    }
+}
+
+/// Formats `offset` as `HH:MM:SS` followed by milliseconds after `separator`.
+/// Hours do not roll over after a day, so that long recordings keep increasing timestamps.
+pub fn hms(offset: Duration, separator: char) -> String {
+    let millis = offset.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// Signed offset added to the timestamps of events, e.g. `+3.2s` or `-500ms`. Bare numbers are in
+/// milliseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct Offset {
+    negative: bool,
+    duration: Duration,
+}
+
+impl FromStr for Offset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let s = s.trim();
+        let (negative, duration) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        Ok(Self {
+            negative,
+            duration: duration::millis(duration)?,
+        })
+    }
+}
+
+impl Offset {
+    /// Returns `at` shifted by the offset, or `None` if it would be before the start of the
+    /// stream.
+    fn apply(self, at: Duration) -> Option<Duration> {
+        if self.negative {
+            at.checked_sub(self.duration)
+        } else {
+            Some(at + self.duration)
+        }
+    }
+}
+
+/// Shifts the timestamps of events by `offset` before forwarding them to `sink`, so that every
+/// output renders the same shifted timestamps, e.g. to sync subtitles with a recording started
+/// earlier. Timestamps shifted before the start of the stream are clamped to it. Wall-clock times
+/// of capture are left as they are.
+pub struct Shift<S> {
+    offset: Offset,
+    sink: S,
+    /// Whether a timestamp was clamped already, which is warned about once.
+    clamped: bool,
+}
+
+impl<S> Shift<S> {
+    pub fn new(offset: Offset, sink: S) -> Self {
+        Self {
+            offset,
+            sink,
+            clamped: false,
+        }
+    }
+
+    fn shift(&mut self, at: Duration) -> Duration {
+        match self.offset.apply(at) {
+            Some(at) => at,
+            None => {
+                if !self.clamped {
+                    self.clamped = true;
+                    warn!(
+                        offset = ?self.offset,
+                        "Timestamps shifted before the start of the stream are clamped to 0"
+                    );
+                }
+                Duration::default()
+            }
+        }
+    }
+
+    fn utterance(&mut self, utterance: &Utterance) -> Utterance {
+        let mut utterance = utterance.clone();
+        utterance.start = self.shift(utterance.start);
+        utterance.end = self.shift(utterance.end);
+        utterance
+    }
+}
+
+impl<S: Sink> Sink for Shift<S> {
+    fn send(&mut self, event: &Event) {
+        let event = match event {
+            Event::SpeechStarted(at) => Event::SpeechStarted(self.shift(*at)),
+            Event::SpeechStopped(at) => Event::SpeechStopped(self.shift(*at)),
+            Event::Partial(utterance) => Event::Partial(self.utterance(utterance)),
+            Event::StablePartial(utterance) => Event::StablePartial(self.utterance(utterance)),
+            Event::Final(utterance) => Event::Final(self.utterance(utterance)),
+            Event::Dropped(utterance) => Event::Dropped(self.utterance(utterance)),
+            Event::Mark(at, label) => Event::Mark(self.shift(*at), label.clone()),
+            Event::Ready | Event::Heartbeat(_) => return self.sink.send(event),
+        };
+        self.sink.send(&event)
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        self.sink.samples(samples)
+    }
+
+    fn segment(&mut self, segment: &Segment) {
+        self.sink.segment(segment)
+    }
+
+    fn finish(&mut self) {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{self, Cues, Details};
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn render() {
+        let millis = Duration::from_millis;
+        for (offset, seconds, hms, srt) in [
+            (millis(0), "0.000", "00:00:00.000", "00:00:00,000"),
+            (millis(83_250), "83.250", "00:01:23.250", "00:01:23,250"),
+            (millis(59_999), "59.999", "00:00:59.999", "00:00:59,999"),
+            (millis(60_000), "60.000", "00:01:00.000", "00:01:00,000"),
+            (
+                millis(3_599_999),
+                "3599.999",
+                "00:59:59.999",
+                "00:59:59,999",
+            ),
+            (
+                millis(3_600_000),
+                "3600.000",
+                "01:00:00.000",
+                "01:00:00,000",
+            ),
+            (
+                millis(86_399_999),
+                "86399.999",
+                "23:59:59.999",
+                "23:59:59,999",
+            ),
+            (
+                millis(86_400_000),
+                "86400.000",
+                "24:00:00.000",
+                "24:00:00,000",
+            ),
+            (
+                millis(100 * 3_600_000 + 1),
+                "360000.001",
+                "100:00:00.001",
+                "100:00:00,001",
+            ),
+            // Sub-millisecond precision is truncated rather than rounded up into the next second.
+            (
+                Duration::from_micros(59_999_999),
+                "59.999",
+                "00:00:59.999",
+                "00:00:59,999",
+            ),
+        ] {
+            assert_eq!(Format::Seconds.render(offset, None), seconds);
+            assert_eq!(Format::Hms.render(offset, None), hms);
+            assert_eq!(Format::Srt.render(offset, None), srt);
+        }
+    }
+
+    #[test]
+    fn render_iso8601() {
+        let offset = Duration::from_millis(83_250);
+        assert_eq!(Format::Iso8601.render(offset, None), "PT83.250S");
+        let captured = UNIX_EPOCH + Duration::from_millis(1_617_280_200_250);
+        assert_eq!(
+            Format::Iso8601.render(offset, Some(captured)),
+            "2021-04-01T12:30:00.250Z"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        for (s, expected) in [
+            ("seconds", Format::Seconds),
+            ("hms", Format::Hms),
+            ("srt", Format::Srt),
+            ("iso8601", Format::Iso8601),
+        ] {
+            assert_eq!(s.parse(), Ok(expected));
+        }
+        assert!("vtt".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn offset() {
+        let offset = |s: &str| s.parse::<Offset>().unwrap();
+        let at = Duration::from_secs(1);
+        assert_eq!(offset("+3.2s").apply(at), Some(Duration::from_millis(4200)));
+        assert_eq!(offset("250").apply(at), Some(Duration::from_millis(1250)));
+        assert_eq!(offset("-500ms").apply(at), Some(Duration::from_millis(500)));
+        assert_eq!(offset("-1s").apply(at), Some(Duration::default()));
+        assert_eq!(offset("-2s").apply(at), None);
+        assert!("-".parse::<Offset>().is_err());
+        assert!("+soon".parse::<Offset>().is_err());
+    }
+
+    /// Collects events.
+    #[derive(Default)]
+    struct Events(Vec<Event>);
+
+    impl Sink for Events {
+        fn send(&mut self, event: &Event) {
+            self.0.push(event.clone());
+        }
+    }
+
+    /// Returns the SRT subtitles of utterances from `start_ms` to `end_ms` shifted by `offset`.
+    fn srt(offset: &str, utterances: &[(u64, u64, &str)]) -> String {
+        let mut shift = Shift::new(offset.parse().unwrap(), Events::default());
+        for &(start, end, text) in utterances {
+            let mut utterance = Utterance::test(text);
+            utterance.start = Duration::from_millis(start);
+            utterance.end = Duration::from_millis(end);
+            shift.send(&Event::Final(utterance));
+        }
+        let details = Details {
+            duration: Duration::default(),
+            date: UNIX_EPOCH,
+            model: None,
+            cues: Cues::default(),
+        };
+        let finals: Vec<_> = shift
+            .sink
+            .0
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Final(utterance) => Some(utterance),
+                _ => None,
+            })
+            .collect();
+        transcript::Format::Srt.render(&finals, &details)
+    }
+
+    #[test]
+    fn shift_srt() {
+        let utterances = [(1000, 2500, "hello"), (3000, 4250, "world")];
+        assert_eq!(
+            srt("+3.2s", &utterances),
+            "1\n00:00:04,200 --> 00:00:05,700\nhello\n\n\
+             2\n00:00:06,200 --> 00:00:07,450\nworld\n\n"
+        );
+        // Cues shifted before the start are clamped to it.
+        assert_eq!(
+            srt("-1.5s", &utterances),
+            "1\n00:00:00,000 --> 00:00:01,000\nhello\n\n\
+             2\n00:00:01,500 --> 00:00:02,750\nworld\n\n"
+        );
+        assert_eq!(
+            srt("0", &utterances),
+            "1\n00:00:01,000 --> 00:00:02,500\nhello\n\n\
+             2\n00:00:03,000 --> 00:00:04,250\nworld\n\n"
+        );
+    }
+
+    #[test]
+    fn shift_events() {
+        let mut shift = Shift::new("-500ms".parse().unwrap(), Events::default());
+        shift.send(&Event::SpeechStarted(Duration::from_millis(200)));
+        shift.send(&Event::SpeechStopped(Duration::from_millis(1200)));
+        assert!(shift.clamped);
+        let at: Vec<_> = shift
+            .sink
+            .0
+            .iter()
+            .map(|event| match event {
+                Event::SpeechStarted(at) | Event::SpeechStopped(at) => *at,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(at, [Duration::default(), Duration::from_millis(700)]);
+    }
+}
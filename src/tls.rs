@@ -0,0 +1,274 @@
+use crate::error::Error;
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey, SigningKey};
+use rustls::{ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig, SignatureScheme};
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread,
+};
+use tracing::{info, warn};
+
+/// Signature schemes, with which a key is checked to belong to its certificate.
+const SCHEMES: &[SignatureScheme] = &[
+    SignatureScheme::ECDSA_NISTP256_SHA256,
+    SignatureScheme::ECDSA_NISTP384_SHA384,
+    SignatureScheme::ED25519,
+    SignatureScheme::RSA_PSS_SHA256,
+];
+
+/// Message signed to check that a key belongs to its certificate.
+const PROBE: &[u8] = b"speech2text";
+
+/// Returns the algorithm verifying signatures of `scheme`, if it is one of `SCHEMES`.
+fn algorithm(scheme: SignatureScheme) -> Option<&'static webpki::SignatureAlgorithm> {
+    match scheme {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => Some(&webpki::ECDSA_P256_SHA256),
+        SignatureScheme::ECDSA_NISTP384_SHA384 => Some(&webpki::ECDSA_P384_SHA384),
+        SignatureScheme::ED25519 => Some(&webpki::ED25519),
+        SignatureScheme::RSA_PSS_SHA256 => Some(&webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY),
+        _ => None,
+    }
+}
+
+/// Whether `key` belongs to `cert`, which is the case if a signature made with it verifies with
+/// the public key of the certificate.
+fn belongs(key: &dyn SigningKey, cert: &webpki::EndEntityCert<'_>) -> bool {
+    let signer = match key.choose_scheme(SCHEMES) {
+        Some(signer) => signer,
+        None => return false,
+    };
+    match (algorithm(signer.get_scheme()), signer.sign(PROBE)) {
+        (Some(algorithm), Ok(signature)) => {
+            cert.verify_signature(algorithm, PROBE, &signature).is_ok()
+        }
+        _ => false,
+    }
+}
+
+fn open(path: &Path, what: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| format!("failed to read {} {}: {}", what, path.display(), err))
+}
+
+/// Loads the PEM-encoded certificate chain at `cert`, leaf first, and its PKCS#8 or RSA private
+/// key at `key`, which needs to belong to the leaf certificate.
+fn load(cert: &Path, key: &Path) -> Result<CertifiedKey, String> {
+    let chain = pemfile::certs(&mut open(cert, "certificate")?)
+        .map_err(|_| format!("failed to parse certificate {} as PEM", cert.display()))?;
+    let leaf = chain
+        .first()
+        .ok_or_else(|| format!("certificate {} contains no certificates", cert.display()))?;
+    let leaf = webpki::EndEntityCert::from(&leaf.0)
+        .map_err(|err| format!("failed to parse certificate {}: {:?}", cert.display(), err))?;
+
+    let invalid = || format!("failed to parse key {} as PEM", key.display());
+    let mut keys = pemfile::pkcs8_private_keys(&mut open(key, "key")?).map_err(|_| invalid())?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut open(key, "key")?).map_err(|_| invalid())?;
+    }
+    let private = keys.first().ok_or_else(|| {
+        format!(
+            "key {} contains no PKCS#8 or RSA private key",
+            key.display()
+        )
+    })?;
+    let private = sign::any_supported_type(private)
+        .map_err(|_| format!("key {} is of an unsupported type", key.display()))?;
+    if !belongs(private.as_ref(), &leaf) {
+        return Err(format!(
+            "key {} does not belong to certificate {}",
+            key.display(),
+            cert.display()
+        ));
+    }
+    Ok(CertifiedKey::new(chain, Arc::new(private)))
+}
+
+/// Certificate served by the TLS listeners, which is reloaded from its files on SIGHUP, e.g.
+/// once it was renewed, so that connections accepted afterwards are served the renewed one
+/// without a restart. The previous certificate is kept, if the reloaded one fails to load.
+pub struct Certificates {
+    cert: PathBuf,
+    key: PathBuf,
+    current: RwLock<CertifiedKey>,
+}
+
+impl Certificates {
+    /// Loads the certificate chain at `cert` and its private key at `key`, failing with
+    /// `Error::Usage` if either is unreadable or the key does not belong to the certificate.
+    pub fn load(cert: PathBuf, key: PathBuf) -> Result<Arc<Self>, Error> {
+        let current = load(&cert, &key)
+            .map_err(Error::with(Error::Usage, "Failed to load TLS certificate"))?;
+        info!(?cert, "Loaded TLS certificate");
+        let mut signals =
+            Signals::new([SIGHUP]).map_err(Error::with(Error::Other, "Failed to handle SIGHUP"))?;
+        let certificates = Arc::new(Self {
+            cert,
+            key,
+            current: RwLock::new(current),
+        });
+        let reloaded = certificates.clone();
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                reloaded.reload();
+            }
+        });
+        Ok(certificates)
+    }
+
+    fn reload(&self) {
+        match load(&self.cert, &self.key) {
+            Ok(current) => {
+                *self
+                    .current
+                    .write()
+                    .expect("Failed to lock TLS certificate") = current;
+                info!(cert = ?self.cert, "Reloaded TLS certificate");
+            }
+            Err(err) => warn!(
+                error = %err,
+                "Failed to reload TLS certificate, serving the previous one"
+            ),
+        }
+    }
+
+    /// Returns the configuration of a TLS listener serving the certificate, which negotiates
+    /// one of the application `protocols` with ALPN, e.g. `h2`.
+    pub fn server_config(self: &Arc<Self>, protocols: &[&[u8]]) -> Arc<ServerConfig> {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config.cert_resolver = self.clone();
+        config.set_protocols(
+            &protocols
+                .iter()
+                .map(|protocol| protocol.to_vec())
+                .collect::<Vec<_>>(),
+        );
+        Arc::new(config)
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<CertifiedKey> {
+        Some(
+            self.current
+                .read()
+                .expect("Failed to lock TLS certificate")
+                .clone(),
+        )
+    }
+}
+
+/// Returns the path of the self-signed fixture `name`, generated with e.g.
+/// `openssl req -x509 -key key.pem -subj /CN=localhost -addext subjectAltName=DNS:localhost`.
+#[cfg(test)]
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+/// Returns the self-signed certificate of `localhost` along with the configuration of a client
+/// trusting it, which negotiates one of the application `protocols`.
+#[cfg(test)]
+pub fn test(protocols: &[&[u8]]) -> (Arc<Certificates>, Arc<rustls::ClientConfig>) {
+    let certificates = Certificates::load(fixture("cert.pem"), fixture("key.pem"))
+        .expect("Failed to load TLS certificate");
+    let mut client = rustls::ClientConfig::new();
+    for cert in &certificates.current.read().unwrap().cert {
+        client.root_store.add(cert).unwrap();
+    }
+    client.set_protocols(
+        &protocols
+            .iter()
+            .map(|protocol| protocol.to_vec())
+            .collect::<Vec<_>>(),
+    );
+    (certificates, Arc::new(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn leaf(key: &CertifiedKey) -> &[u8] {
+        &key.cert[0].0
+    }
+
+    #[test]
+    fn load_pkcs8() {
+        let key = load(&fixture("cert.pem"), &fixture("key.pem")).unwrap();
+        assert_eq!(key.cert.len(), 1);
+        assert_eq!(
+            leaf(&key),
+            &pemfile::certs(&mut open(&fixture("cert.pem"), "certificate").unwrap()).unwrap()[0].0
+        );
+    }
+
+    #[test]
+    fn load_rsa() {
+        assert!(load(&fixture("rsa-cert.pem"), &fixture("rsa-key.pem")).is_ok());
+    }
+
+    #[test]
+    fn load_invalid() {
+        let error = |cert: &str, key: &str| match load(&fixture(cert), &fixture(key)) {
+            Ok(_) => panic!("loaded {} with {}", cert, key),
+            Err(err) => err,
+        };
+        for (cert, key, expected) in [
+            ("missing.pem", "key.pem", "failed to read certificate"),
+            ("cert.pem", "missing.pem", "failed to read key"),
+            ("key.pem", "key.pem", "contains no certificates"),
+            (
+                "cert.pem",
+                "cert.pem",
+                "contains no PKCS#8 or RSA private key",
+            ),
+            (
+                "cert.pem",
+                "other-key.pem",
+                "does not belong to certificate",
+            ),
+            ("cert.pem", "rsa-key.pem", "does not belong to certificate"),
+            ("rsa-cert.pem", "key.pem", "does not belong to certificate"),
+        ] {
+            let err = error(cert, key);
+            assert!(err.contains(expected), "{} with {}: {}", cert, key, err);
+        }
+    }
+
+    #[test]
+    fn load_usage_error() {
+        match Certificates::load(fixture("cert.pem"), fixture("other-key.pem")) {
+            Err(Error::Usage(err)) => assert!(err.contains("does not belong"), "{}", err),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("loaded a mismatched key"),
+        }
+    }
+
+    #[test]
+    fn reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        fs::copy(fixture("cert.pem"), &cert).unwrap();
+        fs::copy(fixture("key.pem"), &key).unwrap();
+        let certificates = Certificates::load(cert.clone(), key.clone()).unwrap();
+        let loaded = leaf(&certificates.current.read().unwrap()).to_vec();
+
+        // The previous certificate is kept, if the renewed one fails to load.
+        fs::copy(fixture("other-key.pem"), &key).unwrap();
+        certificates.reload();
+        assert_eq!(leaf(&certificates.current.read().unwrap()), &loaded[..]);
+
+        fs::copy(fixture("rsa-cert.pem"), &cert).unwrap();
+        fs::copy(fixture("rsa-key.pem"), &key).unwrap();
+        certificates.reload();
+        assert_ne!(leaf(&certificates.current.read().unwrap()), &loaded[..]);
+    }
+}
@@ -0,0 +1,811 @@
+use crate::clock;
+use crate::output::{Event, Paragraphs, Utterance};
+use crate::schema;
+use crate::timestamp::hms;
+use serde_json::Value;
+use std::{
+    fmt::Write,
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// Pause between utterances, after which a new paragraph of meeting notes starts, unless the
+/// utterances are grouped into paragraphs already.
+const NOTES_PARAGRAPH_GAP: Duration = Duration::from_secs(2);
+
+/// Maximum number of characters of the title of a chapter, beyond which it is truncated.
+const MAX_CHAPTER_TITLE: usize = 60;
+
+/// Format of a transcript file.
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    /// Plain text, a line per utterance.
+    Txt,
+    /// SubRip subtitles.
+    Srt,
+    /// WebVTT subtitles.
+    Vtt,
+    /// JSON array of utterances.
+    Json,
+    /// Audacity label track, a line per utterance of its start and end in seconds and its
+    /// transcript separated by tabs.
+    Audacity,
+    /// Praat TextGrid with an interval tier of utterances, in which the silence between them
+    /// is covered by empty intervals.
+    Textgrid,
+    /// Markdown meeting notes headed with the date, duration and model, a paragraph per group of
+    /// utterances prefixed with the timestamp of its start.
+    Markdown,
+    /// FFmpeg metadata with a chapter per utterance, or per paragraph if utterances are grouped
+    /// into paragraphs, titled with its transcript.
+    Ffmetadata,
+}
+
+/// Details of the transcribed audio, which some formats include.
+pub struct Details<'a> {
+    /// Duration of the audio, which TextGrids span.
+    pub duration: Duration,
+    /// Time the audio was recorded at.
+    pub date: SystemTime,
+    /// Path of the model, which transcribed the audio.
+    pub model: Option<&'a Path>,
+    /// Limits of the cues of subtitles.
+    pub cues: Cues,
+}
+
+/// Limits of the cues of subtitles, which utterances are split into and wrapped to.
+#[derive(Clone, Copy, Debug)]
+pub struct Cues {
+    /// Maximum number of characters of a line, of which cues have at most two, 0 for no limit.
+    pub max_chars: usize,
+    /// Maximum duration of a cue, 0 for no limit.
+    pub max_duration: Duration,
+}
+
+impl Default for Cues {
+    /// Limits of common captioning guidelines.
+    fn default() -> Self {
+        Self {
+            max_chars: 42,
+            max_duration: Duration::from_secs(6),
+        }
+    }
+}
+
+impl Cues {
+    /// Returns the lines of `words` wrapped at word boundaries. Words longer than a line are
+    /// not broken.
+    fn wrap(&self, words: &[&str]) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for word in words {
+            match lines.last_mut() {
+                Some(line)
+                    if self.max_chars == 0
+                        || line.chars().count() + 1 + word.chars().count() <= self.max_chars =>
+                {
+                    line.push(' ');
+                    line.push_str(word);
+                }
+                _ => lines.push(word.to_string()),
+            }
+        }
+        lines
+    }
+
+    /// Splits `utterance` into cues of start, end and lines, which last at most `max_duration`
+    /// and have at most two lines. Words are split evenly across the cues of an utterance and
+    /// the cues share its duration in proportion to their words, since the timing of words is
+    /// not known.
+    fn split(&self, utterance: &Utterance) -> Vec<(Duration, Duration, Vec<String>)> {
+        let words: Vec<&str> = utterance.text.split_whitespace().collect();
+        let duration = utterance
+            .end
+            .checked_sub(utterance.start)
+            .unwrap_or_default();
+        let count = if self.max_duration > Duration::default() {
+            let max = self.max_duration.as_micros();
+            duration.as_micros().div_ceil(max).max(1) as usize
+        } else {
+            1
+        };
+        let count = count.min(words.len()).max(1);
+        // Groups of words of cues of at most `max_duration`, which are split further, if they
+        // do not fit into two lines.
+        let mut groups: Vec<&[&str]> = Vec::new();
+        for i in 0..count {
+            let mut group = &words[i * words.len() / count..(i + 1) * words.len() / count];
+            while !group.is_empty() {
+                let mut len = group.len();
+                while len > 1 && self.wrap(&group[..len]).len() > 2 {
+                    len -= 1;
+                }
+                groups.push(&group[..len]);
+                group = &group[len..];
+            }
+        }
+        let at = |word: usize| match words.len() {
+            0 => utterance.start,
+            len => utterance.start + duration * word as u32 / len as u32,
+        };
+        let mut cues = Vec::with_capacity(groups.len());
+        let mut word = 0;
+        for group in groups {
+            cues.push((at(word), at(word + group.len()), self.wrap(group)));
+            word += group.len();
+        }
+        cues
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "txt" => Ok(Self::Txt),
+            "srt" => Ok(Self::Srt),
+            "vtt" => Ok(Self::Vtt),
+            "json" => Ok(Self::Json),
+            "audacity" => Ok(Self::Audacity),
+            "textgrid" => Ok(Self::Textgrid),
+            "markdown" => Ok(Self::Markdown),
+            "ffmetadata" => Ok(Self::Ffmetadata),
+            _ => Err(format!(
+                "failed to parse `{}` into output format of txt, srt, vtt, json, audacity, textgrid, markdown or ffmetadata",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Returns the file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Txt => "txt",
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Json => "json",
+            // Audacity imports labels from text files, the infix tells them apart from
+            // transcripts.
+            Self::Audacity => "labels.txt",
+            Self::Textgrid => "TextGrid",
+            Self::Markdown => "md",
+            Self::Ffmetadata => "ffmetadata",
+        }
+    }
+
+    /// Renders `utterances` in the format. Empty utterances are omitted, except from JSON.
+    /// Paragraphs are separated by blank lines in text and JSON is an array of paragraphs, each
+    /// with the array of its utterances, if utterances are grouped into paragraphs.
+    /// Markdown groups utterances by the pauses between them, if they are not grouped already.
+    pub fn render(self, utterances: &[Utterance], details: &Details) -> String {
+        let mut out = String::new();
+        let spoken = utterances
+            .iter()
+            .filter(|utterance| !utterance.text.is_empty());
+        match self {
+            Self::Txt => {
+                let mut paragraph = None;
+                for utterance in spoken {
+                    if paragraph.is_some() && utterance.paragraph != paragraph {
+                        out.push('\n');
+                    }
+                    paragraph = utterance.paragraph;
+                    out.push_str(&utterance.text);
+                    out.push('\n');
+                }
+            }
+            Self::Srt => {
+                let cues = spoken.flat_map(|utterance| {
+                    let speaker = &utterance.speaker;
+                    details
+                        .cues
+                        .split(utterance)
+                        .into_iter()
+                        .map(move |cue| (speaker, cue))
+                });
+                for (i, (speaker, (start, end, lines))) in cues.enumerate() {
+                    let _ = writeln!(out, "{}\n{} --> {}", i + 1, hms(start, ','), hms(end, ','));
+                    if let Some(speaker) = speaker {
+                        let _ = write!(out, "{}: ", speaker);
+                    }
+                    let _ = write!(out, "{}\n\n", lines.join("\n"));
+                }
+            }
+            Self::Vtt => {
+                out.push_str("WEBVTT\n\n");
+                for utterance in spoken {
+                    for (start, end, lines) in details.cues.split(utterance) {
+                        let _ = writeln!(out, "{} --> {}", hms(start, '.'), hms(end, '.'));
+                        // Voice spans label the speaker of a cue.
+                        if let Some(speaker) = &utterance.speaker {
+                            let _ = write!(out, "<v {}>", speaker);
+                        }
+                        let _ = write!(out, "{}\n\n", lines.join("\n"));
+                    }
+                }
+            }
+            Self::Audacity => {
+                for utterance in spoken {
+                    out.push_str(&label(utterance.start, utterance.end, &utterance.text));
+                }
+            }
+            Self::Textgrid => {
+                out = textgrid(
+                    "utterances",
+                    spoken
+                        .map(|utterance| (utterance.start, utterance.end, utterance.text.as_str())),
+                    details.duration,
+                )
+            }
+            Self::Markdown => out = notes(utterances, details),
+            Self::Ffmetadata => {
+                // Utterances of a paragraph are consecutive.
+                let mut chapters: Vec<(Duration, Duration, String, Option<u64>)> = Vec::new();
+                for utterance in spoken {
+                    match chapters.last_mut() {
+                        Some((_, end, title, paragraph))
+                            if paragraph.is_some() && *paragraph == utterance.paragraph =>
+                        {
+                            *end = utterance.end;
+                            title.push(' ');
+                            title.push_str(&utterance.text);
+                        }
+                        _ => chapters.push((
+                            utterance.start,
+                            utterance.end,
+                            utterance.text.clone(),
+                            utterance.paragraph,
+                        )),
+                    }
+                }
+                out = ffmetadata(
+                    chapters
+                        .into_iter()
+                        .map(|(start, end, title, _)| (start, end, title)),
+                )
+            }
+            Self::Json
+                if utterances
+                    .iter()
+                    .any(|utterance| utterance.paragraph.is_some()) =>
+            {
+                let mut paragraphs: Vec<schema::Paragraph> = Vec::new();
+                for utterance in utterances {
+                    let paragraph = utterance.paragraph.unwrap_or_default();
+                    match paragraphs.last_mut() {
+                        Some(last) if last.paragraph == paragraph => {
+                            last.utterances.push(utterance.to_record())
+                        }
+                        _ => paragraphs.push(schema::Paragraph {
+                            paragraph,
+                            utterances: vec![utterance.to_record()],
+                        }),
+                    }
+                }
+                let paragraphs =
+                    serde_json::to_value(paragraphs).expect("Failed to serialize paragraphs");
+                let _ = writeln!(out, "{:#}", paragraphs);
+            }
+            Self::Json => {
+                let utterances = utterances.iter().map(Utterance::to_json).collect();
+                let _ = writeln!(out, "{:#}", Value::Array(utterances));
+            }
+        }
+        out
+    }
+}
+
+/// Returns the Markdown header of meeting notes about audio described by `details`.
+pub fn notes_header(title: &str, details: &Details) -> String {
+    let date = clock::local(details.date);
+    let mut out = format!(
+        "# {}\n\n- **Date:** {:04}-{:02}-{:02} {:02}:{:02}\n- **Duration:** {}\n",
+        title,
+        date.year,
+        date.month,
+        date.day,
+        date.hour,
+        date.minute,
+        clock_time(details.duration)
+    );
+    if let Some(model) = details.model {
+        let name = model.file_name().unwrap_or(model.as_os_str());
+        let _ = writeln!(out, "- **Model:** {}", name.to_string_lossy());
+    }
+    out.push('\n');
+    out
+}
+
+/// Returns `utterances` as Markdown meeting notes, a paragraph per paragraph of utterances
+/// prefixed with the timestamp of its start in bold.
+/// The speaker is prefixed in bold, whenever it changes.
+fn notes(utterances: &[Utterance], details: &Details) -> String {
+    let mut utterances: Vec<Utterance> = utterances
+        .iter()
+        .filter(|utterance| !utterance.text.is_empty())
+        .cloned()
+        .collect();
+    if utterances
+        .iter()
+        .all(|utterance| utterance.paragraph.is_none())
+    {
+        let mut paragraphs = Paragraphs::new(NOTES_PARAGRAPH_GAP);
+        for utterance in &mut utterances {
+            let mut event = Event::Final(utterance.clone());
+            paragraphs.assign(&mut event);
+            if let Event::Final(assigned) = event {
+                *utterance = assigned
+            }
+        }
+    }
+
+    let mut out = notes_header("Transcript", details);
+    let mut paragraph = None;
+    let mut speaker = None;
+    for utterance in &utterances {
+        if paragraph.is_none() || utterance.paragraph != paragraph {
+            if paragraph.is_some() {
+                out.push_str("\n\n");
+            }
+            paragraph = utterance.paragraph;
+            speaker = None;
+            let _ = write!(out, "**[{}]**", clock_time(utterance.start));
+        }
+        out.push(' ');
+        if utterance.speaker.is_some() && utterance.speaker != speaker {
+            speaker = utterance.speaker.clone();
+            let _ = write!(out, "**{}:** ", speaker.as_deref().unwrap_or_default());
+        }
+        out.push_str(&utterance.text);
+    }
+    if paragraph.is_some() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns `offset` in hours, minutes and seconds, e.g. `00:12:34`.
+pub fn clock_time(offset: Duration) -> String {
+    let secs = offset.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Returns a line of an Audacity label track labeling `start` to `end` with `text`, in which tabs
+/// and line breaks are replaced with spaces, since they separate fields and labels.
+pub fn label(start: Duration, end: Duration, text: &str) -> String {
+    let text: String = text
+        .chars()
+        .map(|c| {
+            if c == '\t' || c == '\n' || c == '\r' {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!(
+        "{:.6}\t{:.6}\t{}\n",
+        start.as_secs_f64(),
+        end.as_secs_f64(),
+        text
+    )
+}
+
+/// Returns FFmpeg metadata of `chapters` of start, end and title in milliseconds, which can be
+/// muxed into media with `ffmpeg -i audio -i metadata -map_metadata 1`. Titles are truncated to
+/// `MAX_CHAPTER_TITLE` characters at a word boundary.
+pub fn ffmetadata(chapters: impl Iterator<Item = (Duration, Duration, String)>) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for (start, end, title) in chapters {
+        let _ = write!(
+            out,
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            start.as_millis(),
+            end.as_millis(),
+            escape_metadata(&truncate(&title, MAX_CHAPTER_TITLE))
+        );
+    }
+    out
+}
+
+/// Returns `text` truncated to at most `max` characters at a word boundary, followed by an
+/// ellipsis, if it is longer.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    for word in text.split_whitespace() {
+        // The ellipsis takes up a character.
+        if !out.is_empty() && out.chars().count() + 1 + word.chars().count() + 1 > max {
+            break;
+        }
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    // Words longer than the title are cut.
+    let mut out: String = out.chars().take(max - 1).collect();
+    out.push('…');
+    out
+}
+
+/// Escapes the characters of a value of FFmpeg metadata, which are special to it, with a
+/// backslash: `=`, `;`, `#`, `\` and line breaks.
+fn escape_metadata(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Returns a TextGrid in the long text format with a single interval tier named `tier` of
+/// `intervals` of start, end and text, which must be ordered.
+/// The gaps between intervals, up to `duration`, are filled with empty intervals, since interval
+/// tiers must cover the whole grid.
+pub fn textgrid<'a>(
+    tier: &str,
+    intervals: impl Iterator<Item = (Duration, Duration, &'a str)>,
+    duration: Duration,
+) -> String {
+    let mut filled: Vec<(f64, f64, &str)> = Vec::new();
+    let mut last = 0.0;
+    for (start, end, text) in intervals {
+        // Clamping prevents overlapping intervals, which Praat refuses to read.
+        let start = start.as_secs_f64().max(last);
+        let end = end.as_secs_f64();
+        if end <= start {
+            continue;
+        }
+        if start > last {
+            filled.push((last, start, ""));
+        }
+        filled.push((start, end, text));
+        last = end;
+    }
+    let xmax = duration.as_secs_f64().max(last);
+    if xmax > last || filled.is_empty() {
+        filled.push((last, xmax, ""));
+    }
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\n\
+         xmin = 0\nxmax = {xmax}\ntiers? <exists>\nsize = 1\nitem []:\n    item [1]:\n        \
+         class = \"IntervalTier\"\n        name = \"{tier}\"\n        xmin = 0\n        \
+         xmax = {xmax}\n        intervals: size = {size}\n",
+        xmax = xmax,
+        tier = tier.replace('"', "\"\""),
+        size = filled.len()
+    );
+    for (i, (xmin, xmax, text)) in filled.into_iter().enumerate() {
+        let _ = write!(
+            out,
+            "        intervals [{}]:\n            xmin = {}\n            xmax = {}\n            \
+             text = \"{}\"\n",
+            i + 1,
+            xmin,
+            xmax,
+            // Quotes are escaped by doubling them.
+            text.replace('"', "\"\"")
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn details(duration: Duration) -> Details<'static> {
+        Details {
+            duration,
+            date: UNIX_EPOCH,
+            model: None,
+            cues: Cues::default(),
+        }
+    }
+
+    fn utterances() -> Vec<Utterance> {
+        vec![
+            Utterance {
+                start: Duration::from_millis(1500),
+                end: Duration::from_millis(3_723_004),
+                ..Utterance::test("hello")
+            },
+            Utterance::test(""),
+            Utterance {
+                speaker: Some("Alice".into()),
+                start: Duration::from_secs(4000),
+                end: Duration::from_secs(4001),
+                ..Utterance::test("world")
+            },
+        ]
+    }
+
+    #[test]
+    fn render() {
+        let utterances = utterances();
+        assert_eq!(
+            Format::Txt.render(&utterances, &details(Duration::default())),
+            "hello\nworld\n"
+        );
+        assert_eq!(
+            Format::Srt.render(&utterances, &details(Duration::default())),
+            "1\n00:00:01,500 --> 01:02:03,004\nhello\n\n\
+             2\n01:06:40,000 --> 01:06:41,000\nAlice: world\n\n"
+        );
+        assert_eq!(
+            Format::Vtt.render(&utterances, &details(Duration::default())),
+            "WEBVTT\n\n\
+             00:00:01.500 --> 01:02:03.004\nhello\n\n\
+             01:06:40.000 --> 01:06:41.000\n<v Alice>world\n\n"
+        );
+        let json: Value =
+            serde_json::from_str(&Format::Json.render(&utterances, &details(Duration::default())))
+                .unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+        assert_eq!(json[2]["speaker"], "Alice");
+    }
+
+    #[test]
+    fn render_paragraphs() {
+        let utterances: Vec<_> = [("one", 1), ("", 1), ("two", 1), ("three", 2)]
+            .iter()
+            .map(|&(text, paragraph)| Utterance {
+                paragraph: Some(paragraph),
+                ..Utterance::test(text)
+            })
+            .collect();
+        assert_eq!(
+            Format::Txt.render(&utterances, &details(Duration::default())),
+            "one\ntwo\n\nthree\n"
+        );
+        let json: Value =
+            serde_json::from_str(&Format::Json.render(&utterances, &details(Duration::default())))
+                .unwrap();
+        let paragraphs = json.as_array().unwrap();
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0]["paragraph"], 1);
+        // JSON keeps empty utterances.
+        assert_eq!(paragraphs[0]["utterances"].as_array().unwrap().len(), 3);
+        assert_eq!(paragraphs[1]["utterances"][0]["text"], "three");
+    }
+
+    #[test]
+    fn render_markdown() {
+        let utterances = [
+            utterance(1000, 2000, "welcome"),
+            Utterance {
+                speaker: Some("Alice".into()),
+                ..utterance(2500, 3000, "thanks")
+            },
+            Utterance {
+                speaker: Some("Alice".into()),
+                ..utterance(3000, 4000, "for coming")
+            },
+            utterance(4000, 4500, ""),
+            // Pauses longer than the gap start a paragraph.
+            utterance(65_000, 66_000, "next item"),
+        ];
+        let model = Path::new("/models/deepspeech.pbmm");
+        let details = Details {
+            model: Some(model),
+            ..details(Duration::from_secs(3723))
+        };
+        let notes = Format::Markdown.render(&utterances, &details);
+        let (date, notes) = notes
+            .strip_prefix("# Transcript\n\n- **Date:** ")
+            .and_then(|notes| notes.split_once('\n'))
+            .unwrap();
+        assert_eq!(date.len(), "1970-01-01 00:00".len(), "{}", date);
+        assert_eq!(
+            notes,
+            "- **Duration:** 01:02:03\n- **Model:** deepspeech.pbmm\n\n\
+             **[00:00:01]** welcome **Alice:** thanks for coming\n\n\
+             **[00:01:05]** next item\n"
+        );
+        assert_eq!(Format::Markdown.extension(), "md");
+    }
+
+    #[test]
+    fn render_audacity() {
+        let mut utterances = utterances();
+        utterances[2].text = "two\tlines\r\nhere".into();
+        assert_eq!(
+            Format::Audacity.render(&utterances, &details(Duration::default())),
+            "1.500000\t3723.004000\thello\n4000.000000\t4001.000000\ttwo lines  here\n"
+        );
+        assert_eq!(Format::Audacity.extension(), "labels.txt");
+        assert!(matches!(Format::from_str("audacity"), Ok(Format::Audacity)));
+    }
+
+    fn utterance(start_ms: u64, end_ms: u64, text: &str) -> Utterance {
+        let mut utterance = Utterance::test(text);
+        utterance.start = Duration::from_millis(start_ms);
+        utterance.end = Duration::from_millis(end_ms);
+        utterance
+    }
+
+    #[test]
+    fn cues_fixture() {
+        let utterances = [
+            utterance(
+                0,
+                14000,
+                "the quick brown fox jumps over the lazy dog while the five boxing wizards jump \
+                 quickly and a wizard's job is to vex chumps quickly in fog",
+            ),
+            utterance(15000, 17000, "short and sweet"),
+            utterance(
+                18000,
+                20000,
+                "pneumonoultramicroscopicsilicovolcanoconiosis is a rather long word",
+            ),
+        ];
+        assert_eq!(
+            Format::Srt.render(&utterances, &details(Duration::from_secs(20))),
+            include_str!("../tests/fixtures/cues.srt")
+        );
+    }
+
+    #[test]
+    fn cues_split() {
+        let cues = Cues {
+            max_chars: 10,
+            max_duration: Duration::default(),
+        };
+        let utterance = utterance(0, 6000, "one two three four five six");
+        // Words not fitting into two lines are split into the next cue.
+        assert_eq!(
+            cues.split(&utterance),
+            [
+                (
+                    Duration::default(),
+                    Duration::from_secs(4),
+                    vec!["one two".to_string(), "three four".to_string()]
+                ),
+                (
+                    Duration::from_secs(4),
+                    Duration::from_secs(6),
+                    vec!["five six".to_string()]
+                ),
+            ]
+        );
+
+        let unlimited = Cues {
+            max_chars: 0,
+            max_duration: Duration::default(),
+        };
+        assert_eq!(
+            unlimited.split(&utterance),
+            [(
+                Duration::default(),
+                Duration::from_secs(6),
+                vec![utterance.text.clone()]
+            )]
+        );
+        assert!(Cues::default()
+            .split(&self::utterance(1000, 2000, ""))
+            .is_empty());
+    }
+
+    #[test]
+    fn textgrid_fixture() {
+        let utterances = [
+            utterance(500, 1750, "hello world"),
+            utterance(1750, 2000, ""),
+            utterance(2250, 4000, "she said \"hi\""),
+        ];
+        assert_eq!(
+            Format::Textgrid.render(&utterances, &details(Duration::from_secs(5))),
+            include_str!("../tests/fixtures/utterances.TextGrid")
+        );
+    }
+
+    #[test]
+    fn textgrid_edges() {
+        // An empty grid still has a single interval covering it.
+        let empty = textgrid("utterances", std::iter::empty(), Duration::default());
+        assert!(empty.contains("intervals: size = 1\n"), "{}", empty);
+
+        // Overlapping intervals are clamped and intervals beyond the duration extend the grid.
+        let grid = textgrid(
+            "a \"tier\"",
+            [
+                (Duration::from_secs(0), Duration::from_secs(2), "a"),
+                (Duration::from_secs(1), Duration::from_secs(3), "b"),
+                (Duration::from_secs(2), Duration::from_secs(3), "c"),
+            ]
+            .iter()
+            .copied(),
+            Duration::from_secs(1),
+        );
+        assert!(grid.contains("name = \"a \"\"tier\"\"\"\n"), "{}", grid);
+        assert!(grid.contains("intervals: size = 2\n"), "{}", grid);
+        assert!(grid.contains("xmin = 2\n            xmax = 3\n            text = \"b\""));
+        assert!(grid.starts_with(
+            "File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\nxmin = 0\nxmax = 3\n"
+        ));
+    }
+
+    #[test]
+    fn escape() {
+        for (value, expected) in [
+            ("plain title", "plain title"),
+            ("a=b", r"a\=b"),
+            ("one; two", r"one\; two"),
+            ("#1 hit", r"\#1 hit"),
+            (r"back\slash", r"back\\slash"),
+            (r"\=;#", r"\\\=\;\#"),
+            ("two\nlines", "two\\\nlines"),
+            ("", ""),
+        ] {
+            assert_eq!(escape_metadata(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let (start, end) = (
+            Duration::from_millis(1500),
+            Duration::from_micros(2_250_001),
+        );
+        assert_eq!(label(start, end, "hello"), "1.500000\t2.250001\thello\n");
+        assert_eq!(
+            label(start, end, "tab\there\r\nand there"),
+            "1.500000\t2.250001\ttab here  and there\n"
+        );
+        assert_eq!(label(start, end, "a=b;#"), "1.500000\t2.250001\ta=b;#\n");
+    }
+
+    #[test]
+    fn truncate_titles() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly 10", 10), "exactly 10");
+        assert_eq!(truncate("one two three", 10), "one two…");
+        assert_eq!(truncate("oneverylongword", 10), "oneverylo…");
+        assert_eq!(truncate("héllo wörld", 8), "héllo…");
+    }
+
+    #[test]
+    fn ffmetadata_chapters() {
+        let mut utterances = [
+            utterance(0, 1500, "intro = start"),
+            utterance(2000, 3000, "#1; part"),
+            utterance(3000, 4000, r"more\less"),
+            utterance(5000, 6000, &"word ".repeat(20)),
+        ];
+        assert_eq!(
+            Format::Ffmetadata.render(&utterances, &details(Duration::from_secs(6))),
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1500\ntitle=intro \\= start\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=2000\nEND=3000\ntitle=\\#1\\; part\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=3000\nEND=4000\ntitle=more\\\\less\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=5000\nEND=6000\n\
+             title=word word word word word word word word word word word word…\n"
+        );
+
+        // Utterances of a paragraph are a single chapter.
+        for (utterance, paragraph) in utterances.iter_mut().zip([0, 1, 1, 2]) {
+            utterance.paragraph = Some(paragraph);
+        }
+        let rendered = Format::Ffmetadata.render(&utterances, &details(Duration::from_secs(6)));
+        assert_eq!(rendered.matches("[CHAPTER]").count(), 3);
+        assert!(
+            rendered.contains("START=2000\nEND=4000\ntitle=\\#1\\; part more\\\\less\n"),
+            "{}",
+            rendered
+        );
+    }
+}
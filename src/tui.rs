@@ -0,0 +1,336 @@
+use crate::control::Control;
+use crate::metrics;
+use crate::output::{Event, Sink};
+use crossterm::{
+    event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::{
+    collections::VecDeque,
+    env,
+    io::{self, Stdout},
+    sync::{atomic::Ordering, mpsc::Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+use tui_rs::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Gauge, Paragraph, Wrap},
+    Terminal,
+};
+
+/// Number of most recent transcripts kept for scrolling.
+const MAX_TRANSCRIPTS: usize = 1000;
+/// Minimum interval between redraws caused by incoming samples, which updates the level meter.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+/// Level in dBFS shown as an empty meter.
+const MIN_LEVEL: f64 = -60.0;
+
+/// Interactive terminal interface showing transcripts, the input level, voice activity and
+/// decoding statistics, with keybindings controlling the pipeline.
+/// It only consumes events, like any other output, and sends controls like the other controls.
+pub struct Tui {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    transcripts: VecDeque<String>,
+    partial: Option<String>,
+    speech: bool,
+    /// RMS level of the most recent samples in dBFS.
+    level: f64,
+    drawn: Instant,
+    /// Whether the terminal is expected to render Unicode, otherwise only ASCII is drawn.
+    unicode: bool,
+    /// Whether the terminal was restored already.
+    restored: bool,
+}
+
+/// Converts an error of crossterm into the I/O error it wraps, if any.
+fn io_error(err: crossterm::ErrorKind) -> io::Error {
+    match err {
+        crossterm::ErrorKind::IoError(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+impl Tui {
+    /// Takes over the terminal and reads keys on a separate thread, sending the controls bound
+    /// to them to `controls`.
+    pub fn new(controls: Sender<Control>) -> io::Result<Self> {
+        terminal::enable_raw_mode().map_err(io_error)?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen).map_err(io_error)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        terminal.hide_cursor()?;
+        thread::spawn(move || keys(controls));
+        let mut tui = Self {
+            terminal,
+            transcripts: VecDeque::new(),
+            partial: None,
+            speech: false,
+            level: MIN_LEVEL,
+            drawn: Instant::now(),
+            unicode: unicode_locale(),
+            restored: false,
+        };
+        tui.draw();
+        Ok(tui)
+    }
+
+    fn draw(&mut self) {
+        self.drawn = Instant::now();
+        let paused = metrics::PAUSED.load(Ordering::Relaxed);
+        let recording = !metrics::RECORDING_SUSPENDED.load(Ordering::Relaxed);
+        let queue = metrics::DECODE_QUEUE_DEPTH.load(Ordering::Relaxed);
+        let utterances = metrics::UTTERANCES.load(Ordering::Relaxed);
+        let rtf = metrics::real_time_factor();
+        let unicode = self.unicode;
+        let level = self.level;
+        let (state, color) = if paused {
+            ("paused", Color::Yellow)
+        } else if self.speech {
+            ("speech", Color::Green)
+        } else {
+            ("silence", Color::DarkGray)
+        };
+        let transcripts = &self.transcripts;
+        let partial = &self.partial;
+        let result = self.terminal.draw(|frame| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(3),
+                    Constraint::Length(3),
+                    Constraint::Length(1),
+                    Constraint::Length(1),
+                ])
+                .split(frame.size());
+
+            let pane = chunks[0];
+            let width = pane.width.saturating_sub(2).max(1) as usize;
+            let height = pane.height.saturating_sub(2) as usize;
+            let mut lines: Vec<Spans> = transcripts
+                .iter()
+                .map(|text| Spans::from(text.as_str()))
+                .collect();
+            if let Some(partial) = partial {
+                lines.push(Spans::from(Span::styled(
+                    partial.as_str(),
+                    Style::default().add_modifier(Modifier::DIM),
+                )));
+            }
+            // Scrolls to the bottom, estimating the number of lines every transcript wraps onto.
+            let wrapped: usize = lines
+                .iter()
+                .map(|line| line.width().max(1).div_ceil(width))
+                .sum();
+            let scroll = wrapped.saturating_sub(height).min(u16::MAX as usize) as u16;
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title("Transcript"))
+                    .wrap(Wrap { trim: true })
+                    .scroll((scroll, 0)),
+                pane,
+            );
+
+            let ratio = ((level - MIN_LEVEL) / -MIN_LEVEL).clamp(0.0, 1.0);
+            frame.render_widget(
+                Gauge::default()
+                    .block(Block::default().borders(Borders::ALL).title("Input level"))
+                    .gauge_style(Style::default().fg(color))
+                    .use_unicode(unicode)
+                    .ratio(ratio)
+                    .label(format!("{:.1} dBFS", level)),
+                chunks[1],
+            );
+
+            frame.render_widget(
+                Paragraph::new(Spans::from(vec![
+                    Span::styled(
+                        format!(" {} ", state),
+                        Style::default().fg(Color::Black).bg(color),
+                    ),
+                    Span::raw(format!(
+                        " queue: {}  RTF: {:.2}  utterances: {}  recording: {}",
+                        queue,
+                        rtf,
+                        utterances,
+                        if recording { "on" } else { "off" }
+                    )),
+                ])),
+                chunks[2],
+            );
+            frame.render_widget(
+                Paragraph::new(Span::styled(
+                    " p pause/resume  f flush  r toggle recording  q quit",
+                    Style::default().add_modifier(Modifier::DIM),
+                )),
+                chunks[3],
+            );
+        });
+        if let Err(err) = result {
+            warn!(error = %err, "Failed to draw TUI");
+        }
+    }
+
+    fn restore(&mut self) {
+        if self.restored {
+            return;
+        }
+        self.restored = true;
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+impl Sink for Tui {
+    fn send(&mut self, event: &Event) {
+        match event {
+            Event::SpeechStarted(_) => self.speech = true,
+            Event::SpeechStopped(_) => self.speech = false,
+            Event::Partial(utterance) | Event::StablePartial(utterance) => {
+                self.partial = Some(utterance.text.clone())
+            }
+            Event::Final(utterance) => {
+                self.partial = None;
+                if utterance.text.is_empty() {
+                    return self.draw();
+                }
+                if self.transcripts.len() == MAX_TRANSCRIPTS {
+                    self.transcripts.pop_front();
+                }
+                self.transcripts.push_back(utterance.text.clone());
+            }
+            Event::Mark(_, label) => {
+                if self.transcripts.len() == MAX_TRANSCRIPTS {
+                    self.transcripts.pop_front();
+                }
+                self.transcripts.push_back(format!("# {}", label));
+            }
+            Event::Ready | Event::Dropped(_) | Event::Heartbeat(_) => {}
+        }
+        self.draw()
+    }
+
+    fn samples(&mut self, samples: &[i16]) {
+        if let Some(level) = level(samples) {
+            self.level = level;
+        }
+        if self.drawn.elapsed() >= REDRAW_INTERVAL {
+            self.draw()
+        }
+    }
+
+    fn finish(&mut self) {
+        self.restore();
+        // Leaves the transcript on the terminal, once the alternate screen is gone.
+        for text in &self.transcripts {
+            println!("{}", text);
+        }
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.restore()
+    }
+}
+
+/// Sends the controls bound to keys read from the terminal to `controls`, until the pipeline
+/// stops. Resizes are picked up by the next draw.
+fn keys(controls: Sender<Control>) {
+    loop {
+        let key = match event::read() {
+            Ok(TermEvent::Key(key)) => key,
+            Ok(_) => continue,
+            Err(err) => return warn!(error = %err, "Failed to read key from terminal"),
+        };
+        let control = match key {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+            }
+            | KeyEvent {
+                code: KeyCode::Char('q'),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Esc, ..
+            } => Control::Stop,
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char(' '),
+                ..
+            } => {
+                if metrics::PAUSED.load(Ordering::Relaxed) {
+                    Control::Resume
+                } else {
+                    Control::Pause
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                ..
+            } => Control::Flush,
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                ..
+            } => {
+                // The next draw shows the new state, which the recorder checks for every segment.
+                metrics::RECORDING_SUSPENDED.fetch_xor(true, Ordering::Relaxed);
+                continue;
+            }
+            _ => continue,
+        };
+        let stop = matches!(control, Control::Stop);
+        if controls.send(control).is_err() || stop {
+            return;
+        }
+    }
+}
+
+/// Returns the RMS level of `samples` in dBFS, clamped to the empty meter, if there are any.
+fn level(samples: &[i16]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let sum: f64 = samples
+        .iter()
+        .map(|&sample| (sample as f64 / -(i16::MIN as f64)).powi(2))
+        .sum();
+    let rms = (sum / samples.len() as f64).sqrt();
+    Some((20.0 * rms.log10()).max(MIN_LEVEL))
+}
+
+/// Returns whether the locale of the process is UTF-8, according to the environment.
+fn unicode_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert_eq!(level(&[]), None);
+        assert_eq!(level(&[0; 100]), Some(MIN_LEVEL));
+        assert_eq!(level(&[i16::MIN; 100]), Some(0.0));
+        let half = level(&[16384, -16384]).unwrap();
+        assert!((half + 6.02).abs() < 0.01, "{}", half);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::output::{Event, Sink};
+use std::{
+    env,
+    process::Command,
+    str::FromStr,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
+use tracing::{error, warn};
+
+#[derive(Clone, Copy)]
+pub enum Backend {
+    #[cfg(feature = "enigo")]
+    Enigo,
+    Wtype,
+    Xdotool,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            #[cfg(feature = "enigo")]
+            "enigo" => Ok(Self::Enigo),
+            "wtype" => Ok(Self::Wtype),
+            "xdotool" => Ok(Self::Xdotool),
+            _ => Err(format!(
+                "failed to parse `{}` into typing backend of wtype or xdotool{}",
+                s,
+                if cfg!(feature = "enigo") {
+                    " or enigo"
+                } else {
+                    ""
+                }
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Separator {
+    Space,
+    Enter,
+    None,
+}
+
+impl FromStr for Separator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "space" => Ok(Self::Space),
+            "enter" => Ok(Self::Enter),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "failed to parse `{}` into typing separator of space, enter or none",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DisplayServer {
+    Wayland,
+    X11,
+}
+
+fn display_server() -> Option<DisplayServer> {
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Some(DisplayServer::Wayland)
+    } else if env::var_os("DISPLAY").is_some() {
+        Some(DisplayServer::X11)
+    } else {
+        None
+    }
+}
+
+pub struct Config {
+    /// Backend to use, chosen according to the display server if `None`.
+    pub backend: Option<Backend>,
+    pub separator: Separator,
+    /// Delay between typed characters.
+    pub delay: Duration,
+    /// Word, which transcripts must start with to be typed. The word itself is not typed.
+    pub wake_word: Option<String>,
+}
+
+fn run(backend: Backend, args: &[&str]) {
+    let program = match backend {
+        #[cfg(feature = "enigo")]
+        Backend::Enigo => unreachable!(),
+        Backend::Wtype => "wtype",
+        Backend::Xdotool => "xdotool",
+    };
+    match Command::new(program).args(args).status() {
+        Ok(status) if !status.success() => warn!(program, %status, "Typing command failed"),
+        Ok(_) => {}
+        Err(err) => error!(program, error = %err, "Failed to execute typing command"),
+    }
+}
+
+fn type_text(backend: Backend, text: &str, separator: Separator, delay: Duration) {
+    let delay_ms = delay.as_millis().to_string();
+    match backend {
+        #[cfg(feature = "enigo")]
+        Backend::Enigo => {
+            use enigo::KeyboardControllable;
+
+            let mut enigo = enigo::Enigo::new();
+            for c in text.chars() {
+                enigo.key_sequence(&c.to_string());
+                thread::sleep(delay);
+            }
+            match separator {
+                Separator::Space => enigo.key_click(enigo::Key::Space),
+                Separator::Enter => enigo.key_click(enigo::Key::Return),
+                Separator::None => {}
+            }
+        }
+        Backend::Wtype => {
+            run(backend, &["-d", &delay_ms, "--", text]);
+            match separator {
+                Separator::Space => run(backend, &["-k", "space"]),
+                Separator::Enter => run(backend, &["-k", "Return"]),
+                Separator::None => {}
+            }
+        }
+        Backend::Xdotool => {
+            run(backend, &["type", "--delay", &delay_ms, "--", text]);
+            match separator {
+                Separator::Space => run(backend, &["key", "space"]),
+                Separator::Enter => run(backend, &["key", "Return"]),
+                Separator::None => {}
+            }
+        }
+    }
+}
+
+/// Types final transcripts into the focused window.
+/// Typing happens on a separate thread, so rate limiting does not stall the pipeline.
+pub struct Typist {
+    wake_word: Option<String>,
+    texts: Sender<String>,
+}
+
+impl Typist {
+    pub fn new(config: Config) -> Result<Self, String> {
+        let display = display_server().ok_or_else(|| {
+            "neither WAYLAND_DISPLAY nor DISPLAY is set, cannot type into windows".to_string()
+        })?;
+        let backend = config.backend.unwrap_or(match display {
+            DisplayServer::Wayland => Backend::Wtype,
+            DisplayServer::X11 => Backend::Xdotool,
+        });
+        match (backend, display) {
+            (Backend::Xdotool, DisplayServer::Wayland) => warn!(
+                "Running under Wayland, xdotool can only type into XWayland windows, consider `--type-backend wtype`"
+            ),
+            (Backend::Wtype, DisplayServer::X11) => {
+                return Err("wtype requires Wayland, but running under X11".to_string())
+            }
+            _ => {}
+        }
+
+        let (texts, rx) = mpsc::channel::<String>();
+        let (separator, delay) = (config.separator, config.delay);
+        thread::spawn(move || {
+            for text in rx {
+                type_text(backend, &text, separator, delay)
+            }
+        });
+        Ok(Self {
+            wake_word: config.wake_word.map(|word| word.to_lowercase()),
+            texts,
+        })
+    }
+}
+
+impl Sink for Typist {
+    fn send(&mut self, event: &Event) {
+        let text = match event {
+            Event::Final(utterance) => utterance.text.trim(),
+            _ => return,
+        };
+        let text = match &self.wake_word {
+            Some(word) => match text.strip_prefix(word.as_str()) {
+                Some(rest) if rest.is_empty() || rest.starts_with(' ') => rest.trim_start(),
+                _ => return,
+            },
+            None => text,
+        };
+        if text.is_empty() {
+            return;
+        }
+        let _ = self.texts.send(text.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+
+    fn typed(wake_word: Option<&str>, texts: &[&str]) -> Vec<String> {
+        let (tx, rx) = mpsc::channel();
+        let mut typist = Typist {
+            wake_word: wake_word.map(str::to_string),
+            texts: tx,
+        };
+        for text in texts {
+            typist.send(&Event::Final(Utterance::test(text)));
+        }
+        drop(typist);
+        rx.into_iter().collect()
+    }
+
+    #[test]
+    fn wake_word() {
+        let texts = [
+            " computer open files ",
+            "computers open",
+            "computer",
+            "open",
+            "",
+        ];
+        assert_eq!(typed(Some("computer"), &texts), ["open files"]);
+        assert_eq!(
+            typed(None, &texts),
+            ["computer open files", "computers open", "computer", "open"]
+        );
+    }
+
+    #[test]
+    fn parse() {
+        assert!(matches!("wtype".parse(), Ok(Backend::Wtype)));
+        assert!(matches!("xdotool".parse(), Ok(Backend::Xdotool)));
+        assert!("ydotool".parse::<Backend>().is_err());
+        assert!(matches!("space".parse(), Ok(Separator::Space)));
+        assert!(matches!("enter".parse(), Ok(Separator::Enter)));
+        assert!(matches!("none".parse(), Ok(Separator::None)));
+        assert!("tab".parse::<Separator>().is_err());
+    }
+}
@@ -0,0 +1,497 @@
+use clap::builder::{EnumValueParser, PossibleValue, TypedValueParser};
+use clap::ValueEnum;
+#[cfg(feature = "fvad")]
+use fvad::Fvad;
+#[cfg(feature = "fvad")]
+use std::convert::TryInto;
+use std::{ffi::OsStr, str::FromStr, time::Duration};
+use tracing::trace;
+
+/// Detector of voice activity, which frames are classified with.
+#[derive(Clone, Copy)]
+pub enum Detector {
+    Fvad,
+    /// Frames louder than the noise floor by a margin are voiced.
+    Energy,
+    /// Every frame is voiced.
+    None,
+}
+
+impl FromStr for Detector {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "fvad" => Ok(Self::Fvad),
+            "energy" => Ok(Self::Energy),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "failed to parse `{}` into VAD of fvad, energy or none",
+                s
+            )),
+        }
+    }
+}
+
+/// Detector used by default, Fvad unless built without it.
+#[cfg(feature = "fvad")]
+pub const DEFAULT_DETECTOR: &str = "fvad";
+#[cfg(not(feature = "fvad"))]
+pub const DEFAULT_DETECTOR: &str = "energy";
+
+/// Margin in dB above the noise floor, which frames need to exceed to be voiced by default.
+pub const DEFAULT_ENERGY_MARGIN: &str = "10";
+
+/// Level in dBFS frames are floored at, so that digital silence does not drag the noise floor
+/// down indefinitely.
+const MIN_DB: f64 = -100.0;
+
+/// Fraction of the distance to the level of a louder silent frame, which the noise floor rises
+/// by per frame. Quieter frames lower it right away.
+const FLOOR_RISE: f64 = 0.05;
+
+/// Fraction of the distance to the level of a voiced frame, which the noise floor rises by per
+/// frame, so that it adapts to noise growing louder, but not to long speech.
+const FLOOR_RISE_VOICED: f64 = 0.001;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FvadSampleLength {
+    #[value(name = "10ms", aliases = ["10", "10 ms"])]
+    Length10ms = 10,
+    #[value(name = "20ms", aliases = ["20", "20 ms"])]
+    Length20ms = 20,
+    #[value(name = "30ms", aliases = ["30", "30 ms"])]
+    Length30ms = 30,
+}
+
+/// Parses Fvad sample lengths like their possible values, falling back to durations of the
+/// same length, e.g. `0.02s`, which were accepted before they became possible values.
+#[derive(Clone)]
+pub struct SampleLengthParser;
+
+impl TypedValueParser for SampleLengthParser {
+    type Value = FvadSampleLength;
+
+    fn parse_ref(
+        &self,
+        command: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<FvadSampleLength, clap::Error> {
+        EnumValueParser::<FvadSampleLength>::new()
+            .parse_ref(command, arg, value)
+            .or_else(|err| {
+                value
+                    .to_str()
+                    .and_then(|s| crate::duration::millis(s).ok())
+                    .and_then(|length| {
+                        FvadSampleLength::value_variants()
+                            .iter()
+                            .copied()
+                            .find(|variant| Duration::from_millis(*variant as u64) == length)
+                    })
+                    .ok_or(err)
+            })
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            FvadSampleLength::value_variants()
+                .iter()
+                .filter_map(ValueEnum::to_possible_value),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FvadMode {
+    #[value(alias = "0")]
+    Quality = 0,
+    #[value(aliases = ["1", "low bitrate", "low_bitrate", "lowbitrate"])]
+    LowBitrate = 1,
+    #[value(alias = "2")]
+    Aggressive = 2,
+    #[value(aliases = ["3", "very aggressive", "very_aggressive", "veryaggressive"])]
+    VeryAggressive = 3,
+}
+
+impl FvadMode {
+    /// Returns the mode of aggressiveness `level` from 0 (quality) to 3 (very aggressive).
+    pub fn from_level(level: i8) -> Option<Self> {
+        match level {
+            0 => Some(Self::Quality),
+            1 => Some(Self::LowBitrate),
+            2 => Some(Self::Aggressive),
+            3 => Some(Self::VeryAggressive),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "fvad")]
+impl From<FvadMode> for fvad::Mode {
+    fn from(mode: FvadMode) -> Self {
+        match mode {
+            FvadMode::Quality => Self::Quality,
+            FvadMode::LowBitrate => Self::LowBitrate,
+            FvadMode::Aggressive => Self::Aggressive,
+            FvadMode::VeryAggressive => Self::VeryAggressive,
+        }
+    }
+}
+
+/// Returns the sample rate supported by Fvad, which is closest to `sample_rate`, if any.
+pub fn supported_sample_rate(sample_rate: u32) -> Option<u32> {
+    match sample_rate / 8000 {
+        1 => Some(8000),
+        2 | 3 => Some(16000),
+        4 | 5 => Some(32000),
+        6 => Some(48000),
+        _ => None,
+    }
+}
+
+/// Returns the sample rate supported by Fvad, which is closest to `sample_rate`, failing if
+/// there is none.
+pub fn sample_rate(sample_rate: u32) -> Result<u32, String> {
+    supported_sample_rate(sample_rate).ok_or_else(|| {
+        format!(
+            "sample rate of {} Hz is not supported by voice activity detection, which supports 8000, 16000, 32000 and 48000 Hz",
+            sample_rate
+        )
+    })
+}
+
+/// Fvad modes, whose votes are combined, e.g. `quality,very-aggressive`.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "fvad"), allow(dead_code))]
+pub struct Ensemble(pub Vec<FvadMode>);
+
+impl FromStr for Ensemble {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        let modes = s
+            .split(',')
+            .map(|mode| {
+                <FvadMode as ValueEnum>::from_str(mode.trim(), false).map_err(|_| {
+                    format!(
+                        "failed to parse `{}` into Fvad mode of 0 (quality), 1 (low-bitrate), 2 (aggressive) or 3 (very-aggressive)",
+                        mode
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if modes.len() < 2 || modes.len() > 3 {
+            return Err(format!(
+                "failed to parse `{}` into an ensemble of 2 or 3 comma-separated Fvad modes",
+                s
+            ));
+        }
+        Ok(Self(modes))
+    }
+}
+
+/// Policy combining the votes of an ensemble.
+#[derive(Clone, Copy)]
+pub enum Policy {
+    /// A frame is voiced if most instances vote so.
+    Majority,
+    /// A frame is voiced if any instance votes so, i.e. speech starts once any instance detects
+    /// voice and ends once all of them detect silence, which catches soft onsets.
+    Any,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "majority" => Ok(Self::Majority),
+            "any" => Ok(Self::Any),
+            _ => Err(format!(
+                "failed to parse `{}` into VAD policy of majority or any",
+                s
+            )),
+        }
+    }
+}
+
+/// Detects voice by the level of frames relative to an adaptive noise floor.
+pub struct Energy {
+    /// Margin in dB above the noise floor, which voiced frames exceed.
+    margin: f64,
+    /// Estimated level of the background noise in dBFS, unset until the first frame.
+    floor: Option<f64>,
+}
+
+impl Energy {
+    fn is_voice_frame(&mut self, frame: &[i16]) -> bool {
+        let sum: f64 = frame.iter().map(|&sample| (sample as f64).powi(2)).sum();
+        let rms = (sum / frame.len().max(1) as f64).sqrt() / i16::MAX as f64;
+        let db = (20.0 * rms.log10()).max(MIN_DB);
+        let floor = *self.floor.get_or_insert(db);
+        let voice = db > floor + self.margin;
+        self.floor = Some(if db < floor {
+            db
+        } else if voice {
+            floor + (db - floor) * FLOOR_RISE_VOICED
+        } else {
+            floor + (db - floor) * FLOOR_RISE
+        });
+        trace!(target: "speech2text::vad", db, floor, voice, "Classified frame by energy");
+        voice
+    }
+}
+
+enum Kind {
+    /// A single Fvad instance, or several combined according to the policy, each in its own
+    /// mode.
+    #[cfg(feature = "fvad")]
+    Fvad {
+        instances: Vec<Fvad>,
+        policy: Policy,
+        sample_rate: u32,
+    },
+    Energy(Energy),
+    None,
+}
+
+/// Detects voice in frames.
+pub struct Vad {
+    kind: Kind,
+}
+
+impl Vad {
+    /// Returns whether `frame` is voiced, or None if its length is not supported by Fvad.
+    /// Votes of ensembles are traced per frame, so that policies can be evaluated.
+    pub fn is_voice_frame(&mut self, frame: &[i16]) -> Option<bool> {
+        match &mut self.kind {
+            #[cfg(feature = "fvad")]
+            Kind::Fvad {
+                instances, policy, ..
+            } => {
+                if instances.len() == 1 {
+                    return instances[0].is_voice_frame(frame);
+                }
+                let mut votes = Vec::with_capacity(instances.len());
+                for instance in instances {
+                    votes.push(instance.is_voice_frame(frame)?);
+                }
+                let ayes = votes.iter().filter(|&&vote| vote).count();
+                let voice = match policy {
+                    Policy::Majority => 2 * ayes > votes.len(),
+                    Policy::Any => ayes > 0,
+                };
+                trace!(target: "speech2text::vad", ?votes, voice, "Combined VAD votes");
+                Some(voice)
+            }
+            Kind::Energy(energy) => Some(energy.is_voice_frame(frame)),
+            Kind::None => Some(true),
+        }
+    }
+
+    /// Switches a single Fvad instance to `mode`, returning whether it was switched.
+    /// Ensembles and the other detectors have no mode to switch.
+    pub fn set_mode(&mut self, mode: FvadMode) -> bool {
+        match &mut self.kind {
+            #[cfg(feature = "fvad")]
+            Kind::Fvad {
+                instances,
+                sample_rate,
+                ..
+            } if instances.len() == 1 => {
+                instances[0] = fvad(*sample_rate, Some(mode));
+                true
+            }
+            _ => {
+                let _ = mode;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fvad")]
+fn fvad(sample_rate: u32, mode: Option<FvadMode>) -> Fvad {
+    let vad = Fvad::new().expect("Failed to create Fvad").set_sample_rate(
+        sample_rate
+            .try_into()
+            .expect("Failed to set Fvad sample rate"),
+    );
+    match mode {
+        Some(mode) => vad.set_mode(mode.into()),
+        None => vad,
+    }
+}
+
+/// Creates Fvad for audio at `sample_rate`, which must be supported by Fvad, in `mode`, if set.
+#[cfg(feature = "fvad")]
+pub fn new(sample_rate: u32, mode: Option<FvadMode>) -> Vad {
+    Vad {
+        kind: Kind::Fvad {
+            instances: vec![fvad(sample_rate, mode)],
+            policy: Policy::Majority,
+            sample_rate,
+        },
+    }
+}
+
+/// Creates the energy detector with the default margin in place of Fvad, which speech2text was
+/// built without.
+#[cfg(not(feature = "fvad"))]
+pub fn new(_sample_rate: u32, _mode: Option<FvadMode>) -> Vad {
+    energy(
+        DEFAULT_ENERGY_MARGIN
+            .parse()
+            .expect("Failed to parse default energy margin"),
+    )
+}
+
+/// Creates an ensemble of Fvad instances for audio at `sample_rate` in the modes of `ensemble`,
+/// whose votes are combined according to `policy`.
+#[cfg(feature = "fvad")]
+pub fn ensemble(sample_rate: u32, ensemble: &Ensemble, policy: Policy) -> Vad {
+    Vad {
+        kind: Kind::Fvad {
+            instances: ensemble
+                .0
+                .iter()
+                .map(|&mode| fvad(sample_rate, Some(mode)))
+                .collect(),
+            policy,
+            sample_rate,
+        },
+    }
+}
+
+/// Creates the energy detector, which classifies frames louder than the noise floor by `margin`
+/// dB as voiced.
+pub fn energy(margin: f64) -> Vad {
+    Vad {
+        kind: Kind::Energy(Energy {
+            margin,
+            floor: None,
+        }),
+    }
+}
+
+/// Creates the detector of `detector` for audio at `sample_rate`. Fvad runs in `mode`, unless
+/// `ensemble` is set, whose votes are combined according to `policy`. The energy detector
+/// classifies frames louder than the noise floor by `margin` dB as voiced.
+pub fn detector(
+    detector: Detector,
+    sample_rate: u32,
+    mode: Option<FvadMode>,
+    ensemble: Option<&Ensemble>,
+    policy: Policy,
+    margin: f64,
+) -> Result<Vad, String> {
+    match detector {
+        #[cfg(feature = "fvad")]
+        Detector::Fvad => Ok(match ensemble {
+            Some(modes) => self::ensemble(sample_rate, modes, policy),
+            None => new(sample_rate, mode),
+        }),
+        #[cfg(not(feature = "fvad"))]
+        Detector::Fvad => {
+            let _ = (sample_rate, mode, ensemble, policy);
+            Err("speech2text was built without Fvad, pass --vad energy or none".into())
+        }
+        Detector::Energy => Ok(energy(margin)),
+        Detector::None => Ok(Vad { kind: Kind::None }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ensemble() {
+        let Ensemble(modes) = "quality, very-aggressive".parse().unwrap();
+        assert!(matches!(
+            modes[..],
+            [FvadMode::Quality, FvadMode::VeryAggressive]
+        ));
+        assert!("0,1,2".parse::<Ensemble>().is_ok());
+        assert!("quality".parse::<Ensemble>().is_err());
+        assert!("0,1,2,3".parse::<Ensemble>().is_err());
+        assert!("quality,loud".parse::<Ensemble>().is_err());
+    }
+
+    #[test]
+    fn parse_policy() {
+        assert!(matches!("majority".parse(), Ok(Policy::Majority)));
+        assert!(matches!("any".parse(), Ok(Policy::Any)));
+        assert!("all".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn parse_detector() {
+        assert!(matches!("fvad".parse(), Ok(Detector::Fvad)));
+        assert!(matches!("energy".parse(), Ok(Detector::Energy)));
+        assert!(matches!("none".parse(), Ok(Detector::None)));
+        assert!("webrtc".parse::<Detector>().is_err());
+    }
+
+    #[test]
+    fn energy_floor() {
+        let mut vad = energy(10.0);
+        let quiet = [30; 160];
+        let loud = [3000; 160];
+        assert_eq!(vad.is_voice_frame(&quiet), Some(false));
+        assert_eq!(vad.is_voice_frame(&loud), Some(true));
+        assert_eq!(vad.is_voice_frame(&quiet), Some(false));
+        assert_eq!(vad.is_voice_frame(&[0; 160]), Some(false));
+        assert_eq!(vad.is_voice_frame(&quiet), Some(true));
+    }
+
+    #[test]
+    fn none_detector() {
+        let mut vad = detector(Detector::None, 16000, None, None, Policy::Majority, 10.0).unwrap();
+        assert_eq!(vad.is_voice_frame(&[0; 160]), Some(true));
+        assert_eq!(vad.is_voice_frame(&[0; 7]), Some(true));
+    }
+
+    #[test]
+    fn from_level() {
+        assert!(matches!(FvadMode::from_level(0), Some(FvadMode::Quality)));
+        assert!(matches!(
+            FvadMode::from_level(3),
+            Some(FvadMode::VeryAggressive)
+        ));
+        assert!(FvadMode::from_level(-1).is_none());
+        assert!(FvadMode::from_level(4).is_none());
+    }
+
+    #[cfg(feature = "fvad")]
+    #[test]
+    fn set_mode() {
+        assert!(new(16000, None).set_mode(FvadMode::Aggressive));
+        let modes = "quality,aggressive".parse().unwrap();
+        assert!(!ensemble(16000, &modes, Policy::Any).set_mode(FvadMode::Aggressive));
+        assert!(!energy(10.0).set_mode(FvadMode::Aggressive));
+    }
+
+    #[cfg(feature = "fvad")]
+    #[test]
+    fn ensemble_votes() {
+        let modes = "quality,aggressive,very-aggressive".parse().unwrap();
+        for policy in [Policy::Majority, Policy::Any] {
+            let mut vad = ensemble(16000, &modes, policy);
+            assert_eq!(vad.is_voice_frame(&[0; 160]), Some(false));
+            assert_eq!(vad.is_voice_frame(&[0; 100]), None);
+        }
+    }
+
+    #[test]
+    fn supported_sample_rate() {
+        assert_eq!(super::supported_sample_rate(8000), Some(8000));
+        assert_eq!(super::supported_sample_rate(22050), Some(16000));
+        assert_eq!(super::supported_sample_rate(44100), Some(32000));
+        assert_eq!(super::supported_sample_rate(48000), Some(48000));
+        assert_eq!(super::supported_sample_rate(4000), None);
+        assert_eq!(super::supported_sample_rate(96000), None);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::batch::{self, Batch, Outcome, Transcription};
+use crate::error::Error;
+use glob::Pattern;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::mpsc::{self, RecvTimeoutError},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// Delay, for which filesystem events are coalesced by the watcher.
+const EVENT_DELAY: Duration = Duration::from_millis(500);
+
+/// Interval at which the sizes of files waiting to become stable are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// File waiting to become stable before it is transcribed.
+struct Pending {
+    size: u64,
+    /// When the size last changed.
+    changed: Instant,
+}
+
+/// Options of watching a directory.
+pub struct Options<'a> {
+    pub dir: &'a Path,
+    /// Files, whose names do not match, are ignored.
+    pub pattern: Pattern,
+    /// Skip files present when watching starts.
+    pub new_only: bool,
+    /// Duration, for which the size of a file must not change, before it is transcribed.
+    pub debounce: Duration,
+}
+
+/// Returns a flag, which is set once SIGTERM is received, instead of terminating the process.
+#[cfg(unix)]
+fn terminated() -> Result<Arc<AtomicBool>, Error> {
+    let flag = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, flag.clone())
+        .map_err(Error::with(Error::Other, "Failed to handle SIGTERM"))?;
+    Ok(flag)
+}
+
+#[cfg(not(unix))]
+fn terminated() -> Result<Arc<AtomicBool>, Error> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
+
+/// Transcribes files created in the directory of `options` with `transcribe` as part of `batch`,
+/// until SIGTERM is received, which lets the file in progress finish first.
+/// Failures to process a file are logged and do not stop watching.
+pub fn run(
+    options: Options,
+    batch: &mut Batch,
+    mut transcribe: impl FnMut(&Path) -> Result<Transcription, Error>,
+) -> Result<(), Error> {
+    let terminated = terminated()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, EVENT_DELAY)
+        .map_err(Error::with(Error::Other, "Failed to create watcher"))?;
+    watcher
+        .watch(options.dir, RecursiveMode::Recursive)
+        .map_err(Error::with(Error::Input, "Failed to watch directory"))?;
+
+    let mut pending = HashMap::new();
+    if !options.new_only {
+        for path in batch::inputs(options.dir, &options.pattern).map_err(Error::Input)? {
+            pending.insert(path, None);
+        }
+    }
+    info!(dir = %options.dir.display(), "Watching for files to transcribe");
+    while !terminated.load(Ordering::Relaxed) {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(DebouncedEvent::Create(path))
+            | Ok(DebouncedEvent::Write(path))
+            | Ok(DebouncedEvent::Rename(_, path)) => {
+                if batch::matches(&options.pattern, &path) && path.is_file() {
+                    debug!(path = %path.display(), "File changed");
+                    pending.insert(path, None);
+                }
+            }
+            Ok(DebouncedEvent::Error(err, path)) => {
+                warn!(error = %err, path = ?path, "Failed to watch directory")
+            }
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::Other("Watcher stopped unexpectedly".into()))
+            }
+        }
+
+        for path in stable(&mut pending, options.debounce) {
+            if terminated.load(Ordering::Relaxed) {
+                break;
+            }
+            match batch.process(&path, &mut transcribe) {
+                Ok(Outcome::Transcribed) => info!(path = %path.display(), "Transcribed file"),
+                Ok(Outcome::Skipped) | Ok(Outcome::Failed) => {}
+                Err(err) => warn!(path = %path.display(), error = %err, "Failed to process file"),
+            }
+        }
+    }
+    info!("Received SIGTERM, stopped watching");
+    Ok(())
+}
+
+/// Removes the files from `pending`, whose size did not change for `debounce`, returning them
+/// sorted by path.
+/// Files, which disappeared, are removed as well.
+fn stable(pending: &mut HashMap<PathBuf, Option<Pending>>, debounce: Duration) -> Vec<PathBuf> {
+    let mut stable = Vec::new();
+    pending.retain(|path, state| {
+        let size = match fs::metadata(path) {
+            Ok(meta) => meta.len(),
+            Err(_) => return false,
+        };
+        match state {
+            Some(state) if state.size == size => {
+                if state.changed.elapsed() < debounce {
+                    return true;
+                }
+                stable.push(path.clone());
+                false
+            }
+            _ => {
+                *state = Some(Pending {
+                    size,
+                    changed: Instant::now(),
+                });
+                true
+            }
+        }
+    });
+    stable.sort();
+    stable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, gone) = (
+            dir.path().join("a.wav"),
+            dir.path().join("b.wav"),
+            dir.path().join("gone.wav"),
+        );
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let mut pending = HashMap::new();
+        for path in [&b, &a, &gone] {
+            pending.insert(path.clone(), None);
+        }
+
+        // The sizes are recorded first, and files, which disappeared, are dropped.
+        assert!(stable(&mut pending, Duration::ZERO).is_empty());
+        assert_eq!(pending.len(), 2);
+
+        // A file, whose size changes, waits for another debounce.
+        fs::write(&b, "bb").unwrap();
+        assert_eq!(stable(&mut pending, Duration::ZERO), [a]);
+        assert!(stable(&mut pending, Duration::from_secs(60)).is_empty());
+        assert_eq!(pending.len(), 1);
+        assert!(stable(&mut pending, Duration::from_secs(60)).is_empty());
+        assert_eq!(stable(&mut pending, Duration::ZERO), [b]);
+        assert!(pending.is_empty());
+    }
+}
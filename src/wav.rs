@@ -0,0 +1,227 @@
+use crate::dither::Dither;
+use crate::error::Error;
+use std::{convert::TryInto, path::Path};
+
+/// Format tag of WAV files describing their encoding by a sub-format GUID, as exported by DAWs
+/// and many recorders, even if the samples are plain PCM.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Trailing 14 bytes shared by the sub-format GUIDs of the basic formats, following the format
+/// tag they embed in their first 2 bytes.
+const GUID_SUFFIX: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+/// Samples and sample rate of a WAV file.
+pub struct Wav {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Vec<i16>,
+}
+
+/// Reads `bytes` of the WAV file at `path`, if it has a WAVE_FORMAT_EXTENSIBLE header, returning
+/// `None` otherwise, so that it is read by the built-in decoders instead.
+/// The sub-format must be PCM or float, of which the channel mask is ignored beyond the channel
+/// count. Float samples are dithered if `dither` is set.
+pub fn read_extensible(bytes: &[u8], path: &Path, dither: bool) -> Result<Option<Wav>, Error> {
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+    let mut fmt = None;
+    let mut data = None;
+    let mut chunks = &bytes[12..];
+    while chunks.len() >= 8 {
+        let id = &chunks[..4];
+        let size = u32::from_le_bytes(chunks[4..8].try_into().expect("Failed to read chunk size"));
+        let body = &chunks[8..];
+        // The size of the data chunk of files written while recording may not be set yet.
+        let size = (size as usize).min(body.len());
+        match id {
+            b"fmt " => fmt = Some(&body[..size]),
+            b"data" => data = Some(&body[..size]),
+            _ => {}
+        }
+        // Chunks are padded to an even size.
+        chunks = &body[(size + size % 2).min(body.len())..];
+    }
+    let fmt = match fmt {
+        Some(fmt) if fmt.len() >= 16 => fmt,
+        _ => return Ok(None),
+    };
+    let field = |offset: usize| u16::from_le_bytes([fmt[offset], fmt[offset + 1]]);
+    if field(0) != WAVE_FORMAT_EXTENSIBLE {
+        return Ok(None);
+    }
+    if fmt.len() < 40 {
+        return Err(Error::Input(format!(
+            "WAVE_FORMAT_EXTENSIBLE header of {} is truncated",
+            path.display()
+        )));
+    }
+    let channels = field(2);
+    let sample_rate = u32::from_le_bytes(fmt[4..8].try_into().expect("Failed to read rate"));
+    let bits = field(14);
+    let guid = &fmt[24..40];
+    let tag = u16::from_le_bytes([guid[0], guid[1]]);
+    if channels == 0 || guid[2..] != GUID_SUFFIX {
+        return Err(Error::Input(format!(
+            "WAVE_FORMAT_EXTENSIBLE sub-format of {} is not supported, only PCM and float are",
+            path.display()
+        )));
+    }
+    let width = bits as usize / 8;
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (WAVE_FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+        (WAVE_FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (WAVE_FORMAT_PCM, 24) => {
+            |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0
+        }
+        (WAVE_FORMAT_PCM, 32) => {
+            |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+        }
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        (WAVE_FORMAT_PCM, _) | (WAVE_FORMAT_IEEE_FLOAT, _) => {
+            return Err(Error::Input(format!(
+                "{}-bit samples of {} are not supported",
+                bits,
+                path.display()
+            )))
+        }
+        _ => {
+            return Err(Error::Input(format!(
+                "WAVE_FORMAT_EXTENSIBLE sub-format {:#06x} of {} is not supported, only PCM and \
+                 float are",
+                tag,
+                path.display()
+            )))
+        }
+    };
+    let data = data.ok_or_else(|| Error::Input(format!("{} has no data chunk", path.display())))?;
+    // Integer samples of 16 bits or less are converted exactly, so only float ones are dithered.
+    let mut dither = Dither::new(dither && tag == WAVE_FORMAT_IEEE_FLOAT);
+    let samples = data
+        .chunks_exact(width * channels as usize)
+        .flat_map(|frame| frame.chunks_exact(width))
+        .map(|sample| dither.quantize(decode(sample)))
+        .collect();
+    Ok(Some(Wav {
+        sample_rate,
+        channels,
+        samples,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a WAV file of `data` with a WAVE_FORMAT_EXTENSIBLE header of sub-format `tag`.
+    fn extensible(tag: u16, bits: u16, channels: u16, data: &[u8]) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(WAVE_FORMAT_EXTENSIBLE.to_le_bytes());
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(16000u32.to_le_bytes());
+        fmt.extend((16000 * (bits / 8 * channels) as u32).to_le_bytes());
+        fmt.extend((bits / 8 * channels).to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+        fmt.extend(22u16.to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+        fmt.extend(0u32.to_le_bytes());
+        fmt.extend(tag.to_le_bytes());
+        fmt.extend(GUID_SUFFIX);
+        let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+        for (id, body) in [(b"fmt ", &fmt[..]), (b"data", data)] {
+            wav.extend(id);
+            wav.extend((body.len() as u32).to_le_bytes());
+            wav.extend(body);
+        }
+        let size = (wav.len() - 8) as u32;
+        wav[4..8].copy_from_slice(&size.to_le_bytes());
+        wav
+    }
+
+    fn read(bytes: &[u8]) -> Result<Option<Wav>, Error> {
+        read_extensible(bytes, Path::new("test.wav"), false)
+    }
+
+    fn message(result: Result<Option<Wav>, Error>) -> String {
+        match result {
+            Err(Error::Input(message)) => message,
+            Err(err) => panic!("failed with {:?}", err),
+            Ok(_) => panic!("read successfully"),
+        }
+    }
+
+    #[test]
+    fn fixture() {
+        let wav = read(include_bytes!("../tests/fixtures/extensible.wav"))
+            .unwrap()
+            .unwrap();
+        assert_eq!((wav.sample_rate, wav.channels), (16000, 2));
+        assert_eq!(wav.samples, [0, 0, 1000, -1000, 32767, -32768, -1, 1]);
+    }
+
+    #[test]
+    fn sub_formats() {
+        let wav = read(&extensible(WAVE_FORMAT_PCM, 8, 1, &[128, 255, 0]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(wav.samples, [0, 32512, -32768]);
+
+        let wav = read(&extensible(
+            WAVE_FORMAT_PCM,
+            24,
+            1,
+            &[0x00, 0x00, 0x40, 0xff, 0xff, 0xff],
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(wav.samples, [16384, 0]);
+
+        let data: Vec<u8> = [0.5f32, -1.0, 0.0]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let wav = read(&extensible(WAVE_FORMAT_IEEE_FLOAT, 32, 1, &data))
+            .unwrap()
+            .unwrap();
+        assert_eq!(wav.samples, [16384, -32768, 0]);
+    }
+
+    #[test]
+    fn other_formats() {
+        assert!(read(b"").unwrap().is_none());
+        assert!(read(b"OggS\0\0\0\0\0\0\0\0").unwrap().is_none());
+        // Plain PCM is read by the built-in decoders.
+        let mut pcm = extensible(WAVE_FORMAT_PCM, 16, 1, &[0, 0]);
+        pcm[20..22].copy_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        assert!(read(&pcm).unwrap().is_none());
+    }
+
+    #[test]
+    fn unsupported() {
+        // A-law, which is not decoded.
+        let err = message(read(&extensible(6, 8, 1, &[0])));
+        assert!(err.contains("sub-format 0x0006"), "{}", err);
+        let err = message(read(&extensible(WAVE_FORMAT_IEEE_FLOAT, 64, 1, &[0; 8])));
+        assert!(err.starts_with("64-bit samples"), "{}", err);
+
+        let mut guid = extensible(WAVE_FORMAT_PCM, 16, 1, &[0, 0]);
+        guid[50] ^= 0xff;
+        let err = message(read(&guid));
+        assert!(err.contains("only PCM and float are"), "{}", err);
+
+        let mut truncated = extensible(WAVE_FORMAT_PCM, 16, 1, &[0, 0]);
+        truncated[16..20].copy_from_slice(&16u32.to_le_bytes());
+        let err = message(read(&truncated));
+        assert!(err.contains("is truncated"), "{}", err);
+
+        let mut no_data = extensible(WAVE_FORMAT_PCM, 16, 1, &[]);
+        no_data.truncate(no_data.len() - 8);
+        let err = message(read(&no_data));
+        assert!(err.contains("has no data chunk"), "{}", err);
+    }
+}
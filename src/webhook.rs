@@ -0,0 +1,302 @@
+use crate::metrics;
+use crate::output::{Event, Sink};
+use crate::queue::Queue;
+use hmac::{Hmac, Mac, NewMac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::{
+    str::FromStr,
+    sync::atomic::Ordering,
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// Maximum number of events waiting to be delivered, beyond which the oldest ones are dropped.
+const QUEUE_CAPACITY: usize = 256;
+
+/// Attempts to deliver an event, before it is given up on.
+const MAX_ATTEMPTS: u32 = 5;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Timeout of a single request, including connecting.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time events still waiting are delivered for once the session ends.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header carrying the hex-encoded HMAC-SHA256 of the body keyed with the shared secret.
+const SIGNATURE_HEADER: &str = "X-Speech2text-Signature";
+
+/// Events, which are delivered to the webhook.
+#[derive(Clone, Copy, Debug)]
+pub struct Events {
+    transcript: bool,
+    speech_start: bool,
+    speech_end: bool,
+}
+
+impl FromStr for Events {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let mut events = Self {
+            transcript: false,
+            speech_start: false,
+            speech_end: false,
+        };
+        for event in s.split(',').map(str::trim) {
+            match event {
+                "transcript" => events.transcript = true,
+                "speech_start" => events.speech_start = true,
+                "speech_end" => events.speech_end = true,
+                _ => {
+                    return Err(format!(
+                        "failed to parse `{}` into webhook event of transcript, speech_start or speech_end",
+                        event
+                    ))
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+pub struct Config {
+    pub url: String,
+    pub events: Events,
+    /// Secret the bodies are signed with, if set.
+    pub secret: Option<String>,
+}
+
+/// Returns the signature of `body` sent in `SIGNATURE_HEADER`.
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("Failed to create HMAC with secret");
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Whether a failed request may succeed if retried, which is the case for connection failures
+/// and server errors.
+fn retryable(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Status(status, _) => *status >= 500 || *status == 429,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+/// POSTs events as JSON bodies to a webhook over HTTP or HTTPS.
+/// Events are delivered in order on a separate thread, which retries failed deliveries with
+/// exponential backoff. Events are queued in the meantime, dropping the oldest ones once the queue
+/// is full, so that an unreachable endpoint neither grows memory nor holds back transcription.
+pub struct Webhook {
+    events: Events,
+    queue: Arc<Queue<String>>,
+    done: Option<mpsc::Receiver<()>>,
+}
+
+impl Webhook {
+    pub fn spawn(config: Config) -> Result<Self, String> {
+        if !config.url.starts_with("http://") && !config.url.starts_with("https://") {
+            return Err(format!(
+                "unsupported webhook URL `{}`, expected an http:// or https:// one",
+                config.url
+            ));
+        }
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let queue = Arc::new(Queue::<String>::new(QUEUE_CAPACITY));
+        let bodies = queue.clone();
+        let (done_tx, done) = mpsc::channel();
+        let Config { url, secret, .. } = config;
+        thread::spawn(move || {
+            while let Some(body) = bodies.pop() {
+                let mut backoff = MIN_BACKOFF;
+                for attempt in 1..=MAX_ATTEMPTS {
+                    let mut request = agent.post(&url).set("Content-Type", "application/json");
+                    if let Some(secret) = &secret {
+                        request = request.set(SIGNATURE_HEADER, &sign(secret, &body));
+                    }
+                    match request.send_string(&body) {
+                        Ok(_) => {
+                            metrics::WEBHOOK_SENT.fetch_add(1, Ordering::Relaxed);
+                            debug!(%url, "Delivered webhook");
+                            break;
+                        }
+                        Err(err) if attempt < MAX_ATTEMPTS && retryable(&err) => {
+                            warn!(%url, error = %err, ?backoff, "Failed to deliver webhook, retrying");
+                            thread::sleep(backoff);
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                        Err(err) => {
+                            metrics::WEBHOOK_FAILED.fetch_add(1, Ordering::Relaxed);
+                            warn!(%url, error = %err, attempt, "Failed to deliver webhook, giving up");
+                            break;
+                        }
+                    }
+                }
+            }
+            let _ = done_tx.send(());
+        });
+        Ok(Self {
+            events: config.events,
+            queue,
+            done: Some(done),
+        })
+    }
+
+    fn body(&self, event: &Event) -> Option<Value> {
+        match event {
+            Event::Final(utterance) if self.events.transcript => {
+                let mut body = utterance.to_json();
+                body["event"] = json!("transcript");
+                Some(body)
+            }
+            Event::SpeechStarted(at) if self.events.speech_start => {
+                Some(json!({"event": "speech_start", "offset_ms": at.as_millis() as u64}))
+            }
+            Event::SpeechStopped(at) if self.events.speech_end => {
+                Some(json!({"event": "speech_end", "offset_ms": at.as_millis() as u64}))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Sink for Webhook {
+    fn send(&mut self, event: &Event) {
+        let body = match self.body(event) {
+            Some(body) => body,
+            None => return,
+        };
+        if self.queue.push(body.to_string()).is_some() {
+            metrics::WEBHOOK_DROPPED.fetch_add(1, Ordering::Relaxed);
+            warn!("Webhook queue is full, dropping oldest event");
+        }
+    }
+
+    fn finish(&mut self) {
+        self.queue.close();
+        let done = match self.done.take() {
+            Some(done) => done,
+            None => return,
+        };
+        if done.recv_timeout(FLUSH_TIMEOUT).is_err() {
+            let pending = self.queue.len();
+            metrics::WEBHOOK_DROPPED.fetch_add(pending as u64, Ordering::Relaxed);
+            warn!(
+                pending,
+                "Failed to deliver webhooks before exiting, dropping them"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{Ipv4Addr, TcpListener},
+    };
+
+    /// Accepts a request for each of `statuses`, responding with it, and returns the headers
+    /// and body of the requests.
+    fn serve(statuses: &'static [u16]) -> (String, thread::JoinHandle<Vec<(String, String)>>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            statuses
+                .iter()
+                .map(|status| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut head = String::new();
+                    while !head.ends_with("\r\n\r\n") {
+                        reader.read_line(&mut head).unwrap();
+                    }
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    )
+                    .unwrap();
+                    (head, String::from_utf8(body).unwrap())
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn parse_events() {
+        let events: Events = "transcript, speech_end".parse().unwrap();
+        assert!(events.transcript);
+        assert!(!events.speech_start);
+        assert!(events.speech_end);
+        assert!("transcript,partial".parse::<Events>().is_err());
+    }
+
+    #[test]
+    fn signature() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn unsupported_url() {
+        let config = Config {
+            url: "ftp://localhost/hook".to_string(),
+            events: "transcript".parse().unwrap(),
+            secret: None,
+        };
+        assert!(Webhook::spawn(config).is_err());
+    }
+
+    #[test]
+    fn deliver() {
+        let (url, server) = serve(&[503, 200]);
+        let mut webhook = Webhook::spawn(Config {
+            url,
+            events: "transcript".parse().unwrap(),
+            secret: Some("secret".to_string()),
+        })
+        .unwrap();
+        // Speech events are not subscribed to.
+        webhook.send(&Event::SpeechStarted(Duration::from_millis(100)));
+        webhook.send(&Event::Final(Utterance::test("hello")));
+        webhook.finish();
+
+        let requests = server.join().unwrap();
+        // The request is retried after the server error.
+        assert_eq!(requests[0], requests[1]);
+        let (head, body) = &requests[1];
+        assert!(head.starts_with("POST /hook "), "{}", head);
+        assert!(
+            head.contains(&format!(
+                "{}: {}\r\n",
+                SIGNATURE_HEADER,
+                sign("secret", body)
+            )),
+            "{}",
+            head
+        );
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["event"], "transcript");
+        assert_eq!(body["text"], "hello");
+    }
+}
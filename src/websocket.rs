@@ -0,0 +1,521 @@
+// Errors of tungstenite and rejections of handshakes are large, but returned at most once per
+// connection.
+#![allow(clippy::result_large_err)]
+
+use crate::dither::Dither;
+use crate::error::Error;
+use crate::output::{Event, Sink};
+use crate::pipeline::{self, Config};
+use crate::pool::{Checkout, Pool};
+use crate::raw::{self, Encoding};
+use crate::resample::{self, Resampler};
+use crate::stable::Stabilize;
+#[cfg(feature = "tls")]
+use crate::tls::Certificates;
+#[cfg(feature = "tls")]
+use rustls::{ServerConfig, ServerSession, StreamOwned};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use tracing::{error, info, warn};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::{http::StatusCode, Message, WebSocket};
+
+/// Interval, at which a connection waiting for audio checks for events to send.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Time, within which a connection needs to complete its handshake and configure its session,
+/// before it is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of channels of the audio of a session.
+const MAX_CHANNELS: usize = 8;
+
+/// Application protocol negotiated with ALPN by connections over TLS.
+#[cfg(feature = "tls")]
+const ALPN: &[&[u8]] = &[b"http/1.1"];
+
+/// Connection of a session, which is encrypted if the server is served over TLS.
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<StreamOwned<ServerSession, TcpStream>>),
+}
+
+impl Stream {
+    /// Wraps `stream` into a connection over TLS with `tls`, if set. The handshake completes
+    /// once the connection is first read from or written to.
+    #[cfg(feature = "tls")]
+    fn new(stream: TcpStream, tls: Option<&Arc<ServerConfig>>) -> Self {
+        match tls {
+            Some(config) => Self::Tls(Box::new(StreamOwned::new(
+                ServerSession::new(config),
+                stream,
+            ))),
+            None => Self::Plain(stream),
+        }
+    }
+
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Self::Plain(stream) => stream,
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => &stream.sock,
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Configuration of a session sent by the client as the first text message, e.g.
+/// `{"sample_rate": 48000, "channels": 1, "format": "f32le"}`.
+#[derive(Deserialize)]
+struct Session {
+    sample_rate: u32,
+    #[serde(default = "mono")]
+    channels: usize,
+    #[serde(default = "s16le")]
+    format: String,
+}
+
+fn mono() -> usize {
+    1
+}
+
+fn s16le() -> String {
+    "s16le".into()
+}
+
+impl Session {
+    fn parse(text: &str) -> Result<raw::Format, String> {
+        let session: Self = serde_json::from_str(text)
+            .map_err(|err| format!("failed to parse session configuration: {}", err))?;
+        if session.sample_rate == 0 {
+            return Err("sample_rate must be positive".into());
+        }
+        if session.channels == 0 || session.channels > MAX_CHANNELS {
+            return Err(format!("channels must be within 1..={}", MAX_CHANNELS));
+        }
+        let encoding: Encoding = session.format.parse()?;
+        Ok(raw::Format {
+            rate: session.sample_rate,
+            channels: session.channels,
+            encoding,
+        })
+    }
+}
+
+/// Returns the message sent to clients for `event`, if any.
+fn message(event: &Event) -> Option<Value> {
+    Some(match event {
+        Event::Ready => json!({"event": "ready"}),
+        Event::SpeechStarted(at) => {
+            json!({"event": "speech_started", "offset_ms": at.as_millis() as u64})
+        }
+        Event::SpeechStopped(at) => {
+            json!({"event": "speech_stopped", "offset_ms": at.as_millis() as u64})
+        }
+        Event::Partial(utterance) => {
+            json!({"event": "partial", "seq": utterance.seq, "text": utterance.text})
+        }
+        Event::StablePartial(utterance) => {
+            json!({"event": "stable_partial", "seq": utterance.seq, "text": utterance.text})
+        }
+        Event::Final(utterance) => {
+            let mut message = utterance.to_json();
+            message["event"] = json!("transcript");
+            message
+        }
+        Event::Mark(at, label) => {
+            json!({"event": "mark", "label": label, "offset_ms": at.as_millis() as u64})
+        }
+        Event::Dropped(_) | Event::Heartbeat(_) => return None,
+    })
+}
+
+/// Forwards messages for events to the connection.
+struct Responder(Sender<Value>);
+
+impl Sink for Responder {
+    fn send(&mut self, event: &Event) {
+        if let Some(message) = message(event) {
+            // Sending only fails if the client went away, in which case the pipeline finishes as
+            // soon as the audio ends.
+            let _ = self.0.send(message);
+        }
+    }
+}
+
+/// Options of the sessions served, shared by all connections.
+#[derive(Clone)]
+pub struct Options {
+    pub config: Config,
+    pub resampler: Resampler,
+    pub dither: bool,
+    /// Number of partial transcripts, across which words need to remain unchanged to be sent as
+    /// stable partials in place of partials, if set.
+    pub stable: Option<usize>,
+    /// Certificate the server is served over TLS with, if set.
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<Certificates>>,
+}
+
+fn send(socket: &mut WebSocket<Stream>, message: &Value) -> tungstenite::Result<()> {
+    socket.write_message(Message::Text(message.to_string()))
+}
+
+/// Response to the handshake of a connection, for which no model became available in time.
+fn unavailable() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("all models are in use".into()));
+    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    response
+}
+
+fn would_block(err: &tungstenite::Error) -> bool {
+    match err {
+        tungstenite::Error::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ),
+        _ => false,
+    }
+}
+
+/// Transcribes the audio of a connection, whose audio is described by the first text message.
+/// Binary messages following it carry the interleaved samples and a text message of
+/// `{"end": true}` ends the audio, after which the remaining transcripts are sent before the
+/// connection is closed.
+fn session(
+    mut socket: WebSocket<Stream>,
+    checkout: Checkout,
+    options: Options,
+) -> tungstenite::Result<()> {
+    let format = loop {
+        match socket.read_message()? {
+            Message::Text(text) => match Session::parse(&text) {
+                Ok(format) => break format,
+                Err(err) => {
+                    send(&mut socket, &json!({"event": "error", "message": err}))?;
+                    return socket.close(None);
+                }
+            },
+            Message::Binary(_) => {
+                let err = "the first message must configure the session";
+                send(&mut socket, &json!({"event": "error", "message": err}))?;
+                return socket.close(None);
+            }
+            Message::Close(_) => return Ok(()),
+            Message::Ping(_) | Message::Pong(_) => {}
+        }
+    };
+    info!(
+        rate = format.rate,
+        channels = format.channels,
+        encoding = ?format.encoding,
+        "Started WebSocket session"
+    );
+
+    let (sample_tx, sample_rx) = mpsc::channel::<Vec<i16>>();
+    let (event_tx, event_rx) = mpsc::channel();
+    let Options {
+        config,
+        resampler,
+        dither,
+        stable,
+        ..
+    } = options;
+    thread::spawn(move || {
+        let samples = resample::resample(
+            sample_rx.into_iter().flatten(),
+            format.rate,
+            config.sample_rate,
+            resampler,
+        );
+        let mut sink: Box<dyn Sink> = match stable {
+            Some(count) => Box::new(Stabilize::new(count, Responder(event_tx))),
+            None => Box::new(Responder(event_tx)),
+        };
+        let (_, controls) = mpsc::channel();
+        let models = vec![checkout.model()];
+        if let Err(err) = pipeline::run(samples, models, &config, &controls, sink.as_mut()) {
+            error!(error = %err, "Failed to transcribe WebSocket session");
+        }
+        // The model is returned to the pool only once the session completed.
+        drop(checkout);
+    });
+    relay(socket, format, dither, sample_tx, event_rx)
+}
+
+/// Forwards the audio of `socket` to `samples` while sending it the messages of `events`, until
+/// the pipeline finished.
+fn relay(
+    mut socket: WebSocket<Stream>,
+    format: raw::Format,
+    dither: bool,
+    samples: Sender<Vec<i16>>,
+    events: Receiver<Value>,
+) -> tungstenite::Result<()> {
+    socket
+        .get_ref()
+        .tcp()
+        .set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut dither = Dither::new(dither && format.encoding == Encoding::F32le);
+    // Bytes of a frame split across messages.
+    let mut pending = Vec::new();
+    let mut samples = Some(samples);
+    loop {
+        match socket.read_message() {
+            Ok(Message::Binary(bytes)) => {
+                pending.extend_from_slice(&bytes);
+                let whole = pending.len() - pending.len() % format.frame_width();
+                let decoded = format.decode(&pending[..whole], &mut dither);
+                pending.drain(..whole);
+                if let Some(samples) = &samples {
+                    let _ = samples.send(decoded);
+                }
+            }
+            Ok(Message::Text(text)) => {
+                match serde_json::from_str::<Value>(&text) {
+                    // Dropping the sender flushes the pipeline.
+                    Ok(message) if message["end"] == json!(true) => samples = None,
+                    _ => warn!(message = %text, "Ignoring unknown WebSocket message"),
+                }
+            }
+            // The client is not interested in the remaining transcripts.
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(err) if would_block(&err) => {}
+            Err(err) => return Err(err),
+        }
+        loop {
+            match events.try_recv() {
+                Ok(message) => send(&mut socket, &message)?,
+                Err(TryRecvError::Empty) => break,
+                // The pipeline finished, once the audio ended.
+                Err(TryRecvError::Disconnected) => {
+                    socket.close(None)?;
+                    // The close handshake completes once the reply of the client is read.
+                    while socket.read_message().is_ok() {}
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Serves transcription sessions over WebSocket on `addr`, each connection transcribing the
+/// audio it sends with a pipeline of its own and a model checked out of `pool`. The model is
+/// checked out once the handshake request was read, so that idle connections do not hold one,
+/// and handshakes of connections, for which none becomes available in time, are answered with
+/// 503. Connections are served over TLS, if the options carry a certificate.
+pub fn serve(addr: SocketAddr, pool: Arc<Pool>, options: Options) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr)
+        .map_err(Error::with(Error::Other, "Failed to bind WebSocket server"))?;
+    #[cfg(feature = "tls")]
+    let tls = options
+        .tls
+        .as_ref()
+        .map(|certificates| certificates.server_config(ALPN));
+    info!(%addr, "Serving WebSocket sessions");
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!(error = %err, "Failed to accept WebSocket connection");
+                continue;
+            }
+        };
+        let peer = stream.peer_addr().ok();
+        #[cfg(feature = "tls")]
+        let stream = Stream::new(stream, tls.as_ref());
+        #[cfg(not(feature = "tls"))]
+        let stream = Stream::Plain(stream);
+        let pool = pool.clone();
+        let options = options.clone();
+        thread::spawn(move || {
+            if let Err(err) = stream.tcp().set_read_timeout(Some(HANDSHAKE_TIMEOUT)) {
+                warn!(?peer, error = %err, "Failed to set WebSocket handshake timeout");
+                return;
+            }
+            let mut checkout = None;
+            let accepted = tungstenite::accept_hdr(stream, |_: &Request, response: Response| {
+                checkout = pool.checkout();
+                if checkout.is_some() {
+                    Ok(response)
+                } else {
+                    warn!(
+                        ?peer,
+                        "No model became available in time, rejecting connection"
+                    );
+                    Err(unavailable())
+                }
+            })
+            // The error of an interrupted handshake holds the callback borrowing the checkout.
+            .map_err(|err| err.to_string());
+            let (socket, checkout) = match (accepted, checkout) {
+                (Ok(socket), Some(checkout)) => (socket, checkout),
+                (Ok(_), None) => unreachable!("handshake succeeded without a model"),
+                (Err(err), _) => {
+                    warn!(?peer, error = %err, "Failed to accept WebSocket connection");
+                    return;
+                }
+            };
+            match session(socket, checkout, options) {
+                Ok(()) => info!(?peer, "WebSocket session ended"),
+                Err(err) => warn!(?peer, error = %err, "WebSocket session failed"),
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Utterance;
+    #[cfg(feature = "tls")]
+    use crate::tls;
+    #[cfg(feature = "tls")]
+    use rustls::{ClientSession, Session as _};
+
+    #[test]
+    fn parse_session() {
+        let format =
+            Session::parse(r#"{"sample_rate": 48000, "channels": 2, "format": "f32le"}"#).unwrap();
+        assert_eq!(format.rate, 48000);
+        assert_eq!(format.channels, 2);
+        assert_eq!(format.encoding, Encoding::F32le);
+
+        let format = Session::parse(r#"{"sample_rate": 16000}"#).unwrap();
+        assert_eq!(format.channels, 1);
+        assert_eq!(format.encoding, Encoding::S16le);
+
+        for text in [
+            r#"{"channels": 1}"#,
+            r#"{"sample_rate": 0}"#,
+            r#"{"sample_rate": 16000, "channels": 9}"#,
+            r#"{"sample_rate": 16000, "format": "mp3"}"#,
+            "16000",
+        ] {
+            assert!(Session::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            message(&Event::SpeechStarted(Duration::from_millis(1500))).unwrap(),
+            json!({"event": "speech_started", "offset_ms": 1500})
+        );
+        assert_eq!(
+            message(&Event::Mark(Duration::from_secs(2), "chapter".into())).unwrap(),
+            json!({"event": "mark", "label": "chapter", "offset_ms": 2000})
+        );
+        let transcript = message(&Event::Final(Utterance::test("hello"))).unwrap();
+        assert_eq!(transcript["event"], "transcript");
+        assert_eq!(transcript["text"], "hello");
+        assert!(message(&Event::Dropped(Utterance::test("hello"))).is_none());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn wss() {
+        let (certificates, client) = tls::test(ALPN);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = certificates.server_config(ALPN);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(Stream::new(stream, Some(&config))).unwrap();
+            send(&mut socket, &json!({"event": "ready"})).unwrap();
+            socket.read_message().unwrap()
+        });
+
+        let localhost = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+        let stream = StreamOwned::new(
+            ClientSession::new(&client, localhost),
+            TcpStream::connect(addr).unwrap(),
+        );
+        let url = format!("wss://localhost:{}/", addr.port());
+        let (mut socket, _) = tungstenite::client(url.as_str(), stream).unwrap();
+        assert_eq!(
+            socket.get_ref().sess.get_alpn_protocol(),
+            Some(&b"http/1.1"[..])
+        );
+        assert_eq!(
+            socket.read_message().unwrap(),
+            Message::Text(json!({"event": "ready"}).to_string())
+        );
+        socket.write_message(Message::Text("done".into())).unwrap();
+        assert_eq!(server.join().unwrap(), Message::Text("done".into()));
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn ws_without_certificate() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(Stream::new(stream, None)).unwrap();
+            send(&mut socket, &json!({"event": "ready"})).unwrap();
+        });
+        let url = format!("ws://localhost:{}/", addr.port());
+        let (mut socket, _) =
+            tungstenite::client(url.as_str(), TcpStream::connect(addr).unwrap()).unwrap();
+        assert_eq!(
+            socket.read_message().unwrap(),
+            Message::Text(json!({"event": "ready"}).to_string())
+        );
+        server.join().unwrap();
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn ws_to_tls_rejected() {
+        let (certificates, _) = tls::test(ALPN);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = certificates.server_config(ALPN);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            tungstenite::accept(Stream::new(stream, Some(&config))).is_err()
+        });
+        let url = format!("ws://localhost:{}/", addr.port());
+        assert!(tungstenite::client(url.as_str(), TcpStream::connect(addr).unwrap()).is_err());
+        assert!(server.join().unwrap());
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+
+/// Edit turning a reference word into a hypothesis word.
+pub enum Edit<'a> {
+    Match(&'a str),
+    Substitution(&'a str, &'a str),
+    /// Hypothesis word missing from the reference.
+    Insertion(&'a str),
+    /// Reference word missing from the hypothesis.
+    Deletion(&'a str),
+}
+
+impl fmt::Display for Edit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Match(word) => write!(f, "  {}", word),
+            Self::Substitution(reference, hypothesis) => {
+                write!(f, "~ {} -> {}", reference, hypothesis)
+            }
+            Self::Insertion(word) => write!(f, "+ {}", word),
+            Self::Deletion(word) => write!(f, "- {}", word),
+        }
+    }
+}
+
+/// Numbers of edits of an alignment.
+#[derive(Clone, Copy, Default)]
+pub struct Counts {
+    /// Number of reference words.
+    pub words: usize,
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl Counts {
+    pub fn of(edits: &[Edit]) -> Self {
+        let mut counts = Self::default();
+        for edit in edits {
+            match edit {
+                Edit::Match(_) => counts.words += 1,
+                Edit::Substitution(..) => {
+                    counts.words += 1;
+                    counts.substitutions += 1
+                }
+                Edit::Insertion(_) => counts.insertions += 1,
+                Edit::Deletion(_) => {
+                    counts.words += 1;
+                    counts.deletions += 1
+                }
+            }
+        }
+        counts
+    }
+
+    pub fn add(&mut self, other: Counts) {
+        self.words += other.words;
+        self.substitutions += other.substitutions;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+    }
+
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.insertions + self.deletions
+    }
+
+    /// Word error rate, which exceeds 1 if there are more errors than reference words.
+    pub fn wer(&self) -> f64 {
+        if self.words == 0 {
+            return if self.errors() == 0 { 0.0 } else { 1.0 };
+        }
+        self.errors() as f64 / self.words as f64
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WER {:.2}% ({} substitutions, {} insertions, {} deletions, {} words)",
+            self.wer() * 100.0,
+            self.substitutions,
+            self.insertions,
+            self.deletions,
+            self.words
+        )
+    }
+}
+
+/// Splits `text` into lowercase words, stripping punctuation except for apostrophes within words.
+pub fn normalize(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+                .trim_matches('\'')
+                .to_string()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Returns the alignment of `hypothesis` to `reference` with the fewest edits.
+pub fn align<'a>(reference: &'a [String], hypothesis: &'a [String]) -> Vec<Edit<'a>> {
+    let columns = hypothesis.len() + 1;
+    // Edit distance between the first i reference words and the first j hypothesis words.
+    let mut distances = vec![0; (reference.len() + 1) * columns];
+    for i in 0..=reference.len() {
+        for j in 0..=hypothesis.len() {
+            distances[i * columns + j] = if i == 0 || j == 0 {
+                i + j
+            } else {
+                let substitution = (reference[i - 1] != hypothesis[j - 1]) as usize;
+                (distances[(i - 1) * columns + j - 1] + substitution)
+                    .min(distances[(i - 1) * columns + j] + 1)
+                    .min(distances[i * columns + j - 1] + 1)
+            };
+        }
+    }
+
+    let mut edits = Vec::with_capacity(reference.len().max(hypothesis.len()));
+    let (mut i, mut j) = (reference.len(), hypothesis.len());
+    while i > 0 || j > 0 {
+        let distance = distances[i * columns + j];
+        if i > 0 && j > 0 {
+            let same = reference[i - 1] == hypothesis[j - 1];
+            if distance == distances[(i - 1) * columns + j - 1] + (!same) as usize {
+                edits.push(if same {
+                    Edit::Match(&reference[i - 1])
+                } else {
+                    Edit::Substitution(&reference[i - 1], &hypothesis[j - 1])
+                });
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && distance == distances[(i - 1) * columns + j] + 1 {
+            edits.push(Edit::Deletion(&reference[i - 1]));
+            i -= 1;
+        } else {
+            edits.push(Edit::Insertion(&hypothesis[j - 1]));
+            j -= 1;
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Aligns `hypothesis` to `reference` and renders the edits.
+    fn edits(reference: &str, hypothesis: &str) -> (Vec<String>, Counts) {
+        let (reference, hypothesis) = (normalize(reference), normalize(hypothesis));
+        let edits = align(&reference, &hypothesis);
+        (
+            edits.iter().map(ToString::to_string).collect(),
+            Counts::of(&edits),
+        )
+    }
+
+    #[test]
+    fn normalize_words() {
+        assert_eq!(
+            normalize("Hello, World! It's 'quoted' -- isn't it?"),
+            ["hello", "world", "it's", "quoted", "isn't", "it"]
+        );
+        assert_eq!(normalize("  ÉTÉ\tStraße\n"), ["été", "straße"]);
+        assert!(normalize(" ... ").is_empty());
+    }
+
+    #[test]
+    fn align_edits() {
+        for (reference, hypothesis, expected) in [
+            ("", "", &[][..]),
+            ("a b c", "a b c", &["  a", "  b", "  c"][..]),
+            ("a b c", "a x c", &["  a", "~ b -> x", "  c"][..]),
+            ("a b c", "a c", &["  a", "- b", "  c"][..]),
+            ("a c", "a b c", &["  a", "+ b", "  c"][..]),
+            ("a b", "", &["- a", "- b"][..]),
+            ("", "a b", &["+ a", "+ b"][..]),
+            (
+                "the cat sat on the mat",
+                "a cat sat the mat down",
+                &[
+                    "~ the -> a",
+                    "  cat",
+                    "  sat",
+                    "- on",
+                    "  the",
+                    "  mat",
+                    "+ down",
+                ][..],
+            ),
+        ] {
+            assert_eq!(
+                edits(reference, hypothesis).0,
+                expected,
+                "{:?} {:?}",
+                reference,
+                hypothesis
+            );
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let (_, counts) = edits("the cat sat on the mat", "A cat sat the mat, down.");
+        assert_eq!(
+            (
+                counts.words,
+                counts.substitutions,
+                counts.insertions,
+                counts.deletions
+            ),
+            (6, 1, 1, 1)
+        );
+        assert_eq!(counts.wer(), 0.5);
+        assert_eq!(
+            counts.to_string(),
+            "WER 50.00% (1 substitutions, 1 insertions, 1 deletions, 6 words)"
+        );
+
+        let mut total = counts;
+        total.add(edits("a", "b c d").1);
+        assert_eq!((total.words, total.errors()), (7, 6));
+
+        assert_eq!(edits("", "").1.wer(), 0.0);
+        assert_eq!(edits("", "a").1.wer(), 1.0);
+        assert_eq!(edits("a", "b c d").1.wer(), 3.0);
+    }
+}
@@ -0,0 +1,66 @@
+use std::{fs, process::Command};
+
+/// Runs speech2text with `args` and returns its exit code along with its stderr.
+fn run(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_speech2text"))
+        .args(args)
+        .output()
+        .expect("failed to run speech2text");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn unknown_argument() {
+    let (code, _) = run(&["--bogus"]);
+    assert_eq!(code, Some(2));
+}
+
+#[test]
+fn invalid_argument() {
+    let (code, _) = run(&["--fvad-mode", "9"]);
+    assert_eq!(code, Some(2));
+}
+
+#[test]
+fn missing_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = dir.path().join("missing.pbmm");
+    let (code, stderr) = run(&["--model", model.to_str().unwrap()]);
+    assert_eq!(code, Some(3), "{}", stderr);
+    assert!(stderr.contains("Failed to verify model"), "{}", stderr);
+}
+
+#[test]
+fn empty_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = dir.path().join("empty.pbmm");
+    fs::write(&model, b"").unwrap();
+    let (code, stderr) = run(&["--model", model.to_str().unwrap()]);
+    assert_eq!(code, Some(3), "{}", stderr);
+}
+
+#[test]
+fn model_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = dir.path().join("model.txt");
+    fs::write(&model, b"model").unwrap();
+    let (code, stderr) = run(&["--model", model.to_str().unwrap()]);
+    assert_eq!(code, Some(3), "{}", stderr);
+    assert!(stderr.contains("does not have an extension"), "{}", stderr);
+}
+
+#[test]
+fn conflicting_inputs() {
+    let (code, _) = run(&["--file", "a.wav", "--device", "mic"]);
+    assert_eq!(code, Some(2));
+}
+
+#[test]
+fn fvad_mode_without_fvad() {
+    let (code, stderr) = run(&["--vad", "energy", "--fvad-mode", "3"]);
+    assert_eq!(code, Some(2), "{}", stderr);
+    assert!(stderr.contains("requires --vad fvad"), "{}", stderr);
+}